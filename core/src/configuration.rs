@@ -1,5 +1,8 @@
 use gveditor_core_api::messaging::ClientMessages;
+use gveditor_core_api::states::{ShutdownConfig, ShutdownPhase};
+use gveditor_core_api::telemetry::TelemetryConfig;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::Mutex;
 
@@ -13,6 +16,16 @@ pub struct Configuration {
     pub server_tx: Option<Sender<ClientMessages>>,
     /// Receiver for the Core Server
     pub server_rx: Option<Receiver<ClientMessages>>,
+    /// Save the States and stop the language servers before exiting when the OS asks to
+    pub autosave_on_signals: bool,
+    /// Hibernate the States not used by any client for this long
+    pub hibernate_after: Option<Duration>,
+    /// Keep the unsaved documents and the States data this often, so they can be recovered after a crash
+    pub recovery_autosave: Option<Duration>,
+    /// Time the extensions, the filesystems and the messages of every State
+    pub telemetry: Option<TelemetryConfig>,
+    /// Timeouts of the shutdown phases
+    pub shutdown: ShutdownConfig,
 }
 
 impl Configuration {
@@ -25,6 +38,47 @@ impl Configuration {
             handler: Arc::new(Mutex::new(handler)),
             server_tx: Some(server_tx),
             server_rx: Some(server_rx),
+            autosave_on_signals: false,
+            hibernate_after: None,
+            recovery_autosave: None,
+            telemetry: None,
+            shutdown: ShutdownConfig::default(),
         }
     }
+
+    /// Handle the OS signals (e.g SIGTERM or SIGHUP) by saving everything and exiting
+    pub fn with_autosave_on_signals(mut self, autosave_on_signals: bool) -> Self {
+        self.autosave_on_signals = autosave_on_signals;
+        self
+    }
+
+    /// Hibernate the idle States, they are awakened by the next message or call of a client
+    pub fn with_hibernate_after(mut self, hibernate_after: Duration) -> Self {
+        self.hibernate_after = Some(hibernate_after);
+        self
+    }
+
+    /// Periodically keep the unsaved work of the States that have a session recovery
+    pub fn with_recovery_autosave(mut self, interval: Duration) -> Self {
+        self.recovery_autosave = Some(interval);
+        self
+    }
+
+    /// Enable the telemetry of the States, its metrics can also be broadcasted periodically
+    pub fn with_telemetry(mut self, config: TelemetryConfig) -> Self {
+        self.telemetry = Some(config);
+        self
+    }
+
+    /// Change how long the shutdown phases can take, by default it's 5 seconds each
+    pub fn with_shutdown_config(mut self, shutdown: ShutdownConfig) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Change how long a single shutdown phase can take
+    pub fn with_shutdown_timeout(mut self, phase: ShutdownPhase, timeout: Duration) -> Self {
+        self.shutdown = self.shutdown.with_timeout(phase, timeout);
+        self
+    }
 }
@@ -0,0 +1,384 @@
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Enum, Error, Object, Result, Schema, SimpleObject,
+};
+use gveditor_core_api::extensions::supervisor::ExtensionStatus;
+use gveditor_core_api::filesystems::remap_path;
+use gveditor_core_api::states::views::TabData;
+use gveditor_core_api::states::TokenScope;
+use gveditor_core_api::workspaces::Workspace;
+use gveditor_core_api::{Mutex, State};
+use std::sync::Arc;
+
+use crate::StatesList;
+
+/// Read-only GraphQL schema over the States, served in `/graphql`
+pub type StatesSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub fn build_schema(states: Arc<Mutex<StatesList>>) -> StatesSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(states)
+        .finish()
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// A State, the token must at least grant read-only access
+    async fn state(&self, ctx: &Context<'_>, id: u8, token: String) -> Result<StateNode> {
+        let states = ctx.data::<Arc<Mutex<StatesList>>>()?;
+        let state = states
+            .lock()
+            .await
+            .get_state_by_id(id)
+            .ok_or_else(|| Error::new("StateNotFound"))?;
+
+        if state
+            .lock()
+            .await
+            .has_any_scope(&token, &[TokenScope::ReadOnly])
+        {
+            Ok(StateNode { state })
+        } else {
+            Err(Error::new("BadToken"))
+        }
+    }
+}
+
+pub struct StateNode {
+    state: Arc<Mutex<State>>,
+}
+
+#[Object]
+impl StateNode {
+    async fn id(&self) -> u8 {
+        self.state.lock().await.data.id
+    }
+
+    async fn revision(&self) -> u64 {
+        self.state.lock().await.data.revision
+    }
+
+    async fn workspaces(&self) -> Vec<WorkspaceNode> {
+        self.state
+            .lock()
+            .await
+            .get_workspaces()
+            .into_iter()
+            .map(|workspace| WorkspaceNode {
+                state: self.state.clone(),
+                workspace,
+            })
+            .collect()
+    }
+
+    /// Tabs of every view
+    async fn tabs(&self) -> Vec<TabNode> {
+        get_tabs(&self.state, |_| true).await
+    }
+
+    async fn extensions(&self) -> Vec<ExtensionNode> {
+        let state = self.state.lock().await;
+        let mut extensions = Vec::new();
+
+        for id in state.get_ext_list() {
+            let info = match state.get_ext_info_by_id(&id) {
+                Ok(info) => info,
+                Err(_) => continue,
+            };
+            let metrics = state.get_extension_metrics(&id).await.ok();
+            let status = state.get_extension_status(&id).await.ok();
+
+            extensions.push(ExtensionNode {
+                id,
+                name: info.extension.name,
+                version: info.extension.version,
+                status: status.map(ExtensionStatusNode::from),
+                panics: metrics.map(|metrics| metrics.panics).unwrap_or_default(),
+                restarts: metrics.map(|metrics| metrics.restarts).unwrap_or_default(),
+            });
+        }
+
+        extensions
+    }
+
+    async fn tasks(&self) -> Vec<TaskNode> {
+        let state = self.state.lock().await;
+        let running = state.get_running_tasks();
+
+        state
+            .get_tasks()
+            .into_iter()
+            .map(|task| TaskNode {
+                running: running.iter().any(|run| run.task_id == task.id),
+                id: task.id,
+                command: task.definition.command,
+                args: task.definition.args,
+                extension_id: task.extension_id,
+            })
+            .collect()
+    }
+}
+
+pub struct WorkspaceNode {
+    state: Arc<Mutex<State>>,
+    workspace: Workspace,
+}
+
+#[Object]
+impl WorkspaceNode {
+    async fn filesystem(&self) -> &str {
+        &self.workspace.filesystem
+    }
+
+    async fn root(&self) -> &str {
+        &self.workspace.root
+    }
+
+    async fn config_path(&self) -> Option<&str> {
+        self.workspace.config_path.as_deref()
+    }
+
+    /// Excluded globs, including the global ones
+    async fn excluded(&self) -> Vec<String> {
+        self.state
+            .lock()
+            .await
+            .get_workspace_config(&self.workspace.filesystem, &self.workspace.root)
+            .map(|config| config.excluded)
+            .unwrap_or_default()
+    }
+
+    /// Tabs of the files inside the workspace
+    async fn tabs(&self) -> Vec<TabNode> {
+        get_tabs(&self.state, |tab| match tab {
+            TabData::TextEditor {
+                filesystem, path, ..
+            } => {
+                filesystem == &self.workspace.filesystem
+                    && remap_path(path, &self.workspace.root, "").is_some()
+            }
+            TabData::Basic { .. } => false,
+        })
+        .await
+    }
+}
+
+pub struct TabNode {
+    state: Arc<Mutex<State>>,
+    tab: TabData,
+}
+
+#[Object]
+impl TabNode {
+    async fn id(&self) -> &str {
+        match &self.tab {
+            TabData::TextEditor { id, .. } => id,
+            TabData::Basic { id, .. } => id,
+        }
+    }
+
+    async fn title(&self) -> &str {
+        match &self.tab {
+            TabData::TextEditor { filename, .. } => filename,
+            TabData::Basic { title, .. } => title,
+        }
+    }
+
+    /// URI of the opened file, if any
+    async fn uri(&self) -> Option<String> {
+        self.tab.get_uri().map(|uri| uri.to_string())
+    }
+
+    /// Decorations contributed by the extensions to the opened file
+    async fn decorations(&self) -> Vec<DecorationNode> {
+        let (filesystem, path) = match &self.tab {
+            TabData::TextEditor {
+                filesystem, path, ..
+            } => (filesystem, path),
+            TabData::Basic { .. } => return Vec::new(),
+        };
+
+        let decorations = self
+            .state
+            .lock()
+            .await
+            .decorations
+            .get(filesystem, &[path.clone()])
+            .await;
+
+        decorations
+            .into_values()
+            .flatten()
+            .map(|(extension_id, decoration)| DecorationNode {
+                extension_id,
+                badge: decoration.badge,
+                color: decoration.color,
+                tooltip: decoration.tooltip,
+            })
+            .collect()
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct DecorationNode {
+    extension_id: String,
+    badge: Option<String>,
+    color: Option<String>,
+    tooltip: Option<String>,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum ExtensionStatusNode {
+    Running,
+    Failed,
+}
+
+impl From<ExtensionStatus> for ExtensionStatusNode {
+    fn from(status: ExtensionStatus) -> Self {
+        match status {
+            ExtensionStatus::Running => Self::Running,
+            ExtensionStatus::Failed => Self::Failed,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct ExtensionNode {
+    id: String,
+    name: String,
+    version: String,
+    /// Missing if the extension is not running
+    status: Option<ExtensionStatusNode>,
+    panics: u64,
+    restarts: u64,
+}
+
+#[derive(SimpleObject)]
+pub struct TaskNode {
+    id: String,
+    command: String,
+    args: Vec<String>,
+    extension_id: Option<String>,
+    running: bool,
+}
+
+/// Tabs of every view that match a filter
+async fn get_tabs(state: &Arc<Mutex<State>>, filter: impl Fn(&TabData) -> bool) -> Vec<TabNode> {
+    state
+        .lock()
+        .await
+        .data
+        .views
+        .iter()
+        .flat_map(|view| view.get_tabs())
+        .filter(|tab| filter(tab))
+        .map(|tab| TabNode {
+            state: state.clone(),
+            tab: tab.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_graphql::value;
+    use gveditor_core_api::extensions::manager::ExtensionsManager;
+    use gveditor_core_api::filesystems::FileFormat;
+    use gveditor_core_api::states::views::{TabData, TabPosition, ViewsData};
+    use gveditor_core_api::states::{MemoryPersistor, StatesList, TokenFlags};
+    use gveditor_core_api::{Mutex, State};
+
+    use super::{build_schema, StatesSchema};
+
+    /// A State with a workspace, a file opened inside it and a settings tab
+    async fn get_schema() -> StatesSchema {
+        let mut state = State::new(
+            1,
+            ExtensionsManager::default(),
+            Box::new(MemoryPersistor::new()),
+        );
+        state.open_workspace("memory", "/project").await.unwrap();
+
+        let mut views = ViewsData::default();
+        views.insert_tab(
+            TabPosition::default(),
+            TabData::Basic {
+                title: "Settings".to_string(),
+                id: "settings".to_string(),
+            },
+        );
+        views.insert_tab(
+            TabPosition {
+                index: 1,
+                ..Default::default()
+            },
+            TabData::TextEditor {
+                path: "/project/main.rs".to_string(),
+                filesystem: "memory".to_string(),
+                format: FileFormat::Unknown,
+                filename: "main.rs".to_string(),
+                id: "main".to_string(),
+            },
+        );
+        let mut data = state.data.clone();
+        data.views = vec![views];
+        state.update(data).await;
+
+        let states = StatesList::new()
+            .with_tokens(&[TokenFlags::All("owner_token".to_string())])
+            .with_state(state);
+        build_schema(Arc::new(Mutex::new(states)))
+    }
+
+    #[tokio::test]
+    async fn query_states() {
+        let schema = get_schema().await;
+
+        let response = schema
+            .execute(
+                r#"{
+                    state(id: 1, token: "owner_token") {
+                        id
+                        tabs { id title }
+                        workspaces { filesystem root tabs { id } }
+                    }
+                }"#,
+            )
+            .await;
+        assert!(response.errors.is_empty());
+        assert_eq!(
+            response.data,
+            value!({
+                "state": {
+                    "id": 1,
+                    "tabs": [
+                        { "id": "settings", "title": "Settings" },
+                        { "id": "main", "title": "main.rs" }
+                    ],
+                    "workspaces": [
+                        { "filesystem": "memory", "root": "/project", "tabs": [{ "id": "main" }] }
+                    ]
+                }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_bad_tokens() {
+        let schema = get_schema().await;
+
+        for (query, error) in [
+            (r#"{ state(id: 1, token: "wrong") { id } }"#, "BadToken"),
+            (
+                r#"{ state(id: 2, token: "owner_token") { id } }"#,
+                "StateNotFound",
+            ),
+        ] {
+            let response = schema.execute(query).await;
+            assert_eq!(response.errors[0].message, error);
+        }
+    }
+}
@@ -1,7 +1,15 @@
 use crate::server::{RpcManager, RpcMethods};
 use crate::StatesList;
 use async_trait::async_trait;
+use gveditor_core_api::filesystems::{
+    extract_archive, zip_folder, ArchiveProgress, MAX_ARCHIVE_SIZE,
+};
+use gveditor_core_api::locale::ClientLocale;
 use gveditor_core_api::messaging::{ClientMessages, ServerMessages};
+use gveditor_core_api::metrics::{MetricsGauges, METRICS};
+use gveditor_core_api::states::TokenScope;
+use gveditor_core_api::{Errors, FilesystemErrors};
+use hyper_tungstenite::hyper::body::HttpBody;
 use hyper_tungstenite::hyper::upgrade::Upgraded;
 use hyper_tungstenite::tungstenite::{self, Message};
 use hyper_tungstenite::{hyper, HyperWebsocket, WebSocketStream};
@@ -22,7 +30,13 @@ use tracing::error;
 
 use jsonrpc_core::serde_json;
 
-use super::TransportHandler;
+#[cfg(feature = "graphql")]
+use super::graphql::{build_schema, StatesSchema};
+use super::{get_revoked_tokens, is_message_allowed, TransportHandler};
+
+/// Biggest GraphQL request accepted, queries are small
+#[cfg(feature = "graphql")]
+const MAX_GRAPHQL_BODY_SIZE: usize = 64 * 1024;
 
 /// HTTP Transport Builder, used to create an instance of the implementation
 pub struct HTTPHandlerBuilder {
@@ -30,6 +44,8 @@ pub struct HTTPHandlerBuilder {
     cors: DomainsValidation<AccessControlAllowOrigin>,
     /// Port in which to run the HTTP Server
     port: u16,
+    /// Serve the metrics in the Prometheus format on `/metrics`
+    metrics: bool,
 }
 
 impl Default for HTTPHandlerBuilder {
@@ -43,6 +59,7 @@ impl HTTPHandlerBuilder {
         Self {
             cors: DomainsValidation::Disabled,
             port: 50010,
+            metrics: false,
         }
     }
 
@@ -56,8 +73,15 @@ impl HTTPHandlerBuilder {
         self
     }
 
+    pub fn metrics(&mut self, metrics: bool) -> &mut Self {
+        self.metrics = metrics;
+        self
+    }
+
     pub fn build(&self) -> HTTPHandler {
-        HTTPHandler::new(self.cors.clone(), self.port)
+        let mut handler = HTTPHandler::new(self.cors.clone(), self.port);
+        handler.metrics = self.metrics;
+        handler
     }
 }
 
@@ -73,13 +97,23 @@ pub fn server_to_ws_message(message: &ServerMessages) -> Option<Message> {
 
 type WebSocket = SplitSink<WebSocketStream<Upgraded>, tungstenite::Message>;
 
-type SocketsRegistry = Arc<Mutex<BTreeMap<u8, Arc<Mutex<WebSocket>>>>>;
+/// A WebSocket, the token it authenticated with and the preferences its client declared
+pub struct ClientSocket {
+    socket: Arc<Mutex<WebSocket>>,
+    token: String,
+    locale: ClientLocale,
+}
+
+type SocketsRegistry = Arc<Mutex<BTreeMap<u8, ClientSocket>>>;
 
 /// WebSockets middleware for HTTP JSON RPC
 struct WebSocketsMiddleware {
     sockets: SocketsRegistry,
     server_tx: Sender<ClientMessages>,
     states: Arc<Mutex<StatesList>>,
+    metrics: bool,
+    #[cfg(feature = "graphql")]
+    graphql_schema: StatesSchema,
 }
 
 impl RequestMiddleware for WebSocketsMiddleware {
@@ -88,6 +122,39 @@ impl RequestMiddleware for WebSocketsMiddleware {
         &self,
         request: jsonrpc_http_server::hyper::Request<jsonrpc_http_server::hyper::Body>,
     ) -> RequestMiddlewareAction {
+        // Scrapers don't have a token, the metrics don't identify any State
+        if self.metrics && request.uri().path() == "/metrics" {
+            let states = self.states.clone();
+            return RequestMiddlewareAction::Respond {
+                should_validate_hosts: true,
+                response: Box::pin(async move {
+                    Ok::<_, hyper::Error>(handle_metrics_request(request, states).await)
+                }),
+            };
+        }
+
+        // Archives endpoints authenticate with their own scopes
+        if matches!(
+            request.uri().path(),
+            "/archives/download" | "/archives/upload"
+        ) {
+            let states = self.states.clone();
+            let server_tx = self.server_tx.clone();
+            return RequestMiddlewareAction::Respond {
+                should_validate_hosts: true,
+                response: Box::pin(async move {
+                    Ok::<_, hyper::Error>(handle_archive_request(request, states, server_tx).await)
+                }),
+            };
+        }
+
+        // GraphQL queries authenticate in the query itself
+        #[cfg(feature = "graphql")]
+        let request = match self.route_graphql(request) {
+            Ok(action) => return action,
+            Err(request) => request,
+        };
+
         // Authentificate the websockets connection
         // TODO: Don't use block_on
         if !block_on(Self::auth_ws(&request, &self.states)) {
@@ -97,13 +164,25 @@ impl RequestMiddleware for WebSocketsMiddleware {
         match request.uri().path() {
             "/websockets" => {
                 if hyper_tungstenite::is_upgrade_request(&request) {
+                    let parameters = get_query_parameters(&request);
                     let (response, websocket) = hyper_tungstenite::upgrade(request, None).unwrap();
                     let sockets = self.sockets.clone();
                     let server_tx = self.server_tx.clone();
+                    let states = self.states.clone();
 
                     // Handle the WebSocket connection
                     tokio::spawn(async move {
-                        Self::handle_ws(sockets.clone(), server_tx.clone(), websocket).await;
+                        let locale = Self::get_client_locale(&parameters, &states).await;
+                        let token = parameters.get("token").cloned().unwrap_or_default();
+                        Self::handle_ws(
+                            sockets.clone(),
+                            server_tx.clone(),
+                            states,
+                            websocket,
+                            token,
+                            locale,
+                        )
+                        .await;
                     });
 
                     // Return the response so the spawned future can continue.
@@ -123,18 +202,42 @@ impl WebSocketsMiddleware {
     /// * `sockets` - Active sockets
     /// * `server_tx`  - A sender to communicate to the Server
     /// * `states`  - A States list
+    /// * `metrics` - Serve the metrics on `/metrics`
     pub fn new(
         sockets: SocketsRegistry,
         server_tx: Sender<ClientMessages>,
         states: Arc<Mutex<StatesList>>,
+        metrics: bool,
     ) -> Self {
         Self {
             sockets,
             server_tx,
+            metrics,
+            #[cfg(feature = "graphql")]
+            graphql_schema: build_schema(states.clone()),
             states,
         }
     }
 
+    /// Respond to the requests on `/graphql`, other requests are given back
+    #[cfg(feature = "graphql")]
+    fn route_graphql(
+        &self,
+        request: hyper::Request<hyper::Body>,
+    ) -> Result<RequestMiddlewareAction, hyper::Request<hyper::Body>> {
+        if request.uri().path() != "/graphql" {
+            return Err(request);
+        }
+
+        let schema = self.graphql_schema.clone();
+        Ok(RequestMiddlewareAction::Respond {
+            should_validate_hosts: true,
+            response: Box::pin(async move {
+                Ok::<_, hyper::Error>(handle_graphql_request(request, schema).await)
+            }),
+        })
+    }
+
     /// Authenticate the Websocket by querying the URL
     ///
     /// * `request`           - The Hyper request
@@ -143,11 +246,7 @@ impl WebSocketsMiddleware {
         request: &hyper::Request<hyper::Body>,
         states: &Arc<Mutex<StatesList>>,
     ) -> bool {
-        let url = request.uri();
-        // Create a URL to so the parameters can be queried
-        let url = url::Url::parse(&format!("ws://locahost{}", &url.to_string())).unwrap();
-        // Get tha parameters
-        let parameters: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        let parameters = get_query_parameters(request);
         let token = parameters.get("token");
         let state_id = parameters.get("state_id");
 
@@ -168,15 +267,54 @@ impl WebSocketsMiddleware {
         }
     }
 
+    /// Preferences of an authenticated client, the ones declared in the query are kept for its token
+    ///
+    /// * `parameters`        - Query parameters of the connection
+    /// * `states`            - A States list
+    async fn get_client_locale(
+        parameters: &HashMap<String, String>,
+        states: &Arc<Mutex<StatesList>>,
+    ) -> ClientLocale {
+        let declared = ClientLocale::from_query_parameters(parameters);
+        let token = parameters.get("token");
+        let state_id = parameters
+            .get("state_id")
+            .and_then(|state_id| state_id.parse::<u8>().ok());
+
+        let state = match (token, state_id) {
+            (Some(token), Some(state_id)) => states
+                .lock()
+                .await
+                .get_state_by_id(state_id)
+                .map(|state| (token, state)),
+            _ => None,
+        };
+
+        match (state, declared) {
+            (Some((token, state)), Some(locale)) => {
+                state.lock().await.set_client_locale(token, locale.clone());
+                locale
+            }
+            (Some((token, state)), None) => state.lock().await.get_client_locale(token),
+            (None, declared) => declared.unwrap_or_default(),
+        }
+    }
+
     /// Handles a WebSockets connection
     ///
-    /// * `states` - The list of registered States
+    /// * `sockets` - Active sockets
     /// * `server_tx` - A Sender to communicate to the Server
+    /// * `states` - The list of registered States
     /// * `websocket` - The Websockets connection
+    /// * `token` - Token the connection authenticated with
+    /// * `locale` - Preferences of the client
     pub async fn handle_ws(
         sockets: SocketsRegistry,
         server_tx: Sender<ClientMessages>,
+        states: Arc<Mutex<StatesList>>,
         websocket: HyperWebsocket,
+        token: String,
+        locale: ClientLocale,
     ) {
         let websocket = websocket.await.unwrap();
         let (sender, mut recv) = websocket.split();
@@ -186,9 +324,24 @@ impl WebSocketsMiddleware {
         while let Some(Ok(raw_message)) = recv.next().await {
             if let Message::Text(text_message) = raw_message {
                 if let Ok(message) = serde_json::from_str::<ClientMessages>(&text_message) {
+                    if !is_message_allowed(&states, &token, &message).await {
+                        error!(
+                            "Ignored message <{}> for State by id <{}>",
+                            message.get_name(),
+                            message.get_state_id()
+                        );
+                        continue;
+                    }
                     // Save the WebSocket if it just subscribed
                     if let ClientMessages::ListenToState { state_id, .. } = message {
-                        sockets.lock().await.insert(state_id, sender.clone());
+                        sockets.lock().await.insert(
+                            state_id,
+                            ClientSocket {
+                                socket: sender.clone(),
+                                token: token.clone(),
+                                locale: locale.clone(),
+                            },
+                        );
                     }
                     // Forward the message to the Server
                     server_tx.send(message).await.unwrap();
@@ -200,12 +353,210 @@ impl WebSocketsMiddleware {
     }
 }
 
+/// Get the query parameters of a request
+fn get_query_parameters(request: &hyper::Request<hyper::Body>) -> HashMap<String, String> {
+    // Create a URL to so the parameters can be queried
+    url::Url::parse(&format!("http://localhost{}", request.uri()))
+        .map(|url| url.query_pairs().into_owned().collect())
+        .unwrap_or_default()
+}
+
+/// Get the token sent in the `Authorization: Bearer <token>` header of a request,
+/// so it doesn't end up in the URLs saved by browsers and proxies
+fn get_bearer_token(request: &hyper::Request<hyper::Body>) -> Option<String> {
+    request
+        .headers()
+        .get(hyper::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::to_owned)
+}
+
+/// Create a response with the given status and body
+fn build_response(status: hyper::StatusCode, body: hyper::Body) -> hyper::Response<hyper::Body> {
+    hyper::Response::builder()
+        .status(status)
+        .body(body)
+        .unwrap()
+}
+
+/// Create an error response containing the serialized error
+fn build_error_response(status: hyper::StatusCode, error: Errors) -> hyper::Response<hyper::Body> {
+    let body = serde_json::to_string(&error).unwrap_or_default();
+    build_response(status, hyper::Body::from(body))
+}
+
+/// Execute a GraphQL query sent in the body of a `POST /graphql`
+#[cfg(feature = "graphql")]
+async fn handle_graphql_request(
+    request: hyper::Request<hyper::Body>,
+    schema: StatesSchema,
+) -> hyper::Response<hyper::Body> {
+    if request.method() != hyper::Method::POST {
+        return build_response(hyper::StatusCode::METHOD_NOT_ALLOWED, hyper::Body::empty());
+    }
+
+    let body = match read_body(request.into_body(), MAX_GRAPHQL_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(status) => return build_response(status, hyper::Body::empty()),
+    };
+
+    let query = match serde_json::from_slice::<async_graphql::Request>(&body) {
+        Ok(query) => query,
+        Err(_) => return build_response(hyper::StatusCode::BAD_REQUEST, hyper::Body::empty()),
+    };
+
+    let response = schema.execute(query).await;
+    let body = serde_json::to_string(&response).unwrap_or_default();
+
+    hyper::Response::builder()
+        .status(hyper::StatusCode::OK)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(hyper::Body::from(body))
+        .unwrap()
+}
+
+/// Handle `GET /metrics`, returns the metrics in the Prometheus text format
+async fn handle_metrics_request(
+    request: hyper::Request<hyper::Body>,
+    states: Arc<Mutex<StatesList>>,
+) -> hyper::Response<hyper::Body> {
+    if request.method() != hyper::Method::GET {
+        return build_response(hyper::StatusCode::METHOD_NOT_ALLOWED, hyper::Body::empty());
+    }
+
+    let gauges = MetricsGauges::collect(&*states.lock().await).await;
+
+    hyper::Response::builder()
+        .status(hyper::StatusCode::OK)
+        .header(hyper::header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(hyper::Body::from(METRICS.to_prometheus(&gauges)))
+        .unwrap()
+}
+
+/// Read the body of a request, without reading more than `max_size` bytes
+async fn read_body(mut body: hyper::Body, max_size: usize) -> Result<Vec<u8>, hyper::StatusCode> {
+    let declared_size = body.size_hint().lower() as usize;
+    if declared_size > max_size {
+        return Err(hyper::StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    let mut content = Vec::with_capacity(declared_size);
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| hyper::StatusCode::BAD_REQUEST)?;
+        if content.len() + chunk.len() > max_size {
+            return Err(hyper::StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        content.extend_from_slice(&chunk);
+    }
+
+    Ok(content)
+}
+
+/// Handle the archives endpoints:
+/// - `GET /archives/download` returns the folder in `path` as a zip archive
+/// - `POST /archives/upload` extracts the zip archive in the body into the folder in `path`
+///
+/// Both require the `state_id`, `filesystem` and `path` query parameters and the token
+/// in an `Authorization: Bearer <token>` header, and report the progress with `ArchiveProgress` messages if an `operation_id` is given.
+async fn handle_archive_request(
+    request: hyper::Request<hyper::Body>,
+    states: Arc<Mutex<StatesList>>,
+    server_tx: Sender<ClientMessages>,
+) -> hyper::Response<hyper::Body> {
+    let is_upload = request.uri().path() == "/archives/upload";
+    let expected_method = if is_upload {
+        hyper::Method::POST
+    } else {
+        hyper::Method::GET
+    };
+
+    if request.method() != expected_method {
+        return build_response(hyper::StatusCode::METHOD_NOT_ALLOWED, hyper::Body::empty());
+    }
+
+    let parameters = get_query_parameters(&request);
+    let state_id = parameters
+        .get("state_id")
+        .and_then(|id| id.parse::<u8>().ok());
+    let token = get_bearer_token(&request);
+    let filesystem_name = parameters.get("filesystem");
+    let path = parameters.get("path");
+
+    let (state_id, token, filesystem_name, path) = match (state_id, token, filesystem_name, path) {
+        (Some(state_id), Some(token), Some(filesystem_name), Some(path)) => {
+            (state_id, token, filesystem_name, path)
+        }
+        _ => return build_response(hyper::StatusCode::BAD_REQUEST, hyper::Body::empty()),
+    };
+
+    let required_scopes: &[TokenScope] = if is_upload {
+        &[TokenScope::Filesystem]
+    } else {
+        &[TokenScope::ReadOnly, TokenScope::Filesystem]
+    };
+
+    let filesystem = {
+        let state = states.lock().await.get_state_by_id(state_id);
+        let state = match state {
+            Some(state) => state,
+            None => {
+                return build_error_response(hyper::StatusCode::NOT_FOUND, Errors::StateNotFound)
+            }
+        };
+        let state = state.lock().await;
+
+        if !state.has_any_scope(&token, required_scopes) {
+            return build_error_response(hyper::StatusCode::FORBIDDEN, Errors::AccessDenied);
+        }
+
+        match state.get_fs_by_name(filesystem_name) {
+            Some(filesystem) => filesystem,
+            None => {
+                return build_error_response(
+                    hyper::StatusCode::NOT_FOUND,
+                    Errors::Fs(FilesystemErrors::FilesystemNotFound),
+                )
+            }
+        }
+    };
+
+    let progress = parameters
+        .get("operation_id")
+        .map(|operation_id| ArchiveProgress::new(server_tx, state_id, operation_id));
+
+    if is_upload {
+        let archive = match read_body(request.into_body(), MAX_ARCHIVE_SIZE).await {
+            Ok(archive) => archive,
+            Err(status) => return build_response(status, hyper::Body::empty()),
+        };
+
+        match extract_archive(&filesystem, archive, path, progress.as_ref()).await {
+            Ok(()) => build_response(hyper::StatusCode::OK, hyper::Body::empty()),
+            Err(err) => build_error_response(hyper::StatusCode::INTERNAL_SERVER_ERROR, err),
+        }
+    } else {
+        match zip_folder(&filesystem, path, progress.as_ref()).await {
+            Ok(archive) => hyper::Response::builder()
+                .status(hyper::StatusCode::OK)
+                .header(hyper::header::CONTENT_TYPE, "application/zip")
+                .body(hyper::Body::from(archive))
+                .unwrap(),
+            Err(err) => build_error_response(hyper::StatusCode::INTERNAL_SERVER_ERROR, err),
+        }
+    }
+}
+
 /// HTTP transport implementation
 pub struct HTTPHandler {
     pub json_rpc_http_cors: DomainsValidation<AccessControlAllowOrigin>,
     pub sockets: SocketsRegistry,
     pub port: u16,
     pub close_handle: Option<CloseHandle>,
+    /// Serve the metrics in the Prometheus format on `/metrics`
+    pub metrics: bool,
+    states: Option<Arc<Mutex<StatesList>>>,
 }
 
 impl HTTPHandler {
@@ -215,6 +566,8 @@ impl HTTPHandler {
             sockets: Arc::new(Mutex::new(BTreeMap::new())),
             port,
             close_handle: None,
+            metrics: false,
+            states: None,
         }
     }
 
@@ -232,9 +585,13 @@ impl HTTPHandler {
     async fn send_message_to_web_socket(&self, message: ServerMessages) {
         let msg_state_id = message.get_state_id();
         let sockets = &*self.sockets.lock().await;
-        if let Some(message) = server_to_ws_message(&message) {
-            if let Some(socket) = sockets.get(&msg_state_id) {
-                let sent_message = socket.lock().await.send(message).await;
+        if let Some(client) = sockets.get(&msg_state_id) {
+            let message = match message.localized(&client.locale) {
+                Some(localized) => server_to_ws_message(&localized),
+                None => server_to_ws_message(&message),
+            };
+            if let Some(message) = message {
+                let sent_message = client.socket.lock().await.send(message).await;
                 match sent_message {
                     Ok(_) => {}
                     Err(_err) => {
@@ -245,6 +602,37 @@ impl HTTPHandler {
         }
     }
 
+    /// Close the WebSocket of a State if its token doesn't grant access anymore
+    ///
+    /// * `states` - The list of registered States
+    /// * `sockets` - Active sockets
+    /// * `state_id` - The State whose tokens or invitations were revoked
+    async fn close_revoked_socket(
+        states: Arc<Mutex<StatesList>>,
+        sockets: SocketsRegistry,
+        state_id: u8,
+    ) {
+        let token = sockets
+            .lock()
+            .await
+            .get(&state_id)
+            .map(|client| client.token.clone());
+
+        if let Some(token) = token {
+            let revoked = get_revoked_tokens(&states, state_id, vec![token]).await;
+            let mut sockets = sockets.lock().await;
+            let is_revoked = sockets
+                .get(&state_id)
+                .map(|client| revoked.contains(&client.token))
+                .unwrap_or(false);
+            if is_revoked {
+                if let Some(client) = sockets.remove(&state_id) {
+                    client.socket.lock().await.close().await.ok();
+                }
+            }
+        }
+    }
+
     /// Runs the JSON HTTP Server that handles
     /// both JSON RPC calls and WebSocket connections
     async fn run_server(
@@ -252,9 +640,15 @@ impl HTTPHandler {
         states: Arc<Mutex<StatesList>>,
         server_tx: Sender<ClientMessages>,
     ) {
+        self.states = Some(states.clone());
+
         // Create a WebSockets Middleware which acts as authenticator
-        let ws_middleware =
-            WebSocketsMiddleware::new(self.sockets.clone(), server_tx, states.clone());
+        let ws_middleware = WebSocketsMiddleware::new(
+            self.sockets.clone(),
+            server_tx,
+            states.clone(),
+            self.metrics,
+        );
 
         // Create the HTTP JSON RPC server
         let mut http_io = IoHandler::default();
@@ -281,8 +675,9 @@ impl HTTPHandler {
 
 impl Drop for HTTPHandler {
     fn drop(&mut self) {
-        let close_handle = self.close_handle.take().unwrap();
-        close_handle.close();
+        if let Some(close_handle) = self.close_handle.take() {
+            close_handle.close();
+        }
     }
 }
 
@@ -293,8 +688,32 @@ impl TransportHandler for HTTPHandler {
     }
 
     async fn send(&self, message: ServerMessages) {
+        // Checked in the background, the States might be locked while their messages are sent
+        if let (
+            Some(states),
+            ServerMessages::TokenRevoked { state_id, .. }
+            | ServerMessages::InvitationRevoked { state_id, .. },
+        ) = (&self.states, &message)
+        {
+            tokio::spawn(Self::close_revoked_socket(
+                states.clone(),
+                self.sockets.clone(),
+                *state_id,
+            ));
+        }
+
         self.send_message_to_web_socket(message).await;
     }
+
+    async fn close(&mut self) {
+        let sockets = std::mem::take(&mut *self.sockets.lock().await);
+        for client in sockets.into_values() {
+            client.socket.lock().await.close().await.ok();
+        }
+        if let Some(close_handle) = self.close_handle.take() {
+            close_handle.close();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -373,4 +792,38 @@ mod tests {
             ServerMessages::StateUpdated { .. }
         ));
     }
+
+    #[cfg(feature = "graphql")]
+    #[tokio::test]
+    async fn graphql_queries_need_a_token() {
+        use crate::handlers::graphql::build_schema;
+
+        let mut sample_state = State::default();
+        sample_state.data.revision = 3;
+        let states = StatesList::new()
+            .with_tokens(&[TokenFlags::All("test".to_string())])
+            .with_state(sample_state);
+        let schema = build_schema(Arc::new(Mutex::new(states)));
+
+        let response = schema
+            .execute(r#"{ state(id: 1, token: "test") { id revision workspaces { root } tabs { id } } }"#)
+            .await;
+        assert!(response.errors.is_empty());
+        assert_eq!(
+            serde_json::to_value(&response.data).unwrap(),
+            serde_json::json!({
+                "state": { "id": 1, "revision": 3, "workspaces": [], "tabs": [] }
+            })
+        );
+
+        let response = schema
+            .execute(r#"{ state(id: 1, token: "wrong") { id } }"#)
+            .await;
+        assert_eq!(response.errors[0].message, "BadToken");
+
+        let response = schema
+            .execute(r#"{ state(id: 2, token: "test") { id } }"#)
+            .await;
+        assert_eq!(response.errors[0].message, "StateNotFound");
+    }
 }
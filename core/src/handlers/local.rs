@@ -32,7 +32,7 @@ impl LocalHandler {
 
         // Create the local JSON RPC instance
         let (client, server) = local::connect::<Client, _, _>(local_io);
-        tokio::task::spawn(async { server.await });
+        tokio::task::spawn(server);
 
         let local = Self {
             receiver_to_local: Some(receiver_to_local),
@@ -106,6 +106,7 @@ mod tests {
                 "local".to_string(),
                 1,
                 "test_token".to_string(),
+                None,
             )
             .await;
 
@@ -6,6 +6,8 @@ use tokio::sync::mpsc::Sender;
 
 use crate::StatesList;
 
+#[cfg(feature = "graphql")]
+mod graphql;
 #[cfg(feature = "http_client")]
 mod http;
 #[cfg(feature = "http_client")]
@@ -16,10 +18,56 @@ mod local;
 #[cfg(feature = "local_client")]
 pub use local::LocalHandler;
 
+#[cfg(feature = "websocket_client")]
+mod websocket;
+#[cfg(feature = "websocket_client")]
+pub use websocket::WebSocketHandler;
+
+/// Check if a remote client can send a message with its token. It's checked for every message,
+/// so revoked or expired tokens stop working in the middle of a connection
+#[cfg(any(feature = "http_client", feature = "websocket_client"))]
+async fn is_message_allowed(
+    states: &Arc<Mutex<StatesList>>,
+    token: &str,
+    message: &ClientMessages,
+) -> bool {
+    let scope = match message.get_client_scope() {
+        Some(scope) => scope,
+        None => return false,
+    };
+    let state = states.lock().await.get_state_by_id(message.get_state_id());
+    match state {
+        Some(state) => state.lock().await.has_scope(token, scope),
+        None => false,
+    }
+}
+
+/// Tokens of the connections to a State that don't grant access to it anymore, e.g they were revoked
+#[cfg(any(feature = "http_client", feature = "websocket_client"))]
+async fn get_revoked_tokens(
+    states: &Arc<Mutex<StatesList>>,
+    state_id: u8,
+    tokens: Vec<String>,
+) -> Vec<String> {
+    let state = states.lock().await.get_state_by_id(state_id);
+    match state {
+        Some(state) => {
+            let state = state.lock().await;
+            tokens
+                .into_iter()
+                .filter(|token| !state.has_token(token))
+                .collect()
+        }
+        None => tokens,
+    }
+}
+
 #[async_trait]
 pub trait TransportHandler {
     /// Run the handler
     async fn run(&mut self, states: Arc<Mutex<StatesList>>, server_tx: Sender<ClientMessages>);
     /// Send a message through the handler
     async fn send(&self, message: ServerMessages);
+    /// Disconnect the clients and stop listening, it's the last phase of the shutdown
+    async fn close(&mut self) {}
 }
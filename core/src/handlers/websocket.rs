@@ -0,0 +1,520 @@
+use crate::server::{RpcManager, RpcMethods};
+use crate::StatesList;
+use async_trait::async_trait;
+use gveditor_core_api::locale::ClientLocale;
+use gveditor_core_api::messaging::{ClientMessages, ServerMessages};
+use jsonrpc_core::futures_util::{SinkExt, StreamExt};
+use jsonrpc_core::serde_json::{self, json};
+use jsonrpc_core::IoHandler;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{unbounded_channel, Sender, UnboundedSender};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, info, warn};
+
+use super::{get_revoked_tokens, is_message_allowed, TransportHandler};
+
+/// WebSocket Transport Builder, used to create an instance of the implementation
+pub struct WebSocketHandlerBuilder {
+    /// Address in which to listen, use `0.0.0.0` to allow remote clients
+    host: String,
+    /// Port in which to listen
+    port: u16,
+}
+
+impl Default for WebSocketHandlerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebSocketHandlerBuilder {
+    pub fn new() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 50020,
+        }
+    }
+
+    pub fn host(&mut self, host: &str) -> &mut Self {
+        self.host = host.to_owned();
+        self
+    }
+
+    pub fn port(&mut self, port: u16) -> &mut Self {
+        self.port = port;
+        self
+    }
+
+    pub fn build(&self) -> WebSocketHandler {
+        WebSocketHandler::new(&self.host, self.port)
+    }
+}
+
+/// An open connection, the token it authenticated with and the preferences its client declared
+pub struct Connection {
+    pub sender: UnboundedSender<Message>,
+    pub token: String,
+    pub locale: ClientLocale,
+}
+
+/// Open connections by the State they are authenticated for
+type ConnectionsRegistry = Arc<Mutex<HashMap<u8, Vec<Connection>>>>;
+
+/// Convert a ServerMessage into a JSON RPC notification
+pub fn server_to_notification(message: &ServerMessages) -> Message {
+    let notification = json!({
+        "jsonrpc": "2.0",
+        "method": "server_message",
+        "params": message,
+    });
+    Message::text(notification.to_string())
+}
+
+/// Headless transport, both the JSON RPC calls and the messages go through a single WebSocket connection
+///
+/// Clients connect to `ws://<host>:<port>/?state_id=<id>&token=<token>`, they can also declare their
+/// `locale`, `date_format`, `hour12`, `decimal_separator`, `group_separator` and `utc_offset`. Then they can:
+/// - Make JSON RPC calls, same methods as the HTTP transport
+/// - Send serialized `ClientMessages` their token allows, e.g `ListenToState`
+/// - Receive `ServerMessages` as `server_message` notifications
+///
+/// Connections are closed when their token or invitation is revoked
+pub struct WebSocketHandler {
+    pub host: String,
+    pub port: u16,
+    pub connections: ConnectionsRegistry,
+    states: Option<Arc<Mutex<StatesList>>>,
+    server_task: Option<JoinHandle<()>>,
+}
+
+impl WebSocketHandler {
+    pub fn new(host: &str, port: u16) -> Self {
+        Self {
+            host: host.to_owned(),
+            port,
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            states: None,
+            server_task: None,
+        }
+    }
+
+    /// Shortcut to builder
+    pub fn builder() -> WebSocketHandlerBuilder {
+        WebSocketHandlerBuilder::new()
+    }
+
+    // Wrap into a trait object
+    pub fn wrap(self) -> Box<dyn TransportHandler + Send + Sync> {
+        Box::new(self)
+    }
+
+    /// Return the State ID, the token and the client's preferences if the token of the query is valid for the State.
+    /// The preferences declared in the query are kept for the token
+    ///
+    /// * `query`   - Query of the connection's URL
+    /// * `states`  - A States list
+    async fn authenticate(
+        query: Option<&str>,
+        states: &Arc<Mutex<StatesList>>,
+    ) -> Option<(u8, String, ClientLocale)> {
+        let parameters: HashMap<String, String> =
+            url::form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+                .into_owned()
+                .collect();
+        let token = parameters.get("token")?;
+        let state_id = parameters.get("state_id")?.parse::<u8>().ok()?;
+
+        let state = states.lock().await.get_state_by_id(state_id)?;
+        let mut state = state.lock().await;
+
+        if !state.has_token(token) {
+            return None;
+        }
+
+        let locale = match ClientLocale::from_query_parameters(&parameters) {
+            Some(locale) => {
+                state.set_client_locale(token, locale.clone());
+                locale
+            }
+            None => state.get_client_locale(token),
+        };
+
+        Some((state_id, token.clone(), locale))
+    }
+
+    /// Handles a WebSocket connection
+    ///
+    /// * `stream`      - The TCP connection
+    /// * `io`          - The JSON RPC methods
+    /// * `states`      - The list of registered States
+    /// * `server_tx`   - A Sender to communicate to the Server
+    /// * `connections` - Open connections
+    async fn handle_connection(
+        stream: TcpStream,
+        io: Arc<IoHandler>,
+        states: Arc<Mutex<StatesList>>,
+        server_tx: Sender<ClientMessages>,
+        connections: ConnectionsRegistry,
+    ) {
+        let mut query = None;
+        // The callback's signature is given by tungstenite
+        #[allow(clippy::result_large_err)]
+        let websocket = tokio_tungstenite::accept_hdr_async(
+            stream,
+            |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+                query = request.uri().query().map(|query| query.to_owned());
+                Ok(response)
+            },
+        )
+        .await;
+
+        let websocket = match websocket {
+            Ok(websocket) => websocket,
+            Err(err) => {
+                error!("Could not open WebSocket connection, error: {}", err);
+                return;
+            }
+        };
+
+        let (mut writer, mut reader) = websocket.split();
+
+        let (state_id, token, locale) = match Self::authenticate(query.as_deref(), &states).await {
+            Some(authenticated) => authenticated,
+            None => {
+                writer
+                    .send(Message::Close(Some(CloseFrame {
+                        code: CloseCode::Policy,
+                        reason: "Invalid token or State".into(),
+                    })))
+                    .await
+                    .ok();
+                return;
+            }
+        };
+
+        // Everything is sent from a single task so responses and notifications don't overlap
+        let (sender, mut receiver) = unbounded_channel::<Message>();
+        tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                let is_close = matches!(message, Message::Close(_));
+                if writer.send(message).await.is_err() || is_close {
+                    break;
+                }
+            }
+        });
+
+        connections
+            .lock()
+            .await
+            .entry(state_id)
+            .or_default()
+            .push(Connection {
+                sender: sender.clone(),
+                token: token.clone(),
+                locale,
+            });
+
+        while let Some(Ok(message)) = reader.next().await {
+            match message {
+                Message::Text(text) => {
+                    if let Ok(message) = serde_json::from_str::<ClientMessages>(&text) {
+                        // Connections can only talk to the State they authenticated for, as their token allows
+                        if message.get_state_id() == state_id
+                            && is_message_allowed(&states, &token, &message).await
+                        {
+                            server_tx.send(message).await.ok();
+                        } else {
+                            warn!(
+                                "Ignored message <{}> for State by id <{}>",
+                                message.get_name(),
+                                message.get_state_id()
+                            );
+                        }
+                    } else if let Some(response) = io.handle_request(&text).await {
+                        sender.send(Message::text(response)).ok();
+                    }
+                }
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+
+        if let Some(state_connections) = connections.lock().await.get_mut(&state_id) {
+            state_connections.retain(|connection| !connection.sender.same_channel(&sender));
+        }
+    }
+
+    /// Close the connections to a State whose tokens don't grant access anymore
+    ///
+    /// * `states`      - The list of registered States
+    /// * `connections` - Open connections
+    /// * `state_id`    - The State whose tokens or invitations were revoked
+    async fn close_revoked_connections(
+        states: Arc<Mutex<StatesList>>,
+        connections: ConnectionsRegistry,
+        state_id: u8,
+    ) {
+        let tokens = connections
+            .lock()
+            .await
+            .get(&state_id)
+            .map(|state_connections| {
+                state_connections
+                    .iter()
+                    .map(|connection| connection.token.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let revoked = get_revoked_tokens(&states, state_id, tokens).await;
+
+        if let Some(state_connections) = connections.lock().await.get_mut(&state_id) {
+            state_connections.retain(|connection| {
+                if revoked.contains(&connection.token) {
+                    connection
+                        .sender
+                        .send(Message::Close(Some(CloseFrame {
+                            code: CloseCode::Policy,
+                            reason: "Access revoked".into(),
+                        })))
+                        .ok();
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+    }
+
+    /// Listen for new connections
+    async fn run_server(
+        &mut self,
+        states: Arc<Mutex<StatesList>>,
+        server_tx: Sender<ClientMessages>,
+    ) {
+        let address = format!("{}:{}", self.host, self.port);
+        let listener = match TcpListener::bind(&address).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!("Could not listen on {}, error: {}", address, err);
+                return;
+            }
+        };
+
+        self.states = Some(states.clone());
+
+        let mut io = IoHandler::default();
+        let manager = RpcManager {
+            states: states.clone(),
+        };
+        io.extend_with(manager.to_delegate());
+        let io = Arc::new(io);
+
+        let connections = self.connections.clone();
+
+        info!("Listening for WebSocket connections on {}", address);
+
+        self.server_task = Some(tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(Self::handle_connection(
+                    stream,
+                    io.clone(),
+                    states.clone(),
+                    server_tx.clone(),
+                    connections.clone(),
+                ));
+            }
+        }));
+    }
+}
+
+impl Drop for WebSocketHandler {
+    fn drop(&mut self) {
+        if let Some(server_task) = self.server_task.take() {
+            server_task.abort();
+        }
+    }
+}
+
+#[async_trait]
+impl TransportHandler for WebSocketHandler {
+    async fn run(&mut self, states: Arc<Mutex<StatesList>>, server_tx: Sender<ClientMessages>) {
+        self.run_server(states, server_tx).await;
+    }
+
+    async fn send(&self, message: ServerMessages) {
+        // Checked in the background, the States might be locked while their messages are sent
+        if let (
+            Some(states),
+            ServerMessages::TokenRevoked { state_id, .. }
+            | ServerMessages::InvitationRevoked { state_id, .. },
+        ) = (&self.states, &message)
+        {
+            tokio::spawn(Self::close_revoked_connections(
+                states.clone(),
+                self.connections.clone(),
+                *state_id,
+            ));
+        }
+
+        let mut connections = self.connections.lock().await;
+        if let Some(state_connections) = connections.get_mut(&message.get_state_id()) {
+            let notification = server_to_notification(&message);
+            state_connections.retain(|connection| {
+                let notification = match message.localized(&connection.locale) {
+                    Some(message) => server_to_notification(&message),
+                    None => notification.clone(),
+                };
+                connection.sender.send(notification).is_ok()
+            });
+        }
+    }
+
+    async fn close(&mut self) {
+        let connections = std::mem::take(&mut *self.connections.lock().await);
+        for connection in connections.into_values().flatten() {
+            connection
+                .sender
+                .send(Message::Close(Some(CloseFrame {
+                    code: CloseCode::Away,
+                    reason: "Shutting down".into(),
+                })))
+                .ok();
+        }
+        if let Some(server_task) = self.server_task.take() {
+            server_task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gveditor_core_api::extensions::manager::ExtensionsManager;
+    use gveditor_core_api::messaging::ClientMessages;
+    use gveditor_core_api::states::{InvitationAccess, MemoryPersistor, TokenFlags, TokenScope};
+    use gveditor_core_api::{Mutex, State};
+    use jsonrpc_core::futures_util::{SinkExt, StreamExt};
+    use jsonrpc_core::serde_json::{self, json, Value};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc::channel;
+    use tokio_tungstenite::tungstenite::Message;
+
+    use crate::{Configuration, Server, StatesList};
+
+    use super::WebSocketHandler;
+
+    #[tokio::test]
+    async fn json_rpc_over_websockets() {
+        let (server_tx, server_rx) = channel::<ClientMessages>(1);
+
+        let states = {
+            let states = StatesList::new()
+                .with_tokens(&[TokenFlags::All("test".to_string())])
+                .with_state(State::default());
+
+            Arc::new(Mutex::new(states))
+        };
+
+        let handler = WebSocketHandler::builder().port(50021).build().wrap();
+        let config = Configuration::new(handler, server_tx, server_rx);
+        let mut server = Server::new(config, states);
+        server.run().await;
+
+        // Wrong tokens are rejected
+        let (mut socket, _) =
+            tokio_tungstenite::connect_async("ws://localhost:50021/?token=wrong&state_id=1")
+                .await
+                .unwrap();
+        assert!(matches!(
+            socket.next().await,
+            Some(Ok(Message::Close(_))) | None
+        ));
+
+        let (mut socket, _) =
+            tokio_tungstenite::connect_async("ws://localhost:50021/?token=test&state_id=1")
+                .await
+                .unwrap();
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_state_by_id",
+            "params": [1, "test"],
+        });
+        socket
+            .send(Message::Text(request.to_string()))
+            .await
+            .unwrap();
+
+        let response = socket.next().await.unwrap().unwrap().into_text().unwrap();
+        let response: Value = serde_json::from_str(&response).unwrap();
+
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["Ok"]["id"], 1);
+    }
+
+    #[tokio::test]
+    async fn close_revoked_connections() {
+        let (server_tx, server_rx) = channel::<ClientMessages>(1);
+
+        let state = State::new(
+            1,
+            ExtensionsManager::new(server_tx.clone(), None),
+            Box::new(MemoryPersistor::new()),
+        );
+        let states = Arc::new(Mutex::new(
+            StatesList::new()
+                .with_tokens(&[TokenFlags::All("test".to_string())])
+                .with_state(state),
+        ));
+        let state = states.lock().await.get_state_by_id(1).unwrap();
+        let scoped = state
+            .lock()
+            .await
+            .issue_token("CI runner", vec![TokenScope::ReadOnly], None);
+        let invitation = state
+            .lock()
+            .await
+            .create_invitation(InvitationAccess::Follow, Duration::from_secs(60));
+
+        let handler = WebSocketHandler::builder().port(50022).build().wrap();
+        let config = Configuration::new(handler, server_tx, server_rx);
+        let mut server = Server::new(config, states);
+        server.run().await;
+
+        for (token, is_invitation) in [(scoped.token, false), (invitation.token, true)] {
+            let (mut socket, _) = tokio_tungstenite::connect_async(format!(
+                "ws://localhost:50022/?token={}&state_id=1",
+                token
+            ))
+            .await
+            .unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+
+            if is_invitation {
+                let revoked = state.lock().await.revoke_invitation(&invitation.id).await;
+                revoked.unwrap();
+            } else {
+                state.lock().await.revoke_token(&scoped.id).await.unwrap();
+            }
+
+            let closed = tokio::time::timeout(Duration::from_secs(5), async {
+                loop {
+                    match socket.next().await {
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                        Some(Ok(_)) => {}
+                    }
+                }
+            })
+            .await;
+            assert!(closed.is_ok());
+        }
+    }
+}
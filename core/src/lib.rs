@@ -1,8 +1,14 @@
 mod configuration;
 pub mod handlers;
+#[cfg(feature = "repl")]
+pub mod repl;
 mod server;
+mod shutdown;
+mod signals;
 
 pub use configuration::Configuration;
 use gveditor_core_api::states::StatesList;
 pub use server::{gen_client, RPCResult, Server};
+pub use shutdown::ShutdownCoordinator;
+pub use signals::wait_for_shutdown_signal;
 pub use {jsonrpc_core_client, tokio};
@@ -0,0 +1,302 @@
+use std::fmt;
+
+use gveditor_core_api::messaging::ClientMessages;
+use jsonrpc_core::futures_util::{SinkExt, StreamExt};
+use jsonrpc_core::serde_json::{self, json, Value};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::{Error, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+const HELP: &str = "Commands:
+  state                      Print the State data
+  extensions                 List the loaded extensions
+  metrics [extension]        Print the metrics of an extension, or of all of them, and the running operations
+  call <method> [params]     Make a JSON RPC call, params is a JSON array without the State ID and token
+  send <message>             Send a JSON serialized ClientMessages, e.g a test notification
+  help                       Print this
+  quit                       Exit";
+
+/// A command typed in the REPL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplCommand {
+    State,
+    Extensions,
+    Metrics(Option<String>),
+    Call { method: String, params: Vec<Value> },
+    Send(ClientMessages),
+    Help,
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplErrors {
+    UnknownCommand(String),
+    BadArguments(String),
+}
+
+impl fmt::Display for ReplErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCommand(command) => write!(f, "Unknown command `{}`, try `help`", command),
+            Self::BadArguments(reason) => write!(f, "Bad arguments, {}", reason),
+        }
+    }
+}
+
+impl ReplCommand {
+    pub fn parse(line: &str) -> Result<Self, ReplErrors> {
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match command {
+            "state" => Ok(Self::State),
+            "extensions" => Ok(Self::Extensions),
+            "metrics" if rest.is_empty() => Ok(Self::Metrics(None)),
+            "metrics" => Ok(Self::Metrics(Some(rest.to_owned()))),
+            "call" => {
+                let (method, params) = rest.split_once(' ').unwrap_or((rest, ""));
+                if method.is_empty() {
+                    return Err(ReplErrors::BadArguments("missing the method".to_owned()));
+                }
+                let params = if params.trim().is_empty() {
+                    Vec::new()
+                } else {
+                    serde_json::from_str(params)
+                        .map_err(|err| ReplErrors::BadArguments(err.to_string()))?
+                };
+                Ok(Self::Call {
+                    method: method.to_owned(),
+                    params,
+                })
+            }
+            "send" => serde_json::from_str(rest)
+                .map(Self::Send)
+                .map_err(|err| ReplErrors::BadArguments(err.to_string())),
+            "help" => Ok(Self::Help),
+            "quit" | "exit" => Ok(Self::Quit),
+            command => Err(ReplErrors::UnknownCommand(command.to_owned())),
+        }
+    }
+}
+
+/// Developer console attached to a running Core through the WebSocket transport,
+/// it prints everything the Core sends while it's open
+pub struct Repl {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    state_id: u8,
+    token: String,
+    last_request_id: u64,
+}
+
+impl Repl {
+    /// Connect to a Core
+    ///
+    /// # Arguments
+    ///
+    /// * `address`    - Host and port of the WebSocket transport, e.g `localhost:50020`
+    /// * `state_id`   - The State to inspect
+    /// * `token`      - A token of that State
+    ///
+    pub async fn connect(address: &str, state_id: u8, token: &str) -> Result<Self, Error> {
+        let url = format!("ws://{}/?state_id={}&token={}", address, state_id, token);
+        let (socket, _) = tokio_tungstenite::connect_async(url).await?;
+
+        Ok(Self {
+            socket,
+            state_id,
+            token: token.to_owned(),
+            last_request_id: 0,
+        })
+    }
+
+    /// Read commands from the stdin until `quit` or the connection is closed
+    pub async fn run(&mut self) -> Result<(), Error> {
+        println!(
+            "Connected to State by id <{}>, type `help` to see the commands",
+            self.state_id
+        );
+
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+        loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    let line = match line {
+                        Ok(Some(line)) => line,
+                        _ => break,
+                    };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match ReplCommand::parse(&line) {
+                        Ok(ReplCommand::Quit) => break,
+                        Ok(command) => self.execute(command).await?,
+                        Err(err) => println!("{}", err),
+                    }
+                }
+                message = self.socket.next() => {
+                    match message {
+                        Some(Ok(message)) => print_message(message),
+                        _ => break,
+                    }
+                }
+            }
+        }
+
+        self.socket.close(None).await.ok();
+        Ok(())
+    }
+
+    async fn execute(&mut self, command: ReplCommand) -> Result<(), Error> {
+        match command {
+            ReplCommand::State => {
+                let state = self.call("get_state_by_id", Vec::new()).await?;
+                print_value(&state);
+            }
+            ReplCommand::Extensions => {
+                let extensions = self.call("get_ext_list", Vec::new()).await?;
+                print_value(&extensions);
+            }
+            ReplCommand::Metrics(extension) => {
+                let extensions = match extension {
+                    Some(extension) => vec![extension],
+                    None => {
+                        let list = self.call("get_ext_list", Vec::new()).await?;
+                        serde_json::from_value(list["Ok"].clone()).unwrap_or_default()
+                    }
+                };
+                for extension in extensions {
+                    let metrics = self
+                        .call("get_extension_metrics", vec![json!(extension)])
+                        .await?;
+                    println!("{}:", extension);
+                    print_value(&metrics);
+                }
+                let progress = self.call("get_progress", Vec::new()).await?;
+                println!("Running operations:");
+                print_value(&progress);
+            }
+            ReplCommand::Call { method, params } => {
+                let result = self.call(&method, params).await?;
+                print_value(&result);
+            }
+            ReplCommand::Send(message) => {
+                if message.get_state_id() != self.state_id {
+                    println!(
+                        "The Core only accepts messages for State by id <{}>",
+                        self.state_id
+                    );
+                } else if let Ok(message) = serde_json::to_string(&message) {
+                    self.socket.send(Message::Text(message)).await?;
+                }
+            }
+            ReplCommand::Help => println!("{}", HELP),
+            ReplCommand::Quit => {}
+        }
+
+        Ok(())
+    }
+
+    /// Make a JSON RPC call with the State ID and token as first params, and wait for it's result.
+    /// Messages received meanwhile are printed
+    async fn call(&mut self, method: &str, params: Vec<Value>) -> Result<Value, Error> {
+        self.last_request_id += 1;
+        let id = self.last_request_id;
+
+        let mut all_params = vec![json!(self.state_id), json!(self.token)];
+        all_params.extend(params);
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": all_params,
+        });
+        self.socket.send(Message::Text(request.to_string())).await?;
+
+        while let Some(message) = self.socket.next().await {
+            let message = message?;
+            if let Message::Text(text) = &message {
+                if let Ok(response) = serde_json::from_str::<Value>(text) {
+                    if response["id"] == json!(id) {
+                        return Ok(match response.get("error") {
+                            Some(error) => json!({ "RpcError": error }),
+                            None => response["result"].clone(),
+                        });
+                    }
+                }
+            }
+            print_message(message);
+        }
+
+        Err(Error::ConnectionClosed)
+    }
+}
+
+fn print_value(value: &Value) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+    );
+}
+
+/// Print something the Core sent without being asked, e.g a `ServerMessages` notification
+fn print_message(message: Message) {
+    match message {
+        Message::Text(text) => match serde_json::from_str::<Value>(&text) {
+            Ok(value) => {
+                println!("<-");
+                print_value(&value);
+            }
+            Err(_) => println!("<- {}", text),
+        },
+        Message::Close(_) => println!("<- Connection closed"),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gveditor_core_api::messaging::ClientMessages;
+    use jsonrpc_core::serde_json::json;
+
+    use super::{ReplCommand, ReplErrors};
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(ReplCommand::parse("state"), Ok(ReplCommand::State));
+        assert_eq!(
+            ReplCommand::parse(" metrics git "),
+            Ok(ReplCommand::Metrics(Some("git".to_owned())))
+        );
+        assert_eq!(
+            ReplCommand::parse(r#"call get_settings_schemas"#),
+            Ok(ReplCommand::Call {
+                method: "get_settings_schemas".to_owned(),
+                params: Vec::new()
+            })
+        );
+        assert_eq!(
+            ReplCommand::parse(r#"call read_file_by_path ["/main.rs", "local"]"#),
+            Ok(ReplCommand::Call {
+                method: "read_file_by_path".to_owned(),
+                params: vec![json!("/main.rs"), json!("local")]
+            })
+        );
+        assert_eq!(
+            ReplCommand::parse(r#"send {"ListenToState":{"state_id":1}}"#),
+            Ok(ReplCommand::Send(ClientMessages::ListenToState {
+                state_id: 1
+            }))
+        );
+        assert!(matches!(
+            ReplCommand::parse("call"),
+            Err(ReplErrors::BadArguments(_))
+        ));
+        assert_eq!(
+            ReplCommand::parse("states"),
+            Err(ReplErrors::UnknownCommand("states".to_owned()))
+        );
+    }
+}
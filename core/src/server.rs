@@ -1,15 +1,62 @@
 use crate::handlers::TransportHandler;
+use crate::signals::wait_for_shutdown_signal;
 use crate::Configuration;
-use gveditor_core_api::filesystems::{DirItemInfo, FileInfo, FilesystemErrors};
-use gveditor_core_api::language_servers::LanguageServerBuilderInfo;
+use crate::ShutdownCoordinator;
+use gveditor_core_api::auth::{AuthErrors, Credentials};
+use gveditor_core_api::changes::{ChangeEntry, ChangesFilter};
+use gveditor_core_api::clipboard::{ClipboardEntry, ClipboardOrigin};
+use gveditor_core_api::decorations::PathsDecorations;
+use gveditor_core_api::documents::{DocumentEdit, DocumentInfo};
+use gveditor_core_api::experiments::ExperimentStatus;
+use gveditor_core_api::explorer::ExplorerListing;
+use gveditor_core_api::extensions::commands::CommandInfo;
+use gveditor_core_api::extensions::compatibility::{verify_extension, CompatibilityReport};
+use gveditor_core_api::extensions::editors::{CustomEditor, OpenedFile};
+use gveditor_core_api::extensions::permissions::{ExtensionPermissions, Permission};
+use gveditor_core_api::extensions::supervisor::{ExtensionMetrics, PanicPolicy};
+use gveditor_core_api::filesystems::{
+    DirItemInfo, FileChunk, FileEncoding, FileInfo, FilesystemCapabilities, FilesystemErrors,
+    GravitonUri,
+};
+use gveditor_core_api::http::HttpSettings;
+use gveditor_core_api::icons::{FileIcon, IconTheme};
+use gveditor_core_api::indexer::QuickOpenItem;
+use gveditor_core_api::language_servers::{LanguageServerBuilderInfo, LanguageServerConfig};
+use gveditor_core_api::locale::ClientLocale;
+use gveditor_core_api::logging::{LogEntry, LogLevel, Logger};
+use gveditor_core_api::matcher::{MatchKind, Ranked};
 use gveditor_core_api::messaging::{ClientMessages, ServerMessages};
-use gveditor_core_api::states::{StateData, StatesList};
+use gveditor_core_api::metrics::METRICS;
+use gveditor_core_api::progress::ProgressInfo;
+use gveditor_core_api::recovery::{Draft, RecoveredSession};
+use gveditor_core_api::refactoring::RenamePreview;
+use gveditor_core_api::save_hooks::SaveOptions;
+use gveditor_core_api::search::{SearchOptions, SearchSource};
+use gveditor_core_api::settings::{Keybinding, SettingSchema, UserSettings};
+use gveditor_core_api::states::closed_tabs::ClosedTab;
+use gveditor_core_api::states::layouts::Layout;
+use gveditor_core_api::states::views::TabData;
+use gveditor_core_api::states::{
+    CommandGroupInfo, DataEvent, Invitation, InvitationAccess, ResourceReport, ScopedToken,
+    ShutdownConfig, ShutdownReport, StateData, StateDataMerge, StateDataUpdate, StateEvent,
+    StateSnapshot, StatesList, TokenScope, COMMAND_GROUP_TIMEOUT,
+};
+use gveditor_core_api::tasks::{RegisteredTask, ScheduledTask, TaskDefinition, TaskRunInfo};
+use gveditor_core_api::telemetry::TelemetryReport;
 use gveditor_core_api::terminal_shells::TerminalShellBuilderInfo;
-use gveditor_core_api::{Errors, ManifestInfo, Mutex, State};
-use jsonrpc_core::BoxFuture;
+use gveditor_core_api::tree_views::{TreeItem, TreeViewInfo};
+use gveditor_core_api::validation::{Diagnostic, ValidationSchema};
+use gveditor_core_api::vcs::RepositoryStatus;
+use gveditor_core_api::workspaces::{Workspace, WorkspaceConfig};
+use gveditor_core_api::{Errors, ExtensionErrors, ManifestInfo, Mutex, State};
+use jsonrpc_core::{BoxFuture, Value};
 use jsonrpc_derive::rpc;
 
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
 
 pub struct Server {
     states: Arc<Mutex<StatesList>>,
@@ -87,6 +134,7 @@ impl Server {
             if let Some(mut server_rx) = server_rx {
                 loop {
                     if let Some(message) = server_rx.recv().await {
+                        METRICS.record_message();
                         Self::process_message(states_list.clone(), message, handler.clone()).await;
                     }
                 }
@@ -99,6 +147,52 @@ impl Server {
     /// Run the Server with the conigured handler
     pub async fn run(&mut self) {
         let states = self.states.clone();
+
+        if self.config.autosave_on_signals {
+            let states = states.clone();
+            let handler = self.config.handler.clone();
+            let shutdown_config = self.config.shutdown.clone();
+            tokio::spawn(async move {
+                Self::shutdown_on_signal(states, handler, shutdown_config).await;
+                std::process::exit(0);
+            });
+        }
+
+        if let Some(hibernate_after) = self.config.hibernate_after {
+            tokio::spawn(Self::hibernate_idle_states(states.clone(), hibernate_after));
+        }
+
+        if let Some(interval) = self.config.recovery_autosave {
+            tokio::spawn(Self::autosave_recovery(states.clone(), interval));
+        }
+
+        if let Some(config) = self.config.telemetry {
+            for state in states.lock().await.get_states() {
+                state.lock().await.enable_telemetry(config);
+            }
+
+            if let Some(interval) = config.broadcast_interval {
+                tokio::spawn(Self::broadcast_metrics(states.clone(), interval));
+            }
+        }
+
+        tokio::spawn(Self::run_scheduled_tasks(
+            states.clone(),
+            self.config.handler.clone(),
+        ));
+
+        tokio::spawn(Self::end_abandoned_groups(states.clone()));
+
+        // Keep all the clients in sync with the changes made by others
+        for state in states.lock().await.get_states() {
+            let events = state.lock().await.subscribe();
+            tokio::spawn(Self::forward_state_events(
+                state,
+                events,
+                self.config.handler.clone(),
+            ));
+        }
+
         let mut handler = self.config.handler.lock().await;
 
         handler
@@ -106,6 +200,217 @@ impl Server {
             .await;
     }
 
+    /// A coordinator to shut down this Server, e.g when an embedder exits
+    pub fn get_shutdown_coordinator(&self) -> ShutdownCoordinator {
+        ShutdownCoordinator::new(
+            self.states.clone(),
+            self.config.handler.clone(),
+            self.config.shutdown.clone(),
+        )
+    }
+
+    /// Stop all the States phase by phase, tell the clients and close the transport,
+    /// see [`ShutdownCoordinator`]
+    ///
+    /// # Arguments
+    ///
+    /// * `states`   - The configured States list
+    /// * `handler`  - The transport handler
+    /// * `config`   - Timeouts of the shutdown phases
+    ///
+    pub async fn shutdown(
+        states: Arc<Mutex<StatesList>>,
+        handler: Arc<Mutex<Box<dyn TransportHandler + Send + Sync>>>,
+        config: ShutdownConfig,
+    ) -> ShutdownReport {
+        ShutdownCoordinator::new(states, handler, config)
+            .run()
+            .await
+    }
+
+    /// Wait for the OS to ask the process to exit and then shut down, see [`wait_for_shutdown_signal`]
+    async fn shutdown_on_signal(
+        states: Arc<Mutex<StatesList>>,
+        handler: Arc<Mutex<Box<dyn TransportHandler + Send + Sync>>>,
+        config: ShutdownConfig,
+    ) -> ShutdownReport {
+        let signal = wait_for_shutdown_signal().await;
+        tracing::info!("Received {}, shutting down", signal);
+        Self::shutdown(states, handler, config).await
+    }
+
+    /// Send the changes of a State to the clients
+    async fn forward_state_events(
+        state: Arc<Mutex<State>>,
+        mut events: broadcast::Receiver<StateEvent>,
+        handler: Arc<Mutex<Box<dyn TransportHandler + Send + Sync>>>,
+    ) {
+        loop {
+            let message = match events.recv().await {
+                // Only what changed since the clients were last sent the data
+                Ok(StateEvent::DataChanged { .. }) => state.lock().await.get_data_message(),
+                Ok(event) => Some(ServerMessages::StateChanged {
+                    state_id: state.lock().await.data.id,
+                    event,
+                }),
+                // Some changes were missed, so the whole state is sent again
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("Clients missed {} changes, sending the whole state", missed);
+                    Some(state.lock().await.get_data_snapshot_message())
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            if let Some(message) = message {
+                handler.lock().await.send(message).await;
+            }
+        }
+    }
+
+    /// Every minute, run the scheduled tasks of the active States
+    async fn run_scheduled_tasks(
+        states: Arc<Mutex<StatesList>>,
+        handler: Arc<Mutex<Box<dyn TransportHandler + Send + Sync>>>,
+    ) {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+        loop {
+            interval.tick().await;
+
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_secs())
+                .unwrap_or_default();
+
+            let states = states.lock().await.get_states();
+            for state in states {
+                let (state_id, due_tasks) = {
+                    let mut state = state.lock().await;
+                    (state.data.id, state.take_due_tasks(now))
+                };
+
+                for task in due_tasks {
+                    let state = state.clone();
+                    let handler = handler.clone();
+                    tokio::spawn(async move {
+                        tracing::info!("Running scheduled task <{}>", task.id);
+                        let run = task.task.run().await;
+
+                        // It might have been removed while running
+                        state
+                            .lock()
+                            .await
+                            .record_task_run(&task.id, run.clone())
+                            .await
+                            .ok();
+
+                        handler
+                            .lock()
+                            .await
+                            .send(ServerMessages::ScheduledTaskFinished {
+                                state_id,
+                                summary: run.get_summary(&task.id, &ClientLocale::default()),
+                                task_id: task.id,
+                                run,
+                            })
+                            .await;
+                    });
+                }
+            }
+        }
+    }
+
+    /// Periodically end the command groups that were never ended, e.g their client disconnected
+    async fn end_abandoned_groups(states: Arc<Mutex<StatesList>>) {
+        let mut interval = tokio::time::interval(COMMAND_GROUP_TIMEOUT / 2);
+
+        loop {
+            interval.tick().await;
+
+            let states = states.lock().await.get_states();
+            for state in states {
+                let mut state = state.lock().await;
+                state.end_abandoned_group(COMMAND_GROUP_TIMEOUT).await;
+            }
+        }
+    }
+
+    /// Periodically hibernate the States that have been idle for too long
+    async fn hibernate_idle_states(states: Arc<Mutex<StatesList>>, hibernate_after: Duration) {
+        let mut interval = tokio::time::interval(hibernate_after.min(Duration::from_secs(60)));
+
+        loop {
+            interval.tick().await;
+
+            let states = states.lock().await.get_states();
+            for state in states {
+                let mut state = state.lock().await;
+                if !state.is_hibernated() && state.get_idle_time() >= hibernate_after {
+                    state.hibernate().await;
+                }
+            }
+        }
+    }
+
+    /// Keep the unsaved work of the States every `interval`, hibernated States already did
+    async fn autosave_recovery(states: Arc<Mutex<StatesList>>, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+
+        loop {
+            interval.tick().await;
+
+            let states = states.lock().await.get_states();
+            for state in states {
+                let mut state = state.lock().await;
+                if !state.is_hibernated() {
+                    state.autosave_recovery();
+                }
+            }
+        }
+    }
+
+    /// Send the metrics of the States with telemetry every `interval`, hibernated States are skipped
+    async fn broadcast_metrics(states: Arc<Mutex<StatesList>>, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+
+        loop {
+            interval.tick().await;
+
+            let states = states.lock().await.get_states();
+            for state in states {
+                let (sender, message) = {
+                    let state = state.lock().await;
+                    if state.is_hibernated() {
+                        continue;
+                    }
+                    let metrics = match state.get_metrics() {
+                        Some(metrics) => metrics,
+                        None => continue,
+                    };
+                    (
+                        state.extensions_manager.sender.clone(),
+                        ClientMessages::Metrics {
+                            state_id: state.data.id,
+                            metrics,
+                        },
+                    )
+                };
+
+                sender.send(message).await.ok();
+            }
+        }
+    }
+
+    /// Awake a State if it's hibernated, in the background so the messages it sends don't block the messages loop
+    async fn awake_state(states: Arc<Mutex<StatesList>>, state_id: u8) {
+        let state = states.lock().await.get_state_by_id(state_id);
+
+        if let Some(state) = state {
+            let mut state_g = state.lock().await;
+            touch_or_awake(&state, &mut state_g);
+        }
+    }
+
     /// Process every message
     ///
     /// # Arguments
@@ -119,6 +424,16 @@ impl Server {
         message: ClientMessages,
         handler: Arc<Mutex<Box<dyn TransportHandler + Send + Sync>>>,
     ) {
+        // Any message from a client counts as activity
+        if !matches!(
+            message,
+            ClientMessages::ServerMessage(..)
+                | ClientMessages::ExtensionCrashed { .. }
+                | ClientMessages::Metrics { .. }
+        ) {
+            Self::awake_state(states.clone(), message.get_state_id()).await;
+        }
+
         match message.clone() {
             ClientMessages::ListenToState { state_id } => {
                 let state = {
@@ -128,12 +443,21 @@ impl Server {
 
                 if let Some(state) = state {
                     {
-                        // Send the loaded state to the handler
+                        // Send the loaded state to the handler, the next deltas are made on top of it
                         let handler = handler.lock().await;
-                        let message = ServerMessages::StateUpdated {
-                            state_data: state.lock().await.data.clone(),
-                        };
+                        let message = state.lock().await.get_data_snapshot_message();
                         handler.send(message).await;
+
+                        // Let the client know what was restored if the last session crashed
+                        let recovered_session = state.lock().await.get_recovered_session();
+                        if let Some(session) = recovered_session {
+                            handler
+                                .send(ServerMessages::SessionRecovered {
+                                    state_id,
+                                    session: Box::new(session),
+                                })
+                                .await;
+                        }
                     }
 
                     // Execute the registered extensions in the State
@@ -183,11 +507,375 @@ impl Server {
                     state.notify_extension(extension_id, message);
                 }
             }
+            ClientMessages::ModalKeyPressed {
+                state_id,
+                view_id,
+                key,
+                content,
+                cursor,
+            } => {
+                let state = {
+                    let states = states.lock().await;
+                    states.get_state_by_id(state_id)
+                };
+
+                if let Some(state) = state {
+                    let output = state
+                        .lock()
+                        .await
+                        .handle_modal_key(&view_id, &key, &content, cursor);
+
+                    let handler = handler.lock().await;
+                    handler
+                        .send(ServerMessages::ModalKeyHandled {
+                            state_id,
+                            view_id,
+                            edits: output.edits,
+                            cursor: output.cursor,
+                            mode: output.mode,
+                        })
+                        .await;
+                }
+            }
+            ClientMessages::RestartExtension {
+                state_id,
+                extension_id,
+            } => {
+                let state = {
+                    let states = states.lock().await;
+                    states.get_state_by_id(state_id)
+                };
+
+                if let Some(state) = state {
+                    let state_handle = state.clone();
+                    state
+                        .lock()
+                        .await
+                        .restart_extension(&extension_id, state_handle)
+                        .await
+                        .ok();
+                }
+            }
+            ClientMessages::ReloadExtension {
+                state_id,
+                extension_id,
+            } => {
+                let state = {
+                    let states = states.lock().await;
+                    states.get_state_by_id(state_id)
+                };
+
+                if let Some(state) = state {
+                    let state_handle = state.clone();
+                    let res = state
+                        .lock()
+                        .await
+                        .reload_extension(&extension_id, state_handle)
+                        .await;
+                    if let Err(err) = res {
+                        tracing::error!("Could not reload extension <{}>: {:?}", extension_id, err);
+                    }
+                }
+            }
+            ClientMessages::SettingsChanged {
+                state_id,
+                keys,
+                settings,
+            } => {
+                let state = {
+                    let states = states.lock().await;
+                    states.get_state_by_id(state_id)
+                };
+
+                // Both the extensions and the client might be interested in the settings
+                if let Some(state) = state {
+                    state.lock().await.notify_extensions(message);
+                }
+
+                let handler = handler.lock().await;
+                handler
+                    .send(ServerMessages::SettingsChanged {
+                        state_id,
+                        keys,
+                        settings,
+                    })
+                    .await;
+            }
+            ClientMessages::WorkspaceConfigChanged {
+                state_id,
+                filesystem,
+                root,
+            } => {
+                let state = {
+                    let states = states.lock().await;
+                    states.get_state_by_id(state_id)
+                };
+
+                if let Some(state) = state {
+                    state
+                        .lock()
+                        .await
+                        .reload_workspace_config(&filesystem, &root)
+                        .await
+                        .ok();
+                }
+            }
+            ClientMessages::CustomRequest {
+                state_id,
+                request_id,
+                message_type,
+                params,
+            } => {
+                let state = {
+                    let states = states.lock().await;
+                    states.get_state_by_id(state_id)
+                };
+
+                if let Some(state) = state {
+                    let res =
+                        state
+                            .lock()
+                            .await
+                            .send_custom_request(&request_id, &message_type, params);
+
+                    // Let the client know nobody will answer
+                    if let Err(err) = res {
+                        let handler = handler.lock().await;
+                        handler
+                            .send(ServerMessages::CustomResponse {
+                                state_id,
+                                request_id,
+                                result: Err(err),
+                            })
+                            .await;
+                    }
+                }
+            }
+            ClientMessages::ExtensionMessage { state_id, message } => {
+                let state = {
+                    let states = states.lock().await;
+                    states.get_state_by_id(state_id)
+                };
+
+                if let Some(state) = state {
+                    state.lock().await.send_extension_message(message).ok();
+                }
+            }
+            ClientMessages::ProgressUpdate { state_id, update } => {
+                let handler = handler.lock().await;
+                handler
+                    .send(ServerMessages::ProgressUpdate { state_id, update })
+                    .await;
+            }
+            ClientMessages::CommandResult {
+                state_id,
+                extension_id,
+                invocation_id,
+                result,
+            } => {
+                let state = {
+                    let states = states.lock().await;
+                    states.get_state_by_id(state_id)
+                };
+
+                if let Some(state) = state {
+                    let finished = state
+                        .lock()
+                        .await
+                        .finish_command(&invocation_id, &extension_id);
+
+                    if finished {
+                        let handler = handler.lock().await;
+                        handler
+                            .send(ServerMessages::CommandFinished {
+                                state_id,
+                                invocation_id,
+                                result,
+                            })
+                            .await;
+                    } else {
+                        tracing::warn!(
+                            "Extension <{}> finished the unknown command invocation <{}>",
+                            extension_id,
+                            invocation_id
+                        );
+                    }
+                }
+            }
+            ClientMessages::CustomResponse {
+                state_id,
+                extension_id,
+                request_id,
+                result,
+            } => {
+                let state = {
+                    let states = states.lock().await;
+                    states.get_state_by_id(state_id)
+                };
+
+                if let Some(state) = state {
+                    let answered = state
+                        .lock()
+                        .await
+                        .complete_custom_request(&request_id, &extension_id);
+
+                    if answered {
+                        let handler = handler.lock().await;
+                        handler
+                            .send(ServerMessages::CustomResponse {
+                                state_id,
+                                request_id,
+                                result,
+                            })
+                            .await;
+                    } else {
+                        tracing::warn!(
+                            "Extension <{}> answered the unknown request <{}>",
+                            extension_id,
+                            request_id
+                        );
+                    }
+                }
+            }
+            ClientMessages::ExtensionCrashed {
+                state_id,
+                extension_id,
+                message: crash_message,
+            } => {
+                let state = {
+                    let states = states.lock().await;
+                    states.get_state_by_id(state_id)
+                };
+
+                if let Some(state) = state {
+                    state.lock().await.notify_extensions(message);
+                }
+
+                let handler = handler.lock().await;
+                handler
+                    .send(ServerMessages::ExtensionCrashed {
+                        state_id,
+                        extension_id,
+                        message: crash_message,
+                    })
+                    .await;
+            }
+            ClientMessages::Metrics { state_id, metrics } => {
+                let state = {
+                    let states = states.lock().await;
+                    states.get_state_by_id(state_id)
+                };
+
+                if let Some(state) = state {
+                    state.lock().await.notify_extensions(message);
+                }
+
+                let handler = handler.lock().await;
+                handler
+                    .send(ServerMessages::Metrics { state_id, metrics })
+                    .await;
+            }
             ClientMessages::ServerMessage(server_msg) => {
                 match server_msg {
                     ServerMessages::StateUpdated { .. } => {
-                        let states = states.lock().await;
-                        states.notify_extensions(message).await;
+                        // Restored snapshots must reach the client too
+                        {
+                            let states = states.lock().await;
+                            states.notify_extensions(message).await;
+                        }
+                        let handler = handler.lock().await;
+                        handler.send(server_msg).await;
+                    }
+                    ServerMessages::StateDelta { state_id, .. } => {
+                        // Extensions are always given the whole data
+                        let state = {
+                            let states = states.lock().await;
+                            states.get_state_by_id(state_id)
+                        };
+                        if let Some(state) = state {
+                            let state_data = Box::new(state.lock().await.data.clone());
+                            let states = states.lock().await;
+                            states
+                                .notify_extensions(ClientMessages::ServerMessage(
+                                    ServerMessages::StateUpdated { state_data },
+                                ))
+                                .await;
+                        }
+                        let handler = handler.lock().await;
+                        handler.send(server_msg).await;
+                    }
+                    ServerMessages::TerminalShellUpdated {
+                        state_id,
+                        ref terminal_shell_id,
+                        ref data,
+                    } => {
+                        // Keep the output so it can be searched
+                        let state = {
+                            let states = states.lock().await;
+                            states.get_state_by_id(state_id)
+                        };
+                        if let Some(state) = state {
+                            let output = String::from_utf8_lossy(data);
+                            let mut state = state.lock().await;
+                            state.output_buffers.push(
+                                SearchSource::Terminal {
+                                    terminal_shell_id: terminal_shell_id.clone(),
+                                },
+                                &output,
+                            );
+                            // Programs can copy with OSC 52, e.g vim or tmux
+                            state.copy_from_terminal(terminal_shell_id, &output).await;
+                        }
+                        let handler = handler.lock().await;
+                        handler.send(server_msg).await;
+                    }
+                    ServerMessages::TerminalShellClosed {
+                        state_id,
+                        ref terminal_shell_id,
+                    } => {
+                        let state = {
+                            let states = states.lock().await;
+                            states.get_state_by_id(state_id)
+                        };
+                        if let Some(state) = state {
+                            let mut state = state.lock().await;
+                            state.terminal_shells.remove(terminal_shell_id);
+                            state.output_buffers.remove(&SearchSource::Terminal {
+                                terminal_shell_id: terminal_shell_id.clone(),
+                            });
+                        }
+                        let handler = handler.lock().await;
+                        handler.send(server_msg).await;
+                    }
+                    ServerMessages::OutputChannelUpdated {
+                        state_id,
+                        ref channel_id,
+                        ref data,
+                        ..
+                    } => {
+                        let state = {
+                            let states = states.lock().await;
+                            states.get_state_by_id(state_id)
+                        };
+                        if let Some(state) = state {
+                            state.lock().await.output_buffers.push(
+                                SearchSource::OutputChannel {
+                                    channel_id: channel_id.clone(),
+                                },
+                                data,
+                            );
+                        }
+                        let handler = handler.lock().await;
+                        handler.send(server_msg).await;
+                    }
+                    ServerMessages::KernelOutput { .. } => {
+                        // Both the extensions and the client might be interested in the outputs
+                        {
+                            let states = states.lock().await;
+                            states.notify_extensions(message).await;
+                        }
+                        let handler = handler.lock().await;
+                        handler.send(server_msg).await;
                     }
                     _ => {
                         // Forward to the handler messages not handled here
@@ -217,9 +905,9 @@ pub trait RpcMethods {
     fn set_state_by_id(
         &self,
         state_id: u8,
-        state: StateData,
+        state: StateDataUpdate,
         token: String,
-    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+    ) -> BoxFuture<RPCResult<Result<StateDataMerge, Errors>>>;
 
     #[rpc(name = "read_file_by_path")]
     fn read_file_by_path(
@@ -228,6 +916,7 @@ pub trait RpcMethods {
         filesystem_name: String,
         state_id: u8,
         token: String,
+        encoding: Option<FileEncoding>,
     ) -> BoxFuture<RPCResult<Result<FileInfo, Errors>>>;
 
     #[rpc(name = "write_file_by_path")]
@@ -238,6 +927,7 @@ pub trait RpcMethods {
         filesystem_name: String,
         state_id: u8,
         token: String,
+        encoding: Option<FileEncoding>,
     ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
 
     #[rpc(name = "list_dir_by_path")]
@@ -338,468 +1028,4125 @@ pub trait RpcMethods {
         language_server_builder_id: String,
         data: String,
     ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
-}
 
-async fn verify_state(
-    states: Arc<Mutex<StatesList>>,
-    state_id: u8,
-    token: String,
-) -> Result<Arc<Mutex<State>>, Errors> {
-    let states = states.lock().await;
-    // Try to get the requested state
-    if let Some(state) = states.get_state_by_id(state_id) {
-        let state_g = state.lock().await;
-        // Make sure the token is valid
-        if state_g.has_token(&token) {
-            drop(state_g);
-            Ok(state)
+    #[rpc(name = "restart_extension")]
+    fn restart_extension(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_language_server_configs")]
+    fn get_language_server_configs(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<LanguageServerConfig>, Errors>>>;
+
+    #[rpc(name = "start_language_server")]
+    fn start_language_server(
+        &self,
+        state_id: u8,
+        token: String,
+        config_id: String,
+        root_uri: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>>;
+
+    #[rpc(name = "stop_language_server")]
+    fn stop_language_server(
+        &self,
+        state_id: u8,
+        token: String,
+        language_server_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "restart_language_server")]
+    fn restart_language_server(
+        &self,
+        state_id: u8,
+        token: String,
+        language_server_id: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>>;
+
+    #[rpc(name = "create_invitation")]
+    fn create_invitation(
+        &self,
+        state_id: u8,
+        token: String,
+        access: InvitationAccess,
+        duration_secs: u64,
+    ) -> BoxFuture<RPCResult<Result<Invitation, Errors>>>;
+
+    #[rpc(name = "revoke_invitation")]
+    fn revoke_invitation(
+        &self,
+        state_id: u8,
+        token: String,
+        invitation_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_invitations")]
+    fn get_invitations(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Invitation>, Errors>>>;
+
+    #[rpc(name = "get_tree_views")]
+    fn get_tree_views(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<TreeViewInfo>, Errors>>>;
+
+    #[rpc(name = "get_tree_view_children")]
+    fn get_tree_view_children(
+        &self,
+        state_id: u8,
+        token: String,
+        view_id: String,
+        parent_id: Option<String>,
+    ) -> BoxFuture<RPCResult<Result<Vec<TreeItem>, Errors>>>;
+
+    #[rpc(name = "search")]
+    fn search(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        query: String,
+        options: SearchOptions,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>>;
+
+    #[rpc(name = "cancel_search")]
+    fn cancel_search(
+        &self,
+        state_id: u8,
+        token: String,
+        search_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "save_draft")]
+    fn save_draft(
+        &self,
+        state_id: u8,
+        token: String,
+        draft: Draft,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "discard_draft")]
+    fn discard_draft(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_drafts")]
+    fn get_drafts(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Draft>, Errors>>>;
+
+    #[rpc(name = "get_repository_status")]
+    fn get_repository_status(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<RepositoryStatus, Errors>>>;
+
+    #[rpc(name = "get_file_diff")]
+    fn get_file_diff(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        file_path: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>>;
+
+    #[rpc(name = "watch_repository")]
+    fn watch_repository(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "unwatch_repository")]
+    fn unwatch_repository(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_extension_permissions")]
+    fn get_extension_permissions(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<ExtensionPermissions, Errors>>>;
+
+    #[rpc(name = "grant_extension_permission")]
+    fn grant_extension_permission(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+        permission: Permission,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "revoke_extension_permission")]
+    fn revoke_extension_permission(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+        permission: Permission,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_extension_metrics")]
+    fn get_extension_metrics(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<ExtensionMetrics, Errors>>>;
+
+    #[rpc(name = "set_extension_panic_policy")]
+    fn set_extension_panic_policy(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+        policy: PanicPolicy,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "reload_extension")]
+    fn reload_extension(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "watch_extension")]
+    fn watch_extension(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "unwatch_extension")]
+    fn unwatch_extension(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_http_settings")]
+    fn get_http_settings(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<HttpSettings, Errors>>>;
+
+    #[rpc(name = "set_http_settings")]
+    fn set_http_settings(
+        &self,
+        state_id: u8,
+        token: String,
+        settings: HttpSettings,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "rename_path")]
+    fn rename_path(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        from: String,
+        to: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "issue_token")]
+    fn issue_token(
+        &self,
+        state_id: u8,
+        token: String,
+        label: String,
+        scopes: Vec<TokenScope>,
+        duration_secs: Option<u64>,
+    ) -> BoxFuture<RPCResult<Result<ScopedToken, Errors>>>;
+
+    #[rpc(name = "revoke_token")]
+    fn revoke_token(
+        &self,
+        state_id: u8,
+        token: String,
+        token_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_scoped_tokens")]
+    fn get_scoped_tokens(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<ScopedToken>, Errors>>>;
+
+    #[rpc(name = "open_document")]
+    fn open_document(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<DocumentInfo, Errors>>>;
+
+    #[rpc(name = "edit_document")]
+    fn edit_document(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        version: i32,
+        edits: Vec<DocumentEdit>,
+    ) -> BoxFuture<RPCResult<Result<i32, Errors>>>;
+
+    #[rpc(name = "save_document")]
+    fn save_document(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        options: Option<SaveOptions>,
+    ) -> BoxFuture<RPCResult<Result<DocumentInfo, Errors>>>;
+
+    #[rpc(name = "close_document")]
+    fn close_document(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_document_content")]
+    fn get_document_content(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>>;
+
+    #[rpc(name = "get_documents")]
+    fn get_documents(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<DocumentInfo>, Errors>>>;
+
+    #[rpc(name = "read_file_range")]
+    fn read_file_range(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        offset: u64,
+        length: usize,
+    ) -> BoxFuture<RPCResult<Result<FileChunk, Errors>>>;
+
+    #[rpc(name = "append_to_file")]
+    fn append_to_file(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        content: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "preview_rename")]
+    fn preview_rename(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        old_name: String,
+        new_name: String,
+        options: SearchOptions,
+    ) -> BoxFuture<RPCResult<Result<RenamePreview, Errors>>>;
+
+    #[rpc(name = "apply_rename")]
+    fn apply_rename(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        preview: RenamePreview,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_file_decorations")]
+    fn get_file_decorations(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        paths: Vec<String>,
+    ) -> BoxFuture<RPCResult<Result<PathsDecorations, Errors>>>;
+
+    #[rpc(name = "move_to_trash")]
+    fn move_to_trash(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "move_path")]
+    fn move_path(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        from: String,
+        to: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "copy_folder")]
+    fn copy_folder(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        from: String,
+        to: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>>;
+
+    #[rpc(name = "cancel_file_operation")]
+    fn cancel_file_operation(
+        &self,
+        state_id: u8,
+        token: String,
+        operation_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "hibernate_state")]
+    fn hibernate_state(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "snapshot_state")]
+    fn snapshot_state(
+        &self,
+        state_id: u8,
+        token: String,
+        label: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "undo_state")]
+    fn undo_state(&self, state_id: u8, token: String)
+        -> BoxFuture<RPCResult<Result<bool, Errors>>>;
+
+    #[rpc(name = "redo_state")]
+    fn redo_state(&self, state_id: u8, token: String)
+        -> BoxFuture<RPCResult<Result<bool, Errors>>>;
+
+    #[rpc(name = "get_state_snapshots")]
+    fn get_state_snapshots(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<StateSnapshot>, Errors>>>;
+
+    #[rpc(name = "get_logs")]
+    fn get_logs(
+        &self,
+        state_id: u8,
+        token: String,
+        limit: usize,
+    ) -> BoxFuture<RPCResult<Result<Vec<LogEntry>, Errors>>>;
+
+    #[rpc(name = "get_log_levels")]
+    fn get_log_levels(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<BTreeMap<String, LogLevel>, Errors>>>;
+
+    #[rpc(name = "set_log_level")]
+    fn set_log_level(
+        &self,
+        state_id: u8,
+        token: String,
+        target: String,
+        level: Option<LogLevel>,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "read_file_by_uri")]
+    fn read_file_by_uri(
+        &self,
+        state_id: u8,
+        token: String,
+        uri: GravitonUri,
+    ) -> BoxFuture<RPCResult<Result<FileInfo, Errors>>>;
+
+    #[rpc(name = "write_file_by_uri")]
+    fn write_file_by_uri(
+        &self,
+        state_id: u8,
+        token: String,
+        uri: GravitonUri,
+        content: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_scheduled_tasks")]
+    fn get_scheduled_tasks(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<ScheduledTask>, Errors>>>;
+
+    #[rpc(name = "set_scheduled_task")]
+    fn set_scheduled_task(
+        &self,
+        state_id: u8,
+        token: String,
+        task: ScheduledTask,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "remove_scheduled_task")]
+    fn remove_scheduled_task(
+        &self,
+        state_id: u8,
+        token: String,
+        task_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "enable_scheduled_task")]
+    fn enable_scheduled_task(
+        &self,
+        state_id: u8,
+        token: String,
+        task_id: String,
+        enabled: bool,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_tasks")]
+    fn get_tasks(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<RegisteredTask>, Errors>>>;
+
+    #[rpc(name = "register_task")]
+    fn register_task(
+        &self,
+        state_id: u8,
+        token: String,
+        task_id: String,
+        definition: TaskDefinition,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "unregister_task")]
+    fn unregister_task(
+        &self,
+        state_id: u8,
+        token: String,
+        task_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "run_task")]
+    fn run_task(
+        &self,
+        state_id: u8,
+        token: String,
+        task_id: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>>;
+
+    #[rpc(name = "cancel_task")]
+    fn cancel_task(
+        &self,
+        state_id: u8,
+        token: String,
+        run_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_running_tasks")]
+    fn get_running_tasks(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<TaskRunInfo>, Errors>>>;
+
+    #[rpc(name = "get_message_handlers")]
+    fn get_message_handlers(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<HashMap<String, String>, Errors>>>;
+
+    #[rpc(name = "open_workspace")]
+    fn open_workspace(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        root: String,
+    ) -> BoxFuture<RPCResult<Result<WorkspaceConfig, Errors>>>;
+
+    #[rpc(name = "close_workspace")]
+    fn close_workspace(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        root: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "trust_workspace")]
+    fn trust_workspace(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        root: String,
+    ) -> BoxFuture<RPCResult<Result<WorkspaceConfig, Errors>>>;
+
+    #[rpc(name = "get_workspaces")]
+    fn get_workspaces(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Workspace>, Errors>>>;
+
+    #[rpc(name = "get_workspace_config")]
+    fn get_workspace_config(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        root: String,
+    ) -> BoxFuture<RPCResult<Result<WorkspaceConfig, Errors>>>;
+
+    #[rpc(name = "get_settings")]
+    fn get_settings(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<UserSettings, Errors>>>;
+
+    #[rpc(name = "get_settings_schemas")]
+    fn get_settings_schemas(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<SettingSchema>, Errors>>>;
+
+    #[rpc(name = "set_settings")]
+    fn set_settings(
+        &self,
+        state_id: u8,
+        token: String,
+        values: BTreeMap<String, Value>,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "reset_setting")]
+    fn reset_setting(
+        &self,
+        state_id: u8,
+        token: String,
+        key: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "set_keybindings")]
+    fn set_keybindings(
+        &self,
+        state_id: u8,
+        token: String,
+        keybindings: Vec<Keybinding>,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_layouts")]
+    fn get_layouts(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Layout>, Errors>>>;
+
+    #[rpc(name = "save_layout")]
+    fn save_layout(
+        &self,
+        state_id: u8,
+        token: String,
+        name: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "apply_layout")]
+    fn apply_layout(
+        &self,
+        state_id: u8,
+        token: String,
+        name: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "remove_layout")]
+    fn remove_layout(
+        &self,
+        state_id: u8,
+        token: String,
+        name: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_commands")]
+    fn get_commands(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<CommandInfo>, Errors>>>;
+
+    #[rpc(name = "invoke_command")]
+    fn invoke_command(
+        &self,
+        state_id: u8,
+        token: String,
+        command_id: String,
+        arguments: Value,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>>;
+
+    #[rpc(name = "get_progress")]
+    fn get_progress(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<ProgressInfo>, Errors>>>;
+
+    #[rpc(name = "cancel_progress")]
+    fn cancel_progress(
+        &self,
+        state_id: u8,
+        token: String,
+        progress_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "search_commands")]
+    fn search_commands(
+        &self,
+        state_id: u8,
+        token: String,
+        query: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Ranked<CommandInfo>>, Errors>>>;
+
+    #[rpc(name = "fuzzy_match")]
+    fn fuzzy_match(
+        &self,
+        state_id: u8,
+        token: String,
+        query: String,
+        candidates: Vec<String>,
+        kind: MatchKind,
+    ) -> BoxFuture<RPCResult<Result<Vec<Ranked<String>>, Errors>>>;
+
+    #[rpc(name = "quick_open")]
+    fn quick_open(
+        &self,
+        state_id: u8,
+        token: String,
+        query: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Ranked<QuickOpenItem>>, Errors>>>;
+
+    #[rpc(name = "reopen_document_with_encoding")]
+    fn reopen_document_with_encoding(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        encoding: FileEncoding,
+    ) -> BoxFuture<RPCResult<Result<DocumentInfo, Errors>>>;
+
+    #[rpc(name = "set_document_encoding")]
+    fn set_document_encoding(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        encoding: FileEncoding,
+    ) -> BoxFuture<RPCResult<Result<DocumentInfo, Errors>>>;
+
+    #[rpc(name = "open_archive")]
+    fn open_archive(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>>;
+
+    #[rpc(name = "close_archive")]
+    fn close_archive(
+        &self,
+        state_id: u8,
+        token: String,
+        name: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_recovered_session")]
+    fn get_recovered_session(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Option<RecoveredSession>, Errors>>>;
+
+    #[rpc(name = "restore_recovered_session")]
+    fn restore_recovered_session(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<DocumentInfo>, Errors>>>;
+
+    #[rpc(name = "discard_recovered_session")]
+    fn discard_recovered_session(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_closed_tabs")]
+    fn get_closed_tabs(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<ClosedTab>, Errors>>>;
+
+    #[rpc(name = "reopen_closed_tab")]
+    fn reopen_closed_tab(
+        &self,
+        state_id: u8,
+        token: String,
+        index: usize,
+    ) -> BoxFuture<RPCResult<Result<TabData, Errors>>>;
+
+    #[rpc(name = "reopen_last_closed_tab")]
+    fn reopen_last_closed_tab(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<TabData, Errors>>>;
+
+    #[rpc(name = "set_icon_theme")]
+    fn set_icon_theme(
+        &self,
+        state_id: u8,
+        token: String,
+        icon_theme: Option<IconTheme>,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_file_icon")]
+    fn get_file_icon(
+        &self,
+        state_id: u8,
+        token: String,
+        name: String,
+        is_file: bool,
+    ) -> BoxFuture<RPCResult<Result<Option<FileIcon>, Errors>>>;
+
+    #[rpc(name = "get_metrics")]
+    fn get_metrics(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Option<TelemetryReport>, Errors>>>;
+
+    #[rpc(name = "set_client_locale")]
+    fn set_client_locale(
+        &self,
+        state_id: u8,
+        token: String,
+        locale: ClientLocale,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_filesystem_capabilities")]
+    fn get_filesystem_capabilities(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+    ) -> BoxFuture<RPCResult<Result<FilesystemCapabilities, Errors>>>;
+
+    #[rpc(name = "apply_edits")]
+    fn apply_edits(
+        &self,
+        state_id: u8,
+        token: String,
+        document: GravitonUri,
+        version: i32,
+        edits: Vec<DocumentEdit>,
+    ) -> BoxFuture<RPCResult<Result<i32, Errors>>>;
+
+    #[rpc(name = "validate_file")]
+    fn validate_file(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Diagnostic>, Errors>>>;
+
+    #[rpc(name = "register_validation_schema")]
+    fn register_validation_schema(
+        &self,
+        state_id: u8,
+        token: String,
+        schema: ValidationSchema,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_validation_schemas")]
+    fn get_validation_schemas(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<ValidationSchema>, Errors>>>;
+
+    #[rpc(name = "open_file")]
+    fn open_file(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        editor_id: Option<String>,
+    ) -> BoxFuture<RPCResult<Result<OpenedFile, Errors>>>;
+
+    #[rpc(name = "save_custom_document")]
+    fn save_custom_document(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "close_custom_document")]
+    fn close_custom_document(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "get_custom_editors")]
+    fn get_custom_editors(
+        &self,
+        state_id: u8,
+        token: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<CustomEditor>, Errors>>>;
+
+    #[rpc(name = "get_state_resource_report")]
+    fn get_state_resource_report(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<ResourceReport, Errors>>>;
+
+    #[rpc(name = "get_data_events")]
+    fn get_data_events(
+        &self,
+        state_id: u8,
+        token: String,
+        since_revision: u64,
+    ) -> BoxFuture<RPCResult<Result<Vec<DataEvent>, Errors>>>;
+
+    #[rpc(name = "get_data_at_revision")]
+    fn get_data_at_revision(
+        &self,
+        state_id: u8,
+        token: String,
+        revision: u64,
+    ) -> BoxFuture<RPCResult<Result<Option<StateData>, Errors>>>;
+
+    #[rpc(name = "copy_to_clipboard")]
+    fn copy_to_clipboard(
+        &self,
+        state_id: u8,
+        token: String,
+        text: String,
+    ) -> BoxFuture<RPCResult<Result<ClipboardEntry, Errors>>>;
+
+    #[rpc(name = "get_clipboard")]
+    fn get_clipboard(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Option<ClipboardEntry>, Errors>>>;
+
+    #[rpc(name = "explorer_list")]
+    fn explorer_list(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<ExplorerListing, Errors>>>;
+
+    #[rpc(name = "get_experiments")]
+    fn get_experiments(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<ExperimentStatus>, Errors>>>;
+
+    #[rpc(name = "set_experiment_override")]
+    fn set_experiment_override(
+        &self,
+        state_id: u8,
+        token: String,
+        flag_id: String,
+        enabled: Option<bool>,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>>;
+
+    #[rpc(name = "begin_group")]
+    fn begin_group(
+        &self,
+        state_id: u8,
+        token: String,
+        label: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>>;
+
+    #[rpc(name = "end_group")]
+    fn end_group(
+        &self,
+        state_id: u8,
+        token: String,
+        group_id: String,
+    ) -> BoxFuture<RPCResult<Result<Option<CommandGroupInfo>, Errors>>>;
+
+    #[rpc(name = "authenticate")]
+    fn authenticate(
+        &self,
+        state_id: u8,
+        credentials: Credentials,
+    ) -> BoxFuture<RPCResult<Result<ScopedToken, Errors>>>;
+
+    #[rpc(name = "get_authorization_url")]
+    fn get_authorization_url(
+        &self,
+        redirect_uri: String,
+        csrf_state: String,
+    ) -> BoxFuture<RPCResult<Result<Option<String>, Errors>>>;
+
+    #[rpc(name = "verify_extension")]
+    fn verify_extension(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<CompatibilityReport, Errors>>>;
+
+    #[rpc(name = "changes_feed")]
+    fn changes_feed(
+        &self,
+        state_id: u8,
+        token: String,
+        filter: ChangesFilter,
+    ) -> BoxFuture<RPCResult<Result<Vec<ChangeEntry>, Errors>>>;
+}
+
+/// Count a request as activity of the State. Hibernated States are awaken in the background,
+/// awaking sends messages and runs git, so no lock can be held meanwhile
+fn touch_or_awake(state: &Arc<Mutex<State>>, state_g: &mut State) {
+    if state_g.is_hibernated() {
+        let state = state.clone();
+        tokio::spawn(async move {
+            state.lock().await.awake().await;
+        });
+    } else {
+        state_g.touch();
+    }
+}
+
+async fn verify_state(
+    states: Arc<Mutex<StatesList>>,
+    state_id: u8,
+    token: String,
+) -> Result<Arc<Mutex<State>>, Errors> {
+    let state = states.lock().await.get_state_by_id(state_id);
+    // Try to get the requested state
+    if let Some(state) = state {
+        let mut state_g = state.lock().await;
+        // Make sure the token is valid
+        if state_g.has_token(&token) {
+            touch_or_awake(&state, &mut state_g);
+            drop(state_g);
+            Ok(state)
         } else {
             Err(Errors::BadToken)
         }
     } else {
         Err(Errors::StateNotFound)
     }
-}
+}
+
+/// Same as `verify_state` but also makes sure the token is allowed to modify the State
+async fn verify_state_with_edit_access(
+    states: Arc<Mutex<StatesList>>,
+    state_id: u8,
+    token: String,
+) -> Result<Arc<Mutex<State>>, Errors> {
+    let state = verify_state(states, state_id, token.clone()).await?;
+
+    if state.lock().await.has_edit_access(&token) {
+        Ok(state)
+    } else {
+        Err(Errors::AccessDenied)
+    }
+}
+
+/// Same as `verify_state` but only the owner of the State is allowed, guests are denied
+async fn verify_state_as_owner(
+    states: Arc<Mutex<StatesList>>,
+    state_id: u8,
+    token: String,
+) -> Result<Arc<Mutex<State>>, Errors> {
+    let state = verify_state(states, state_id, token.clone()).await?;
+
+    if state.lock().await.is_owner_token(&token) {
+        Ok(state)
+    } else {
+        Err(Errors::AccessDenied)
+    }
+}
+
+/// Same as `verify_state` but the token only needs to grant any of the given scopes
+async fn verify_state_with_scopes(
+    states: Arc<Mutex<StatesList>>,
+    state_id: u8,
+    token: String,
+    scopes: &[TokenScope],
+) -> Result<Arc<Mutex<State>>, Errors> {
+    let state = states.lock().await.get_state_by_id(state_id);
+    // Try to get the requested state
+    if let Some(state) = state {
+        let mut state_g = state.lock().await;
+        // Make sure the token grants the access
+        if state_g.has_any_scope(&token, scopes) {
+            touch_or_awake(&state, &mut state_g);
+            drop(state_g);
+            Ok(state)
+        } else {
+            Err(Errors::AccessDenied)
+        }
+    } else {
+        Err(Errors::StateNotFound)
+    }
+}
+
+/// Run the body of a JSON RPC method, its errors are the result of the method
+fn rpc<T: Send + 'static>(
+    body: impl Future<Output = Result<T, Errors>> + Send + 'static,
+) -> BoxFuture<RPCResult<Result<T, Errors>>> {
+    Box::pin(async move { Ok(body.await) })
+}
+
+/// JSON RPC manager
+pub struct RpcManager {
+    pub states: Arc<Mutex<StatesList>>,
+}
+
+/// Implementation of all JSON RPC methods
+impl RpcMethods for RpcManager {
+    /// Return the state by the given ID if found
+    fn get_state_by_id(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Option<StateData>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(Some(state.data.clone()))
+        })
+    }
+
+    /// Update an state
+    fn set_state_by_id(
+        &self,
+        state_id: u8,
+        new_state_data: StateDataUpdate,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<StateDataMerge, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token.clone())
+                .await?
+                .lock_owned()
+                .await;
+
+            let merge = state.update_with_token(new_state_data, &token).await?;
+            tracing::info!("Updated state by id <{}>", state.data.id);
+            Ok(merge)
+        })
+    }
+
+    /// Returns the content of a file
+    /// Internally implemented by the given filesystem
+    fn read_file_by_path(
+        &self,
+        path: String,
+        filesystem_name: String,
+        state_id: u8,
+        token: String,
+        encoding: Option<FileEncoding>,
+    ) -> BoxFuture<RPCResult<Result<FileInfo, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            state
+                .read_file_and_notify(&filesystem_name, &path, encoding)
+                .await
+        })
+    }
+
+    /// Writes new content to the specified path
+    fn write_file_by_path(
+        &self,
+        path: String,
+        content: String,
+        filesystem_name: String,
+        state_id: u8,
+        token: String,
+        encoding: Option<FileEncoding>,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state
+                .write_file_and_notify(&filesystem_name, &path, content, encoding)
+                .await
+        })
+    }
+
+    /// Returns the list of items inside the given directory
+    /// Internally implemented by the given filesystem
+    fn list_dir_by_path(
+        &self,
+        path: String,
+        filesystem_name: String,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<DirItemInfo>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            state.list_dir_and_notify(&filesystem_name, &path).await
+        })
+    }
+
+    /// Returns the information about a extension
+    fn get_ext_info_by_id(
+        &self,
+        extension_id: String,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<ManifestInfo, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.get_ext_info_by_id(&extension_id)
+        })
+    }
+    /// Returns the list of extensions in the specified state
+    fn get_ext_list(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<String>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            Ok(state.get_ext_list())
+        })
+    }
+
+    /// Returns the list of language servers builders registered in the specified state
+    fn get_all_language_server_builders(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<LanguageServerBuilderInfo>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            Ok(state.get_all_language_server_builders().await)
+        })
+    }
+
+    fn notify_extension(
+        &self,
+        state_id: u8,
+        token: String,
+        message: ClientMessages,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.notify_extensions(message);
+
+            Ok(())
+        })
+    }
+
+    fn write_to_terminal_shell(
+        &self,
+        state_id: u8,
+        token: String,
+        terminal_shell_id: String,
+        data: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.write_to_terminal_shell(terminal_shell_id, data).await;
+
+            Ok(())
+        })
+    }
+
+    fn create_terminal_shell(
+        &self,
+        state_id: u8,
+        token: String,
+        terminal_shell_builder_id: String,
+        terminal_shell_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state
+                .create_terminal_shell(terminal_shell_builder_id, terminal_shell_id)
+                .await;
+
+            Ok(())
+        })
+    }
+
+    fn close_terminal_shell(
+        &self,
+        state_id: u8,
+        token: String,
+        terminal_shell_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.close_terminal_shell(terminal_shell_id).await;
+
+            Ok(())
+        })
+    }
+
+    fn get_terminal_shell_builders(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<TerminalShellBuilderInfo>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            Ok(state.get_terminal_shell_builders().await)
+        })
+    }
+
+    fn resize_terminal_shell(
+        &self,
+        state_id: u8,
+        token: String,
+        terminal_shell_id: String,
+        cols: i32,
+        rows: i32,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state
+                .resize_terminal_shell(terminal_shell_id, cols, rows)
+                .await;
+
+            Ok(())
+        })
+    }
+
+    fn create_language_server(
+        &self,
+        state_id: u8,
+        token: String,
+        language_server_builder_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state
+                .create_language_server(language_server_builder_id)
+                .await;
+
+            Ok(())
+        })
+    }
+
+    fn write_to_language_server(
+        &self,
+        state_id: u8,
+        token: String,
+        language_server_id: String,
+        data: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state
+                .write_to_language_server(language_server_id, data)
+                .await;
+
+            Ok(())
+        })
+    }
+
+    fn restart_extension(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::Edit, TokenScope::ExtensionAdmin],
+            )
+            .await?;
+
+            let state_handle = state.clone();
+            let state = state.lock().await;
+
+            state.restart_extension(&extension_id, state_handle).await
+        })
+    }
+
+    /// Returns the configurations of the Language Servers managed by the Core
+    fn get_language_server_configs(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<LanguageServerConfig>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            Ok(state.language_servers_manager.get_configs())
+        })
+    }
+
+    /// Launch a managed Language Server for a workspace
+    fn start_language_server(
+        &self,
+        state_id: u8,
+        token: String,
+        config_id: String,
+        root_uri: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_edit_access(states, state_id, token).await?;
+
+            State::start_language_server(state, &config_id, &root_uri).await
+        })
+    }
+
+    /// Stop a managed Language Server
+    fn stop_language_server(
+        &self,
+        state_id: u8,
+        token: String,
+        language_server_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.stop_language_server(&language_server_id).await
+        })
+    }
+
+    /// Restart a managed Language Server
+    fn restart_language_server(
+        &self,
+        state_id: u8,
+        token: String,
+        language_server_id: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_edit_access(states, state_id, token).await?;
+
+            State::restart_language_server(state, &language_server_id).await
+        })
+    }
+
+    /// Invite a guest to the State, only the owner can do this
+    fn create_invitation(
+        &self,
+        state_id: u8,
+        token: String,
+        access: InvitationAccess,
+        duration_secs: u64,
+    ) -> BoxFuture<RPCResult<Result<Invitation, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_as_owner(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            Ok(state.create_invitation(access, Duration::from_secs(duration_secs)))
+        })
+    }
+
+    /// Revoke the access of a guest, only the owner can do this
+    fn revoke_invitation(
+        &self,
+        state_id: u8,
+        token: String,
+        invitation_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_as_owner(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.revoke_invitation(&invitation_id).await
+        })
+    }
+
+    /// Returns the invitations still valid, only the owner can do this
+    fn get_invitations(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Invitation>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_as_owner(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            Ok(state.get_invitations())
+        })
+    }
+
+    /// Returns the tree views contributed by the extensions
+    fn get_tree_views(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<TreeViewInfo>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            Ok(state.tree_views.get_views())
+        })
+    }
+
+    /// Returns the children of an item of a tree view, or the root items if there is no parent
+    fn get_tree_view_children(
+        &self,
+        state_id: u8,
+        token: String,
+        view_id: String,
+        parent_id: Option<String>,
+    ) -> BoxFuture<RPCResult<Result<Vec<TreeItem>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token).await?;
+
+            // The State is not kept locked while the provider loads the items
+            let view = state.lock().await.tree_views.get_view(&view_id);
+
+            let view = view.ok_or(Errors::TreeViewNotFound)?;
+            Ok(view.get_children(parent_id).await)
+        })
+    }
+
+    /// Search across a filesystem, returns the ID of the search
+    fn search(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        query: String,
+        options: SearchOptions,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.search(&filesystem_name, &query, options)
+        })
+    }
+
+    /// Abort a running search
+    fn cancel_search(
+        &self,
+        state_id: u8,
+        token: String,
+        search_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.cancel_search(&search_id)
+        })
+    }
+
+    /// Keep the unsaved content of a file
+    fn save_draft(
+        &self,
+        state_id: u8,
+        token: String,
+        draft: Draft,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.save_draft(draft);
+            Ok(())
+        })
+    }
+
+    /// Forget the draft of a file
+    fn discard_draft(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.discard_draft(&filesystem_name, &path);
+            Ok(())
+        })
+    }
+
+    /// Returns the unsaved content of files
+    fn get_drafts(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Draft>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            Ok(state.drafts.clone())
+        })
+    }
+
+    /// Returns the branch and the changed files of a repository
+    fn get_repository_status(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<RepositoryStatus, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token).await?;
+
+            // Don't keep the State locked while the provider runs
+            let provider = state.lock().await.get_vcs_provider(&filesystem_name)?;
+            provider.get_status(&path).await
+        })
+    }
+
+    /// Returns the unified diff of a file in a repository
+    fn get_file_diff(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        file_path: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token).await?;
+
+            // Don't keep the State locked while the provider runs
+            let provider = state.lock().await.get_vcs_provider(&filesystem_name)?;
+            provider.get_diff(&path, &file_path).await
+        })
+    }
+
+    /// Notify the client when the status of a repository changes
+    fn watch_repository(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.watch_repository(&filesystem_name, &path).await
+        })
+    }
+
+    /// Stop watching a repository
+    fn unwatch_repository(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.unwatch_repository(&filesystem_name, &path);
+            Ok(())
+        })
+    }
+
+    /// Returns the permissions of an extension
+    fn get_extension_permissions(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<ExtensionPermissions, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.get_extension_permissions(&extension_id)
+        })
+    }
+
+    /// Allow an extension to do something, needs the extension admin scope
+    fn grant_extension_permission(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+        permission: Permission,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::ExtensionAdmin])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state.grant_extension_permission(&extension_id, permission)
+        })
+    }
+
+    /// Disallow an extension to do something, needs the extension admin scope
+    fn revoke_extension_permission(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+        permission: Permission,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::ExtensionAdmin])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state.revoke_extension_permission(&extension_id, &permission)
+        })
+    }
+
+    /// Returns the panics and restarts counters of an extension
+    fn get_extension_metrics(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<ExtensionMetrics, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.get_extension_metrics(&extension_id).await
+        })
+    }
+
+    /// Configure what to do when an extension panics
+    fn set_extension_panic_policy(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+        policy: PanicPolicy,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::Edit, TokenScope::ExtensionAdmin],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            state
+                .set_extension_panic_policy(&extension_id, policy)
+                .await
+        })
+    }
+
+    /// Load again an extension from the filesystem without restarting the Core
+    fn reload_extension(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::Edit, TokenScope::ExtensionAdmin],
+            )
+            .await?;
+
+            let state_handle = state.clone();
+            let mut state = state.lock().await;
+
+            state.reload_extension(&extension_id, state_handle).await
+        })
+    }
+
+    /// Reload an extension every time its files change
+    fn watch_extension(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::Edit, TokenScope::ExtensionAdmin],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            state.watch_extension(&extension_id).await
+        })
+    }
+
+    /// Stop reloading an extension when its files change
+    fn unwatch_extension(
+        &self,
+        state_id: u8,
+        token: String,
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::Edit, TokenScope::ExtensionAdmin],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            state.unwatch_extension(&extension_id);
+            Ok(())
+        })
+    }
+
+    /// Returns the networking configuration, only the owner can see it
+    fn get_http_settings(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<HttpSettings, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_as_owner(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            Ok(state.data.http_settings.clone())
+        })
+    }
+
+    /// Configure the proxy, certificates and offline mode, only the owner can do it
+    fn set_http_settings(
+        &self,
+        state_id: u8,
+        token: String,
+        settings: HttpSettings,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_as_owner(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.set_http_settings(settings).await
+        })
+    }
+
+    /// Rename a file or folder, the open tabs and language servers are updated
+    fn rename_path(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        from: String,
+        to: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state.rename_path(&filesystem_name, &from, &to).await
+        })
+    }
+
+    /// Give a token with limited access, only the owner can do this
+    fn issue_token(
+        &self,
+        state_id: u8,
+        token: String,
+        label: String,
+        scopes: Vec<TokenScope>,
+        duration_secs: Option<u64>,
+    ) -> BoxFuture<RPCResult<Result<ScopedToken, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_as_owner(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            Ok(state.issue_token(&label, scopes, duration_secs.map(Duration::from_secs)))
+        })
+    }
+
+    /// Revoke a scoped token, only the owner can do this
+    fn revoke_token(
+        &self,
+        state_id: u8,
+        token: String,
+        token_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_as_owner(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.revoke_token(&token_id).await
+        })
+    }
+
+    /// Returns the scoped tokens that are still valid, only the owner can see them
+    fn get_scoped_tokens(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<ScopedToken>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_as_owner(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            Ok(state.get_scoped_tokens())
+        })
+    }
+
+    /// Open a document for incremental editing
+    fn open_document(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<DocumentInfo, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            state.open_document(&filesystem_name, &path).await
+        })
+    }
+
+    /// Apply edits to an open document, returns the new version
+    fn edit_document(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        version: i32,
+        edits: Vec<DocumentEdit>,
+    ) -> BoxFuture<RPCResult<Result<i32, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state
+                .edit_document(&filesystem_name, &path, version, edits)
+                .await
+        })
+    }
+
+    /// Run the save hooks and write an open document to it's filesystem
+    fn save_document(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        options: Option<SaveOptions>,
+    ) -> BoxFuture<RPCResult<Result<DocumentInfo, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state
+                .save_document(&filesystem_name, &path, &options.unwrap_or_default())
+                .await
+        })
+    }
+
+    /// Stop tracking an open document
+    fn close_document(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            state.close_document(&filesystem_name, &path).await
+        })
+    }
+
+    /// Return the current content of an open document
+    fn get_document_content(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            state.get_document_content(&filesystem_name, &path)
+        })
+    }
+
+    /// Return the open documents
+    fn get_documents(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<DocumentInfo>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            Ok(state.documents.get_all())
+        })
+    }
+
+    /// Read a range of a file, so big files can be paged through
+    fn read_file_range(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        offset: u64,
+        length: usize,
+    ) -> BoxFuture<RPCResult<Result<FileChunk, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            state
+                .read_file_range(&filesystem_name, &path, offset, length)
+                .await
+        })
+    }
+
+    /// Append content at the end of a file
+    fn append_to_file(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        content: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            if let Some(filesystem) = state.get_fs_by_name(&filesystem_name) {
+                let filesystem = filesystem.lock().await;
+                let result = filesystem.append_file_by_path(&path, content.as_bytes());
+                result.await
+            } else {
+                Err(Errors::Fs(FilesystemErrors::FilesystemNotFound))
+            }
+        })
+    }
+
+    /// Find the occurrences of a symbol in a project, without a language server
+    fn preview_rename(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        old_name: String,
+        new_name: String,
+        options: SearchOptions,
+    ) -> BoxFuture<RPCResult<Result<RenamePreview, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            state
+                .preview_rename(&filesystem_name, &old_name, &new_name, options)
+                .await
+        })
+    }
+
+    /// Apply a previewed rename to all the files at once
+    fn apply_rename(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        preview: RenamePreview,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state.apply_rename(&filesystem_name, preview).await
+        })
+    }
+
+    /// Return the decorations of some paths, by path and extension
+    fn get_file_decorations(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        paths: Vec<String>,
+    ) -> BoxFuture<RPCResult<Result<PathsDecorations, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            Ok(state.decorations.get(&filesystem_name, &paths).await)
+        })
+    }
+
+    /// Move a file or folder to the trash
+    fn move_to_trash(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state.move_to_trash(&filesystem_name, &path).await
+        })
+    }
+
+    /// Move a file or folder, even across devices
+    fn move_path(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        from: String,
+        to: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state.move_path(&filesystem_name, &from, &to).await
+        })
+    }
+
+    /// Copy a folder in the background, returns the ID of the operation
+    fn copy_folder(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        from: String,
+        to: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state.copy_folder(&filesystem_name, &from, &to)
+        })
+    }
+
+    /// Abort a running file operation
+    fn cancel_file_operation(
+        &self,
+        state_id: u8,
+        token: String,
+        operation_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state.cancel_file_operation(&operation_id)
+        })
+    }
+
+    /// Hibernate the State right away, only the owner can do this
+    fn hibernate_state(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_as_owner(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.hibernate().await;
+            Ok(())
+        })
+    }
+
+    /// Save the current state data so it can be restored later
+    fn snapshot_state(
+        &self,
+        state_id: u8,
+        token: String,
+        label: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.snapshot(&label).await;
+            Ok(())
+        })
+    }
+
+    /// Restore the last snapshot of the state data
+    fn undo_state(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<bool, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.undo().await)
+        })
+    }
+
+    /// Restore the last undone snapshot of the state data
+    fn redo_state(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<bool, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.redo().await)
+        })
+    }
+
+    /// Snapshots of the state data that can be restored
+    fn get_state_snapshots(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<StateSnapshot>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.get_snapshots().to_vec())
+        })
+    }
+
+    /// Return the last log entries of the process
+    fn get_logs(
+        &self,
+        state_id: u8,
+        token: String,
+        limit: usize,
+    ) -> BoxFuture<RPCResult<Result<Vec<LogEntry>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_as_owner(states, state_id, token.clone())
+                .await?
+                .lock_owned()
+                .await;
+
+            let locale = state.get_client_locale(&token);
+            Logger::get()
+                .map(|logger| {
+                    logger
+                        .get_entries(limit)
+                        .into_iter()
+                        .map(|entry| entry.localized(&locale))
+                        .collect()
+                })
+                .map_err(Errors::Logging)
+        })
+    }
+
+    /// Return the modules with a specific log level
+    fn get_log_levels(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<BTreeMap<String, LogLevel>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            verify_state_as_owner(states, state_id, token).await?;
+
+            Logger::get()
+                .map(|logger| logger.get_levels())
+                .map_err(Errors::Logging)
+        })
+    }
+
+    /// Change the log level of a module while running, `None` restores the default level
+    fn set_log_level(
+        &self,
+        state_id: u8,
+        token: String,
+        target: String,
+        level: Option<LogLevel>,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            verify_state_as_owner(states, state_id, token).await?;
+
+            Logger::get()
+                .and_then(|logger| logger.set_level(&target, level))
+                .map_err(Errors::Logging)
+        })
+    }
+
+    /// Returns the content of a file, e.g `graviton://local/home/user/notes.md`
+    fn read_file_by_uri(
+        &self,
+        state_id: u8,
+        token: String,
+        uri: GravitonUri,
+    ) -> BoxFuture<RPCResult<Result<FileInfo, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            match state.get_fs_by_uri(&uri) {
+                Ok((filesystem, path)) => {
+                    let filesystem = filesystem.lock().await;
+                    let result = filesystem.read_file_by_path(path);
+                    let result = result.await;
+
+                    state.notify_extensions(ClientMessages::ReadFile(
+                        state_id,
+                        uri.get_filesystem().to_owned(),
+                        result.clone(),
+                    ));
+
+                    result
+                }
+                Err(err) => Err(err),
+            }
+        })
+    }
+
+    /// Writes new content to a file, e.g `graviton://local/home/user/notes.md`
+    fn write_file_by_uri(
+        &self,
+        state_id: u8,
+        token: String,
+        uri: GravitonUri,
+        content: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state
+                .write_file_and_notify(uri.get_filesystem(), uri.get_path(), content, None)
+                .await
+        })
+    }
+
+    /// Return the scheduled tasks with the result of their last run
+    fn get_scheduled_tasks(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<ScheduledTask>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.data.scheduled_tasks.clone())
+        })
+    }
+
+    /// Add a scheduled task, or replace the one with the same ID
+    fn set_scheduled_task(
+        &self,
+        state_id: u8,
+        token: String,
+        task: ScheduledTask,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_as_owner(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            // Tasks run any command, so only the owner can add them
+            state.set_scheduled_task(task).await;
+            Ok(())
+        })
+    }
+
+    /// Remove a scheduled task
+    fn remove_scheduled_task(
+        &self,
+        state_id: u8,
+        token: String,
+        task_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.remove_scheduled_task(&task_id).await
+        })
+    }
+
+    /// Enable or disable a scheduled task
+    fn enable_scheduled_task(
+        &self,
+        state_id: u8,
+        token: String,
+        task_id: String,
+        enabled: bool,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.enable_scheduled_task(&task_id, enabled).await
+        })
+    }
+
+    /// Get the tasks that can be run
+    fn get_tasks(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<RegisteredTask>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.get_tasks())
+        })
+    }
+
+    /// Add a task that can be run, or replace the one with the same ID
+    fn register_task(
+        &self,
+        state_id: u8,
+        token: String,
+        task_id: String,
+        definition: TaskDefinition,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_as_owner(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            // Tasks run any command, so only the owner can add them
+            state.register_task(&task_id, definition, None)
+        })
+    }
+
+    /// Remove a task
+    fn unregister_task(
+        &self,
+        state_id: u8,
+        token: String,
+        task_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.unregister_task(&task_id)
+        })
+    }
 
-/// JSON RPC manager
-pub struct RpcManager {
-    pub states: Arc<Mutex<StatesList>>,
-}
+    /// Run a task, its output is streamed with `TaskOutput` messages. Returns the ID of the run
+    fn run_task(
+        &self,
+        state_id: u8,
+        token: String,
+        task_id: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.run_task(&task_id)
+        })
+    }
 
-/// Implementation of all JSON RPC methods
-impl RpcMethods for RpcManager {
-    /// Return the state by the given ID if found
-    fn get_state_by_id(
+    /// Stop a running task
+    fn cancel_task(
         &self,
         state_id: u8,
         token: String,
-    ) -> BoxFuture<RPCResult<Result<Option<StateData>, Errors>>> {
+        run_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
         let states = self.states.clone();
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
-                if let Ok(state) = state {
-                    let state = state.lock().await;
-                    Ok(Some(state.data.clone()))
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.cancel_task(&run_id)
+        })
+    }
+
+    /// Get the tasks being run, or waiting to be run
+    fn get_running_tasks(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<TaskRunInfo>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.get_running_tasks())
+        })
+    }
+
+    /// Get the custom message types handled by the extensions, and the extension handling each one
+    fn get_message_handlers(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<HashMap<String, String>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.get_message_handlers())
+        })
+    }
+
+    /// Open a folder as a workspace, returns its configuration merged with the global settings
+    fn open_workspace(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        root: String,
+    ) -> BoxFuture<RPCResult<Result<WorkspaceConfig, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.open_workspace(&filesystem_name, &root).await
+        })
+    }
+
+    /// Close a workspace
+    fn close_workspace(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        root: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.close_workspace(&filesystem_name, &root)
+        })
+    }
+
+    /// Register the tasks of a workspace's configuration, only the owner can do this
+    fn trust_workspace(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        root: String,
+    ) -> BoxFuture<RPCResult<Result<WorkspaceConfig, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_as_owner(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.trust_workspace(&filesystem_name, &root).await
+        })
+    }
+
+    /// Get the opened workspaces
+    fn get_workspaces(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Workspace>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.get_workspaces())
+        })
+    }
+
+    /// Get the configuration of a workspace merged with the global settings
+    fn get_workspace_config(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        root: String,
+    ) -> BoxFuture<RPCResult<Result<WorkspaceConfig, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.get_workspace_config(&filesystem_name, &root)
+        })
+    }
+
+    /// Get the user settings and keybindings, including the default values
+    fn get_settings(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<UserSettings, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.get_settings())
+        })
+    }
+
+    /// Get the settings contributed by the extensions
+    fn get_settings_schemas(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<SettingSchema>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.get_settings_schemas())
+        })
+    }
+
+    /// Modify several user settings at once
+    fn set_settings(
+        &self,
+        state_id: u8,
+        token: String,
+        values: BTreeMap<String, Value>,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.set_settings(values).await
+        })
+    }
+
+    /// Go back to the default value of a setting
+    fn reset_setting(
+        &self,
+        state_id: u8,
+        token: String,
+        key: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.reset_setting(&key).await
+        })
+    }
+
+    /// Replace the user keybindings
+    fn set_keybindings(
+        &self,
+        state_id: u8,
+        token: String,
+        keybindings: Vec<Keybinding>,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.set_keybindings(keybindings).await;
+            Ok(())
+        })
+    }
+
+    /// Get the saved layouts
+    fn get_layouts(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Layout>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.data.layouts.clone())
+        })
+    }
+
+    /// Save the current views as a layout
+    fn save_layout(
+        &self,
+        state_id: u8,
+        token: String,
+        name: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.save_layout(&name).await;
+            Ok(())
+        })
+    }
+
+    /// Switch to a saved layout
+    fn apply_layout(
+        &self,
+        state_id: u8,
+        token: String,
+        name: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.apply_layout(&name).await
+        })
+    }
+
+    /// Remove a saved layout
+    fn remove_layout(
+        &self,
+        state_id: u8,
+        token: String,
+        name: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.remove_layout(&name).await
+        })
+    }
+
+    /// Get the commands registered by the extensions, e.g for the command palette
+    fn get_commands(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<CommandInfo>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.get_commands())
+        })
+    }
+
+    /// Run a command registered by an extension, its result is sent with a `CommandFinished` message. Returns the ID of the invocation
+    fn invoke_command(
+        &self,
+        state_id: u8,
+        token: String,
+        command_id: String,
+        arguments: Value,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.invoke_command(&command_id, arguments)
+        })
+    }
+
+    /// Get the running long operations of the Core and the extensions
+    fn get_progress(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<ProgressInfo>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.get_progress())
+        })
+    }
+
+    /// Ask the owner of a long operation to stop it, if it's cancellable
+    fn cancel_progress(
+        &self,
+        state_id: u8,
+        token: String,
+        progress_id: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.cancel_progress(&progress_id)
+        })
+    }
+
+    /// Commands matching a query, best first
+    fn search_commands(
+        &self,
+        state_id: u8,
+        token: String,
+        query: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Ranked<CommandInfo>>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.search_commands(&query))
+        })
+    }
+
+    /// Rank some candidates with the user's matcher weights
+    fn fuzzy_match(
+        &self,
+        state_id: u8,
+        token: String,
+        query: String,
+        candidates: Vec<String>,
+        kind: MatchKind,
+    ) -> BoxFuture<RPCResult<Result<Vec<Ranked<String>>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.fuzzy_match(&query, candidates, kind))
+        })
+    }
+
+    /// Files of the opened workspaces matching a query, or their symbols if it starts with `#`
+    fn quick_open(
+        &self,
+        state_id: u8,
+        token: String,
+        query: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Ranked<QuickOpenItem>>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.quick_open(&query))
+        })
+    }
+
+    /// Read an open document again with another encoding
+    fn reopen_document_with_encoding(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        encoding: FileEncoding,
+    ) -> BoxFuture<RPCResult<Result<DocumentInfo, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state
+                .reopen_document_with_encoding(&filesystem_name, &path, encoding)
+                .await
+        })
+    }
+
+    /// Change the encoding an open document is saved with
+    fn set_document_encoding(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+        encoding: FileEncoding,
+    ) -> BoxFuture<RPCResult<Result<DocumentInfo, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
+
+            state.set_document_encoding(&filesystem_name, &path, encoding)
+        })
+    }
+
+    /// Browse an archive as a read-only filesystem, returns its name
+    fn open_archive(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            state.open_archive(&filesystem_name, &path).await
+        })
+    }
+
+    /// Stop browsing an archive
+    fn close_archive(
+        &self,
+        state_id: u8,
+        token: String,
+        name: String,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
+
+            state.close_archive(&name)
+        })
+    }
+
+    /// Return what was recovered from a crashed session, if anything
+    fn get_recovered_session(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Option<RecoveredSession>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            Ok(state.get_recovered_session())
+        })
+    }
+
+    /// Apply the data and reopen the drafts of a crashed session
+    fn restore_recovered_session(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<DocumentInfo>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+
+            state.restore_recovered_session().await
         })
     }
 
-    /// Update an state
-    fn set_state_by_id(
+    /// Forget everything recovered from a crashed session
+    fn discard_recovered_session(
         &self,
         state_id: u8,
-        new_state_data: StateData,
         token: String,
     ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
         let states = self.states.clone();
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
 
-                if let Ok(state) = state {
-                    let mut state = state.lock().await;
+            state.discard_recovered_session();
+            Ok(())
+        })
+    }
 
-                    tracing::info!("Updated state by id <{}>", state.data.id);
-                    state.update(new_state_data).await;
+    /// Recently closed tabs, the last closed goes last
+    fn get_closed_tabs(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<ClosedTab>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
 
-                    Ok(())
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+            Ok(state.get_closed_tabs())
         })
     }
 
-    /// Returns the content of a file
-    /// Internally implemented by the given filesystem
-    fn read_file_by_path(
+    /// Put a closed tab back where it was, by its position in the closed tabs
+    fn reopen_closed_tab(
         &self,
-        path: String,
-        filesystem_name: String,
         state_id: u8,
         token: String,
-    ) -> BoxFuture<RPCResult<Result<FileInfo, Errors>>> {
+        index: usize,
+    ) -> BoxFuture<RPCResult<Result<TabData, Errors>>> {
         let states = self.states.clone();
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
-
-                if let Ok(state) = state {
-                    let state = state.lock().await;
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
 
-                    if let Some(filesystem) = state.get_fs_by_name(&filesystem_name) {
-                        let filesystem = filesystem.lock().await;
-                        let result = filesystem.read_file_by_path(&path);
-                        let result = result.await;
+            state.reopen_closed_tab(Some(index)).await
+        })
+    }
 
-                        state.notify_extensions(ClientMessages::ReadFile(
-                            state_id,
-                            filesystem_name,
-                            result.clone(),
-                        ));
+    /// Put the last closed tab back where it was
+    fn reopen_last_closed_tab(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<TabData, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
 
-                        result
-                    } else {
-                        Err(Errors::Fs(FilesystemErrors::FilesystemNotFound))
-                    }
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+            state.reopen_last_closed_tab().await
         })
     }
 
-    /// Writes new content to the specified path
-    fn write_file_by_path(
+    /// Use an icon theme in the directory listings
+    fn set_icon_theme(
         &self,
-        path: String,
-        content: String,
-        filesystem_name: String,
         state_id: u8,
         token: String,
+        icon_theme: Option<IconTheme>,
     ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
         let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
 
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
+            state.set_icon_theme(icon_theme);
+            Ok(())
+        })
+    }
 
-                if let Ok(state) = state {
-                    let state = state.lock().await;
+    /// Icon of a file or folder in the active icon theme
+    fn get_file_icon(
+        &self,
+        state_id: u8,
+        token: String,
+        name: String,
+        is_file: bool,
+    ) -> BoxFuture<RPCResult<Result<Option<FileIcon>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
 
-                    if let Some(filesystem) = state.get_fs_by_name(&filesystem_name) {
-                        let filesystem = filesystem.lock().await;
-                        let result = filesystem.write_file_by_path(&path, &content);
-                        let result = result.await;
+            Ok(state.get_file_icon(&name, is_file))
+        })
+    }
 
-                        state.notify_extensions(ClientMessages::WriteFile(
-                            state_id,
-                            filesystem_name,
-                            content,
-                            result.clone(),
-                        ));
+    /// Returns what the telemetry of the State has measured, none if it's not enabled
+    fn get_metrics(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Option<TelemetryReport>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
 
-                        result
-                    } else {
-                        Err(Errors::Fs(FilesystemErrors::FilesystemNotFound))
-                    }
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+            Ok(state.get_metrics())
         })
     }
 
-    /// Returns the list of items inside the given directory
-    /// Internally implemented by the given filesystem
-    fn list_dir_by_path(
+    /// Declare the locale and formats of the client using the token, for clients without a WebSocket handshake
+    fn set_client_locale(
         &self,
-        path: String,
-        filesystem_name: String,
         state_id: u8,
         token: String,
-    ) -> BoxFuture<RPCResult<Result<Vec<DirItemInfo>, Errors>>> {
+        locale: ClientLocale,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
         let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state(states, state_id, token.clone())
+                .await?
+                .lock_owned()
+                .await;
 
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
+            state.set_client_locale(&token, locale);
+            Ok(())
+        })
+    }
 
-                if let Ok(state) = state {
-                    let state = state.lock().await;
+    /// Returns what a filesystem supports, so the explorer can adapt to it
+    fn get_filesystem_capabilities(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+    ) -> BoxFuture<RPCResult<Result<FilesystemCapabilities, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
 
-                    if let Some(filesystem) = state.get_fs_by_name(&filesystem_name) {
-                        let filesystem = filesystem.lock().await;
-                        let result = filesystem.list_dir_by_path(&path);
-                        let result = result.await;
+            state.get_fs_capabilities(&filesystem_name).await
+        })
+    }
 
-                        state.notify_extensions(ClientMessages::ListDir(
-                            state_id,
-                            filesystem_name,
-                            path,
-                            result.clone(),
-                        ));
+    /// Apply edits to an open document by it's URI, returns the new version
+    fn apply_edits(
+        &self,
+        state_id: u8,
+        token: String,
+        document: GravitonUri,
+        version: i32,
+        edits: Vec<DocumentEdit>,
+    ) -> BoxFuture<RPCResult<Result<i32, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
 
-                        result
-                    } else {
-                        Err(Errors::Fs(FilesystemErrors::FilesystemNotFound))
-                    }
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+            state.apply_edits(&document, version, edits).await
         })
     }
 
-    /// Returns the information about a extension
-    fn get_ext_info_by_id(
+    /// Validate a configuration file with it's schema, e.g `Cargo.toml`
+    fn validate_file(
         &self,
-        extension_id: String,
         state_id: u8,
         token: String,
-    ) -> BoxFuture<RPCResult<Result<ManifestInfo, Errors>>> {
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<Diagnostic>, Errors>>> {
         let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
 
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
+            state.validate_file(&filesystem_name, &path).await
+        })
+    }
 
-                if let Ok(state) = state {
-                    let state = state.lock().await;
+    /// Validate configuration files with a schema, e.g one fetched from schemastore.org
+    fn register_validation_schema(
+        &self,
+        state_id: u8,
+        token: String,
+        schema: ValidationSchema,
+    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
 
-                    state.get_ext_info_by_id(&extension_id)
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+            state.register_validation_schema(schema);
+            Ok(())
         })
     }
-    /// Returns the list of extensions in the specified state
-    fn get_ext_list(
+
+    /// Schemas the configuration files are validated with
+    fn get_validation_schemas(
         &self,
         state_id: u8,
         token: String,
-    ) -> BoxFuture<RPCResult<Result<Vec<String>, Errors>>> {
+    ) -> BoxFuture<RPCResult<Result<Vec<ValidationSchema>, Errors>>> {
         let states = self.states.clone();
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
-
-                if let Ok(state) = state {
-                    let state = state.lock().await;
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
 
-                    Ok(state.get_ext_list())
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+            Ok(state.get_validation_schemas())
         })
     }
 
-    /// Returns the list of language servers builders registered in the specified state
-    fn get_all_language_server_builders(
+    /// Open a file with the custom editor claiming it, or as a text document
+    fn open_file(
         &self,
         state_id: u8,
         token: String,
-    ) -> BoxFuture<RPCResult<Result<Vec<LanguageServerBuilderInfo>, Errors>>> {
+        filesystem_name: String,
+        path: String,
+        editor_id: Option<String>,
+    ) -> BoxFuture<RPCResult<Result<OpenedFile, Errors>>> {
         let states = self.states.clone();
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
-
-                if let Ok(state) = state {
-                    let state = state.lock().await;
+        rpc(async move {
+            let mut state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
 
-                    Ok(state.get_all_language_server_builders().await)
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+            state
+                .open_file(&filesystem_name, &path, editor_id.as_deref())
+                .await
         })
     }
 
-    fn notify_extension(
+    /// Ask the custom editor of a file to save it
+    fn save_custom_document(
         &self,
         state_id: u8,
         token: String,
-        message: ClientMessages,
+        filesystem_name: String,
+        path: String,
     ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
         let states = self.states.clone();
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
-
-                if let Ok(state) = state {
-                    let state = state.lock().await;
-
-                    state.notify_extensions(message);
+        rpc(async move {
+            let state =
+                verify_state_with_scopes(states, state_id, token, &[TokenScope::Filesystem])
+                    .await?
+                    .lock_owned()
+                    .await;
 
-                    Ok(())
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+            state.save_custom_document(&filesystem_name, &path).await
         })
     }
 
-    fn write_to_terminal_shell(
+    /// Close a file opened with a custom editor
+    fn close_custom_document(
         &self,
         state_id: u8,
         token: String,
-        terminal_shell_id: String,
-        data: String,
+        filesystem_name: String,
+        path: String,
     ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
         let states = self.states.clone();
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
+        rpc(async move {
+            let mut state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
 
-                if let Ok(state) = state {
-                    let state = state.lock().await;
+            state.close_custom_document(&filesystem_name, &path)
+        })
+    }
 
-                    state.write_to_terminal_shell(terminal_shell_id, data).await;
+    /// Custom editors that can open a file, e.g for "Open with..."
+    fn get_custom_editors(
+        &self,
+        state_id: u8,
+        token: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<CustomEditor>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
 
-                    Ok(())
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+            Ok(state.get_custom_editors_for(&path))
         })
     }
 
-    fn create_terminal_shell(
+    /// Summarize what the State is holding, hibernated States are not awakened
+    fn get_state_resource_report(
         &self,
         state_id: u8,
         token: String,
-        terminal_shell_builder_id: String,
-        terminal_shell_id: String,
-    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+    ) -> BoxFuture<RPCResult<Result<ResourceReport, Errors>>> {
         let states = self.states.clone();
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
+        rpc(async move {
+            let state = states.lock().await.get_state_by_id(state_id);
+            let state = state.ok_or(Errors::StateNotFound)?.lock_owned().await;
 
-                if let Ok(state) = state {
-                    let mut state = state.lock().await;
+            if state.has_token(&token) {
+                Ok(state.get_resource_report())
+            } else {
+                Err(Errors::BadToken)
+            }
+        })
+    }
 
-                    state
-                        .create_terminal_shell(terminal_shell_builder_id, terminal_shell_id)
-                        .await;
+    /// Changes made to the State data after a revision, from the oldest to the newest
+    fn get_data_events(
+        &self,
+        state_id: u8,
+        token: String,
+        since_revision: u64,
+    ) -> BoxFuture<RPCResult<Result<Vec<DataEvent>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
 
-                    Ok(())
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+            Ok(state.get_data_events(since_revision))
         })
     }
 
-    fn close_terminal_shell(
+    /// Rebuild the State data as it was on a past revision, none if it's not logged anymore
+    fn get_data_at_revision(
         &self,
         state_id: u8,
         token: String,
-        terminal_shell_id: String,
-    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        revision: u64,
+    ) -> BoxFuture<RPCResult<Result<Option<StateData>, Errors>>> {
         let states = self.states.clone();
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
-
-                if let Ok(state) = state {
-                    let mut state = state.lock().await;
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
 
-                    state.close_terminal_shell(terminal_shell_id).await;
+            Ok(state.get_data_at(revision))
+        })
+    }
 
-                    Ok(())
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+    /// Copy a text from a client so it can be pasted remotely
+    fn copy_to_clipboard(
+        &self,
+        state_id: u8,
+        token: String,
+        text: String,
+    ) -> BoxFuture<RPCResult<Result<ClipboardEntry, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.copy_to_clipboard(text, ClipboardOrigin::Client).await
         })
     }
 
-    fn get_terminal_shell_builders(
+    /// What was last copied, e.g by a remote terminal
+    fn get_clipboard(
         &self,
         state_id: u8,
         token: String,
-    ) -> BoxFuture<RPCResult<Result<Vec<TerminalShellBuilderInfo>, Errors>>> {
+    ) -> BoxFuture<RPCResult<Result<Option<ClipboardEntry>, Errors>>> {
         let states = self.states.clone();
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
+        rpc(async move {
+            let state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.get_clipboard()
+        })
+    }
 
-                if let Ok(state) = state {
-                    let state = state.lock().await;
+    /// Returns the items of a directory ready to be rendered in the explorer, with their excludes,
+    /// nesting, icons, decorations and git status applied
+    fn explorer_list(
+        &self,
+        state_id: u8,
+        token: String,
+        filesystem_name: String,
+        path: String,
+    ) -> BoxFuture<RPCResult<Result<ExplorerListing, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::ReadOnly, TokenScope::Filesystem],
+            )
+            .await?
+            .lock_owned()
+            .await;
+            state.explorer_list(&filesystem_name, &path).await
+        })
+    }
 
-                    Ok(state.get_terminal_shell_builders().await)
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+    /// Returns the feature flags of the Core and the extensions and whether they are enabled
+    fn get_experiments(
+        &self,
+        state_id: u8,
+        token: String,
+    ) -> BoxFuture<RPCResult<Result<Vec<ExperimentStatus>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.get_experiments())
         })
     }
 
-    fn resize_terminal_shell(
+    /// Enable or disable a feature regardless of its rollout, `null` follows the rollout again
+    fn set_experiment_override(
         &self,
         state_id: u8,
         token: String,
-        terminal_shell_id: String,
-        cols: i32,
-        rows: i32,
+        flag_id: String,
+        enabled: Option<bool>,
     ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
         let states = self.states.clone();
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
-
-                if let Ok(state) = state {
-                    let mut state = state.lock().await;
-                    state
-                        .resize_terminal_shell(terminal_shell_id, cols, rows)
-                        .await;
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token)
+                .await?
+                .lock_owned()
+                .await;
+            state.set_experiment_override(&flag_id, enabled).await
+        })
+    }
 
-                    Ok(())
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+    /// Apply the next commands as a single undoable change, returns the ID of the group to end
+    fn begin_group(
+        &self,
+        state_id: u8,
+        token: String,
+        label: String,
+    ) -> BoxFuture<RPCResult<Result<String, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token.clone())
+                .await?
+                .lock_owned()
+                .await;
+            state.begin_group(&label, &token).await
         })
     }
 
-    fn create_language_server(
+    /// End a group of commands, what it changed is returned once the outermost group is ended
+    fn end_group(
         &self,
         state_id: u8,
         token: String,
-        language_server_builder_id: String,
-    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        group_id: String,
+    ) -> BoxFuture<RPCResult<Result<Option<CommandGroupInfo>, Errors>>> {
         let states = self.states.clone();
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
+        rpc(async move {
+            let mut state = verify_state_with_edit_access(states, state_id, token.clone())
+                .await?
+                .lock_owned()
+                .await;
+            state.end_group(&group_id, &token).await
+        })
+    }
 
-                if let Ok(state) = state {
-                    let mut state = state.lock().await;
+    /// Log in with the authentication provider of the server, returns a token with the configured scopes
+    fn authenticate(
+        &self,
+        state_id: u8,
+        credentials: Credentials,
+    ) -> BoxFuture<RPCResult<Result<ScopedToken, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let (authenticator, state) = {
+                let states = states.lock().await;
+                (states.get_authenticator(), states.get_state_by_id(state_id))
+            };
+            let authenticator = authenticator.ok_or(Errors::Auth(AuthErrors::NotConfigured))?;
+            let state = state.ok_or(Errors::StateNotFound)?;
 
-                    state
-                        .create_language_server(language_server_builder_id)
-                        .await;
+            // The provider might take a while, so no State is locked meanwhile
+            let identity = authenticator
+                .authenticate(&credentials)
+                .await
+                .map_err(Errors::Auth)?;
 
-                    Ok(())
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+            let mut state_g = state.lock().await;
+            touch_or_awake(&state, &mut state_g);
+            Ok(state_g.issue_token(
+                &format!("{}:{}", identity.provider, identity.username),
+                authenticator.scopes.clone(),
+                Some(authenticator.session_duration),
+            ))
         })
     }
 
-    fn write_to_language_server(
+    /// Where the users log in, only for OAuth providers
+    fn get_authorization_url(
+        &self,
+        redirect_uri: String,
+        csrf_state: String,
+    ) -> BoxFuture<RPCResult<Result<Option<String>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let authenticator = states.lock().await.get_authenticator();
+            let authenticator = authenticator.ok_or(Errors::Auth(AuthErrors::NotConfigured))?;
+
+            authenticator
+                .get_provider()
+                .get_authorization_url(&redirect_uri, &csrf_state)
+                .await
+                .map_err(Errors::Auth)
+        })
+    }
+
+    /// Run an extension in a throwaway State and report if it's compatible, before enabling it
+    fn verify_extension(
         &self,
         state_id: u8,
         token: String,
-        language_server_id: String,
-        data: String,
-    ) -> BoxFuture<RPCResult<Result<(), Errors>>> {
+        extension_id: String,
+    ) -> BoxFuture<RPCResult<Result<CompatibilityReport, Errors>>> {
         let states = self.states.clone();
-        Box::pin(async move {
-            Ok({
-                let state = verify_state(states, state_id, token).await;
+        rpc(async move {
+            let state = verify_state_with_scopes(
+                states,
+                state_id,
+                token,
+                &[TokenScope::Edit, TokenScope::ExtensionAdmin],
+            )
+            .await?;
 
-                if let Ok(state) = state {
-                    let mut state = state.lock().await;
+            let (source, host) = {
+                let state = state.lock().await;
+                (
+                    state.extensions_manager.sources.get(&extension_id).cloned(),
+                    state.extensions_manager.clone(),
+                )
+            };
 
-                    state
-                        .write_to_language_server(language_server_id, data)
-                        .await;
+            // The sandbox doesn't need the State, so it's not locked meanwhile
+            match source {
+                Some(source) => Ok(verify_extension(&source, &host).await),
+                None => Err(Errors::Ext(ExtensionErrors::NotReloadable)),
+            }
+        })
+    }
 
-                    Ok(())
-                } else {
-                    Err(state.unwrap_err())
-                }
-            })
+    /// Recent writes, saves and git status changes across the filesystems, oldest first
+    fn changes_feed(
+        &self,
+        state_id: u8,
+        token: String,
+        filter: ChangesFilter,
+    ) -> BoxFuture<RPCResult<Result<Vec<ChangeEntry>, Errors>>> {
+        let states = self.states.clone();
+        rpc(async move {
+            let state = verify_state_with_scopes(states, state_id, token, &[TokenScope::ReadOnly])
+                .await?
+                .lock_owned()
+                .await;
+            Ok(state.get_changes(&filter))
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use gveditor_core_api::extensions::manager::ExtensionsManager;
+    use gveditor_core_api::messaging::{ClientMessages, ServerMessages};
+    use gveditor_core_api::save_hooks::{SaveHook, SaveHookAction};
+    use gveditor_core_api::states::{
+        InvitationAccess, MemoryPersistor, ShutdownConfig, StateDataField, StateDataUpdate,
+        StatesList, TokenFlags,
+    };
+    use gveditor_core_api::{Errors, Mutex, State};
+    use tokio::sync::mpsc::Sender;
+
+    use super::{RpcManager, RpcMethods, Server};
+    use crate::handlers::TransportHandler;
+
+    /// Counts the messages it was asked to send
+    struct CountingHandler {
+        sent: Arc<std::sync::Mutex<usize>>,
+    }
+
+    #[async_trait]
+    impl TransportHandler for CountingHandler {
+        async fn run(&mut self, _: Arc<Mutex<StatesList>>, _: Sender<ClientMessages>) {}
+
+        async fn send(&self, _: ServerMessages) {
+            *self.sent.lock().unwrap() += 1;
+        }
+    }
+
+    #[tokio::test]
+    async fn followers_cant_message_extensions_or_language_servers() {
+        let state = State::new(
+            1,
+            ExtensionsManager::default(),
+            Box::new(MemoryPersistor::new()),
+        );
+        let states = StatesList::new()
+            .with_tokens(&[TokenFlags::All("owner_token".to_string())])
+            .with_state(state);
+        let follower = {
+            let state = states.get_state_by_id(1).unwrap();
+            let mut state = state.lock().await;
+            state
+                .create_invitation(InvitationAccess::Follow, Duration::from_secs(60))
+                .token
+        };
+        let manager = RpcManager {
+            states: Arc::new(Mutex::new(states)),
+        };
+
+        for token in [follower, "owner_token".to_string()] {
+            let expected = if token == "owner_token" {
+                Ok(())
+            } else {
+                Err(Errors::AccessDenied)
+            };
+
+            let notified = manager
+                .notify_extension(1, token.clone(), ClientMessages::Unload(1))
+                .await
+                .unwrap();
+            assert_eq!(notified, expected);
+
+            let written = manager
+                .write_to_language_server(1, token, "rust".to_string(), "{}".to_string())
+                .await
+                .unwrap();
+            assert_eq!(written, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn only_owners_change_save_hooks() {
+        let state = State::new(
+            1,
+            ExtensionsManager::default(),
+            Box::new(MemoryPersistor::new()),
+        );
+        let states = StatesList::new()
+            .with_tokens(&[TokenFlags::All("owner_token".to_string())])
+            .with_state(state);
+        let editor = {
+            let state = states.get_state_by_id(1).unwrap();
+            let mut state = state.lock().await;
+            state
+                .create_invitation(InvitationAccess::Edit, Duration::from_secs(60))
+                .token
+        };
+        let manager = RpcManager {
+            states: Arc::new(Mutex::new(states)),
+        };
+
+        let mut save_hooks = HashMap::new();
+        save_hooks.insert(
+            "rust".to_string(),
+            vec![SaveHook::new(
+                "format",
+                SaveHookAction::Format {
+                    command: "rustfmt".to_string(),
+                    args: Vec::new(),
+                },
+            )],
+        );
+        let update = StateDataUpdate {
+            save_hooks: Some(save_hooks),
+            ..StateDataUpdate::default()
+        };
+
+        let denied = manager
+            .set_state_by_id(1, update.clone(), editor)
+            .await
+            .unwrap();
+        assert_eq!(denied, Err(Errors::AccessDenied));
+
+        let merge = manager
+            .set_state_by_id(1, update, "owner_token".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(merge.changed, vec![StateDataField::SaveHooks]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn save_the_states_on_shutdown_signals() {
+        let mut state = State::new(
+            1,
+            ExtensionsManager::default(),
+            Box::new(MemoryPersistor::new()),
+        );
+        state.data.http_settings.offline = true;
+        let persistor = state.persistor.clone().unwrap();
+        let states = Arc::new(Mutex::new(StatesList::new().with_state(state)));
+
+        let sent = Arc::new(std::sync::Mutex::new(0));
+        let handler: Box<dyn TransportHandler + Send + Sync> =
+            Box::new(CountingHandler { sent: sent.clone() });
+
+        let shutdown = tokio::spawn(Server::shutdown_on_signal(
+            states,
+            Arc::new(Mutex::new(handler)),
+            ShutdownConfig::new(Duration::from_secs(1)),
+        ));
+
+        // Nothing is saved until the OS asks to exit
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!persistor.lock().await.load().http_settings.offline);
+
+        std::process::Command::new("kill")
+            .args(["-HUP", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+
+        let report = tokio::time::timeout(Duration::from_secs(10), shutdown)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(report.is_clean());
+        assert!(persistor.lock().await.load().http_settings.offline);
+        // Told the clients it's shutting down and when it's done
+        assert_eq!(*sent.lock().unwrap(), 2);
+    }
+}
@@ -0,0 +1,186 @@
+use gveditor_core_api::messaging::ServerMessages;
+use gveditor_core_api::states::{
+    PhaseReport, ShutdownConfig, ShutdownPhase, ShutdownReport, StatesList,
+};
+use gveditor_core_api::Mutex;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::handlers::TransportHandler;
+
+/// Stops the subsystems of all the States in dependency order, the documents are flushed
+/// before the language servers are stopped, the terminals are killed before the data is persisted,
+/// and the transports are closed once the clients have been sent `ServerMessages::ShutdownComplete`.
+///
+/// A phase that doesn't finish in time is skipped for that State, so a stuck language server
+/// can't keep the process alive.
+pub struct ShutdownCoordinator {
+    states: Arc<Mutex<StatesList>>,
+    handler: Arc<Mutex<Box<dyn TransportHandler + Send + Sync>>>,
+    config: ShutdownConfig,
+}
+
+impl ShutdownCoordinator {
+    pub fn new(
+        states: Arc<Mutex<StatesList>>,
+        handler: Arc<Mutex<Box<dyn TransportHandler + Send + Sync>>>,
+        config: ShutdownConfig,
+    ) -> Self {
+        Self {
+            states,
+            handler,
+            config,
+        }
+    }
+
+    /// Run every phase and tell the clients how it went
+    pub async fn run(&self) -> ShutdownReport {
+        let states = self.states.lock().await.get_states();
+
+        let mut state_ids = Vec::new();
+        for state in &states {
+            state_ids.push(state.lock().await.data.id);
+        }
+
+        for state_id in &state_ids {
+            self.send(ServerMessages::ShuttingDown {
+                state_id: *state_id,
+            })
+            .await;
+        }
+
+        let mut report = ShutdownReport::default();
+
+        for phase in ShutdownPhase::ALL {
+            let timeout = self.config.get_timeout(phase);
+            let started = Instant::now();
+            let mut timed_out_states = Vec::new();
+
+            if phase == ShutdownPhase::CloseTransports {
+                for state_id in &state_ids {
+                    self.send(ServerMessages::ShutdownComplete {
+                        state_id: *state_id,
+                        report: report.clone(),
+                    })
+                    .await;
+                }
+
+                let closed = tokio::time::timeout(timeout, async {
+                    self.handler.lock().await.close().await;
+                })
+                .await;
+                if closed.is_err() {
+                    warn!("Transports didn't close in {:?}", timeout);
+                    timed_out_states = state_ids.clone();
+                }
+            } else {
+                for (state, state_id) in states.iter().zip(&state_ids) {
+                    let ran = tokio::time::timeout(timeout, async {
+                        state.lock().await.run_shutdown_phase(phase).await;
+                    })
+                    .await;
+                    if ran.is_err() {
+                        warn!(
+                            "State by id <{}> didn't finish {:?} in {:?}, skipping it",
+                            state_id, phase, timeout
+                        );
+                        timed_out_states.push(*state_id);
+                    }
+                }
+            }
+
+            report.phases.push(PhaseReport {
+                phase,
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                timed_out_states,
+            });
+        }
+
+        info!("Shut down in {} phases", report.phases.len());
+
+        report
+    }
+
+    async fn send(&self, message: ServerMessages) {
+        self.handler.lock().await.send(message).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use gveditor_core_api::extensions::manager::ExtensionsManager;
+    use gveditor_core_api::messaging::{ClientMessages, ServerMessages};
+    use gveditor_core_api::state_persistors::memory::MemoryPersistor;
+    use gveditor_core_api::states::{ShutdownConfig, ShutdownPhase, StatesList};
+    use gveditor_core_api::{Mutex, State};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc::Sender;
+
+    use super::ShutdownCoordinator;
+    use crate::handlers::TransportHandler;
+
+    /// Records what it was asked to do
+    struct RecordingHandler {
+        events: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl TransportHandler for RecordingHandler {
+        async fn run(&mut self, _: Arc<Mutex<StatesList>>, _: Sender<ClientMessages>) {}
+
+        async fn send(&self, message: ServerMessages) {
+            let event = match message {
+                ServerMessages::ShuttingDown { .. } => "shutting_down",
+                ServerMessages::ShutdownComplete { .. } => "complete",
+                _ => return,
+            };
+            self.events.lock().unwrap().push(event.to_owned());
+        }
+
+        async fn close(&mut self) {
+            self.events.lock().unwrap().push("closed".to_owned());
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_in_order() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let handler: Box<dyn TransportHandler + Send + Sync> = Box::new(RecordingHandler {
+            events: events.clone(),
+        });
+        let state = State::new(
+            1,
+            ExtensionsManager::default(),
+            Box::new(MemoryPersistor::new()),
+        );
+        let states = Arc::new(Mutex::new(StatesList::new().with_state(state)));
+
+        let config = ShutdownConfig::new(Duration::from_secs(1))
+            .with_timeout(ShutdownPhase::CloseTransports, Duration::from_millis(500));
+        assert_eq!(
+            config.get_timeout(ShutdownPhase::CloseTransports),
+            Duration::from_millis(500)
+        );
+
+        let report = ShutdownCoordinator::new(states, Arc::new(Mutex::new(handler)), config)
+            .run()
+            .await;
+
+        assert!(report.is_clean());
+        assert_eq!(
+            report
+                .phases
+                .iter()
+                .map(|phase| phase.phase)
+                .collect::<Vec<ShutdownPhase>>(),
+            ShutdownPhase::ALL.to_vec()
+        );
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec!["shutting_down", "complete", "closed"]
+        );
+    }
+}
@@ -0,0 +1,69 @@
+/// Wait until the OS asks the process to exit, returns the name of the received signal
+///
+/// On Unix these are SIGTERM, SIGHUP (e.g the terminal was closed) and SIGINT,
+/// on Windows the Ctrl+C, Ctrl+Break and close console events.
+#[cfg(unix)]
+pub async fn wait_for_shutdown_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate()).expect("Unable to listen for SIGTERM");
+    let mut hangup = signal(SignalKind::hangup()).expect("Unable to listen for SIGHUP");
+    let mut interrupt = signal(SignalKind::interrupt()).expect("Unable to listen for SIGINT");
+
+    tokio::select! {
+        _ = terminate.recv() => "SIGTERM",
+        _ = hangup.recv() => "SIGHUP",
+        _ = interrupt.recv() => "SIGINT",
+    }
+}
+
+#[cfg(windows)]
+pub async fn wait_for_shutdown_signal() -> &'static str {
+    use tokio::signal::windows::{ctrl_break, ctrl_c, ctrl_close};
+
+    let mut ctrl_c = ctrl_c().expect("Unable to listen for Ctrl+C");
+    let mut ctrl_break = ctrl_break().expect("Unable to listen for Ctrl+Break");
+    let mut ctrl_close = ctrl_close().expect("Unable to listen for the console closing");
+
+    tokio::select! {
+        _ = ctrl_c.recv() => "Ctrl+C",
+        _ = ctrl_break.recv() => "Ctrl+Break",
+        _ = ctrl_close.recv() => "console closed",
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::process::Command;
+    use std::time::Duration;
+
+    use tokio::signal::unix::{signal, SignalKind};
+    use tokio::time::{sleep, timeout};
+
+    use super::wait_for_shutdown_signal;
+
+    #[tokio::test]
+    async fn receive_hangups() {
+        // Listening first makes sure the test process is never killed by the signal
+        let _hangup = signal(SignalKind::hangup()).unwrap();
+        let mut waiting = Box::pin(wait_for_shutdown_signal());
+
+        let received = timeout(Duration::from_secs(10), async {
+            // The signal is sent again in case the listeners were not ready yet
+            loop {
+                Command::new("kill")
+                    .args(["-HUP", &std::process::id().to_string()])
+                    .status()
+                    .unwrap();
+                tokio::select! {
+                    received = &mut waiting => break received,
+                    _ = sleep(Duration::from_millis(100)) => {}
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(received, "SIGHUP");
+    }
+}
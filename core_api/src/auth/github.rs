@@ -0,0 +1,242 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::{AuthErrors, AuthIdentity, AuthProvider, Credentials};
+
+const USER_AGENT: &str = "graviton";
+
+/// A GitHub OAuth app
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GithubConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    /// Logins allowed to log in
+    #[serde(default)]
+    pub allowed_users: Vec<String>,
+    /// Members of these organizations are allowed to log in, these or some users are required
+    #[serde(default)]
+    pub allowed_organizations: Vec<String>,
+}
+
+impl GithubConfig {
+    /// Without a list anyone with a GitHub account could log in
+    pub fn has_allow_list(&self) -> bool {
+        !self.allowed_users.is_empty() || !self.allowed_organizations.is_empty()
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct User {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct Organization {
+    login: String,
+}
+
+/// Users log in with their GitHub account, the code is exchanged by the server
+/// so the client secret never leaves it
+pub struct GithubProvider {
+    config: GithubConfig,
+    client: reqwest::Client,
+}
+
+fn failed(err: impl ToString) -> AuthErrors {
+    AuthErrors::ProviderFailed(err.to_string())
+}
+
+impl GithubProvider {
+    pub fn new(config: GithubConfig) -> Result<Self, AuthErrors> {
+        Ok(Self {
+            config,
+            client: reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .build()
+                .map_err(failed)?,
+        })
+    }
+
+    /// Check if the user's login is allowed
+    fn is_allowed_user(&self, login: &str) -> bool {
+        self.config
+            .allowed_users
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(login))
+    }
+
+    /// Check if any of the organizations of an user is allowed
+    fn is_allowed_organization(&self, organizations: &[String]) -> bool {
+        organizations.iter().any(|organization| {
+            self.config
+                .allowed_organizations
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(organization))
+        })
+    }
+
+    async fn get_organizations(&self, token: &str) -> Result<Vec<String>, AuthErrors> {
+        let organizations = self
+            .client
+            .get("https://api.github.com/user/orgs")
+            .bearer_auth(token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(failed)?
+            .json::<Vec<Organization>>()
+            .await
+            .map_err(failed)?;
+
+        Ok(organizations
+            .into_iter()
+            .map(|organization| organization.login)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl AuthProvider for GithubProvider {
+    fn get_name(&self) -> &str {
+        "github"
+    }
+
+    async fn authenticate(&self, credentials: &Credentials) -> Result<AuthIdentity, AuthErrors> {
+        let (code, redirect_uri) = match credentials {
+            Credentials::AuthorizationCode { code, redirect_uri } => (code, redirect_uri),
+            _ => return Err(AuthErrors::UnsupportedCredentials),
+        };
+
+        // GitHub answers invalid codes with a 200 and an `error` field
+        let token = self
+            .client
+            .post("https://github.com/login/oauth/access_token")
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&[
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", self.config.client_secret.as_str()),
+                ("code", code.as_str()),
+                ("redirect_uri", redirect_uri.as_str()),
+            ])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(failed)?
+            .json::<TokenResponse>()
+            .await
+            .map_err(failed)?
+            .access_token
+            .ok_or(AuthErrors::InvalidCredentials)?;
+
+        let user = self
+            .client
+            .get("https://api.github.com/user")
+            .bearer_auth(&token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(failed)?
+            .json::<User>()
+            .await
+            .map_err(failed)?;
+
+        let mut allowed = self.is_allowed_user(&user.login);
+        if !allowed && !self.config.allowed_organizations.is_empty() {
+            let organizations = self.get_organizations(&token).await?;
+            allowed = self.is_allowed_organization(&organizations);
+        }
+
+        if !allowed {
+            return Err(AuthErrors::NotAllowed);
+        }
+
+        Ok(AuthIdentity {
+            provider: self.get_name().to_owned(),
+            username: user.login,
+        })
+    }
+
+    async fn get_authorization_url(
+        &self,
+        redirect_uri: &str,
+        csrf_state: &str,
+    ) -> Result<Option<String>, AuthErrors> {
+        let url = reqwest::Url::parse_with_params(
+            "https://github.com/login/oauth/authorize",
+            &[
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", redirect_uri),
+                ("scope", "read:user read:org"),
+                ("state", csrf_state),
+            ],
+        )
+        .map_err(failed)?;
+        Ok(Some(url.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GithubConfig, GithubProvider};
+    use crate::auth::{AuthErrors, AuthProvider, Credentials};
+
+    fn get_provider() -> GithubProvider {
+        GithubProvider::new(GithubConfig {
+            client_id: "graviton".to_string(),
+            client_secret: "secret".to_string(),
+            allowed_users: vec!["octocat".to_string()],
+            allowed_organizations: vec!["graviton-app".to_string()],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn allow_users_and_organizations() {
+        let provider = get_provider();
+
+        assert!(provider.is_allowed_user("OctoCat"));
+        assert!(!provider.is_allowed_user("someone"));
+        assert!(provider
+            .is_allowed_organization(&["rust-lang".to_string(), "Graviton-App".to_string()]));
+        assert!(!provider.is_allowed_organization(&["rust-lang".to_string()]));
+        assert!(!provider.is_allowed_organization(&[]));
+    }
+
+    #[tokio::test]
+    async fn authorization_urls() {
+        let provider = get_provider();
+        let url = provider
+            .get_authorization_url("http://localhost:50010/auth", "csrf")
+            .await
+            .unwrap()
+            .unwrap();
+        let url = reqwest::Url::parse(&url).unwrap();
+
+        assert_eq!(url.host_str(), Some("github.com"));
+        assert_eq!(url.path(), "/login/oauth/authorize");
+        let query = url.query_pairs().into_owned().collect::<Vec<_>>();
+        for param in [
+            ("client_id", "graviton"),
+            ("redirect_uri", "http://localhost:50010/auth"),
+            ("state", "csrf"),
+        ] {
+            assert!(query.contains(&(param.0.to_string(), param.1.to_string())));
+        }
+        // The secret is only sent by the server when exchanging the code
+        assert!(!query.iter().any(|(_, value)| value == "secret"));
+
+        assert_eq!(
+            provider
+                .authenticate(&Credentials::Token {
+                    token: "secret".to_string(),
+                })
+                .await,
+            Err(AuthErrors::UnsupportedCredentials)
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::states::TokenScope;
+
+#[cfg(feature = "http")]
+mod github;
+#[cfg(feature = "http")]
+mod oidc;
+#[cfg(feature = "pam_auth")]
+mod pam;
+
+#[cfg(feature = "http")]
+pub use github::{GithubConfig, GithubProvider};
+#[cfg(feature = "http")]
+pub use oidc::{OidcConfig, OidcProvider};
+#[cfg(feature = "pam_auth")]
+pub use pam::PamProvider;
+
+/// Authentication errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum AuthErrors {
+    /// The server has no authentication provider
+    NotConfigured,
+    /// The provider was not compiled in, e.g `pam` without the `pam_auth` feature
+    ProviderNotAvailable(String),
+    /// The provider doesn't take this kind of credentials
+    UnsupportedCredentials,
+    InvalidCredentials,
+    /// The user is authenticated but not allowed to use this server
+    NotAllowed,
+    /// The identity provider couldn't be reached or answered something unexpected
+    ProviderFailed(String),
+    /// OAuth and PAM providers must be configured with who is allowed to log in
+    AllowListRequired,
+    /// Too many failed attempts were made recently, see [`MAX_FAILED_ATTEMPTS`]
+    TooManyAttempts,
+}
+
+/// Failed attempts allowed for the same user in [`FAILED_ATTEMPTS_WINDOW`]
+pub const MAX_FAILED_ATTEMPTS: usize = 5;
+
+pub const FAILED_ATTEMPTS_WINDOW: Duration = Duration::from_secs(60);
+
+/// What a client logs in with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Credentials {
+    Token {
+        token: String,
+    },
+    Password {
+        username: String,
+        password: String,
+    },
+    /// Code given by an OAuth provider to the redirect URI
+    AuthorizationCode {
+        code: String,
+        redirect_uri: String,
+    },
+}
+
+impl Credentials {
+    /// Who the credentials claim to be, the failed attempts are counted by it
+    fn get_subject(&self) -> String {
+        match self {
+            Credentials::Token { .. } => "token".to_owned(),
+            Credentials::Password { username, .. } => format!("password:{}", username),
+            Credentials::AuthorizationCode { .. } => "authorization_code".to_owned(),
+        }
+    }
+}
+
+/// Who logged in
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuthIdentity {
+    /// Name of the provider, e.g `github`
+    pub provider: String,
+    /// e.g the GitHub login or the email of an OIDC user
+    pub username: String,
+}
+
+/// Checks the credentials of the users against an identity system
+#[async_trait]
+pub trait AuthProvider {
+    /// Short name, e.g `github`
+    fn get_name(&self) -> &str;
+
+    /// Check the credentials, returns who they belong to
+    async fn authenticate(&self, credentials: &Credentials) -> Result<AuthIdentity, AuthErrors>;
+
+    /// Where the users are sent to log in, only for OAuth providers
+    ///
+    /// # Arguments
+    ///
+    /// * `redirect_uri`   - Where the provider sends the authorization code back
+    /// * `csrf_state`     - Random value the client checks when it's redirected back
+    ///
+    async fn get_authorization_url(
+        &self,
+        _redirect_uri: &str,
+        _csrf_state: &str,
+    ) -> Result<Option<String>, AuthErrors> {
+        Ok(None)
+    }
+}
+
+/// Users log in with one of a list of shared secrets
+pub struct StaticTokenProvider {
+    tokens: Vec<String>,
+}
+
+impl StaticTokenProvider {
+    pub fn new(tokens: Vec<String>) -> Self {
+        Self { tokens }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for StaticTokenProvider {
+    fn get_name(&self) -> &str {
+        "static_token"
+    }
+
+    async fn authenticate(&self, credentials: &Credentials) -> Result<AuthIdentity, AuthErrors> {
+        match credentials {
+            Credentials::Token { token } if self.tokens.contains(token) => Ok(AuthIdentity {
+                provider: self.get_name().to_owned(),
+                username: "token".to_owned(),
+            }),
+            Credentials::Token { .. } => Err(AuthErrors::InvalidCredentials),
+            _ => Err(AuthErrors::UnsupportedCredentials),
+        }
+    }
+}
+
+/// Which provider the server authenticates the users with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum AuthProviderConfig {
+    StaticToken {
+        tokens: Vec<String>,
+    },
+    /// Accounts of the host, checked with the given PAM service, e.g `login`
+    Pam {
+        service: String,
+        /// Users allowed to log in
+        #[serde(default)]
+        allowed_users: Vec<String>,
+        /// Members of these groups are allowed to log in, these or some users are required
+        #[serde(default)]
+        allowed_groups: Vec<String>,
+    },
+    #[cfg(feature = "http")]
+    Oidc(OidcConfig),
+    #[cfg(feature = "http")]
+    Github(GithubConfig),
+}
+
+fn default_scopes() -> Vec<TokenScope> {
+    vec![TokenScope::Edit]
+}
+
+fn default_session_secs() -> u64 {
+    12 * 60 * 60
+}
+
+/// How the remote server authenticates its users, e.g in the launch configuration file:
+///
+/// ```toml
+/// [auth]
+/// provider = "github"
+/// client_id = "..."
+/// client_secret = "..."
+/// allowed_organizations = ["graviton-code-editor"]
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuthConfig {
+    #[serde(flatten)]
+    pub provider: AuthProviderConfig,
+    /// Scopes of the tokens given to the authenticated users
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<TokenScope>,
+    /// How long the sessions last
+    #[serde(default = "default_session_secs")]
+    pub session_secs: u64,
+}
+
+impl AuthConfig {
+    pub fn build(&self) -> Result<Authenticator, AuthErrors> {
+        let provider: Arc<dyn AuthProvider + Send + Sync> = match &self.provider {
+            AuthProviderConfig::StaticToken { tokens } => {
+                Arc::new(StaticTokenProvider::new(tokens.clone()))
+            }
+            AuthProviderConfig::Pam {
+                allowed_users,
+                allowed_groups,
+                ..
+            } if allowed_users.is_empty() && allowed_groups.is_empty() => {
+                return Err(AuthErrors::AllowListRequired)
+            }
+            #[cfg(feature = "pam_auth")]
+            AuthProviderConfig::Pam {
+                service,
+                allowed_users,
+                allowed_groups,
+            } => Arc::new(PamProvider::new(service, allowed_users, allowed_groups)),
+            #[cfg(not(feature = "pam_auth"))]
+            AuthProviderConfig::Pam { .. } => {
+                return Err(AuthErrors::ProviderNotAvailable("pam".to_owned()))
+            }
+            #[cfg(feature = "http")]
+            AuthProviderConfig::Oidc(config) if !config.has_allow_list() => {
+                return Err(AuthErrors::AllowListRequired)
+            }
+            #[cfg(feature = "http")]
+            AuthProviderConfig::Oidc(config) => Arc::new(OidcProvider::new(config.clone())?),
+            #[cfg(feature = "http")]
+            AuthProviderConfig::Github(config) if !config.has_allow_list() => {
+                return Err(AuthErrors::AllowListRequired)
+            }
+            #[cfg(feature = "http")]
+            AuthProviderConfig::Github(config) => Arc::new(GithubProvider::new(config.clone())?),
+        };
+
+        Ok(Authenticator::new(
+            provider,
+            self.scopes.clone(),
+            Duration::from_secs(self.session_secs),
+        ))
+    }
+}
+
+/// The provider of the server and what the authenticated users are given
+#[derive(Clone)]
+pub struct Authenticator {
+    provider: Arc<dyn AuthProvider + Send + Sync>,
+    pub scopes: Vec<TokenScope>,
+    pub session_duration: Duration,
+    /// When the last attempts failed, by the subject of the credentials
+    failed_attempts: Arc<Mutex<HashMap<String, Vec<Instant>>>>,
+}
+
+impl Authenticator {
+    pub fn new(
+        provider: Arc<dyn AuthProvider + Send + Sync>,
+        scopes: Vec<TokenScope>,
+        session_duration: Duration,
+    ) -> Self {
+        Self {
+            provider,
+            scopes,
+            session_duration,
+            failed_attempts: Arc::default(),
+        }
+    }
+
+    pub fn get_provider(&self) -> Arc<dyn AuthProvider + Send + Sync> {
+        self.provider.clone()
+    }
+
+    /// Check the credentials with the provider, unless they failed too many times recently
+    pub async fn authenticate(
+        &self,
+        credentials: &Credentials,
+    ) -> Result<AuthIdentity, AuthErrors> {
+        let subject = credentials.get_subject();
+
+        {
+            let mut failed_attempts = self.failed_attempts.lock().unwrap();
+            failed_attempts.retain(|_, attempts| {
+                attempts.retain(|attempt| attempt.elapsed() < FAILED_ATTEMPTS_WINDOW);
+                !attempts.is_empty()
+            });
+            let attempts = failed_attempts.get(&subject).map(Vec::len).unwrap_or(0);
+            if attempts >= MAX_FAILED_ATTEMPTS {
+                return Err(AuthErrors::TooManyAttempts);
+            }
+        }
+
+        let identity = self.provider.authenticate(credentials).await;
+
+        if let Err(AuthErrors::InvalidCredentials | AuthErrors::NotAllowed) = identity {
+            self.failed_attempts
+                .lock()
+                .unwrap()
+                .entry(subject)
+                .or_default()
+                .push(Instant::now());
+        }
+
+        identity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuthConfig, AuthErrors, AuthProviderConfig, Credentials, MAX_FAILED_ATTEMPTS};
+    use crate::states::TokenScope;
+
+    #[tokio::test]
+    async fn static_tokens() {
+        let config: AuthConfig =
+            toml::from_str("provider = \"static_token\"\ntokens = [\"secret\"]").unwrap();
+        assert_eq!(
+            config.provider,
+            AuthProviderConfig::StaticToken {
+                tokens: vec!["secret".to_string()]
+            }
+        );
+        assert_eq!(config.scopes, vec![TokenScope::Edit]);
+
+        let provider = config.build().unwrap().get_provider();
+        let identity = provider
+            .authenticate(&Credentials::Token {
+                token: "secret".to_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(identity.provider, "static_token");
+        assert_eq!(
+            provider
+                .authenticate(&Credentials::Password {
+                    username: "root".to_string(),
+                    password: "secret".to_string(),
+                })
+                .await,
+            Err(AuthErrors::UnsupportedCredentials)
+        );
+    }
+
+    #[tokio::test]
+    async fn limit_failed_attempts() {
+        let config: AuthConfig =
+            toml::from_str("provider = \"static_token\"\ntokens = [\"secret\"]").unwrap();
+        let authenticator = config.build().unwrap();
+        let wrong = Credentials::Token {
+            token: "wrong".to_string(),
+        };
+        let right = Credentials::Token {
+            token: "secret".to_string(),
+        };
+
+        for _ in 0..MAX_FAILED_ATTEMPTS {
+            assert_eq!(
+                authenticator.authenticate(&wrong).await,
+                Err(AuthErrors::InvalidCredentials)
+            );
+        }
+
+        assert_eq!(
+            authenticator.authenticate(&right).await,
+            Err(AuthErrors::TooManyAttempts)
+        );
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn require_allow_lists() {
+        let config: AuthConfig =
+            toml::from_str("provider = \"github\"\nclient_id = \"id\"\nclient_secret = \"secret\"")
+                .unwrap();
+        assert!(matches!(config.build(), Err(AuthErrors::AllowListRequired)));
+
+        let config: AuthConfig = toml::from_str(
+            "provider = \"oidc\"\nissuer = \"https://accounts.example.com\"\nclient_id = \"id\"\nclient_secret = \"secret\"\nallowed_domains = [\"example.com\"]",
+        )
+        .unwrap();
+        assert!(config.build().is_ok());
+    }
+
+    #[test]
+    fn require_pam_allow_lists() {
+        let config: AuthConfig = toml::from_str("provider = \"pam\"\nservice = \"login\"").unwrap();
+        assert!(matches!(config.build(), Err(AuthErrors::AllowListRequired)));
+    }
+}
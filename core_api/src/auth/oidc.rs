@@ -0,0 +1,330 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+
+use super::{AuthErrors, AuthIdentity, AuthProvider, Credentials};
+
+/// An OpenID Connect provider, e.g Google, Okta or Keycloak
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OidcConfig {
+    /// e.g `https://accounts.google.com`, its endpoints are discovered from it
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Emails allowed to log in, these or some domains are required
+    #[serde(default)]
+    pub allowed_users: Vec<String>,
+    /// Email domains allowed to log in, e.g `example.com`
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+}
+
+impl OidcConfig {
+    /// Without a list anyone with an account in the provider could log in
+    pub fn has_allow_list(&self) -> bool {
+        !self.allowed_users.is_empty() || !self.allowed_domains.is_empty()
+    }
+}
+
+/// Endpoints of the `.well-known/openid-configuration` document
+#[derive(Deserialize, Debug, Clone)]
+struct Discovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct UserInfo {
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: Option<bool>,
+}
+
+/// Users log in with the authorization code flow, the code is exchanged by the server
+/// so the client secret never leaves it
+pub struct OidcProvider {
+    config: OidcConfig,
+    client: reqwest::Client,
+    discovery: OnceCell<Discovery>,
+}
+
+fn failed(err: impl ToString) -> AuthErrors {
+    AuthErrors::ProviderFailed(err.to_string())
+}
+
+impl OidcProvider {
+    pub fn new(config: OidcConfig) -> Result<Self, AuthErrors> {
+        Ok(Self {
+            config,
+            client: reqwest::Client::builder().build().map_err(failed)?,
+            discovery: OnceCell::new(),
+        })
+    }
+
+    async fn get_discovery(&self) -> Result<&Discovery, AuthErrors> {
+        self.discovery
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer.trim_end_matches('/')
+                );
+                self.client
+                    .get(url)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map_err(failed)?
+                    .json::<Discovery>()
+                    .await
+                    .map_err(failed)
+            })
+            .await
+    }
+
+    fn is_allowed(&self, email: &str) -> bool {
+        let (allowed_users, allowed_domains) =
+            (&self.config.allowed_users, &self.config.allowed_domains);
+        let domain = email.rsplit_once('@').map(|(_, domain)| domain);
+        allowed_users
+            .iter()
+            .any(|user| user.eq_ignore_ascii_case(email))
+            || domain.is_some_and(|domain| {
+                allowed_domains
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(domain))
+            })
+    }
+}
+
+#[async_trait]
+impl AuthProvider for OidcProvider {
+    fn get_name(&self) -> &str {
+        "oidc"
+    }
+
+    async fn authenticate(&self, credentials: &Credentials) -> Result<AuthIdentity, AuthErrors> {
+        let (code, redirect_uri) = match credentials {
+            Credentials::AuthorizationCode { code, redirect_uri } => (code, redirect_uri),
+            _ => return Err(AuthErrors::UnsupportedCredentials),
+        };
+        let discovery = self.get_discovery().await?;
+
+        // The token comes straight from the issuer over TLS, so it's trusted without checking the ID token
+        let token = self
+            .client
+            .post(&discovery.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code.as_str()),
+                ("redirect_uri", redirect_uri.as_str()),
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", self.config.client_secret.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(failed)?;
+        if !token.status().is_success() {
+            return Err(AuthErrors::InvalidCredentials);
+        }
+        let token = token.json::<TokenResponse>().await.map_err(failed)?;
+
+        let user = self
+            .client
+            .get(&discovery.userinfo_endpoint)
+            .bearer_auth(token.access_token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(failed)?
+            .json::<UserInfo>()
+            .await
+            .map_err(failed)?;
+
+        // Unverified emails can't be trusted to identify anyone, nor the ones not said to be verified
+        let username = match (user.email, user.email_verified) {
+            (Some(email), Some(true)) if self.is_allowed(&email) => email,
+            _ => return Err(AuthErrors::NotAllowed),
+        };
+
+        Ok(AuthIdentity {
+            provider: self.get_name().to_owned(),
+            username,
+        })
+    }
+
+    async fn get_authorization_url(
+        &self,
+        redirect_uri: &str,
+        csrf_state: &str,
+    ) -> Result<Option<String>, AuthErrors> {
+        let discovery = self.get_discovery().await?;
+        let url = reqwest::Url::parse_with_params(
+            &discovery.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("scope", "openid email"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", redirect_uri),
+                ("state", csrf_state),
+            ],
+        )
+        .map_err(failed)?;
+        Ok(Some(url.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::{OidcConfig, OidcProvider};
+    use crate::auth::{AuthErrors, AuthIdentity, AuthProvider, Credentials};
+
+    fn get_provider(issuer: &str) -> OidcProvider {
+        OidcProvider::new(OidcConfig {
+            issuer: issuer.to_string(),
+            client_id: "graviton".to_string(),
+            client_secret: "secret".to_string(),
+            allowed_users: vec!["guest@gmail.com".to_string()],
+            allowed_domains: vec!["example.com".to_string()],
+        })
+        .unwrap()
+    }
+
+    /// Answer the requests of the provider like an issuer would, returns its URL
+    fn serve_issuer() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let discovery = serde_json::json!({
+            "authorization_endpoint": format!("{}/authorize", issuer),
+            "token_endpoint": format!("{}/token", issuer),
+            "userinfo_endpoint": format!("{}/userinfo", issuer),
+        });
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line
+                    .split(' ')
+                    .nth(1)
+                    .unwrap_or_default()
+                    .to_owned();
+                let (mut authorization, mut content_length) = (String::new(), 0);
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    let header = header.trim_end();
+                    if header.is_empty() {
+                        break;
+                    }
+                    let (name, value) = header.split_once(": ").unwrap_or_default();
+                    match name.to_lowercase().as_str() {
+                        "authorization" => authorization = value.to_owned(),
+                        "content-length" => content_length = value.parse().unwrap(),
+                        _ => {}
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let body = String::from_utf8(body).unwrap();
+
+                let (status, response) = match path.as_str() {
+                    "/.well-known/openid-configuration" => ("200 OK", discovery.clone()),
+                    "/token" if body.contains("code=valid") => {
+                        ("200 OK", serde_json::json!({ "access_token": "verified" }))
+                    }
+                    "/token" if body.contains("code=unverified") => (
+                        "200 OK",
+                        serde_json::json!({ "access_token": "unverified" }),
+                    ),
+                    "/token" => ("400 Bad Request", serde_json::json!({})),
+                    "/userinfo" => (
+                        "200 OK",
+                        serde_json::json!({
+                            "email": "someone@example.com",
+                            "email_verified": authorization == "Bearer verified",
+                        }),
+                    ),
+                    _ => ("404 Not Found", serde_json::json!({})),
+                };
+                let response = response.to_string();
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    response.len(),
+                    response
+                )
+                .unwrap();
+            }
+        });
+
+        issuer
+    }
+
+    fn get_code(code: &str) -> Credentials {
+        Credentials::AuthorizationCode {
+            code: code.to_string(),
+            redirect_uri: "http://localhost:50010/auth".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn authenticate_with_issuer() {
+        let issuer = serve_issuer();
+        let provider = get_provider(&issuer);
+
+        let url = provider
+            .get_authorization_url("http://localhost:50010/auth", "csrf")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(url.starts_with(&format!("{}/authorize?response_type=code", issuer)));
+        assert!(url.contains("state=csrf"));
+
+        assert_eq!(
+            provider.authenticate(&get_code("valid")).await,
+            Ok(AuthIdentity {
+                provider: "oidc".to_string(),
+                username: "someone@example.com".to_string(),
+            })
+        );
+        assert_eq!(
+            provider.authenticate(&get_code("wrong")).await,
+            Err(AuthErrors::InvalidCredentials)
+        );
+        assert_eq!(
+            provider.authenticate(&get_code("unverified")).await,
+            Err(AuthErrors::NotAllowed)
+        );
+        assert_eq!(
+            provider
+                .authenticate(&Credentials::Token {
+                    token: "verified".to_string(),
+                })
+                .await,
+            Err(AuthErrors::UnsupportedCredentials)
+        );
+    }
+
+    #[test]
+    fn allow_users() {
+        let provider = get_provider("https://accounts.example.com");
+
+        assert!(provider.is_allowed("Someone@Example.com"));
+        assert!(provider.is_allowed("guest@gmail.com"));
+        assert!(!provider.is_allowed("someone@gmail.com"));
+    }
+}
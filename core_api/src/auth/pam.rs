@@ -0,0 +1,306 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+
+use async_trait::async_trait;
+
+use super::{AuthErrors, AuthIdentity, AuthProvider, Credentials};
+
+const PAM_SUCCESS: c_int = 0;
+const PAM_BUF_ERR: c_int = 5;
+const PAM_CONV_ERR: c_int = 19;
+const PAM_PROMPT_ECHO_OFF: c_int = 1;
+const PAM_PROMPT_ECHO_ON: c_int = 2;
+
+/// Size of the buffers given to `getpwnam_r` and `getgrnam_r`, the groups that don't fit are ignored
+const ENTRY_BUFFER_SIZE: usize = 64 * 1024;
+
+#[repr(C)]
+struct PamMessage {
+    msg_style: c_int,
+    msg: *const c_char,
+}
+
+#[repr(C)]
+struct PamResponse {
+    resp: *mut c_char,
+    resp_retcode: c_int,
+}
+
+#[repr(C)]
+struct PamConv {
+    conv: extern "C" fn(c_int, *mut *const PamMessage, *mut *mut PamResponse, *mut c_void) -> c_int,
+    appdata_ptr: *mut c_void,
+}
+
+#[link(name = "pam")]
+extern "C" {
+    fn pam_start(
+        service_name: *const c_char,
+        user: *const c_char,
+        pam_conversation: *const PamConv,
+        pamh: *mut *mut c_void,
+    ) -> c_int;
+    fn pam_authenticate(pamh: *mut c_void, flags: c_int) -> c_int;
+    fn pam_acct_mgmt(pamh: *mut c_void, flags: c_int) -> c_int;
+    fn pam_end(pamh: *mut c_void, pam_status: c_int) -> c_int;
+}
+
+/// Answers the prompts of the PAM modules with the credentials of the user
+extern "C" fn converse(
+    num_msg: c_int,
+    msg: *mut *const PamMessage,
+    resp: *mut *mut PamResponse,
+    appdata_ptr: *mut c_void,
+) -> c_int {
+    if num_msg <= 0 || msg.is_null() || resp.is_null() || appdata_ptr.is_null() {
+        return PAM_CONV_ERR;
+    }
+    let num_msg = num_msg as usize;
+    let (username, password) = unsafe { &*(appdata_ptr as *const (CString, CString)) };
+
+    // PAM frees the responses
+    let responses =
+        unsafe { libc::calloc(num_msg, std::mem::size_of::<PamResponse>()) } as *mut PamResponse;
+    if responses.is_null() {
+        return PAM_BUF_ERR;
+    }
+
+    for i in 0..num_msg {
+        let message = unsafe { &**msg.add(i) };
+        let answer = match message.msg_style {
+            PAM_PROMPT_ECHO_OFF => Some(password),
+            PAM_PROMPT_ECHO_ON => Some(username),
+            // Errors and information are not shown to anybody
+            _ => None,
+        };
+        if let Some(answer) = answer {
+            let answer = unsafe { libc::strdup(answer.as_ptr()) };
+            if answer.is_null() {
+                for j in 0..i {
+                    unsafe { libc::free((*responses.add(j)).resp as *mut c_void) };
+                }
+                unsafe { libc::free(responses as *mut c_void) };
+                return PAM_BUF_ERR;
+            }
+            unsafe { (*responses.add(i)).resp = answer };
+        }
+    }
+
+    unsafe { *resp = responses };
+    PAM_SUCCESS
+}
+
+/// Check the password of a user of the host, and that its account can be used, with a PAM service
+fn check_password(service: &str, username: &str, password: &str) -> Result<(), AuthErrors> {
+    let service = CString::new(service).map_err(|_| AuthErrors::InvalidCredentials)?;
+    let credentials = (
+        CString::new(username).map_err(|_| AuthErrors::InvalidCredentials)?,
+        CString::new(password).map_err(|_| AuthErrors::InvalidCredentials)?,
+    );
+    let conversation = PamConv {
+        conv: converse,
+        appdata_ptr: &credentials as *const (CString, CString) as *mut c_void,
+    };
+
+    let mut handle = ptr::null_mut();
+    let started = unsafe {
+        pam_start(
+            service.as_ptr(),
+            credentials.0.as_ptr(),
+            &conversation,
+            &mut handle,
+        )
+    };
+    if started != PAM_SUCCESS {
+        return Err(AuthErrors::ProviderFailed(format!(
+            "Could not start PAM, error: {}",
+            started
+        )));
+    }
+
+    let mut status = unsafe { pam_authenticate(handle, 0) };
+    if status == PAM_SUCCESS {
+        status = unsafe { pam_acct_mgmt(handle, 0) };
+    }
+    unsafe { pam_end(handle, status) };
+
+    if status == PAM_SUCCESS {
+        Ok(())
+    } else {
+        Err(AuthErrors::InvalidCredentials)
+    }
+}
+
+/// Primary group of a user of the host
+fn get_primary_group(username: &CStr) -> Option<libc::gid_t> {
+    let mut buffer = vec![0 as c_char; ENTRY_BUFFER_SIZE];
+    let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = ptr::null_mut();
+    let res = unsafe {
+        libc::getpwnam_r(
+            username.as_ptr(),
+            &mut entry,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if res != 0 || result.is_null() {
+        None
+    } else {
+        Some(entry.pw_gid)
+    }
+}
+
+/// Check if a user of the host is a member of a group, as its primary group or not
+fn is_group_member(username: &CStr, primary_group: Option<libc::gid_t>, group: &str) -> bool {
+    let group = match CString::new(group) {
+        Ok(group) => group,
+        Err(_) => return false,
+    };
+
+    let mut buffer = vec![0 as c_char; ENTRY_BUFFER_SIZE];
+    let mut entry: libc::group = unsafe { std::mem::zeroed() };
+    let mut result = ptr::null_mut();
+    let res = unsafe {
+        libc::getgrnam_r(
+            group.as_ptr(),
+            &mut entry,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if res != 0 || result.is_null() {
+        return false;
+    }
+
+    if primary_group == Some(entry.gr_gid) {
+        return true;
+    }
+
+    let mut member = entry.gr_mem;
+    while !member.is_null() && unsafe { !(*member).is_null() } {
+        if unsafe { CStr::from_ptr(*member) } == username {
+            return true;
+        }
+        member = unsafe { member.add(1) };
+    }
+    false
+}
+
+/// Users log in with their account in the host, checked by a PAM service
+pub struct PamProvider {
+    service: String,
+    /// Users allowed to log in
+    allowed_users: Vec<String>,
+    /// Members of these groups are allowed to log in
+    allowed_groups: Vec<String>,
+}
+
+impl PamProvider {
+    pub fn new(service: &str, allowed_users: &[String], allowed_groups: &[String]) -> Self {
+        Self {
+            service: service.to_owned(),
+            allowed_users: allowed_users.to_vec(),
+            allowed_groups: allowed_groups.to_vec(),
+        }
+    }
+}
+
+/// Check if a user of the host is allowed to log in, by its name or its groups
+fn is_allowed(username: &str, allowed_users: &[String], allowed_groups: &[String]) -> bool {
+    if allowed_users.iter().any(|allowed| allowed == username) {
+        return true;
+    }
+
+    let username = match CString::new(username) {
+        Ok(username) => username,
+        Err(_) => return false,
+    };
+    let primary_group = get_primary_group(&username);
+    allowed_groups
+        .iter()
+        .any(|group| is_group_member(&username, primary_group, group))
+}
+
+#[async_trait]
+impl AuthProvider for PamProvider {
+    fn get_name(&self) -> &str {
+        "pam"
+    }
+
+    async fn authenticate(&self, credentials: &Credentials) -> Result<AuthIdentity, AuthErrors> {
+        let (username, password) = match credentials {
+            Credentials::Password { username, password } => (username.clone(), password.clone()),
+            _ => return Err(AuthErrors::UnsupportedCredentials),
+        };
+        let service = self.service.clone();
+        let allowed_users = self.allowed_users.clone();
+        let allowed_groups = self.allowed_groups.clone();
+
+        // PAM modules and the lookups of the groups block, e.g to delay failed attempts
+        tokio::task::spawn_blocking(move || {
+            check_password(&service, &username, &password)?;
+
+            if !is_allowed(&username, &allowed_users, &allowed_groups) {
+                return Err(AuthErrors::NotAllowed);
+            }
+
+            Ok(AuthIdentity {
+                provider: "pam".to_owned(),
+                username,
+            })
+        })
+        .await
+        .map_err(|err| AuthErrors::ProviderFailed(err.to_string()))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_allowed, PamProvider};
+    use crate::auth::{AuthErrors, AuthProvider, Credentials};
+
+    #[test]
+    fn allowed_users_and_groups() {
+        let root = ["root".to_string()];
+        assert!(is_allowed("root", &root, &[]));
+        assert!(!is_allowed("nobody", &root, &[]));
+
+        // The primary group of root
+        let root_group = if cfg!(target_os = "macos") {
+            ["wheel".to_string()]
+        } else {
+            ["root".to_string()]
+        };
+        assert!(is_allowed("root", &[], &root_group));
+        assert!(!is_allowed("missing\0user", &[], &root_group));
+        assert!(!is_allowed("root", &[], &["missing-group".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_credentials() {
+        let provider = PamProvider::new("login", &["root".to_string()], &[]);
+
+        assert_eq!(
+            provider
+                .authenticate(&Credentials::Token {
+                    token: "secret".to_string(),
+                })
+                .await,
+            Err(AuthErrors::UnsupportedCredentials)
+        );
+        // Names that can't be given to PAM are rejected before reaching it
+        assert_eq!(
+            provider
+                .authenticate(&Credentials::Password {
+                    username: "root\0".to_string(),
+                    password: "secret".to_string(),
+                })
+                .await,
+            Err(AuthErrors::InvalidCredentials)
+        );
+    }
+}
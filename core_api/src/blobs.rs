@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+/// Identifies the content of a blob, it's the hex SHA-256 of its bytes
+pub type BlobId = String;
+
+/// Space used by the store
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct BlobsStats {
+    pub blobs: usize,
+    /// Bytes stored
+    pub size: usize,
+    /// Bytes that would be stored if every reference kept its own copy
+    pub referenced_size: usize,
+    /// References by owner, e.g `drafts`
+    pub references: HashMap<String, usize>,
+}
+
+/// What a garbage collection removed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct GarbageReport {
+    pub removed: usize,
+    pub freed_size: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct BlobEntry {
+    size: usize,
+    /// References by owner
+    refs: HashMap<String, usize>,
+    /// Content of the blobs of stores without a directory
+    #[serde(skip)]
+    content: Option<Arc<Vec<u8>>>,
+}
+
+/// Content-addressable store, so the same content is only stored once. It keeps the contents
+/// of the hot-exit drafts, and the data of the snapshots and checkpoints persisted by the
+/// [`crate::state_persistors::file::FilePersistor`], other subsystems can share it through
+/// [`crate::recovery::SessionRecovery::get_blob_store`].
+///
+/// Every subsystem references the blobs it uses with its own owner name, and blobs nobody references
+/// are removed by [`BlobStore::collect_garbage`], which runs when the store is opened and every time
+/// the drafts are saved.
+///
+/// The references are only written to disk by [`BlobStore::flush`] and [`BlobStore::collect_garbage`],
+/// so a batch of changes rewrites the index once
+#[derive(Clone, Default)]
+pub struct BlobStore {
+    /// Where the blobs and their references are kept, in memory if none
+    dir: Option<PathBuf>,
+    entries: Arc<Mutex<HashMap<BlobId, BlobEntry>>>,
+    /// The references changed since the index was last saved
+    unsaved: Arc<AtomicBool>,
+}
+
+/// Hash some content
+pub fn get_blob_id(content: &[u8]) -> BlobId {
+    hex::encode(Sha256::digest(content))
+}
+
+/// If it's an ID made by [`get_blob_id`], anything else could point outside the store
+fn is_valid_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+impl BlobStore {
+    /// A store kept in memory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open (or create) a store in a directory, the blobs left unreferenced by the previous session are removed
+    pub fn open(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        let entries = fs::read_to_string(dir.join("index.json"))
+            .ok()
+            .and_then(|content| serde_json::from_str::<HashMap<BlobId, BlobEntry>>(&content).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(id, _)| is_valid_id(id))
+            .collect();
+
+        let store = Self {
+            dir: Some(dir),
+            entries: Arc::new(Mutex::new(entries)),
+            unsaved: Arc::default(),
+        };
+        store.collect_garbage();
+
+        Ok(store)
+    }
+
+    fn get_blob_path(&self, id: &str) -> Option<PathBuf> {
+        if !is_valid_id(id) {
+            return None;
+        }
+        self.dir.as_ref().map(|dir| dir.join(&id[..2]).join(id))
+    }
+
+    /// Save the references
+    fn save_index(&self, entries: &HashMap<BlobId, BlobEntry>) {
+        self.unsaved.store(false, Ordering::SeqCst);
+        if let Some(dir) = &self.dir {
+            let content = serde_json::to_string(entries).unwrap();
+            if let Err(err) = fs::write(dir.join("index.json"), content) {
+                warn!("Could not save the blobs index, error: {}", err);
+            }
+        }
+    }
+
+    /// Store some content, if it's already stored only a reference is added
+    ///
+    /// # Arguments
+    ///
+    /// * `owner`     - Subsystem referencing it, e.g `drafts`
+    /// * `content`   - Bytes to store
+    ///
+    pub fn put(&self, owner: &str, content: &[u8]) -> io::Result<BlobId> {
+        let id = get_blob_id(content);
+        let mut entries = self.entries.lock().unwrap();
+
+        if !entries.contains_key(&id) {
+            let stored = match self.get_blob_path(&id) {
+                Some(path) => {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(path, content)?;
+                    None
+                }
+                None => Some(Arc::new(content.to_vec())),
+            };
+            entries.insert(
+                id.clone(),
+                BlobEntry {
+                    size: content.len(),
+                    refs: HashMap::new(),
+                    content: stored,
+                },
+            );
+        }
+
+        if let Some(entry) = entries.get_mut(&id) {
+            *entry.refs.entry(owner.to_owned()).or_default() += 1;
+        }
+        self.unsaved.store(true, Ordering::SeqCst);
+
+        Ok(id)
+    }
+
+    /// Store a value serialized as JSON
+    pub fn put_json<T: Serialize>(&self, owner: &str, value: &T) -> io::Result<BlobId> {
+        let content = serde_json::to_vec(value)?;
+        self.put(owner, &content)
+    }
+
+    /// Add a reference to a stored blob, returns false if it's not stored
+    pub fn retain(&self, owner: &str, id: &str) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get_mut(id) {
+            Some(entry) => {
+                *entry.refs.entry(owner.to_owned()).or_default() += 1;
+                self.unsaved.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a reference to a blob, it's kept until the next garbage collection
+    pub fn release(&self, owner: &str, id: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get_mut(id) {
+            if let Some(refs) = entry.refs.get_mut(owner) {
+                *refs = refs.saturating_sub(1);
+                if *refs == 0 {
+                    entry.refs.remove(owner);
+                }
+            }
+            self.unsaved.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Remove every reference of an owner, e.g before it references its current blobs again
+    pub fn release_owner(&self, owner: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        for entry in entries.values_mut() {
+            entry.refs.remove(owner);
+        }
+        self.unsaved.store(true, Ordering::SeqCst);
+    }
+
+    /// Save the references changed since the last time
+    pub fn flush(&self) {
+        let entries = self.entries.lock().unwrap();
+        if self.unsaved.load(Ordering::SeqCst) {
+            self.save_index(&entries);
+        }
+    }
+
+    /// Content of a blob
+    pub fn get(&self, id: &str) -> Option<Vec<u8>> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(id)?;
+
+        match (&entry.content, self.get_blob_path(id)) {
+            (Some(content), _) => Some(content.as_ref().clone()),
+            (None, Some(path)) => fs::read(path).ok(),
+            (None, None) => None,
+        }
+    }
+
+    /// Content of a blob, if it's valid UTF-8
+    pub fn get_text(&self, id: &str) -> Option<String> {
+        self.get(id)
+            .and_then(|content| String::from_utf8(content).ok())
+    }
+
+    /// Value of a blob stored with [`BlobStore::put_json`]
+    pub fn get_json<T: DeserializeOwned>(&self, id: &str) -> Option<T> {
+        self.get(id)
+            .and_then(|content| serde_json::from_slice(&content).ok())
+    }
+
+    /// Remove the files of blobs missing in the index, e.g written right before a crash
+    fn remove_unindexed(&self, entries: &HashMap<BlobId, BlobEntry>, report: &mut GarbageReport) {
+        let folders = match self.dir.as_ref().and_then(|dir| fs::read_dir(dir).ok()) {
+            Some(folders) => folders,
+            None => return,
+        };
+
+        for folder in folders.flatten() {
+            if !folder.path().is_dir() {
+                continue;
+            }
+            for blob in fs::read_dir(folder.path()).into_iter().flatten().flatten() {
+                let id = blob.file_name().to_string_lossy().to_string();
+                if entries.contains_key(&id) {
+                    continue;
+                }
+                let size = blob.metadata().map(|meta| meta.len()).unwrap_or_default();
+                if fs::remove_file(blob.path()).is_ok() {
+                    report.removed += 1;
+                    report.freed_size += size as usize;
+                }
+            }
+        }
+    }
+
+    /// Remove the blobs nobody references
+    pub fn collect_garbage(&self) -> GarbageReport {
+        let mut entries = self.entries.lock().unwrap();
+        let mut report = GarbageReport::default();
+
+        let unreferenced = entries
+            .iter()
+            .filter(|(_, entry)| entry.refs.is_empty())
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<BlobId>>();
+
+        for id in unreferenced {
+            if let Some(entry) = entries.remove(&id) {
+                if let Some(path) = self.get_blob_path(&id) {
+                    fs::remove_file(path).ok();
+                }
+                report.removed += 1;
+                report.freed_size += entry.size;
+            }
+        }
+
+        if report.removed > 0 || self.unsaved.load(Ordering::SeqCst) {
+            self.save_index(&entries);
+        }
+        self.remove_unindexed(&entries, &mut report);
+
+        report
+    }
+
+    pub fn get_stats(&self) -> BlobsStats {
+        let entries = self.entries.lock().unwrap();
+        let mut stats = BlobsStats::default();
+
+        for entry in entries.values() {
+            stats.blobs += 1;
+            stats.size += entry.size;
+            for (owner, refs) in &entry.refs {
+                stats.referenced_size += entry.size * refs;
+                *stats.references.entry(owner.clone()).or_default() += refs;
+            }
+        }
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlobStore;
+
+    #[test]
+    fn share_blobs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let store = BlobStore::open(dir.to_path_buf()).unwrap();
+
+        let draft = store.put("drafts", b"fn main() {}").unwrap();
+        let snapshot = store.put("snapshots", b"fn main() {}").unwrap();
+        store.put("drafts", b"old").unwrap();
+        assert_eq!(draft, snapshot);
+
+        let stats = store.get_stats();
+        assert_eq!(stats.blobs, 2);
+        assert_eq!(stats.size, 15);
+        assert_eq!(stats.referenced_size, 27);
+
+        // Still referenced by the snapshots
+        store.release_owner("drafts");
+        let report = store.collect_garbage();
+        assert_eq!(report.removed, 1);
+        assert_eq!(report.freed_size, 3);
+
+        // The references survive a restart
+        let store = BlobStore::open(dir.to_path_buf()).unwrap();
+        assert_eq!(store.get_text(&draft), Some("fn main() {}".to_string()));
+        store.release("snapshots", &draft);
+        assert_eq!(store.collect_garbage().removed, 1);
+        assert_eq!(store.get(&draft), None);
+
+        // Unreferenced and unindexed blobs are removed when it's opened again
+        let unreferenced = store.put("drafts", b"draft").unwrap();
+        store.release("drafts", &unreferenced);
+        let unindexed = dir.join("ab").join("ab".repeat(32));
+        std::fs::create_dir_all(unindexed.parent().unwrap()).unwrap();
+        std::fs::write(&unindexed, "lost").unwrap();
+        let store = BlobStore::open(dir.to_path_buf()).unwrap();
+        assert_eq!(store.get(&unreferenced), None);
+        assert!(!unindexed.exists());
+        assert_eq!(store.get_stats().blobs, 0);
+    }
+
+    #[test]
+    fn ignore_invalid_ids() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        std::fs::write(
+            dir.join("index.json"),
+            r#"{"a":{"size":1,"refs":{}},"../../secret":{"size":1,"refs":{"drafts":1}}}"#,
+        )
+        .unwrap();
+
+        let store = BlobStore::open(dir.to_path_buf()).unwrap();
+        assert_eq!(store.get_stats().blobs, 0);
+        assert_eq!(store.get("a"), None);
+        assert!(!store.retain("drafts", "../../secret"));
+
+        // The references are written once flushed
+        let id = store.put("drafts", b"draft").unwrap();
+        assert_eq!(BlobStore::open(dir.to_path_buf()).unwrap().get(&id), None);
+        let id = store.put("drafts", b"other draft").unwrap();
+        store.flush();
+        assert_eq!(
+            BlobStore::open(dir.to_path_buf()).unwrap().get_text(&id),
+            Some("other draft".to_string())
+        );
+    }
+}
@@ -0,0 +1,509 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use globset::GlobSet;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+
+use crate::filesystems::FilesystemErrors;
+use crate::search::{build_globset, relative_path};
+use crate::states::{now_secs, StateEvent};
+use crate::vcs::{FileStatus, RepositoryStatus};
+use crate::Errors;
+
+/// How many changes are kept, the oldest ones are forgotten
+pub const CHANGES_FEED_CAPACITY: usize = 2000;
+
+/// Seconds during which a watcher can notice a change that was also made through the State,
+/// it's only recorded once
+const WATCHED_CHANGES_DELAY: u64 = 2;
+
+/// Where a change was noticed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeSource {
+    /// Writes, renames and deletions made through the State, e.g by extensions or other clients
+    Filesystem,
+    /// The status of a watched repository
+    Git,
+    /// A document saved from an editor
+    Save,
+    /// Made outside of the editor, e.g by other programs, noticed by watching the workspaces
+    Watcher,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ChangeKind {
+    Written,
+    Renamed {
+        from: String,
+    },
+    Deleted,
+    Saved,
+    /// The git status of the file changed, it's None once it's clean again, e.g committed or reverted
+    Git {
+        status: Option<FileStatus>,
+    },
+}
+
+/// An entry of the feed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEntry {
+    /// Increases with every change, so clients can ask for what they missed
+    pub id: u64,
+    /// Seconds since the UNIX epoch
+    pub timestamp: u64,
+    pub filesystem: String,
+    pub path: String,
+    pub source: ChangeSource,
+    pub change: ChangeKind,
+}
+
+/// Which changes are returned, everything by default
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct ChangesFilter {
+    /// Names of the filesystems, all if there are none
+    pub filesystems: Vec<String>,
+    /// All the sources if there are none
+    pub sources: Vec<ChangeSource>,
+    /// Only the changes in this folder
+    pub path: Option<String>,
+    /// Only the changes after this one, e.g the last one the client saw
+    pub after: Option<u64>,
+    /// Only the changes since this time, in seconds since the UNIX epoch
+    pub since: Option<u64>,
+    /// Only the latest changes
+    pub limit: Option<usize>,
+}
+
+impl ChangesFilter {
+    pub fn matches(&self, entry: &ChangeEntry) -> bool {
+        let in_folder = |folder: &str| {
+            let folder = folder.trim_end_matches('/');
+            entry.path == folder || entry.path.starts_with(&format!("{}/", folder))
+        };
+
+        (self.filesystems.is_empty() || self.filesystems.contains(&entry.filesystem))
+            && (self.sources.is_empty() || self.sources.contains(&entry.source))
+            && self.path.as_deref().is_none_or(in_folder)
+            && self.after.is_none_or(|after| entry.id > after)
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+    }
+}
+
+#[derive(Debug, Default)]
+struct FeedEntries {
+    entries: VecDeque<ChangeEntry>,
+    next_id: u64,
+}
+
+/// Recent changes across every filesystem of a State, in chronological order.
+/// It's shared by the State and the watchers of its repositories and workspaces
+#[derive(Clone, Debug, Default)]
+pub struct ChangesFeed {
+    entries: Arc<Mutex<FeedEntries>>,
+}
+
+impl ChangesFeed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(
+        &self,
+        filesystem: &str,
+        path: &str,
+        source: ChangeSource,
+        change: ChangeKind,
+    ) -> ChangeEntry {
+        let mut feed = self.entries.lock().unwrap();
+
+        // The watcher noticed it before the State recorded it
+        if source != ChangeSource::Watcher {
+            let since = now_secs().saturating_sub(WATCHED_CHANGES_DELAY);
+            feed.entries.retain(|entry| {
+                entry.source != ChangeSource::Watcher
+                    || entry.timestamp < since
+                    || entry.filesystem != filesystem
+                    || entry.path != path
+            });
+        }
+
+        feed.next_id += 1;
+
+        let entry = ChangeEntry {
+            id: feed.next_id,
+            timestamp: now_secs(),
+            filesystem: filesystem.to_owned(),
+            path: path.to_owned(),
+            source,
+            change,
+        };
+        feed.entries.push_back(entry.clone());
+        if feed.entries.len() > CHANGES_FEED_CAPACITY {
+            feed.entries.pop_front();
+        }
+
+        entry
+    }
+
+    /// Record the filesystem changes of a State event, the other events are ignored
+    pub fn record_event(&self, event: &StateEvent) {
+        match event {
+            StateEvent::FileWritten { filesystem, path } => {
+                self.record(
+                    filesystem,
+                    path,
+                    ChangeSource::Filesystem,
+                    ChangeKind::Written,
+                );
+            }
+            StateEvent::PathRenamed {
+                filesystem,
+                from,
+                to,
+            } => {
+                self.record(
+                    filesystem,
+                    to,
+                    ChangeSource::Filesystem,
+                    ChangeKind::Renamed { from: from.clone() },
+                );
+            }
+            StateEvent::PathDeleted { filesystem, path } => {
+                self.record(
+                    filesystem,
+                    path,
+                    ChangeSource::Filesystem,
+                    ChangeKind::Deleted,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// A file was written because it was saved, so the write is shown as a save
+    pub fn record_save(&self, filesystem: &str, path: &str) {
+        {
+            let mut feed = self.entries.lock().unwrap();
+            let written = feed.entries.back_mut().filter(|entry| {
+                entry.filesystem == filesystem
+                    && entry.path == path
+                    && entry.change == ChangeKind::Written
+            });
+            if let Some(entry) = written {
+                entry.source = ChangeSource::Save;
+                entry.change = ChangeKind::Saved;
+                return;
+            }
+        }
+
+        self.record(filesystem, path, ChangeSource::Save, ChangeKind::Saved);
+    }
+
+    /// Record a change noticed by a watcher, unless it was recently recorded,
+    /// e.g it was made through the State or the watcher noticed it twice
+    pub fn record_watched(&self, filesystem: &str, path: &str, change: ChangeKind) {
+        {
+            let feed = self.entries.lock().unwrap();
+            let since = now_secs().saturating_sub(WATCHED_CHANGES_DELAY);
+            let recorded = feed
+                .entries
+                .iter()
+                .rev()
+                .take_while(|entry| entry.timestamp >= since)
+                .any(|entry| {
+                    entry.filesystem == filesystem
+                        && entry.path == path
+                        && (entry.source != ChangeSource::Watcher || entry.change == change)
+                });
+            if recorded {
+                return;
+            }
+        }
+
+        self.record(filesystem, path, ChangeSource::Watcher, change);
+    }
+
+    /// Record the changes made to a local folder outside of the editor, until the returned watcher is dropped
+    ///
+    /// # Arguments
+    ///
+    /// * `filesystem`   - Name of the filesystem the folder is shown in
+    /// * `root`         - The watched folder
+    /// * `excluded`     - Globs of the paths left out, relative to the folder
+    ///
+    pub fn watch_folder(
+        &self,
+        filesystem: &str,
+        root: &str,
+        excluded: &[String],
+    ) -> Result<RecommendedWatcher, Errors> {
+        let feed = self.clone();
+        let filesystem = filesystem.to_owned();
+        let folder = root.to_owned();
+        let excluded = build_globset(excluded)?;
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event {
+                for (path, change) in get_watched_changes(&event) {
+                    if is_watched(&folder, &excluded, &path) {
+                        feed.record_watched(&filesystem, &path, change);
+                    }
+                }
+            }
+        })
+        .map_err(|_| Errors::Fs(FilesystemErrors::FileNotFound))?;
+
+        watcher
+            .watch(Path::new(root), RecursiveMode::Recursive)
+            .map_err(|_| Errors::Fs(FilesystemErrors::FileNotFound))?;
+
+        Ok(watcher)
+    }
+
+    /// Record the files whose git status changed between two checks of a repository
+    ///
+    /// # Arguments
+    ///
+    /// * `filesystem`   - Filesystem of the repository
+    /// * `root`         - Watched folder, the paths of the status are relative to it
+    /// * `previous`     - Status of the previous check
+    /// * `current`      - Status of this check
+    ///
+    pub fn record_repository(
+        &self,
+        filesystem: &str,
+        root: &str,
+        previous: &RepositoryStatus,
+        current: &RepositoryStatus,
+    ) {
+        let get_path = |path: &str| format!("{}/{}", root.trim_end_matches('/'), path);
+        let previous = previous
+            .files
+            .iter()
+            .map(|file| (file.path.as_str(), &file.status))
+            .collect::<HashMap<&str, &FileStatus>>();
+
+        for file in &current.files {
+            if previous.get(file.path.as_str()) != Some(&&file.status) {
+                self.record(
+                    filesystem,
+                    &get_path(&file.path),
+                    ChangeSource::Git,
+                    ChangeKind::Git {
+                        status: Some(file.status.clone()),
+                    },
+                );
+            }
+        }
+
+        for path in previous.keys() {
+            if !current.files.iter().any(|file| file.path == *path) {
+                self.record(
+                    filesystem,
+                    &get_path(path),
+                    ChangeSource::Git,
+                    ChangeKind::Git { status: None },
+                );
+            }
+        }
+    }
+
+    /// Changes matching a filter, oldest first
+    pub fn get(&self, filter: &ChangesFilter) -> Vec<ChangeEntry> {
+        let feed = self.entries.lock().unwrap();
+        let mut entries = feed
+            .entries
+            .iter()
+            .filter(|entry| filter.matches(entry))
+            .cloned()
+            .collect::<Vec<ChangeEntry>>();
+
+        if let Some(limit) = filter.limit {
+            entries.drain(..entries.len().saturating_sub(limit));
+        }
+
+        entries
+    }
+}
+
+/// What a watcher event changed, the accesses and the metadata changes are left out
+fn get_watched_changes(event: &Event) -> Vec<(String, ChangeKind)> {
+    let paths = event
+        .paths
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect::<Vec<String>>();
+
+    let change = match &event.kind {
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            if let [from, to] = paths.as_slice() {
+                return vec![(to.clone(), ChangeKind::Renamed { from: from.clone() })];
+            }
+            return Vec::new();
+        }
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            ChangeKind::Deleted
+        }
+        EventKind::Create(_)
+        | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any) => {
+            ChangeKind::Written
+        }
+        _ => return Vec::new(),
+    };
+
+    paths
+        .into_iter()
+        .map(|path| (path, change.clone()))
+        .collect()
+}
+
+/// If a change in a watched folder belongs in the feed, it and its parent folders must not be excluded
+fn is_watched(root: &str, excluded: &GlobSet, path: &str) -> bool {
+    let relative = relative_path(root, path);
+    if relative.is_empty() || relative.split('/').any(|segment| segment == ".git") {
+        return false;
+    }
+
+    let ancestors_excluded = relative
+        .match_indices('/')
+        .any(|(end, _)| excluded.is_match(&relative[..end]));
+
+    !ancestors_excluded && !excluded.is_match(&relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use super::{ChangeKind, ChangeSource, ChangesFeed, ChangesFilter};
+    use crate::states::StateEvent;
+    use crate::vcs::{FileChange, FileStatus, RepositoryStatus};
+
+    #[test]
+    fn merge_changes() {
+        let feed = ChangesFeed::new();
+        feed.record_event(&StateEvent::FileWritten {
+            filesystem: "local".to_string(),
+            path: "/project/src/main.rs".to_string(),
+        });
+        feed.record_save("local", "/project/src/main.rs");
+        feed.record_event(&StateEvent::PathDeleted {
+            filesystem: "ftp".to_string(),
+            path: "/www/index.html".to_string(),
+        });
+
+        let status = |files: &[(&str, FileStatus)]| RepositoryStatus {
+            branch: Some("main".to_string()),
+            files: files
+                .iter()
+                .map(|(path, status)| FileChange {
+                    path: path.to_string(),
+                    status: status.clone(),
+                })
+                .collect(),
+        };
+        let previous = status(&[("README.md", FileStatus::Modified)]);
+        let current = status(&[("src/main.rs", FileStatus::Modified)]);
+        feed.record_repository("local", "/project/", &previous, &current);
+
+        let changes = feed.get(&ChangesFilter::default());
+        assert_eq!(changes.len(), 4);
+        // The write of the save is shown once
+        assert_eq!(changes[0].change, ChangeKind::Saved);
+        assert_eq!(changes[2].path, "/project/src/main.rs");
+        assert_eq!(changes[3].change, ChangeKind::Git { status: None });
+
+        let git = feed.get(&ChangesFilter {
+            sources: vec![ChangeSource::Git],
+            path: Some("/project".to_string()),
+            limit: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(git.len(), 1);
+        assert_eq!(git[0].path, "/project/README.md");
+
+        let missed = feed.get(&ChangesFilter {
+            after: Some(changes[1].id),
+            filesystems: vec!["local".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(missed.len(), 2);
+    }
+
+    #[test]
+    fn record_watched_changes_once() {
+        let feed = ChangesFeed::new();
+        let written = StateEvent::FileWritten {
+            filesystem: "local".to_string(),
+            path: "/project/main.rs".to_string(),
+        };
+
+        // Made through the State and then noticed by the watcher
+        feed.record_event(&written);
+        feed.record_watched("local", "/project/main.rs", ChangeKind::Written);
+
+        // Noticed by the watcher before the State recorded it
+        feed.record_watched("local", "/project/lib.rs", ChangeKind::Written);
+        feed.record_watched("local", "/project/lib.rs", ChangeKind::Written);
+        feed.record_event(&StateEvent::FileWritten {
+            filesystem: "local".to_string(),
+            path: "/project/lib.rs".to_string(),
+        });
+
+        feed.record_watched("local", "/project/README.md", ChangeKind::Deleted);
+
+        let changes = feed.get(&ChangesFilter::default());
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| (change.path.as_str(), change.source))
+                .collect::<Vec<_>>(),
+            vec![
+                ("/project/main.rs", ChangeSource::Filesystem),
+                ("/project/lib.rs", ChangeSource::Filesystem),
+                ("/project/README.md", ChangeSource::Watcher),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn watch_changes_outside_of_the_editor() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        fs::create_dir_all(dir.join("target")).unwrap();
+        let root = dir.to_str().unwrap();
+
+        let feed = ChangesFeed::new();
+        let watcher = feed
+            .watch_folder("local", root, &["target".to_string()])
+            .unwrap();
+
+        fs::write(dir.join("target").join("build.log"), "ignored").unwrap();
+        fs::write(dir.join("notes.md"), "edited outside").unwrap();
+
+        let path = dir.join("notes.md").to_string_lossy().into_owned();
+        let mut changes = Vec::new();
+        for _ in 0..50 {
+            changes = feed.get(&ChangesFilter {
+                sources: vec![ChangeSource::Watcher],
+                ..Default::default()
+            });
+            if !changes.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        assert!(!changes.is_empty());
+        assert!(changes.iter().all(|change| change.path == path));
+        assert_eq!(changes[0].change, ChangeKind::Written);
+
+        drop(watcher);
+    }
+}
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+
+use crate::states::now_secs;
+
+/// User setting to sync the clipboard between the clients and the Core, it's disabled by default
+pub const CLIPBOARD_SYNC_SETTING: &str = "clipboard.sync";
+
+/// User setting with the maximum size (bytes) of the synced text
+pub const CLIPBOARD_MAX_SIZE_SETTING: &str = "clipboard.maxSize";
+
+/// 1 MiB
+pub const DEFAULT_CLIPBOARD_MAX_SIZE: usize = 1024 * 1024;
+
+/// Clipboard errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardErrors {
+    /// The user didn't opt in to sync the clipboard
+    SyncDisabled,
+    TooLarge {
+        size: usize,
+        limit: usize,
+    },
+}
+
+/// Where the text was copied
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind")]
+pub enum ClipboardOrigin {
+    /// Copied locally by a client, so it can be pasted in the remote terminals and editors
+    Client,
+    /// A program in a remote terminal, with an OSC 52 escape sequence
+    Terminal {
+        terminal_shell_id: String,
+    },
+    Extension {
+        extension_id: String,
+    },
+}
+
+/// What was last copied
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardEntry {
+    pub text: String,
+    pub origin: ClipboardOrigin,
+    /// Unix timestamp (seconds) of when it was copied
+    pub copied_at: u64,
+}
+
+/// If and how much is synced
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClipboardSettings {
+    pub enabled: bool,
+    pub max_size: usize,
+}
+
+impl Default for ClipboardSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_size: DEFAULT_CLIPBOARD_MAX_SIZE,
+        }
+    }
+}
+
+/// Clipboard shared by the clients and the remote terminals and editors of a State
+#[derive(Clone, Debug, Default)]
+pub struct Clipboard {
+    entry: Option<ClipboardEntry>,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace what was copied
+    pub fn copy(
+        &mut self,
+        settings: &ClipboardSettings,
+        text: String,
+        origin: ClipboardOrigin,
+    ) -> Result<ClipboardEntry, ClipboardErrors> {
+        if !settings.enabled {
+            return Err(ClipboardErrors::SyncDisabled);
+        }
+        if text.len() > settings.max_size {
+            return Err(ClipboardErrors::TooLarge {
+                size: text.len(),
+                limit: settings.max_size,
+            });
+        }
+
+        let entry = ClipboardEntry {
+            text,
+            origin,
+            copied_at: now_secs(),
+        };
+        self.entry = Some(entry.clone());
+        Ok(entry)
+    }
+
+    /// What was last copied, to paste it
+    pub fn get(
+        &self,
+        settings: &ClipboardSettings,
+    ) -> Result<Option<ClipboardEntry>, ClipboardErrors> {
+        if settings.enabled {
+            Ok(self.entry.clone())
+        } else {
+            Err(ClipboardErrors::SyncDisabled)
+        }
+    }
+
+    /// Forget what was copied, e.g when the sync is disabled
+    pub fn clear(&mut self) {
+        self.entry = None;
+    }
+}
+
+/// Find the text copied by terminal programs (e.g tmux or vim) with the OSC 52 escape sequence,
+/// `ESC ] 52 ; <selection> ; <base64 text>` ended by `BEL` or `ESC \`.
+/// Queries (`?`) and invalid sequences are ignored
+pub fn extract_osc52(output: &str) -> Vec<String> {
+    let mut copied = Vec::new();
+    let mut rest = output;
+
+    while let Some(start) = rest.find("\x1b]52;") {
+        rest = &rest[start + 5..];
+
+        let end = match (rest.find('\x07'), rest.find("\x1b\\")) {
+            (Some(bel), Some(st)) => bel.min(st),
+            (Some(end), None) | (None, Some(end)) => end,
+            (None, None) => break,
+        };
+        let sequence = &rest[..end];
+        rest = &rest[end..];
+
+        let payload = match sequence.split_once(';') {
+            Some((_, payload)) if payload != "?" => payload,
+            _ => continue,
+        };
+        if let Some(text) = base64::decode(payload)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+        {
+            copied.push(text);
+        }
+    }
+
+    copied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract_osc52, Clipboard, ClipboardErrors, ClipboardOrigin, ClipboardSettings};
+
+    #[test]
+    fn sync_clipboard() {
+        let output = "vim\x1b]52;c;aGVsbG8=\x07 \x1b]52;c;?\x07\x1b]52;p;d29ybGQ=\x1b\\";
+        assert_eq!(
+            extract_osc52(output),
+            vec!["hello".to_string(), "world".to_string()]
+        );
+
+        let mut clipboard = Clipboard::new();
+        let mut settings = ClipboardSettings::default();
+
+        // It's opt-in
+        assert_eq!(
+            clipboard.copy(&settings, "hello".to_string(), ClipboardOrigin::Client),
+            Err(ClipboardErrors::SyncDisabled)
+        );
+
+        settings.enabled = true;
+        settings.max_size = 5;
+        assert_eq!(
+            clipboard.copy(&settings, "too long".to_string(), ClipboardOrigin::Client),
+            Err(ClipboardErrors::TooLarge { size: 8, limit: 5 })
+        );
+        clipboard
+            .copy(&settings, "hello".to_string(), ClipboardOrigin::Client)
+            .unwrap();
+        assert_eq!(clipboard.get(&settings).unwrap().unwrap().text, "hello");
+    }
+}
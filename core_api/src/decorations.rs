@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::Mutex;
+
+use crate::messaging::{ClientMessages, ServerMessages};
+
+/// How long the updates are batched before they are sent to the client
+const BATCH_DELAY: Duration = Duration::from_millis(100);
+
+/// How a file or folder is decorated in the explorer, e.g. by its git status
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct FileDecoration {
+    /// Short text next to the name, e.g `M`
+    pub badge: Option<String>,
+    /// Color of the name, e.g `#e2c08d`
+    pub color: Option<String>,
+    pub tooltip: Option<String>,
+}
+
+/// A decoration of a path published by an extension
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DecorationUpdate {
+    pub extension_id: String,
+    pub filesystem: String,
+    pub path: String,
+    /// `None` if the decoration was removed
+    pub decoration: Option<FileDecoration>,
+}
+
+type DecorationKey = (String, String);
+
+/// Decorations by path and extension
+pub type PathsDecorations = HashMap<String, HashMap<String, FileDecoration>>;
+
+#[derive(Default)]
+struct DecorationsData {
+    /// Decorations of every extension, by filesystem and path
+    decorations: HashMap<DecorationKey, HashMap<String, FileDecoration>>,
+    /// Updates not sent to the client yet
+    pending: Vec<DecorationUpdate>,
+    flush_scheduled: bool,
+}
+
+/// Decorations contributed by extensions, the updates are batched and pushed to the client
+#[derive(Clone, Default)]
+pub struct DecorationRegistry {
+    data: Arc<Mutex<DecorationsData>>,
+}
+
+impl DecorationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set (or remove) the decorations of an extension, returns true if a flush should be scheduled
+    pub async fn update(
+        &self,
+        extension_id: &str,
+        filesystem: &str,
+        decorations: Vec<(String, Option<FileDecoration>)>,
+    ) -> bool {
+        let mut data = self.data.lock().await;
+
+        for (path, decoration) in decorations {
+            let key = (filesystem.to_owned(), path.clone());
+            let changed = match &decoration {
+                Some(decoration) => {
+                    let entry = data.decorations.entry(key).or_default();
+                    entry.insert(extension_id.to_owned(), decoration.clone())
+                        != Some(decoration.clone())
+                }
+                None => {
+                    let removed = data
+                        .decorations
+                        .get_mut(&key)
+                        .and_then(|entry| entry.remove(extension_id))
+                        .is_some();
+                    if data.decorations.get(&key).is_some_and(HashMap::is_empty) {
+                        data.decorations.remove(&key);
+                    }
+                    removed
+                }
+            };
+
+            if changed {
+                // Only the latest update of a path matters
+                data.pending.retain(|update| {
+                    update.extension_id != extension_id
+                        || update.filesystem != filesystem
+                        || update.path != path
+                });
+                data.pending.push(DecorationUpdate {
+                    extension_id: extension_id.to_owned(),
+                    filesystem: filesystem.to_owned(),
+                    path,
+                    decoration,
+                });
+            }
+        }
+
+        let schedule = !data.pending.is_empty() && !data.flush_scheduled;
+        if schedule {
+            data.flush_scheduled = true;
+        }
+        schedule
+    }
+
+    /// Remove all the decorations of an extension, e.g. when it's unloaded
+    pub async fn clear_extension(&self, extension_id: &str) -> bool {
+        let decorations = {
+            let data = self.data.lock().await;
+            data.decorations
+                .iter()
+                .filter(|(_, entry)| entry.contains_key(extension_id))
+                .map(|((filesystem, path), _)| (filesystem.clone(), path.clone()))
+                .collect::<Vec<DecorationKey>>()
+        };
+
+        let mut schedule = false;
+        for (filesystem, path) in decorations {
+            schedule |= self
+                .update(extension_id, &filesystem, vec![(path, None)])
+                .await;
+        }
+        schedule
+    }
+
+    /// Return the decorations of the given paths, by path and extension
+    pub async fn get(&self, filesystem: &str, paths: &[String]) -> PathsDecorations {
+        let data = self.data.lock().await;
+        paths
+            .iter()
+            .filter_map(|path| {
+                let entry = data
+                    .decorations
+                    .get(&(filesystem.to_owned(), path.clone()))?;
+                Some((path.clone(), entry.clone()))
+            })
+            .collect()
+    }
+
+    /// Take the pending updates
+    pub async fn take_pending(&self) -> Vec<DecorationUpdate> {
+        let mut data = self.data.lock().await;
+        data.flush_scheduled = false;
+        std::mem::take(&mut data.pending)
+    }
+
+    /// Send the pending updates to the client after a short delay, so many updates go together
+    pub fn schedule_flush(&self, sender: Sender<ClientMessages>, state_id: u8) {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(BATCH_DELAY).await;
+
+            let updates = registry.take_pending().await;
+            if !updates.is_empty() {
+                sender
+                    .send(ClientMessages::ServerMessage(
+                        ServerMessages::DecorationsUpdated { state_id, updates },
+                    ))
+                    .await
+                    .ok();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecorationRegistry, FileDecoration};
+
+    fn badge(text: &str) -> Option<FileDecoration> {
+        Some(FileDecoration {
+            badge: Some(text.to_string()),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn batch_updates() {
+        let registry = DecorationRegistry::new();
+
+        assert!(
+            registry
+                .update("git", "local", vec![("/a".to_string(), badge("M"))])
+                .await
+        );
+        // A flush is already scheduled
+        assert!(
+            !registry
+                .update(
+                    "git",
+                    "local",
+                    vec![
+                        ("/a".to_string(), badge("A")),
+                        ("/b".to_string(), badge("U"))
+                    ]
+                )
+                .await
+        );
+        registry
+            .update("lint", "local", vec![("/a".to_string(), badge("2"))])
+            .await;
+
+        let updates = registry.take_pending().await;
+        assert_eq!(updates.len(), 3);
+        assert_eq!(updates[0].decoration, badge("A"));
+
+        let decorations = registry.get("local", &["/a".to_string()]).await;
+        assert_eq!(decorations["/a"].len(), 2);
+
+        // Same decoration again, nothing to send
+        assert!(
+            !registry
+                .update("git", "local", vec![("/b".to_string(), badge("U"))])
+                .await
+        );
+
+        assert!(registry.clear_extension("git").await);
+        let updates = registry.take_pending().await;
+        assert_eq!(updates.len(), 2);
+        assert!(updates.iter().all(|update| update.decoration.is_none()));
+        assert_eq!(
+            registry.get("local", &["/a".to_string()]).await["/a"].len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,493 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+
+use crate::filesystems::{
+    get_format_from_path, remap_path, FileEncoding, FileFormat, GravitonUri, LineEnding,
+};
+
+/// Documents errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum DocumentErrors {
+    DocumentNotFound,
+    InvalidRange,
+    /// The edits were made on top of another version of the document
+    VersionMismatch,
+}
+
+/// A position in a document, `character` is counted in characters (not bytes)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, character: usize) -> Self {
+        Self { line, character }
+    }
+}
+
+/// A range between two positions of a document
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextRange {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl TextRange {
+    pub fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+}
+
+/// Replace the text in a range, an empty range inserts and an empty text deletes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DocumentEdit {
+    pub range: TextRange,
+    pub text: String,
+}
+
+impl DocumentEdit {
+    pub fn insert(position: Position, text: &str) -> Self {
+        Self {
+            range: TextRange::new(position, position),
+            text: text.to_owned(),
+        }
+    }
+
+    pub fn delete(range: TextRange) -> Self {
+        Self {
+            range,
+            text: String::new(),
+        }
+    }
+
+    /// The smallest edit turning `content` into `new_content`, none if they are equal.
+    /// Only the changed span is sent, e.g to language servers, instead of the whole content
+    pub fn between(content: &str, new_content: &str) -> Option<Self> {
+        let old = content.chars().collect::<Vec<char>>();
+        let new = new_content.chars().collect::<Vec<char>>();
+
+        let mut prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+        if prefix == old.len() && prefix == new.len() {
+            return None;
+        }
+        // A position can't point between `\r` and `\n`
+        let splits_line_break = |index: usize| {
+            index > 0 && index < old.len() && old[index - 1] == '\r' && old[index] == '\n'
+        };
+        if splits_line_break(prefix) {
+            prefix -= 1;
+        }
+
+        let max_suffix = old.len().min(new.len()) - prefix;
+        let mut suffix = old
+            .iter()
+            .rev()
+            .zip(new.iter().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        if splits_line_break(old.len() - suffix) {
+            suffix -= 1;
+        }
+
+        let position_of = |index: usize| {
+            let line = old[..index].iter().filter(|c| **c == '\n').count();
+            let line_start = old[..index]
+                .iter()
+                .rposition(|c| *c == '\n')
+                .map(|i| i + 1)
+                .unwrap_or(0);
+            Position::new(line, index - line_start)
+        };
+
+        Some(Self {
+            range: TextRange::new(position_of(prefix), position_of(old.len() - suffix)),
+            text: new[prefix..new.len() - suffix].iter().collect(),
+        })
+    }
+}
+
+/// Information about an open document
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DocumentInfo {
+    pub filesystem: String,
+    pub path: String,
+    pub uri: GravitonUri,
+    pub version: i32,
+    pub is_dirty: bool,
+    /// Language of the document, e.g `typescript`
+    pub language: Option<String>,
+    /// Encoding it's saved with
+    pub encoding: FileEncoding,
+    /// Line endings of the file when it was opened or last saved
+    pub line_ending: LineEnding,
+}
+
+/// Convert a position into a char index of the rope
+fn get_char_index(rope: &Rope, position: Position) -> Result<usize, DocumentErrors> {
+    if position.line >= rope.len_lines() {
+        return Err(DocumentErrors::InvalidRange);
+    }
+
+    let line = rope.line(position.line);
+    let mut line_len = line.len_chars();
+    // The line break is not part of the line
+    if line_len > 0 && line.char(line_len - 1) == '\n' {
+        line_len -= 1;
+        if line_len > 0 && line.char(line_len - 1) == '\r' {
+            line_len -= 1;
+        }
+    }
+
+    if position.character > line_len {
+        return Err(DocumentErrors::InvalidRange);
+    }
+
+    Ok(rope.line_to_char(position.line) + position.character)
+}
+
+fn get_char_range(rope: &Rope, range: TextRange) -> Result<(usize, usize), DocumentErrors> {
+    let start = get_char_index(rope, range.start)?;
+    let end = get_char_index(rope, range.end)?;
+    if start > end {
+        Err(DocumentErrors::InvalidRange)
+    } else {
+        Ok((start, end))
+    }
+}
+
+/// Convert a char index into a position counted in UTF-16 code units, as used by LSP
+fn get_utf16_position(rope: &Rope, char_index: usize) -> Position {
+    let line = rope.char_to_line(char_index);
+    let line_start = rope.line_to_char(line);
+    let character = rope.char_to_utf16_cu(char_index) - rope.char_to_utf16_cu(line_start);
+    Position::new(line, character)
+}
+
+/// An open text buffer backed by a rope
+#[derive(Clone, Debug)]
+pub struct Document {
+    pub filesystem: String,
+    pub path: String,
+    rope: Rope,
+    version: i32,
+    saved_version: i32,
+    encoding: FileEncoding,
+    line_ending: LineEnding,
+}
+
+impl Document {
+    pub fn new(filesystem: &str, path: &str, content: &str) -> Self {
+        Self {
+            filesystem: filesystem.to_owned(),
+            path: path.to_owned(),
+            rope: Rope::from_str(content),
+            version: 1,
+            saved_version: 1,
+            encoding: FileEncoding::Utf8,
+            line_ending: LineEnding::detect(content),
+        }
+    }
+
+    /// Encoding the content was decoded from, it's saved with it too
+    pub fn with_encoding(mut self, encoding: FileEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn get_encoding(&self) -> FileEncoding {
+        self.encoding
+    }
+
+    /// Save the document with another encoding
+    pub fn set_encoding(&mut self, encoding: FileEncoding) {
+        self.encoding = encoding;
+    }
+
+    /// Remember the line endings the file was saved with
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
+    /// Replace the content with the one read again from the file, e.g decoded with another encoding
+    pub fn reload(&mut self, content: &str, encoding: FileEncoding) {
+        self.rope = Rope::from_str(content);
+        self.version += 1;
+        self.saved_version = self.version;
+        self.encoding = encoding;
+        self.line_ending = LineEnding::detect(content);
+    }
+
+    pub fn get_version(&self) -> i32 {
+        self.version
+    }
+
+    /// Check if the document has changed since it was opened or saved
+    pub fn is_dirty(&self) -> bool {
+        self.version != self.saved_version
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved_version = self.version;
+    }
+
+    pub fn get_content(&self) -> String {
+        self.rope.to_string()
+    }
+
+    /// Replace the whole content, it's not saved
+    pub fn set_content(&mut self, content: &str) {
+        self.rope = Rope::from_str(content);
+        self.version += 1;
+    }
+
+    pub fn get_line_count(&self) -> usize {
+        self.rope.len_lines()
+    }
+
+    /// Language of the document, lowercased as language servers expect, e.g `typescript`
+    pub fn get_language(&self) -> Option<String> {
+        match get_format_from_path(&self.path) {
+            FileFormat::Text(language) => Some(language.to_lowercase()),
+            _ => None,
+        }
+    }
+
+    pub fn get_uri(&self) -> GravitonUri {
+        GravitonUri::new(&self.filesystem, &self.path)
+    }
+
+    pub fn get_info(&self) -> DocumentInfo {
+        DocumentInfo {
+            filesystem: self.filesystem.clone(),
+            path: self.path.clone(),
+            uri: self.get_uri(),
+            version: self.version,
+            is_dirty: self.is_dirty(),
+            language: self.get_language(),
+            encoding: self.encoding,
+            line_ending: self.line_ending,
+        }
+    }
+
+    /// Convert a position counted in UTF-16 code units, as sent by language servers
+    pub fn get_char_position(&self, position: Position) -> Result<Position, DocumentErrors> {
+        if position.line >= self.rope.len_lines() {
+            return Err(DocumentErrors::InvalidRange);
+        }
+
+        let line_start = self.rope.line_to_char(position.line);
+        let utf16_index = self.rope.char_to_utf16_cu(line_start) + position.character;
+        if utf16_index > self.rope.len_utf16_cu() {
+            return Err(DocumentErrors::InvalidRange);
+        }
+
+        let character = self.rope.utf16_cu_to_char(utf16_index) - line_start;
+        Ok(Position::new(position.line, character))
+    }
+
+    /// Return the text in the given range
+    pub fn get_text(&self, range: TextRange) -> Result<String, DocumentErrors> {
+        let (start, end) = get_char_range(&self.rope, range)?;
+        Ok(self.rope.slice(start..end).to_string())
+    }
+
+    /// Apply the edits in order and bump the version
+    ///
+    /// Returns the same edits with their positions in UTF-16 code units,
+    /// ready to be sent as `contentChanges` of a `textDocument/didChange`
+    ///
+    /// # Arguments
+    ///
+    /// * `version`   - Version the edits were made on
+    /// * `edits`     - Edits to apply
+    ///
+    pub fn apply_edits(
+        &mut self,
+        version: i32,
+        edits: &[DocumentEdit],
+    ) -> Result<Vec<DocumentEdit>, DocumentErrors> {
+        if version != self.version {
+            return Err(DocumentErrors::VersionMismatch);
+        }
+
+        // Don't leave the document half edited if any of the edits is wrong
+        let mut rope = self.rope.clone();
+        let mut changes = Vec::with_capacity(edits.len());
+
+        for edit in edits {
+            let (start, end) = get_char_range(&rope, edit.range)?;
+
+            changes.push(DocumentEdit {
+                range: TextRange::new(
+                    get_utf16_position(&rope, start),
+                    get_utf16_position(&rope, end),
+                ),
+                text: edit.text.clone(),
+            });
+
+            rope.remove(start..end);
+            rope.insert(start, &edit.text);
+        }
+
+        self.rope = rope;
+        self.version += 1;
+
+        Ok(changes)
+    }
+}
+
+/// Documents opened in a State, by filesystem and path
+#[derive(Clone, Debug, Default)]
+pub struct Documents {
+    documents: HashMap<(String, String), Document>,
+}
+
+impl Documents {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track a document, returns false if it was already open
+    pub fn open(&mut self, document: Document) -> bool {
+        let key = (document.filesystem.clone(), document.path.clone());
+        match self.documents.entry(key) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(document);
+                true
+            }
+        }
+    }
+
+    pub fn close(&mut self, filesystem: &str, path: &str) -> Option<Document> {
+        self.documents
+            .remove(&(filesystem.to_owned(), path.to_owned()))
+    }
+
+    pub fn get(&self, filesystem: &str, path: &str) -> Option<&Document> {
+        self.documents
+            .get(&(filesystem.to_owned(), path.to_owned()))
+    }
+
+    pub fn get_mut(&mut self, filesystem: &str, path: &str) -> Option<&mut Document> {
+        self.documents
+            .get_mut(&(filesystem.to_owned(), path.to_owned()))
+    }
+
+    /// Follow the documents affected by renaming `from` to `to`
+    pub fn rename_paths(&mut self, filesystem: &str, from: &str, to: &str) {
+        let renamed = self
+            .documents
+            .keys()
+            .filter(|(doc_filesystem, _)| doc_filesystem == filesystem)
+            .filter_map(|key| Some((key.clone(), remap_path(&key.1, from, to)?)))
+            .collect::<Vec<((String, String), String)>>();
+
+        for (key, new_path) in renamed {
+            if let Some(mut document) = self.documents.remove(&key) {
+                document.path = new_path.clone();
+                self.documents.insert((key.0, new_path), document);
+            }
+        }
+    }
+
+    pub fn get_all(&self) -> Vec<DocumentInfo> {
+        self.documents.values().map(|doc| doc.get_info()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Bytes held by the content of all the documents
+    pub fn get_size(&self) -> usize {
+        self.documents
+            .values()
+            .map(|doc| doc.rope.len_bytes())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Document, DocumentEdit, DocumentErrors, Position, TextRange};
+
+    #[test]
+    fn incremental_edits() {
+        let mut document = Document::new("local", "/main.ts", "let a = 1;\nlet b = 2;\n");
+
+        assert!(!document.is_dirty());
+        assert_eq!(document.get_language(), Some("typescript".to_string()));
+
+        let changes = document
+            .apply_edits(
+                1,
+                &[
+                    DocumentEdit::insert(Position::new(1, 0), "const ç = 0;\n"),
+                    DocumentEdit::delete(TextRange::new(Position::new(1, 6), Position::new(1, 7))),
+                    DocumentEdit::insert(Position::new(1, 6), "𝑥"),
+                ],
+            )
+            .unwrap();
+
+        assert_eq!(
+            document.get_content(),
+            "let a = 1;\nconst 𝑥 = 0;\nlet b = 2;\n"
+        );
+        assert_eq!(document.get_version(), 2);
+        assert!(document.is_dirty());
+
+        // The inserted character takes two UTF-16 code units
+        let inserted = document
+            .apply_edits(2, &[DocumentEdit::insert(Position::new(1, 7), "y")])
+            .unwrap();
+        assert_eq!(inserted[0].range.start, Position::new(1, 8));
+        assert_eq!(changes.len(), 3);
+
+        // Outdated versions and invalid ranges are refused
+        assert_eq!(
+            document.apply_edits(1, &[]),
+            Err(DocumentErrors::VersionMismatch)
+        );
+        assert_eq!(
+            document.apply_edits(3, &[DocumentEdit::insert(Position::new(0, 50), "a")]),
+            Err(DocumentErrors::InvalidRange)
+        );
+        assert_eq!(document.get_version(), 3);
+
+        document.mark_saved();
+        assert!(!document.is_dirty());
+    }
+
+    #[test]
+    fn edit_only_what_changed() {
+        let content = "fn main() {\r\n  let a=1;\r\n}\r\n";
+        let formatted = "fn main() {\r\n    let a = 1;\r\n}\r\n";
+
+        let edit = DocumentEdit::between(content, formatted).unwrap();
+        assert_eq!(edit.range.start, Position::new(1, 2));
+        assert_eq!(edit.range.end, Position::new(1, 8));
+
+        let mut document = Document::new("local", "/main.rs", content);
+        document.apply_edits(1, &[edit]).unwrap();
+        assert_eq!(document.get_content(), formatted);
+
+        // Line breaks are never split
+        let edit = DocumentEdit::between("a\r\nb", "a\r\r\nb").unwrap();
+        let mut document = Document::new("local", "/notes.txt", "a\r\nb");
+        document.apply_edits(1, &[edit]).unwrap();
+        assert_eq!(document.get_content(), "a\r\r\nb");
+
+        assert!(DocumentEdit::between(content, content).is_none());
+    }
+}
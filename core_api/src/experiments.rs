@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Owner of the flags registered by the Core itself
+pub const CORE_EXPERIMENTS_OWNER: &str = "core";
+
+/// Local overrides are user settings with this prefix and the flag ID, e.g `experiments.collab`
+pub const EXPERIMENTS_SETTING_PREFIX: &str = "experiments.";
+
+/// Experiments errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ExperimentsErrors {
+    FlagNotFound,
+}
+
+/// A feature that is gradually enabled
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExperimentFlag {
+    pub id: String,
+    pub description: Option<String>,
+    /// Percentage (0-100) of the installs it's enabled in
+    pub rollout: u8,
+}
+
+impl ExperimentFlag {
+    pub fn new(id: &str, rollout: u8) -> Self {
+        Self {
+            id: id.to_owned(),
+            description: None,
+            rollout: rollout.min(100),
+        }
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_owned());
+        self
+    }
+
+    /// Key of the user setting overriding it
+    pub fn get_setting_key(&self) -> String {
+        format!("{}{}", EXPERIMENTS_SETTING_PREFIX, self.id)
+    }
+}
+
+/// A flag and whether it's enabled in this install
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExperimentStatus {
+    pub flag: ExperimentFlag,
+    /// `core` or the ID of the extension registering it
+    pub owner: String,
+    pub enabled: bool,
+    /// Enabled or disabled by the user regardless of the rollout
+    pub overridden: bool,
+}
+
+/// FNV-1a, the bucket of an install must not change between builds
+fn stable_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Flags of the Core and the extensions
+#[derive(Clone, Debug, Default)]
+pub struct ExperimentsRegistry {
+    /// Flags by their ID, with the owner registering them
+    flags: HashMap<String, (String, ExperimentFlag)>,
+    /// Identifies the install, so every flag is enabled in the same installs across restarts
+    seed: String,
+}
+
+impl ExperimentsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bucket the install with a stable identifier, e.g an ID generated on its first launch
+    pub fn set_seed(&mut self, seed: &str) {
+        self.seed = seed.to_owned();
+    }
+
+    pub fn register(&mut self, owner: &str, flags: Vec<ExperimentFlag>) {
+        for flag in flags {
+            self.flags.insert(flag.id.clone(), (owner.to_owned(), flag));
+        }
+    }
+
+    pub fn unregister(&mut self, owner: &str) {
+        self.flags.retain(|_, (id, _)| id != owner);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ExperimentFlag> {
+        self.flags.get(id).map(|(_, flag)| flag)
+    }
+
+    /// Bucket (0-99) of this install for a flag, it's enabled if it's below the rollout
+    pub fn get_bucket(&self, id: &str) -> u8 {
+        (stable_hash(&format!("{}:{}", self.seed, id)) % 100) as u8
+    }
+
+    /// Check if a flag is enabled, unknown flags are always disabled
+    ///
+    /// # Arguments
+    ///
+    /// * `id`              - ID of the flag
+    /// * `user_override`   - What the user chose, if anything
+    ///
+    pub fn get_status(&self, id: &str, user_override: Option<bool>) -> Option<ExperimentStatus> {
+        let (owner, flag) = self.flags.get(id)?;
+        let enabled = user_override.unwrap_or_else(|| self.get_bucket(id) < flag.rollout);
+
+        Some(ExperimentStatus {
+            flag: flag.clone(),
+            owner: owner.clone(),
+            enabled,
+            overridden: user_override.is_some(),
+        })
+    }
+
+    pub fn get_all(&self) -> Vec<ExperimentFlag> {
+        self.flags.values().map(|(_, flag)| flag.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExperimentFlag, ExperimentsRegistry};
+
+    #[test]
+    fn rollout_flags() {
+        let mut registry = ExperimentsRegistry::new();
+        registry.set_seed("install");
+        registry.register(
+            "collab",
+            vec![
+                ExperimentFlag::new("everyone", 100),
+                ExperimentFlag::new("nobody", 0),
+                ExperimentFlag::new("half", 50),
+            ],
+        );
+
+        assert!(registry.get_status("everyone", None).unwrap().enabled);
+        assert!(!registry.get_status("nobody", None).unwrap().enabled);
+        assert!(
+            registry
+                .get_status("nobody", Some(true))
+                .unwrap()
+                .overridden
+        );
+        assert!(registry.get_status("unknown", None).is_none());
+
+        // The same install always lands in the same bucket
+        assert_eq!(registry.get_bucket("half"), registry.get_bucket("half"));
+        let enabled = (0..100)
+            .filter(|install| {
+                registry.set_seed(&install.to_string());
+                registry.get_status("half", None).unwrap().enabled
+            })
+            .count();
+        assert!(enabled > 25 && enabled < 75);
+
+        registry.unregister("collab");
+        assert!(registry.get_all().is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use globset::GlobSet;
+use serde::{Deserialize, Serialize};
+
+use crate::decorations::{FileDecoration, PathsDecorations};
+use crate::filesystems::DirItemInfo;
+use crate::icons::FileIcon;
+use crate::vcs::{FileChange, FileStatus};
+
+/// A file or folder of the explorer, with everything needed to render it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExplorerItem {
+    pub path: String,
+    pub name: String,
+    pub is_file: bool,
+    /// Icon of the item in the active icon theme
+    pub icon: Option<FileIcon>,
+    /// Status in the repository, folders are `Modified` if they contain any change
+    pub git_status: Option<FileStatus>,
+    /// Decorations contributed by extensions, by extension ID
+    pub decorations: HashMap<String, FileDecoration>,
+    /// Files grouped under this one by the nesting rules
+    pub nested: Vec<ExplorerItem>,
+}
+
+/// The items of a folder as they are shown in the explorer
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExplorerListing {
+    pub path: String,
+    /// Branch of the repository containing the folder, if it's in one
+    pub branch: Option<String>,
+    pub items: Vec<ExplorerItem>,
+    /// How many items were hidden by the excluded globs
+    pub excluded: usize,
+}
+
+/// Check if an item is hidden by the excluded globs, they are matched against the
+/// path relative to the workspace root, or the name if it's not inside a workspace
+pub fn is_excluded(excluded: &GlobSet, root: Option<&str>, item: &DirItemInfo) -> bool {
+    let relative = root.and_then(|root| {
+        item.path
+            .strip_prefix(root.trim_end_matches(['/', '\\']))
+            .map(|rest| rest.trim_start_matches(['/', '\\']).replace('\\', "/"))
+    });
+
+    match relative {
+        Some(relative) => excluded.is_match(&relative),
+        None => excluded.is_match(&item.name),
+    }
+}
+
+/// Find the status of an item in the changes of its repository, their paths are relative
+/// to the repository root so they are matched against the end of the item's path
+pub fn get_git_status(path: &str, is_file: bool, changes: &[FileChange]) -> Option<FileStatus> {
+    let path = path.replace('\\', "/");
+    let path = path.trim_end_matches('/');
+
+    let is_item = |change_path: &str| path.ends_with(&format!("/{}", change_path));
+
+    for change in changes {
+        // Untracked folders end with a slash
+        let change_path = change.path.trim_end_matches('/');
+        if is_item(change_path) {
+            return Some(change.status.clone());
+        }
+        if !is_file
+            && change_path
+                .match_indices('/')
+                .any(|(end, _)| is_item(&change_path[..end]))
+        {
+            return Some(FileStatus::Modified);
+        }
+    }
+
+    None
+}
+
+/// Turn the items of a directory listing into explorer items
+///
+/// # Arguments
+///
+/// * `items`         - Items already nested
+/// * `changes`       - Changes of the repository containing them
+/// * `decorations`   - Decorations by path and extension, the used ones are taken
+///
+pub fn build_items(
+    items: Vec<DirItemInfo>,
+    changes: &[FileChange],
+    decorations: &mut PathsDecorations,
+) -> Vec<ExplorerItem> {
+    items
+        .into_iter()
+        .map(|item| ExplorerItem {
+            git_status: get_git_status(&item.path, item.is_file, changes),
+            decorations: decorations.remove(&item.path).unwrap_or_default(),
+            nested: build_items(item.nested, changes, decorations),
+            path: item.path,
+            name: item.name,
+            is_file: item.is_file,
+            icon: item.icon,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_git_status, is_excluded};
+    use crate::filesystems::DirItemInfo;
+    use crate::search::build_globset;
+    use crate::vcs::{FileChange, FileStatus};
+
+    #[test]
+    fn match_items() {
+        let changes = vec![
+            FileChange {
+                path: "project/src/lib.rs".to_string(),
+                status: FileStatus::Added,
+            },
+            FileChange {
+                path: "project/notes/".to_string(),
+                status: FileStatus::Untracked,
+            },
+        ];
+
+        assert_eq!(
+            get_git_status("/repo/project/src/lib.rs", true, &changes),
+            Some(FileStatus::Added)
+        );
+        assert_eq!(
+            get_git_status("/repo/project/src", false, &changes),
+            Some(FileStatus::Modified)
+        );
+        assert_eq!(
+            get_git_status("/repo/project/notes", false, &changes),
+            Some(FileStatus::Untracked)
+        );
+        assert_eq!(
+            get_git_status("/repo/project/main.rs", true, &changes),
+            None
+        );
+
+        let excluded = build_globset(&["target".to_string(), "*.log".to_string()]).unwrap();
+        let item = |path: &str| DirItemInfo {
+            path: path.to_string(),
+            name: path.rsplit('/').next().unwrap().to_string(),
+            is_file: true,
+            nested: Vec::new(),
+            icon: None,
+        };
+
+        assert!(is_excluded(
+            &excluded,
+            Some("/project"),
+            &item("/project/target")
+        ));
+        assert!(!is_excluded(
+            &excluded,
+            Some("/project"),
+            &item("/project/src/target")
+        ));
+        assert!(is_excluded(&excluded, None, &item("/tmp/debug.log")));
+    }
+}
@@ -3,6 +3,11 @@ use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
+use super::editors::CustomEditor;
+use super::subscriptions::MessageFilter;
+use crate::experiments::ExperimentFlag;
+use crate::settings::SettingSchema;
+use crate::validation::ValidationSchema;
 use crate::{messaging::ClientMessages, State};
 
 /// Information about a extension instance
@@ -27,4 +32,38 @@ pub trait Extension {
 
     /// Retrieve info from the exension
     fn get_info(&self) -> ExtensionInfo;
+
+    /// Messages the extension wants to be notified about, asked once when registering it
+    fn get_message_filter(&self) -> MessageFilter {
+        MessageFilter::all()
+    }
+
+    /// Custom message types the extension answers, e.g `myext/doThing`, asked once when registering it.
+    /// They must be namespaced with the extension ID
+    fn get_message_handlers(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Settings the extension can be configured with, asked once when registering it
+    fn get_settings_schema(&self) -> Vec<SettingSchema> {
+        Vec::new()
+    }
+
+    /// Features of the extension gated by flags, asked once when registering it.
+    /// It can check if they are enabled with `State::is_experiment_enabled`
+    fn get_experiments(&self) -> Vec<ExperimentFlag> {
+        Vec::new()
+    }
+
+    /// Schemas of the configuration files the extension knows about, e.g `Cargo.toml`,
+    /// asked once when registering it
+    fn get_validation_schemas(&self) -> Vec<ValidationSchema> {
+        Vec::new()
+    }
+
+    /// Editors the extension opens some files with instead of the text editor, asked once when registering it.
+    /// They must be namespaced with the extension ID
+    fn get_custom_editors(&self) -> Vec<CustomEditor> {
+        Vec::new()
+    }
 }
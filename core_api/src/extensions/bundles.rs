@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tracing::info;
+
+use super::installation::{is_valid_extension_id, replace_extension_files};
+use super::manifest::ManifestInfo;
+use super::ExtensionErrors;
+use crate::Manifest;
+
+/// File extension of the bundles
+pub const BUNDLE_EXTENSION: &str = "gvext";
+
+const MANIFEST_FILE: &str = "Graviton.toml";
+
+/// Signature of a bundle, kept in it, it's not part of the signed content
+const SIGNATURE_FILE: &str = "signature.json";
+
+/// Biggest bundle that can be installed
+pub const MAX_BUNDLE_SIZE: u64 = 128 * 1024 * 1024;
+
+/// Biggest size of all the files of a bundle once extracted, as they are kept in memory until they are unpacked
+pub const MAX_UNPACKED_BUNDLE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Read the files of a bundle, by their path in it, failing if they add up to more than `max_size`
+fn read_files(bundle: &[u8], max_size: u64) -> Result<BTreeMap<PathBuf, Vec<u8>>, ExtensionErrors> {
+    let mut archive =
+        zip::ZipArchive::new(Cursor::new(bundle)).map_err(|_| ExtensionErrors::BadPackage)?;
+
+    let mut files = BTreeMap::new();
+    let mut remaining_size = max_size;
+    for i in 0..archive.len() {
+        let file = archive
+            .by_index(i)
+            .map_err(|_| ExtensionErrors::BadPackage)?;
+        if file.is_dir() {
+            continue;
+        }
+
+        // Entries that would end up outside the extension's folder are not allowed
+        let path = file
+            .enclosed_name()
+            .ok_or(ExtensionErrors::BadPackage)?
+            .to_path_buf();
+
+        // The declared size can't be trusted, so the reads are bounded too
+        let mut content = Vec::new();
+        file.take(remaining_size + 1)
+            .read_to_end(&mut content)
+            .map_err(|_| ExtensionErrors::BadPackage)?;
+        remaining_size = remaining_size
+            .checked_sub(content.len() as u64)
+            .ok_or(ExtensionErrors::BundleTooBig)?;
+        files.insert(path, content);
+    }
+
+    Ok(files)
+}
+
+/// Signature of the digest of a bundle, see [`ExtensionBundle::get_digest`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundleSignature {
+    /// Ed25519 public key of the publisher, hex encoded
+    pub public_key: String,
+    /// Ed25519 signature, hex encoded
+    pub signature: String,
+}
+
+/// What bundles can be installed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct BundleOptions {
+    /// Public keys of the trusted publishers, hex encoded
+    pub trusted_keys: Vec<String>,
+    /// Install bundles without a signature
+    pub allow_unsigned: bool,
+}
+
+/// An extension packaged in a `.gvext` file to be installed without a registry, e.g in air-gapped machines.
+///
+/// It's a zip with the `Graviton.toml` manifest and the files of the extension, and optionally a `signature.json`
+pub struct ExtensionBundle {
+    pub manifest: ManifestInfo,
+    pub signature: Option<BundleSignature>,
+    /// Content of the files, by their path in the bundle
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl ExtensionBundle {
+    /// Read a bundle and validate its manifest, its files can't add up to more than [`MAX_UNPACKED_BUNDLE_SIZE`]
+    pub fn read(bundle: &[u8]) -> Result<Self, ExtensionErrors> {
+        let mut files = read_files(bundle, MAX_UNPACKED_BUNDLE_SIZE)?;
+
+        let manifest = files
+            .get(Path::new(MANIFEST_FILE))
+            .and_then(|manifest| std::str::from_utf8(manifest).ok())
+            .and_then(|manifest| toml::from_str::<ManifestInfo>(manifest).ok())
+            .ok_or(ExtensionErrors::BadManifest)?;
+
+        // The ID is the name of the extension's folder
+        if !is_valid_extension_id(&manifest.extension.id) {
+            return Err(ExtensionErrors::BadManifest);
+        }
+
+        if let Some(main) = &manifest.extension.main {
+            if !files.contains_key(Path::new(main)) {
+                return Err(ExtensionErrors::BadManifest);
+            }
+        }
+
+        let signature = match files.remove(Path::new(SIGNATURE_FILE)) {
+            Some(signature) => Some(
+                serde_json::from_slice(&signature).map_err(|_| ExtensionErrors::BadSignature)?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            manifest,
+            signature,
+            files,
+        })
+    }
+
+    /// SHA-256 of every file path and its content, in order, this is what publishers sign
+    pub fn get_digest(&self) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        for (path, content) in &self.files {
+            hasher.update(path.to_string_lossy().replace('\\', "/").as_bytes());
+            hasher.update([0u8]);
+            hasher.update(Sha256::digest(content));
+        }
+        hasher.finalize().to_vec()
+    }
+
+    /// Make sure the bundle is signed by a trusted publisher, unless unsigned bundles are allowed
+    pub fn verify(&self, options: &BundleOptions) -> Result<(), ExtensionErrors> {
+        let signature = match &self.signature {
+            Some(signature) => signature,
+            None if options.allow_unsigned => return Ok(()),
+            None => return Err(ExtensionErrors::UnsignedBundle),
+        };
+
+        if !options
+            .trusted_keys
+            .iter()
+            .any(|key| key.eq_ignore_ascii_case(&signature.public_key))
+        {
+            return Err(ExtensionErrors::UntrustedSignature);
+        }
+
+        let public_key = hex::decode(&signature.public_key)
+            .ok()
+            .and_then(|key| PublicKey::from_bytes(&key).ok())
+            .ok_or(ExtensionErrors::BadSignature)?;
+        let bundle_signature = hex::decode(&signature.signature)
+            .ok()
+            .and_then(|bytes| Signature::try_from(bytes.as_slice()).ok())
+            .ok_or(ExtensionErrors::BadSignature)?;
+
+        public_key
+            .verify(&self.get_digest(), &bundle_signature)
+            .map_err(|_| ExtensionErrors::BadSignature)
+    }
+
+    /// Write the files of the bundle into the given directory
+    fn unpack(&self, destination: &Path) -> Result<(), ExtensionErrors> {
+        for (path, content) in &self.files {
+            let path = destination.join(path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|_| ExtensionErrors::BadPackage)?;
+            }
+            std::fs::write(&path, content).map_err(|_| ExtensionErrors::BadPackage)?;
+        }
+        Ok(())
+    }
+}
+
+/// Verify and unpack a bundle, replacing any installed version of the extension
+///
+/// # Arguments
+///
+/// * `bundle_path`       - Path of the `.gvext` file, up to [`MAX_BUNDLE_SIZE`]
+/// * `extensions_path`   - Directory where the extensions are installed
+/// * `options`           - What bundles can be installed
+///
+pub async fn install_bundle(
+    bundle_path: &Path,
+    extensions_path: &Path,
+    options: &BundleOptions,
+) -> Result<Manifest, ExtensionErrors> {
+    let size = fs::metadata(bundle_path)
+        .await
+        .map_err(|_| ExtensionErrors::BadPackage)?
+        .len();
+    if size > MAX_BUNDLE_SIZE {
+        return Err(ExtensionErrors::BundleTooBig);
+    }
+
+    let bundle = fs::read(bundle_path)
+        .await
+        .map_err(|_| ExtensionErrors::BadPackage)?;
+
+    let extensions_path = extensions_path.to_path_buf();
+    let options = options.clone();
+    let (extension_id, destination) = tokio::task::spawn_blocking(move || {
+        let bundle = ExtensionBundle::read(&bundle)?;
+        bundle.verify(&options)?;
+
+        let extension_id = bundle.manifest.extension.id.clone();
+        let destination = replace_extension_files(&extensions_path, &extension_id, |staging| {
+            bundle.unpack(staging)
+        })?;
+
+        Ok::<_, ExtensionErrors>((extension_id, destination))
+    })
+    .await
+    .map_err(|_| ExtensionErrors::BadPackage)??;
+
+    let manifest = Manifest::parse(&destination.join(MANIFEST_FILE))
+        .await
+        .map_err(|_| ExtensionErrors::BadManifest)?;
+
+    info!(
+        "Installed extension <{}> v{} from a bundle",
+        extension_id, manifest.info.extension.version
+    );
+
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+
+    use super::{read_files, BundleOptions, BundleSignature, ExtensionBundle};
+    use crate::ExtensionErrors;
+
+    fn create_bundle(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut bundle = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, content) in files {
+            bundle
+                .start_file(*path, zip::write::FileOptions::default())
+                .unwrap();
+            bundle.write_all(content.as_bytes()).unwrap();
+        }
+        bundle.finish().unwrap().into_inner()
+    }
+
+    const MANIFEST: &str = "[extension]\nname = \"Test\"\nid = \"test\"\nauthor = \"me\"\nversion = \"1.0.0\"\nrepository = \"\"\nmain = \"main.js\"";
+
+    #[test]
+    fn validate_manifest() {
+        let bundle = create_bundle(&[("Graviton.toml", MANIFEST)]);
+        assert!(matches!(
+            ExtensionBundle::read(&bundle),
+            Err(ExtensionErrors::BadManifest)
+        ));
+
+        let bundle = create_bundle(&[("Graviton.toml", MANIFEST), ("main.js", "")]);
+        let bundle = ExtensionBundle::read(&bundle).unwrap();
+        assert_eq!(bundle.manifest.extension.id, "test");
+
+        // The ID can't point outside of the extensions directory
+        for id in ["../test", "/tmp/test", "nested/test"] {
+            let manifest = MANIFEST.replace("id = \"test\"", &format!("id = \"{}\"", id));
+            let bundle = create_bundle(&[("Graviton.toml", &manifest), ("main.js", "")]);
+            assert!(matches!(
+                ExtensionBundle::read(&bundle),
+                Err(ExtensionErrors::BadManifest)
+            ));
+        }
+    }
+
+    #[test]
+    fn verify_signature() {
+        let secret = SecretKey::from_bytes(&[7; 32]).unwrap();
+        let keypair = Keypair {
+            public: PublicKey::from(&secret),
+            secret,
+        };
+        let public_key = hex::encode(keypair.public.as_bytes());
+
+        let files = [("Graviton.toml", MANIFEST), ("main.js", "console.log(1)")];
+        let mut bundle = ExtensionBundle::read(&create_bundle(&files)).unwrap();
+
+        let trusted = BundleOptions {
+            trusted_keys: vec![public_key.clone()],
+            allow_unsigned: false,
+        };
+        assert_eq!(
+            bundle.verify(&trusted),
+            Err(ExtensionErrors::UnsignedBundle)
+        );
+
+        bundle.signature = Some(BundleSignature {
+            public_key,
+            signature: hex::encode(keypair.sign(&bundle.get_digest()).to_bytes()),
+        });
+        assert_eq!(bundle.verify(&trusted), Ok(()));
+        assert_eq!(
+            bundle.verify(&BundleOptions::default()),
+            Err(ExtensionErrors::UntrustedSignature)
+        );
+
+        // The content changed after it was signed
+        let signature = bundle.signature.take();
+        let mut tampered =
+            ExtensionBundle::read(&create_bundle(&[files[0], ("main.js", "evil()")])).unwrap();
+        tampered.signature = signature;
+        assert_eq!(
+            tampered.verify(&trusted),
+            Err(ExtensionErrors::BadSignature)
+        );
+    }
+
+    #[test]
+    fn reject_big_bundles() {
+        let bundle = create_bundle(&[("Graviton.toml", MANIFEST), ("main.js", "0123456789")]);
+        let size = (MANIFEST.len() + 10) as u64;
+
+        assert_eq!(read_files(&bundle, size).unwrap().len(), 2);
+        assert_eq!(
+            read_files(&bundle, size - 1),
+            Err(ExtensionErrors::BundleTooBig)
+        );
+    }
+}
@@ -1,12 +1,26 @@
+use serde::Serialize;
 use std::path::PathBuf;
 use std::sync::Arc;
+use tokio::fs;
+use tokio::process::{Child, Command};
 use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::Sender;
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
 
-use crate::messaging::ClientMessages;
+use crate::filesystems::map_io_error;
+#[cfg(feature = "http")]
+use crate::http::HttpClient;
+use crate::messaging::{ClientMessages, UIEvent};
+use crate::progress::{ProgressHandle, ProgressKind, ProgressRegistry};
+use crate::tasks::TaskErrors;
+use crate::Errors;
+#[cfg(feature = "http")]
+use crate::HttpErrors;
 
+use super::messages::{ExtensionMessage, MessageTarget};
+use super::permissions::{ExtensionPermissions, Permission, PermissionsRegistry};
 use super::settings::ExtensionSettings;
+use super::ExtensionErrors;
 use uuid::Uuid;
 
 pub enum EventActions {
@@ -18,6 +32,10 @@ pub enum EventActions {
         id_owner: String,
         sender: Sender<()>,
     },
+    OnMessageCallback {
+        id_owner: String,
+        callback: Box<dyn Fn(serde_json::Value) + Send>,
+    },
     Nothing,
 }
 
@@ -25,7 +43,10 @@ pub enum EventActions {
 #[derive(Clone)]
 pub struct ExtensionClient {
     pub name: String,
+    extension_id: String,
     sender: Sender<ClientMessages>,
+    permissions: Option<PermissionsRegistry>,
+    progress: ProgressRegistry,
     settings_path: Option<PathBuf>,
     pub event_actions: Arc<Mutex<Vec<EventActions>>>,
 }
@@ -39,13 +60,121 @@ impl ExtensionClient {
     ) -> Self {
         Self {
             name: name.to_string(),
+            extension_id: extension_id.to_string(),
             sender,
+            permissions: None,
+            progress: ProgressRegistry::new(),
             // TODO(marc2332) This should also take the State ID
             settings_path: settings_path.map(|path| path.join(extension_id)),
             event_actions: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
+    /// Enforce the permissions of the extension, clients without them have full access
+    pub fn with_permissions(mut self, permissions: PermissionsRegistry) -> Self {
+        self.permissions = Some(permissions);
+        self
+    }
+
+    /// Track the progresses of the extension in the State's registry, so clients can see them
+    pub fn with_progress(mut self, progress: ProgressRegistry) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Report a long-running operation to the clients, it's finished once the handle is dropped
+    ///
+    /// # Arguments
+    ///
+    /// * `state_id`      - The State to report it in
+    /// * `title`         - What's being done, e.g `Installing dependencies`
+    /// * `kind`          - If the amount of work is known
+    /// * `cancellable`   - If the clients can cancel it, see [`ProgressHandle::is_cancelled`]
+    ///
+    pub async fn start_progress(
+        &self,
+        state_id: u8,
+        title: &str,
+        kind: ProgressKind,
+        cancellable: bool,
+    ) -> ProgressHandle {
+        self.progress
+            .start(
+                title,
+                kind,
+                cancellable,
+                Some(&self.extension_id),
+                self.sender.clone(),
+                state_id,
+            )
+            .await
+    }
+
+    /// Make sure the extension is allowed to do something before doing it on its behalf
+    pub fn check_permission(&self, permission: &Permission) -> Result<(), ExtensionErrors> {
+        if let Some(permissions) = &self.permissions {
+            permissions.check(&self.extension_id, permission)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Read a file on behalf of the extension
+    pub async fn read_file(&self, path: &str) -> Result<String, Errors> {
+        self.check_permission(&Permission::Read(path.to_owned()))
+            .map_err(Errors::Ext)?;
+        fs::read_to_string(path).await.map_err(map_io_error)
+    }
+
+    /// Write a file on behalf of the extension
+    pub async fn write_file(&self, path: &str, content: &str) -> Result<(), Errors> {
+        self.check_permission(&Permission::Write(path.to_owned()))
+            .map_err(Errors::Ext)?;
+        fs::write(path, content).await.map_err(map_io_error)
+    }
+
+    /// Spawn a process on behalf of the extension
+    pub fn spawn_process(&self, command: &mut Command) -> Result<Child, Errors> {
+        self.check_permission(&Permission::Run)
+            .map_err(Errors::Ext)?;
+        command
+            .spawn()
+            .map_err(|_| Errors::Task(TaskErrors::CouldNotStart))
+    }
+
+    /// Start building an HTTP request on behalf of the extension, only to the hosts it can reach
+    #[cfg(feature = "http")]
+    pub fn http_request(
+        &self,
+        http: &HttpClient,
+        method: reqwest::Method,
+        url: &str,
+    ) -> Result<reqwest::RequestBuilder, Errors> {
+        let parsed_url =
+            reqwest::Url::parse(url).map_err(|_| Errors::Http(HttpErrors::RequestFailed))?;
+        let host = parsed_url.host_str().unwrap_or_default();
+        self.check_permission(&Permission::Net(host.to_owned()))
+            .map_err(Errors::Ext)?;
+        http.request(method, url).map_err(Errors::Http)
+    }
+
+    /// Get notified whenever the permissions of the extension might have changed,
+    /// clients without managed permissions never change
+    pub fn subscribe_permissions(&self) -> Option<watch::Receiver<()>> {
+        self.permissions
+            .as_ref()
+            .map(|permissions| permissions.subscribe())
+    }
+
+    /// Retrieve the current permissions of the extension
+    pub fn get_permissions(&self) -> ExtensionPermissions {
+        if let Some(permissions) = &self.permissions {
+            permissions.get(&self.extension_id).unwrap_or_default()
+        } else {
+            ExtensionPermissions::all()
+        }
+    }
+
     pub fn get_id(&mut self) -> String {
         format!("{}/{}", self.name, Uuid::new_v4())
     }
@@ -54,6 +183,52 @@ impl ExtensionClient {
         self.sender.send(message).await
     }
 
+    /// Answer a custom message the extension handles, see [`crate::extensions::base::Extension::get_message_handlers`]
+    pub async fn respond(
+        &self,
+        state_id: u8,
+        request_id: &str,
+        result: Result<serde_json::Value, Errors>,
+    ) -> Result<(), SendError<ClientMessages>> {
+        self.send(ClientMessages::CustomResponse {
+            state_id,
+            extension_id: self.extension_id.clone(),
+            request_id: request_id.to_owned(),
+            result,
+        })
+        .await
+    }
+
+    /// Send a message to another extension, or to all of them.
+    /// If it can't be delivered the extension is notified with an `ExtensionMessageFailed`
+    pub async fn send_to_extension(
+        &self,
+        state_id: u8,
+        target: MessageTarget,
+        payload: &impl Serialize,
+    ) -> Result<(), ExtensionErrors> {
+        let message = ExtensionMessage::new(&self.extension_id, target, payload)?;
+        self.send(ClientMessages::ExtensionMessage { state_id, message })
+            .await
+            .map_err(|_| ExtensionErrors::ExtensionNotFound)
+    }
+
+    /// Tell the result of a command registered by the extension
+    pub async fn send_command_result(
+        &self,
+        state_id: u8,
+        invocation_id: &str,
+        result: Result<serde_json::Value, Errors>,
+    ) -> Result<(), SendError<ClientMessages>> {
+        self.send(ClientMessages::CommandResult {
+            state_id,
+            extension_id: self.extension_id.clone(),
+            invocation_id: invocation_id.to_owned(),
+            result,
+        })
+        .await
+    }
+
     pub async fn get_settings(&self) -> Option<ExtensionSettings> {
         let path = self.settings_path.as_ref()?;
         Some(ExtensionSettings::new(path.clone()).await)
@@ -68,22 +243,26 @@ impl ExtensionClient {
         if let ClientMessages::UIEvent(event) = message {
             let id = event.get_owner_id();
             actions.retain(|action| match action {
+                EventActions::OnMessageCallback { id_owner, callback } => {
+                    if let UIEvent::PanelMessage { data, .. } = event {
+                        if id_owner == id {
+                            callback(data.clone())
+                        }
+                    }
+                    true
+                }
                 EventActions::OnClickCallback { id_owner, callback } => {
                     if id_owner == id {
                         callback()
                     }
                     true
                 }
-                EventActions::OnClick { id_owner, sender } => {
-                    if id_owner == id {
-                        let sender = sender.clone();
-                        tokio::spawn(async move {
-                            sender.send(()).await.unwrap();
-                        });
-                        false
-                    } else {
-                        true
-                    }
+                EventActions::OnClick { id_owner, sender } if id_owner == id => {
+                    let sender = sender.clone();
+                    tokio::spawn(async move {
+                        sender.send(()).await.unwrap();
+                    });
+                    false
                 }
                 _ => true,
             });
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::matcher::Matchable;
+use crate::settings::SettingType;
+
+/// Commands errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum CommandErrors {
+    CommandNotFound,
+    /// The arguments don't follow the schema of the command
+    InvalidArguments(String),
+    /// The extension couldn't run the command
+    Failed(String),
+}
+
+/// An argument a command can be invoked with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CommandArgument {
+    pub name: String,
+    pub argument_type: SettingType,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// An action an extension exposes, e.g in the command palette
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CommandInfo {
+    pub id: String,
+    pub title: String,
+    /// Extension running it
+    pub extension_id: String,
+    /// Arguments it's invoked with, in an object, if any
+    #[serde(default)]
+    pub arguments: Option<Vec<CommandArgument>>,
+}
+
+impl Matchable for CommandInfo {
+    fn get_text(&self) -> &str {
+        &self.title
+    }
+
+    fn get_key(&self) -> &str {
+        &self.id
+    }
+}
+
+impl CommandInfo {
+    pub fn new(extension_id: &str, id: &str, title: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            title: title.to_owned(),
+            extension_id: extension_id.to_owned(),
+            arguments: None,
+        }
+    }
+
+    pub fn with_arguments(mut self, arguments: Vec<CommandArgument>) -> Self {
+        self.arguments = Some(arguments);
+        self
+    }
+
+    /// Make sure the arguments follow the schema of the command, commands without one take anything
+    pub fn validate(&self, arguments: &Value) -> Result<(), CommandErrors> {
+        let schema = if let Some(schema) = &self.arguments {
+            schema
+        } else {
+            return Ok(());
+        };
+
+        let empty = serde_json::Map::new();
+        let values = match arguments {
+            Value::Object(values) => values,
+            Value::Null => &empty,
+            _ => {
+                return Err(CommandErrors::InvalidArguments(
+                    "expected an object".to_owned(),
+                ))
+            }
+        };
+
+        for argument in schema {
+            match values.get(&argument.name) {
+                Some(value) if !argument.argument_type.matches(value) => {
+                    return Err(CommandErrors::InvalidArguments(format!(
+                        "<{}> should be a {:?}",
+                        argument.name, argument.argument_type
+                    )))
+                }
+                None if argument.required => {
+                    return Err(CommandErrors::InvalidArguments(format!(
+                        "<{}> is required",
+                        argument.name
+                    )))
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Commands registered by the extensions, by ID
+#[derive(Clone, Debug, Default)]
+pub struct CommandsRegistry {
+    commands: HashMap<String, CommandInfo>,
+    /// Command and State of each invocation that didn't finish yet
+    pending: HashMap<String, (String, u8)>,
+}
+
+impl CommandsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a command, or replace the one with the same ID
+    pub fn register(&mut self, command: CommandInfo) {
+        self.commands.insert(command.id.clone(), command);
+    }
+
+    /// Remove the commands of an extension, returns the invocations that won't finish and their States
+    pub fn unregister(&mut self, extension_id: &str) -> Vec<(String, u8)> {
+        let commands = &self.commands;
+        let unfinished = self
+            .pending
+            .iter()
+            .filter(|(_, (command_id, _))| {
+                commands
+                    .get(command_id)
+                    .map(|command| command.extension_id == extension_id)
+                    .unwrap_or(true)
+            })
+            .map(|(invocation_id, (_, state_id))| (invocation_id.clone(), *state_id))
+            .collect::<Vec<(String, u8)>>();
+        for (invocation_id, _) in &unfinished {
+            self.pending.remove(invocation_id);
+        }
+
+        self.commands
+            .retain(|_, command| command.extension_id != extension_id);
+        unfinished
+    }
+
+    pub fn get(&self, command_id: &str) -> Option<&CommandInfo> {
+        self.commands.get(command_id)
+    }
+
+    pub fn get_all(&self) -> Vec<CommandInfo> {
+        self.commands.values().cloned().collect()
+    }
+
+    /// Validate the arguments and remember the invocation, returns the command
+    pub fn invoke(
+        &mut self,
+        invocation_id: &str,
+        command_id: &str,
+        arguments: &Value,
+        state_id: u8,
+    ) -> Result<CommandInfo, CommandErrors> {
+        let command = self
+            .commands
+            .get(command_id)
+            .ok_or(CommandErrors::CommandNotFound)?;
+        command.validate(arguments)?;
+
+        self.pending
+            .insert(invocation_id.to_owned(), (command_id.to_owned(), state_id));
+        Ok(command.clone())
+    }
+
+    /// Forget a finished invocation, only the extension running it can finish it.
+    /// Returns the ID of the command
+    pub fn finish(&mut self, invocation_id: &str, extension_id: &str) -> Option<String> {
+        let (command_id, _) = self.pending.get(invocation_id)?;
+        let command = self.commands.get(command_id)?;
+
+        if command.extension_id == extension_id {
+            self.pending
+                .remove(invocation_id)
+                .map(|(command_id, _)| command_id)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{CommandArgument, CommandErrors, CommandInfo, CommandsRegistry};
+    use crate::settings::SettingType;
+
+    #[test]
+    fn invoke_commands() {
+        let mut registry = CommandsRegistry::new();
+        registry.register(
+            CommandInfo::new("git", "git.checkout", "Checkout a branch").with_arguments(vec![
+                CommandArgument {
+                    name: "branch".to_owned(),
+                    argument_type: SettingType::String,
+                    required: true,
+                },
+            ]),
+        );
+
+        assert_eq!(
+            registry.invoke("1", "git.push", &json!(null), 1),
+            Err(CommandErrors::CommandNotFound)
+        );
+        assert!(matches!(
+            registry.invoke("1", "git.checkout", &json!({ "branch": 1 }), 1),
+            Err(CommandErrors::InvalidArguments(_))
+        ));
+        assert!(registry
+            .invoke("1", "git.checkout", &json!({ "branch": "main" }), 1)
+            .is_ok());
+
+        assert_eq!(registry.finish("1", "terminal"), None);
+        assert_eq!(registry.finish("1", "git"), Some("git.checkout".to_owned()));
+
+        registry
+            .invoke("2", "git.checkout", &json!({ "branch": "dev" }), 3)
+            .unwrap();
+        assert_eq!(registry.unregister("git"), vec![("2".to_owned(), 3)]);
+        assert!(registry.get_all().is_empty());
+    }
+}
@@ -0,0 +1,432 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::channel;
+use tokio::sync::Mutex;
+
+use super::base::ExtensionInfo;
+use super::handlers::get_namespace;
+use super::manager::{ExtensionSource, ExtensionsManager};
+use super::permissions::ExtensionPermissions;
+use super::subscriptions::MessageFilter;
+use crate::filesystems::MemoryFilesystem;
+use crate::messaging::ClientMessages;
+use crate::state_persistors::memory::MemoryPersistor;
+use crate::{Errors, State};
+
+/// ID of the throwaway States extensions are verified in, it never reaches the clients
+pub const SANDBOX_STATE_ID: u8 = 0;
+
+/// Inits taking longer than this are reported, they delay the launch of every State
+const SLOW_INIT: Duration = Duration::from_secs(1);
+
+/// Inits still running after this are stopped and reported as crashed
+const INIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long the messages sent by the extension after its init are still collected,
+/// e.g the ones of Deno extensions, which init in their own thread
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+/// Messages the sandbox keeps before the extension has to wait
+const SANDBOX_MESSAGES: usize = 256;
+
+/// What an extension adds to the Core
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContributionKind {
+    MessageHandler,
+    CustomEditor,
+    Setting,
+    Experiment,
+    Command,
+}
+
+/// How bad an issue is
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueSeverity {
+    /// It works but something is ignored or might be unexpected
+    Warning,
+    /// It shouldn't be enabled
+    Error,
+}
+
+/// Something wrong found while verifying an extension
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CompatibilityIssue {
+    /// Its manifest was loaded but it didn't register any instance
+    NoInstances,
+    /// It panicked while initializing
+    InitCrashed {
+        message: String,
+    },
+    SlowInit {
+        millis: u64,
+    },
+    /// It didn't finish initializing in time
+    InitTimedOut {
+        millis: u64,
+    },
+    /// It's ignored because it's not namespaced with the extension ID
+    OutsideNamespace {
+        contribution: ContributionKind,
+        id: String,
+    },
+    /// Another loaded extension already contributes it
+    Conflict {
+        contribution: ContributionKind,
+        id: String,
+        owner: String,
+    },
+    /// It registered a command on behalf of another extension
+    ForeignCommand {
+        id: String,
+        extension_id: String,
+    },
+    /// The instance doesn't subscribe to the messages declared in the manifest
+    SubscriptionsMismatch {
+        declared: MessageFilter,
+        actual: MessageFilter,
+    },
+}
+
+impl CompatibilityIssue {
+    pub fn get_severity(&self) -> IssueSeverity {
+        match self {
+            Self::NoInstances
+            | Self::InitCrashed { .. }
+            | Self::InitTimedOut { .. }
+            | Self::Conflict { .. }
+            | Self::ForeignCommand { .. } => IssueSeverity::Error,
+            Self::SlowInit { .. }
+            | Self::OutsideNamespace { .. }
+            | Self::SubscriptionsMismatch { .. } => IssueSeverity::Warning,
+        }
+    }
+}
+
+/// What the extension contributed in the sandbox
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Contributions {
+    pub message_handlers: Vec<String>,
+    pub custom_editors: Vec<String>,
+    pub settings: Vec<String>,
+    pub experiments: Vec<String>,
+    /// Names of the validation schemas
+    pub validation_schemas: Vec<String>,
+    pub commands: Vec<String>,
+}
+
+/// Result of running an extension in a sandbox
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CompatibilityReport {
+    pub extension_id: String,
+    pub version: String,
+    /// No errors were found, so it can be enabled
+    pub compatible: bool,
+    pub instances: Vec<ExtensionInfo>,
+    pub init_millis: u64,
+    pub contributions: Contributions,
+    /// Messages sent while initializing, by name
+    pub messages: BTreeMap<String, usize>,
+    pub issues: Vec<CompatibilityIssue>,
+}
+
+/// Check a contribution of the extension against the ones of the host
+fn check_contribution(
+    issues: &mut Vec<CompatibilityIssue>,
+    extension_id: &str,
+    contribution: ContributionKind,
+    id: &str,
+    owner: Option<&str>,
+) {
+    match owner {
+        Some(owner) if owner != extension_id => issues.push(CompatibilityIssue::Conflict {
+            contribution,
+            id: id.to_owned(),
+            owner: owner.to_owned(),
+        }),
+        _ => {}
+    }
+}
+
+/// Load an extension again in a throwaway State and run its init, so it can be checked before
+/// enabling it for real. The sandbox has no persistence, no settings file and its `local`
+/// filesystem is in memory. The extension is also stripped of its manifest permissions,
+/// so Deno extensions can't reach the real disk, network or processes while verified.
+///
+/// # Arguments
+///
+/// * `source`   - Where the extension is loaded from
+/// * `host`     - Extensions of the State it would be enabled in, to find conflicts
+///
+pub async fn verify_extension(
+    source: &ExtensionSource,
+    host: &ExtensionsManager,
+) -> CompatibilityReport {
+    let info = &source.manifest.info;
+    let extension_id = info.extension.id.as_str();
+    let mut issues = Vec::new();
+    let mut contributions = Contributions::default();
+
+    let (sender, mut receiver) = channel::<ClientMessages>(SANDBOX_MESSAGES);
+    let mut manager = ExtensionsManager::new(sender, None);
+    (source.loader)(&mut manager, &source.manifest, SANDBOX_STATE_ID);
+    manager
+        .permissions
+        .set(extension_id, ExtensionPermissions::default());
+
+    // What the instances declare when they are registered
+    let mut instances = Vec::new();
+    for instance in manager.get_instances(extension_id) {
+        instances.push(instance.info.clone());
+
+        let plugin = instance.plugin.lock().await;
+        for message_type in plugin.get_message_handlers() {
+            if get_namespace(&message_type) != Some(extension_id) {
+                issues.push(CompatibilityIssue::OutsideNamespace {
+                    contribution: ContributionKind::MessageHandler,
+                    id: message_type,
+                });
+                continue;
+            }
+            let owner = host.message_handlers.get_handler(&message_type);
+            check_contribution(
+                &mut issues,
+                extension_id,
+                ContributionKind::MessageHandler,
+                &message_type,
+                owner,
+            );
+            contributions.message_handlers.push(message_type);
+        }
+        for editor in plugin.get_custom_editors() {
+            if get_namespace(&editor.id) != Some(extension_id) {
+                issues.push(CompatibilityIssue::OutsideNamespace {
+                    contribution: ContributionKind::CustomEditor,
+                    id: editor.id,
+                });
+                continue;
+            }
+            let owner = host.custom_editors.get(&editor.id).map(|(owner, _)| owner);
+            check_contribution(
+                &mut issues,
+                extension_id,
+                ContributionKind::CustomEditor,
+                &editor.id,
+                owner,
+            );
+            contributions.custom_editors.push(editor.id);
+        }
+        for schema in plugin.get_settings_schema() {
+            let owner = host.settings_schemas.get_owner(&schema.key);
+            check_contribution(
+                &mut issues,
+                extension_id,
+                ContributionKind::Setting,
+                &schema.key,
+                owner,
+            );
+            contributions.settings.push(schema.key);
+        }
+        for flag in plugin.get_experiments() {
+            let owner = host
+                .experiments
+                .get_status(&flag.id, None)
+                .map(|status| status.owner);
+            check_contribution(
+                &mut issues,
+                extension_id,
+                ContributionKind::Experiment,
+                &flag.id,
+                owner.as_deref(),
+            );
+            contributions.experiments.push(flag.id);
+        }
+        for schema in plugin.get_validation_schemas() {
+            contributions.validation_schemas.push(schema.name);
+        }
+
+        let actual = plugin.get_message_filter();
+        if info.subscriptions != MessageFilter::default() && info.subscriptions != actual {
+            issues.push(CompatibilityIssue::SubscriptionsMismatch {
+                declared: info.subscriptions.clone(),
+                actual,
+            });
+        }
+    }
+
+    if instances.is_empty() {
+        issues.push(CompatibilityIssue::NoInstances);
+    }
+
+    // Run the init against the mocked services
+    let mut state = State::new(SANDBOX_STATE_ID, manager, Box::new(MemoryPersistor::new()));
+    state.filesystems.clear();
+    state.register_filesystem("local", Box::new(MemoryFilesystem::new()));
+    let state = Arc::new(Mutex::new(state));
+
+    let started = Instant::now();
+    let init_result = if instances.is_empty() {
+        Some(Ok(()))
+    } else {
+        let init = async {
+            state
+                .lock()
+                .await
+                .run_extension(extension_id, state.clone())
+                .await
+        };
+        tokio::time::timeout(INIT_TIMEOUT, init).await.ok()
+    };
+    let init_millis = started.elapsed().as_millis() as u64;
+    if init_result.is_none() {
+        issues.push(CompatibilityIssue::InitTimedOut {
+            millis: init_millis,
+        });
+    } else if started.elapsed() > SLOW_INIT {
+        issues.push(CompatibilityIssue::SlowInit {
+            millis: init_millis,
+        });
+    }
+
+    tokio::time::sleep(SETTLE_TIME).await;
+
+    let mut messages = BTreeMap::<String, usize>::new();
+    let mut crash_message = None;
+    while let Ok(message) = receiver.try_recv() {
+        if let ClientMessages::ExtensionCrashed { message, .. } = &message {
+            crash_message = Some(message.clone());
+        }
+        *messages.entry(message.get_name().to_owned()).or_default() += 1;
+    }
+    if let Some(Err(Errors::Ext(_))) = init_result {
+        issues.push(CompatibilityIssue::InitCrashed {
+            message: crash_message.unwrap_or_default(),
+        });
+    }
+
+    // Commands are registered while initializing
+    let mut state = state.lock().await;
+    for command in state.get_commands() {
+        if command.extension_id != extension_id {
+            issues.push(CompatibilityIssue::ForeignCommand {
+                id: command.id,
+                extension_id: command.extension_id,
+            });
+            continue;
+        }
+        let owner = host
+            .commands
+            .get(&command.id)
+            .map(|existing| existing.extension_id.as_str());
+        check_contribution(
+            &mut issues,
+            extension_id,
+            ContributionKind::Command,
+            &command.id,
+            owner,
+        );
+        contributions.commands.push(command.id);
+    }
+
+    // Nothing of the sandbox outlives the check
+    state.extensions_manager.unregister(extension_id).await;
+
+    CompatibilityReport {
+        extension_id: extension_id.to_owned(),
+        version: info.extension.version.clone(),
+        compatible: !issues
+            .iter()
+            .any(|issue| issue.get_severity() == IssueSeverity::Error),
+        instances,
+        init_millis,
+        contributions,
+        messages,
+        issues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use tokio::sync::Mutex;
+
+    use super::{verify_extension, CompatibilityIssue, ContributionKind};
+    use crate::extensions::base::{Extension, ExtensionInfo};
+    use crate::extensions::commands::CommandInfo;
+    use crate::extensions::manager::{ExtensionSource, ExtensionsManager};
+    use crate::messaging::ClientMessages;
+    use crate::{Manifest, ManifestExtension, ManifestInfo, State};
+
+    struct SampleExtension;
+
+    impl Extension for SampleExtension {
+        fn init(&mut self, state: Arc<Mutex<State>>) {
+            tokio::spawn(async move {
+                let mut state = state.lock().await;
+                state.register_command(CommandInfo::new("sample", "sample.run", "Run"));
+                state.register_command(CommandInfo::new("git", "git.commit", "Commit"));
+            });
+        }
+
+        fn unload(&mut self) {}
+
+        fn notify(&mut self, _message: ClientMessages) {}
+
+        fn get_info(&self) -> ExtensionInfo {
+            ExtensionInfo {
+                id: "sample".to_string(),
+                name: "Sample".to_string(),
+            }
+        }
+
+        fn get_message_handlers(&self) -> Vec<String> {
+            vec!["sample/ping".to_string(), "git/blame".to_string()]
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_sample_extension() {
+        let source = ExtensionSource {
+            manifest: Manifest {
+                location: PathBuf::from("/extensions/sample/Graviton.toml"),
+                info: ManifestInfo {
+                    extension: ManifestExtension {
+                        id: "sample".to_string(),
+                        name: "Sample".to_string(),
+                        version: "0.1.0".to_string(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            },
+            loader: |manager, _, _| {
+                manager.register("sample", Box::new(SampleExtension));
+            },
+        };
+        let mut host = ExtensionsManager::default();
+        host.message_handlers
+            .register("git", &["git/blame".to_string()]);
+
+        let report = verify_extension(&source, &host).await;
+
+        assert!(!report.compatible);
+        assert_eq!(report.instances.len(), 1);
+        assert_eq!(report.contributions.message_handlers, vec!["sample/ping"]);
+        assert_eq!(report.contributions.commands, vec!["sample.run"]);
+        assert!(report
+            .issues
+            .contains(&CompatibilityIssue::OutsideNamespace {
+                contribution: ContributionKind::MessageHandler,
+                id: "git/blame".to_string()
+            }));
+        assert!(report.issues.contains(&CompatibilityIssue::ForeignCommand {
+            id: "git.commit".to_string(),
+            extension_id: "git".to_string()
+        }));
+    }
+}
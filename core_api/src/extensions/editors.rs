@@ -0,0 +1,201 @@
+use globset::{GlobBuilder, GlobMatcher};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::handlers::get_namespace;
+use crate::documents::DocumentInfo;
+use crate::filesystems::GravitonUri;
+
+/// When a custom editor is used to open the files it claims
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EditorPriority {
+    /// Open the files with it instead of the text editor
+    #[default]
+    Default,
+    /// Only when it's explicitly chosen, e.g with "Open with..."
+    Option,
+}
+
+/// An editor of an extension for some files, e.g an image editor or a database viewer
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CustomEditor {
+    /// Namespaced with the extension ID, e.g `sqlite/viewer`
+    pub id: String,
+    pub name: String,
+    /// Globs of the file names it can open, e.g `*.sqlite`
+    pub file_match: Vec<String>,
+    #[serde(default)]
+    pub priority: EditorPriority,
+}
+
+impl CustomEditor {
+    pub fn new(id: &str, name: &str, file_match: &[&str]) -> Self {
+        Self {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            file_match: file_match.iter().map(|glob| glob.to_string()).collect(),
+            priority: EditorPriority::Default,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: EditorPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Check if it can open a file, only the file name is matched
+    pub fn matches(&self, path: &str) -> bool {
+        let file_name = path.rsplit(['/', '\\']).next().unwrap_or_default();
+        self.file_match
+            .iter()
+            .filter_map(|glob| get_matcher(glob))
+            .any(|matcher| matcher.is_match(file_name))
+    }
+}
+
+fn get_matcher(glob: &str) -> Option<GlobMatcher> {
+    GlobBuilder::new(glob)
+        .case_insensitive(true)
+        .build()
+        .ok()
+        .map(|glob| glob.compile_matcher())
+}
+
+/// What is asked to the extension of a custom editor
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CustomEditorAction {
+    /// Read the file and show it, e.g with `ServerMessages::ShowCustomEditor`
+    Open,
+    /// Write the changes made in the editor to the file
+    Save,
+    /// The editor was closed, the unsaved changes are discarded
+    Close,
+}
+
+/// A file opened with a custom editor
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CustomDocumentInfo {
+    pub filesystem: String,
+    pub path: String,
+    pub uri: GravitonUri,
+    pub editor_id: String,
+}
+
+/// How a file was opened, as text or with a custom editor
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind")]
+pub enum OpenedFile {
+    Document(DocumentInfo),
+    Custom(CustomDocumentInfo),
+}
+
+/// Custom editors contributed by the extensions
+#[derive(Clone, Debug, Default)]
+pub struct CustomEditorsRegistry {
+    /// Editors with the extension contributing them, in registration order
+    editors: Vec<(String, CustomEditor)>,
+}
+
+impl CustomEditorsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the editors of an extension, the ones outside its namespace are ignored
+    pub fn register(&mut self, extension_id: &str, editors: Vec<CustomEditor>) {
+        for editor in editors {
+            if get_namespace(&editor.id) != Some(extension_id) {
+                warn!(
+                    "Extension <{}> can't contribute editors outside its namespace, ignoring <{}>",
+                    extension_id, editor.id
+                );
+                continue;
+            }
+            self.editors.retain(|(_, e)| e.id != editor.id);
+            self.editors.push((extension_id.to_owned(), editor));
+        }
+    }
+
+    pub fn unregister(&mut self, extension_id: &str) {
+        self.editors.retain(|(id, _)| id != extension_id);
+    }
+
+    /// Find an editor and the extension contributing it
+    pub fn get(&self, editor_id: &str) -> Option<(&str, &CustomEditor)> {
+        self.editors
+            .iter()
+            .find(|(_, editor)| editor.id == editor_id)
+            .map(|(extension_id, editor)| (extension_id.as_str(), editor))
+    }
+
+    pub fn get_all(&self) -> Vec<CustomEditor> {
+        self.editors
+            .iter()
+            .map(|(_, editor)| editor.clone())
+            .collect()
+    }
+
+    /// Every editor that can open a file, e.g for "Open with..."
+    pub fn get_editors_for(&self, path: &str) -> Vec<CustomEditor> {
+        self.editors
+            .iter()
+            .filter(|(_, editor)| editor.matches(path))
+            .map(|(_, editor)| editor.clone())
+            .collect()
+    }
+
+    /// The editor a file is opened with by default, none if it's opened as text.
+    /// The last registered wins
+    pub fn get_default_editor(&self, path: &str) -> Option<&CustomEditor> {
+        self.editors
+            .iter()
+            .rev()
+            .map(|(_, editor)| editor)
+            .find(|editor| editor.priority == EditorPriority::Default && editor.matches(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CustomEditor, CustomEditorsRegistry, EditorPriority};
+
+    #[test]
+    fn claim_files() {
+        let mut registry = CustomEditorsRegistry::new();
+        registry.register(
+            "images",
+            vec![
+                CustomEditor::new("images/svg", "SVG Editor", &["*.svg"])
+                    .with_priority(EditorPriority::Option),
+                CustomEditor::new("images/raster", "Image Viewer", &["*.png", "*.jpg"]),
+                CustomEditor::new("sqlite/viewer", "Stolen", &["*.sqlite"]),
+            ],
+        );
+        registry.register(
+            "sqlite",
+            vec![CustomEditor::new(
+                "sqlite/viewer",
+                "SQLite",
+                &["*.sqlite", "*.db"],
+            )],
+        );
+
+        assert_eq!(
+            registry
+                .get_default_editor("/home/user/Logo.PNG")
+                .unwrap()
+                .id,
+            "images/raster"
+        );
+        assert_eq!(registry.get("sqlite/viewer").unwrap().0, "sqlite");
+
+        // Optional editors are not used by default
+        assert!(registry.get_default_editor("/icons/logo.svg").is_none());
+        assert_eq!(registry.get_editors_for("C:\\icons\\logo.svg").len(), 1);
+        assert!(registry.get_default_editor("/main.rs").is_none());
+
+        registry.unregister("images");
+        assert!(registry.get_default_editor("/logo.png").is_none());
+        assert_eq!(registry.get_all().len(), 1);
+    }
+}
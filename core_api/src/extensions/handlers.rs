@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use tracing::warn;
+
+/// Separates the namespace from the name of a custom message, e.g `git/blame`
+pub const NAMESPACE_SEPARATOR: char = '/';
+
+/// Extensions that handle the custom messages, by the message type.
+/// Extensions can only handle the messages in their own namespace, which is their ID
+#[derive(Clone, Debug, Default)]
+pub struct MessageHandlersIndex {
+    /// Extension handling each message type
+    handlers: HashMap<String, String>,
+    /// Extension and State of each request that wasn't answered yet
+    pending: HashMap<String, (String, u8)>,
+}
+
+/// Return the namespace of a custom message type, if it has one
+pub fn get_namespace(message_type: &str) -> Option<&str> {
+    message_type
+        .split_once(NAMESPACE_SEPARATOR)
+        .map(|(namespace, _)| namespace)
+        .filter(|namespace| !namespace.is_empty())
+}
+
+impl MessageHandlersIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle the given message types with an extension, the ones outside its namespace are ignored
+    pub fn register(&mut self, extension_id: &str, message_types: &[String]) {
+        for message_type in message_types {
+            if get_namespace(message_type) == Some(extension_id) {
+                self.handlers
+                    .insert(message_type.clone(), extension_id.to_owned());
+            } else {
+                warn!(
+                    "Extension <{}> can't handle messages outside its namespace, ignoring <{}>",
+                    extension_id, message_type
+                );
+            }
+        }
+    }
+
+    /// Stop routing messages to an extension, returns the requests it didn't answer and their States
+    pub fn unregister(&mut self, extension_id: &str) -> Vec<(String, u8)> {
+        self.handlers.retain(|_, handler| handler != extension_id);
+
+        let unanswered = self
+            .pending
+            .iter()
+            .filter(|(_, (handler, _))| handler == extension_id)
+            .map(|(request_id, (_, state_id))| (request_id.clone(), *state_id))
+            .collect::<Vec<(String, u8)>>();
+        for (request_id, _) in &unanswered {
+            self.pending.remove(request_id);
+        }
+        unanswered
+    }
+
+    pub fn get_handler(&self, message_type: &str) -> Option<&str> {
+        self.handlers.get(message_type).map(|id| id.as_str())
+    }
+
+    /// Message types handled by every extension
+    pub fn get_handlers(&self) -> HashMap<String, String> {
+        self.handlers.clone()
+    }
+
+    /// Remember which extension got a request, returns its ID if there is any handler
+    pub fn route(&mut self, request_id: &str, message_type: &str, state_id: u8) -> Option<String> {
+        let extension_id = self.get_handler(message_type)?.to_owned();
+        self.pending
+            .insert(request_id.to_owned(), (extension_id.clone(), state_id));
+        Some(extension_id)
+    }
+
+    /// Forget an answered request, only the extension that got it can answer it
+    pub fn complete(&mut self, request_id: &str, extension_id: &str) -> bool {
+        let handler = self.pending.get(request_id).map(|(id, _)| id.as_str());
+        if handler == Some(extension_id) {
+            self.pending.remove(request_id);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MessageHandlersIndex;
+
+    #[test]
+    fn route_namespaced_messages() {
+        let mut index = MessageHandlersIndex::new();
+        index.register(
+            "git",
+            &["git/blame".to_string(), "terminal/open".to_string()],
+        );
+
+        assert_eq!(index.get_handler("git/blame"), Some("git"));
+        assert_eq!(index.get_handler("terminal/open"), None);
+
+        assert_eq!(index.route("1", "git/blame", 1), Some("git".to_string()));
+        assert_eq!(index.route("2", "git/log", 1), None);
+
+        assert!(!index.complete("1", "terminal"));
+        assert!(index.complete("1", "git"));
+        assert!(!index.complete("1", "git"));
+
+        index.route("3", "git/blame", 2);
+        assert_eq!(index.unregister("git"), vec![("3".to_string(), 2)]);
+        assert_eq!(index.get_handler("git/blame"), None);
+    }
+}
@@ -0,0 +1,147 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use super::ExtensionErrors;
+
+/// Folder of the extensions directory where new versions are unpacked before replacing the installed ones.
+/// It has no manifest so it's never loaded as an extension
+const STAGING_FOLDER: &str = ".installing";
+
+/// Folder of the extensions directory where the installed version is kept while it's replaced
+const PREVIOUS_FOLDER: &str = ".previous";
+
+/// Check if an extension ID can be used as the name of its folder,
+/// it must be a single path component so it can't point outside of the extensions directory
+pub fn is_valid_extension_id(extension_id: &str) -> bool {
+    let mut components = Path::new(extension_id).components();
+    let is_name = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(name)), None) if name == extension_id
+    );
+    is_name && !extension_id.contains(['/', '\\'])
+}
+
+/// Folder where an extension is installed
+///
+/// # Arguments
+///
+/// * `extensions_path`   - Directory where the extensions are installed
+/// * `extension_id`      - ID of the extension
+///
+pub fn get_extension_folder(
+    extensions_path: &Path,
+    extension_id: &str,
+) -> Result<PathBuf, ExtensionErrors> {
+    if is_valid_extension_id(extension_id) {
+        Ok(extensions_path.join(extension_id))
+    } else {
+        Err(ExtensionErrors::ExtensionNotFound)
+    }
+}
+
+/// Unpack a new version of an extension and only then replace the installed one,
+/// so a failed install leaves the previous version untouched. Returns the folder of the extension
+///
+/// # Arguments
+///
+/// * `extensions_path`   - Directory where the extensions are installed
+/// * `extension_id`      - ID of the extension
+/// * `unpack`            - Writes the files of the extension into the given folder
+///
+pub fn replace_extension_files(
+    extensions_path: &Path,
+    extension_id: &str,
+    unpack: impl FnOnce(&Path) -> Result<(), ExtensionErrors>,
+) -> Result<PathBuf, ExtensionErrors> {
+    let destination = get_extension_folder(extensions_path, extension_id)?;
+    let staging = extensions_path.join(STAGING_FOLDER).join(extension_id);
+    let previous = extensions_path.join(PREVIOUS_FOLDER).join(extension_id);
+
+    // Leftovers of an interrupted install
+    fs::remove_dir_all(&staging).ok();
+    fs::remove_dir_all(&previous).ok();
+
+    if let Err(err) = unpack(&staging) {
+        fs::remove_dir_all(&staging).ok();
+        return Err(err);
+    }
+
+    let is_installed = destination.exists();
+    if is_installed {
+        let moved = fs::create_dir_all(extensions_path.join(PREVIOUS_FOLDER))
+            .and_then(|_| fs::rename(&destination, &previous));
+        if moved.is_err() {
+            fs::remove_dir_all(&staging).ok();
+            return Err(ExtensionErrors::BadPackage);
+        }
+    }
+
+    if fs::rename(&staging, &destination).is_err() {
+        if is_installed {
+            fs::rename(&previous, &destination).ok();
+        }
+        fs::remove_dir_all(&staging).ok();
+        return Err(ExtensionErrors::BadPackage);
+    }
+
+    fs::remove_dir_all(&previous).ok();
+
+    Ok(destination)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{is_valid_extension_id, replace_extension_files};
+    use crate::ExtensionErrors;
+
+    #[test]
+    fn validate_extension_ids() {
+        assert!(is_valid_extension_id("git-for-graviton"));
+        assert!(is_valid_extension_id("graviton.typescript"));
+
+        for id in ["", ".", "..", "../evil", "/etc", "a/b", "a\\b", "./a", "a/"] {
+            assert!(!is_valid_extension_id(id), "{} should be rejected", id);
+        }
+    }
+
+    #[test]
+    fn keep_the_installed_version_on_failure() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let write_version = |version: &str| {
+            let version = version.to_owned();
+            move |folder: &std::path::Path| {
+                fs::create_dir_all(folder).unwrap();
+                fs::write(folder.join("Graviton.toml"), version).unwrap();
+                Ok(())
+            }
+        };
+
+        let folder = replace_extension_files(dir, "sample", write_version("1.0.0")).unwrap();
+        assert_eq!(folder, dir.join("sample"));
+
+        // A broken package doesn't remove the installed version
+        let failed = replace_extension_files(dir, "sample", |folder| {
+            fs::create_dir_all(folder).unwrap();
+            Err(ExtensionErrors::BadPackage)
+        });
+        assert_eq!(failed, Err(ExtensionErrors::BadPackage));
+        assert_eq!(
+            fs::read_to_string(folder.join("Graviton.toml")).unwrap(),
+            "1.0.0"
+        );
+
+        replace_extension_files(dir, "sample", write_version("2.0.0")).unwrap();
+        assert_eq!(
+            fs::read_to_string(folder.join("Graviton.toml")).unwrap(),
+            "2.0.0"
+        );
+
+        assert_eq!(
+            replace_extension_files(dir, "../sample", write_version("3.0.0")),
+            Err(ExtensionErrors::ExtensionNotFound)
+        );
+    }
+}
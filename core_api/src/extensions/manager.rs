@@ -1,22 +1,142 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 
 use tokio::sync::mpsc::{channel, Sender};
 use tokio::sync::Mutex;
 
+use crate::experiments::ExperimentsRegistry;
 use crate::extensions::base::Extension;
-use crate::messaging::ClientMessages;
-use crate::{Manifest, ManifestInfo};
+use crate::messaging::{ClientMessages, ServerMessages};
+use crate::progress::ProgressRegistry;
+use crate::settings::SettingsSchemas;
+use crate::telemetry::{ExtensionHandler, Telemetry};
+use crate::validation::ValidationSchemas;
+use crate::{Errors, Manifest, ManifestInfo};
 
 use super::base::ExtensionInfo;
 use super::client::ExtensionClient;
+use super::commands::CommandErrors;
+use super::commands::CommandsRegistry;
+use super::editors::CustomEditorsRegistry;
+use super::handlers::MessageHandlersIndex;
+use super::messages::{ExtensionMessage, MessageTarget};
+use super::permissions::PermissionsRegistry;
+use super::subscriptions::SubscriptionsIndex;
+use super::supervisor::{run_isolated, ExtensionHealth};
+use super::ExtensionErrors;
+
+/// Instantiates an extension from its manifest, e.g the Deno runtime
+pub type ExtensionLoader = fn(&mut ExtensionsManager, &Manifest, u8);
+
+/// Where an extension was loaded from, so it can be loaded again
+#[derive(Clone)]
+pub struct ExtensionSource {
+    pub manifest: Manifest,
+    pub loader: ExtensionLoader,
+}
+
+/// Positions in the loaded extensions list by extension ID, so lookups don't scan the whole list
+#[derive(Clone, Default)]
+struct ExtensionsIndex {
+    /// Manifests by their ID
+    manifests: HashMap<String, usize>,
+    /// Instances by the ID of the extension that registered them, in load order
+    instances: HashMap<String, Vec<usize>>,
+    /// Instances by the ID they report in their [`ExtensionInfo`]
+    instances_info: HashMap<String, usize>,
+}
+
+impl ExtensionsIndex {
+    fn add(&mut self, position: usize, extension: &LoadedExtension) {
+        match extension {
+            LoadedExtension::ManifestBuiltin { info } => {
+                self.manifests.insert(info.extension.id.clone(), position);
+            }
+            LoadedExtension::ManifestFile { manifest } => {
+                self.manifests
+                    .insert(manifest.info.extension.id.clone(), position);
+            }
+            LoadedExtension::ExtensionInstance {
+                info, parent_id, ..
+            } => {
+                self.instances
+                    .entry(parent_id.clone())
+                    .or_default()
+                    .push(position);
+                self.instances_info
+                    .entry(info.id.clone())
+                    .or_insert(position);
+            }
+        }
+    }
+
+    fn build(extensions: &[LoadedExtension]) -> Self {
+        let mut index = Self::default();
+        for (position, extension) in extensions.iter().enumerate() {
+            index.add(position, extension);
+        }
+        index
+    }
+}
+
+/// A loaded extension instance, borrowed from the manager
+pub struct ExtensionInstance<'a> {
+    pub plugin: &'a Arc<Mutex<Box<dyn Extension + Send>>>,
+    pub info: &'a ExtensionInfo,
+    pub parent_id: &'a str,
+    pub health: &'a Arc<Mutex<ExtensionHealth>>,
+}
+
+impl<'a> ExtensionInstance<'a> {
+    fn from_loaded(extension: &'a LoadedExtension) -> Option<Self> {
+        match extension {
+            LoadedExtension::ExtensionInstance {
+                plugin,
+                info,
+                parent_id,
+                health,
+            } => Some(Self {
+                plugin,
+                info,
+                parent_id,
+                health,
+            }),
+            _ => None,
+        }
+    }
+}
 
 /// Manage a group of extensions
 #[derive(Clone)]
 pub struct ExtensionsManager {
-    pub extensions: Vec<LoadedExtension>,
+    /// Every loaded extension, in load order
+    extensions: Vec<LoadedExtension>,
+    index: ExtensionsIndex,
     pub sender: Sender<ClientMessages>,
     pub settings_path: Option<PathBuf>,
+    /// What each extension is allowed to do
+    pub permissions: PermissionsRegistry,
+    /// Extensions loaded from the filesystem, by ID
+    pub sources: HashMap<String, ExtensionSource>,
+    /// What messages each extension is interested in
+    pub subscriptions: SubscriptionsIndex,
+    /// What custom messages each extension answers
+    pub message_handlers: MessageHandlersIndex,
+    /// Settings contributed by the extensions
+    pub settings_schemas: SettingsSchemas,
+    /// Flags of the features of the Core and the extensions
+    pub experiments: ExperimentsRegistry,
+    /// Schemas of configuration files contributed by the extensions
+    pub validation_schemas: ValidationSchemas,
+    /// Editors of the extensions for some files, e.g images
+    pub custom_editors: CustomEditorsRegistry,
+    /// Commands the extensions can be asked to run
+    pub commands: CommandsRegistry,
+    /// Long-running operations of the Core and the extensions
+    pub progress: ProgressRegistry,
+    /// Opt-in timings of the extensions and the filesystems
+    pub telemetry: Telemetry,
 }
 
 impl Default for ExtensionsManager {
@@ -24,8 +144,20 @@ impl Default for ExtensionsManager {
         let (sender, _) = channel::<ClientMessages>(1);
         Self {
             extensions: Vec::new(),
+            index: ExtensionsIndex::default(),
             sender,
             settings_path: None,
+            permissions: PermissionsRegistry::new(),
+            sources: HashMap::new(),
+            subscriptions: SubscriptionsIndex::new(),
+            message_handlers: MessageHandlersIndex::new(),
+            settings_schemas: SettingsSchemas::new(),
+            experiments: ExperimentsRegistry::new(),
+            validation_schemas: ValidationSchemas::new(),
+            custom_editors: CustomEditorsRegistry::new(),
+            commands: CommandsRegistry::new(),
+            progress: ProgressRegistry::new(),
+            telemetry: Telemetry::new(),
         }
     }
 }
@@ -34,8 +166,20 @@ impl ExtensionsManager {
     pub fn new(sender: Sender<ClientMessages>, settings_path: Option<PathBuf>) -> Self {
         Self {
             extensions: Vec::new(),
+            index: ExtensionsIndex::default(),
             sender,
             settings_path,
+            permissions: PermissionsRegistry::new(),
+            sources: HashMap::new(),
+            subscriptions: SubscriptionsIndex::new(),
+            message_handlers: MessageHandlersIndex::new(),
+            settings_schemas: SettingsSchemas::new(),
+            experiments: ExperimentsRegistry::new(),
+            validation_schemas: ValidationSchemas::new(),
+            custom_editors: CustomEditorsRegistry::new(),
+            commands: CommandsRegistry::new(),
+            progress: ProgressRegistry::new(),
+            telemetry: Telemetry::new(),
         }
     }
 
@@ -46,26 +190,253 @@ impl ExtensionsManager {
         info: ManifestInfo,
         state_id: u8,
     ) -> &mut ExtensionsManager {
+        self.permissions
+            .set(&info.extension.id, info.permissions.clone());
         let client = ExtensionClient::new(
             &info.extension.id,
             &info.extension.name,
             self.sender.clone(),
             self.settings_path.clone(),
-        );
+        )
+        .with_permissions(self.permissions.clone())
+        .with_progress(self.progress.clone());
         entry(self, client, state_id);
-        self.extensions
-            .push(LoadedExtension::ManifestBuiltin { info });
+        self.push(LoadedExtension::ManifestBuiltin { info });
         self
     }
 
+    /// Add an extension to the list
+    pub fn push(&mut self, extension: LoadedExtension) {
+        self.index.add(self.extensions.len(), &extension);
+        self.extensions.push(extension);
+    }
+
+    /// All the loaded extensions, in load order
+    pub fn get_all(&self) -> &[LoadedExtension] {
+        &self.extensions
+    }
+
+    /// All the loaded instances, in load order
+    pub fn get_all_instances(&self) -> impl Iterator<Item = ExtensionInstance<'_>> {
+        self.extensions
+            .iter()
+            .filter_map(ExtensionInstance::from_loaded)
+    }
+
+    /// Instances registered by an extension, in load order
+    pub fn get_instances(&self, extension_id: &str) -> impl Iterator<Item = ExtensionInstance<'_>> {
+        self.index
+            .instances
+            .get(extension_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|position| ExtensionInstance::from_loaded(&self.extensions[*position]))
+    }
+
+    /// Find an instance by the ID it reports in its info
+    pub fn get_instance_by_info_id(&self, id: &str) -> Option<ExtensionInstance<'_>> {
+        self.index
+            .instances_info
+            .get(id)
+            .and_then(|position| ExtensionInstance::from_loaded(&self.extensions[*position]))
+    }
+
+    /// Find the manifest of an extension
+    pub fn get_manifest_info(&self, extension_id: &str) -> Option<&ManifestInfo> {
+        self.index.manifests.get(extension_id).and_then(|position| {
+            match &self.extensions[*position] {
+                LoadedExtension::ManifestBuiltin { info } => Some(info),
+                LoadedExtension::ManifestFile { manifest } => Some(&manifest.info),
+                _ => None,
+            }
+        })
+    }
+
+    /// IDs of the extensions loaded from a manifest, in load order
+    pub fn get_manifest_ids(&self) -> Vec<String> {
+        self.extensions
+            .iter()
+            .filter_map(|extension| match extension {
+                LoadedExtension::ManifestBuiltin { info } => Some(info.extension.id.clone()),
+                LoadedExtension::ManifestFile { manifest } => {
+                    Some(manifest.info.extension.id.clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Unload and remove all the entries of an extension, returns false if it wasn't loaded
+    pub async fn unregister(&mut self, extension_id: &str) -> bool {
+        let mut found = false;
+
+        let plugins = self
+            .get_instances(extension_id)
+            .map(|instance| instance.plugin.clone())
+            .collect::<Vec<_>>();
+        for plugin in plugins {
+            // Extensions might panic when unloading, it shouldn't take down the Core
+            tokio::spawn(async move {
+                plugin.lock().await.unload();
+            })
+            .await
+            .ok();
+        }
+
+        self.extensions.retain(|ext| {
+            let id = match ext {
+                LoadedExtension::ManifestBuiltin { info } => &info.extension.id,
+                LoadedExtension::ManifestFile { manifest } => &manifest.info.extension.id,
+                LoadedExtension::ExtensionInstance { parent_id, .. } => parent_id,
+            };
+            let matches = id == extension_id;
+            found |= matches;
+            !matches
+        });
+        if found {
+            self.index = ExtensionsIndex::build(&self.extensions);
+        }
+
+        self.permissions.remove(extension_id);
+        self.progress.cancel_owner(extension_id);
+        self.sources.remove(extension_id);
+        self.subscriptions.unsubscribe(extension_id);
+        self.settings_schemas.unregister(extension_id);
+        self.experiments.unregister(extension_id);
+        self.validation_schemas.unregister(extension_id);
+        self.custom_editors.unregister(extension_id);
+
+        // Nobody will answer the requests the extension was handling
+        for (request_id, state_id) in self.message_handlers.unregister(extension_id) {
+            self.sender
+                .send(ClientMessages::ServerMessage(
+                    ServerMessages::CustomResponse {
+                        state_id,
+                        request_id,
+                        result: Err(Errors::Ext(ExtensionErrors::MessageHandlerNotFound)),
+                    },
+                ))
+                .await
+                .ok();
+        }
+        for (invocation_id, state_id) in self.commands.unregister(extension_id) {
+            self.sender
+                .send(ClientMessages::ServerMessage(
+                    ServerMessages::CommandFinished {
+                        state_id,
+                        invocation_id,
+                        result: Err(Errors::Command(CommandErrors::CommandNotFound)),
+                    },
+                ))
+                .await
+                .ok();
+        }
+
+        found
+    }
+
+    /// Remember how an extension was loaded from the filesystem so it can be reloaded
+    pub fn set_source(&mut self, manifest: &Manifest, loader: ExtensionLoader) {
+        self.sources.insert(
+            manifest.info.extension.id.clone(),
+            ExtensionSource {
+                manifest: manifest.clone(),
+                loader,
+            },
+        );
+    }
+
+    /// Unload an extension, read again its manifest and instantiate it, the new instance still needs to be run
+    pub async fn reload_extension(
+        &mut self,
+        extension_id: &str,
+        state_id: u8,
+    ) -> Result<(), ExtensionErrors> {
+        let source = self
+            .sources
+            .get(extension_id)
+            .cloned()
+            .ok_or(ExtensionErrors::NotReloadable)?;
+
+        let manifest = Manifest::parse(&source.manifest.location)
+            .await
+            .map_err(|_| ExtensionErrors::BadManifest)?;
+
+        self.unregister(extension_id).await;
+        (source.loader)(self, &manifest, state_id);
+
+        // The loader might not remember the source
+        if !self.sources.contains_key(extension_id) {
+            self.set_source(&manifest, source.loader);
+        }
+
+        Ok(())
+    }
+
+    /// Deliver a message from an extension to another one, or to all the others.
+    /// Fails if the target extension is not loaded
+    pub fn send(&self, message: ExtensionMessage, state_id: u8) -> Result<(), ExtensionErrors> {
+        let mut delivered = false;
+
+        let recipients: Box<dyn Iterator<Item = ExtensionInstance>> = match &message.target {
+            MessageTarget::Extension(target) => Box::new(self.get_instances(target)),
+            MessageTarget::Broadcast => Box::new(
+                self.get_all_instances()
+                    .filter(|instance| message.is_for(instance.parent_id)),
+            ),
+        };
+
+        for instance in recipients {
+            delivered = true;
+
+            let plugin = instance.plugin.clone();
+            let health = instance.health.clone();
+            let parent_id = instance.parent_id.to_owned();
+            let sender = self.sender.clone();
+            let message = ClientMessages::ExtensionMessage {
+                state_id,
+                message: message.clone(),
+            };
+            let call = self
+                .telemetry
+                .timed(&parent_id, ExtensionHandler::Notify, move |plugin| {
+                    plugin.notify(message)
+                });
+            tokio::spawn(async move {
+                run_isolated(&parent_id, &plugin, &health, &sender, state_id, call)
+                    .await
+                    .ok();
+            });
+        }
+
+        if delivered || message.target == MessageTarget::Broadcast {
+            Ok(())
+        } else {
+            Err(ExtensionErrors::ExtensionNotFound)
+        }
+    }
+
     /// Load a extension
     pub fn register(&mut self, parent_id: &str, plugin: Box<dyn Extension + Send>) {
         let info = plugin.get_info();
+        self.subscriptions
+            .subscribe(parent_id, &plugin.get_message_filter());
+        self.message_handlers
+            .register(parent_id, &plugin.get_message_handlers());
+        self.settings_schemas
+            .register(parent_id, plugin.get_settings_schema());
+        self.experiments
+            .register(parent_id, plugin.get_experiments());
+        self.validation_schemas
+            .register(parent_id, plugin.get_validation_schemas());
+        self.custom_editors
+            .register(parent_id, plugin.get_custom_editors());
         let plugin = Arc::new(Mutex::new(plugin));
-        self.extensions.push(LoadedExtension::ExtensionInstance {
+        self.push(LoadedExtension::ExtensionInstance {
             plugin,
             info,
             parent_id: parent_id.to_string(),
+            health: Arc::new(Mutex::new(ExtensionHealth::default())),
         });
     }
 }
@@ -86,5 +457,6 @@ pub enum LoadedExtension {
         plugin: Arc<Mutex<Box<dyn Extension + Send>>>,
         info: ExtensionInfo,
         parent_id: String,
+        health: Arc<Mutex<ExtensionHealth>>,
     },
 }
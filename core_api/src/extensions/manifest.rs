@@ -3,6 +3,9 @@ use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 use tokio::fs::read_to_string;
 
+use super::permissions::ExtensionPermissions;
+use super::subscriptions::MessageFilter;
+
 /// Possible errors when trying to read a manifest file
 #[derive(PartialEq, Eq, Debug)]
 pub enum ManifestErrors {
@@ -25,6 +28,11 @@ pub struct ManifestExtension {
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug, Default)]
 pub struct ManifestInfo {
     pub extension: ManifestExtension,
+    #[serde(default)]
+    pub permissions: ExtensionPermissions,
+    /// Messages the extension wants to receive
+    #[serde(default)]
+    pub subscriptions: MessageFilter,
 }
 
 #[derive(Deserialize, PartialEq, Eq, Clone, Debug)]
@@ -0,0 +1,81 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::ExtensionErrors;
+
+/// Who a message between extensions is for
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum MessageTarget {
+    Extension(String),
+    /// Every other loaded extension
+    Broadcast,
+}
+
+/// A message from an extension to others, e.g the Git extension telling the current branch
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionMessage {
+    pub from: String,
+    pub target: MessageTarget,
+    pub payload: Value,
+}
+
+impl ExtensionMessage {
+    pub fn new(
+        from: &str,
+        target: MessageTarget,
+        payload: &impl Serialize,
+    ) -> Result<Self, ExtensionErrors> {
+        Ok(Self {
+            from: from.to_owned(),
+            target,
+            payload: serde_json::to_value(payload).map_err(|_| ExtensionErrors::BadPayload)?,
+        })
+    }
+
+    /// Read the payload as the type the sender used
+    pub fn get_payload<T: DeserializeOwned>(&self) -> Result<T, ExtensionErrors> {
+        serde_json::from_value(self.payload.clone()).map_err(|_| ExtensionErrors::BadPayload)
+    }
+
+    /// Check if an extension should receive the message, the sender never does
+    pub fn is_for(&self, extension_id: &str) -> bool {
+        match &self.target {
+            MessageTarget::Extension(target) => target == extension_id,
+            MessageTarget::Broadcast => self.from != extension_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{ExtensionMessage, MessageTarget};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Branch {
+        name: String,
+    }
+
+    #[test]
+    fn typed_payloads() {
+        let branch = Branch {
+            name: "main".to_owned(),
+        };
+        let message = ExtensionMessage::new(
+            "git",
+            MessageTarget::Extension("status".to_owned()),
+            &branch,
+        )
+        .unwrap();
+        assert!(message.is_for("status"));
+        assert!(!message.is_for("git"));
+        assert_eq!(message.get_payload::<Branch>().unwrap(), branch);
+        assert!(message.get_payload::<Vec<String>>().is_err());
+
+        let message = ExtensionMessage::new("git", MessageTarget::Broadcast, &branch).unwrap();
+        assert!(message.is_for("status"));
+        assert!(!message.is_for("git"));
+    }
+}
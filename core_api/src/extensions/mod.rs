@@ -1,14 +1,44 @@
 use serde::{Deserialize, Serialize};
 
 pub mod base;
+#[cfg(feature = "bundles")]
+pub mod bundles;
 pub mod client;
+pub mod commands;
+pub mod compatibility;
+pub mod editors;
+pub mod handlers;
+pub mod installation;
 pub mod manager;
 pub mod manifest;
+pub mod messages;
 pub mod modules;
+pub mod permissions;
+#[cfg(feature = "registry")]
+pub mod registry;
 pub mod settings;
+pub mod subscriptions;
+pub mod supervisor;
 
 /// Extensions errors
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum ExtensionErrors {
     ExtensionNotFound,
+    ExtensionCrashed,
+    PermissionDenied,
+    RegistryUnreachable,
+    ChecksumMismatch,
+    BadPackage,
+    BadManifest,
+    NotReloadable,
+    MessageHandlerNotFound,
+    BadPayload,
+    /// The bundle has no signature and unsigned bundles are not allowed
+    UnsignedBundle,
+    /// The bundle is signed by a publisher that is not trusted
+    UntrustedSignature,
+    BadSignature,
+    /// The bundle or its files are too big
+    BundleTooBig,
+    CustomEditorNotFound,
 }
@@ -1,3 +1,5 @@
 pub mod command;
+pub mod output_channel;
 pub mod popup;
 pub mod statusbar_item;
+pub mod webview_panel;
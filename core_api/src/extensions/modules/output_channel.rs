@@ -0,0 +1,40 @@
+use crate::extensions::client::ExtensionClient;
+use crate::messaging::{ClientMessages, ServerMessages};
+
+/// Named channel where an extension can log its output, its content can be searched
+#[derive(Clone)]
+pub struct OutputChannel {
+    pub id: String,
+    pub name: String,
+    client: ExtensionClient,
+    state_id: u8,
+}
+
+impl OutputChannel {
+    pub fn new(mut client: ExtensionClient, state_id: u8, name: &str) -> Self {
+        Self {
+            id: client.get_id(),
+            client,
+            state_id,
+            name: name.to_string(),
+        }
+    }
+
+    pub async fn append(&self, data: &str) {
+        self.client
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::OutputChannelUpdated {
+                    state_id: self.state_id,
+                    channel_id: self.id.clone(),
+                    name: self.name.clone(),
+                    data: data.to_string(),
+                },
+            ))
+            .await
+            .unwrap();
+    }
+
+    pub async fn append_line(&self, line: &str) {
+        self.append(&format!("{line}\n")).await
+    }
+}
@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::extensions::client::{EventActions, ExtensionClient};
+use crate::messaging::{ClientMessages, ServerMessages};
+
+/// What is rendered inside a panel
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum PanelContent {
+    /// A bundle of HTML, rendered in an isolated webview
+    Html { html: String },
+    /// A descriptor of UI components, rendered by the frontend itself
+    Declarative { ui: Value },
+}
+
+/// Panel with custom content, it can exchange messages with the extension
+#[derive(Clone)]
+pub struct WebviewPanel {
+    pub id: String,
+    title: String,
+    content: Arc<Mutex<PanelContent>>,
+    client: ExtensionClient,
+    state_id: u8,
+}
+
+impl WebviewPanel {
+    pub fn new(
+        mut client: ExtensionClient,
+        state_id: u8,
+        title: &str,
+        content: PanelContent,
+    ) -> Self {
+        Self {
+            id: client.get_id(),
+            client,
+            state_id,
+            title: title.to_string(),
+            content: Arc::new(Mutex::new(content)),
+        }
+    }
+
+    pub async fn show(&self) {
+        self.client
+            .send(ClientMessages::ServerMessage(ServerMessages::ShowPanel {
+                state_id: self.state_id,
+                panel_id: self.id.clone(),
+                title: self.title.clone(),
+                content: self.content.lock().await.clone(),
+            }))
+            .await
+            .unwrap();
+    }
+
+    pub async fn close(&self) {
+        self.client
+            .send(ClientMessages::ServerMessage(ServerMessages::ClosePanel {
+                state_id: self.state_id,
+                panel_id: self.id.clone(),
+            }))
+            .await
+            .unwrap();
+    }
+
+    pub async fn set_content(&mut self, content: PanelContent) {
+        *self.content.lock().await = content;
+
+        self.show().await
+    }
+
+    /// Send a message to the content of the panel
+    pub async fn post_message(&self, data: Value) {
+        self.client
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::MessageToPanel {
+                    state_id: self.state_id,
+                    panel_id: self.id.clone(),
+                    data,
+                },
+            ))
+            .await
+            .unwrap();
+    }
+
+    /// Listen for messages sent by the content of the panel
+    pub async fn on_message(&mut self, callback: impl Fn(Value) + 'static + Send) {
+        let mut event_actions = self.client.event_actions.lock().await;
+        event_actions.push(EventActions::OnMessageCallback {
+            id_owner: self.id.clone(),
+            callback: Box::new(callback),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::json;
+    use tokio::sync::mpsc::channel;
+
+    use super::{PanelContent, WebviewPanel};
+    use crate::extensions::client::ExtensionClient;
+    use crate::messaging::{ClientMessages, ServerMessages, UIEvent};
+
+    #[tokio::test]
+    async fn exchange_messages_with_panels() {
+        let (sender, mut receiver) = channel::<ClientMessages>(5);
+        let mut client = ExtensionClient::new("sample", "Sample", sender, None);
+        let mut panel = WebviewPanel::new(
+            client.clone(),
+            1,
+            "Preview",
+            PanelContent::Html {
+                html: "<h1>Hello</h1>".to_string(),
+            },
+        );
+
+        let mut get_message = || match receiver.try_recv() {
+            Ok(ClientMessages::ServerMessage(message)) => message,
+            other => panic!("Expected a message for the clients, got {:?}", other),
+        };
+
+        panel.show().await;
+        assert_eq!(
+            get_message(),
+            ServerMessages::ShowPanel {
+                state_id: 1,
+                panel_id: panel.id.clone(),
+                title: "Preview".to_string(),
+                content: PanelContent::Html {
+                    html: "<h1>Hello</h1>".to_string()
+                },
+            }
+        );
+
+        // Changing the content shows the panel again
+        let ui = json!({ "component": "button", "text": "Run" });
+        panel
+            .set_content(PanelContent::Declarative { ui: ui.clone() })
+            .await;
+        assert!(matches!(
+            get_message(),
+            ServerMessages::ShowPanel { content: PanelContent::Declarative { ui: content }, .. } if content == ui
+        ));
+
+        panel.post_message(json!({ "count": 1 })).await;
+        assert_eq!(
+            get_message(),
+            ServerMessages::MessageToPanel {
+                state_id: 1,
+                panel_id: panel.id.clone(),
+                data: json!({ "count": 1 }),
+            }
+        );
+
+        panel.close().await;
+        assert!(matches!(get_message(), ServerMessages::ClosePanel { .. }));
+
+        // Only the messages sent by its own content are received
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let panel_received = received.clone();
+        panel
+            .on_message(move |data| panel_received.lock().unwrap().push(data))
+            .await;
+
+        for panel_id in [panel.id.clone(), "Sample/other".to_string()] {
+            client
+                .process_message(&ClientMessages::UIEvent(UIEvent::PanelMessage {
+                    state_id: 1,
+                    panel_id,
+                    data: json!("clicked"),
+                }))
+                .await;
+        }
+        assert_eq!(*received.lock().unwrap(), vec![json!("clicked")]);
+    }
+
+    #[test]
+    fn serialize_panel_content() {
+        let content = PanelContent::Html {
+            html: "<p></p>".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&content).unwrap(),
+            json!({ "type": "Html", "html": "<p></p>" })
+        );
+    }
+}
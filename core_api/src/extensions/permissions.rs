@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use super::ExtensionErrors;
+
+/// Permissions requested in the [permissions] section of a manifest
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug, Default)]
+pub struct ExtensionPermissions {
+    /// Paths (and their children) that can be read
+    #[serde(default)]
+    pub read: Vec<String>,
+    /// Paths (and their children) that can be written
+    #[serde(default)]
+    pub write: Vec<String>,
+    /// Spawn processes
+    #[serde(default)]
+    pub run: bool,
+    /// Hosts that can be reached, `*` means any
+    #[serde(default)]
+    pub net: Vec<String>,
+}
+
+/// A specific access an extension needs
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub enum Permission {
+    Read(String),
+    Write(String),
+    Run,
+    Net(String),
+}
+
+/// Resolve the `.` and `..` components without touching the filesystem,
+/// so `/workspace/../etc` can't pass as a child of `/workspace`
+fn normalize_path(path: &str) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn allows_path(paths: &[String], path: &str) -> bool {
+    let path = normalize_path(path);
+    paths
+        .iter()
+        .any(|allowed| allowed == "*" || path.starts_with(normalize_path(allowed)))
+}
+
+impl ExtensionPermissions {
+    /// Full access, used by the built-in extensions
+    pub fn all() -> Self {
+        Self {
+            read: vec!["*".to_string()],
+            write: vec!["*".to_string()],
+            run: true,
+            net: vec!["*".to_string()],
+        }
+    }
+
+    pub fn allows(&self, permission: &Permission) -> bool {
+        match permission {
+            Permission::Read(path) => allows_path(&self.read, path),
+            Permission::Write(path) => allows_path(&self.write, path),
+            Permission::Run => self.run,
+            Permission::Net(host) => self
+                .net
+                .iter()
+                .any(|allowed| allowed == "*" || allowed == host),
+        }
+    }
+
+    pub fn grant(&mut self, permission: Permission) {
+        if self.allows(&permission) {
+            return;
+        }
+        match permission {
+            Permission::Read(path) => self.read.push(path),
+            Permission::Write(path) => self.write.push(path),
+            Permission::Run => self.run = true,
+            Permission::Net(host) => self.net.push(host),
+        }
+    }
+
+    pub fn revoke(&mut self, permission: &Permission) {
+        match permission {
+            Permission::Read(path) => self.read.retain(|allowed| allowed != path),
+            Permission::Write(path) => self.write.retain(|allowed| allowed != path),
+            Permission::Run => self.run = false,
+            Permission::Net(host) => self.net.retain(|allowed| allowed != host),
+        }
+    }
+}
+
+/// Permissions of every extension, shared between the manager and the extension clients
+#[derive(Clone)]
+pub struct PermissionsRegistry {
+    permissions: Arc<RwLock<HashMap<String, ExtensionPermissions>>>,
+    changes: Arc<watch::Sender<()>>,
+}
+
+impl Default for PermissionsRegistry {
+    fn default() -> Self {
+        let (changes, _) = watch::channel(());
+        Self {
+            permissions: Arc::default(),
+            changes: Arc::new(changes),
+        }
+    }
+}
+
+impl PermissionsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get notified whenever the permissions of any extension change,
+    /// e.g so running workers can apply a revoke right away
+    pub fn subscribe(&self) -> watch::Receiver<()> {
+        self.changes.subscribe()
+    }
+
+    fn notify_change(&self) {
+        self.changes.send_replace(());
+    }
+
+    pub fn set(&self, extension_id: &str, permissions: ExtensionPermissions) {
+        self.permissions
+            .write()
+            .unwrap()
+            .insert(extension_id.to_owned(), permissions);
+        self.notify_change();
+    }
+
+    pub fn remove(&self, extension_id: &str) {
+        self.permissions.write().unwrap().remove(extension_id);
+        self.notify_change();
+    }
+
+    pub fn get(&self, extension_id: &str) -> Option<ExtensionPermissions> {
+        self.permissions.read().unwrap().get(extension_id).cloned()
+    }
+
+    /// Make sure an extension has a permission
+    pub fn check(
+        &self,
+        extension_id: &str,
+        permission: &Permission,
+    ) -> Result<(), ExtensionErrors> {
+        let permissions = self.permissions.read().unwrap();
+        match permissions.get(extension_id) {
+            Some(permissions) if permissions.allows(permission) => Ok(()),
+            _ => Err(ExtensionErrors::PermissionDenied),
+        }
+    }
+
+    pub fn grant(&self, extension_id: &str, permission: Permission) -> Result<(), ExtensionErrors> {
+        {
+            let mut permissions = self.permissions.write().unwrap();
+            let permissions = permissions
+                .get_mut(extension_id)
+                .ok_or(ExtensionErrors::ExtensionNotFound)?;
+            permissions.grant(permission);
+        }
+        self.notify_change();
+        Ok(())
+    }
+
+    pub fn revoke(
+        &self,
+        extension_id: &str,
+        permission: &Permission,
+    ) -> Result<(), ExtensionErrors> {
+        {
+            let mut permissions = self.permissions.write().unwrap();
+            let permissions = permissions
+                .get_mut(extension_id)
+                .ok_or(ExtensionErrors::ExtensionNotFound)?;
+            permissions.revoke(permission);
+        }
+        self.notify_change();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExtensionPermissions, Permission, PermissionsRegistry};
+    use crate::ExtensionErrors;
+
+    #[test]
+    fn check_permissions() {
+        let registry = PermissionsRegistry::new();
+        registry.set(
+            "sample",
+            ExtensionPermissions {
+                read: vec!["/workspace".to_string()],
+                ..Default::default()
+            },
+        );
+
+        let read_inside = Permission::Read("/workspace/src/main.rs".to_string());
+        let read_outside = Permission::Read("/etc/passwd".to_string());
+
+        assert!(registry.check("sample", &read_inside).is_ok());
+        assert!(registry
+            .check("sample", &Permission::Read("/workspace/./src".to_string()))
+            .is_ok());
+        assert_eq!(
+            registry.check(
+                "sample",
+                &Permission::Read("/workspace/../../etc/passwd".to_string())
+            ),
+            Err(ExtensionErrors::PermissionDenied)
+        );
+        assert_eq!(
+            registry.check("sample", &read_outside),
+            Err(ExtensionErrors::PermissionDenied)
+        );
+        assert!(registry.check("sample", &Permission::Run).is_err());
+
+        registry.grant("sample", Permission::Run).unwrap();
+        assert!(registry.check("sample", &Permission::Run).is_ok());
+
+        registry
+            .revoke("sample", &Permission::Read("/workspace".to_string()))
+            .unwrap();
+        assert!(registry.check("sample", &read_inside).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tracing::info;
+
+use super::installation::{get_extension_folder, replace_extension_files};
+use super::manifest::ManifestInfo;
+use super::ExtensionErrors;
+use crate::http::HttpClient;
+use crate::Manifest;
+
+/// An extension published in a registry
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RegistryExtension {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub author: String,
+    pub description: Option<String>,
+    /// Where to download the package (a zip containing the Graviton.toml)
+    pub download_url: String,
+    /// SHA-256 checksum of the package, hex encoded
+    pub checksum: String,
+}
+
+/// Make sure the downloaded package is the published one
+pub fn verify_checksum(package: &[u8], checksum: &str) -> Result<(), ExtensionErrors> {
+    let digest = hex::encode(Sha256::digest(package));
+    if digest.eq_ignore_ascii_case(checksum) {
+        Ok(())
+    } else {
+        Err(ExtensionErrors::ChecksumMismatch)
+    }
+}
+
+/// Extract a package into the given directory, it must contain a valid manifest
+fn unpack(package: Vec<u8>, destination: &Path) -> Result<(), ExtensionErrors> {
+    let mut archive =
+        zip::ZipArchive::new(Cursor::new(package)).map_err(|_| ExtensionErrors::BadPackage)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive
+            .by_index(i)
+            .map_err(|_| ExtensionErrors::BadPackage)?;
+
+        // Ignore entries that would end up outside the destination
+        let path = match file.enclosed_name() {
+            Some(path) => destination.join(path),
+            None => continue,
+        };
+
+        if file.is_dir() {
+            std::fs::create_dir_all(&path).map_err(|_| ExtensionErrors::BadPackage)?;
+        } else {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|_| ExtensionErrors::BadPackage)?;
+            }
+            let mut output =
+                std::fs::File::create(&path).map_err(|_| ExtensionErrors::BadPackage)?;
+            std::io::copy(&mut file, &mut output).map_err(|_| ExtensionErrors::BadPackage)?;
+        }
+    }
+
+    std::fs::read_to_string(destination.join("Graviton.toml"))
+        .ok()
+        .and_then(|manifest| toml::from_str::<ManifestInfo>(&manifest).ok())
+        .ok_or(ExtensionErrors::BadPackage)?;
+
+    Ok(())
+}
+
+/// Client for a remote extensions registry
+///
+/// The registry must expose:
+/// - `GET /extensions?query=<text>` returning a list of [`RegistryExtension`]
+/// - `GET /extensions/<id>` returning a [`RegistryExtension`]
+#[derive(Clone)]
+pub struct RegistryClient {
+    url: String,
+    extensions_path: PathBuf,
+    http: HttpClient,
+}
+
+impl RegistryClient {
+    /// Create a client for a registry
+    ///
+    /// # Arguments
+    ///
+    /// * `url`               - Base URL of the registry
+    /// * `extensions_path`   - Directory where the extensions are installed
+    ///
+    pub fn new(url: &str, extensions_path: PathBuf) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_owned(),
+            extensions_path,
+            http: HttpClient::default(),
+        }
+    }
+
+    /// Make the requests with the given client, e.g the one of the State
+    pub fn with_http_client(mut self, http: HttpClient) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Directory where the given extension is installed, the ID must be a valid folder name
+    pub fn get_extension_path(&self, extension_id: &str) -> Result<PathBuf, ExtensionErrors> {
+        get_extension_folder(&self.extensions_path, extension_id)
+    }
+
+    /// Search extensions in the registry
+    pub async fn search(&self, query: &str) -> Result<Vec<RegistryExtension>, ExtensionErrors> {
+        self.http
+            .get(&format!("{}/extensions", self.url))
+            .map_err(|_| ExtensionErrors::RegistryUnreachable)?
+            .query(&[("query", query)])
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|_| ExtensionErrors::RegistryUnreachable)?
+            .json()
+            .await
+            .map_err(|_| ExtensionErrors::RegistryUnreachable)
+    }
+
+    /// Retrieve an extension from the registry
+    pub async fn get(&self, extension_id: &str) -> Result<RegistryExtension, ExtensionErrors> {
+        let res = self
+            .http
+            .get(&format!("{}/extensions/{}", self.url, extension_id))
+            .map_err(|_| ExtensionErrors::RegistryUnreachable)?
+            .send()
+            .await
+            .map_err(|_| ExtensionErrors::RegistryUnreachable)?;
+
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(ExtensionErrors::ExtensionNotFound);
+        }
+
+        res.error_for_status()
+            .map_err(|_| ExtensionErrors::RegistryUnreachable)?
+            .json()
+            .await
+            .map_err(|_| ExtensionErrors::RegistryUnreachable)
+    }
+
+    /// Download, verify and unpack an extension, replacing any installed version
+    pub async fn install(&self, extension_id: &str) -> Result<Manifest, ExtensionErrors> {
+        self.get_extension_path(extension_id)?;
+        let extension = self.get(extension_id).await?;
+
+        let package = self
+            .http
+            .get(&extension.download_url)
+            .map_err(|_| ExtensionErrors::RegistryUnreachable)?
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|_| ExtensionErrors::RegistryUnreachable)?
+            .bytes()
+            .await
+            .map_err(|_| ExtensionErrors::RegistryUnreachable)?;
+
+        verify_checksum(&package, &extension.checksum)?;
+
+        let extensions_path = self.extensions_path.clone();
+        let id = extension_id.to_owned();
+        let package = package.to_vec();
+        let destination = tokio::task::spawn_blocking(move || {
+            replace_extension_files(&extensions_path, &id, |staging| unpack(package, staging))
+        })
+        .await
+        .map_err(|_| ExtensionErrors::BadPackage)??;
+
+        let manifest = Manifest::parse(&destination.join("Graviton.toml"))
+            .await
+            .map_err(|_| ExtensionErrors::BadPackage)?;
+
+        info!(
+            "Installed extension <{}> v{}",
+            extension_id, extension.version
+        );
+
+        Ok(manifest)
+    }
+
+    /// Return the installed version of an extension
+    pub async fn get_installed_version(&self, extension_id: &str) -> Option<String> {
+        let manifest_path = self
+            .get_extension_path(extension_id)
+            .ok()?
+            .join("Graviton.toml");
+        let manifest = Manifest::parse(&manifest_path).await.ok()?;
+        Some(manifest.info.extension.version)
+    }
+
+    /// Install the published version of an extension if it's different from the installed one
+    pub async fn update(&self, extension_id: &str) -> Result<Option<Manifest>, ExtensionErrors> {
+        let extension = self.get(extension_id).await?;
+
+        if self.get_installed_version(extension_id).await == Some(extension.version) {
+            Ok(None)
+        } else {
+            self.install(extension_id).await.map(Some)
+        }
+    }
+
+    /// Remove the files of an installed extension
+    pub async fn uninstall(&self, extension_id: &str) -> Result<(), ExtensionErrors> {
+        fs::remove_dir_all(self.get_extension_path(extension_id)?)
+            .await
+            .map_err(|_| ExtensionErrors::ExtensionNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify_checksum;
+    use crate::ExtensionErrors;
+
+    #[test]
+    fn verify_package_checksum() {
+        let checksum = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+        assert!(verify_checksum(b"hello", checksum).is_ok());
+        assert!(verify_checksum(b"hello", &checksum.to_uppercase()).is_ok());
+        assert_eq!(
+            verify_checksum(b"bye", checksum),
+            Err(ExtensionErrors::ChecksumMismatch)
+        );
+    }
+}
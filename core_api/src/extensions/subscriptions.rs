@@ -0,0 +1,205 @@
+use std::collections::{HashMap, HashSet};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::messaging::ClientMessages;
+
+/// Messages that are always delivered, no matter the filters
+const LIFECYCLE_MESSAGES: [&str; 1] = ["unload"];
+
+/// What messages an extension is interested in, empty lists mean all
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct MessageFilter {
+    /// Names of the messages, e.g `listDir` or `ui`
+    #[serde(default)]
+    pub messages: Vec<String>,
+    /// Names of the filesystems
+    #[serde(default)]
+    pub filesystems: Vec<String>,
+    /// Globs of the paths, e.g `**/*.rs`
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+impl MessageFilter {
+    /// Receive every message
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Only receive the messages with the given names
+    pub fn messages(messages: &[&str]) -> Self {
+        Self {
+            messages: messages.iter().map(|name| name.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_filesystems(mut self, filesystems: &[&str]) -> Self {
+        self.filesystems = filesystems.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    pub fn with_paths(mut self, paths: &[&str]) -> Self {
+        self.paths = paths.iter().map(|glob| glob.to_string()).collect();
+        self
+    }
+}
+
+/// A filter ready to be matched against messages
+#[derive(Clone, Debug)]
+struct Subscription {
+    filesystems: Vec<String>,
+    paths: Option<GlobSet>,
+}
+
+impl Subscription {
+    fn new(filter: &MessageFilter) -> Self {
+        let paths = if filter.paths.is_empty() {
+            None
+        } else {
+            let mut builder = GlobSetBuilder::new();
+            for path in &filter.paths {
+                match Glob::new(path) {
+                    Ok(glob) => {
+                        builder.add(glob);
+                    }
+                    Err(_) => warn!("Ignoring invalid glob <{}> in a message filter", path),
+                }
+            }
+            builder.build().ok()
+        };
+
+        Self {
+            filesystems: filter.filesystems.clone(),
+            paths,
+        }
+    }
+
+    /// Messages without filesystem or path pass those filters
+    fn matches(&self, message: &ClientMessages) -> bool {
+        if let Some(filesystem) = message.get_filesystem() {
+            if !self.filesystems.is_empty() && !self.filesystems.iter().any(|fs| fs == filesystem) {
+                return false;
+            }
+        }
+
+        if let (Some(path), Some(paths)) = (message.get_path(), &self.paths) {
+            if !paths.is_match(path) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Index of the extensions by the messages they are interested in
+#[derive(Clone, Debug, Default)]
+pub struct SubscriptionsIndex {
+    /// Extensions interested in any message
+    any_message: HashSet<String>,
+    /// Extensions by the name of the messages they are interested in
+    by_message: HashMap<String, HashSet<String>>,
+    /// Filters of every extension, an extension might have several instances
+    subscriptions: HashMap<String, Vec<Subscription>>,
+}
+
+impl SubscriptionsIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&mut self, extension_id: &str, filter: &MessageFilter) {
+        if filter.messages.is_empty() {
+            self.any_message.insert(extension_id.to_owned());
+        } else {
+            for message in &filter.messages {
+                self.by_message
+                    .entry(message.clone())
+                    .or_default()
+                    .insert(extension_id.to_owned());
+            }
+        }
+
+        self.subscriptions
+            .entry(extension_id.to_owned())
+            .or_default()
+            .push(Subscription::new(filter));
+    }
+
+    pub fn unsubscribe(&mut self, extension_id: &str) {
+        self.any_message.remove(extension_id);
+        self.by_message.retain(|_, extensions| {
+            extensions.remove(extension_id);
+            !extensions.is_empty()
+        });
+        self.subscriptions.remove(extension_id);
+    }
+
+    /// Return the IDs of the extensions that should receive the message
+    pub fn get_subscribers(&self, message: &ClientMessages) -> HashSet<&str> {
+        let name = message.get_name();
+
+        if LIFECYCLE_MESSAGES.contains(&name) {
+            return self.subscriptions.keys().map(|id| id.as_str()).collect();
+        }
+
+        self.any_message
+            .iter()
+            .chain(self.by_message.get(name).into_iter().flatten())
+            .filter(|extension_id| {
+                self.subscriptions
+                    .get(*extension_id)
+                    .map(|subscriptions| {
+                        subscriptions
+                            .iter()
+                            .any(|subscription| subscription.matches(message))
+                    })
+                    .unwrap_or(true)
+            })
+            .map(|extension_id| extension_id.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MessageFilter, SubscriptionsIndex};
+    use crate::messaging::ClientMessages;
+
+    #[test]
+    fn only_notify_interested_extensions() {
+        let mut index = SubscriptionsIndex::new();
+        index.subscribe("everything", &MessageFilter::all());
+        index.subscribe(
+            "rust",
+            &MessageFilter::messages(&["listDir"])
+                .with_filesystems(&["local"])
+                .with_paths(&["**/src"]),
+        );
+        index.subscribe("nothing", &MessageFilter::messages(&["ui"]));
+
+        let message = ClientMessages::ListDir(
+            0,
+            "local".to_string(),
+            "/project/src".to_string(),
+            Ok(vec![]),
+        );
+        let subscribers = index.get_subscribers(&message);
+        assert_eq!(subscribers.len(), 2);
+        assert!(subscribers.contains("everything"));
+        assert!(subscribers.contains("rust"));
+
+        let message =
+            ClientMessages::ListDir(0, "ftp".to_string(), "/project/src".to_string(), Ok(vec![]));
+        assert!(!index.get_subscribers(&message).contains("rust"));
+
+        assert_eq!(index.get_subscribers(&ClientMessages::Unload(0)).len(), 3);
+
+        index.unsubscribe("everything");
+        assert!(index.get_subscribers(&message).is_empty());
+    }
+}
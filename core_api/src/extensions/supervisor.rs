@@ -0,0 +1,238 @@
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::Mutex;
+use tracing::{error, warn};
+
+use crate::extensions::base::Extension;
+use crate::messaging::ClientMessages;
+use crate::ExtensionErrors;
+
+/// How many times an extension is automatically restarted before it's disabled
+const MAX_AUTOMATIC_RESTARTS: u64 = 3;
+
+/// Extensions running this long without crashing can be automatically restarted again
+const HEALTHY_RUNTIME: Duration = Duration::from_secs(10 * 60);
+
+/// Runtime status of an extension instance
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExtensionStatus {
+    /// Running normally
+    #[default]
+    Running,
+    /// Panicked, it won't be called again until it's restarted
+    Failed,
+}
+
+/// What to do when an extension panics
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicPolicy {
+    /// Stop calling it until it's manually restarted
+    #[default]
+    Disable,
+    /// Restart it automatically, a few times at most
+    Restart,
+    /// Keep calling it as if nothing happened, for known-flaky extensions
+    Ignore,
+}
+
+/// Counters about the health of an extension
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExtensionMetrics {
+    pub panics: u64,
+    pub ignored_panics: u64,
+    pub restarts: u64,
+}
+
+/// Supervision data of an extension instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExtensionHealth {
+    pub status: ExtensionStatus,
+    pub policy: PanicPolicy,
+    pub metrics: ExtensionMetrics,
+    /// Restarts made by the panic policy since it last ran for [`HEALTHY_RUNTIME`] without crashing,
+    /// unlike `metrics.restarts` it doesn't count the manual ones
+    pub automatic_restarts: u64,
+    pub last_crash: Option<Instant>,
+}
+
+/// Extract a readable message from a panic payload
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic".to_string()
+    }
+}
+
+/// Run a call on an extension instance, isolating the Core from any panic it might throw.
+/// If the extension panics its panic policy decides if it's disabled, restarted or ignored.
+///
+/// # Arguments
+///
+/// * `extension_id` - The ID of the extension
+/// * `plugin`       - The extension instance
+/// * `health`       - The supervision data of the extension instance
+/// * `sender`       - A sender to the Core
+/// * `state_id`     - The State in which the extension is running
+/// * `call`         - What to do with the extension
+///
+pub async fn run_isolated(
+    extension_id: &str,
+    plugin: &Arc<Mutex<Box<dyn Extension + Send>>>,
+    health: &Arc<Mutex<ExtensionHealth>>,
+    sender: &Sender<ClientMessages>,
+    state_id: u8,
+    call: impl FnOnce(&mut Box<dyn Extension + Send>) + Send,
+) -> Result<(), ExtensionErrors> {
+    if health.lock().await.status == ExtensionStatus::Failed {
+        return Err(ExtensionErrors::ExtensionCrashed);
+    }
+
+    let mut ext_plugin = plugin.lock().await;
+    let result = catch_unwind(AssertUnwindSafe(|| call(&mut ext_plugin)));
+    drop(ext_plugin);
+
+    if let Err(payload) = result {
+        let message = panic_message(&*payload);
+
+        let mut health = health.lock().await;
+        health.metrics.panics += 1;
+
+        if health.policy == PanicPolicy::Ignore {
+            health.metrics.ignored_panics += 1;
+            warn!(
+                "Extension <{}> crashed and it was ignored, error: {}",
+                extension_id, message
+            );
+            return Err(ExtensionErrors::ExtensionCrashed);
+        }
+
+        error!("Extension <{}> crashed, error: {}", extension_id, message);
+
+        health.status = ExtensionStatus::Failed;
+
+        if health
+            .last_crash
+            .is_some_and(|last_crash| last_crash.elapsed() >= HEALTHY_RUNTIME)
+        {
+            health.automatic_restarts = 0;
+        }
+        health.last_crash = Some(Instant::now());
+
+        let restart = health.policy == PanicPolicy::Restart
+            && health.automatic_restarts < MAX_AUTOMATIC_RESTARTS;
+        if restart {
+            health.automatic_restarts += 1;
+        }
+        drop(health);
+
+        sender
+            .send(ClientMessages::ExtensionCrashed {
+                state_id,
+                extension_id: extension_id.to_owned(),
+                message,
+            })
+            .await
+            .ok();
+
+        if restart {
+            sender
+                .send(ClientMessages::RestartExtension {
+                    state_id,
+                    extension_id: extension_id.to_owned(),
+                })
+                .await
+                .ok();
+        }
+
+        Err(ExtensionErrors::ExtensionCrashed)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    use tokio::sync::mpsc::{channel, Receiver, Sender};
+    use tokio::sync::Mutex;
+
+    use super::{run_isolated, ExtensionHealth, ExtensionStatus, PanicPolicy, HEALTHY_RUNTIME};
+    use crate::extensions::base::{Extension, ExtensionInfo};
+    use crate::messaging::ClientMessages;
+    use crate::State;
+
+    struct CrashingExtension;
+
+    impl Extension for CrashingExtension {
+        fn init(&mut self, _state: Arc<Mutex<State>>) {}
+
+        fn unload(&mut self) {}
+
+        fn notify(&mut self, _message: ClientMessages) {
+            panic!("crashed");
+        }
+
+        fn get_info(&self) -> ExtensionInfo {
+            ExtensionInfo {
+                id: "crashing".to_string(),
+                name: "Crashing".to_string(),
+            }
+        }
+    }
+
+    /// Crash the extension, returns if it was restarted automatically
+    async fn crash(
+        plugin: &Arc<Mutex<Box<dyn Extension + Send>>>,
+        health: &Arc<Mutex<ExtensionHealth>>,
+        sender: &Sender<ClientMessages>,
+        receiver: &mut Receiver<ClientMessages>,
+    ) -> bool {
+        {
+            let mut health = health.lock().await;
+            health.status = ExtensionStatus::Running;
+            // Manual restarts don't count
+            health.metrics.restarts += 10;
+        }
+        run_isolated("crashing", plugin, health, sender, 1, |plugin| {
+            plugin.notify(ClientMessages::ListenToState { state_id: 1 })
+        })
+        .await
+        .ok();
+
+        let mut restarted = false;
+        while let Ok(message) = receiver.try_recv() {
+            restarted |= matches!(message, ClientMessages::RestartExtension { .. });
+        }
+        restarted
+    }
+
+    #[tokio::test]
+    async fn limit_automatic_restarts() {
+        let (sender, mut receiver) = channel(32);
+        let plugin: Arc<Mutex<Box<dyn Extension + Send>>> =
+            Arc::new(Mutex::new(Box::new(CrashingExtension)));
+        let health = Arc::new(Mutex::new(ExtensionHealth {
+            policy: PanicPolicy::Restart,
+            ..ExtensionHealth::default()
+        }));
+
+        for _ in 0..3 {
+            assert!(crash(&plugin, &health, &sender, &mut receiver).await);
+        }
+        assert!(!crash(&plugin, &health, &sender, &mut receiver).await);
+
+        // It's restarted again after running fine for a while
+        health.lock().await.last_crash = Instant::now().checked_sub(HEALTHY_RUNTIME);
+        assert!(crash(&plugin, &health, &sender, &mut receiver).await);
+    }
+}
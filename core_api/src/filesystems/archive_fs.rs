@@ -0,0 +1,259 @@
+use std::io::{Cursor, Read};
+use std::path::{Component, Path};
+
+use async_trait::async_trait;
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
+
+use super::{
+    DirItemInfo, FileInfo, Filesystem, FilesystemCapabilities, FilesystemErrors, MemoryFilesystem,
+};
+use crate::Errors;
+
+/// Formats of the archives that can be browsed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Guess the format of an archive from its first bytes
+    pub fn detect(content: &[u8]) -> Option<Self> {
+        if content.starts_with(b"PK\x03\x04") || content.starts_with(b"PK\x05\x06") {
+            Some(Self::Zip)
+        } else if content.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::TarGz)
+        } else if content.get(257..262) == Some(&b"ustar"[..]) {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+
+    /// Prefix of the name the archive filesystems are registered with, e.g `zip:/home/user/code.zip`
+    pub fn get_prefix(&self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::Tar | Self::TarGz => "tar",
+        }
+    }
+}
+
+fn bad_archive<T>(_: T) -> Errors {
+    Errors::Fs(FilesystemErrors::BadArchive)
+}
+
+/// Path of an entry inside the archive, none if it would end up outside of it
+fn get_entry_path(path: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(format!("/{}", parts.join("/")))
+    }
+}
+
+/// Path of an entry and its content, folders have no content
+type ArchiveEntry = (String, Option<Vec<u8>>);
+
+/// Read all the entries of an archive
+fn read_entries(kind: ArchiveKind, content: Vec<u8>) -> Result<Vec<ArchiveEntry>, Errors> {
+    let mut entries = Vec::new();
+
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = ZipArchive::new(Cursor::new(content)).map_err(bad_archive)?;
+            for i in 0..archive.len() {
+                let mut file = archive.by_index(i).map_err(bad_archive)?;
+                let path = match file.enclosed_name().and_then(get_entry_path) {
+                    Some(path) => path,
+                    None => continue,
+                };
+
+                if file.is_dir() {
+                    entries.push((path, None));
+                } else {
+                    let mut content = Vec::new();
+                    file.read_to_end(&mut content).map_err(bad_archive)?;
+                    entries.push((path, Some(content)));
+                }
+            }
+        }
+        ArchiveKind::Tar | ArchiveKind::TarGz => {
+            let reader: Box<dyn Read> = if kind == ArchiveKind::TarGz {
+                Box::new(GzDecoder::new(Cursor::new(content)))
+            } else {
+                Box::new(Cursor::new(content))
+            };
+            let mut archive = tar::Archive::new(reader);
+            for entry in archive.entries().map_err(bad_archive)? {
+                let mut entry = entry.map_err(bad_archive)?;
+                let path = match entry.path().ok().as_deref().and_then(get_entry_path) {
+                    Some(path) => path,
+                    None => continue,
+                };
+
+                let entry_type = entry.header().entry_type();
+                if entry_type.is_dir() {
+                    entries.push((path, None));
+                } else if entry_type.is_file() {
+                    let mut content = Vec::new();
+                    entry.read_to_end(&mut content).map_err(bad_archive)?;
+                    entries.push((path, Some(content)));
+                }
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Read-only filesystem with the content of a zip or tar archive, its root is `/`
+pub struct ArchiveFilesystem {
+    kind: ArchiveKind,
+    files: MemoryFilesystem,
+}
+
+impl ArchiveFilesystem {
+    /// Load an archive, its format is detected from the content
+    pub async fn from_bytes(content: Vec<u8>) -> Result<Self, Errors> {
+        let kind = ArchiveKind::detect(&content).ok_or(Errors::Fs(FilesystemErrors::BadArchive))?;
+        let files = MemoryFilesystem::new();
+
+        for (path, content) in read_entries(kind, content)? {
+            match content {
+                Some(content) => files.write_bytes_by_path(&path, &content).await?,
+                None => files.create_dir_by_path(&path).await?,
+            }
+        }
+
+        Ok(Self { kind, files })
+    }
+
+    pub fn get_kind(&self) -> ArchiveKind {
+        self.kind
+    }
+}
+
+#[async_trait]
+impl Filesystem for ArchiveFilesystem {
+    /// Archives don't change once they are opened
+    fn capabilities(&self) -> FilesystemCapabilities {
+        FilesystemCapabilities {
+            read_only: true,
+            ..FilesystemCapabilities::default()
+        }
+    }
+
+    /// Read a file of the archive
+    async fn read_file_by_path(&self, path: &str) -> Result<FileInfo, Errors> {
+        self.files.read_file_by_path(path).await
+    }
+
+    /// Archives can't be modified
+    async fn write_file_by_path(&self, _path: &str, _content: &str) -> Result<(), Errors> {
+        Err(Errors::Fs(FilesystemErrors::ReadOnly))
+    }
+
+    /// List a folder of the archive
+    async fn list_dir_by_path(&self, path: &str) -> Result<Vec<DirItemInfo>, Errors> {
+        self.files.list_dir_by_path(path).await
+    }
+
+    async fn read_bytes_by_path(&self, path: &str) -> Result<Vec<u8>, Errors> {
+        self.files.read_bytes_by_path(path).await
+    }
+
+    /// Archives can't be modified
+    async fn write_bytes_by_path(&self, _path: &str, _content: &[u8]) -> Result<(), Errors> {
+        Err(Errors::Fs(FilesystemErrors::ReadOnly))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    use super::{ArchiveFilesystem, ArchiveKind};
+    use crate::filesystems::{Filesystem, FilesystemErrors};
+    use crate::Errors;
+
+    #[tokio::test]
+    async fn browse_zip_archives() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("src/main.rs", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"fn main() {}").unwrap();
+        writer
+            .start_file("readme.md", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"Hello").unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+
+        let filesystem = ArchiveFilesystem::from_bytes(archive).await.unwrap();
+        assert_eq!(filesystem.get_kind(), ArchiveKind::Zip);
+
+        let items = filesystem.list_dir_by_path("/").await.unwrap();
+        assert_eq!(items.len(), 2);
+        assert!(items
+            .iter()
+            .any(|item| item.path == "/src" && !item.is_file));
+
+        assert_eq!(
+            filesystem
+                .read_file_by_path("/src/main.rs")
+                .await
+                .unwrap()
+                .content,
+            "fn main() {}"
+        );
+        assert_eq!(
+            filesystem.write_file_by_path("/readme.md", "Bye").await,
+            Err(Errors::Fs(FilesystemErrors::ReadOnly))
+        );
+    }
+
+    #[tokio::test]
+    async fn browse_tar_archives() {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "docs/notes.txt", &b"Hello"[..])
+            .unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        let filesystem = ArchiveFilesystem::from_bytes(archive).await.unwrap();
+        assert_eq!(filesystem.get_kind(), ArchiveKind::TarGz);
+        assert_eq!(
+            filesystem
+                .read_bytes_by_path("/docs/notes.txt")
+                .await
+                .unwrap(),
+            b"Hello"
+        );
+
+        assert!(ArchiveFilesystem::from_bytes(b"not an archive".to_vec())
+            .await
+            .is_err());
+    }
+}
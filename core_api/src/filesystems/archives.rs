@@ -0,0 +1,357 @@
+use std::io::{Cursor, Read, Write};
+use std::sync::Arc;
+
+use tokio::sync::mpsc::Sender;
+use tokio::sync::Mutex;
+use zip::read::ZipFile;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use super::{Filesystem, FilesystemErrors};
+use crate::messaging::{ClientMessages, ServerMessages};
+use crate::Errors;
+
+type FilesystemHandle = Arc<Mutex<Box<dyn Filesystem + Send>>>;
+
+/// Biggest archive that can be extracted or downloaded
+pub const MAX_ARCHIVE_SIZE: usize = 256 * 1024 * 1024;
+
+/// Biggest size of all the files of an archive once extracted, so small archives can't fill the disk
+pub const MAX_EXTRACTED_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Biggest size of a single file of an archive once extracted, as each file is kept in memory until it's written
+pub const MAX_EXTRACTED_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Reports the progress of an archive operation to the clients
+#[derive(Clone)]
+pub struct ArchiveProgress {
+    sender: Sender<ClientMessages>,
+    state_id: u8,
+    operation_id: String,
+}
+
+impl ArchiveProgress {
+    pub fn new(sender: Sender<ClientMessages>, state_id: u8, operation_id: &str) -> Self {
+        Self {
+            sender,
+            state_id,
+            operation_id: operation_id.to_owned(),
+        }
+    }
+
+    async fn report(&self, processed: usize, total: usize) {
+        self.sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::ArchiveProgress {
+                    state_id: self.state_id,
+                    operation_id: self.operation_id.clone(),
+                    processed,
+                    total,
+                },
+            ))
+            .await
+            .ok();
+    }
+}
+
+fn bad_archive<T>(_: T) -> Errors {
+    Errors::Fs(FilesystemErrors::BadArchive)
+}
+
+/// Return all the files inside a folder, relative to it
+async fn walk_folder(
+    filesystem: &FilesystemHandle,
+    path: &str,
+) -> Result<Vec<(String, String)>, Errors> {
+    let mut files = Vec::new();
+    let mut pending = vec![(path.to_owned(), String::new())];
+
+    while let Some((folder, prefix)) = pending.pop() {
+        let items = {
+            let filesystem = filesystem.lock().await;
+            let items = filesystem.list_dir_by_path(&folder);
+            items.await?
+        };
+
+        for item in items {
+            let relative = format!("{}{}", prefix, item.name);
+            if item.is_file {
+                files.push((item.path, relative));
+            } else {
+                pending.push((item.path, format!("{}/", relative)));
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Compress a folder into a zip archive
+///
+/// # Arguments
+///
+/// * `filesystem` - The filesystem where the folder is
+/// * `path`       - Path of the folder, its files can't add up to more than [`MAX_ARCHIVE_SIZE`]
+/// * `progress`   - Optionally, where to report the progress
+///
+pub async fn zip_folder(
+    filesystem: &FilesystemHandle,
+    path: &str,
+    progress: Option<&ArchiveProgress>,
+) -> Result<Vec<u8>, Errors> {
+    let files = walk_folder(filesystem, path).await?;
+    let total = files.len();
+    let mut remaining_size = MAX_ARCHIVE_SIZE;
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for (i, (file_path, relative)) in files.iter().enumerate() {
+        let content = {
+            let filesystem = filesystem.lock().await;
+            let content = filesystem.read_bytes_by_path(file_path);
+            content.await?
+        };
+        remaining_size = remaining_size
+            .checked_sub(content.len())
+            .ok_or(Errors::Fs(FilesystemErrors::ArchiveTooBig))?;
+        writer.start_file(relative, options).map_err(bad_archive)?;
+        writer.write_all(&content).map_err(bad_archive)?;
+
+        if let Some(progress) = progress {
+            progress.report(i + 1, total).await;
+        }
+    }
+
+    Ok(writer.finish().map_err(bad_archive)?.into_inner())
+}
+
+/// Path inside the destination of an entry, `None` if it would end up outside of it
+fn get_entry_path(destination: &str, file: &ZipFile) -> Option<String> {
+    let relative = file
+        .enclosed_name()?
+        .components()
+        .filter_map(|c| c.as_os_str().to_str())
+        .collect::<Vec<&str>>()
+        .join("/");
+    Some(format!("{}/{}", destination, relative))
+}
+
+/// Extract a zip archive into a folder. The files are written one by one, so only
+/// one of them is kept in memory at a time
+///
+/// # Arguments
+///
+/// * `filesystem`  - The filesystem where to extract the archive
+/// * `archive`     - The zip archive, up to [`MAX_ARCHIVE_SIZE`], and up to [`MAX_EXTRACTED_SIZE`] once extracted
+/// * `destination` - Path of the folder
+/// * `progress`    - Optionally, where to report the progress
+///
+pub async fn extract_archive(
+    filesystem: &FilesystemHandle,
+    archive: Vec<u8>,
+    destination: &str,
+    progress: Option<&ArchiveProgress>,
+) -> Result<(), Errors> {
+    let destination = destination.trim_end_matches(['/', '\\']);
+
+    if archive.len() > MAX_ARCHIVE_SIZE {
+        return Err(Errors::Fs(FilesystemErrors::ArchiveTooBig));
+    }
+
+    let mut archive = ZipArchive::new(Cursor::new(archive)).map_err(bad_archive)?;
+    let total = archive.len();
+
+    // Check the declared sizes before touching the filesystem, so most bad archives don't write anything
+    let mut declared_size: u64 = 0;
+    for i in 0..total {
+        let file = archive.by_index_raw(i).map_err(bad_archive)?;
+        if file.size() > MAX_EXTRACTED_FILE_SIZE {
+            return Err(Errors::Fs(FilesystemErrors::ArchiveTooBig));
+        }
+        declared_size += file.size();
+    }
+    if declared_size > MAX_EXTRACTED_SIZE {
+        return Err(Errors::Fs(FilesystemErrors::ArchiveTooBig));
+    }
+
+    {
+        let filesystem = filesystem.lock().await;
+        let created = filesystem.create_dir_by_path(destination);
+        created.await?;
+    }
+
+    let mut remaining_size = MAX_EXTRACTED_SIZE;
+
+    for i in 0..total {
+        let (path, content) = {
+            let mut file = archive.by_index(i).map_err(bad_archive)?;
+
+            // Ignore entries that would end up outside the destination
+            let path = match get_entry_path(destination, &file) {
+                Some(path) => path,
+                None => continue,
+            };
+
+            if file.is_dir() {
+                (path, None)
+            } else {
+                // The declared size can't be trusted, so the reads are bounded too
+                let limit = remaining_size.min(MAX_EXTRACTED_FILE_SIZE);
+                let mut content = Vec::new();
+                (&mut file)
+                    .take(limit + 1)
+                    .read_to_end(&mut content)
+                    .map_err(bad_archive)?;
+                if content.len() as u64 > limit {
+                    return Err(Errors::Fs(FilesystemErrors::ArchiveTooBig));
+                }
+                remaining_size -= content.len() as u64;
+                (path, Some(content))
+            }
+        };
+
+        {
+            let filesystem = filesystem.lock().await;
+            match content {
+                Some(content) => {
+                    if let Some((parent, _)) = path.rsplit_once('/') {
+                        let created = filesystem.create_dir_by_path(parent);
+                        created.await?;
+                    }
+                    let written = filesystem.write_bytes_by_path(&path, &content);
+                    written.await?;
+                }
+                None => {
+                    let created = filesystem.create_dir_by_path(&path);
+                    created.await?;
+                }
+            }
+        }
+
+        if let Some(progress) = progress {
+            progress.report(i + 1, total).await;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::mpsc::channel;
+    use tokio::sync::Mutex;
+
+    use std::io::{Cursor, Write};
+
+    use zip::write::FileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    use super::{
+        extract_archive, zip_folder, ArchiveProgress, MAX_ARCHIVE_SIZE, MAX_EXTRACTED_FILE_SIZE,
+    };
+    use crate::filesystems::{Filesystem, FilesystemErrors, LocalFilesystem};
+    use crate::messaging::{ClientMessages, ServerMessages};
+    use crate::Errors;
+
+    #[tokio::test]
+    async fn zip_and_extract_folders() {
+        let fs = LocalFilesystem::new();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let source = root.join("source");
+        let source_path = source.to_str().unwrap();
+
+        fs.create_dir_by_path(&format!("{}/src", source_path))
+            .await
+            .unwrap();
+        fs.write_file_by_path(&format!("{}/readme.md", source_path), "Hello")
+            .await
+            .unwrap();
+        fs.write_bytes_by_path(&format!("{}/src/data.bin", source_path), &[0, 159, 146])
+            .await
+            .unwrap();
+
+        let (tx, mut rx) = channel::<ClientMessages>(5);
+        let progress = ArchiveProgress::new(tx, 1, "download");
+
+        let handle = Arc::new(Mutex::new(
+            Box::new(LocalFilesystem::new()) as Box<dyn Filesystem + Send>
+        ));
+
+        let archive = zip_folder(&handle, source_path, Some(&progress))
+            .await
+            .unwrap();
+
+        let destination = root.join("destination");
+        let destination_path = destination.to_str().unwrap();
+
+        extract_archive(&handle, archive, destination_path, None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fs.read_bytes_by_path(&format!("{}/src/data.bin", destination_path))
+                .await
+                .unwrap(),
+            vec![0, 159, 146]
+        );
+        assert_eq!(
+            fs.read_file_by_path(&format!("{}/readme.md", destination_path))
+                .await
+                .unwrap()
+                .content,
+            "Hello"
+        );
+
+        rx.recv().await.unwrap();
+        assert!(matches!(
+            rx.recv().await,
+            Some(ClientMessages::ServerMessage(
+                ServerMessages::ArchiveProgress {
+                    processed: 2,
+                    total: 2,
+                    ..
+                }
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reject_big_archives() {
+        let handle = Arc::new(Mutex::new(
+            Box::new(LocalFilesystem::new()) as Box<dyn Filesystem + Send>
+        ));
+
+        let archive = vec![0; MAX_ARCHIVE_SIZE + 1];
+        assert_eq!(
+            extract_archive(&handle, archive, "./missing_folder", None).await,
+            Err(Errors::Fs(FilesystemErrors::ArchiveTooBig))
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_big_files() {
+        let handle = Arc::new(Mutex::new(
+            Box::new(LocalFilesystem::new()) as Box<dyn Filesystem + Send>
+        ));
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().join("destination");
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("big.bin", options).unwrap();
+        writer
+            .write_all(&vec![0; MAX_EXTRACTED_FILE_SIZE as usize + 1])
+            .unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+
+        assert_eq!(
+            extract_archive(&handle, archive, destination.to_str().unwrap(), None).await,
+            Err(Errors::Fs(FilesystemErrors::ArchiveTooBig))
+        );
+        assert!(!destination.exists());
+    }
+}
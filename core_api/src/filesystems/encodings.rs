@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+
+use super::FilesystemErrors;
+
+const UTF8_BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: [u8; 2] = [0xFF, 0xFE];
+const UTF16BE_BOM: [u8; 2] = [0xFE, 0xFF];
+
+/// Text encoding of a file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileEncoding {
+    #[default]
+    Utf8,
+    /// UTF-8 starting with a byte order mark
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /// Windows-1252, a superset of ISO-8859-1
+    Latin1,
+    /// Windows-1251
+    Cyrillic,
+    ShiftJis,
+    EucJp,
+    EucKr,
+    Gbk,
+    Big5,
+}
+
+impl FileEncoding {
+    fn get_encoding(&self) -> &'static encoding_rs::Encoding {
+        match self {
+            Self::Utf8 | Self::Utf8Bom => encoding_rs::UTF_8,
+            Self::Utf16Le => encoding_rs::UTF_16LE,
+            Self::Utf16Be => encoding_rs::UTF_16BE,
+            Self::Latin1 => encoding_rs::WINDOWS_1252,
+            Self::Cyrillic => encoding_rs::WINDOWS_1251,
+            Self::ShiftJis => encoding_rs::SHIFT_JIS,
+            Self::EucJp => encoding_rs::EUC_JP,
+            Self::EucKr => encoding_rs::EUC_KR,
+            Self::Gbk => encoding_rs::GBK,
+            Self::Big5 => encoding_rs::BIG5,
+        }
+    }
+
+    /// Guess the encoding of some content, it can be just the beginning of a file.
+    /// The byte order marks are checked first, then if it's UTF-16 or valid UTF-8, and Latin-1 is used if nothing else fits
+    pub fn detect(content: &[u8]) -> Self {
+        if content.starts_with(&UTF8_BOM) {
+            Self::Utf8Bom
+        } else if content.starts_with(&UTF16LE_BOM) {
+            Self::Utf16Le
+        } else if content.starts_with(&UTF16BE_BOM) {
+            Self::Utf16Be
+        } else if let Some(encoding) = detect_utf16(content) {
+            // Zeros are valid UTF-8 too, so this goes first
+            encoding
+        } else if is_utf8(content) {
+            Self::Utf8
+        } else if looks_like_shift_jis(content) {
+            Self::ShiftJis
+        } else {
+            Self::Latin1
+        }
+    }
+
+    /// Decode the content of a file, fails if it's not valid in this encoding
+    pub fn decode(&self, content: &[u8]) -> Result<String, FilesystemErrors> {
+        let (text, had_errors) = self.get_encoding().decode_with_bom_removal(content);
+        if had_errors {
+            Err(FilesystemErrors::InvalidEncoding)
+        } else {
+            Ok(text.into_owned())
+        }
+    }
+
+    /// Decode the content of a file, invalid sequences are replaced
+    pub fn decode_lossy(&self, content: &[u8]) -> String {
+        self.get_encoding()
+            .decode_with_bom_removal(content)
+            .0
+            .into_owned()
+    }
+
+    /// Encode a text to be written, the byte order mark is included for UTF-16 and `Utf8Bom`.
+    /// Fails if some characters can't be represented in this encoding
+    pub fn encode(&self, content: &str) -> Result<Vec<u8>, FilesystemErrors> {
+        match self {
+            Self::Utf8 => Ok(content.as_bytes().to_vec()),
+            Self::Utf8Bom => Ok([&UTF8_BOM[..], content.as_bytes()].concat()),
+            // encoding_rs only encodes into UTF-8 when asked for UTF-16
+            Self::Utf16Le => Ok(UTF16LE_BOM
+                .into_iter()
+                .chain(content.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect()),
+            Self::Utf16Be => Ok(UTF16BE_BOM
+                .into_iter()
+                .chain(content.encode_utf16().flat_map(u16::to_be_bytes))
+                .collect()),
+            _ => {
+                let (bytes, _, had_unmappable) = self.get_encoding().encode(content);
+                if had_unmappable {
+                    Err(FilesystemErrors::InvalidEncoding)
+                } else {
+                    Ok(bytes.into_owned())
+                }
+            }
+        }
+    }
+}
+
+/// Content with zeros that is not UTF-16 is not text
+pub fn is_binary(content: &[u8]) -> bool {
+    content.contains(&0) && detect_utf16(content).is_none()
+}
+
+/// Valid UTF-8, except for a character cut at the end
+fn is_utf8(content: &[u8]) -> bool {
+    match std::str::from_utf8(content) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    }
+}
+
+/// UTF-16 without a byte order mark, mostly ASCII text has a zero in every other byte
+fn detect_utf16(content: &[u8]) -> Option<FileEncoding> {
+    let pairs = content.len() / 2;
+    if pairs == 0 {
+        return None;
+    }
+
+    let even_zeros = content.iter().step_by(2).filter(|byte| **byte == 0).count();
+    let odd_zeros = content
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|byte| **byte == 0)
+        .count();
+
+    if odd_zeros * 10 > pairs * 3 && even_zeros * 10 < pairs {
+        Some(FileEncoding::Utf16Le)
+    } else if even_zeros * 10 > pairs * 3 && odd_zeros * 10 < pairs {
+        Some(FileEncoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Japanese text is made of two-byte characters, while in Latin-1 text the accented letters are surrounded by ASCII
+fn looks_like_shift_jis(content: &[u8]) -> bool {
+    let (_, had_errors) = encoding_rs::SHIFT_JIS.decode_without_bom_handling(content);
+    if had_errors {
+        return false;
+    }
+
+    let mut non_ascii = 0;
+    let mut paired = 0;
+    let mut bytes = content.iter().peekable();
+    while let Some(byte) = bytes.next() {
+        if *byte >= 0x80 {
+            non_ascii += 1;
+            if bytes.peek().map(|next| **next >= 0x80).unwrap_or(false) {
+                paired += 1;
+                bytes.next();
+            }
+        }
+    }
+
+    non_ascii > 0 && paired * 2 > non_ascii
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_binary, FileEncoding};
+
+    #[test]
+    fn detect_encodings() {
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0\x03\0"));
+        assert!(!is_binary(b"h\0e\0l\0"));
+
+        assert_eq!(FileEncoding::detect(b"hello"), FileEncoding::Utf8);
+        assert_eq!(
+            FileEncoding::detect("caf\u{e9}".as_bytes()),
+            FileEncoding::Utf8
+        );
+        // Cut in the middle of a character
+        assert_eq!(
+            FileEncoding::detect(&"caf\u{e9}".as_bytes()[..4]),
+            FileEncoding::Utf8
+        );
+        assert_eq!(
+            FileEncoding::detect(b"\xEF\xBB\xBFhello"),
+            FileEncoding::Utf8Bom
+        );
+        assert_eq!(FileEncoding::detect(b"h\0e\0l\0"), FileEncoding::Utf16Le);
+        assert_eq!(FileEncoding::detect(b"\0h\0e\0l"), FileEncoding::Utf16Be);
+        assert_eq!(
+            FileEncoding::detect(b"caf\xE9 cr\xE8me"),
+            FileEncoding::Latin1
+        );
+        // "こんにちは"
+        assert_eq!(
+            FileEncoding::detect(b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd"),
+            FileEncoding::ShiftJis
+        );
+    }
+
+    #[test]
+    fn decode_and_encode() {
+        for encoding in [
+            FileEncoding::Utf8,
+            FileEncoding::Utf8Bom,
+            FileEncoding::Utf16Le,
+            FileEncoding::Utf16Be,
+            FileEncoding::Latin1,
+        ] {
+            let encoded = encoding.encode("caf\u{e9}\r\n").unwrap();
+            assert_eq!(FileEncoding::detect(&encoded), encoding);
+            assert_eq!(encoding.decode(&encoded).unwrap(), "caf\u{e9}\r\n");
+        }
+
+        assert!(FileEncoding::Latin1.encode("\u{3053}").is_err());
+        assert!(FileEncoding::Utf8.decode(b"caf\xE9").is_err());
+    }
+}
@@ -0,0 +1,318 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ftp")]
+use async_trait::async_trait;
+#[cfg(feature = "ftp")]
+use std::io::Cursor;
+#[cfg(feature = "ftp")]
+use std::str::FromStr;
+#[cfg(feature = "ftp")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "ftp")]
+use suppaftp::native_tls::TlsConnector as NativeTlsConnector;
+#[cfg(feature = "ftp")]
+use suppaftp::{list, FtpError, FtpStream, Mode, TlsConnector};
+
+use crate::secrets::SecretsStore;
+
+#[cfg(feature = "ftp")]
+use super::{DirItemInfo, FileInfo, Filesystem, FilesystemErrors};
+#[cfg(feature = "ftp")]
+use crate::Errors;
+
+/// How the data connections are established
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum FtpMode {
+    /// The client connects to the server, works behind most firewalls
+    #[default]
+    Passive,
+    /// The server connects to the client
+    Active,
+}
+
+fn default_port() -> u16 {
+    21
+}
+
+/// Connection to a FTP server, mounted as a filesystem with the given name
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FtpSettings {
+    /// Name of the filesystem
+    pub name: String,
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub username: String,
+    /// Password sent by a client, it's moved to the server's secrets as soon as it's received
+    /// and never serialized, see [`FtpSettings::store_password`]
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    /// ID of the secret holding the password
+    #[serde(default)]
+    pub password_secret: Option<String>,
+    #[serde(default)]
+    pub mode: FtpMode,
+    /// Use explicit TLS (FTPS)
+    #[serde(default)]
+    pub secure: bool,
+}
+
+impl FtpSettings {
+    /// Move the password into the secrets, leaving only a reference to it
+    pub fn store_password(&mut self, secrets: &SecretsStore) {
+        if let Some(password) = self.password.take() {
+            let secret_id = self
+                .password_secret
+                .clone()
+                .unwrap_or_else(|| format!("ftp:{}", self.name));
+            secrets.set(&secret_id, password);
+            self.password_secret = Some(secret_id);
+        }
+    }
+}
+
+/// Implementation of FileSystem methods for a remote FTP or FTPS server
+///
+/// The connection is opened lazily and reused, and opened again if it's lost
+#[cfg(feature = "ftp")]
+pub struct FtpFilesystem {
+    settings: FtpSettings,
+    secrets: SecretsStore,
+    connection: Arc<Mutex<Option<FtpStream>>>,
+}
+
+#[cfg(feature = "ftp")]
+impl FtpFilesystem {
+    /// The password is looked up in the secrets every time it connects
+    pub fn new(settings: FtpSettings, secrets: SecretsStore) -> Self {
+        Self {
+            settings,
+            secrets,
+            connection: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn connect(settings: &FtpSettings, password: &str) -> Result<FtpStream, FtpError> {
+        let mut stream = FtpStream::connect(format!("{}:{}", settings.host, settings.port))?;
+
+        if settings.secure {
+            let connector =
+                NativeTlsConnector::new().map_err(|err| FtpError::SecureError(err.to_string()))?;
+            stream = stream.into_secure(TlsConnector::from(connector), &settings.host)?;
+        }
+
+        stream.login(settings.username.as_str(), password)?;
+
+        stream.set_mode(match settings.mode {
+            FtpMode::Passive => Mode::Passive,
+            FtpMode::Active => Mode::Active,
+        });
+
+        Ok(stream)
+    }
+
+    /// Run an operation with the connection in a blocking thread
+    async fn run<T: Send + 'static>(
+        &self,
+        operation: impl FnOnce(&mut FtpStream) -> Result<T, FtpError> + Send + 'static,
+    ) -> Result<T, Errors> {
+        let settings = self.settings.clone();
+        let password = settings
+            .password_secret
+            .as_ref()
+            .and_then(|secret_id| self.secrets.get(secret_id))
+            .unwrap_or_default();
+        let connection = self.connection.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection.lock().unwrap();
+
+            if connection.is_none() {
+                *connection = Some(Self::connect(&settings, &password).map_err(map_error)?);
+            }
+
+            let res = operation(connection.as_mut().unwrap());
+
+            // Open a new connection next time
+            if let Err(FtpError::ConnectionError(_)) = res {
+                *connection = None;
+            }
+
+            res.map_err(map_error)
+        })
+        .await
+        .map_err(|_| Errors::Fs(FilesystemErrors::ConnectionFailed))?
+    }
+}
+
+#[cfg(feature = "ftp")]
+fn map_error(err: FtpError) -> Errors {
+    match err {
+        FtpError::UnexpectedResponse(res) if res.status.code() == 550 => {
+            Errors::Fs(FilesystemErrors::FileNotFound)
+        }
+        FtpError::UnexpectedResponse(res) if res.status.code() == 530 => {
+            Errors::Fs(FilesystemErrors::PermissionDenied)
+        }
+        _ => Errors::Fs(FilesystemErrors::ConnectionFailed),
+    }
+}
+
+#[cfg(feature = "ftp")]
+#[async_trait]
+impl Filesystem for FtpFilesystem {
+    /// Download a remote file
+    async fn read_file_by_path(&self, path: &str) -> Result<FileInfo, Errors> {
+        let remote_path = path.to_owned();
+        let content = self
+            .run(move |stream| stream.retr_as_buffer(&remote_path))
+            .await?;
+
+        String::from_utf8(content.into_inner())
+            .map(|content| FileInfo::new(path, content))
+            .map_err(|_| Errors::Fs(FilesystemErrors::FileNotSupported))
+    }
+
+    /// Upload a file, replacing the remote one
+    async fn write_file_by_path(&self, path: &str, content: &str) -> Result<(), Errors> {
+        let path = path.to_owned();
+        let content = content.as_bytes().to_vec();
+
+        self.run(move |stream| stream.put_file(&path, &mut Cursor::new(content)))
+            .await
+            .map(|_| ())
+    }
+
+    /// Rename or move a remote file or folder
+    async fn rename_by_path(&self, from: &str, to: &str) -> Result<(), Errors> {
+        let from = from.to_owned();
+        let to = to.to_owned();
+
+        self.run(move |stream| stream.rename(&from, &to)).await
+    }
+
+    /// Download the raw content of a remote file
+    async fn read_bytes_by_path(&self, path: &str) -> Result<Vec<u8>, Errors> {
+        let path = path.to_owned();
+        self.run(move |stream| stream.retr_as_buffer(&path))
+            .await
+            .map(|content| content.into_inner())
+    }
+
+    /// Upload raw content, replacing the remote file
+    async fn write_bytes_by_path(&self, path: &str, content: &[u8]) -> Result<(), Errors> {
+        let path = path.to_owned();
+        let content = content.to_vec();
+
+        self.run(move |stream| stream.put_file(&path, &mut Cursor::new(content)))
+            .await
+            .map(|_| ())
+    }
+
+    /// Size of a remote file in bytes
+    async fn get_file_size(&self, path: &str) -> Result<u64, Errors> {
+        let path = path.to_owned();
+        self.run(move |stream| stream.size(&path))
+            .await
+            .map(|size| size as u64)
+    }
+
+    /// Append content to a remote file
+    async fn append_file_by_path(&self, path: &str, content: &[u8]) -> Result<(), Errors> {
+        let path = path.to_owned();
+        let content = content.to_vec();
+
+        self.run(move |stream| stream.append_file(&path, &mut Cursor::new(content)))
+            .await
+            .map(|_| ())
+    }
+
+    /// Delete a remote file or an empty folder
+    async fn delete_by_path(&self, path: &str) -> Result<(), Errors> {
+        let path = path.to_owned();
+        self.run(move |stream| stream.rm(&path).or_else(|_| stream.rmdir(&path)))
+            .await
+    }
+
+    /// Create a remote folder and all it's missing parents
+    async fn create_dir_by_path(&self, path: &str) -> Result<(), Errors> {
+        let path = path.to_owned();
+
+        self.run(move |stream| {
+            let mut current = String::new();
+            for component in path.split('/').filter(|c| !c.is_empty()) {
+                current = format!("{}/{}", current, component);
+                // Existing folders fail, that's fine
+                let _ = stream.mkdir(&current);
+            }
+            // Make sure the folder is there without moving the working directory
+            let cwd = stream.pwd()?;
+            stream.cwd(&path)?;
+            stream.cwd(&cwd)
+        })
+        .await
+    }
+
+    /// List a remote directory
+    async fn list_dir_by_path(&self, path: &str) -> Result<Vec<DirItemInfo>, Errors> {
+        let remote_path = path.to_owned();
+        let lines = self
+            .run(move |stream| stream.list(Some(&remote_path)))
+            .await?;
+
+        let mut result = lines
+            .iter()
+            .filter_map(|line| list::File::from_str(line).ok())
+            .filter(|file| file.name() != "." && file.name() != "..")
+            .map(|file| DirItemInfo {
+                path: format!("{}/{}", path.trim_end_matches('/'), file.name()),
+                name: file.name().to_owned(),
+                is_file: !file.is_directory(),
+                nested: Vec::new(),
+                icon: None,
+            })
+            .collect::<Vec<DirItemInfo>>();
+
+        result.sort_by_key(|item| item.is_file);
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FtpMode, FtpSettings};
+    use crate::secrets::SecretsStore;
+
+    #[test]
+    fn default_settings() {
+        let settings: FtpSettings = serde_json::from_str(
+            r#"{ "name": "hosting", "host": "ftp.example.com", "username": "user", "password": "pass" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.port, 21);
+        assert_eq!(settings.mode, FtpMode::Passive);
+        assert!(!settings.secure);
+    }
+
+    #[test]
+    fn keep_password_out_of_the_settings() {
+        let secrets = SecretsStore::new();
+        let mut settings: FtpSettings = serde_json::from_str(
+            r#"{ "name": "hosting", "host": "ftp.example.com", "username": "user", "password": "pass" }"#,
+        )
+        .unwrap();
+
+        settings.store_password(&secrets);
+
+        assert_eq!(settings.password, None);
+        assert_eq!(settings.password_secret.as_deref(), Some("ftp:hosting"));
+        assert_eq!(secrets.get("ftp:hosting").as_deref(), Some("pass"));
+
+        settings.password = Some("pass".to_string());
+        assert!(!serde_json::to_string(&settings)
+            .unwrap()
+            .contains("\"password\""));
+    }
+}
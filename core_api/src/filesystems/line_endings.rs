@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+/// Line endings found in a file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    /// There are no line breaks
+    #[default]
+    Unknown,
+    Lf,
+    Crlf,
+    /// Both LF and CRLF are used
+    Mixed,
+}
+
+impl LineEnding {
+    /// Find what line endings are used in a text
+    pub fn detect(content: &str) -> Self {
+        let crlf = content.matches("\r\n").count();
+        let lf = content.matches('\n').count() - crlf;
+
+        match (lf, crlf) {
+            (0, 0) => Self::Unknown,
+            (_, 0) => Self::Lf,
+            (0, _) => Self::Crlf,
+            _ => Self::Mixed,
+        }
+    }
+}
+
+/// How line endings are handled when saving files
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EolPolicy {
+    /// Keep the line endings the file already had
+    #[default]
+    Preserve,
+    ForceLf,
+    ForceCrlf,
+    /// Save as it is but warn if LF and CRLF are mixed
+    WarnOnMixed,
+}
+
+impl EolPolicy {
+    /// Apply the policy to a content about to be saved
+    ///
+    /// # Arguments
+    ///
+    /// * `content`    - New content of the file
+    /// * `current`    - Line endings of the file in the filesystem, if it exists
+    ///
+    pub fn apply(&self, content: &str, current: LineEnding) -> String {
+        match (self, current) {
+            (Self::ForceLf, _) | (Self::Preserve, LineEnding::Lf) => to_lf(content),
+            (Self::ForceCrlf, _) | (Self::Preserve, LineEnding::Crlf) => to_crlf(content),
+            _ => content.to_owned(),
+        }
+    }
+
+    /// Check if saving this content should warn the user
+    pub fn should_warn(&self, content: &str) -> bool {
+        *self == Self::WarnOnMixed && LineEnding::detect(content) == LineEnding::Mixed
+    }
+}
+
+fn to_lf(content: &str) -> String {
+    content.replace("\r\n", "\n")
+}
+
+fn to_crlf(content: &str) -> String {
+    to_lf(content).replace('\n', "\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EolPolicy, LineEnding};
+
+    #[test]
+    fn line_endings_policies() {
+        let mixed = "a\r\nb\nc";
+
+        assert_eq!(LineEnding::detect("a"), LineEnding::Unknown);
+        assert_eq!(LineEnding::detect("a\nb"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\nb"), LineEnding::Crlf);
+        assert_eq!(LineEnding::detect(mixed), LineEnding::Mixed);
+
+        assert_eq!(EolPolicy::ForceLf.apply(mixed, LineEnding::Crlf), "a\nb\nc");
+        assert_eq!(
+            EolPolicy::ForceCrlf.apply(mixed, LineEnding::Lf),
+            "a\r\nb\r\nc"
+        );
+        assert_eq!(EolPolicy::Preserve.apply(mixed, LineEnding::Lf), "a\nb\nc");
+        assert_eq!(EolPolicy::Preserve.apply(mixed, LineEnding::Mixed), mixed);
+        assert_eq!(EolPolicy::WarnOnMixed.apply(mixed, LineEnding::Lf), mixed);
+
+        assert!(EolPolicy::WarnOnMixed.should_warn(mixed));
+        assert!(!EolPolicy::Preserve.should_warn(mixed));
+    }
+}
@@ -1,12 +1,33 @@
 use async_trait::async_trait;
-use tokio::fs;
-use tokio_stream::wrappers::ReadDirStream;
+use tokio::fs::{self, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::mpsc::channel;
+use tokio_stream::wrappers::{ReadDirStream, ReceiverStream};
 use tokio_stream::StreamExt;
 
 use crate::Errors;
 
-use super::{DirItemInfo, FileInfo, Filesystem, FilesystemErrors};
-use std::io::ErrorKind;
+use super::{
+    DirItemInfo, FileChunks, FileInfo, Filesystem, FilesystemCapabilities, FilesystemErrors,
+};
+use std::io::{ErrorKind, SeekFrom};
+
+pub(crate) fn map_io_error(err: std::io::Error) -> Errors {
+    match err.kind() {
+        ErrorKind::PermissionDenied => Errors::Fs(FilesystemErrors::PermissionDenied),
+        _ => Errors::Fs(FilesystemErrors::FileNotFound),
+    }
+}
+
+/// Check if a rename failed because the paths are in different devices
+fn is_cross_device_error(err: &std::io::Error) -> bool {
+    // EXDEV on Unix, ERROR_NOT_SAME_DEVICE on Windows
+    if cfg!(windows) {
+        err.raw_os_error() == Some(17)
+    } else {
+        err.raw_os_error() == Some(18)
+    }
+}
 
 /// Implementation of FileSystem methods for a local access
 #[derive(Default)]
@@ -20,27 +41,197 @@ impl LocalFilesystem {
 
 #[async_trait]
 impl Filesystem for LocalFilesystem {
+    /// Linux filesystems are usually case-sensitive, the default ones of Windows and macOS are not
+    fn capabilities(&self) -> FilesystemCapabilities {
+        FilesystemCapabilities {
+            watch: true,
+            trash: true,
+            symlinks: true,
+            atomic_rename: true,
+            case_sensitive: !cfg!(any(windows, target_os = "macos")),
+            read_only: false,
+        }
+    }
+
     /// Read a local file
     async fn read_file_by_path(&self, path: &str) -> Result<FileInfo, Errors> {
-        fs::read_to_string(path)
+        self.read_file_with_encoding(path, None).await
+    }
+
+    /// Write a local file
+    async fn write_file_by_path(&self, path: &str, content: &str) -> Result<(), Errors> {
+        fs::write(path, content)
             .await
-            .map(|content| FileInfo::new(path, content))
             .map_err(|err| match err.kind() {
                 ErrorKind::NotFound => Errors::Fs(FilesystemErrors::FileNotFound),
                 _ => Errors::Fs(FilesystemErrors::FileNotFound),
             })
     }
 
-    /// Write a local file
-    async fn write_file_by_path(&self, path: &str, content: &str) -> Result<(), Errors> {
+    /// Rename or move a local file or folder
+    async fn rename_by_path(&self, from: &str, to: &str) -> Result<(), Errors> {
+        fs::rename(from, to).await.map_err(|err| match err.kind() {
+            ErrorKind::NotFound => Errors::Fs(FilesystemErrors::FileNotFound),
+            ErrorKind::PermissionDenied => Errors::Fs(FilesystemErrors::PermissionDenied),
+            _ => Errors::Fs(FilesystemErrors::FileNotFound),
+        })
+    }
+
+    /// Read the raw content of a local file
+    async fn read_bytes_by_path(&self, path: &str) -> Result<Vec<u8>, Errors> {
+        fs::read(path).await.map_err(|err| match err.kind() {
+            ErrorKind::NotFound => Errors::Fs(FilesystemErrors::FileNotFound),
+            ErrorKind::PermissionDenied => Errors::Fs(FilesystemErrors::PermissionDenied),
+            _ => Errors::Fs(FilesystemErrors::FileNotFound),
+        })
+    }
+
+    /// Write raw content into a local file
+    async fn write_bytes_by_path(&self, path: &str, content: &[u8]) -> Result<(), Errors> {
         fs::write(path, content)
             .await
             .map_err(|err| match err.kind() {
-                ErrorKind::NotFound => Errors::Fs(FilesystemErrors::FileNotFound),
+                ErrorKind::PermissionDenied => Errors::Fs(FilesystemErrors::PermissionDenied),
                 _ => Errors::Fs(FilesystemErrors::FileNotFound),
             })
     }
 
+    /// Create a local folder and all it's missing parents
+    async fn create_dir_by_path(&self, path: &str) -> Result<(), Errors> {
+        fs::create_dir_all(path)
+            .await
+            .map_err(|err| match err.kind() {
+                ErrorKind::PermissionDenied => Errors::Fs(FilesystemErrors::PermissionDenied),
+                _ => Errors::Fs(FilesystemErrors::FileNotFound),
+            })
+    }
+
+    /// Size of a local file in bytes
+    async fn get_file_size(&self, path: &str) -> Result<u64, Errors> {
+        fs::metadata(path)
+            .await
+            .map(|metadata| metadata.len())
+            .map_err(map_io_error)
+    }
+
+    /// Read a range of a local file without loading the rest of it
+    async fn read_range(&self, path: &str, offset: u64, length: usize) -> Result<Vec<u8>, Errors> {
+        let mut file = fs::File::open(path).await.map_err(map_io_error)?;
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(map_io_error)?;
+
+        let mut content = Vec::new();
+        file.take(length as u64)
+            .read_to_end(&mut content)
+            .await
+            .map_err(map_io_error)?;
+
+        Ok(content)
+    }
+
+    /// Read a local file in chunks, only a few chunks are kept in memory at the same time
+    async fn read_chunks(&self, path: &str, chunk_size: usize) -> Result<FileChunks, Errors> {
+        let mut file = fs::File::open(path).await.map_err(map_io_error)?;
+        let chunk_size = chunk_size.max(1);
+        let (tx, rx) = channel(2);
+
+        tokio::spawn(async move {
+            loop {
+                let mut chunk = Vec::with_capacity(chunk_size);
+                let read = (&mut file)
+                    .take(chunk_size as u64)
+                    .read_to_end(&mut chunk)
+                    .await;
+
+                match read {
+                    Ok(0) => break,
+                    Ok(_) => {
+                        // Nobody is reading anymore
+                        if tx.send(Ok(chunk)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(err) => {
+                        tx.send(Err(map_io_error(err))).await.ok();
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Box::pin(ReceiverStream::new(rx)))
+    }
+
+    /// Write into a local file at the given offset
+    async fn write_chunk(&self, path: &str, offset: u64, content: &[u8]) -> Result<(), Errors> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .await
+            .map_err(map_io_error)?;
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(map_io_error)?;
+        file.write_all(content).await.map_err(map_io_error)?;
+        file.flush().await.map_err(map_io_error)
+    }
+
+    /// Append content to a local file
+    async fn append_file_by_path(&self, path: &str, content: &[u8]) -> Result<(), Errors> {
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .await
+            .map_err(map_io_error)?;
+        file.write_all(content).await.map_err(map_io_error)?;
+        file.flush().await.map_err(map_io_error)
+    }
+
+    /// Delete a local file, or a folder with all it's content
+    async fn delete_by_path(&self, path: &str) -> Result<(), Errors> {
+        let metadata = fs::symlink_metadata(path).await.map_err(map_io_error)?;
+        if metadata.is_dir() {
+            fs::remove_dir_all(path).await.map_err(map_io_error)
+        } else {
+            fs::remove_file(path).await.map_err(map_io_error)
+        }
+    }
+
+    /// Move a local file or folder to the trash of the platform
+    async fn move_to_trash(&self, path: &str) -> Result<(), Errors> {
+        let path = path.to_owned();
+        tokio::task::spawn_blocking(move || {
+            if !std::path::Path::new(&path).exists() {
+                return Err(Errors::Fs(FilesystemErrors::FileNotFound));
+            }
+            trash::delete(&path).map_err(|_| Errors::Fs(FilesystemErrors::FileNotSupported))
+        })
+        .await
+        .map_err(|_| Errors::Fs(FilesystemErrors::FileNotSupported))?
+    }
+
+    /// Move a local file or folder, even to another device
+    async fn move_item(&self, from: &str, to: &str) -> Result<(), Errors> {
+        match fs::rename(from, to).await {
+            Ok(()) => Ok(()),
+            // Renaming doesn't work across devices, so copy it and then delete the original
+            Err(err) if is_cross_device_error(&err) => {
+                let metadata = fs::metadata(from).await.map_err(map_io_error)?;
+                if metadata.is_dir() {
+                    self.copy_dir_recursive(from, to, None, None).await?;
+                } else {
+                    fs::copy(from, to).await.map_err(map_io_error)?;
+                }
+                self.delete_by_path(from).await
+            }
+            Err(err) => Err(map_io_error(err)),
+        }
+    }
+
     // List a local directory
     async fn list_dir_by_path(&self, path: &str) -> Result<Vec<DirItemInfo>, Errors> {
         let dirs = fs::read_dir(path).await;
@@ -59,6 +250,8 @@ impl Filesystem for LocalFilesystem {
                     path: str_path,
                     name: item_name,
                     is_file,
+                    nested: Vec::new(),
+                    icon: None,
                 });
             }
 
@@ -78,7 +271,10 @@ impl Filesystem for LocalFilesystem {
 #[cfg(test)]
 mod tests {
 
+    use tokio_stream::StreamExt;
+
     use super::{Filesystem, LocalFilesystem};
+    use crate::filesystems::ProgressCallback;
 
     #[tokio::test]
     async fn read_files() {
@@ -105,4 +301,77 @@ mod tests {
 
         assert!(!items_in_dir[0].is_file);
     }
+
+    #[tokio::test]
+    async fn chunked_io() {
+        let fs = LocalFilesystem::new();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("chunks");
+        let path = path.to_str().unwrap();
+
+        fs.append_file_by_path(path, b"0123").await.unwrap();
+        fs.append_file_by_path(path, b"4567").await.unwrap();
+        fs.write_chunk(path, 8, b"89").await.unwrap();
+
+        assert_eq!(fs.get_file_size(path).await.unwrap(), 10);
+        assert_eq!(fs.read_range(path, 3, 4).await.unwrap(), b"3456");
+        assert_eq!(fs.read_range(path, 8, 100).await.unwrap(), b"89");
+
+        let chunks = fs
+            .read_chunks(path, 4)
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect::<Vec<Vec<u8>>>()
+            .await;
+        assert_eq!(
+            chunks,
+            vec![b"0123".to_vec(), b"4567".to_vec(), b"89".to_vec()]
+        );
+    }
+
+    #[tokio::test]
+    async fn copy_and_delete_folders() {
+        let fs = LocalFilesystem::new();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().to_str().unwrap();
+        let source = format!("{}/source", root);
+
+        fs.create_dir_by_path(&format!("{}/nested", source))
+            .await
+            .unwrap();
+        fs.write_file_by_path(&format!("{}/a.txt", source), "a")
+            .await
+            .unwrap();
+        fs.write_file_by_path(&format!("{}/nested/b.txt", source), "b")
+            .await
+            .unwrap();
+
+        let copied = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = copied.clone();
+        let progress = move |done: usize, total: usize| {
+            assert_eq!(total, 2);
+            counter.store(done, std::sync::atomic::Ordering::SeqCst);
+        };
+
+        let copy = format!("{}/copy", root);
+        fs.copy_dir_recursive(&source, &copy, Some(&progress as &ProgressCallback), None)
+            .await
+            .unwrap();
+        assert_eq!(copied.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(
+            fs.read_file_by_path(&format!("{}/nested/b.txt", copy))
+                .await
+                .unwrap()
+                .content,
+            "b"
+        );
+
+        let moved = format!("{}/moved", root);
+        fs.move_item(&copy, &moved).await.unwrap();
+        assert!(fs.list_dir_by_path(&copy).await.is_err());
+
+        fs.delete_by_path(root).await.unwrap();
+        assert!(fs.list_dir_by_path(root).await.is_err());
+    }
 }
@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+
+use crate::Errors;
+
+use super::{
+    remap_path, DirItemInfo, FileInfo, Filesystem, FilesystemCapabilities, FilesystemErrors,
+};
+
+#[derive(Default)]
+struct MemoryData {
+    files: BTreeMap<String, Vec<u8>>,
+    dirs: BTreeSet<String>,
+}
+
+/// Remove the trailing slashes, the root is `/`
+fn normalize(path: &str) -> String {
+    let path = path.trim_end_matches('/');
+    if path.is_empty() {
+        "/".to_owned()
+    } else if path.starts_with('/') {
+        path.to_owned()
+    } else {
+        format!("/{}", path)
+    }
+}
+
+fn get_parent(path: &str) -> Option<&str> {
+    match path.rsplit_once('/') {
+        Some(("", _)) if path != "/" => Some("/"),
+        Some((parent, _)) if !parent.is_empty() => Some(parent),
+        _ => None,
+    }
+}
+
+fn get_name(path: &str) -> &str {
+    path.rsplit_once('/').map_or(path, |(_, name)| name)
+}
+
+/// Filesystem kept in memory, useful for tests and for scratch files not saved yet.
+/// Clones share the same files.
+#[derive(Clone)]
+pub struct MemoryFilesystem {
+    data: Arc<Mutex<MemoryData>>,
+}
+
+impl Default for MemoryFilesystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryFilesystem {
+    pub fn new() -> Self {
+        let mut data = MemoryData::default();
+        data.dirs.insert("/".to_owned());
+        Self {
+            data: Arc::new(Mutex::new(data)),
+        }
+    }
+
+    /// Create a filesystem with some files, their folders are created too
+    pub fn with_files(files: &[(&str, &str)]) -> Self {
+        let filesystem = Self::new();
+        {
+            let mut data = filesystem.data.lock().unwrap();
+            for (path, content) in files {
+                Self::insert_file(&mut data, &normalize(path), content.as_bytes().to_vec());
+            }
+        }
+        filesystem
+    }
+
+    fn insert_file(data: &mut MemoryData, path: &str, content: Vec<u8>) {
+        let mut parent = get_parent(path);
+        while let Some(dir) = parent {
+            data.dirs.insert(dir.to_owned());
+            parent = get_parent(dir);
+        }
+        data.files.insert(path.to_owned(), content);
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, Errors> {
+        self.data
+            .lock()
+            .unwrap()
+            .files
+            .get(&normalize(path))
+            .cloned()
+            .ok_or(Errors::Fs(FilesystemErrors::FileNotFound))
+    }
+
+    fn write(&self, path: &str, content: Vec<u8>) {
+        let mut data = self.data.lock().unwrap();
+        Self::insert_file(&mut data, &normalize(path), content);
+    }
+}
+
+#[async_trait]
+impl Filesystem for MemoryFilesystem {
+    /// Everything happens under a single lock, and checking for changes is cheap
+    fn capabilities(&self) -> FilesystemCapabilities {
+        FilesystemCapabilities {
+            watch: true,
+            atomic_rename: true,
+            ..FilesystemCapabilities::default()
+        }
+    }
+
+    /// Read a file from memory
+    async fn read_file_by_path(&self, path: &str) -> Result<FileInfo, Errors> {
+        let content = self.read(path)?;
+        String::from_utf8(content)
+            .map(|content| FileInfo::new(path, content))
+            .map_err(|_| Errors::Fs(FilesystemErrors::FileNotSupported))
+    }
+
+    /// Write a file to memory, the missing folders are created
+    async fn write_file_by_path(&self, path: &str, content: &str) -> Result<(), Errors> {
+        self.write(path, content.as_bytes().to_vec());
+        Ok(())
+    }
+
+    /// List a folder in memory
+    async fn list_dir_by_path(&self, path: &str) -> Result<Vec<DirItemInfo>, Errors> {
+        let path = normalize(path);
+        let data = self.data.lock().unwrap();
+
+        if !data.dirs.contains(&path) {
+            return Err(Errors::Fs(FilesystemErrors::FileNotFound));
+        }
+
+        let dirs = data
+            .dirs
+            .iter()
+            .filter(|dir| get_parent(dir) == Some(path.as_str()))
+            .map(|dir| (dir, false));
+        let files = data
+            .files
+            .keys()
+            .filter(|file| get_parent(file) == Some(path.as_str()))
+            .map(|file| (file, true));
+
+        Ok(dirs
+            .chain(files)
+            .map(|(item_path, is_file)| DirItemInfo {
+                path: item_path.clone(),
+                name: get_name(item_path).to_owned(),
+                is_file,
+                nested: Vec::new(),
+                icon: None,
+            })
+            .collect())
+    }
+
+    /// Rename or move a file or folder in memory
+    async fn rename_by_path(&self, from: &str, to: &str) -> Result<(), Errors> {
+        let from = normalize(from);
+        let to = normalize(to);
+        let mut data = self.data.lock().unwrap();
+
+        if !data.files.contains_key(&from) && !data.dirs.contains(&from) {
+            return Err(Errors::Fs(FilesystemErrors::FileNotFound));
+        }
+
+        let files = std::mem::take(&mut data.files);
+        for (path, content) in files {
+            let path = remap_path(&path, &from, &to).unwrap_or(path);
+            Self::insert_file(&mut data, &path, content);
+        }
+
+        let dirs = std::mem::take(&mut data.dirs);
+        data.dirs = dirs
+            .into_iter()
+            .map(|dir| remap_path(&dir, &from, &to).unwrap_or(dir))
+            .collect();
+        if let Some(parent) = get_parent(&to) {
+            data.dirs.insert(parent.to_owned());
+        }
+
+        Ok(())
+    }
+
+    async fn read_bytes_by_path(&self, path: &str) -> Result<Vec<u8>, Errors> {
+        self.read(path)
+    }
+
+    async fn write_bytes_by_path(&self, path: &str, content: &[u8]) -> Result<(), Errors> {
+        self.write(path, content.to_vec());
+        Ok(())
+    }
+
+    async fn create_dir_by_path(&self, path: &str) -> Result<(), Errors> {
+        let mut data = self.data.lock().unwrap();
+        let mut dir = Some(normalize(path));
+        while let Some(path) = dir {
+            dir = get_parent(&path).map(str::to_owned);
+            data.dirs.insert(path);
+        }
+        Ok(())
+    }
+
+    async fn append_file_by_path(&self, path: &str, content: &[u8]) -> Result<(), Errors> {
+        let mut data = self.data.lock().unwrap();
+        let path = normalize(path);
+        let mut file = data.files.remove(&path).unwrap_or_default();
+        file.extend_from_slice(content);
+        Self::insert_file(&mut data, &path, file);
+        Ok(())
+    }
+
+    async fn write_chunk(&self, path: &str, offset: u64, content: &[u8]) -> Result<(), Errors> {
+        let mut data = self.data.lock().unwrap();
+        let path = normalize(path);
+        let mut file = data.files.remove(&path).unwrap_or_default();
+        let offset = offset as usize;
+        if file.len() < offset + content.len() {
+            file.resize(offset + content.len(), 0);
+        }
+        file[offset..offset + content.len()].copy_from_slice(content);
+        Self::insert_file(&mut data, &path, file);
+        Ok(())
+    }
+
+    /// Delete a file, or a folder with all it's content
+    async fn delete_by_path(&self, path: &str) -> Result<(), Errors> {
+        let path = normalize(path);
+        let mut data = self.data.lock().unwrap();
+
+        if data.files.remove(&path).is_some() {
+            return Ok(());
+        }
+
+        if path == "/" || !data.dirs.contains(&path) {
+            return Err(Errors::Fs(FilesystemErrors::FileNotFound));
+        }
+
+        data.files
+            .retain(|file, _| remap_path(file, &path, "").is_none());
+        data.dirs.retain(|dir| remap_path(dir, &path, "").is_none());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryFilesystem;
+    use crate::filesystems::Filesystem;
+
+    #[tokio::test]
+    async fn memory_files() {
+        let fs = MemoryFilesystem::with_files(&[("/project/src/main.rs", "fn main() {}")]);
+
+        let items = fs.list_dir_by_path("/project").await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(!items[0].is_file);
+        assert_eq!(items[0].path, "/project/src");
+
+        fs.write_file_by_path("/project/readme.md", "# Hello")
+            .await
+            .unwrap();
+        fs.rename_by_path("/project/src", "/project/source")
+            .await
+            .unwrap();
+
+        assert!(fs.read_file_by_path("/project/src/main.rs").await.is_err());
+        assert_eq!(
+            fs.read_file_by_path("/project/source/main.rs")
+                .await
+                .unwrap()
+                .content,
+            "fn main() {}"
+        );
+        assert_eq!(fs.list_dir_by_path("/project/").await.unwrap().len(), 2);
+
+        // Clones share the files
+        let clone = fs.clone();
+        clone.delete_by_path("/project/source").await.unwrap();
+        assert_eq!(fs.list_dir_by_path("/project").await.unwrap().len(), 1);
+    }
+}
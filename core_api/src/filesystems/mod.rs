@@ -1,9 +1,38 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::pin::Pin;
+use tokio_stream::Stream;
+#[cfg(feature = "archives")]
+mod archive_fs;
+#[cfg(feature = "archives")]
+mod archives;
+mod encodings;
+mod ftp;
+mod line_endings;
 mod local;
+mod memory;
+mod nesting;
+mod uri;
+#[cfg(feature = "archives")]
+pub use archive_fs::{ArchiveFilesystem, ArchiveKind};
+#[cfg(feature = "archives")]
+pub use archives::{
+    extract_archive, zip_folder, ArchiveProgress, MAX_ARCHIVE_SIZE, MAX_EXTRACTED_SIZE,
+};
+pub use encodings::{is_binary, FileEncoding};
+#[cfg(feature = "ftp")]
+pub use ftp::FtpFilesystem;
+pub use ftp::{FtpMode, FtpSettings};
+pub use line_endings::{EolPolicy, LineEnding};
+pub(crate) use local::map_io_error;
 pub use local::LocalFilesystem;
+pub use memory::MemoryFilesystem;
+pub use nesting::nest_items;
+pub use uri::{GravitonUri, GRAVITON_SCHEME, UNTITLED_SCHEME};
 
+use crate::icons::FileIcon;
+use crate::search::CancellationToken;
 use crate::Errors;
 
 /// Filesystem errors
@@ -13,14 +42,237 @@ pub enum FilesystemErrors {
     FileNotFound,
     FileNotSupported,
     PermissionDenied,
+    ConnectionFailed,
+    BadArchive,
+    /// The archive, or its content once extracted, is bigger than allowed
+    ArchiveTooBig,
+    Cancelled,
+    OperationNotFound,
+    InvalidUri,
+    /// The content is not valid in the encoding, or can't be represented in it
+    InvalidEncoding,
+    /// The filesystem can't be modified
+    ReadOnly,
+}
+
+/// Stream with the content of a file, in chunks
+pub type FileChunks = Pin<Box<dyn Stream<Item = Result<Vec<u8>, Errors>> + Send>>;
+
+/// Called with the processed items and the total of items of a long operation
+pub type ProgressCallback = dyn Fn(usize, usize) + Send + Sync;
+
+/// Join a file or folder name to a folder path
+pub fn join_path(folder: &str, name: &str) -> String {
+    format!("{}/{}", folder.trim_end_matches(['/', '\\']), name)
+}
+
+/// What a filesystem supports, so the explorer and the save pipeline don't assume local semantics
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilesystemCapabilities {
+    /// Changes made outside of the editor can be noticed, so watching it is worth it
+    pub watch: bool,
+    /// Items can be moved to a trash and restored
+    pub trash: bool,
+    /// Symbolic links can be listed and followed
+    pub symlinks: bool,
+    /// Renaming replaces the destination in a single step, there is no moment without it
+    pub atomic_rename: bool,
+    /// `README.md` and `readme.md` are different files
+    pub case_sensitive: bool,
+    /// Nothing can be written
+    pub read_only: bool,
+}
+
+impl Default for FilesystemCapabilities {
+    fn default() -> Self {
+        Self {
+            watch: false,
+            trash: false,
+            symlinks: false,
+            atomic_rename: false,
+            case_sensitive: true,
+            read_only: false,
+        }
+    }
 }
 
 /// Filesystem interface
 #[async_trait]
-pub trait Filesystem {
+pub trait Filesystem: Send + Sync {
+    /// What the filesystem supports, by default nothing beyond reading and writing
+    fn capabilities(&self) -> FilesystemCapabilities {
+        FilesystemCapabilities::default()
+    }
     async fn read_file_by_path(&self, path: &str) -> Result<FileInfo, Errors>;
     async fn write_file_by_path(&self, path: &str, content: &str) -> Result<(), Errors>;
     async fn list_dir_by_path(&self, path: &str) -> Result<Vec<DirItemInfo>, Errors>;
+    /// Rename or move a file or a folder
+    async fn rename_by_path(&self, _from: &str, _to: &str) -> Result<(), Errors> {
+        Err(Errors::Fs(FilesystemErrors::FileNotSupported))
+    }
+    /// Read the raw content of a file, by default only text files are supported
+    async fn read_bytes_by_path(&self, path: &str) -> Result<Vec<u8>, Errors> {
+        self.read_file_by_path(path)
+            .await
+            .map(|file| file.content.into_bytes())
+    }
+    /// Write raw content into a file, by default only UTF-8 content is supported
+    async fn write_bytes_by_path(&self, path: &str, content: &[u8]) -> Result<(), Errors> {
+        let content = std::str::from_utf8(content)
+            .map_err(|_| Errors::Fs(FilesystemErrors::FileNotSupported))?;
+        self.write_file_by_path(path, content).await
+    }
+    /// Read a text file in the given encoding, it's detected if there is none
+    async fn read_file_with_encoding(
+        &self,
+        path: &str,
+        encoding: Option<FileEncoding>,
+    ) -> Result<FileInfo, Errors> {
+        let content = self.read_bytes_by_path(path).await?;
+        let encoding = match encoding {
+            Some(encoding) => encoding,
+            None if is_binary(&content) => {
+                return Err(Errors::Fs(FilesystemErrors::FileNotSupported))
+            }
+            None => FileEncoding::detect(&content),
+        };
+        let content = encoding.decode(&content).map_err(Errors::Fs)?;
+        Ok(FileInfo::new(path, content).with_encoding(encoding))
+    }
+    /// Write a text file in the given encoding
+    async fn write_file_with_encoding(
+        &self,
+        path: &str,
+        content: &str,
+        encoding: FileEncoding,
+    ) -> Result<(), Errors> {
+        let content = encoding.encode(content).map_err(Errors::Fs)?;
+        self.write_bytes_by_path(path, &content).await
+    }
+    /// Create a folder and all it's missing parents
+    async fn create_dir_by_path(&self, _path: &str) -> Result<(), Errors> {
+        Err(Errors::Fs(FilesystemErrors::FileNotSupported))
+    }
+    /// Size of a file in bytes
+    async fn get_file_size(&self, path: &str) -> Result<u64, Errors> {
+        self.read_bytes_by_path(path)
+            .await
+            .map(|content| content.len() as u64)
+    }
+    /// Read up to `length` bytes starting at `offset`, by default the whole file is read
+    async fn read_range(&self, path: &str, offset: u64, length: usize) -> Result<Vec<u8>, Errors> {
+        let content = self.read_bytes_by_path(path).await?;
+        let start = (offset as usize).min(content.len());
+        let end = start.saturating_add(length).min(content.len());
+        Ok(content[start..end].to_vec())
+    }
+    /// Read a file in chunks of `chunk_size` bytes, by default the whole file is read first
+    async fn read_chunks(&self, path: &str, chunk_size: usize) -> Result<FileChunks, Errors> {
+        let content = self.read_bytes_by_path(path).await?;
+        let chunks = content
+            .chunks(chunk_size.max(1))
+            .map(|chunk| Ok(chunk.to_vec()))
+            .collect::<Vec<Result<Vec<u8>, Errors>>>();
+        Ok(Box::pin(tokio_stream::iter(chunks)))
+    }
+    /// Write `content` at `offset`, creating the file if necessary
+    async fn write_chunk(&self, _path: &str, _offset: u64, _content: &[u8]) -> Result<(), Errors> {
+        Err(Errors::Fs(FilesystemErrors::FileNotSupported))
+    }
+    /// Append `content` at the end of a file, creating the file if necessary
+    async fn append_file_by_path(&self, _path: &str, _content: &[u8]) -> Result<(), Errors> {
+        Err(Errors::Fs(FilesystemErrors::FileNotSupported))
+    }
+    /// Delete a file, or a folder with all it's content
+    async fn delete_by_path(&self, _path: &str) -> Result<(), Errors> {
+        Err(Errors::Fs(FilesystemErrors::FileNotSupported))
+    }
+    /// Move a file or a folder to the trash of the platform, so it can be restored
+    async fn move_to_trash(&self, _path: &str) -> Result<(), Errors> {
+        Err(Errors::Fs(FilesystemErrors::FileNotSupported))
+    }
+    /// Move a file or a folder, by default it's renamed
+    async fn move_item(&self, from: &str, to: &str) -> Result<(), Errors> {
+        self.rename_by_path(from, to).await
+    }
+    /// Copy a folder with all it's content
+    ///
+    /// # Arguments
+    ///
+    /// * `from`           - Folder to copy
+    /// * `to`             - Where to copy it, it's created if necessary
+    /// * `progress`       - Called after every copied file
+    /// * `cancellation`   - Stops the copy once it's cancelled, the copied files are kept
+    ///
+    async fn copy_dir_recursive(
+        &self,
+        from: &str,
+        to: &str,
+        progress: Option<&ProgressCallback>,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<(), Errors> {
+        // Find all the files first so the total is known
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+        let mut pending = vec![(from.to_owned(), to.to_owned())];
+
+        while let Some((source, destination)) = pending.pop() {
+            for item in self.list_dir_by_path(&source).await? {
+                let target = join_path(&destination, &item.name);
+                if item.is_file {
+                    files.push((item.path, target));
+                } else {
+                    pending.push((item.path, target));
+                }
+            }
+            dirs.push(destination);
+        }
+
+        for dir in &dirs {
+            self.create_dir_by_path(dir).await?;
+        }
+
+        let total = files.len();
+        for (i, (source, destination)) in files.iter().enumerate() {
+            if cancellation.is_some_and(CancellationToken::is_cancelled) {
+                return Err(Errors::Fs(FilesystemErrors::Cancelled));
+            }
+
+            let content = self.read_bytes_by_path(source).await?;
+            self.write_bytes_by_path(destination, &content).await?;
+
+            if let Some(progress) = progress {
+                progress(i + 1, total);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Return where the path ends up after renaming `from` to `to`, if it's affected at all
+///
+/// # Arguments
+///
+/// * `path`   - A path, e.g `/project/src/main.rs`
+/// * `from`   - Renamed file or folder, e.g `/project/src`
+/// * `to`     - New path, e.g `/project/source`
+///
+pub fn remap_path(path: &str, from: &str, to: &str) -> Option<String> {
+    if path == from {
+        return Some(to.to_owned());
+    }
+
+    let from = from.trim_end_matches(['/', '\\']);
+    let rest = path.strip_prefix(from)?;
+
+    if rest.is_empty() {
+        Some(to.to_owned())
+    } else if rest.starts_with(['/', '\\']) {
+        Some(format!("{}{}", to.trim_end_matches(['/', '\\']), rest))
+    } else {
+        None
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -28,6 +280,12 @@ pub struct DirItemInfo {
     pub path: String,
     pub name: String,
     pub is_file: bool,
+    /// Files grouped under this one by the nesting rules
+    #[serde(default)]
+    pub nested: Vec<DirItemInfo>,
+    /// Icon of the item in the active icon theme
+    #[serde(default)]
+    pub icon: Option<FileIcon>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -72,20 +330,73 @@ pub fn get_format_from_path(path: &str) -> FileFormat {
     }
 }
 
+/// A range of a file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileChunk {
+    pub path: String,
+    pub offset: u64,
+    pub content: Vec<u8>,
+    /// Size of the whole file
+    pub size: u64,
+    /// If the chunk reaches the end of the file
+    pub is_last: bool,
+}
+
 /// Contains information about a file
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct FileInfo {
     pub content: String,
     pub format: FileFormat,
     pub path: String,
+    #[serde(default)]
+    pub line_ending: LineEnding,
+    #[serde(default)]
+    pub encoding: FileEncoding,
 }
 
 impl FileInfo {
     pub fn new(path: &str, content: String) -> Self {
         Self {
+            line_ending: LineEnding::detect(&content),
             content,
             format: get_format_from_path(path),
             path: path.to_owned(),
+            encoding: FileEncoding::Utf8,
         }
     }
+
+    /// Encoding the content was decoded from
+    pub fn with_encoding(mut self, encoding: FileEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::remap_path;
+
+    #[test]
+    fn remap_renamed_paths() {
+        assert_eq!(
+            remap_path("/project/src/main.rs", "/project/src", "/project/source"),
+            Some("/project/source/main.rs".to_string())
+        );
+        assert_eq!(
+            remap_path("/project/src", "/project/src/", "/project/source"),
+            Some("/project/source".to_string())
+        );
+        assert_eq!(
+            remap_path(
+                "C:\\project\\src\\main.rs",
+                "C:\\project\\src",
+                "C:\\project\\source"
+            ),
+            Some("C:\\project\\source\\main.rs".to_string())
+        );
+        assert_eq!(
+            remap_path("/project/srcs/main.rs", "/project/src", "/project/source"),
+            None
+        );
+    }
 }
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use super::DirItemInfo;
+
+/// Placeholder of the child patterns replaced with what the `*` of the parent pattern matched
+const CAPTURE: &str = "$(capture)";
+
+/// Match a name against a pattern where `*` matches any text, returns what the first `*` matched
+fn match_pattern(pattern: &str, name: &str) -> Option<String> {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = name.strip_prefix(first)?;
+    let mut capture = None;
+    let mut parts = parts.peekable();
+
+    while let Some(part) = parts.next() {
+        // The last part must be at the end of the name, the others at their first occurrence
+        let skipped = if parts.peek().is_none() {
+            rest.strip_suffix(part)?
+        } else {
+            &rest[..rest.find(part)?]
+        };
+        capture.get_or_insert_with(|| skipped.to_owned());
+        rest = &rest[skipped.len() + part.len()..];
+    }
+
+    if rest.is_empty() {
+        Some(capture.unwrap_or_default())
+    } else {
+        None
+    }
+}
+
+/// Check if a file is a child of the parent it's compared with
+fn is_child(children: &[String], capture: &str, name: &str) -> bool {
+    children
+        .iter()
+        .any(|child| match_pattern(&child.replace(CAPTURE, capture), name).is_some())
+}
+
+/// Group the files of a directory listing under their parents, following the nesting rules
+///
+/// # Arguments
+///
+/// * `items`   - Items of the directory, in the order they are shown
+/// * `rules`   - Child patterns by parent pattern, e.g `*.js` => [`$(capture).js.map`]
+///
+pub fn nest_items(
+    items: Vec<DirItemInfo>,
+    rules: &HashMap<String, Vec<String>>,
+) -> Vec<DirItemInfo> {
+    if rules.is_empty() {
+        return items;
+    }
+
+    // Sorted so the first matching rule is always the same one
+    let mut rules = rules.iter().collect::<Vec<(&String, &Vec<String>)>>();
+    rules.sort_by_key(|(parent, _)| *parent);
+
+    // Index of the parent of each nested item
+    let mut parents: Vec<Option<usize>> = vec![None; items.len()];
+    let mut has_children = vec![false; items.len()];
+
+    for (parent_index, parent) in items.iter().enumerate() {
+        if !parent.is_file || parents[parent_index].is_some() {
+            continue;
+        }
+
+        for (pattern, children) in &rules {
+            let capture = match match_pattern(pattern, &parent.name) {
+                Some(capture) => capture,
+                None => continue,
+            };
+
+            for (child_index, child) in items.iter().enumerate() {
+                // Only one level of nesting
+                if child_index == parent_index
+                    || !child.is_file
+                    || parents[child_index].is_some()
+                    || has_children[child_index]
+                {
+                    continue;
+                }
+                if is_child(children, &capture, &child.name) {
+                    parents[child_index] = Some(parent_index);
+                    has_children[parent_index] = true;
+                }
+            }
+        }
+    }
+
+    let mut nested: Vec<Vec<DirItemInfo>> = vec![Vec::new(); items.len()];
+    let mut result = Vec::new();
+    for (index, item) in items.into_iter().enumerate() {
+        match parents[index] {
+            Some(parent) => nested[parent].push(item),
+            None => result.push((index, item)),
+        }
+    }
+
+    result
+        .into_iter()
+        .map(|(index, mut item)| {
+            item.nested = std::mem::take(&mut nested[index]);
+            item
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{match_pattern, nest_items};
+    use crate::filesystems::DirItemInfo;
+
+    fn file(name: &str) -> DirItemInfo {
+        DirItemInfo {
+            path: format!("/project/{}", name),
+            name: name.to_owned(),
+            is_file: true,
+            nested: Vec::new(),
+            icon: None,
+        }
+    }
+
+    #[test]
+    fn match_patterns() {
+        assert_eq!(match_pattern("*.js", "foo.js"), Some("foo".to_owned()));
+        assert_eq!(match_pattern("*.js", "foo.ts"), None);
+        assert_eq!(
+            match_pattern("Cargo.toml", "Cargo.toml"),
+            Some("".to_owned())
+        );
+        assert_eq!(
+            match_pattern("foo.*.js", "foo.bar.js"),
+            Some("bar".to_owned())
+        );
+        assert_eq!(match_pattern("*.*", "a.b.c"), Some("a".to_owned()));
+    }
+
+    #[test]
+    fn nest_files() {
+        let rules = HashMap::from([
+            (
+                "*.js".to_owned(),
+                vec!["$(capture).js.map".to_owned(), "$(capture).d.ts".to_owned()],
+            ),
+            ("Cargo.toml".to_owned(), vec!["Cargo.lock".to_owned()]),
+        ]);
+        let items = vec![
+            DirItemInfo {
+                path: "/project/src".to_owned(),
+                name: "src".to_owned(),
+                is_file: false,
+                nested: Vec::new(),
+                icon: None,
+            },
+            file("Cargo.lock"),
+            file("Cargo.toml"),
+            file("bar.js.map"),
+            file("foo.d.ts"),
+            file("foo.js"),
+            file("foo.js.map"),
+        ];
+
+        let result = nest_items(items, &rules);
+        let names = result
+            .iter()
+            .map(|item| item.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, vec!["src", "Cargo.toml", "bar.js.map", "foo.js"]);
+        assert_eq!(result[1].nested, vec![file("Cargo.lock")]);
+        assert_eq!(result[3].nested, vec![file("foo.d.ts"), file("foo.js.map")]);
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt;
+use std::str::FromStr;
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{join_path, FilesystemErrors};
+
+/// Scheme of the files of the registered filesystems
+pub const GRAVITON_SCHEME: &str = "graviton";
+
+/// Scheme of the scratch documents not saved anywhere yet
+pub const UNTITLED_SCHEME: &str = "untitled";
+
+/// Characters kept as they are in the paths of `file://` URIs, the rest is percent-encoded
+const FILE_URI_PATH: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b':')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Identifies a file or folder in a filesystem of the State, e.g `graviton://local/home/user/notes.md`
+///
+/// The path is kept as the filesystem understands it, so Windows paths look like
+/// `graviton://local/C:\Users\notes.md`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GravitonUri {
+    scheme: String,
+    filesystem: String,
+    path: String,
+}
+
+/// Check if a path starts with a Windows drive, e.g `C:`
+fn starts_with_drive(path: &str) -> bool {
+    let mut chars = path.chars();
+    matches!(
+        (chars.next(), chars.next()),
+        (Some(letter), Some(':')) if letter.is_ascii_alphabetic()
+    )
+}
+
+impl GravitonUri {
+    /// A file or folder of a registered filesystem
+    pub fn new(filesystem: &str, path: &str) -> Self {
+        Self::with_scheme(GRAVITON_SCHEME, filesystem, path)
+    }
+
+    pub fn with_scheme(scheme: &str, filesystem: &str, path: &str) -> Self {
+        Self {
+            scheme: scheme.to_owned(),
+            filesystem: filesystem.to_owned(),
+            path: path.to_owned(),
+        }
+    }
+
+    /// A file or folder of the `local` filesystem
+    pub fn local(path: &str) -> Self {
+        Self::new("local", path)
+    }
+
+    /// Parse an URI like `scheme://fs-name/path`
+    pub fn parse(uri: &str) -> Result<Self, FilesystemErrors> {
+        let (scheme, rest) = uri.split_once("://").ok_or(FilesystemErrors::InvalidUri)?;
+        let (filesystem, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+
+        if scheme.is_empty() || filesystem.is_empty() {
+            return Err(FilesystemErrors::InvalidUri);
+        }
+
+        // Windows paths don't start with a slash
+        let path = match path.strip_prefix('/') {
+            Some(drive_path) if starts_with_drive(drive_path) => drive_path,
+            _ => path,
+        };
+
+        Ok(Self::with_scheme(scheme, filesystem, path))
+    }
+
+    /// Convert a `file://` URI, like the ones used by language servers, into a local URI
+    pub fn from_file_uri(uri: &str) -> Result<Self, FilesystemErrors> {
+        let path = uri
+            .strip_prefix("file://")
+            .ok_or(FilesystemErrors::InvalidUri)?;
+        let path = percent_decode_str(path)
+            .decode_utf8()
+            .map_err(|_| FilesystemErrors::InvalidUri)?;
+        let path = match path.strip_prefix('/') {
+            Some(drive_path) if starts_with_drive(drive_path) => drive_path,
+            _ => &path,
+        };
+        Ok(Self::local(path))
+    }
+
+    /// The `file://` URI of the path, e.g for language servers
+    pub fn to_file_uri(&self) -> String {
+        let path = self.path.replace('\\', "/");
+        let path = utf8_percent_encode(&path, FILE_URI_PATH).to_string();
+        if path.starts_with('/') {
+            format!("file://{}", path)
+        } else {
+            format!("file:///{}", path)
+        }
+    }
+
+    pub fn get_scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    pub fn get_filesystem(&self) -> &str {
+        &self.filesystem
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    /// A file or folder inside this folder
+    pub fn join(&self, name: &str) -> Self {
+        Self {
+            path: join_path(&self.path, name),
+            ..self.clone()
+        }
+    }
+}
+
+impl fmt::Display for GravitonUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.path.starts_with('/') { "" } else { "/" };
+        write!(
+            f,
+            "{}://{}{}{}",
+            self.scheme, self.filesystem, separator, self.path
+        )
+    }
+}
+
+impl FromStr for GravitonUri {
+    type Err = FilesystemErrors;
+
+    fn from_str(uri: &str) -> Result<Self, Self::Err> {
+        Self::parse(uri)
+    }
+}
+
+impl Serialize for GravitonUri {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for GravitonUri {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let uri = String::deserialize(deserializer)?;
+        Self::parse(&uri).map_err(|_| serde::de::Error::custom("invalid Graviton URI"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GravitonUri;
+
+    #[test]
+    fn parse_and_format_uris() {
+        let uri = GravitonUri::parse("graviton://local/home/user/notes.md").unwrap();
+        assert_eq!(uri.get_filesystem(), "local");
+        assert_eq!(uri.get_path(), "/home/user/notes.md");
+        assert_eq!(uri.to_file_uri(), "file:///home/user/notes.md");
+        assert_eq!(uri.to_string(), "graviton://local/home/user/notes.md");
+
+        let windows = GravitonUri::local("C:\\Users\\notes.md");
+        assert_eq!(windows.to_string(), "graviton://local/C:\\Users\\notes.md");
+        assert_eq!(GravitonUri::parse(&windows.to_string()).unwrap(), windows);
+        assert_eq!(windows.to_file_uri(), "file:///C:/Users/notes.md");
+
+        assert_eq!(
+            GravitonUri::from_file_uri("file:///C:/Users/notes.md")
+                .unwrap()
+                .get_path(),
+            "C:/Users/notes.md"
+        );
+        assert_eq!(
+            GravitonUri::parse("untitled://memory")
+                .unwrap()
+                .join("Untitled-1")
+                .to_string(),
+            "untitled://memory/Untitled-1"
+        );
+
+        assert!(GravitonUri::parse("/home/user").is_err());
+        assert!(GravitonUri::parse("graviton:///home").is_err());
+    }
+
+    #[test]
+    fn encode_file_uris() {
+        let paths = [
+            ("/home/user/my notes.md", "file:///home/user/my%20notes.md"),
+            ("/home/user/#1.md", "file:///home/user/%231.md"),
+            (
+                "/home/user/café/日本.md",
+                "file:///home/user/caf%C3%A9/%E6%97%A5%E6%9C%AC.md",
+            ),
+            ("C:/Users/100%.md", "file:///C:/Users/100%25.md"),
+        ];
+
+        for (path, file_uri) in paths {
+            let uri = GravitonUri::local(path);
+            assert_eq!(uri.to_file_uri(), file_uri);
+            assert_eq!(GravitonUri::from_file_uri(file_uri).unwrap(), uri);
+        }
+
+        assert!(GravitonUri::from_file_uri("file:///home/%FF.md").is_err());
+    }
+}
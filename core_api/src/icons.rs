@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// File icons of an icon theme, the values are the icon IDs of the theme
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct IconTheme {
+    pub id: String,
+    /// Icons by file name, e.g `Cargo.toml`, they win over the extensions
+    pub file_names: HashMap<String, String>,
+    /// Icons by file extension without the dot, e.g `rs`
+    pub file_extensions: HashMap<String, String>,
+    /// Icon of the folders
+    pub folder: Option<String>,
+}
+
+/// Icon to show next to a file or folder
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "icon_type")]
+pub enum FileIcon {
+    /// An icon of the active theme
+    Theme { theme: String, id: String },
+    /// Generated when the theme has no icon for the file, a letter over a colored badge
+    Fallback { letter: String, color: String },
+}
+
+/// FNV-1a, so the colors are the same on every platform and version
+fn hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Convert a hue in degrees into an RGB hex color with a fixed saturation and lightness
+fn hue_to_hex(hue: f64) -> String {
+    let (saturation, lightness) = (0.55, 0.45);
+    let chroma = (1.0 - (2.0 * lightness - 1.0_f64).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = lightness - chroma / 2.0;
+
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    let channel = |value: f64| ((value + m) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(r), channel(g), channel(b))
+}
+
+/// Generate the icon of a file, the same file type always gets the same icon
+pub fn get_fallback_icon(file_name: &str) -> FileIcon {
+    // Dotfiles like `.gitignore` have no extension
+    let key = Path::new(file_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or(file_name)
+        .to_lowercase();
+
+    let letter = key
+        .chars()
+        .find(|c| c.is_alphanumeric())
+        .map(|c| c.to_uppercase().to_string())
+        .unwrap_or_else(|| "?".to_owned());
+
+    FileIcon::Fallback {
+        letter,
+        color: hue_to_hex((hash(&key) % 360) as f64),
+    }
+}
+
+impl IconTheme {
+    /// Icon of a file or folder, files the theme doesn't know get a generated one
+    pub fn get_icon(&self, name: &str, is_file: bool) -> Option<FileIcon> {
+        let theme_icon = |id: &String| FileIcon::Theme {
+            theme: self.id.clone(),
+            id: id.clone(),
+        };
+
+        if !is_file {
+            return self.folder.as_ref().map(theme_icon);
+        }
+
+        let by_name = self.file_names.get(name);
+        let by_extension = || {
+            let extension = Path::new(name).extension()?.to_str()?.to_lowercase();
+            self.file_extensions.get(&extension)
+        };
+
+        match by_name.or_else(by_extension) {
+            Some(id) => Some(theme_icon(id)),
+            None => Some(get_fallback_icon(name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{get_fallback_icon, FileIcon, IconTheme};
+
+    #[test]
+    fn theme_icons_and_fallbacks() {
+        let theme = IconTheme {
+            id: "minimal".to_owned(),
+            file_names: HashMap::from([("Cargo.toml".to_owned(), "cargo".to_owned())]),
+            file_extensions: HashMap::from([("rs".to_owned(), "rust".to_owned())]),
+            folder: None,
+        };
+
+        assert_eq!(
+            theme.get_icon("main.RS", true),
+            Some(FileIcon::Theme {
+                theme: "minimal".to_owned(),
+                id: "rust".to_owned()
+            })
+        );
+        assert_eq!(
+            theme.get_icon("Cargo.toml", true),
+            Some(FileIcon::Theme {
+                theme: "minimal".to_owned(),
+                id: "cargo".to_owned()
+            })
+        );
+        assert_eq!(theme.get_icon("src", false), None);
+
+        // Deterministic, and only the extension matters
+        let icon = theme.get_icon("notes.md", true).unwrap();
+        assert_eq!(icon, get_fallback_icon("readme.MD"));
+        match icon {
+            FileIcon::Fallback { letter, color } => {
+                assert_eq!(letter, "M");
+                assert_eq!(color.len(), 7);
+            }
+            _ => panic!("Expected a fallback icon"),
+        }
+        assert_ne!(get_fallback_icon("a.md"), get_fallback_icon("a.py"));
+
+        match get_fallback_icon(".gitignore") {
+            FileIcon::Fallback { letter, .. } => assert_eq!(letter, "G"),
+            _ => panic!("Expected a fallback icon"),
+        }
+    }
+}
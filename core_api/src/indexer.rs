@@ -0,0 +1,505 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, RwLock};
+
+use globset::GlobSet;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::Mutex;
+
+use crate::filesystems::{remap_path, Filesystem};
+use crate::matcher::{MatchKind, Matchable};
+use crate::progress::ProgressHandle;
+use crate::search::{build_globset, is_ignored, relative_path, CancellationToken, IgnoreRule};
+use crate::states::StateEvent;
+use crate::workspaces::get_workspace_id;
+use crate::Errors;
+
+/// Files indexed at most per workspace, the rest are left out
+const MAX_INDEXED_FILES: usize = 200_000;
+
+/// Results returned at most by the quick-open
+pub const QUICK_OPEN_LIMIT: usize = 100;
+
+/// A symbol of a file, as returned by `textDocument/documentSymbol`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IndexedSymbol {
+    pub name: String,
+    /// LSP `SymbolKind`
+    pub kind: u32,
+    /// Symbol it's declared in, e.g the struct of a method
+    pub container: Option<String>,
+    /// Zero-based line where it's declared
+    pub line: u32,
+}
+
+impl IndexedSymbol {
+    /// Flatten the `DocumentSymbol[]` or `SymbolInformation[]` replied by a Language Server
+    pub fn from_lsp(reply: &Value) -> Vec<Self> {
+        let mut symbols = Vec::new();
+        if let Some(items) = reply.as_array() {
+            for item in items {
+                Self::collect(item, None, &mut symbols);
+            }
+        }
+        symbols
+    }
+
+    fn collect(item: &Value, container: Option<&str>, symbols: &mut Vec<Self>) {
+        let name = match item.get("name").and_then(Value::as_str) {
+            Some(name) => name,
+            None => return,
+        };
+
+        // `DocumentSymbol` has a range, `SymbolInformation` has a location with a range
+        let range = item
+            .get("selectionRange")
+            .or_else(|| item.get("range"))
+            .or_else(|| item.pointer("/location/range"));
+        let line = range
+            .and_then(|range| range.pointer("/start/line"))
+            .and_then(Value::as_u64)
+            .unwrap_or_default();
+
+        symbols.push(Self {
+            name: name.to_owned(),
+            kind: item.get("kind").and_then(Value::as_u64).unwrap_or_default() as u32,
+            container: container
+                .or_else(|| item.get("containerName").and_then(Value::as_str))
+                .map(str::to_owned),
+            line: line as u32,
+        });
+
+        if let Some(children) = item.get("children").and_then(Value::as_array) {
+            for child in children {
+                Self::collect(child, Some(name), symbols);
+            }
+        }
+    }
+}
+
+/// A result of the quick-open
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "item_type")]
+pub enum QuickOpenItem {
+    File {
+        filesystem: String,
+        path: String,
+        /// Path relative to the workspace, it's what the query is matched against
+        relative: String,
+    },
+    Symbol {
+        filesystem: String,
+        path: String,
+        symbol: IndexedSymbol,
+    },
+}
+
+impl Matchable for QuickOpenItem {
+    fn get_text(&self) -> &str {
+        match self {
+            Self::File { relative, .. } => relative,
+            Self::Symbol { symbol, .. } => &symbol.name,
+        }
+    }
+
+    fn get_key(&self) -> &str {
+        match self {
+            Self::File { path, .. } | Self::Symbol { path, .. } => path,
+        }
+    }
+
+    fn get_kind(&self) -> MatchKind {
+        match self {
+            Self::File { .. } => MatchKind::Path,
+            Self::Symbol { .. } => MatchKind::Text,
+        }
+    }
+}
+
+/// Files and symbols of a workspace
+struct WorkspaceIndex {
+    filesystem: String,
+    root: String,
+    files: BTreeSet<String>,
+    /// Symbols by the path of their file
+    symbols: HashMap<String, Vec<IndexedSymbol>>,
+    /// Rules of the .gitignore files found so far
+    rules: Vec<IgnoreRule>,
+    excluded: GlobSet,
+    /// Stops the crawling and the listening of the events
+    token: CancellationToken,
+}
+
+impl WorkspaceIndex {
+    /// If a file or folder belongs in the index, it and its parent folders must not be ignored
+    fn should_index(&self, path: &str, is_dir: bool) -> bool {
+        if remap_path(path, &self.root, &self.root).is_none() {
+            return false;
+        }
+
+        let relative = relative_path(&self.root, path);
+        if relative.is_empty() || relative.split('/').any(|segment| segment == ".git") {
+            return false;
+        }
+
+        let root = self.root.trim_end_matches(['/', '\\']);
+        let ancestors_ignored = relative
+            .match_indices('/')
+            .map(|(end, _)| &relative[..end])
+            .any(|ancestor| {
+                self.excluded.is_match(ancestor)
+                    || is_ignored(&self.rules, &format!("{}/{}", root, ancestor), true)
+            });
+
+        !ancestors_ignored
+            && !self.excluded.is_match(&relative)
+            && !is_ignored(&self.rules, path, is_dir)
+    }
+
+    fn insert_file(&mut self, path: &str) {
+        if self.files.len() < MAX_INDEXED_FILES && self.should_index(path, false) {
+            self.files.insert(path.to_owned());
+        }
+    }
+
+    /// Forget a file or everything inside a folder
+    fn remove_path(&mut self, path: &str) {
+        self.files
+            .retain(|file| remap_path(file, path, path).is_none());
+        self.symbols
+            .retain(|file, _| remap_path(file, path, path).is_none());
+    }
+
+    /// Update the index after a change in the State, returns false if it's not about this workspace
+    fn apply(&mut self, event: &StateEvent) -> bool {
+        match event {
+            StateEvent::FileWritten { filesystem, path } if *filesystem == self.filesystem => {
+                self.insert_file(path);
+            }
+            StateEvent::PathDeleted { filesystem, path } if *filesystem == self.filesystem => {
+                self.remove_path(path);
+            }
+            StateEvent::PathRenamed {
+                filesystem,
+                from,
+                to,
+            } if *filesystem == self.filesystem => {
+                let renamed = self
+                    .files
+                    .iter()
+                    .filter_map(|file| remap_path(file, from, to))
+                    .collect::<Vec<String>>();
+                let symbols = self
+                    .symbols
+                    .iter()
+                    .filter_map(|(file, symbols)| {
+                        Some((remap_path(file, from, to)?, symbols.clone()))
+                    })
+                    .collect::<Vec<_>>();
+
+                self.remove_path(from);
+                for file in renamed {
+                    self.insert_file(&file);
+                }
+                for (file, symbols) in symbols {
+                    if self.files.contains(&file) {
+                        self.symbols.insert(file, symbols);
+                    }
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Searchable index of the files of the opened workspaces, shared with the tasks keeping it up to date
+#[derive(Clone, Default)]
+pub struct Indexer(Arc<RwLock<HashMap<String, WorkspaceIndex>>>);
+
+impl Indexer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Crawl a workspace in the background, it's searchable while it's being crawled.
+    /// Then it's kept up to date with the events of the State, it's only crawled again if some were missed
+    ///
+    /// # Arguments
+    ///
+    /// * `filesystem`     - Name of its filesystem
+    /// * `fs`             - Its filesystem
+    /// * `root`           - Its root folder
+    /// * `excluded`       - Globs of the paths left out
+    /// * `events`         - Events of the State, to follow the changes
+    /// * `progress`       - Where to report the crawling
+    ///
+    pub fn index(
+        &self,
+        filesystem: &str,
+        fs: Arc<Mutex<Box<dyn Filesystem + Send>>>,
+        root: &str,
+        excluded: &[String],
+        mut events: Receiver<StateEvent>,
+        progress: ProgressHandle,
+    ) -> Result<(), Errors> {
+        let workspace_id = get_workspace_id(filesystem, root);
+        let token = CancellationToken::new();
+        let index = WorkspaceIndex {
+            filesystem: filesystem.to_owned(),
+            root: root.to_owned(),
+            files: BTreeSet::new(),
+            symbols: HashMap::new(),
+            rules: Vec::new(),
+            excluded: build_globset(excluded)?,
+            token: token.clone(),
+        };
+
+        if let Some(previous) = self.0.write().unwrap().insert(workspace_id.clone(), index) {
+            previous.token.cancel();
+        }
+
+        let indexer = self.clone();
+        let root = root.to_owned();
+
+        tokio::spawn(async move {
+            indexer
+                .crawl(&workspace_id, &fs, &root, &token, Some(progress))
+                .await;
+
+            while !token.is_cancelled() {
+                match events.recv().await {
+                    Ok(event) => {
+                        let mut indexes = indexer.0.write().unwrap();
+                        if let Some(index) = indexes.get_mut(&workspace_id) {
+                            index.apply(&event);
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => {
+                        indexer.crawl(&workspace_id, &fs, &root, &token, None).await;
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Walk the folders of a workspace, the files are added as each folder is listed
+    async fn crawl(
+        &self,
+        workspace_id: &str,
+        fs: &Arc<Mutex<Box<dyn Filesystem + Send>>>,
+        root: &str,
+        token: &CancellationToken,
+        mut progress: Option<ProgressHandle>,
+    ) {
+        if let Some(index) = self.0.write().unwrap().get_mut(workspace_id) {
+            index.files.clear();
+            index.rules.clear();
+        }
+
+        let mut pending_dirs = vec![root.to_owned()];
+
+        while let Some(dir) = pending_dirs.pop() {
+            if token.is_cancelled() {
+                break;
+            }
+
+            let gitignore = format!("{}/.gitignore", dir.trim_end_matches(['/', '\\']));
+            let gitignore = fs.lock().await.read_file_by_path(&gitignore).await;
+            let items = fs.lock().await.list_dir_by_path(&dir).await;
+
+            let indexed = {
+                let mut indexes = self.0.write().unwrap();
+                let index = match indexes.get_mut(workspace_id) {
+                    Some(index) => index,
+                    None => break,
+                };
+
+                if let Ok(gitignore) = gitignore {
+                    index.rules.extend(
+                        gitignore
+                            .content
+                            .lines()
+                            .filter_map(|line| IgnoreRule::parse(&dir, line)),
+                    );
+                }
+
+                for item in items.unwrap_or_default() {
+                    if item.is_file {
+                        index.insert_file(&item.path);
+                    } else if index.should_index(&item.path, true) {
+                        pending_dirs.push(item.path);
+                    }
+                }
+                index.files.len()
+            };
+
+            if let Some(progress) = &mut progress {
+                progress.report(indexed as u64, Some(&dir)).await;
+            }
+        }
+
+        if let Some(progress) = progress {
+            progress.finish().await;
+        }
+    }
+
+    /// Stop following a workspace and forget its files
+    pub fn remove(&self, workspace_id: &str) {
+        if let Some(index) = self.0.write().unwrap().remove(workspace_id) {
+            index.token.cancel();
+        }
+    }
+
+    /// Replace the symbols of a file, it's ignored if the file is not indexed
+    pub fn set_symbols(&self, filesystem: &str, path: &str, symbols: Vec<IndexedSymbol>) {
+        for index in self.0.write().unwrap().values_mut() {
+            if index.filesystem == filesystem && index.files.contains(path) {
+                index.symbols.insert(path.to_owned(), symbols.clone());
+            }
+        }
+    }
+
+    /// Every indexed file of all the workspaces
+    pub fn get_files(&self) -> Vec<QuickOpenItem> {
+        self.0
+            .read()
+            .unwrap()
+            .values()
+            .flat_map(|index| {
+                index.files.iter().map(|path| QuickOpenItem::File {
+                    filesystem: index.filesystem.clone(),
+                    path: path.clone(),
+                    relative: relative_path(&index.root, path),
+                })
+            })
+            .collect()
+    }
+
+    /// Approximate bytes held by the indexed paths and symbols
+    pub fn get_size(&self) -> usize {
+        self.0
+            .read()
+            .unwrap()
+            .values()
+            .map(|index| {
+                let files = index.files.iter().map(String::len).sum::<usize>();
+                let symbols = index
+                    .symbols
+                    .iter()
+                    .map(|(path, symbols)| {
+                        path.len()
+                            + symbols
+                                .iter()
+                                .map(|symbol| {
+                                    symbol.name.len()
+                                        + symbol.container.as_ref().map_or(0, String::len)
+                                })
+                                .sum::<usize>()
+                    })
+                    .sum::<usize>();
+                files + symbols
+            })
+            .sum()
+    }
+
+    /// Every indexed symbol of all the workspaces
+    pub fn get_symbols(&self) -> Vec<QuickOpenItem> {
+        self.0
+            .read()
+            .unwrap()
+            .values()
+            .flat_map(|index| {
+                index.symbols.iter().flat_map(|(path, symbols)| {
+                    symbols.iter().map(|symbol| QuickOpenItem::Symbol {
+                        filesystem: index.filesystem.clone(),
+                        path: path.clone(),
+                        symbol: symbol.clone(),
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashMap};
+
+    use serde_json::json;
+
+    use super::{IndexedSymbol, WorkspaceIndex};
+    use crate::search::{build_globset, CancellationToken, IgnoreRule};
+    use crate::states::StateEvent;
+
+    #[test]
+    fn follow_events() {
+        let mut index = WorkspaceIndex {
+            filesystem: "local".to_owned(),
+            root: "/project".to_owned(),
+            files: BTreeSet::new(),
+            symbols: HashMap::new(),
+            rules: IgnoreRule::parse("/project", "target/")
+                .into_iter()
+                .collect(),
+            excluded: build_globset(&["node_modules".to_owned()]).unwrap(),
+            token: CancellationToken::new(),
+        };
+
+        let written = |path: &str| StateEvent::FileWritten {
+            filesystem: "local".to_owned(),
+            path: path.to_owned(),
+        };
+
+        index.apply(&written("/project/src/main.rs"));
+        index.apply(&written("/project/target/debug/build.rs"));
+        index.apply(&written("/project/node_modules/lib/index.js"));
+        index.apply(&written("/elsewhere/notes.md"));
+        assert!(!index.apply(&StateEvent::FileWritten {
+            filesystem: "memory".to_owned(),
+            path: "/project/scratch.md".to_owned(),
+        }));
+        assert_eq!(
+            index.files.iter().collect::<Vec<_>>(),
+            vec!["/project/src/main.rs"]
+        );
+
+        index.apply(&StateEvent::PathRenamed {
+            filesystem: "local".to_owned(),
+            from: "/project/src".to_owned(),
+            to: "/project/source".to_owned(),
+        });
+        assert!(index.files.contains("/project/source/main.rs"));
+
+        index.apply(&StateEvent::PathDeleted {
+            filesystem: "local".to_owned(),
+            path: "/project/source".to_owned(),
+        });
+        assert!(index.files.is_empty());
+    }
+
+    #[test]
+    fn flatten_lsp_symbols() {
+        let reply = json!([{
+            "name": "State",
+            "kind": 23,
+            "range": { "start": { "line": 10, "character": 0 } },
+            "children": [{
+                "name": "quick_open",
+                "kind": 6,
+                "selectionRange": { "start": { "line": 42, "character": 4 } },
+            }]
+        }]);
+
+        let symbols = IndexedSymbol::from_lsp(&reply);
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[1].name, "quick_open");
+        assert_eq!(symbols[1].container.as_deref(), Some("State"));
+        assert_eq!(symbols[1].line, 42);
+    }
+}
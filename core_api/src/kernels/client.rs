@@ -0,0 +1,387 @@
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+use tracing::warn;
+use uuid::Uuid;
+use zeromq::{DealerSocket, Socket, SocketRecv, SocketSend, SubSocket};
+
+use super::wire::JupyterMessage;
+use super::{KernelConnectionInfo, KernelErrors, KernelExecution, KernelSpec};
+use crate::messaging::{ClientMessages, ServerMessages};
+
+/// How many times to try to connect to a kernel that is still booting
+const CONNECTION_RETRIES: u32 = 25;
+
+/// How long to wait for a kernel to reply to a shutdown request
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for a kernel to reply to an interrupt request
+const INTERRUPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long an execution can take, the kernel is considered stuck after it
+const EXECUTE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Directory for the connection files, only the current user can access it.
+/// It's `$XDG_RUNTIME_DIR/graviton` if it's set, or the Graviton runtime folder of the user
+pub fn get_runtime_dir() -> PathBuf {
+    if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return PathBuf::from(runtime_dir).join("graviton");
+    }
+
+    let data_dir = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+    };
+    data_dir
+        .unwrap_or_else(std::env::temp_dir)
+        .join("graviton")
+        .join("runtime")
+}
+
+/// Write the connection file so only the current user can read it, it contains the key of the kernel
+async fn write_connection_file(path: &Path, content: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        builder.mode(0o700);
+        builder.create(dir).await?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    file.write_all(content.as_bytes()).await?;
+    file.flush().await
+}
+
+/// Find a free local TCP port
+fn free_port() -> Result<u16, KernelErrors> {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .map_err(|_| KernelErrors::CouldNotStart)
+}
+
+/// Connect a socket, retrying while the kernel is still booting
+async fn connect_with_retries(
+    socket: &mut impl Socket,
+    endpoint: &str,
+) -> Result<(), KernelErrors> {
+    for _ in 0..CONNECTION_RETRIES {
+        if socket.connect(endpoint).await.is_ok() {
+            return Ok(());
+        }
+        sleep(Duration::from_millis(200)).await;
+    }
+    Err(KernelErrors::ConnectionFailed)
+}
+
+/// Send a request and wait for its reply
+async fn send_request(
+    socket: &Mutex<DealerSocket>,
+    key: &str,
+    message: JupyterMessage,
+) -> Result<JupyterMessage, KernelErrors> {
+    let msg_id = message.header.msg_id.clone();
+    let mut socket = socket.lock().await;
+
+    socket
+        .send(message.encode(key)?)
+        .await
+        .map_err(|_| KernelErrors::ConnectionFailed)?;
+
+    // Skip late replies of previous requests
+    loop {
+        let reply = socket
+            .recv()
+            .await
+            .map_err(|_| KernelErrors::ConnectionFailed)?;
+        let reply = JupyterMessage::decode(reply, key)?;
+        if reply.parent_msg_id() == Some(msg_id.as_str()) {
+            return Ok(reply);
+        }
+    }
+}
+
+/// Send a request and wait for its reply, for as long as the given duration
+async fn request(
+    socket: &Mutex<DealerSocket>,
+    key: &str,
+    message: JupyterMessage,
+    max_duration: Duration,
+) -> Result<JupyterMessage, KernelErrors> {
+    timeout(max_duration, send_request(socket, key, message))
+        .await
+        .map_err(|_| KernelErrors::Timeout)?
+}
+
+/// Connection file written for a launched kernel, it contains the key of the kernel so
+/// it's removed once the kernel is shutdown or dropped
+struct ConnectionFile(PathBuf);
+
+impl Drop for ConnectionFile {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+/// A connection to a Jupyter kernel
+#[derive(Clone)]
+pub struct Kernel {
+    pub id: String,
+    pub spec: Option<KernelSpec>,
+    connection: KernelConnectionInfo,
+    session: String,
+    shell: Arc<Mutex<DealerSocket>>,
+    control: Arc<Mutex<DealerSocket>>,
+    process: Arc<Mutex<Option<Child>>>,
+    connection_file: Arc<Mutex<Option<ConnectionFile>>>,
+    iopub_task: Arc<JoinHandle<()>>,
+}
+
+impl Kernel {
+    /// Launch a kernel process and connect to it
+    ///
+    /// # Arguments
+    ///
+    /// * `id`          - ID for the kernel
+    /// * `spec`        - How to launch the kernel
+    /// * `runtime_dir` - Where to write the connection file, see [`get_runtime_dir`]
+    /// * `state_id`    - The State the kernel belongs to
+    /// * `sender`      - Where to send the outputs of the kernel
+    ///
+    pub async fn start(
+        id: &str,
+        spec: KernelSpec,
+        runtime_dir: &Path,
+        state_id: u8,
+        sender: Sender<ClientMessages>,
+    ) -> Result<Self, KernelErrors> {
+        let connection = KernelConnectionInfo {
+            ip: "127.0.0.1".to_string(),
+            transport: "tcp".to_string(),
+            shell_port: free_port()?,
+            iopub_port: free_port()?,
+            stdin_port: free_port()?,
+            control_port: free_port()?,
+            hb_port: free_port()?,
+            key: Uuid::new_v4().to_string(),
+            signature_scheme: "hmac-sha256".to_string(),
+            kernel_name: spec.display_name.clone(),
+        };
+
+        let connection_file = runtime_dir.join(format!("kernel-{}.json", Uuid::new_v4()));
+        let connection_content =
+            serde_json::to_string(&connection).map_err(|_| KernelErrors::CouldNotStart)?;
+        write_connection_file(&connection_file, &connection_content)
+            .await
+            .map_err(|_| KernelErrors::CouldNotStart)?;
+        let connection_file = ConnectionFile(connection_file);
+
+        let argv = spec
+            .argv
+            .iter()
+            .map(|arg| arg.replace("{connection_file}", &connection_file.0.to_string_lossy()))
+            .collect::<Vec<String>>();
+        let (program, args) = argv.split_first().ok_or(KernelErrors::CouldNotStart)?;
+
+        let process = Command::new(program)
+            .args(args)
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|_| KernelErrors::CouldNotStart)?;
+
+        let mut kernel = Self::connect(id, connection, state_id, sender).await?;
+        kernel.spec = Some(spec);
+        kernel.process = Arc::new(Mutex::new(Some(process)));
+        kernel.connection_file = Arc::new(Mutex::new(Some(connection_file)));
+
+        Ok(kernel)
+    }
+
+    /// Connect to an already running kernel
+    pub async fn connect(
+        id: &str,
+        connection: KernelConnectionInfo,
+        state_id: u8,
+        sender: Sender<ClientMessages>,
+    ) -> Result<Self, KernelErrors> {
+        let mut shell = DealerSocket::new();
+        connect_with_retries(&mut shell, &connection.endpoint(connection.shell_port)).await?;
+
+        let mut control = DealerSocket::new();
+        connect_with_retries(&mut control, &connection.endpoint(connection.control_port)).await?;
+
+        let mut iopub = SubSocket::new();
+        connect_with_retries(&mut iopub, &connection.endpoint(connection.iopub_port)).await?;
+        iopub
+            .subscribe("")
+            .await
+            .map_err(|_| KernelErrors::ConnectionFailed)?;
+
+        // Forward all the outputs published by the kernel
+        let key = connection.key.clone();
+        let kernel_id = id.to_owned();
+        let iopub_task = tokio::spawn(async move {
+            while let Ok(message) = iopub.recv().await {
+                match JupyterMessage::decode(message, &key) {
+                    Ok(message) => {
+                        if let Some(output) = message.to_output() {
+                            let msg_id = message.parent_msg_id().unwrap_or_default().to_owned();
+                            sender
+                                .send(ClientMessages::ServerMessage(
+                                    ServerMessages::KernelOutput {
+                                        state_id,
+                                        kernel_id: kernel_id.clone(),
+                                        msg_id,
+                                        output,
+                                    },
+                                ))
+                                .await
+                                .ok();
+                        }
+                    }
+                    Err(_) => {
+                        warn!("Kernel <{}> published an invalid message", kernel_id);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            id: id.to_owned(),
+            spec: None,
+            connection,
+            session: Uuid::new_v4().to_string(),
+            shell: Arc::new(Mutex::new(shell)),
+            control: Arc::new(Mutex::new(control)),
+            process: Arc::new(Mutex::new(None)),
+            connection_file: Arc::new(Mutex::new(None)),
+            iopub_task: Arc::new(iopub_task),
+        })
+    }
+
+    /// Execute some code, the outputs are published as `ServerMessages::KernelOutput` messages
+    /// and this will return once the execution has finished
+    pub async fn execute(&self, code: &str) -> Result<KernelExecution, KernelErrors> {
+        let message = JupyterMessage::new(
+            &self.session,
+            "execute_request",
+            json!({
+                "code": code,
+                "silent": false,
+                "store_history": true,
+                "user_expressions": {},
+                "allow_stdin": false,
+                "stop_on_error": true,
+            }),
+        );
+        let msg_id = message.header.msg_id.clone();
+
+        let reply = request(&self.shell, &self.connection.key, message, EXECUTE_TIMEOUT).await?;
+
+        Ok(KernelExecution {
+            msg_id,
+            status: reply
+                .content
+                .get("status")
+                .and_then(|v| v.as_str())
+                .unwrap_or("error")
+                .to_string(),
+            execution_count: reply
+                .content
+                .get("execution_count")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32),
+        })
+    }
+
+    /// Interrupt the current execution
+    pub async fn interrupt(&self) -> Result<(), KernelErrors> {
+        let message = JupyterMessage::new(&self.session, "interrupt_request", json!({}));
+        request(
+            &self.control,
+            &self.connection.key,
+            message,
+            INTERRUPT_TIMEOUT,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Ask the kernel to shutdown and stop listening to it
+    pub async fn shutdown(&self) -> Result<(), KernelErrors> {
+        let message = JupyterMessage::new(
+            &self.session,
+            "shutdown_request",
+            json!({ "restart": false }),
+        );
+
+        let reply = request(
+            &self.control,
+            &self.connection.key,
+            message,
+            SHUTDOWN_TIMEOUT,
+        )
+        .await;
+
+        if reply.is_err() {
+            warn!("Kernel <{}> did not shutdown gracefully", self.id);
+        }
+
+        if let Some(mut process) = self.process.lock().await.take() {
+            process.kill().await.ok();
+        }
+        self.connection_file.lock().await.take();
+
+        self.iopub_task.abort();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{free_port, write_connection_file};
+
+    #[tokio::test]
+    async fn private_connection_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("runtime").join("kernel.json");
+
+        write_connection_file(&path, "{}").await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = |path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(path.parent().unwrap()), 0o700);
+            assert_eq!(mode(path.as_path()), 0o600);
+        }
+
+        // Existing files are never overwritten
+        assert!(write_connection_file(&path, "{}").await.is_err());
+    }
+
+    #[test]
+    fn free_ports() {
+        let port = free_port().unwrap();
+        assert!(std::net::TcpListener::bind(("127.0.0.1", port)).is_ok());
+    }
+}
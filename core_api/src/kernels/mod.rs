@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "kernels")]
+mod client;
+#[cfg(feature = "kernels")]
+mod wire;
+#[cfg(feature = "kernels")]
+pub use client::{get_runtime_dir, Kernel};
+
+/// Kernels errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum KernelErrors {
+    KernelNotFound,
+    CouldNotStart,
+    ConnectionFailed,
+    BadMessage,
+    /// The kernel didn't reply in time
+    Timeout,
+}
+
+/// How to launch a kernel, same format as Jupyter's `kernel.json`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KernelSpec {
+    /// Command to run, `{connection_file}` will be replaced with the connection file path
+    pub argv: Vec<String>,
+    pub display_name: String,
+    pub language: String,
+}
+
+/// Connection details of a kernel, same format as Jupyter's connection files
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KernelConnectionInfo {
+    pub ip: String,
+    pub transport: String,
+    pub shell_port: u16,
+    pub iopub_port: u16,
+    pub stdin_port: u16,
+    pub control_port: u16,
+    pub hb_port: u16,
+    pub key: String,
+    pub signature_scheme: String,
+    #[serde(default)]
+    pub kernel_name: String,
+}
+
+impl KernelConnectionInfo {
+    /// Address of the given port
+    pub fn endpoint(&self, port: u16) -> String {
+        format!("{}://{}:{}", self.transport, self.ip, port)
+    }
+}
+
+/// Result of executing a cell
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KernelExecution {
+    pub msg_id: String,
+    /// `ok`, `error` or `aborted`
+    pub status: String,
+    pub execution_count: Option<u32>,
+}
+
+/// Outputs published by a kernel while executing code
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "output_type")]
+pub enum KernelOutput {
+    Stream {
+        name: String,
+        text: String,
+    },
+    /// `data` is the JSON encoded mime bundle
+    ExecuteResult {
+        execution_count: Option<u32>,
+        data: String,
+    },
+    /// `data` is the JSON encoded mime bundle
+    DisplayData {
+        data: String,
+    },
+    Error {
+        ename: String,
+        evalue: String,
+        traceback: Vec<String>,
+    },
+    Status {
+        execution_state: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{KernelConnectionInfo, KernelOutput, KernelSpec};
+
+    #[test]
+    fn parse_jupyter_files() {
+        let spec: KernelSpec = serde_json::from_value(json!({
+            "argv": ["python3", "-m", "ipykernel_launcher", "-f", "{connection_file}"],
+            "display_name": "Python 3",
+            "language": "python",
+            "metadata": { "debugger": true }
+        }))
+        .unwrap();
+        assert_eq!(spec.argv[4], "{connection_file}");
+        assert_eq!(spec.language, "python");
+
+        // Older connection files have no kernel name
+        let info: KernelConnectionInfo = serde_json::from_value(json!({
+            "ip": "127.0.0.1",
+            "transport": "tcp",
+            "shell_port": 50001,
+            "iopub_port": 50002,
+            "stdin_port": 50003,
+            "control_port": 50004,
+            "hb_port": 50005,
+            "key": "secret",
+            "signature_scheme": "hmac-sha256"
+        }))
+        .unwrap();
+        assert_eq!(info.kernel_name, "");
+        assert_eq!(info.endpoint(info.shell_port), "tcp://127.0.0.1:50001");
+    }
+
+    #[test]
+    fn serialize_outputs() {
+        let output = KernelOutput::Error {
+            ename: "NameError".to_string(),
+            evalue: "name 'x' is not defined".to_string(),
+            traceback: Vec::new(),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&output).unwrap(),
+            json!({
+                "output_type": "Error",
+                "ename": "NameError",
+                "evalue": "name 'x' is not defined",
+                "traceback": []
+            })
+        );
+    }
+}
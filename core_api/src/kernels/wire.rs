@@ -0,0 +1,213 @@
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use uuid::Uuid;
+use zeromq::ZmqMessage;
+
+use super::{KernelErrors, KernelOutput};
+
+/// Separates the routing identities from the message itself
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+
+/// Version of the Jupyter messaging protocol
+const PROTOCOL_VERSION: &str = "5.3";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Header {
+    pub msg_id: String,
+    pub session: String,
+    pub username: String,
+    pub date: String,
+    pub msg_type: String,
+    pub version: String,
+}
+
+/// A message of the Jupyter messaging protocol
+#[derive(Debug, Clone)]
+pub struct JupyterMessage {
+    pub identities: Vec<Bytes>,
+    pub header: Header,
+    pub parent_header: Value,
+    pub metadata: Value,
+    pub content: Value,
+}
+
+impl JupyterMessage {
+    pub fn new(session: &str, msg_type: &str, content: Value) -> Self {
+        Self {
+            identities: Vec::new(),
+            header: Header {
+                msg_id: Uuid::new_v4().to_string(),
+                session: session.to_owned(),
+                username: "graviton".to_string(),
+                date: String::new(),
+                msg_type: msg_type.to_owned(),
+                version: PROTOCOL_VERSION.to_string(),
+            },
+            parent_header: json!({}),
+            metadata: json!({}),
+            content,
+        }
+    }
+
+    /// ID of the message this one is replying to
+    pub fn parent_msg_id(&self) -> Option<&str> {
+        self.parent_header.get("msg_id")?.as_str()
+    }
+
+    /// Serialize and sign the message into ZMQ frames
+    pub fn encode(&self, key: &str) -> Result<ZmqMessage, KernelErrors> {
+        let parts = [
+            serde_json::to_vec(&self.header),
+            serde_json::to_vec(&self.parent_header),
+            serde_json::to_vec(&self.metadata),
+            serde_json::to_vec(&self.content),
+        ]
+        .into_iter()
+        .collect::<Result<Vec<Vec<u8>>, _>>()
+        .map_err(|_| KernelErrors::BadMessage)?;
+
+        let mut frames = self.identities.clone();
+        frames.push(Bytes::from_static(DELIMITER));
+        frames.push(Bytes::from(sign(key, &parts)));
+        frames.extend(parts.into_iter().map(Bytes::from));
+
+        ZmqMessage::try_from(frames).map_err(|_| KernelErrors::BadMessage)
+    }
+
+    /// Verify and deserialize a message from ZMQ frames
+    pub fn decode(message: ZmqMessage, key: &str) -> Result<Self, KernelErrors> {
+        let frames = message.into_vec();
+        let delimiter = frames
+            .iter()
+            .position(|frame| &frame[..] == DELIMITER)
+            .ok_or(KernelErrors::BadMessage)?;
+
+        let rest = &frames[delimiter + 1..];
+        if rest.len() < 5 {
+            return Err(KernelErrors::BadMessage);
+        }
+
+        let parts = rest[1..5]
+            .iter()
+            .map(|frame| frame.to_vec())
+            .collect::<Vec<Vec<u8>>>();
+
+        if !verify(key, &parts, &rest[0]) {
+            return Err(KernelErrors::BadMessage);
+        }
+
+        let parse =
+            |part: &[u8]| serde_json::from_slice(part).map_err(|_| KernelErrors::BadMessage);
+
+        Ok(Self {
+            identities: frames[..delimiter].to_vec(),
+            header: serde_json::from_slice(&parts[0]).map_err(|_| KernelErrors::BadMessage)?,
+            parent_header: parse(&parts[1])?,
+            metadata: parse(&parts[2])?,
+            content: parse(&parts[3])?,
+        })
+    }
+
+    /// Convert an IOPub message into an output, if it's one
+    pub fn to_output(&self) -> Option<KernelOutput> {
+        let content = &self.content;
+        let text = |key: &str| content.get(key)?.as_str().map(|v| v.to_string());
+
+        match self.header.msg_type.as_str() {
+            "stream" => Some(KernelOutput::Stream {
+                name: text("name")?,
+                text: text("text")?,
+            }),
+            "execute_result" => Some(KernelOutput::ExecuteResult {
+                execution_count: content
+                    .get("execution_count")
+                    .and_then(|v| v.as_u64())
+                    .map(|v| v as u32),
+                data: content.get("data")?.to_string(),
+            }),
+            "display_data" => Some(KernelOutput::DisplayData {
+                data: content.get("data")?.to_string(),
+            }),
+            "error" => Some(KernelOutput::Error {
+                ename: text("ename")?,
+                evalue: text("evalue")?,
+                traceback: content
+                    .get("traceback")?
+                    .as_array()?
+                    .iter()
+                    .filter_map(|line| line.as_str().map(|v| v.to_string()))
+                    .collect(),
+            }),
+            "status" => Some(KernelOutput::Status {
+                execution_state: text("execution_state")?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// HMAC-SHA256 of the message parts, none when the kernel doesn't use a key
+fn get_mac(key: &str, parts: &[Vec<u8>]) -> Option<Hmac<Sha256>> {
+    if key.is_empty() {
+        return None;
+    }
+
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC can take keys of any size");
+    for part in parts {
+        mac.update(part);
+    }
+    Some(mac)
+}
+
+/// Hex encoded signature of the message parts, empty when the kernel doesn't use a key
+fn sign(key: &str, parts: &[Vec<u8>]) -> String {
+    get_mac(key, parts)
+        .map(|mac| hex::encode(mac.finalize().into_bytes()))
+        .unwrap_or_default()
+}
+
+/// Check the signature of a received message in constant time
+fn verify(key: &str, parts: &[Vec<u8>], signature: &[u8]) -> bool {
+    match get_mac(key, parts) {
+        Some(mac) => hex::decode(signature)
+            .map(|signature| mac.verify_slice(&signature).is_ok())
+            .unwrap_or(false),
+        None => signature.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::JupyterMessage;
+    use crate::kernels::KernelOutput;
+
+    #[test]
+    fn encode_and_decode() {
+        let message = JupyterMessage::new(
+            "session",
+            "stream",
+            json!({ "name": "stdout", "text": "hi" }),
+        );
+        let encoded = message.encode("secret").unwrap();
+
+        let decoded = JupyterMessage::decode(encoded.clone(), "secret").unwrap();
+        assert_eq!(decoded.header.msg_id, message.header.msg_id);
+        assert_eq!(
+            decoded.to_output(),
+            Some(KernelOutput::Stream {
+                name: "stdout".to_string(),
+                text: "hi".to_string()
+            })
+        );
+
+        assert!(JupyterMessage::decode(encoded.clone(), "wrong key").is_err());
+        assert!(JupyterMessage::decode(encoded, "").is_err());
+    }
+}
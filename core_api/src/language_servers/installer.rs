@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use super::LanguageServerErrors;
+use crate::extensions::installation::is_valid_extension_id;
+
+/// File kept in the folder of an installed language server, written once it's fully unpacked
+const INSTALLED_FILE: &str = "installed.json";
+
+/// How a language server download is packaged
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+    /// A single gzipped binary
+    Gz,
+    /// The binary itself
+    Binary,
+}
+
+/// Where to download a language server for a specific platform
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LanguageServerDownload {
+    pub url: String,
+    /// SHA-256 checksum of the download, hex encoded. It only proves the integrity of the sources
+    /// in the configurations, the updates come with their checksum so it just detects corrupted downloads
+    pub checksum: String,
+    pub archive: ArchiveFormat,
+    /// Relative path of the binary inside the archive, or its name for single files
+    pub binary: String,
+}
+
+/// A version of a language server that can be installed by the Core
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LanguageServerSource {
+    /// Also the name of the folder where it's installed
+    pub version: String,
+    /// Downloads by platform, e.g `linux-x86_64`, `macos-aarch64` or `windows-x86_64`
+    pub platforms: HashMap<String, LanguageServerDownload>,
+    /// Where to get the latest [`LanguageServerSource`], to check for updates
+    pub update_url: Option<String>,
+}
+
+impl LanguageServerSource {
+    /// Download for the platform the Core is running on
+    pub fn get_download(&self) -> Option<&LanguageServerDownload> {
+        self.platforms.get(&get_platform())
+    }
+
+    /// Sources might come from anywhere, make sure their version and binaries can't point
+    /// outside of the folder where they are installed
+    pub fn validate(&self) -> Result<(), LanguageServerErrors> {
+        let is_valid = is_valid_extension_id(&self.version)
+            && self
+                .platforms
+                .values()
+                .all(|download| is_relative_path(&download.binary));
+
+        if is_valid {
+            Ok(())
+        } else {
+            Err(LanguageServerErrors::BadSource)
+        }
+    }
+}
+
+/// Check if a path only has normal components, e.g `bin/server` but not `/bin/server` or `../server`
+fn is_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.contains('\\')
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// A language server installed in the managed directory
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InstalledLanguageServer {
+    pub version: String,
+    /// Absolute path of its binary
+    pub binary: PathBuf,
+}
+
+/// Platform the Core is running on, as used in [`LanguageServerSource::platforms`]
+pub fn get_platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// Return the installed version of a language server, if any
+///
+/// # Arguments
+///
+/// * `installs_path`   - Directory where the language servers are installed
+/// * `config_id`       - ID of the language server configuration
+///
+pub async fn get_installed(
+    installs_path: &Path,
+    config_id: &str,
+) -> Option<InstalledLanguageServer> {
+    if !is_valid_extension_id(config_id) {
+        return None;
+    }
+
+    let installed = fs::read(installs_path.join(config_id).join(INSTALLED_FILE))
+        .await
+        .ok()?;
+    let installed = serde_json::from_slice::<InstalledLanguageServer>(&installed).ok()?;
+
+    // It might have been removed by hand
+    if fs::metadata(&installed.binary).await.is_ok() {
+        Some(installed)
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "installer")]
+pub use downloads::*;
+
+#[cfg(feature = "installer")]
+mod downloads {
+    use std::io::{Cursor, Read};
+    use std::path::{Path, PathBuf};
+
+    use sha2::{Digest, Sha256};
+    use tokio::fs;
+    use tracing::info;
+
+    use super::{
+        get_installed, ArchiveFormat, InstalledLanguageServer, LanguageServerSource, INSTALLED_FILE,
+    };
+    use crate::extensions::installation::is_valid_extension_id;
+    use crate::http::HttpClient;
+    use crate::language_servers::{LanguageServerConfig, LanguageServerErrors};
+
+    /// Make sure the download is the published one
+    pub fn verify_download(download: &[u8], checksum: &str) -> Result<(), LanguageServerErrors> {
+        let digest = hex::encode(Sha256::digest(download));
+        if digest.eq_ignore_ascii_case(checksum) {
+            Ok(())
+        } else {
+            Err(LanguageServerErrors::ChecksumMismatch)
+        }
+    }
+
+    /// Extract a download into the given directory
+    fn unpack(
+        download: Vec<u8>,
+        archive: ArchiveFormat,
+        destination: &Path,
+        binary: &str,
+    ) -> Result<(), LanguageServerErrors> {
+        std::fs::create_dir_all(destination).map_err(|_| LanguageServerErrors::BadArchive)?;
+
+        match archive {
+            ArchiveFormat::Zip => {
+                zip::ZipArchive::new(Cursor::new(download))
+                    .and_then(|mut archive| archive.extract(destination))
+                    .map_err(|_| LanguageServerErrors::BadArchive)?;
+            }
+            ArchiveFormat::TarGz => {
+                let decoder = flate2::read::GzDecoder::new(Cursor::new(download));
+                tar::Archive::new(decoder)
+                    .unpack(destination)
+                    .map_err(|_| LanguageServerErrors::BadArchive)?;
+            }
+            ArchiveFormat::Gz => {
+                let mut content = Vec::new();
+                flate2::read::GzDecoder::new(Cursor::new(download))
+                    .read_to_end(&mut content)
+                    .map_err(|_| LanguageServerErrors::BadArchive)?;
+                std::fs::write(destination.join(binary), content)
+                    .map_err(|_| LanguageServerErrors::BadArchive)?;
+            }
+            ArchiveFormat::Binary => {
+                std::fs::write(destination.join(binary), download)
+                    .map_err(|_| LanguageServerErrors::BadArchive)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn make_executable(binary: &Path) -> Result<(), LanguageServerErrors> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut permissions = std::fs::metadata(binary)
+            .map_err(|_| LanguageServerErrors::BinaryNotFound)?
+            .permissions();
+        permissions.set_mode(permissions.mode() | 0o755);
+        std::fs::set_permissions(binary, permissions).map_err(|_| LanguageServerErrors::BadArchive)
+    }
+
+    #[cfg(not(unix))]
+    fn make_executable(binary: &Path) -> Result<(), LanguageServerErrors> {
+        if binary.exists() {
+            Ok(())
+        } else {
+            Err(LanguageServerErrors::BinaryNotFound)
+        }
+    }
+
+    /// Downloads and updates the language servers that declare a [`LanguageServerSource`]
+    ///
+    /// Each one is installed in `<installs_path>/<config id>/<version>`
+    #[derive(Clone)]
+    pub struct LanguageServerInstaller {
+        installs_path: PathBuf,
+        http: HttpClient,
+    }
+
+    impl LanguageServerInstaller {
+        /// Create an installer
+        ///
+        /// # Arguments
+        ///
+        /// * `installs_path`   - Directory where the language servers are installed
+        ///
+        pub fn new(installs_path: PathBuf) -> Self {
+            Self {
+                installs_path,
+                http: HttpClient::default(),
+            }
+        }
+
+        /// Make the requests with the given client, e.g the one of the State
+        pub fn with_http_client(mut self, http: HttpClient) -> Self {
+            self.http = http;
+            self
+        }
+
+        async fn download(&self, url: &str) -> Result<Vec<u8>, LanguageServerErrors> {
+            self.http
+                .get(url)
+                .map_err(|_| LanguageServerErrors::DownloadFailed)?
+                .send()
+                .await
+                .and_then(|res| res.error_for_status())
+                .map_err(|_| LanguageServerErrors::DownloadFailed)?
+                .bytes()
+                .await
+                .map(|bytes| bytes.to_vec())
+                .map_err(|_| LanguageServerErrors::DownloadFailed)
+        }
+
+        /// Download, verify and unpack a version of a language server, the previous versions are removed
+        pub async fn install(
+            &self,
+            config_id: &str,
+            source: &LanguageServerSource,
+        ) -> Result<InstalledLanguageServer, LanguageServerErrors> {
+            if !is_valid_extension_id(config_id) {
+                return Err(LanguageServerErrors::BadSource);
+            }
+            source.validate()?;
+
+            let download = source
+                .get_download()
+                .ok_or(LanguageServerErrors::NotInstallable)?;
+
+            let package = self.download(&download.url).await?;
+            verify_download(&package, &download.checksum)?;
+
+            let folder = self.installs_path.join(config_id);
+            let destination = folder.join(&source.version);
+            fs::remove_dir_all(&destination).await.ok();
+
+            let binary = destination.join(&download.binary);
+            {
+                let destination = destination.clone();
+                let archive = download.archive;
+                let binary_name = download.binary.clone();
+                let binary = binary.clone();
+                tokio::task::spawn_blocking(move || {
+                    unpack(package, archive, &destination, &binary_name)?;
+                    make_executable(&binary)
+                })
+                .await
+                .map_err(|_| LanguageServerErrors::BadArchive)??;
+            }
+
+            let installed = InstalledLanguageServer {
+                version: source.version.clone(),
+                binary,
+            };
+            let marker = serde_json::to_vec(&installed).unwrap_or_default();
+            fs::write(folder.join(INSTALLED_FILE), marker)
+                .await
+                .map_err(|_| LanguageServerErrors::BadArchive)?;
+
+            // Only the new version is kept
+            if let Ok(mut entries) = fs::read_dir(&folder).await {
+                while let Ok(Some(entry)) = entries.next_entry().await {
+                    let is_dir = entry.file_type().await.map(|kind| kind.is_dir());
+                    if is_dir.unwrap_or(false) && entry.file_name() != source.version.as_str() {
+                        fs::remove_dir_all(entry.path()).await.ok();
+                    }
+                }
+            }
+
+            info!(
+                "Installed language server <{}> v{}",
+                config_id, source.version
+            );
+
+            Ok(installed)
+        }
+
+        /// Latest source published for a language server, or its own source if it has no update URL
+        pub async fn get_latest_source(
+            &self,
+            config: &LanguageServerConfig,
+        ) -> Result<LanguageServerSource, LanguageServerErrors> {
+            let source = config
+                .source
+                .as_ref()
+                .ok_or(LanguageServerErrors::NotInstallable)?;
+
+            match &source.update_url {
+                Some(url) => self
+                    .http
+                    .get(url)
+                    .map_err(|_| LanguageServerErrors::DownloadFailed)?
+                    .send()
+                    .await
+                    .and_then(|res| res.error_for_status())
+                    .map_err(|_| LanguageServerErrors::DownloadFailed)?
+                    .json()
+                    .await
+                    .map_err(|_| LanguageServerErrors::DownloadFailed),
+                None => Ok(source.clone()),
+            }
+        }
+
+        /// Install the latest version of a language server if it's not the installed one,
+        /// returns None if it was already up to date.
+        /// The checksum of the update comes from the update URL too, so the update is as trusted as that URL
+        pub async fn update(
+            &self,
+            config: &LanguageServerConfig,
+        ) -> Result<Option<InstalledLanguageServer>, LanguageServerErrors> {
+            let latest = self.get_latest_source(config).await?;
+            let installed = get_installed(&self.installs_path, &config.id).await;
+
+            if installed.map(|installed| installed.version) == Some(latest.version.clone()) {
+                Ok(None)
+            } else {
+                self.install(&config.id, &latest).await.map(Some)
+            }
+        }
+
+        /// Remove the files of an installed language server
+        pub async fn uninstall(&self, config_id: &str) -> Result<(), LanguageServerErrors> {
+            if !is_valid_extension_id(config_id) {
+                return Err(LanguageServerErrors::NotInstallable);
+            }
+
+            fs::remove_dir_all(self.installs_path.join(config_id))
+                .await
+                .map_err(|_| LanguageServerErrors::NotInstallable)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{get_platform, ArchiveFormat, LanguageServerDownload, LanguageServerSource};
+    use crate::language_servers::LanguageServerErrors;
+
+    #[test]
+    fn download_for_current_platform() {
+        let download = LanguageServerDownload {
+            url: "https://example.com/server.gz".to_owned(),
+            checksum: String::new(),
+            archive: ArchiveFormat::Gz,
+            binary: "server".to_owned(),
+        };
+        let mut source = LanguageServerSource {
+            version: "1.0.0".to_owned(),
+            platforms: HashMap::new(),
+            update_url: None,
+        };
+        assert!(source.get_download().is_none());
+
+        source.platforms.insert(get_platform(), download.clone());
+        assert_eq!(source.get_download(), Some(&download));
+    }
+
+    #[test]
+    fn reject_paths_outside_the_installation() {
+        let download = |binary: &str| LanguageServerDownload {
+            url: "https://example.com/server.tar.gz".to_owned(),
+            checksum: String::new(),
+            archive: ArchiveFormat::TarGz,
+            binary: binary.to_owned(),
+        };
+        let source = |version: &str, binary: &str| LanguageServerSource {
+            version: version.to_owned(),
+            platforms: HashMap::from([(get_platform(), download(binary))]),
+            update_url: None,
+        };
+
+        assert!(source("1.0.0", "bin/server").validate().is_ok());
+        for (version, binary) in [
+            ("../..", "server"),
+            ("1.0.0/..", "server"),
+            ("", "server"),
+            ("1.0.0", "/usr/bin/server"),
+            ("1.0.0", "bin/../../server"),
+            ("1.0.0", "..\\server"),
+        ] {
+            assert_eq!(
+                source(version, binary).validate(),
+                Err(LanguageServerErrors::BadSource)
+            );
+        }
+    }
+
+    #[cfg(feature = "installer")]
+    #[test]
+    fn verify_download_checksum() {
+        use super::verify_download;
+
+        let checksum = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert!(verify_download(b"hello", checksum).is_ok());
+        assert_eq!(
+            verify_download(b"bye", checksum),
+            Err(LanguageServerErrors::ChecksumMismatch)
+        );
+    }
+}
@@ -0,0 +1,822 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::{oneshot, Mutex};
+use tokio::time::timeout;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::{get_installed, LanguageServerSource, LanguageServersPool};
+use crate::documents::{DocumentEdit, TextRange};
+use crate::messaging::{ClientMessages, ServerMessages};
+use crate::metrics::METRICS;
+
+/// How long a language server has to exit by itself before it's killed
+const EXIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a language server has to reply to the `initialize` request
+const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a language server has to reply to a request made by the Core
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Biggest message accepted from a language server, so a bad header can't exhaust the memory
+const MAX_MESSAGE_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Requests made by the Core use IDs with this prefix, so their replies are not forwarded to the client
+const CORE_REQUEST_PREFIX: &str = "core-";
+
+/// Language servers errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum LanguageServerErrors {
+    ConfigNotFound,
+    NotRunning,
+    CouldNotStart,
+    InitializeFailed,
+    WriteFailed,
+    RequestFailed,
+    /// It has no source to download it from, for this platform
+    NotInstallable,
+    DownloadFailed,
+    ChecksumMismatch,
+    BadArchive,
+    /// The binary is not where the source says
+    BinaryNotFound,
+    /// Its version or binary is not a relative path inside the installation folder
+    BadSource,
+}
+
+/// How to launch a language server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LanguageServerConfig {
+    pub id: String,
+    pub name: String,
+    /// Language handled by the server, e.g `typescript`
+    pub language: String,
+    pub command: String,
+    pub args: Vec<String>,
+    /// JSON encoded `initializationOptions`
+    pub initialization_options: Option<String>,
+    /// Where to download it from, when installed by the Core `command` is replaced by the installed binary
+    #[serde(default)]
+    pub source: Option<LanguageServerSource>,
+}
+
+/// Lifecycle of a managed language server
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanguageServerStatus {
+    Starting,
+    Running,
+    Stopped,
+    Crashed,
+}
+
+/// Write a message using the LSP base protocol framing
+pub async fn write_lsp_message(
+    writer: &mut (impl AsyncWrite + Unpin),
+    content: &str,
+) -> std::io::Result<()> {
+    let message = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
+    writer.write_all(message.as_bytes()).await?;
+    writer.flush().await
+}
+
+/// Read a message using the LSP base protocol framing, `None` once the stream is closed
+pub async fn read_lsp_message(reader: &mut (impl AsyncBufRead + Unpin)) -> Option<String> {
+    let mut content_length = None;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.ok()? == 0 {
+            return None;
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let content_length = content_length.filter(|length| *length <= MAX_MESSAGE_LENGTH)?;
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await.ok()?;
+    String::from_utf8(body).ok()
+}
+
+/// The State a managed language server works for
+#[derive(Clone)]
+struct ServerOwner {
+    state_id: u8,
+    sender: Sender<ClientMessages>,
+}
+
+type SharedOwner = Arc<RwLock<Option<ServerOwner>>>;
+
+fn get_owner(owner: &SharedOwner) -> Option<ServerOwner> {
+    owner.read().unwrap().clone()
+}
+
+async fn send_status(owner: &SharedOwner, id: &str, status: LanguageServerStatus) {
+    if let Some(ServerOwner { state_id, sender }) = get_owner(owner) {
+        sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::LanguageServerStatusChanged {
+                    state_id,
+                    id: id.to_owned(),
+                    status,
+                },
+            ))
+            .await
+            .ok();
+    }
+}
+
+/// A language server process launched by the Core for a specific workspace
+#[derive(Clone)]
+pub struct ManagedLanguageServer {
+    pub id: String,
+    pub config: LanguageServerConfig,
+    pub root_uri: String,
+    /// None while it's waiting in a pool
+    owner: SharedOwner,
+    stdin: Arc<Mutex<ChildStdin>>,
+    process: Arc<Mutex<Child>>,
+    stopping: Arc<AtomicBool>,
+    initialize_result: Value,
+    requests: Arc<Mutex<HashMap<String, oneshot::Sender<Value>>>>,
+}
+
+impl ManagedLanguageServer {
+    /// Spawn the language server and do the `initialize` handshake
+    ///
+    /// # Arguments
+    ///
+    /// * `id`       - ID for the running server
+    /// * `config`   - How to launch the server
+    /// * `root_uri` - The workspace root
+    /// * `state_id` - The State the server belongs to
+    /// * `sender`   - Where to send the server messages and status changes
+    ///
+    pub async fn start(
+        id: &str,
+        config: LanguageServerConfig,
+        root_uri: &str,
+        state_id: u8,
+        sender: Sender<ClientMessages>,
+    ) -> Result<Self, LanguageServerErrors> {
+        Self::launch(id, config, root_uri, Some(ServerOwner { state_id, sender })).await
+    }
+
+    /// Spawn the language server before any State needs it, what it says is dropped
+    /// until it's handed to a State with [`ManagedLanguageServer::adopt`]
+    pub async fn start_unowned(
+        id: &str,
+        config: LanguageServerConfig,
+        root_uri: &str,
+    ) -> Result<Self, LanguageServerErrors> {
+        Self::launch(id, config, root_uri, None).await
+    }
+
+    /// Make the server work for a State
+    pub async fn adopt(&self, state_id: u8, sender: Sender<ClientMessages>) {
+        *self.owner.write().unwrap() = Some(ServerOwner { state_id, sender });
+        send_status(&self.owner, &self.id, LanguageServerStatus::Running).await;
+        info!(
+            "Language Server <{}> adopted by State <{}>",
+            self.id, state_id
+        );
+    }
+
+    async fn launch(
+        id: &str,
+        config: LanguageServerConfig,
+        root_uri: &str,
+        owner: Option<ServerOwner>,
+    ) -> Result<Self, LanguageServerErrors> {
+        let owner = Arc::new(RwLock::new(owner));
+        send_status(&owner, id, LanguageServerStatus::Starting).await;
+
+        let mut process = Command::new(&config.command)
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|_| LanguageServerErrors::CouldNotStart)?;
+
+        let mut stdin = process
+            .stdin
+            .take()
+            .ok_or(LanguageServerErrors::CouldNotStart)?;
+        let mut stdout = BufReader::new(
+            process
+                .stdout
+                .take()
+                .ok_or(LanguageServerErrors::CouldNotStart)?,
+        );
+
+        let initialization_options = config
+            .initialization_options
+            .as_ref()
+            .and_then(|options| serde_json::from_str::<Value>(options).ok());
+
+        let initialize = json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "initialize",
+            "params": {
+                "processId": std::process::id(),
+                "rootUri": root_uri,
+                "capabilities": {},
+                "initializationOptions": initialization_options,
+            }
+        });
+
+        let handshake = async {
+            write_lsp_message(&mut stdin, &initialize.to_string())
+                .await
+                .map_err(|_| LanguageServerErrors::InitializeFailed)?;
+
+            // Ignore anything sent by the server before replying to the handshake,
+            // its own requests might use the same ID
+            loop {
+                let message = read_lsp_message(&mut stdout)
+                    .await
+                    .ok_or(LanguageServerErrors::InitializeFailed)?;
+                let message = serde_json::from_str::<Value>(&message)
+                    .map_err(|_| LanguageServerErrors::InitializeFailed)?;
+
+                if message.get("id").and_then(Value::as_u64) == Some(0)
+                    && message.get("method").is_none()
+                {
+                    break message
+                        .get("result")
+                        .cloned()
+                        .ok_or(LanguageServerErrors::InitializeFailed);
+                }
+            }
+        };
+
+        // The process is killed once dropped if it never replies
+        let initialize_result = timeout(INITIALIZE_TIMEOUT, handshake)
+            .await
+            .map_err(|_| LanguageServerErrors::InitializeFailed)??;
+
+        let initialized = json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} });
+        write_lsp_message(&mut stdin, &initialized.to_string())
+            .await
+            .map_err(|_| LanguageServerErrors::InitializeFailed)?;
+
+        let stopping = Arc::new(AtomicBool::new(false));
+        let requests = Arc::new(Mutex::new(HashMap::<String, oneshot::Sender<Value>>::new()));
+
+        // Forward everything the server says to the client
+        {
+            let id = id.to_owned();
+            let language = config.language.clone();
+            let owner = owner.clone();
+            let stopping = stopping.clone();
+            let requests = requests.clone();
+            tokio::spawn(async move {
+                while let Some(content) = read_lsp_message(&mut stdout).await {
+                    if content.contains(CORE_REQUEST_PREFIX) {
+                        if let Ok(message) = serde_json::from_str::<Value>(&content) {
+                            let reply_id = message
+                                .get("id")
+                                .and_then(Value::as_str)
+                                .filter(|_| message.get("method").is_none());
+                            if let Some(reply_id) = reply_id {
+                                let reply = requests.lock().await.remove(reply_id);
+                                if let Some(reply) = reply {
+                                    reply.send(message).ok();
+                                    continue;
+                                }
+                            }
+                        }
+                    }
+
+                    if let Some(ServerOwner { state_id, sender }) = get_owner(&owner) {
+                        sender
+                            .send(ClientMessages::ServerMessage(
+                                ServerMessages::NotifyLanguageServersClient {
+                                    state_id,
+                                    id: id.clone(),
+                                    language: language.clone(),
+                                    content,
+                                },
+                            ))
+                            .await
+                            .ok();
+                    }
+                }
+
+                // Fail the requests nobody is going to reply anymore
+                requests.lock().await.clear();
+
+                let status = if stopping.load(Ordering::SeqCst) {
+                    LanguageServerStatus::Stopped
+                } else {
+                    error!("Language Server <{}> crashed", id);
+                    LanguageServerStatus::Crashed
+                };
+                send_status(&owner, &id, status).await;
+            });
+        }
+
+        send_status(&owner, id, LanguageServerStatus::Running).await;
+
+        info!("Started Language Server <{}>", id);
+
+        Ok(Self {
+            id: id.to_owned(),
+            config,
+            root_uri: root_uri.to_owned(),
+            owner,
+            stdin: Arc::new(Mutex::new(stdin)),
+            process: Arc::new(Mutex::new(process)),
+            stopping,
+            initialize_result,
+            requests,
+        })
+    }
+
+    /// Send a JSON-RPC message from the client to the server
+    pub async fn write(&self, content: &str) -> Result<(), LanguageServerErrors> {
+        let message = serde_json::from_str::<Value>(content).ok();
+        let method = message
+            .as_ref()
+            .and_then(|message| message.get("method"))
+            .and_then(Value::as_str);
+
+        match method {
+            // The handshake was already done by the Core, so reply with the cached result
+            Some("initialize") => {
+                let reply = json!({
+                    "jsonrpc": "2.0",
+                    "id": message.as_ref().and_then(|message| message.get("id")).cloned(),
+                    "result": self.initialize_result,
+                });
+                if let Some(ServerOwner { state_id, sender }) = get_owner(&self.owner) {
+                    sender
+                        .send(ClientMessages::ServerMessage(
+                            ServerMessages::NotifyLanguageServersClient {
+                                state_id,
+                                id: self.id.clone(),
+                                language: self.config.language.clone(),
+                                content: reply.to_string(),
+                            },
+                        ))
+                        .await
+                        .ok();
+                }
+                Ok(())
+            }
+            Some("initialized") => Ok(()),
+            _ => {
+                let mut stdin = self.stdin.lock().await;
+                write_lsp_message(&mut *stdin, content)
+                    .await
+                    .map_err(|_| LanguageServerErrors::WriteFailed)
+            }
+        }
+    }
+
+    /// Send a notification on behalf of the Core
+    pub async fn notify(&self, method: &str, params: Value) -> Result<(), LanguageServerErrors> {
+        let notification = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        let mut stdin = self.stdin.lock().await;
+        write_lsp_message(&mut *stdin, &notification.to_string())
+            .await
+            .map_err(|_| LanguageServerErrors::WriteFailed)
+    }
+
+    /// Send a request on behalf of the Core and wait for it's result, failing
+    /// if the server doesn't reply in time or exits before replying
+    pub async fn request(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, LanguageServerErrors> {
+        let id = format!("{}{}", CORE_REQUEST_PREFIX, Uuid::new_v4());
+        let (reply_sender, reply) = oneshot::channel();
+        self.requests.lock().await.insert(id.clone(), reply_sender);
+
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let written = {
+            let mut stdin = self.stdin.lock().await;
+            write_lsp_message(&mut *stdin, &request.to_string()).await
+        };
+        if written.is_err() {
+            self.requests.lock().await.remove(&id);
+            return Err(LanguageServerErrors::WriteFailed);
+        }
+
+        let reply = match timeout(REQUEST_TIMEOUT, reply).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(_)) => return Err(LanguageServerErrors::RequestFailed),
+            Err(_) => {
+                self.requests.lock().await.remove(&id);
+                return Err(LanguageServerErrors::RequestFailed);
+            }
+        };
+        match reply.get("error") {
+            Some(_) => Err(LanguageServerErrors::RequestFailed),
+            None => Ok(reply.get("result").cloned().unwrap_or(Value::Null)),
+        }
+    }
+
+    /// Check if the server asked to be told about renamed files
+    pub fn supports_did_rename_files(&self) -> bool {
+        self.initialize_result
+            .pointer("/capabilities/workspace/fileOperations/didRename")
+            .is_some()
+    }
+
+    /// Ask the server to exit, and kill it if it doesn't
+    pub async fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+
+        {
+            let mut stdin = self.stdin.lock().await;
+            let shutdown = json!({ "jsonrpc": "2.0", "id": "shutdown", "method": "shutdown" });
+            let exit = json!({ "jsonrpc": "2.0", "method": "exit" });
+            write_lsp_message(&mut *stdin, &shutdown.to_string())
+                .await
+                .ok();
+            write_lsp_message(&mut *stdin, &exit.to_string()).await.ok();
+        }
+
+        let mut process = self.process.lock().await;
+        if timeout(EXIT_TIMEOUT, process.wait()).await.is_err() {
+            process.kill().await.ok();
+        }
+
+        info!("Stopped Language Server <{}>", self.id);
+    }
+}
+
+/// Use the installed binary of a language server, if it was installed by the Core
+pub async fn resolve_command(
+    installs_path: Option<&PathBuf>,
+    mut config: LanguageServerConfig,
+) -> LanguageServerConfig {
+    if let (Some(installs_path), Some(_)) = (installs_path, &config.source) {
+        if let Some(installed) = get_installed(installs_path, &config.id).await {
+            config.command = installed.binary.to_string_lossy().into_owned();
+        }
+    }
+    config
+}
+
+/// Registry of language servers configurations and the servers running from them
+///
+/// The clones share the running servers, so servers can be launched with a clone
+/// without locking the State during the handshake
+#[derive(Clone, Default)]
+pub struct LanguageServersManager {
+    configs: HashMap<String, LanguageServerConfig>,
+    running: Arc<RwLock<HashMap<String, ManagedLanguageServer>>>,
+    /// Directory of the language servers installed by the Core
+    installs_path: Option<PathBuf>,
+    /// Servers launched before they were needed, shared by the States
+    pool: Option<LanguageServersPool>,
+}
+
+impl LanguageServersManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look for installed language servers in the given directory
+    pub fn with_installs_path(mut self, installs_path: PathBuf) -> Self {
+        self.installs_path = Some(installs_path);
+        self
+    }
+
+    pub fn get_installs_path(&self) -> Option<&PathBuf> {
+        self.installs_path.as_ref()
+    }
+
+    /// Take the servers from a pool when they are already warm
+    pub fn with_pool(mut self, pool: LanguageServersPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    pub fn get_pool(&self) -> Option<&LanguageServersPool> {
+        self.pool.as_ref()
+    }
+
+    /// Retrieve a registered configuration
+    pub fn get_config(&self, config_id: &str) -> Option<&LanguageServerConfig> {
+        self.configs.get(config_id)
+    }
+
+    /// Register how to launch a language server
+    pub fn register_config(&mut self, config: LanguageServerConfig) {
+        self.configs.insert(config.id.clone(), config);
+    }
+
+    /// Return all the registered configurations
+    pub fn get_configs(&self) -> Vec<LanguageServerConfig> {
+        self.configs.values().cloned().collect()
+    }
+
+    /// Retrieve a running server
+    pub fn get_running(&self, id: &str) -> Option<ManagedLanguageServer> {
+        self.running.read().unwrap().get(id).cloned()
+    }
+
+    /// Return all the running servers
+    pub fn get_all_running(&self) -> Vec<ManagedLanguageServer> {
+        self.running.read().unwrap().values().cloned().collect()
+    }
+
+    /// Launch a server for the given workspace, returns the ID of the running server.
+    /// If there is already a server for that workspace it will be reused.
+    pub async fn start(
+        &self,
+        config_id: &str,
+        root_uri: &str,
+        state_id: u8,
+        sender: Sender<ClientMessages>,
+    ) -> Result<String, LanguageServerErrors> {
+        let config = self
+            .configs
+            .get(config_id)
+            .cloned()
+            .ok_or(LanguageServerErrors::ConfigNotFound)?;
+
+        let id = format!("{}:{}", config_id, root_uri);
+
+        if self.get_running(&id).is_none() {
+            let config = resolve_command(self.installs_path.as_ref(), config).await;
+            let warm = match &self.pool {
+                Some(pool) => pool.take(&config, root_uri).await,
+                None => None,
+            };
+            let server = match warm {
+                Some(server) => {
+                    server.adopt(state_id, sender).await;
+                    server
+                }
+                None => {
+                    ManagedLanguageServer::start(&id, config, root_uri, state_id, sender).await?
+                }
+            };
+
+            // Another one might have been launched meanwhile
+            let previous = self.running.write().unwrap().insert(id.clone(), server);
+            if let Some(previous) = previous {
+                previous.stop().await;
+            }
+        }
+
+        Ok(id)
+    }
+
+    /// Stop a running server
+    pub async fn stop(&self, id: &str) -> Result<(), LanguageServerErrors> {
+        let server = self
+            .running
+            .write()
+            .unwrap()
+            .remove(id)
+            .ok_or(LanguageServerErrors::NotRunning)?;
+        server.stop().await;
+        Ok(())
+    }
+
+    /// Stop and launch again a running server
+    pub async fn restart(
+        &self,
+        id: &str,
+        state_id: u8,
+        sender: Sender<ClientMessages>,
+    ) -> Result<String, LanguageServerErrors> {
+        let server = self
+            .get_running(id)
+            .ok_or(LanguageServerErrors::NotRunning)?;
+
+        self.stop(id).await?;
+        METRICS.record_language_server_restart();
+        self.start(&server.config.id, &server.root_uri, state_id, sender)
+            .await
+    }
+
+    /// Send `workspace/didRenameFiles` to the running servers interested in it
+    ///
+    /// # Arguments
+    ///
+    /// * `renames`   - Pairs of old and new URIs
+    ///
+    pub async fn did_rename_files(&self, renames: &[(String, String)]) {
+        let files = renames
+            .iter()
+            .map(|(old_uri, new_uri)| json!({ "oldUri": old_uri, "newUri": new_uri }))
+            .collect::<Vec<Value>>();
+
+        for server in self.get_all_running() {
+            if server.supports_did_rename_files() {
+                server
+                    .notify("workspace/didRenameFiles", json!({ "files": files }))
+                    .await
+                    .ok();
+            }
+        }
+    }
+
+    /// Running servers that handle the given language
+    fn get_running_for_language(&self, language: &str) -> Vec<ManagedLanguageServer> {
+        self.running
+            .read()
+            .unwrap()
+            .values()
+            .filter(|server| server.config.language == language)
+            .cloned()
+            .collect()
+    }
+
+    /// Running server of the given language whose workspace contains the document,
+    /// the innermost one if the workspaces are nested
+    fn get_running_for_document(&self, language: &str, uri: &str) -> Option<ManagedLanguageServer> {
+        self.get_running_for_language(language)
+            .into_iter()
+            .filter(|server| is_inside_root(uri, &server.root_uri))
+            .max_by_key(|server| server.root_uri.len())
+    }
+
+    /// Send a request about a document to the running server of its language and workspace
+    pub async fn request(
+        &self,
+        language: &str,
+        uri: &str,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, LanguageServerErrors> {
+        let server = self
+            .get_running_for_document(language, uri)
+            .ok_or(LanguageServerErrors::NotRunning)?;
+        server.request(method, params).await
+    }
+
+    /// Send `textDocument/didOpen` to the running servers of the document's language
+    pub async fn did_open_document(&self, uri: &str, language: &str, version: i32, text: &str) {
+        for server in self.get_running_for_language(language) {
+            server
+                .notify(
+                    "textDocument/didOpen",
+                    json!({
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": language,
+                            "version": version,
+                            "text": text,
+                        }
+                    }),
+                )
+                .await
+                .ok();
+        }
+    }
+
+    /// Send an incremental `textDocument/didChange` to the running servers of the document's language
+    ///
+    /// # Arguments
+    ///
+    /// * `uri`        - URI of the document
+    /// * `language`   - Language of the document
+    /// * `version`    - Version after the changes
+    /// * `changes`    - The edits, with their positions in UTF-16 code units
+    ///
+    pub async fn did_change_document(
+        &self,
+        uri: &str,
+        language: &str,
+        version: i32,
+        changes: &[DocumentEdit],
+    ) {
+        let content_changes = changes
+            .iter()
+            .map(|change| {
+                let TextRange { start, end } = change.range;
+                json!({
+                    "range": {
+                        "start": { "line": start.line, "character": start.character },
+                        "end": { "line": end.line, "character": end.character },
+                    },
+                    "text": change.text,
+                })
+            })
+            .collect::<Vec<Value>>();
+
+        for server in self.get_running_for_language(language) {
+            server
+                .notify(
+                    "textDocument/didChange",
+                    json!({
+                        "textDocument": { "uri": uri, "version": version },
+                        "contentChanges": content_changes,
+                    }),
+                )
+                .await
+                .ok();
+        }
+    }
+
+    /// Send `textDocument/didSave` to the running servers of the document's language
+    pub async fn did_save_document(&self, uri: &str, language: &str) {
+        for server in self.get_running_for_language(language) {
+            server
+                .notify(
+                    "textDocument/didSave",
+                    json!({ "textDocument": { "uri": uri } }),
+                )
+                .await
+                .ok();
+        }
+    }
+
+    /// Send `textDocument/didClose` to the running servers of the document's language
+    pub async fn did_close_document(&self, uri: &str, language: &str) {
+        for server in self.get_running_for_language(language) {
+            server
+                .notify(
+                    "textDocument/didClose",
+                    json!({ "textDocument": { "uri": uri } }),
+                )
+                .await
+                .ok();
+        }
+    }
+
+    /// Stop all the running servers
+    pub async fn stop_all(&self) {
+        let running = std::mem::take(&mut *self.running.write().unwrap());
+        for server in running.into_values() {
+            server.stop().await;
+        }
+    }
+}
+
+/// Check if a document URI is inside a workspace root URI
+fn is_inside_root(uri: &str, root_uri: &str) -> bool {
+    let root_uri = root_uri.trim_end_matches('/');
+    uri.strip_prefix(root_uri)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::BufReader;
+
+    use super::{is_inside_root, read_lsp_message, write_lsp_message, MAX_MESSAGE_LENGTH};
+
+    #[tokio::test]
+    async fn lsp_framing() {
+        let mut output = Vec::new();
+        write_lsp_message(&mut output, "{}").await.unwrap();
+        write_lsp_message(&mut output, "[]").await.unwrap();
+
+        assert_eq!(
+            output,
+            b"Content-Length: 2\r\n\r\n{}Content-Length: 2\r\n\r\n[]"
+        );
+
+        let mut reader = BufReader::new(&output[..]);
+        assert_eq!(read_lsp_message(&mut reader).await, Some("{}".to_string()));
+        assert_eq!(read_lsp_message(&mut reader).await, Some("[]".to_string()));
+        assert_eq!(read_lsp_message(&mut reader).await, None);
+    }
+
+    #[tokio::test]
+    async fn reject_huge_messages() {
+        let header = format!("Content-Length: {}\r\n\r\n{{}}", MAX_MESSAGE_LENGTH + 1);
+        let mut reader = BufReader::new(header.as_bytes());
+        assert_eq!(read_lsp_message(&mut reader).await, None);
+    }
+
+    #[test]
+    fn documents_inside_roots() {
+        assert!(is_inside_root("file:///a/b/c.rs", "file:///a/b"));
+        assert!(is_inside_root("file:///a/b/c.rs", "file:///a/b/"));
+        assert!(!is_inside_root("file:///a/bc/d.rs", "file:///a/b"));
+        assert!(!is_inside_root("file:///c/d.rs", "file:///a/b"));
+    }
+}
@@ -1,6 +1,20 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
+use crate::filesystems::GravitonUri;
+
+mod installer;
+mod manager;
+mod pool;
+pub use installer::*;
+pub use manager::*;
+pub use pool::*;
+
+/// Convert a local path into a `file://` URI
+pub fn path_to_uri(path: &str) -> String {
+    GravitonUri::local(path).to_file_uri()
+}
+
 #[async_trait]
 pub trait LanguageServer {
     /// Write data to the Language Server
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use super::{resolve_command, LanguageServerConfig, ManagedLanguageServer};
+
+type ServerSlot = Arc<Mutex<Option<ManagedLanguageServer>>>;
+
+/// Language servers launched when the app starts, before any document is opened, so the
+/// first State asking for one of them doesn't wait for it to start and index the project.
+///
+/// Every warm server is handed to a single State, the next one launches its own.
+#[derive(Clone, Default)]
+pub struct LanguageServersPool {
+    /// Warm servers by their running ID, the slot stays locked while the server is starting
+    servers: Arc<Mutex<HashMap<String, ServerSlot>>>,
+    /// Directory of the language servers installed by the Core
+    installs_path: Option<PathBuf>,
+}
+
+impl LanguageServersPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look for installed language servers in the given directory
+    pub fn with_installs_path(mut self, installs_path: PathBuf) -> Self {
+        self.installs_path = Some(installs_path);
+        self
+    }
+
+    /// Launch a server in the background for a workspace
+    ///
+    /// # Arguments
+    ///
+    /// * `config`     - How to launch the server, the States must register the same configuration
+    /// * `root_uri`   - The workspace root
+    ///
+    pub fn warm(&self, config: LanguageServerConfig, root_uri: &str) {
+        let servers = self.servers.clone();
+        let installs_path = self.installs_path.clone();
+        let root_uri = root_uri.to_owned();
+
+        tokio::spawn(async move {
+            let id = format!("{}:{}", config.id, root_uri);
+
+            // States asking for it meanwhile wait until it's started
+            let mut slot = {
+                let mut servers = servers.lock().await;
+                if servers.contains_key(&id) {
+                    return;
+                }
+                let slot = ServerSlot::default();
+                let locked_slot = slot.clone().lock_owned().await;
+                servers.insert(id.clone(), slot);
+                locked_slot
+            };
+
+            let config = resolve_command(installs_path.as_ref(), config).await;
+            match ManagedLanguageServer::start_unowned(&id, config, &root_uri).await {
+                Ok(server) => {
+                    info!("Language Server <{}> is warm", id);
+                    *slot = Some(server);
+                }
+                Err(err) => {
+                    warn!("Could not warm Language Server <{}>, error: {:?}", id, err);
+                    drop(slot);
+                    servers.lock().await.remove(&id);
+                }
+            }
+        });
+    }
+
+    /// Hand a warm server to a State, it's only used if it was launched with the same configuration.
+    /// The caller must [`ManagedLanguageServer::adopt`] it
+    pub async fn take(
+        &self,
+        config: &LanguageServerConfig,
+        root_uri: &str,
+    ) -> Option<ManagedLanguageServer> {
+        let id = format!("{}:{}", config.id, root_uri);
+        let slot = self.servers.lock().await.remove(&id)?;
+        let server = slot.lock().await.take()?;
+
+        if &server.config == config {
+            Some(server)
+        } else {
+            warn!(
+                "Configuration of Language Server <{}> changed since it was warmed",
+                id
+            );
+            server.stop().await;
+            None
+        }
+    }
+
+    /// IDs of the servers waiting for a State
+    pub async fn get_warm_ids(&self) -> Vec<String> {
+        self.servers.lock().await.keys().cloned().collect()
+    }
+
+    /// Stop the servers no State took
+    pub async fn stop_all(&self) {
+        let servers = std::mem::take(&mut *self.servers.lock().await);
+        for slot in servers.into_values() {
+            if let Some(server) = slot.lock().await.take() {
+                server.stop().await;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+
+    use super::LanguageServersPool;
+    use crate::language_servers::LanguageServerConfig;
+
+    /// A server that only answers the handshake
+    fn get_config(id: &str) -> LanguageServerConfig {
+        let script = r#"body='{"jsonrpc":"2.0","id":0,"result":{}}'; printf 'Content-Length: %d\r\n\r\n%s' ${#body} "$body"; cat > /dev/null"#;
+        LanguageServerConfig {
+            id: id.to_string(),
+            name: id.to_string(),
+            language: "rust".to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            initialization_options: None,
+            source: None,
+        }
+    }
+
+    async fn wait_until_warm(pool: &LanguageServersPool, id: &str) {
+        for _ in 0..100 {
+            if pool.get_warm_ids().await.iter().any(|warm| warm == id) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Language Server <{}> was never warmed", id);
+    }
+
+    #[tokio::test]
+    async fn hand_warm_servers_once() {
+        let pool = LanguageServersPool::new();
+        let config = get_config("sample");
+        pool.warm(config.clone(), "file:///project");
+        wait_until_warm(&pool, "sample:file:///project").await;
+
+        // Warming it again doesn't launch a second one
+        pool.warm(config.clone(), "file:///project");
+        assert_eq!(pool.get_warm_ids().await.len(), 1);
+
+        // Other workspaces don't get it
+        assert!(pool.take(&config, "file:///other").await.is_none());
+
+        let server = pool.take(&config, "file:///project").await;
+        assert!(server.is_some());
+        assert!(pool.take(&config, "file:///project").await.is_none());
+        assert!(pool.get_warm_ids().await.is_empty());
+
+        server.unwrap().stop().await;
+    }
+
+    #[tokio::test]
+    async fn stop_servers_with_another_configuration() {
+        let pool = LanguageServersPool::new();
+        pool.warm(get_config("sample"), "file:///project");
+        wait_until_warm(&pool, "sample:file:///project").await;
+
+        let mut changed = get_config("sample");
+        changed.args.push("--verbose".to_string());
+        assert!(pool.take(&changed, "file:///project").await.is_none());
+        assert!(pool.get_warm_ids().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn stop_all_the_warm_servers() {
+        let pool = LanguageServersPool::new();
+        pool.warm(get_config("first"), "file:///project");
+        pool.warm(get_config("second"), "file:///project");
+        wait_until_warm(&pool, "first:file:///project").await;
+        wait_until_warm(&pool, "second:file:///project").await;
+
+        pool.stop_all().await;
+        assert!(pool.get_warm_ids().await.is_empty());
+        assert!(pool
+            .take(&get_config("first"), "file:///project")
+            .await
+            .is_none());
+    }
+}
@@ -1,18 +1,65 @@
+pub mod auth;
+pub mod blobs;
+pub mod changes;
+pub mod clipboard;
+pub mod decorations;
+pub mod documents;
+pub mod experiments;
+pub mod explorer;
 pub mod extensions;
 pub mod filesystems;
+pub mod http;
+pub mod icons;
+pub mod indexer;
+pub mod kernels;
 pub mod language_servers;
+pub mod locale;
+pub mod logging;
+pub mod matcher;
 pub mod messaging;
+pub mod metrics;
+pub mod modal_editing;
+pub mod output_buffers;
+pub mod progress;
+pub mod recovery;
+pub mod refactoring;
+pub mod save_hooks;
+pub mod search;
+pub mod secrets;
+pub mod settings;
 pub mod state_persistors;
 pub mod states;
+pub mod tasks;
+pub mod telemetry;
 pub mod terminal_shells;
+pub mod tree_views;
+pub mod validation;
+pub mod vcs;
+pub mod workspaces;
+pub use auth::AuthErrors;
+pub use clipboard::ClipboardErrors;
+pub use documents::DocumentErrors;
+pub use experiments::ExperimentsErrors;
+pub use extensions::commands::CommandErrors;
 pub use extensions::manifest::{Manifest, ManifestErrors, ManifestExtension, ManifestInfo};
 pub use extensions::ExtensionErrors;
 pub use filesystems::FilesystemErrors;
-pub use language_servers::LanguageServer;
+pub use http::HttpErrors;
+pub use kernels::KernelErrors;
+pub use language_servers::{LanguageServer, LanguageServerErrors};
+pub use logging::LoggingErrors;
+pub use progress::ProgressErrors;
+pub use refactoring::RefactorErrors;
+pub use save_hooks::SaveHookErrors;
+pub use search::SearchErrors;
 pub use serde::{Deserialize, Serialize};
-pub use states::State;
+pub use settings::SettingsErrors;
+pub use states::{State, TransactionErrors};
+pub use tasks::TaskErrors;
 pub use tokio::sync::mpsc::Sender;
 pub use tokio::sync::Mutex;
+pub use vcs::VcsErrors;
+pub use workspaces::WorkspaceErrors;
 pub use {serde, tokio};
 
 /// Global errors enum
@@ -21,5 +68,29 @@ pub enum Errors {
     StateNotFound,
     Fs(FilesystemErrors),
     Ext(ExtensionErrors),
+    Kernel(KernelErrors),
+    LanguageServer(LanguageServerErrors),
+    Search(SearchErrors),
+    Vcs(VcsErrors),
+    Http(HttpErrors),
+    Document(DocumentErrors),
+    Refactor(RefactorErrors),
+    Logging(LoggingErrors),
+    Task(TaskErrors),
+    Settings(SettingsErrors),
+    Command(CommandErrors),
+    Workspace(WorkspaceErrors),
+    Progress(ProgressErrors),
+    Clipboard(ClipboardErrors),
+    Experiment(ExperimentsErrors),
+    Transaction(TransactionErrors),
+    Auth(AuthErrors),
     BadToken,
+    InvitationNotFound,
+    TokenNotFound,
+    AccessDenied,
+    TreeViewNotFound,
+    LayoutNotFound,
+    RecoveredSessionNotFound,
+    ClosedTabNotFound,
 }
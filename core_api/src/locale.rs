@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Order of the day, month and year in the dates
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateFormat {
+    /// `2022-03-25`
+    #[default]
+    Iso,
+    /// `25/03/2022`
+    DayMonthYear,
+    /// `03/25/2022`
+    MonthDayYear,
+}
+
+impl DateFormat {
+    fn parse(format: &str) -> Option<Self> {
+        match format {
+            "iso" => Some(Self::Iso),
+            "dmy" => Some(Self::DayMonthYear),
+            "mdy" => Some(Self::MonthDayYear),
+            _ => None,
+        }
+    }
+}
+
+/// Locale and formatting preferences of a client, the strings made by the Core for it follow them
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ClientLocale {
+    /// BCP 47 tag, e.g `en-US`
+    pub locale: String,
+    pub date_format: DateFormat,
+    /// Use a 12-hour clock with AM and PM
+    pub hour12: bool,
+    pub decimal_separator: char,
+    pub group_separator: Option<char>,
+    /// Offset of the client's timezone from UTC in minutes, e.g `120` for UTC+2
+    pub utc_offset: i32,
+}
+
+impl Default for ClientLocale {
+    fn default() -> Self {
+        Self {
+            locale: "en".to_owned(),
+            date_format: DateFormat::Iso,
+            hour12: false,
+            decimal_separator: '.',
+            group_separator: None,
+            utc_offset: 0,
+        }
+    }
+}
+
+/// Convert days since the Unix epoch into a (year, month, day) date
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+impl ClientLocale {
+    /// The usual formats of a locale, e.g `de-DE` uses `25/03/2022` and `1.234,5`
+    pub fn from_tag(tag: &str) -> Self {
+        let mut parts = tag.split(['-', '_']);
+        let language = parts.next().unwrap_or_default().to_lowercase();
+        let region = parts.next().map(|region| region.to_uppercase());
+
+        let (date_format, hour12, decimal_separator, group_separator) =
+            match (language.as_str(), region.as_deref()) {
+                ("en", None | Some("US")) => (DateFormat::MonthDayYear, true, '.', Some(',')),
+                ("en", _) => (DateFormat::DayMonthYear, false, '.', Some(',')),
+                ("de" | "es" | "it" | "pt" | "nl" | "tr" | "da" | "id", _) => {
+                    (DateFormat::DayMonthYear, false, ',', Some('.'))
+                }
+                ("fr" | "ru" | "pl" | "cs" | "sv" | "fi" | "nb" | "uk", _) => {
+                    (DateFormat::DayMonthYear, false, ',', Some(' '))
+                }
+                ("zh" | "ja" | "ko", _) => (DateFormat::Iso, false, '.', Some(',')),
+                _ => (DateFormat::Iso, false, '.', None),
+            };
+
+        Self {
+            locale: tag.to_owned(),
+            date_format,
+            hour12,
+            decimal_separator,
+            group_separator,
+            utc_offset: 0,
+        }
+    }
+
+    /// Read the preferences declared in the query of a connection, e.g `?locale=en-GB&utc_offset=60`.
+    /// The formats default to the locale's ones, none if nothing was declared
+    pub fn from_query_parameters(parameters: &HashMap<String, String>) -> Option<Self> {
+        const KEYS: [&str; 6] = [
+            "locale",
+            "date_format",
+            "hour12",
+            "decimal_separator",
+            "group_separator",
+            "utc_offset",
+        ];
+        if !KEYS.iter().any(|key| parameters.contains_key(*key)) {
+            return None;
+        }
+
+        let mut locale = parameters
+            .get("locale")
+            .map(|tag| Self::from_tag(tag))
+            .unwrap_or_default();
+
+        if let Some(date_format) = parameters
+            .get("date_format")
+            .and_then(|f| DateFormat::parse(f))
+        {
+            locale.date_format = date_format;
+        }
+        if let Some(hour12) = parameters.get("hour12").and_then(|h| h.parse().ok()) {
+            locale.hour12 = hour12;
+        }
+        if let Some(separator) = parameters
+            .get("decimal_separator")
+            .and_then(|s| s.chars().next())
+        {
+            locale.decimal_separator = separator;
+        }
+        if let Some(separator) = parameters.get("group_separator") {
+            locale.group_separator = separator.chars().next();
+        }
+        if let Some(utc_offset) = parameters.get("utc_offset").and_then(|o| o.parse().ok()) {
+            locale.utc_offset = utc_offset;
+        }
+
+        Some(locale)
+    }
+
+    /// Format a Unix timestamp (seconds) in the client's timezone, e.g `03/25/2022 2:05:09 PM`
+    pub fn format_timestamp(&self, timestamp: u64) -> String {
+        let local = timestamp as i64 + i64::from(self.utc_offset) * 60;
+        let (year, month, day) = civil_from_days(local.div_euclid(86400));
+        let seconds = local.rem_euclid(86400);
+        let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+        let date = match self.date_format {
+            DateFormat::Iso => format!("{:04}-{:02}-{:02}", year, month, day),
+            DateFormat::DayMonthYear => format!("{:02}/{:02}/{:04}", day, month, year),
+            DateFormat::MonthDayYear => format!("{:02}/{:02}/{:04}", month, day, year),
+        };
+
+        if self.hour12 {
+            let period = if hours < 12 { "AM" } else { "PM" };
+            let hours = match hours % 12 {
+                0 => 12,
+                hours => hours,
+            };
+            format!(
+                "{} {}:{:02}:{:02} {}",
+                date, hours, minutes, seconds, period
+            )
+        } else {
+            format!("{} {:02}:{:02}:{:02}", date, hours, minutes, seconds)
+        }
+    }
+
+    /// Format a Unix timestamp in milliseconds, the milliseconds are dropped
+    pub fn format_timestamp_millis(&self, timestamp: u64) -> String {
+        self.format_timestamp(timestamp / 1000)
+    }
+
+    /// Format a number with the client's separators, e.g `1,234.50`
+    pub fn format_number(&self, number: f64, decimals: usize) -> String {
+        let formatted = format!("{:.*}", decimals, number.abs());
+        let (integer, fraction) = match formatted.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (formatted.as_str(), None),
+        };
+
+        let mut result = String::new();
+        if number < 0.0 && formatted.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            result.push('-');
+        }
+        for (index, digit) in integer.chars().enumerate() {
+            if index > 0 && (integer.len() - index) % 3 == 0 {
+                if let Some(separator) = self.group_separator {
+                    result.push(separator);
+                }
+            }
+            result.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            result.push(self.decimal_separator);
+            result.push_str(fraction);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{ClientLocale, DateFormat};
+
+    #[test]
+    fn format_with_locales() {
+        // 2022-03-25 14:05:09 UTC
+        let timestamp = 1648217109;
+
+        let neutral = ClientLocale::default();
+        assert_eq!(neutral.format_timestamp(timestamp), "2022-03-25 14:05:09");
+        assert_eq!(neutral.format_number(1234.5, 2), "1234.50");
+
+        let us = ClientLocale::from_tag("en-US");
+        assert_eq!(us.format_timestamp(timestamp), "03/25/2022 2:05:09 PM");
+        assert_eq!(us.format_number(-1234567.0, 0), "-1,234,567");
+
+        let german = ClientLocale::from_tag("de-DE");
+        assert_eq!(german.date_format, DateFormat::DayMonthYear);
+        assert_eq!(german.format_number(1234.5, 1), "1.234,5");
+
+        // Declared in the handshake, the timezone moves the date too
+        let parameters = HashMap::from([
+            ("locale".to_owned(), "de-DE".to_owned()),
+            ("utc_offset".to_owned(), "600".to_owned()),
+            ("date_format".to_owned(), "iso".to_owned()),
+        ]);
+        let declared = ClientLocale::from_query_parameters(&parameters).unwrap();
+        assert_eq!(declared.format_timestamp(timestamp), "2022-03-26 00:05:09");
+        assert_eq!(declared.decimal_separator, ',');
+
+        assert!(ClientLocale::from_query_parameters(&HashMap::new()).is_none());
+    }
+}
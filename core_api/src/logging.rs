@@ -0,0 +1,415 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Debug;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
+
+use crate::locale::ClientLocale;
+
+/// The logger of the process, once it's initialized
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Logging errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum LoggingErrors {
+    AlreadyInitialized,
+    NotInitialized,
+    InvalidTarget,
+    CouldNotOpenFile,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+            Self::Trace => "trace",
+        }
+    }
+}
+
+impl From<&Level> for LogLevel {
+    fn from(level: &Level) -> Self {
+        match *level {
+            Level::ERROR => Self::Error,
+            Level::WARN => Self::Warn,
+            Level::INFO => Self::Info,
+            Level::DEBUG => Self::Debug,
+            _ => Self::Trace,
+        }
+    }
+}
+
+/// A logged event
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Unix timestamp (milliseconds)
+    pub timestamp: u64,
+    pub level: LogLevel,
+    /// Module that logged it
+    pub target: String,
+    pub message: String,
+    /// The timestamp formatted for the client that asked for the entry
+    #[serde(default)]
+    pub time: Option<String>,
+}
+
+impl LogEntry {
+    /// Format the timestamp with the preferences of a client
+    pub fn localized(mut self, locale: &ClientLocale) -> Self {
+        self.time = Some(locale.format_timestamp_millis(self.timestamp));
+        self
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{} {} {}: {}\n",
+            self.timestamp,
+            self.level.as_str().to_uppercase(),
+            self.target,
+            self.message
+        )
+    }
+}
+
+/// Log file renamed to `<name>.1`, `<name>.2`... once it's too big
+struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: &Path, max_size: u64, max_files: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_size,
+            max_files: max_files.max(1),
+            file,
+            size,
+        })
+    }
+
+    fn get_rotated_path(&self, index: usize) -> PathBuf {
+        PathBuf::from(format!("{}.{}", self.path.display(), index))
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for index in (1..self.max_files).rev() {
+            let from = self.get_rotated_path(index);
+            if from.exists() {
+                fs::rename(from, self.get_rotated_path(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.get_rotated_path(1))?;
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Where the events are kept
+struct LogSink {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+    file: Option<RotatingFile>,
+}
+
+impl LogSink {
+    fn push(&mut self, entry: LogEntry) {
+        if let Some(file) = &mut self.file {
+            // Nowhere to report it, the entry is still kept in memory
+            file.write_line(&entry.to_line()).ok();
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+/// Join the message and the other fields of an event
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields
+                .push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+}
+
+/// Captures all the events into the [`LogSink`]
+struct CaptureLayer {
+    sink: Arc<Mutex<LogSink>>,
+}
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let metadata = event.metadata();
+        let entry = LogEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_millis() as u64)
+                .unwrap_or_default(),
+            level: metadata.level().into(),
+            target: metadata.target().to_owned(),
+            message: visitor.message + &visitor.fields,
+            time: None,
+        };
+
+        self.sink.lock().unwrap().push(entry);
+    }
+}
+
+/// Build a filter from the default level and the level of every module
+fn build_filter(
+    default_level: LogLevel,
+    levels: &BTreeMap<String, LogLevel>,
+) -> Result<EnvFilter, LoggingErrors> {
+    let mut filter = EnvFilter::new(default_level.as_str());
+    for (target, level) in levels {
+        let directive = format!("{}={}", target, level.as_str())
+            .parse()
+            .map_err(|_| LoggingErrors::InvalidTarget)?;
+        filter = filter.add_directive(directive);
+    }
+    Ok(filter)
+}
+
+/// Configure the [`Logger`]
+pub struct LoggerBuilder {
+    default_level: LogLevel,
+    levels: BTreeMap<String, LogLevel>,
+    capacity: usize,
+    file: Option<(PathBuf, u64, usize)>,
+}
+
+impl LoggerBuilder {
+    /// Level of a module and it's submodules, e.g `gveditor_core_api::states`
+    pub fn level(mut self, target: &str, level: LogLevel) -> Self {
+        self.levels.insert(target.to_owned(), level);
+        self
+    }
+
+    /// Level of the modules without a specific level
+    pub fn default_level(mut self, level: LogLevel) -> Self {
+        self.default_level = level;
+        self
+    }
+
+    /// How many entries are kept in memory for the clients
+    pub fn buffer_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Also write the entries to a file
+    ///
+    /// # Arguments
+    ///
+    /// * `path`        - Log file
+    /// * `max_size`    - Size in bytes from which the file is rotated
+    /// * `max_files`   - How many rotated files are kept
+    ///
+    pub fn file(mut self, path: PathBuf, max_size: u64, max_files: usize) -> Self {
+        self.file = Some((path, max_size, max_files));
+        self
+    }
+
+    /// Set the logger as the global subscriber, it can only be done once
+    pub fn init(self) -> Result<Logger, LoggingErrors> {
+        let file = match &self.file {
+            Some((path, max_size, max_files)) => Some(
+                RotatingFile::open(path, *max_size, *max_files)
+                    .map_err(|_| LoggingErrors::CouldNotOpenFile)?,
+            ),
+            None => None,
+        };
+
+        let sink = Arc::new(Mutex::new(LogSink {
+            entries: VecDeque::with_capacity(self.capacity),
+            capacity: self.capacity,
+            file,
+        }));
+
+        let filter = build_filter(self.default_level, &self.levels)?;
+        let (filter, filter_handle) = reload::Layer::new(filter);
+
+        let subscriber = Registry::default()
+            .with(filter)
+            .with(fmt::Layer::default())
+            .with(CaptureLayer { sink: sink.clone() });
+
+        tracing::subscriber::set_global_default(subscriber)
+            .map_err(|_| LoggingErrors::AlreadyInitialized)?;
+
+        let logger = Logger {
+            default_level: self.default_level,
+            levels: Arc::new(Mutex::new(self.levels)),
+            filter_handle,
+            sink,
+        };
+
+        LOGGER
+            .set(logger.clone())
+            .map_err(|_| LoggingErrors::AlreadyInitialized)?;
+
+        Ok(logger)
+    }
+}
+
+/// Logs to the standard output, to a memory buffer the clients can read and optionally to a file.
+/// The level of every module can be changed while running.
+#[derive(Clone)]
+pub struct Logger {
+    default_level: LogLevel,
+    levels: Arc<Mutex<BTreeMap<String, LogLevel>>>,
+    filter_handle: reload::Handle<EnvFilter, Registry>,
+    sink: Arc<Mutex<LogSink>>,
+}
+
+impl Logger {
+    pub fn builder() -> LoggerBuilder {
+        LoggerBuilder {
+            default_level: LogLevel::Error,
+            levels: BTreeMap::new(),
+            capacity: 1000,
+            file: None,
+        }
+    }
+
+    /// The logger of the process, if it was initialized
+    pub fn get() -> Result<&'static Logger, LoggingErrors> {
+        LOGGER.get().ok_or(LoggingErrors::NotInitialized)
+    }
+
+    /// Change the level of a module, `None` makes it use the default level again
+    pub fn set_level(&self, target: &str, level: Option<LogLevel>) -> Result<(), LoggingErrors> {
+        let mut levels = self.levels.lock().unwrap();
+
+        let mut new_levels = levels.clone();
+        match level {
+            Some(level) => new_levels.insert(target.to_owned(), level),
+            None => new_levels.remove(target),
+        };
+
+        let filter = build_filter(self.default_level, &new_levels)?;
+        self.filter_handle
+            .reload(filter)
+            .map_err(|_| LoggingErrors::NotInitialized)?;
+        *levels = new_levels;
+
+        Ok(())
+    }
+
+    /// The level of every module with a specific one
+    pub fn get_levels(&self) -> BTreeMap<String, LogLevel> {
+        self.levels.lock().unwrap().clone()
+    }
+
+    /// The last entries, from the oldest to the newest
+    pub fn get_entries(&self, limit: usize) -> Vec<LogEntry> {
+        let sink = self.sink.lock().unwrap();
+        let skip = sink.entries.len().saturating_sub(limit);
+        sink.entries.iter().skip(skip).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    use super::{CaptureLayer, LogLevel, LogSink, RotatingFile};
+
+    #[test]
+    fn capture_and_rotate_logs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let path = dir.join("graviton.log");
+
+        let sink = Arc::new(Mutex::new(LogSink {
+            entries: VecDeque::new(),
+            capacity: 2,
+            file: Some(RotatingFile::open(&path, 60, 2).unwrap()),
+        }));
+        let subscriber = Registry::default().with(CaptureLayer { sink: sink.clone() });
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("first");
+            tracing::warn!(path = "/notes.md", "second");
+            tracing::error!("third");
+        });
+
+        // Only the last entries are kept in memory
+        let sink = sink.lock().unwrap();
+        assert_eq!(sink.entries.len(), 2);
+        assert_eq!(sink.entries[0].message, "second path=\"/notes.md\"");
+        assert_eq!(sink.entries[0].level, LogLevel::Warn);
+
+        // Every line is bigger than half of the maximum size
+        assert!(path.exists());
+        assert!(dir.join("graviton.log.1").exists());
+        assert!(dir.join("graviton.log.2").exists());
+        assert!(!dir.join("graviton.log.3").exists());
+    }
+}
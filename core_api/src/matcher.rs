@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// User setting with the weights, missing ones use the default value
+pub const MATCHER_WEIGHTS_SETTING: &str = "matcher.weights";
+
+/// How many recent items are remembered
+const RECENT_ITEMS_LIMIT: usize = 500;
+
+/// Scoring of the fuzzy matches, tunable by the user
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct MatcherWeights {
+    /// Every matched character
+    pub char_match: i64,
+    /// A character matched right after the previous one
+    pub consecutive: i64,
+    /// A character at the start of a word, e.g after `_`, `-`, `.` or a space
+    pub word_start: i64,
+    /// An uppercase character after a lowercase one, e.g the `C` in `getCommands`
+    pub camel_case: i64,
+    /// A character in the last segment of a path, usually the file name
+    pub path_segment: i64,
+    /// Every skipped character between two matched ones
+    pub gap_penalty: i64,
+    /// Every skipped character before the first match
+    pub leading_gap_penalty: i64,
+    /// Multiplies the frecency of an item, see [`RecentItems::get_frecency`]
+    pub frecency: f64,
+}
+
+impl Default for MatcherWeights {
+    fn default() -> Self {
+        Self {
+            char_match: 16,
+            consecutive: 24,
+            word_start: 32,
+            camel_case: 28,
+            path_segment: 20,
+            gap_penalty: 2,
+            leading_gap_penalty: 1,
+            frecency: 10.0,
+        }
+    }
+}
+
+/// How the text of the candidates is treated
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchKind {
+    #[default]
+    Text,
+    /// Matches in the last segment are boosted, and `/` starts a word
+    Path,
+}
+
+/// Something that can be fuzzy matched, e.g a command or a file
+pub trait Matchable {
+    /// Text the query is matched against
+    fn get_text(&self) -> &str;
+
+    /// Key used to remember it as a recent item
+    fn get_key(&self) -> &str {
+        self.get_text()
+    }
+
+    fn get_kind(&self) -> MatchKind {
+        MatchKind::Text
+    }
+}
+
+impl Matchable for String {
+    fn get_text(&self) -> &str {
+        self
+    }
+}
+
+/// Result of matching a query against a text
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// Indexes of the matched characters in the text
+    pub positions: Vec<usize>,
+}
+
+/// An item that matched a query
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Ranked<T> {
+    pub item: T,
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+/// Items used recently, the more often and the more recently the higher their frecency
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct RecentItems {
+    /// Times used and last use (Unix timestamp in seconds), by key
+    items: HashMap<String, (u32, u64)>,
+}
+
+impl RecentItems {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember an item was used, the oldest one is forgotten once the limit is reached
+    pub fn record(&mut self, key: &str, now: u64) {
+        let entry = self.items.entry(key.to_owned()).or_insert((0, now));
+        entry.0 += 1;
+        entry.1 = now;
+
+        if self.items.len() > RECENT_ITEMS_LIMIT {
+            let oldest = self
+                .items
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.items.remove(&oldest);
+            }
+        }
+    }
+
+    /// Uses weighted by how long ago the item was last used
+    pub fn get_frecency(&self, key: &str, now: u64) -> f64 {
+        let (count, last_used) = match self.items.get(key) {
+            Some(item) => *item,
+            None => return 0.0,
+        };
+
+        let age = now.saturating_sub(last_used);
+        let recency = match age {
+            age if age < 60 * 60 => 4.0,
+            age if age < 24 * 60 * 60 => 2.0,
+            age if age < 7 * 24 * 60 * 60 => 1.0,
+            _ => 0.5,
+        };
+        f64::from(count) * recency
+    }
+}
+
+fn is_separator(character: char) -> bool {
+    matches!(character, ' ' | '_' | '-' | '.' | '/' | '\\' | ':')
+}
+
+/// Fuzzy matcher shared by the quick-open, the command palette and the completions filtering
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FuzzyMatcher {
+    weights: MatcherWeights,
+}
+
+impl FuzzyMatcher {
+    pub fn new(weights: MatcherWeights) -> Self {
+        Self { weights }
+    }
+
+    /// Bonus of matching the character at `index`
+    fn get_bonus(&self, text: &[char], index: usize, segment_start: usize, kind: MatchKind) -> i64 {
+        let mut bonus = self.weights.char_match;
+
+        match index.checked_sub(1).map(|previous| text[previous]) {
+            None => bonus += self.weights.word_start,
+            Some(previous) if is_separator(previous) => bonus += self.weights.word_start,
+            Some(previous) if previous.is_lowercase() && text[index].is_uppercase() => {
+                bonus += self.weights.camel_case
+            }
+            _ => {}
+        }
+
+        if kind == MatchKind::Path && index >= segment_start {
+            bonus += self.weights.path_segment;
+        }
+
+        bonus
+    }
+
+    /// Match the characters of the query in order, case insensitively.
+    /// `None` if some are missing
+    pub fn score(&self, query: &str, text: &str, kind: MatchKind) -> Option<FuzzyMatch> {
+        let query = query
+            .chars()
+            .flat_map(char::to_lowercase)
+            .collect::<Vec<_>>();
+        let text = text.chars().collect::<Vec<_>>();
+
+        if query.is_empty() {
+            return Some(FuzzyMatch {
+                score: 0,
+                positions: Vec::new(),
+            });
+        }
+        if query.len() > text.len() {
+            return None;
+        }
+
+        let lowercase = text
+            .iter()
+            .map(|character| character.to_lowercase().next().unwrap_or(*character))
+            .collect::<Vec<_>>();
+        let segment_start = text
+            .iter()
+            .rposition(|character| *character == '/' || *character == '\\')
+            .map(|index| index + 1)
+            .unwrap_or_default();
+
+        // Best score of matching the query up to `i` with its last character at `j`, and where the previous one was
+        let mut best: Vec<Vec<Option<(i64, usize)>>> = vec![vec![None; text.len()]; query.len()];
+
+        for (i, query_char) in query.iter().enumerate() {
+            for j in i..text.len() {
+                if lowercase[j] != *query_char {
+                    continue;
+                }
+                let bonus = self.get_bonus(&text, j, segment_start, kind);
+
+                if i == 0 {
+                    let score = bonus - self.weights.leading_gap_penalty * j as i64;
+                    best[i][j] = Some((score, 0));
+                    continue;
+                }
+
+                best[i][j] = (i - 1..j)
+                    .filter_map(|k| {
+                        let (score, _) = best[i - 1][k]?;
+                        let link = if k + 1 == j {
+                            self.weights.consecutive
+                        } else {
+                            -self.weights.gap_penalty * (j - k - 1) as i64
+                        };
+                        Some((score + link + bonus, k))
+                    })
+                    .max_by_key(|(score, _)| *score);
+            }
+        }
+
+        let last = query.len() - 1;
+        let (mut position, (score, _)) = best[last]
+            .iter()
+            .enumerate()
+            .filter_map(|(j, cell)| cell.map(|cell| (j, cell)))
+            .max_by_key(|(_, (score, _))| *score)?;
+
+        let mut positions = vec![0; query.len()];
+        for i in (0..query.len()).rev() {
+            positions[i] = position;
+            if i > 0 {
+                position = best[i][position].map(|(_, previous)| previous)?;
+            }
+        }
+
+        Some(FuzzyMatch { score, positions })
+    }
+
+    /// Match every item and sort the matching ones from the best to the worst
+    ///
+    /// # Arguments
+    ///
+    /// * `query`    - What the user typed
+    /// * `items`    - The candidates
+    /// * `recent`   - Recently used items, they are ranked higher
+    /// * `now`      - Current Unix timestamp in seconds
+    ///
+    pub fn rank<T: Matchable>(
+        &self,
+        query: &str,
+        items: impl IntoIterator<Item = T>,
+        recent: Option<&RecentItems>,
+        now: u64,
+    ) -> Vec<Ranked<T>> {
+        let mut ranked = items
+            .into_iter()
+            .filter_map(|item| {
+                let found = self.score(query, item.get_text(), item.get_kind())?;
+                let frecency = recent
+                    .map(|recent| recent.get_frecency(item.get_key(), now))
+                    .unwrap_or_default();
+                Some(Ranked {
+                    score: found.score + (frecency * self.weights.frecency) as i64,
+                    positions: found.positions,
+                    item,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Shorter texts first when the score is the same
+        ranked.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.item.get_text().len().cmp(&b.item.get_text().len()))
+        });
+        ranked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FuzzyMatcher, MatchKind, MatcherWeights, RecentItems};
+
+    #[test]
+    fn score_matches() {
+        let matcher = FuzzyMatcher::default();
+
+        assert!(matcher
+            .score("xyz", "getCommands", MatchKind::Text)
+            .is_none());
+
+        let found = matcher.score("gc", "getCommands", MatchKind::Text).unwrap();
+        assert_eq!(found.positions, vec![0, 3]);
+
+        // Word starts beat scattered characters
+        let camel = matcher.score("gc", "getCommands", MatchKind::Text).unwrap();
+        let scattered = matcher.score("gc", "tagcloud", MatchKind::Text).unwrap();
+        assert!(camel.score > scattered.score);
+
+        // The file name matters more than the folders
+        let file = matcher
+            .score("main", "src/main.rs", MatchKind::Path)
+            .unwrap();
+        let folder = matcher
+            .score("main", "main/lib.rs", MatchKind::Path)
+            .unwrap();
+        assert!(file.score > folder.score);
+    }
+
+    #[test]
+    fn rank_with_frecency() {
+        let matcher = FuzzyMatcher::new(MatcherWeights {
+            frecency: 100.0,
+            ..MatcherWeights::default()
+        });
+        let items = vec!["open file".to_owned(), "open folder".to_owned()];
+
+        let ranked = matcher.rank("of", items.clone(), None, 0);
+        assert_eq!(ranked[0].item, "open file");
+
+        let mut recent = RecentItems::new();
+        recent.record("open folder", 0);
+        let ranked = matcher.rank("of", items, Some(&recent), 10);
+        assert_eq!(ranked[0].item, "open folder");
+        assert_eq!(ranked[0].positions, vec![0, 5]);
+    }
+}
@@ -1,4 +1,11 @@
+use crate::documents::DocumentEdit;
+use crate::extensions::editors::CustomEditorAction;
+use crate::extensions::messages::{ExtensionMessage, MessageTarget};
 use crate::filesystems::{DirItemInfo, FileInfo};
+use crate::progress::ProgressUpdate;
+use crate::settings::UserSettings;
+use crate::states::TokenScope;
+use crate::telemetry::TelemetryReport;
 use crate::Errors;
 use serde::{Deserialize, Serialize};
 
@@ -19,6 +26,104 @@ pub enum ClientMessages {
     WriteFile(u8, String, String, Result<(), Errors>),
     ListDir(u8, String, String, Result<Vec<DirItemInfo>, Errors>),
     Unload(u8),
+    ModalKeyPressed {
+        state_id: u8,
+        view_id: String,
+        key: String,
+        content: String,
+        cursor: usize,
+    },
+    RestartExtension {
+        state_id: u8,
+        extension_id: String,
+    },
+    ReloadExtension {
+        state_id: u8,
+        extension_id: String,
+    },
+    DocumentChanged {
+        state_id: u8,
+        filesystem: String,
+        path: String,
+        version: i32,
+        changes: Vec<DocumentEdit>,
+    },
+    /// Message between extensions, sent to the Core and then delivered to the target extensions
+    ExtensionMessage {
+        state_id: u8,
+        message: ExtensionMessage,
+    },
+    /// A message couldn't be delivered, sent to the extension that sent it
+    ExtensionMessageFailed {
+        state_id: u8,
+        target: MessageTarget,
+        error: Errors,
+    },
+    /// A long-running operation changed, sent to the Core and then to the clients
+    ProgressUpdate {
+        state_id: u8,
+        update: ProgressUpdate,
+    },
+    /// Run a command registered by an extension
+    InvokeCommand {
+        state_id: u8,
+        invocation_id: String,
+        command_id: String,
+        arguments: serde_json::Value,
+    },
+    /// Result of a command run by an extension
+    CommandResult {
+        state_id: u8,
+        extension_id: String,
+        invocation_id: String,
+        result: Result<serde_json::Value, Errors>,
+    },
+    /// The user settings or keybindings were modified
+    SettingsChanged {
+        state_id: u8,
+        /// Modified settings, `keybindings` if the keybindings were modified
+        keys: Vec<String>,
+        settings: UserSettings,
+    },
+    /// The configuration file of a workspace was modified
+    WorkspaceConfigChanged {
+        state_id: u8,
+        filesystem: String,
+        root: String,
+    },
+    /// Custom message handled by an extension, e.g `myext/doThing`
+    CustomRequest {
+        state_id: u8,
+        request_id: String,
+        message_type: String,
+        params: serde_json::Value,
+    },
+    /// Answer of an extension to a custom message
+    CustomResponse {
+        state_id: u8,
+        extension_id: String,
+        request_id: String,
+        result: Result<serde_json::Value, Errors>,
+    },
+    /// Ask the extension of a custom editor to open, save or close a file
+    CustomEditorRequest {
+        state_id: u8,
+        editor_id: String,
+        filesystem: String,
+        path: String,
+        action: CustomEditorAction,
+    },
+    /// An extension panicked, sent to the Core and then to the extensions and the clients
+    ExtensionCrashed {
+        state_id: u8,
+        extension_id: String,
+        message: String,
+    },
+    /// Periodic report of the telemetry, sent to the Core and then to the extensions and the clients
+    Metrics {
+        state_id: u8,
+        metrics: TelemetryReport,
+    },
 }
 
 impl ClientMessages {
@@ -33,6 +138,22 @@ impl ClientMessages {
             Self::Unload(state_id, ..) => *state_id,
             Self::UIEvent(event) => event.get_state_id(),
             Self::NotifyLanguageServers(msg) => msg.get_state_id(),
+            Self::ModalKeyPressed { state_id, .. } => *state_id,
+            Self::RestartExtension { state_id, .. } => *state_id,
+            Self::ReloadExtension { state_id, .. } => *state_id,
+            Self::DocumentChanged { state_id, .. } => *state_id,
+            Self::ExtensionMessage { state_id, .. } => *state_id,
+            Self::ExtensionMessageFailed { state_id, .. } => *state_id,
+            Self::ProgressUpdate { state_id, .. } => *state_id,
+            Self::InvokeCommand { state_id, .. } => *state_id,
+            Self::CommandResult { state_id, .. } => *state_id,
+            Self::SettingsChanged { state_id, .. } => *state_id,
+            Self::WorkspaceConfigChanged { state_id, .. } => *state_id,
+            Self::CustomRequest { state_id, .. } => *state_id,
+            Self::CustomResponse { state_id, .. } => *state_id,
+            Self::CustomEditorRequest { state_id, .. } => *state_id,
+            Self::ExtensionCrashed { state_id, .. } => *state_id,
+            Self::Metrics { state_id, .. } => *state_id,
         }
     }
 
@@ -47,6 +168,80 @@ impl ClientMessages {
             Self::Unload(..) => "unload",
             Self::UIEvent(..) => "ui",
             Self::NotifyLanguageServers { .. } => "lsp",
+            Self::ModalKeyPressed { .. } => "modalKeyPressed",
+            Self::RestartExtension { .. } => "restartExtension",
+            Self::ReloadExtension { .. } => "reloadExtension",
+            Self::DocumentChanged { .. } => "documentChanged",
+            Self::ExtensionMessage { .. } => "extensionMessage",
+            Self::ExtensionMessageFailed { .. } => "extensionMessageFailed",
+            Self::ProgressUpdate { .. } => "progressUpdate",
+            Self::InvokeCommand { .. } => "invokeCommand",
+            Self::CommandResult { .. } => "commandResult",
+            Self::SettingsChanged { .. } => "settingsChanged",
+            Self::WorkspaceConfigChanged { .. } => "workspaceConfigChanged",
+            Self::CustomRequest { .. } => "customRequest",
+            Self::CustomResponse { .. } => "customResponse",
+            Self::CustomEditorRequest { .. } => "customEditorRequest",
+            Self::ExtensionCrashed { .. } => "extensionCrashed",
+            Self::Metrics { .. } => "metrics",
+        }
+    }
+
+    /// Scope a remote client needs to send the message, None if only the Core and the extensions can send it,
+    /// e.g clients could impersonate an extension by answering its requests
+    pub fn get_client_scope(&self) -> Option<TokenScope> {
+        match self {
+            Self::ListenToState { .. } => Some(TokenScope::ReadOnly),
+            Self::NotifyExtension(..)
+            | Self::NotifyLanguageServers(..)
+            | Self::UIEvent(..)
+            | Self::ModalKeyPressed { .. }
+            | Self::DocumentChanged { .. }
+            | Self::InvokeCommand { .. }
+            | Self::CustomRequest { .. }
+            | Self::CustomEditorRequest { .. } => Some(TokenScope::Edit),
+            Self::RestartExtension { .. } | Self::ReloadExtension { .. } => {
+                Some(TokenScope::ExtensionAdmin)
+            }
+            Self::ServerMessage(..)
+            | Self::ReadFile(..)
+            | Self::WriteFile(..)
+            | Self::ListDir(..)
+            | Self::Unload(..)
+            | Self::ExtensionMessage { .. }
+            | Self::ExtensionMessageFailed { .. }
+            | Self::ProgressUpdate { .. }
+            | Self::CommandResult { .. }
+            | Self::SettingsChanged { .. }
+            | Self::WorkspaceConfigChanged { .. }
+            | Self::CustomResponse { .. }
+            | Self::ExtensionCrashed { .. }
+            | Self::Metrics { .. } => None,
+        }
+    }
+
+    /// Name of the filesystem the message is about, if any
+    pub fn get_filesystem(&self) -> Option<&str> {
+        match self {
+            Self::ReadFile(_, filesystem, ..) => Some(filesystem),
+            Self::WriteFile(_, filesystem, ..) => Some(filesystem),
+            Self::ListDir(_, filesystem, ..) => Some(filesystem),
+            Self::DocumentChanged { filesystem, .. } => Some(filesystem),
+            Self::WorkspaceConfigChanged { filesystem, .. } => Some(filesystem),
+            Self::CustomEditorRequest { filesystem, .. } => Some(filesystem),
+            _ => None,
+        }
+    }
+
+    /// Path the message is about, if any
+    pub fn get_path(&self) -> Option<&str> {
+        match self {
+            Self::ReadFile(_, _, Ok(file)) => Some(&file.path),
+            Self::ListDir(_, _, path, ..) => Some(path),
+            Self::DocumentChanged { path, .. } => Some(path),
+            Self::WorkspaceConfigChanged { root, .. } => Some(root),
+            Self::CustomEditorRequest { path, .. } => Some(path),
+            _ => None,
         }
     }
 }
@@ -73,8 +268,24 @@ impl LanguageServerMessage {
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(tag = "msg_type")]
 pub enum UIEvent {
-    StatusBarItemClicked { state_id: u8, id: String },
-    CommandActioned { state_id: u8, id: String },
+    StatusBarItemClicked {
+        state_id: u8,
+        id: String,
+    },
+    CommandActioned {
+        state_id: u8,
+        id: String,
+    },
+    TreeItemClicked {
+        state_id: u8,
+        view_id: String,
+        item_id: String,
+    },
+    PanelMessage {
+        state_id: u8,
+        panel_id: String,
+        data: serde_json::Value,
+    },
 }
 
 impl UIEvent {
@@ -82,6 +293,8 @@ impl UIEvent {
         match self {
             Self::CommandActioned { id, .. } => id,
             Self::StatusBarItemClicked { id, .. } => id,
+            Self::TreeItemClicked { view_id, .. } => view_id,
+            Self::PanelMessage { panel_id, .. } => panel_id,
         }
     }
 
@@ -89,6 +302,8 @@ impl UIEvent {
         match self {
             Self::CommandActioned { state_id, .. } => *state_id,
             Self::StatusBarItemClicked { state_id, .. } => *state_id,
+            Self::TreeItemClicked { state_id, .. } => *state_id,
+            Self::PanelMessage { state_id, .. } => *state_id,
         }
     }
 }
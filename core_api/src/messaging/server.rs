@@ -1,4 +1,23 @@
-use crate::states::StateData;
+use crate::clipboard::ClipboardOrigin;
+use crate::decorations::DecorationUpdate;
+use crate::extensions::modules::webview_panel::PanelContent;
+use crate::kernels::KernelOutput;
+use crate::language_servers::LanguageServerStatus;
+use crate::locale::ClientLocale;
+use crate::modal_editing::{Mode, TextEdit};
+use crate::progress::ProgressUpdate;
+use crate::recovery::RecoveredSession;
+use crate::save_hooks::SaveHookErrors;
+use crate::search::SearchMatch;
+use crate::settings::UserSettings;
+use crate::states::{CommandGroupInfo, ShutdownReport, StateData, StateDelta, StateEvent};
+use crate::tasks::{Problem, TaskErrors, TaskRun, TaskStream};
+use crate::telemetry::TelemetryReport;
+use crate::tree_views::TreeViewInfo;
+use crate::validation::Diagnostic;
+use crate::vcs::RepositoryStatus;
+use crate::workspaces::WorkspaceConfig;
+use crate::Errors;
 use serde::{Deserialize, Serialize};
 
 /// Messages sent from the Server to the Client
@@ -16,6 +35,29 @@ pub enum ServerMessages {
         content: String,
         title: String,
     },
+    ShowPanel {
+        state_id: u8,
+        panel_id: String,
+        title: String,
+        content: PanelContent,
+    },
+    ClosePanel {
+        state_id: u8,
+        panel_id: String,
+    },
+    /// Content of a file opened with a custom editor, sent by it's extension
+    ShowCustomEditor {
+        state_id: u8,
+        editor_id: String,
+        filesystem: String,
+        path: String,
+        content: PanelContent,
+    },
+    MessageToPanel {
+        state_id: u8,
+        panel_id: String,
+        data: serde_json::Value,
+    },
     ShowStatusBarItem {
         state_id: u8,
         id: String,
@@ -32,13 +74,28 @@ pub enum ServerMessages {
         content: String,
     },
     StateUpdated {
-        state_data: StateData,
+        state_data: Box<StateData>,
+    },
+    /// Changes since the last StateData the clients were sent
+    StateDelta {
+        state_id: u8,
+        delta: StateDelta,
     },
     TerminalShellUpdated {
         state_id: u8,
         terminal_shell_id: String,
         data: Vec<u8>,
     },
+    TerminalShellClosed {
+        state_id: u8,
+        terminal_shell_id: String,
+    },
+    OutputChannelUpdated {
+        state_id: u8,
+        channel_id: String,
+        name: String,
+        data: String,
+    },
     RegisterCommand {
         state_id: u8,
         name: String,
@@ -48,20 +105,279 @@ pub enum ServerMessages {
         id: String,
         state_id: u8,
     },
+    ExtensionCrashed {
+        state_id: u8,
+        extension_id: String,
+        message: String,
+    },
+    ModalKeyHandled {
+        state_id: u8,
+        view_id: String,
+        edits: Vec<TextEdit>,
+        cursor: usize,
+        mode: Mode,
+    },
+    KernelOutput {
+        state_id: u8,
+        kernel_id: String,
+        msg_id: String,
+        output: KernelOutput,
+    },
+    LanguageServerStatusChanged {
+        state_id: u8,
+        id: String,
+        status: LanguageServerStatus,
+    },
+    LanguageServerInstalled {
+        state_id: u8,
+        config_id: String,
+        version: String,
+    },
+    InvitationRevoked {
+        state_id: u8,
+        invitation_id: String,
+    },
+    TreeViewRegistered {
+        state_id: u8,
+        view: TreeViewInfo,
+    },
+    TreeViewRefreshed {
+        state_id: u8,
+        view_id: String,
+        item_id: Option<String>,
+    },
+    SearchResults {
+        state_id: u8,
+        search_id: String,
+        matches: Vec<SearchMatch>,
+        done: bool,
+    },
+    SessionRecovered {
+        state_id: u8,
+        session: Box<RecoveredSession>,
+    },
+    RepositoryChanged {
+        state_id: u8,
+        filesystem: String,
+        path: String,
+        status: RepositoryStatus,
+    },
+    PathRenamed {
+        state_id: u8,
+        filesystem: String,
+        from: String,
+        to: String,
+    },
+    ShuttingDown {
+        state_id: u8,
+    },
+    /// Something was copied remotely, or by another client. The text isn't included,
+    /// so only the clients with edit access can read it with `get_clipboard`
+    ClipboardChanged {
+        state_id: u8,
+        origin: ClipboardOrigin,
+        /// Unix timestamp (seconds) of when it was copied
+        copied_at: u64,
+    },
+    /// A group of commands was applied as a single undoable change
+    CommandGroupEnded {
+        state_id: u8,
+        group: CommandGroupInfo,
+    },
+    /// Last message of a State before the transports are closed
+    ShutdownComplete {
+        state_id: u8,
+        report: ShutdownReport,
+    },
+    StateHibernated {
+        state_id: u8,
+    },
+    MixedLineEndings {
+        state_id: u8,
+        filesystem: String,
+        path: String,
+    },
+    /// A hook failed before saving a document, it was saved anyway
+    SaveHookFailed {
+        state_id: u8,
+        filesystem: String,
+        path: String,
+        hook_id: String,
+        error: SaveHookErrors,
+    },
+    /// Problems found validating a configuration file with it's schema
+    DocumentDiagnostics {
+        state_id: u8,
+        filesystem: String,
+        path: String,
+        version: i32,
+        diagnostics: Vec<Diagnostic>,
+    },
+    StateAwakened {
+        state_id: u8,
+    },
+    TokenRevoked {
+        state_id: u8,
+        token_id: String,
+    },
+    StateChanged {
+        state_id: u8,
+        event: StateEvent,
+    },
+    ScheduledTaskFinished {
+        state_id: u8,
+        task_id: String,
+        run: TaskRun,
+        /// Formatted for each client
+        summary: String,
+    },
+    ProgressUpdate {
+        state_id: u8,
+        update: ProgressUpdate,
+    },
+    CommandFinished {
+        state_id: u8,
+        invocation_id: String,
+        result: Result<serde_json::Value, Errors>,
+    },
+    SettingsChanged {
+        state_id: u8,
+        keys: Vec<String>,
+        settings: UserSettings,
+    },
+    WorkspaceConfigUpdated {
+        state_id: u8,
+        filesystem: String,
+        root: String,
+        /// The merged configuration, or why it couldn't be loaded
+        config: Result<WorkspaceConfig, Errors>,
+    },
+    CustomResponse {
+        state_id: u8,
+        request_id: String,
+        result: Result<serde_json::Value, Errors>,
+    },
+    TaskStarted {
+        state_id: u8,
+        run_id: String,
+        task_id: String,
+    },
+    TaskOutput {
+        state_id: u8,
+        run_id: String,
+        stream: TaskStream,
+        line: String,
+    },
+    TaskFinished {
+        state_id: u8,
+        run_id: String,
+        task_id: String,
+        result: Result<Option<i32>, TaskErrors>,
+        problems: Vec<Problem>,
+    },
+    DecorationsUpdated {
+        state_id: u8,
+        updates: Vec<DecorationUpdate>,
+    },
+    FileOperationProgress {
+        state_id: u8,
+        operation_id: String,
+        processed: usize,
+        total: usize,
+    },
+    FileOperationFinished {
+        state_id: u8,
+        operation_id: String,
+        result: Result<(), Errors>,
+    },
+    ArchiveProgress {
+        state_id: u8,
+        operation_id: String,
+        processed: usize,
+        total: usize,
+    },
+    /// Periodic report of the telemetry of a State
+    Metrics {
+        state_id: u8,
+        metrics: TelemetryReport,
+    },
 }
 
 impl ServerMessages {
+    /// Copy of the message with the text made by the Core formatted for a client,
+    /// none if there is no such text so the message can be sent as it is
+    pub fn localized(&self, locale: &ClientLocale) -> Option<Self> {
+        match self {
+            Self::ScheduledTaskFinished {
+                state_id,
+                task_id,
+                run,
+                ..
+            } => Some(Self::ScheduledTaskFinished {
+                state_id: *state_id,
+                task_id: task_id.clone(),
+                run: run.clone(),
+                summary: run.get_summary(task_id, locale),
+            }),
+            _ => None,
+        }
+    }
+
     pub fn get_state_id(&self) -> u8 {
         match self {
             Self::UnloadedLanguageServer { state_id, .. } => *state_id,
+            Self::ExtensionCrashed { state_id, .. } => *state_id,
+            Self::ModalKeyHandled { state_id, .. } => *state_id,
+            Self::KernelOutput { state_id, .. } => *state_id,
+            Self::LanguageServerStatusChanged { state_id, .. } => *state_id,
+            Self::LanguageServerInstalled { state_id, .. } => *state_id,
+            Self::InvitationRevoked { state_id, .. } => *state_id,
+            Self::TreeViewRegistered { state_id, .. } => *state_id,
+            Self::TreeViewRefreshed { state_id, .. } => *state_id,
+            Self::SearchResults { state_id, .. } => *state_id,
+            Self::SessionRecovered { state_id, .. } => *state_id,
+            Self::RepositoryChanged { state_id, .. } => *state_id,
+            Self::PathRenamed { state_id, .. } => *state_id,
+            Self::ShuttingDown { state_id } => *state_id,
+            Self::ClipboardChanged { state_id, .. } => *state_id,
+            Self::CommandGroupEnded { state_id, .. } => *state_id,
+            Self::ShutdownComplete { state_id, .. } => *state_id,
+            Self::StateHibernated { state_id } => *state_id,
+            Self::MixedLineEndings { state_id, .. } => *state_id,
+            Self::SaveHookFailed { state_id, .. } => *state_id,
+            Self::DocumentDiagnostics { state_id, .. } => *state_id,
+            Self::StateAwakened { state_id } => *state_id,
+            Self::TokenRevoked { state_id, .. } => *state_id,
+            Self::StateChanged { state_id, .. } => *state_id,
+            Self::ScheduledTaskFinished { state_id, .. } => *state_id,
+            Self::ProgressUpdate { state_id, .. } => *state_id,
+            Self::CommandFinished { state_id, .. } => *state_id,
+            Self::SettingsChanged { state_id, .. } => *state_id,
+            Self::WorkspaceConfigUpdated { state_id, .. } => *state_id,
+            Self::CustomResponse { state_id, .. } => *state_id,
+            Self::TaskStarted { state_id, .. } => *state_id,
+            Self::TaskOutput { state_id, .. } => *state_id,
+            Self::TaskFinished { state_id, .. } => *state_id,
+            Self::ArchiveProgress { state_id, .. } => *state_id,
+            Self::FileOperationProgress { state_id, .. } => *state_id,
+            Self::FileOperationFinished { state_id, .. } => *state_id,
+            Self::DecorationsUpdated { state_id, .. } => *state_id,
             Self::RegisterCommand { state_id, .. } => *state_id,
             Self::TerminalShellUpdated { state_id, .. } => *state_id,
+            Self::TerminalShellClosed { state_id, .. } => *state_id,
+            Self::OutputChannelUpdated { state_id, .. } => *state_id,
             Self::MessageFromExtension { state_id, .. } => *state_id,
             Self::StateUpdated { state_data } => state_data.id,
+            Self::StateDelta { state_id, .. } => *state_id,
             Self::ShowPopup { state_id, .. } => *state_id,
+            Self::ShowPanel { state_id, .. } => *state_id,
+            Self::ClosePanel { state_id, .. } => *state_id,
+            Self::ShowCustomEditor { state_id, .. } => *state_id,
+            Self::MessageToPanel { state_id, .. } => *state_id,
             Self::ShowStatusBarItem { state_id, .. } => *state_id,
             Self::HideStatusBarItem { state_id, .. } => *state_id,
             Self::NotifyLanguageServersClient { state_id, .. } => *state_id,
+            Self::Metrics { state_id, .. } => *state_id,
         }
     }
 }
@@ -0,0 +1,176 @@
+use std::fmt::{Display, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::states::StatesList;
+
+/// Counters of the whole process, shared by all the States
+pub static METRICS: Metrics = Metrics::new();
+
+/// Counters that only go up, exported in the Prometheus text format
+#[derive(Debug, Default)]
+pub struct Metrics {
+    messages: AtomicU64,
+    persists: AtomicU64,
+    persist_micros: AtomicU64,
+    language_server_restarts: AtomicU64,
+}
+
+/// Values measured at the moment the metrics are exported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MetricsGauges {
+    pub states: usize,
+    pub extensions: usize,
+    pub language_servers: usize,
+}
+
+impl MetricsGauges {
+    /// Measure the States in the list
+    pub async fn collect(states: &StatesList) -> Self {
+        let mut gauges = Self::default();
+
+        for state in states.get_states() {
+            let state = state.lock().await;
+            gauges.states += 1;
+            gauges.extensions += state.get_ext_list().len();
+            gauges.language_servers += state.language_servers_manager.get_all_running().len();
+        }
+
+        gauges
+    }
+}
+
+/// Append a metric with its description and type
+fn write_metric(output: &mut String, name: &str, kind: &str, help: &str, value: impl Display) {
+    writeln!(output, "# HELP {} {}", name, help).ok();
+    writeln!(output, "# TYPE {} {}", name, kind).ok();
+    writeln!(output, "{} {}", name, value).ok();
+}
+
+impl Metrics {
+    pub const fn new() -> Self {
+        Self {
+            messages: AtomicU64::new(0),
+            persists: AtomicU64::new(0),
+            persist_micros: AtomicU64::new(0),
+            language_server_restarts: AtomicU64::new(0),
+        }
+    }
+
+    /// A message was received from a client or an extension
+    pub fn record_message(&self) {
+        self.messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Messages received since the process started
+    pub fn get_messages(&self) -> u64 {
+        self.messages.load(Ordering::Relaxed)
+    }
+
+    /// A State was saved with its persistor
+    pub fn record_persist(&self, elapsed: Duration) {
+        self.persists.fetch_add(1, Ordering::Relaxed);
+        self.persist_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// A language server was restarted
+    pub fn record_language_server_restart(&self) {
+        self.language_server_restarts
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Export the metrics in the Prometheus text format
+    pub fn to_prometheus(&self, gauges: &MetricsGauges) -> String {
+        let mut output = String::new();
+
+        write_metric(
+            &mut output,
+            "graviton_states",
+            "gauge",
+            "Open States",
+            gauges.states,
+        );
+        write_metric(
+            &mut output,
+            "graviton_extensions_loaded",
+            "gauge",
+            "Extensions loaded in all the States",
+            gauges.extensions,
+        );
+        write_metric(
+            &mut output,
+            "graviton_language_servers_running",
+            "gauge",
+            "Language servers running in all the States",
+            gauges.language_servers,
+        );
+        write_metric(
+            &mut output,
+            "graviton_messages_total",
+            "counter",
+            "Messages received by the server",
+            self.messages.load(Ordering::Relaxed),
+        );
+
+        // Summary without quantiles, the average latency is sum / count
+        let persist_seconds = self.persist_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        writeln!(
+            output,
+            "# HELP graviton_persist_duration_seconds Time spent saving the States"
+        )
+        .ok();
+        writeln!(output, "# TYPE graviton_persist_duration_seconds summary").ok();
+        writeln!(
+            output,
+            "graviton_persist_duration_seconds_sum {}",
+            persist_seconds
+        )
+        .ok();
+        writeln!(
+            output,
+            "graviton_persist_duration_seconds_count {}",
+            self.persists.load(Ordering::Relaxed)
+        )
+        .ok();
+
+        write_metric(
+            &mut output,
+            "graviton_language_server_restarts_total",
+            "counter",
+            "Language servers restarted",
+            self.language_server_restarts.load(Ordering::Relaxed),
+        );
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Metrics, MetricsGauges};
+
+    #[test]
+    fn export_prometheus_metrics() {
+        let metrics = Metrics::new();
+        metrics.record_message();
+        metrics.record_message();
+        metrics.record_persist(Duration::from_millis(250));
+        metrics.record_persist(Duration::from_millis(250));
+
+        let output = metrics.to_prometheus(&MetricsGauges {
+            states: 2,
+            extensions: 3,
+            language_servers: 0,
+        });
+
+        assert!(output.contains("# TYPE graviton_states gauge\ngraviton_states 2\n"));
+        assert!(output.contains("graviton_extensions_loaded 3\n"));
+        assert!(output.contains("graviton_messages_total 2\n"));
+        assert!(output.contains("graviton_persist_duration_seconds_sum 0.5\n"));
+        assert!(output.contains("graviton_persist_duration_seconds_count 2\n"));
+        assert!(output.contains("graviton_language_server_restarts_total 0\n"));
+    }
+}
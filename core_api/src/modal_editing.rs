@@ -0,0 +1,610 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Editing mode of a view
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+}
+
+/// Operators that wait for a motion, e.g `d` in `dw`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+impl Operator {
+    /// The key that triggers the operator, also used for linewise operations, e.g `dd`
+    fn key(&self) -> &str {
+        match self {
+            Self::Delete => "d",
+            Self::Change => "c",
+            Self::Yank => "y",
+        }
+    }
+}
+
+/// An edit over the characters offsets of a text
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// Content of a register
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Register {
+    pub content: String,
+    pub linewise: bool,
+}
+
+/// Result of handling a key, the edits must be applied in order
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ModalOutput {
+    pub edits: Vec<TextEdit>,
+    pub cursor: usize,
+    pub mode: Mode,
+}
+
+/// Vim-like modal editing state of a view
+#[derive(Clone, Debug, Default)]
+pub struct ModalEngine {
+    mode: Mode,
+    count: Option<usize>,
+    operator: Option<(Operator, usize)>,
+    awaiting_register: bool,
+    register: Option<char>,
+    registers: HashMap<char, Register>,
+    visual_anchor: usize,
+}
+
+impl ModalEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current mode
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Retrieve the content of a register
+    pub fn get_register(&self, name: char) -> Option<&Register> {
+        self.registers.get(&name)
+    }
+
+    /// Handle a key pressed in the view
+    ///
+    /// # Arguments
+    ///
+    /// * `key`     - The key, e.g `w`, `<Esc>` or `<Enter>`
+    /// * `content` - The content of the document
+    /// * `cursor`  - The cursor position, in characters
+    ///
+    pub fn handle_key(&mut self, key: &str, content: &str, cursor: usize) -> ModalOutput {
+        let text = content.chars().collect::<Vec<char>>();
+        let mut output = ModalOutput {
+            edits: Vec::new(),
+            cursor: cursor.min(text.len()),
+            mode: self.mode,
+        };
+
+        if self.mode == Mode::Insert {
+            self.handle_insert_key(key, &text, &mut output);
+        } else if self.awaiting_register {
+            self.awaiting_register = false;
+            self.register = key.chars().next();
+        } else if key == "\"" {
+            self.awaiting_register = true;
+        } else if let Some(digit) = self.count_digit(key) {
+            self.count = Some(self.count.unwrap_or(0) * 10 + digit);
+        } else {
+            let count = self.count.take().unwrap_or(1);
+            match self.mode {
+                Mode::Visual => self.handle_visual_key(key, count, &text, &mut output),
+                _ => self.handle_normal_key(key, count, &text, &mut output),
+            }
+        }
+
+        output.mode = self.mode;
+        output
+    }
+
+    /// Return the digit if the key is part of a count
+    fn count_digit(&self, key: &str) -> Option<usize> {
+        let mut chars = key.chars();
+        let digit = chars.next()?.to_digit(10)? as usize;
+
+        if chars.next().is_some() || (digit == 0 && self.count.is_none()) {
+            None
+        } else {
+            Some(digit)
+        }
+    }
+
+    fn handle_insert_key(&mut self, key: &str, text: &[char], output: &mut ModalOutput) {
+        let cursor = output.cursor;
+        match key {
+            "<Esc>" => {
+                self.mode = Mode::Normal;
+                if cursor > line_start(text, cursor) {
+                    output.cursor = cursor - 1;
+                }
+            }
+            "<BS>" => {
+                if cursor > 0 {
+                    output.edits.push(TextEdit {
+                        start: cursor - 1,
+                        end: cursor,
+                        text: String::new(),
+                    });
+                    output.cursor = cursor - 1;
+                }
+            }
+            "<Enter>" => insert_text(output, "\n"),
+            "<Tab>" => insert_text(output, "\t"),
+            key => {
+                if key.chars().count() == 1 {
+                    insert_text(output, key);
+                }
+            }
+        }
+    }
+
+    fn handle_normal_key(
+        &mut self,
+        key: &str,
+        count: usize,
+        text: &[char],
+        output: &mut ModalOutput,
+    ) {
+        let cursor = output.cursor;
+
+        // Complete a pending operator, any other key cancels it
+        if let Some((operator, operator_count)) = self.operator.take() {
+            let count = count * operator_count;
+            if key == operator.key() {
+                self.apply_linewise(operator, count, text, output);
+            } else if let Some(target) = motion_target(key, text, cursor, count) {
+                let (start, end) = if target < cursor {
+                    (target, cursor)
+                } else {
+                    (cursor, target)
+                };
+                self.apply_operator(operator, start, end, text, output);
+            } else {
+                self.register = None;
+            }
+            return;
+        }
+
+        match key {
+            "d" => self.operator = Some((Operator::Delete, count)),
+            "c" => self.operator = Some((Operator::Change, count)),
+            "y" => self.operator = Some((Operator::Yank, count)),
+            "x" => {
+                let end = (cursor + count).min(line_end(text, cursor));
+                if end > cursor {
+                    self.apply_operator(Operator::Delete, cursor, end, text, output);
+                }
+            }
+            "i" => self.mode = Mode::Insert,
+            "a" => {
+                if cursor < line_end(text, cursor) {
+                    output.cursor = cursor + 1;
+                }
+                self.mode = Mode::Insert;
+            }
+            "I" => {
+                output.cursor = line_start(text, cursor);
+                self.mode = Mode::Insert;
+            }
+            "A" => {
+                output.cursor = line_end(text, cursor);
+                self.mode = Mode::Insert;
+            }
+            "o" => {
+                output.cursor = line_end(text, cursor);
+                insert_text(output, "\n");
+                self.mode = Mode::Insert;
+            }
+            "O" => {
+                let start = line_start(text, cursor);
+                output.cursor = start;
+                insert_text(output, "\n");
+                output.cursor = start;
+                self.mode = Mode::Insert;
+            }
+            "p" => self.paste(true, count, text, output),
+            "P" => self.paste(false, count, text, output),
+            "v" => {
+                self.visual_anchor = cursor;
+                self.mode = Mode::Visual;
+            }
+            "<Esc>" => self.register = None,
+            key => {
+                if let Some(target) = motion_target(key, text, cursor, count) {
+                    output.cursor = target;
+                }
+            }
+        }
+    }
+
+    fn handle_visual_key(
+        &mut self,
+        key: &str,
+        count: usize,
+        text: &[char],
+        output: &mut ModalOutput,
+    ) {
+        let cursor = output.cursor;
+        let start = self.visual_anchor.min(cursor);
+        let end = (self.visual_anchor.max(cursor) + 1).min(text.len());
+
+        match key {
+            "<Esc>" | "v" => self.mode = Mode::Normal,
+            "d" | "x" => {
+                self.mode = Mode::Normal;
+                self.apply_operator(Operator::Delete, start, end, text, output);
+            }
+            "c" => {
+                self.mode = Mode::Normal;
+                self.apply_operator(Operator::Change, start, end, text, output);
+            }
+            "y" => {
+                self.mode = Mode::Normal;
+                self.apply_operator(Operator::Yank, start, end, text, output);
+            }
+            key => {
+                if let Some(target) = motion_target(key, text, cursor, count) {
+                    output.cursor = target;
+                }
+            }
+        }
+    }
+
+    /// Apply a characterwise operator over a range
+    fn apply_operator(
+        &mut self,
+        operator: Operator,
+        start: usize,
+        end: usize,
+        text: &[char],
+        output: &mut ModalOutput,
+    ) {
+        self.store_register(Register {
+            content: text[start..end].iter().collect(),
+            linewise: false,
+        });
+
+        output.cursor = start;
+
+        if operator != Operator::Yank {
+            output.edits.push(TextEdit {
+                start,
+                end,
+                text: String::new(),
+            });
+        }
+
+        if operator == Operator::Change {
+            self.mode = Mode::Insert;
+        }
+    }
+
+    /// Apply an operator over `count` lines starting from the cursor line, e.g `dd`
+    fn apply_linewise(
+        &mut self,
+        operator: Operator,
+        count: usize,
+        text: &[char],
+        output: &mut ModalOutput,
+    ) {
+        let start = line_start(text, output.cursor);
+        let mut end = start;
+        for _ in 0..count {
+            let current_line_end = line_end(text, end);
+            if current_line_end >= text.len() {
+                end = text.len();
+                break;
+            }
+            end = current_line_end + 1;
+        }
+
+        let mut content = text[start..end].iter().collect::<String>();
+        let has_newline = content.ends_with('\n');
+        if !has_newline {
+            content.push('\n');
+        }
+
+        self.store_register(Register {
+            content,
+            linewise: true,
+        });
+
+        match operator {
+            Operator::Yank => {}
+            Operator::Delete => {
+                // Deleting the last line also removes the previous line break
+                let edit_start = if !has_newline && start > 0 {
+                    start - 1
+                } else {
+                    start
+                };
+                output.edits.push(TextEdit {
+                    start: edit_start,
+                    end,
+                    text: String::new(),
+                });
+                output.cursor = line_start(text, edit_start);
+            }
+            Operator::Change => {
+                let edit_end = if has_newline { end - 1 } else { end };
+                output.edits.push(TextEdit {
+                    start,
+                    end: edit_end,
+                    text: String::new(),
+                });
+                output.cursor = start;
+                self.mode = Mode::Insert;
+            }
+        }
+    }
+
+    /// Paste the selected register (or the unnamed one) `count` times
+    fn paste(&mut self, after: bool, count: usize, text: &[char], output: &mut ModalOutput) {
+        let name = self.register.take().unwrap_or('"');
+        let register = if let Some(register) = self.registers.get(&name) {
+            register.clone()
+        } else {
+            return;
+        };
+
+        let content = register.content.repeat(count);
+        let cursor = output.cursor;
+
+        if register.linewise {
+            let mut position = line_start(text, cursor);
+            if after {
+                let current_line_end = line_end(text, cursor);
+                position = (current_line_end + 1).min(text.len());
+            }
+
+            // Pasting after a last line that has no line break
+            let content = if after && position == text.len() && text.last() != Some(&'\n') {
+                format!("\n{}", content.strip_suffix('\n').unwrap_or(&content))
+            } else {
+                content
+            };
+
+            output.cursor = if content.starts_with('\n') {
+                position + 1
+            } else {
+                position
+            };
+            output.edits.push(TextEdit {
+                start: position,
+                end: position,
+                text: content,
+            });
+        } else {
+            let position = if after && cursor < line_end(text, cursor) {
+                cursor + 1
+            } else {
+                cursor
+            };
+            output.cursor = (position + content.chars().count()).saturating_sub(1);
+            output.edits.push(TextEdit {
+                start: position,
+                end: position,
+                text: content,
+            });
+        }
+    }
+
+    /// Save into the unnamed register and also the selected register if any
+    fn store_register(&mut self, register: Register) {
+        if let Some(name) = self.register.take() {
+            self.registers.insert(name, register.clone());
+        }
+        self.registers.insert('"', register);
+    }
+}
+
+/// Insert text at the cursor and move it to the end of the inserted text
+fn insert_text(output: &mut ModalOutput, text: &str) {
+    output.edits.push(TextEdit {
+        start: output.cursor,
+        end: output.cursor,
+        text: text.to_string(),
+    });
+    output.cursor += text.chars().count();
+}
+
+/// Position where the line of `pos` starts
+fn line_start(text: &[char], pos: usize) -> usize {
+    text[..pos]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map(|i| i + 1)
+        .unwrap_or(0)
+}
+
+/// Position of the line break (or the end of the text) of the line of `pos`
+fn line_end(text: &[char], pos: usize) -> usize {
+    text[pos..]
+        .iter()
+        .position(|c| *c == '\n')
+        .map(|i| pos + i)
+        .unwrap_or(text.len())
+}
+
+/// 0 for whitespaces, 1 for words and 2 for punctuation
+fn char_class(c: char) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+fn next_word_start(text: &[char], pos: usize) -> usize {
+    let mut pos = pos;
+    if pos >= text.len() {
+        return text.len();
+    }
+
+    let class = char_class(text[pos]);
+    if class != 0 {
+        while pos < text.len() && char_class(text[pos]) == class {
+            pos += 1;
+        }
+    }
+    while pos < text.len() && text[pos].is_whitespace() {
+        pos += 1;
+    }
+    pos
+}
+
+fn prev_word_start(text: &[char], pos: usize) -> usize {
+    let mut pos = pos;
+    while pos > 0 && text[pos - 1].is_whitespace() {
+        pos -= 1;
+    }
+    if pos == 0 {
+        return 0;
+    }
+
+    let class = char_class(text[pos - 1]);
+    while pos > 0 && char_class(text[pos - 1]) == class {
+        pos -= 1;
+    }
+    pos
+}
+
+/// Move to the same column in the next or previous line
+fn vertical_motion(text: &[char], pos: usize, down: bool) -> usize {
+    let start = line_start(text, pos);
+    let column = pos - start;
+
+    if down {
+        let end = line_end(text, pos);
+        if end >= text.len() {
+            return pos;
+        }
+        let next_start = end + 1;
+        (next_start + column).min(line_end(text, next_start))
+    } else {
+        if start == 0 {
+            return pos;
+        }
+        let prev_start = line_start(text, start - 1);
+        (prev_start + column).min(start - 1)
+    }
+}
+
+/// Resolve where a motion key would move the cursor
+fn motion_target(key: &str, text: &[char], cursor: usize, count: usize) -> Option<usize> {
+    let mut pos = cursor;
+    match key {
+        "h" | "<Left>" => Some(pos.saturating_sub(count).max(line_start(text, pos))),
+        "l" | "<Right>" => Some((pos + count).min(line_end(text, pos))),
+        "0" | "<Home>" => Some(line_start(text, pos)),
+        "$" | "<End>" => Some(line_end(text, pos)),
+        "G" => Some(line_start(text, text.len())),
+        "w" => {
+            for _ in 0..count {
+                pos = next_word_start(text, pos);
+            }
+            Some(pos)
+        }
+        "b" => {
+            for _ in 0..count {
+                pos = prev_word_start(text, pos);
+            }
+            Some(pos)
+        }
+        "j" | "<Down>" => {
+            for _ in 0..count {
+                pos = vertical_motion(text, pos, true);
+            }
+            Some(pos)
+        }
+        "k" | "<Up>" => {
+            for _ in 0..count {
+                pos = vertical_motion(text, pos, false);
+            }
+            Some(pos)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ModalEngine, Mode, TextEdit};
+
+    #[test]
+    fn counts_and_operators() {
+        let mut engine = ModalEngine::new();
+        let content = "one two three four";
+
+        engine.handle_key("2", content, 0);
+        engine.handle_key("d", content, 0);
+        let output = engine.handle_key("w", content, 0);
+
+        assert_eq!(
+            output.edits,
+            vec![TextEdit {
+                start: 0,
+                end: 8,
+                text: String::new()
+            }]
+        );
+        assert_eq!(engine.get_register('"').unwrap().content, "one two ");
+    }
+
+    #[test]
+    fn linewise_yank_and_paste() {
+        let mut engine = ModalEngine::new();
+        let content = "first\nsecond";
+
+        engine.handle_key("\"", content, 0);
+        engine.handle_key("a", content, 0);
+        engine.handle_key("y", content, 0);
+        engine.handle_key("y", content, 0);
+
+        assert_eq!(engine.get_register('a').unwrap().content, "first\n");
+
+        let output = engine.handle_key("p", content, 0);
+
+        assert_eq!(output.cursor, 6);
+        assert_eq!(output.edits[0].start, 6);
+        assert_eq!(output.edits[0].text, "first\n");
+    }
+
+    #[test]
+    fn insert_mode() {
+        let mut engine = ModalEngine::new();
+
+        let output = engine.handle_key("A", "abc", 0);
+        assert_eq!(output.mode, Mode::Insert);
+        assert_eq!(output.cursor, 3);
+
+        let output = engine.handle_key("d", "abc", 3);
+        assert_eq!(output.edits[0].text, "d");
+
+        let output = engine.handle_key("<Esc>", "abcd", 4);
+        assert_eq!(output.mode, Mode::Normal);
+        assert_eq!(output.cursor, 3);
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::search::SearchSource;
+
+/// How many lines are kept per buffer
+const MAX_LINES: usize = 5000;
+
+/// Remove the escape sequences (colors, cursor movements...) of terminal output
+fn strip_escape_sequences(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.peek() == Some(&'[') {
+                chars.next();
+                // Skip until the final byte of the sequence
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            } else {
+                chars.next();
+            }
+        } else if c != '\r' {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+/// Latest lines of output of a terminal or an output channel
+#[derive(Clone, Debug, Default)]
+pub struct OutputBuffer {
+    lines: VecDeque<String>,
+    /// Last line, not finished yet
+    current_line: String,
+}
+
+impl OutputBuffer {
+    pub fn push(&mut self, data: &str) {
+        let data = strip_escape_sequences(data);
+        let mut parts = data.split('\n');
+
+        if let Some(first) = parts.next() {
+            self.current_line.push_str(first);
+        }
+
+        for part in parts {
+            let line = std::mem::replace(&mut self.current_line, part.to_owned());
+            self.lines.push_back(line);
+            if self.lines.len() > MAX_LINES {
+                self.lines.pop_front();
+            }
+        }
+    }
+
+    /// Return all the lines, including the unfinished one
+    pub fn get_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.lines.iter().cloned().collect();
+        if !self.current_line.is_empty() {
+            lines.push(self.current_line.clone());
+        }
+        lines
+    }
+
+    /// Bytes held by the lines
+    pub fn get_size(&self) -> usize {
+        self.lines.iter().map(String::len).sum::<usize>() + self.current_line.len()
+    }
+}
+
+/// Output of terminals and extension output channels, kept so it can be searched
+#[derive(Clone, Debug, Default)]
+pub struct OutputBuffers {
+    buffers: HashMap<SearchSource, OutputBuffer>,
+}
+
+impl OutputBuffers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, source: SearchSource, data: &str) {
+        self.buffers.entry(source).or_default().push(data);
+    }
+
+    pub fn remove(&mut self, source: &SearchSource) {
+        self.buffers.remove(source);
+    }
+
+    /// Return a copy of the lines of every buffer
+    pub fn snapshot(&self) -> Vec<(SearchSource, Vec<String>)> {
+        self.buffers
+            .iter()
+            .map(|(source, buffer)| (source.clone(), buffer.get_lines()))
+            .collect()
+    }
+
+    /// Bytes held by all the buffers
+    pub fn get_size(&self) -> usize {
+        self.buffers.values().map(OutputBuffer::get_size).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OutputBuffer;
+
+    #[test]
+    fn split_output_in_lines() {
+        let mut buffer = OutputBuffer::default();
+        buffer.push("\x1b[32mcompiling\x1b[0m crate\r\nerr");
+        buffer.push("or: something failed\r\n$ ");
+
+        assert_eq!(
+            buffer.get_lines(),
+            vec![
+                "compiling crate".to_string(),
+                "error: something failed".to_string(),
+                "$ ".to_string()
+            ]
+        );
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+use uuid::Uuid;
+
+use crate::messaging::ClientMessages;
+use crate::search::CancellationToken;
+
+/// Reports sent closer than this are not forwarded to the clients, except the last one
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Progress errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ProgressErrors {
+    ProgressNotFound,
+    NotCancellable,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressKind {
+    Indeterminate,
+    /// Measured in units of work, e.g files indexed
+    Determinate {
+        total: u64,
+    },
+}
+
+/// A long-running operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProgressInfo {
+    pub id: String,
+    pub title: String,
+    /// The extension that started it, `None` for the Core
+    pub owner: Option<String>,
+    pub kind: ProgressKind,
+    pub done: u64,
+    pub message: Option<String>,
+    pub cancellable: bool,
+}
+
+/// Changes in a progress, sent to the clients
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ProgressUpdate {
+    Started(ProgressInfo),
+    Reported {
+        id: String,
+        done: u64,
+        message: Option<String>,
+    },
+    Finished {
+        id: String,
+    },
+}
+
+struct TrackedProgress {
+    info: ProgressInfo,
+    cancellation: CancellationToken,
+}
+
+/// Running progresses of a State, shared between the manager and the extension clients
+#[derive(Clone, Default)]
+pub struct ProgressRegistry(Arc<RwLock<HashMap<String, TrackedProgress>>>);
+
+impl ProgressRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start tracking an operation, the clients are told about it right away
+    ///
+    /// # Arguments
+    ///
+    /// * `title`         - What's being done, e.g `Indexing`
+    /// * `kind`          - If the amount of work is known
+    /// * `cancellable`   - If the clients can cancel it
+    /// * `owner`         - Extension that started it
+    /// * `sender`        - Where to send the updates
+    /// * `state_id`      - The State it belongs to
+    ///
+    pub async fn start(
+        &self,
+        title: &str,
+        kind: ProgressKind,
+        cancellable: bool,
+        owner: Option<&str>,
+        sender: Sender<ClientMessages>,
+        state_id: u8,
+    ) -> ProgressHandle {
+        let info = ProgressInfo {
+            id: Uuid::new_v4().to_string(),
+            title: title.to_owned(),
+            owner: owner.map(str::to_owned),
+            kind,
+            done: 0,
+            message: None,
+            cancellable,
+        };
+        let cancellation = CancellationToken::new();
+
+        self.0.write().unwrap().insert(
+            info.id.clone(),
+            TrackedProgress {
+                info: info.clone(),
+                cancellation: cancellation.clone(),
+            },
+        );
+
+        sender
+            .send(ClientMessages::ProgressUpdate {
+                state_id,
+                update: ProgressUpdate::Started(info.clone()),
+            })
+            .await
+            .ok();
+
+        ProgressHandle {
+            id: info.id,
+            kind,
+            state_id,
+            sender,
+            registry: self.clone(),
+            cancellation,
+            last_report: None,
+            finished: false,
+        }
+    }
+
+    /// Return all the running progresses
+    pub fn get_all(&self) -> Vec<ProgressInfo> {
+        self.0
+            .read()
+            .unwrap()
+            .values()
+            .map(|progress| progress.info.clone())
+            .collect()
+    }
+
+    /// Ask the owner of a progress to stop, it's finished by the owner
+    pub fn cancel(&self, id: &str) -> Result<(), ProgressErrors> {
+        let progresses = self.0.read().unwrap();
+        let progress = progresses.get(id).ok_or(ProgressErrors::ProgressNotFound)?;
+        if !progress.info.cancellable {
+            return Err(ProgressErrors::NotCancellable);
+        }
+        progress.cancellation.cancel();
+        Ok(())
+    }
+
+    /// Cancel all the progresses of an extension, e.g when it's unloaded
+    pub fn cancel_owner(&self, extension_id: &str) {
+        for progress in self.0.read().unwrap().values() {
+            if progress.info.owner.as_deref() == Some(extension_id) {
+                progress.cancellation.cancel();
+            }
+        }
+    }
+
+    fn report(&self, id: &str, done: u64, message: Option<String>) {
+        if let Some(progress) = self.0.write().unwrap().get_mut(id) {
+            progress.info.done = done;
+            progress.info.message = message;
+        }
+    }
+
+    fn remove(&self, id: &str) {
+        self.0.write().unwrap().remove(id);
+    }
+}
+
+/// Owned by whoever runs the operation, the progress is finished when it's dropped
+pub struct ProgressHandle {
+    id: String,
+    kind: ProgressKind,
+    state_id: u8,
+    sender: Sender<ClientMessages>,
+    registry: ProgressRegistry,
+    cancellation: CancellationToken,
+    last_report: Option<Instant>,
+    finished: bool,
+}
+
+impl ProgressHandle {
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    /// Check if a client asked to cancel the operation
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Token cancelled when a client cancels the operation, e.g to pass it to a search
+    pub fn get_cancellation(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// Use an existing token, e.g the one of a search so cancelling either one cancels both
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        if let Some(progress) = self.registry.0.write().unwrap().get_mut(&self.id) {
+            progress.cancellation = cancellation.clone();
+        }
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Tell how much is done, reports are throttled before reaching the clients
+    pub async fn report(&mut self, done: u64, message: Option<&str>) {
+        let message = message.map(str::to_owned);
+        self.registry.report(&self.id, done, message.clone());
+
+        let is_last = matches!(self.kind, ProgressKind::Determinate { total } if done >= total);
+        let is_throttled = self
+            .last_report
+            .map(|last_report| last_report.elapsed() < REPORT_INTERVAL)
+            .unwrap_or_default();
+        if is_throttled && !is_last {
+            return;
+        }
+        self.last_report = Some(Instant::now());
+
+        self.sender
+            .send(ClientMessages::ProgressUpdate {
+                state_id: self.state_id,
+                update: ProgressUpdate::Reported {
+                    id: self.id.clone(),
+                    done,
+                    message,
+                },
+            })
+            .await
+            .ok();
+    }
+
+    pub async fn finish(mut self) {
+        self.finished = true;
+        self.registry.remove(&self.id);
+        self.sender.send(self.get_finished_message()).await.ok();
+    }
+
+    fn get_finished_message(&self) -> ClientMessages {
+        ClientMessages::ProgressUpdate {
+            state_id: self.state_id,
+            update: ProgressUpdate::Finished {
+                id: self.id.clone(),
+            },
+        }
+    }
+}
+
+impl Drop for ProgressHandle {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        // The owner is gone without finishing it, e.g it failed
+        self.registry.remove(&self.id);
+        let message = self.get_finished_message();
+        let sender = self.sender.clone();
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                sender.send(message).await.ok();
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::channel;
+
+    use super::{ProgressErrors, ProgressKind, ProgressRegistry, ProgressUpdate};
+    use crate::messaging::ClientMessages;
+
+    #[tokio::test]
+    async fn progress_lifecycle() {
+        let (sender, mut receiver) = channel(10);
+        let registry = ProgressRegistry::new();
+
+        let mut handle = registry
+            .start(
+                "Indexing",
+                ProgressKind::Determinate { total: 2 },
+                true,
+                Some("indexer"),
+                sender.clone(),
+                1,
+            )
+            .await;
+        let other = registry
+            .start(
+                "Installing",
+                ProgressKind::Indeterminate,
+                false,
+                None,
+                sender,
+                1,
+            )
+            .await;
+        assert_eq!(registry.get_all().len(), 2);
+
+        handle.report(1, Some("main.rs")).await;
+        // Throttled
+        handle.report(1, Some("lib.rs")).await;
+        // Last one
+        handle.report(2, None).await;
+
+        assert_eq!(
+            registry.cancel(other.get_id()),
+            Err(ProgressErrors::NotCancellable)
+        );
+        registry.cancel_owner("indexer");
+        assert!(handle.is_cancelled());
+        assert!(!other.is_cancelled());
+
+        handle.finish().await;
+        drop(other);
+        assert!(registry.get_all().is_empty());
+
+        let mut updates = Vec::new();
+        while updates.len() < 6 {
+            if let Some(ClientMessages::ProgressUpdate { update, .. }) = receiver.recv().await {
+                updates.push(update);
+            }
+        }
+        assert!(matches!(updates[4], ProgressUpdate::Finished { .. }));
+        assert!(receiver.try_recv().is_err());
+    }
+}
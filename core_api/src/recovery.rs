@@ -0,0 +1,212 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::blobs::{BlobId, BlobStore};
+use crate::states::views::TabData;
+use crate::states::StateData;
+
+/// Unsaved content of a file, kept so it can be restored in the next session
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Draft {
+    pub filesystem: String,
+    pub path: String,
+    pub content: String,
+}
+
+/// Owner of the blobs referenced by the drafts
+const DRAFTS_OWNER: &str = "drafts";
+
+/// A draft as it's saved, its content is kept in the blob store
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct StoredDraft {
+    filesystem: String,
+    path: String,
+    blob: BlobId,
+}
+
+/// What was restored after an unclean shutdown
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecoveredSession {
+    /// Tabs from the last persisted state
+    pub tabs: Vec<TabData>,
+    /// Hot-exit drafts
+    pub drafts: Vec<Draft>,
+    /// Data of the State the last time it was autosaved, it can be newer than the persisted one
+    #[serde(default)]
+    pub data: Option<StateData>,
+}
+
+/// Detects unclean shutdowns and keeps the drafts of a session
+///
+/// A lock file is created when the session starts and only removed on a clean exit,
+/// so finding it when starting means the previous session crashed.
+pub struct SessionRecovery {
+    dir: PathBuf,
+    lock_path: PathBuf,
+    drafts_path: PathBuf,
+    unclean_shutdown: bool,
+    blobs: BlobStore,
+}
+
+impl SessionRecovery {
+    /// Start a session in the given directory
+    pub fn start(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        let lock_path = dir.join("session.lock");
+        let unclean_shutdown = lock_path.exists();
+
+        if unclean_shutdown {
+            warn!("Previous session didn't exit cleanly");
+        }
+
+        fs::write(&lock_path, std::process::id().to_string())?;
+
+        let drafts_path = dir.join("drafts.json");
+        let blobs = BlobStore::open(dir.join("blobs"))?;
+
+        Ok(Self {
+            dir,
+            lock_path,
+            drafts_path,
+            unclean_shutdown,
+            blobs,
+        })
+    }
+
+    /// Store where the contents of the drafts are kept, other subsystems can share it
+    pub fn get_blob_store(&self) -> BlobStore {
+        self.blobs.clone()
+    }
+
+    /// If the previous session didn't exit cleanly
+    pub fn was_unclean_shutdown(&self) -> bool {
+        self.unclean_shutdown
+    }
+
+    pub fn load_drafts(&self) -> Vec<Draft> {
+        let content = fs::read_to_string(&self.drafts_path).unwrap_or_default();
+
+        // Drafts saved before the blob store kept their content inline
+        if let Ok(drafts) = serde_json::from_str::<Vec<Draft>>(&content) {
+            return drafts;
+        }
+
+        serde_json::from_str::<Vec<StoredDraft>>(&content)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|draft| {
+                Some(Draft {
+                    content: self.blobs.get_text(&draft.blob)?,
+                    filesystem: draft.filesystem,
+                    path: draft.path,
+                })
+            })
+            .collect()
+    }
+
+    /// Replace the saved drafts, the contents nobody else references are removed
+    pub fn save_drafts(&self, drafts: &[Draft]) {
+        self.blobs.release_owner(DRAFTS_OWNER);
+
+        let mut stored = Vec::new();
+        for draft in drafts {
+            match self.blobs.put(DRAFTS_OWNER, draft.content.as_bytes()) {
+                Ok(blob) => stored.push(StoredDraft {
+                    filesystem: draft.filesystem.clone(),
+                    path: draft.path.clone(),
+                    blob,
+                }),
+                Err(err) => warn!("Could not save the draft of {}, error: {}", draft.path, err),
+            }
+        }
+
+        let content = serde_json::to_string(&stored).unwrap();
+        if let Err(err) = fs::write(&self.drafts_path, content) {
+            warn!("Could not save the drafts, error: {}", err);
+        }
+        self.blobs.collect_garbage();
+    }
+
+    fn get_state_path(&self, state_id: u8) -> PathBuf {
+        self.dir.join(format!("state_{}.json", state_id))
+    }
+
+    /// Data of a State autosaved in a session that didn't exit cleanly
+    pub fn load_state(&self, state_id: u8) -> Option<StateData> {
+        fs::read_to_string(self.get_state_path(state_id))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+    }
+
+    /// Keep the current data of a State in case the session crashes
+    pub fn save_state(&self, data: &StateData) {
+        let content = serde_json::to_string(data).unwrap();
+        if let Err(err) = fs::write(self.get_state_path(data.id), content) {
+            warn!(
+                "Could not autosave the State by id <{}>, error: {}",
+                data.id, err
+            );
+        }
+    }
+
+    /// Forget the autosaved data of a State
+    pub fn discard_state(&self, state_id: u8) {
+        fs::remove_file(self.get_state_path(state_id)).ok();
+    }
+
+    /// Mark the session as cleanly finished, the autosaved States are not needed anymore
+    pub fn finish(&self) {
+        if let Ok(entries) = fs::read_dir(&self.dir) {
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                if name.to_string_lossy().starts_with("state_") {
+                    fs::remove_file(entry.path()).ok();
+                }
+            }
+        }
+        fs::remove_file(&self.lock_path).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Draft, SessionRecovery};
+    use crate::states::StateData;
+
+    #[test]
+    fn detect_unclean_shutdown() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+
+        let session = SessionRecovery::start(dir.to_path_buf()).unwrap();
+        assert!(!session.was_unclean_shutdown());
+        session.save_drafts(&[Draft {
+            filesystem: "local".to_string(),
+            path: "/readme.md".to_string(),
+            content: "# Hello".to_string(),
+        }]);
+
+        session.save_state(&StateData {
+            id: 3,
+            ..StateData::default()
+        });
+
+        // The session was never finished
+        let session = SessionRecovery::start(dir.to_path_buf()).unwrap();
+        assert!(session.was_unclean_shutdown());
+        assert_eq!(session.load_drafts()[0].content, "# Hello");
+        assert_eq!(session.get_blob_store().get_stats().blobs, 1);
+        assert_eq!(session.load_state(3).map(|data| data.id), Some(3));
+        session.finish();
+        assert!(session.load_state(3).is_none());
+
+        let session = SessionRecovery::start(dir.to_path_buf()).unwrap();
+        assert!(!session.was_unclean_shutdown());
+        session.finish();
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::documents::{DocumentEdit, Position, TextRange};
+use crate::filesystems::Filesystem;
+use crate::search::{CancellationToken, Search, SearchMatch, SearchOptions};
+use crate::Errors;
+
+/// Refactoring errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum RefactorErrors {
+    /// The names must be non-empty identifiers
+    InvalidName,
+    /// A file changed since the preview was made
+    FileChanged,
+    /// A file has unsaved changes in an open document
+    UnsavedChanges,
+}
+
+/// Check if the character can be part of an identifier
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(is_identifier_char)
+}
+
+/// Check if the match is a whole token and not a part of a longer identifier
+fn is_whole_token(found: &SearchMatch) -> bool {
+    let chars = found.line_content.chars().collect::<Vec<char>>();
+    let before = found.start.checked_sub(1).and_then(|i| chars.get(i));
+    let after = chars.get(found.end);
+
+    !before.copied().is_some_and(is_identifier_char)
+        && !after.copied().is_some_and(is_identifier_char)
+}
+
+/// Occurrences of the renamed symbol in a file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileRename {
+    pub path: String,
+    pub matches: Vec<SearchMatch>,
+}
+
+/// Everything a rename will change, to be reviewed before applying it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RenamePreview {
+    pub old_name: String,
+    pub new_name: String,
+    pub files: Vec<FileRename>,
+}
+
+impl RenamePreview {
+    /// Find all the occurrences of a symbol in a project
+    ///
+    /// # Arguments
+    ///
+    /// * `filesystem`   - Filesystem of the project
+    /// * `old_name`     - The symbol to rename
+    /// * `new_name`     - The new name
+    /// * `options`      - Where to look, the query options (regex and case) are ignored
+    ///
+    pub async fn new(
+        filesystem: Arc<Mutex<Box<dyn Filesystem + Send>>>,
+        old_name: &str,
+        new_name: &str,
+        options: SearchOptions,
+    ) -> Result<Self, Errors> {
+        if !is_identifier(old_name) || !is_identifier(new_name) {
+            return Err(Errors::Refactor(RefactorErrors::InvalidName));
+        }
+
+        let options = SearchOptions {
+            regex: false,
+            case_sensitive: true,
+            max_results: None,
+            include_terminals: false,
+            include_output_channels: false,
+            ..options
+        };
+
+        let matches = Search::new(old_name, options)?
+            .collect(filesystem, CancellationToken::new())
+            .await;
+
+        let mut files = BTreeMap::<String, Vec<SearchMatch>>::new();
+        for found in matches.into_iter().filter(is_whole_token) {
+            files.entry(found.path.clone()).or_default().push(found);
+        }
+
+        Ok(Self {
+            old_name: old_name.to_owned(),
+            new_name: new_name.to_owned(),
+            files: files
+                .into_iter()
+                .map(|(path, matches)| FileRename { path, matches })
+                .collect(),
+        })
+    }
+
+    /// The rename of a file as edits, from the last occurrence to the first so they can be applied in order
+    pub fn get_document_edits(&self, file: &FileRename) -> Vec<DocumentEdit> {
+        let mut matches = file.matches.iter().collect::<Vec<&SearchMatch>>();
+        matches.sort_by_key(|found| std::cmp::Reverse((found.line, found.start)));
+        matches
+            .into_iter()
+            .map(|found| DocumentEdit {
+                range: TextRange::new(
+                    Position::new(found.line, found.start),
+                    Position::new(found.line, found.end),
+                ),
+                text: self.new_name.clone(),
+            })
+            .collect()
+    }
+
+    /// Apply the rename to the content of a file, fails if it changed since the preview
+    pub fn apply_to_content(&self, file: &FileRename, content: &str) -> Result<String, Errors> {
+        let mut lines = content
+            .split_inclusive('\n')
+            .map(str::to_owned)
+            .collect::<Vec<String>>();
+
+        let mut matches_by_line = BTreeMap::<usize, Vec<&SearchMatch>>::new();
+        for found in &file.matches {
+            matches_by_line.entry(found.line).or_default().push(found);
+        }
+
+        for (line, mut matches) in matches_by_line {
+            let line_content = lines
+                .get_mut(line)
+                .ok_or(Errors::Refactor(RefactorErrors::FileChanged))?;
+            let text = line_content.trim_end_matches(['\n', '\r']);
+            let ending = line_content[text.len()..].to_owned();
+
+            if matches.iter().any(|found| found.line_content != text) {
+                return Err(Errors::Refactor(RefactorErrors::FileChanged));
+            }
+
+            // Replace from the end so the offsets of the previous matches are still valid
+            matches.sort_by_key(|found| std::cmp::Reverse(found.start));
+            let mut chars = text.chars().collect::<Vec<char>>();
+            for found in matches {
+                chars.splice(found.start..found.end, self.new_name.chars());
+            }
+
+            *line_content = chars.into_iter().collect::<String>() + &ending;
+        }
+
+        Ok(lines.concat())
+    }
+
+    /// Write the rename into all the files, if any of them can't be written the others are restored
+    pub async fn apply(
+        &self,
+        filesystem: Arc<Mutex<Box<dyn Filesystem + Send>>>,
+    ) -> Result<(), Errors> {
+        let filesystem = filesystem.lock().await;
+
+        // Prepare all the changes before writing anything
+        let mut changes = Vec::with_capacity(self.files.len());
+        for file in &self.files {
+            let original = filesystem.read_file_by_path(&file.path).await?.content;
+            let renamed = self.apply_to_content(file, &original)?;
+            changes.push((file.path.clone(), original, renamed));
+        }
+
+        for (i, (path, _, renamed)) in changes.iter().enumerate() {
+            if let Err(err) = filesystem.write_file_by_path(path, renamed).await {
+                for (path, original, _) in &changes[..i] {
+                    filesystem.write_file_by_path(path, original).await.ok();
+                }
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileRename, RenamePreview};
+    use crate::search::SearchMatch;
+
+    fn found(line: usize, start: usize, line_content: &str) -> SearchMatch {
+        SearchMatch {
+            source: Default::default(),
+            path: "/notes.txt".to_string(),
+            line,
+            start,
+            end: start + 3,
+            line_content: line_content.to_string(),
+        }
+    }
+
+    #[test]
+    fn rename_tokens() {
+        assert!(super::is_whole_token(&found(0, 4, "let foo = 1;")));
+        assert!(!super::is_whole_token(&found(0, 4, "let food = 1;")));
+        assert!(!super::is_whole_token(&found(0, 5, "let _foo = 1;")));
+
+        let file = FileRename {
+            path: "/notes.txt".to_string(),
+            matches: vec![
+                found(0, 0, "foo(foo)"),
+                found(0, 4, "foo(foo)"),
+                found(2, 2, "  foo"),
+            ],
+        };
+        let preview = RenamePreview {
+            old_name: "foo".to_string(),
+            new_name: "ñame".to_string(),
+            files: vec![file.clone()],
+        };
+
+        assert_eq!(
+            preview
+                .apply_to_content(&file, "foo(foo)\r\nbar\r\n  foo")
+                .unwrap(),
+            "ñame(ñame)\r\nbar\r\n  ñame"
+        );
+        assert!(preview
+            .apply_to_content(&file, "bar(foo)\nbar\n  foo")
+            .is_err());
+    }
+}
@@ -0,0 +1,322 @@
+use std::process::Stdio;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::documents::{DocumentEdit, Position, TextRange};
+use crate::language_servers::LanguageServerErrors;
+
+/// How long a hook can run when it doesn't have it's own timeout
+pub const DEFAULT_HOOK_TIMEOUT_MS: u64 = 3000;
+
+/// Save hooks errors, these don't prevent the document from being saved
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum SaveHookErrors {
+    TimedOut,
+    LanguageServer(LanguageServerErrors),
+    FormatterFailed(String),
+    /// The hook returned edits that don't fit in the document
+    BadEdits,
+}
+
+/// What a hook does to the document before it's saved
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum SaveHookAction {
+    TrimTrailingWhitespace,
+    InsertFinalNewline,
+    /// Apply the `source.organizeImports` code action of the language server
+    OrganizeImports,
+    /// Pipe the document through a command, e.g `rustfmt --emit stdout`
+    Format {
+        command: String,
+        args: Vec<String>,
+    },
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// A step of the save pipeline of a language
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SaveHook {
+    pub id: String,
+    pub action: SaveHookAction,
+    /// In milliseconds
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+impl SaveHook {
+    pub fn new(id: &str, action: SaveHookAction) -> Self {
+        Self {
+            id: id.to_owned(),
+            action,
+            timeout: None,
+            enabled: true,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: u64) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout.unwrap_or(DEFAULT_HOOK_TIMEOUT_MS))
+    }
+}
+
+/// Options for a single save
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct SaveOptions {
+    /// Save the document as it is
+    #[serde(default)]
+    pub skip_all_hooks: bool,
+    /// IDs of the hooks to not run
+    #[serde(default)]
+    pub skip_hooks: Vec<String>,
+}
+
+impl SaveOptions {
+    pub fn should_run(&self, hook: &SaveHook) -> bool {
+        hook.enabled && !self.skip_all_hooks && !self.skip_hooks.contains(&hook.id)
+    }
+}
+
+/// Position right after the last character
+fn get_end_position(content: &str) -> Position {
+    let line = content.matches('\n').count();
+    let last_line = content.rsplit('\n').next().unwrap_or_default();
+    Position::new(line, last_line.chars().count())
+}
+
+/// Edits removing the whitespace at the end of every line
+pub fn trim_trailing_whitespace(content: &str) -> Vec<DocumentEdit> {
+    content
+        .split('\n')
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            let len = text.chars().count();
+            let trimmed = text.trim_end().chars().count();
+            if trimmed < len {
+                Some(DocumentEdit::delete(TextRange::new(
+                    Position::new(line, trimmed),
+                    Position::new(line, len),
+                )))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Edit adding a line break at the end, following the line endings already used
+pub fn insert_final_newline(content: &str) -> Option<DocumentEdit> {
+    if content.is_empty() || content.ends_with('\n') {
+        return None;
+    }
+
+    let line_ending = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    Some(DocumentEdit::insert(get_end_position(content), line_ending))
+}
+
+/// Edit replacing the changed part of the content, none if nothing changed
+pub fn replace_content(content: &str, new_content: &str) -> Option<DocumentEdit> {
+    DocumentEdit::between(content, new_content)
+}
+
+/// Run a formatter with the content in it's stdin, returns it's stdout
+pub async fn run_formatter(
+    command: &str,
+    args: &[String],
+    content: &str,
+) -> Result<String, SaveHookErrors> {
+    let mut process = Command::new(command)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Timed out formatters are dropped
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| SaveHookErrors::FormatterFailed(err.to_string()))?;
+
+    if let Some(mut stdin) = process.stdin.take() {
+        stdin
+            .write_all(content.as_bytes())
+            .await
+            .map_err(|err| SaveHookErrors::FormatterFailed(err.to_string()))?;
+    }
+
+    let output = process
+        .wait_with_output()
+        .await
+        .map_err(|err| SaveHookErrors::FormatterFailed(err.to_string()))?;
+
+    if output.status.success() {
+        String::from_utf8(output.stdout)
+            .map_err(|_| SaveHookErrors::FormatterFailed("Invalid UTF-8 output".to_owned()))
+    } else {
+        Err(SaveHookErrors::FormatterFailed(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ))
+    }
+}
+
+/// Params of a `textDocument/codeAction` request for only organizing the imports
+pub fn get_organize_imports_params(uri: &str, line_count: usize) -> Value {
+    json!({
+        "textDocument": { "uri": uri },
+        "range": {
+            "start": { "line": 0, "character": 0 },
+            "end": { "line": line_count, "character": 0 },
+        },
+        "context": { "diagnostics": [], "only": ["source.organizeImports"] },
+    })
+}
+
+fn parse_text_edit(edit: &Value) -> Option<DocumentEdit> {
+    let position = |pointer: &str| -> Option<Position> {
+        Some(Position::new(
+            edit.pointer(&format!("{}/line", pointer))?.as_u64()? as usize,
+            edit.pointer(&format!("{}/character", pointer))?.as_u64()? as usize,
+        ))
+    };
+
+    Some(DocumentEdit {
+        range: TextRange::new(position("/range/start")?, position("/range/end")?),
+        text: edit.get("newText")?.as_str()?.to_owned(),
+    })
+}
+
+/// Edits of a document in the code actions returned by a language server,
+/// positions are in UTF-16 code units and relative to the original content
+pub fn get_code_actions_edits(actions: &Value, uri: &str) -> Vec<DocumentEdit> {
+    let mut edits = Vec::new();
+
+    for action in actions.as_array().into_iter().flatten() {
+        let edit = match action.get("edit") {
+            Some(edit) => edit,
+            None => continue,
+        };
+
+        if let Some(changes) = edit.pointer("/changes").and_then(|c| c.get(uri)) {
+            edits.extend(
+                changes
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(parse_text_edit),
+            );
+        }
+
+        for change in edit
+            .get("documentChanges")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            if change.pointer("/textDocument/uri").and_then(Value::as_str) == Some(uri) {
+                edits.extend(
+                    change
+                        .get("edits")
+                        .and_then(Value::as_array)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(parse_text_edit),
+                );
+            }
+        }
+    }
+
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{
+        get_code_actions_edits, insert_final_newline, trim_trailing_whitespace, SaveHook,
+        SaveHookAction, SaveOptions,
+    };
+    use crate::documents::{Document, Position};
+
+    #[test]
+    fn text_hooks() {
+        let content = "let a = 1;  \r\nlet b = 2;\t\r\n\nlet c = 3; ";
+        let mut document = Document::new("local", "/main.ts", content);
+
+        let edits = trim_trailing_whitespace(content);
+        assert_eq!(edits.len(), 3);
+        document.apply_edits(1, &edits).unwrap();
+
+        let content = document.get_content();
+        let edit = insert_final_newline(&content).unwrap();
+        assert_eq!(edit.range.start, Position::new(3, 10));
+        document.apply_edits(2, &[edit]).unwrap();
+
+        assert_eq!(
+            document.get_content(),
+            "let a = 1;\r\nlet b = 2;\r\n\nlet c = 3;\r\n"
+        );
+        assert!(insert_final_newline(&document.get_content()).is_none());
+    }
+
+    #[test]
+    fn skip_hooks() {
+        let hook = SaveHook::new("trim", SaveHookAction::TrimTrailingWhitespace);
+        assert!(SaveOptions::default().should_run(&hook));
+        assert!(!SaveOptions {
+            skip_hooks: vec!["trim".to_owned()],
+            ..SaveOptions::default()
+        }
+        .should_run(&hook));
+        assert!(!SaveOptions {
+            skip_all_hooks: true,
+            ..SaveOptions::default()
+        }
+        .should_run(&hook));
+    }
+
+    #[test]
+    fn code_actions_edits() {
+        let actions = json!([{
+            "title": "Organize Imports",
+            "kind": "source.organizeImports",
+            "edit": {
+                "changes": {
+                    "file:///main.ts": [{
+                        "range": {
+                            "start": { "line": 0, "character": 0 },
+                            "end": { "line": 1, "character": 0 }
+                        },
+                        "newText": ""
+                    }],
+                    "file:///other.ts": [{
+                        "range": {
+                            "start": { "line": 0, "character": 0 },
+                            "end": { "line": 0, "character": 0 }
+                        },
+                        "newText": "a"
+                    }]
+                }
+            }
+        }]);
+
+        let edits = get_code_actions_edits(&actions, "file:///main.ts");
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range.end, Position::new(1, 0));
+    }
+}
@@ -0,0 +1,592 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use globset::{Glob, GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::Mutex;
+
+use crate::filesystems::Filesystem;
+use crate::messaging::{ClientMessages, ServerMessages};
+use crate::Errors;
+
+/// How many matches are sent to the client at once
+const BATCH_SIZE: usize = 50;
+
+/// Search errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum SearchErrors {
+    InvalidPattern,
+    InvalidGlob,
+    SearchNotFound,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Options of a search
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Directory where to search
+    pub root: String,
+    /// Treat the query as a regular expression
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Only search files matching these globs (relative to the root)
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Skip files and folders matching these globs (relative to the root)
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Skip the files ignored by the .gitignore files
+    #[serde(default = "default_true")]
+    pub respect_gitignore: bool,
+    /// Stop once this many matches were found
+    #[serde(default)]
+    pub max_results: Option<usize>,
+    /// Also search the scrollback of the terminals
+    #[serde(default)]
+    pub include_terminals: bool,
+    /// Also search the output channels of the extensions
+    #[serde(default)]
+    pub include_output_channels: bool,
+}
+
+impl SearchOptions {
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: root.to_owned(),
+            regex: false,
+            case_sensitive: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            respect_gitignore: true,
+            max_results: None,
+            include_terminals: false,
+            include_output_channels: false,
+        }
+    }
+}
+
+/// Where a match was found
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[serde(tag = "source_type")]
+pub enum SearchSource {
+    #[default]
+    File,
+    Terminal {
+        terminal_shell_id: String,
+    },
+    OutputChannel {
+        channel_id: String,
+    },
+}
+
+/// A match found in a file, a terminal or an output channel
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SearchMatch {
+    #[serde(default)]
+    pub source: SearchSource,
+    /// Path of the file, or ID of the terminal or output channel
+    pub path: String,
+    /// Line number, starting from 0
+    pub line: usize,
+    /// Character where the match starts in the line
+    pub start: usize,
+    /// Character where the match ends in the line
+    pub end: usize,
+    pub line_content: String,
+}
+
+/// Allows aborting a running search, it's also cancelled once the search finishes
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// A rule of a .gitignore file
+pub(crate) struct IgnoreRule {
+    /// Directory containing the .gitignore
+    base: String,
+    matcher: GlobMatcher,
+    negated: bool,
+    only_dirs: bool,
+}
+
+impl IgnoreRule {
+    pub(crate) fn parse(base: &str, line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, line),
+        };
+
+        let (only_dirs, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+
+        // Patterns with a separator are relative to the .gitignore, the rest can match at any level
+        let glob = if pattern.contains('/') {
+            pattern.trim_start_matches('/').to_owned()
+        } else {
+            format!("**/{}", pattern)
+        };
+
+        let matcher = GlobBuilder::new(&glob)
+            .literal_separator(true)
+            .build()
+            .ok()?
+            .compile_matcher();
+
+        Some(Self {
+            base: base.to_owned(),
+            matcher,
+            negated,
+            only_dirs,
+        })
+    }
+}
+
+/// Return the path relative to the given directory using `/` as separator, None if it's not in it.
+/// They are compared component by component, so `/project` doesn't contain `/project-old`
+pub(crate) fn strip_base(base: &str, path: &str) -> Option<String> {
+    let path = path.replace('\\', "/");
+    let base = base.replace('\\', "/");
+    let is_named = |component: &&str| !component.is_empty() && *component != ".";
+    let mut components = path.split('/').filter(is_named);
+    for base_component in base.split('/').filter(is_named) {
+        if components.next() != Some(base_component) {
+            return None;
+        }
+    }
+    Some(components.collect::<Vec<&str>>().join("/"))
+}
+
+/// Return the path relative to the given directory, or the whole path if it's not in it
+pub(crate) fn relative_path(base: &str, path: &str) -> String {
+    strip_base(base, path)
+        .unwrap_or_else(|| path.replace('\\', "/").trim_start_matches('/').to_owned())
+}
+
+pub(crate) fn is_ignored(rules: &[IgnoreRule], path: &str, is_dir: bool) -> bool {
+    let mut ignored = false;
+    for rule in rules {
+        if rule.only_dirs && !is_dir {
+            continue;
+        }
+        // Rules only apply inside the directory of their .gitignore
+        let relative = match strip_base(&rule.base, path) {
+            Some(relative) => relative,
+            None => continue,
+        };
+        if rule.matcher.is_match(relative) {
+            ignored = !rule.negated;
+        }
+    }
+    ignored
+}
+
+pub(crate) fn build_globset(globs: &[String]) -> Result<GlobSet, Errors> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        let glob = Glob::new(glob).map_err(|_| Errors::Search(SearchErrors::InvalidGlob))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|_| Errors::Search(SearchErrors::InvalidGlob))
+}
+
+/// Sends the matches of a search in batches, or keeps all of them if there is no sender
+struct ResultsSender {
+    sender: Option<Sender<ClientMessages>>,
+    state_id: u8,
+    search_id: String,
+    batch: Vec<SearchMatch>,
+    found: usize,
+    max_results: Option<usize>,
+}
+
+impl ResultsSender {
+    async fn send(&mut self, done: bool) {
+        let sender = match &self.sender {
+            Some(sender) => sender,
+            None => return,
+        };
+        sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::SearchResults {
+                    state_id: self.state_id,
+                    search_id: self.search_id.clone(),
+                    matches: std::mem::take(&mut self.batch),
+                    done,
+                },
+            ))
+            .await
+            .ok();
+    }
+
+    /// Add a match, returns true once the limit of matches is reached
+    async fn push(&mut self, found_match: SearchMatch) -> bool {
+        self.batch.push(found_match);
+        self.found += 1;
+
+        if self.sender.is_some() && self.batch.len() == BATCH_SIZE {
+            self.send(false).await;
+        }
+
+        Some(self.found) == self.max_results
+    }
+
+    /// Send the remaining matches
+    async fn finish(mut self) {
+        self.send(true).await;
+    }
+}
+
+/// A search ready to be run over a filesystem
+pub struct Search {
+    pattern: Regex,
+    include: GlobSet,
+    exclude: GlobSet,
+    options: SearchOptions,
+    buffers: Vec<(SearchSource, Vec<String>)>,
+}
+
+impl Search {
+    /// Validate the query and the options
+    pub fn new(query: &str, options: SearchOptions) -> Result<Self, Errors> {
+        let query = if options.regex {
+            query.to_owned()
+        } else {
+            regex::escape(query)
+        };
+
+        let pattern = RegexBuilder::new(&query)
+            .case_insensitive(!options.case_sensitive)
+            .build()
+            .map_err(|_| Errors::Search(SearchErrors::InvalidPattern))?;
+
+        Ok(Self {
+            pattern,
+            include: build_globset(&options.include)?,
+            exclude: build_globset(&options.exclude)?,
+            options,
+            buffers: Vec::new(),
+        })
+    }
+
+    /// Also search in the output of terminals and output channels, only the enabled sources are kept
+    pub fn with_buffers(mut self, buffers: Vec<(SearchSource, Vec<String>)>) -> Self {
+        self.buffers = buffers
+            .into_iter()
+            .filter(|(source, _)| match source {
+                SearchSource::File => false,
+                SearchSource::Terminal { .. } => self.options.include_terminals,
+                SearchSource::OutputChannel { .. } => self.options.include_output_channels,
+            })
+            .collect();
+        self
+    }
+
+    /// Find the matches in some lines
+    fn find_matches<'a>(
+        &self,
+        source: &SearchSource,
+        path: &str,
+        lines: impl Iterator<Item = &'a str>,
+    ) -> Vec<SearchMatch> {
+        let mut matches = Vec::new();
+        for (line, line_content) in lines.enumerate() {
+            for found in self.pattern.find_iter(line_content) {
+                let start = line_content[..found.start()].chars().count();
+                matches.push(SearchMatch {
+                    source: source.clone(),
+                    path: path.to_owned(),
+                    line,
+                    start,
+                    end: start + found.as_str().chars().count(),
+                    line_content: line_content.to_owned(),
+                });
+            }
+        }
+        matches
+    }
+
+    /// Walk the filesystem and send the matches in batches until it's done or cancelled
+    pub async fn run(
+        self,
+        filesystem: Arc<Mutex<Box<dyn Filesystem + Send>>>,
+        token: CancellationToken,
+        sender: Sender<ClientMessages>,
+        state_id: u8,
+        search_id: String,
+    ) {
+        let mut results = ResultsSender {
+            sender: Some(sender),
+            state_id,
+            search_id,
+            batch: Vec::new(),
+            found: 0,
+            max_results: self.options.max_results,
+        };
+
+        self.walk(filesystem, &token, &mut results).await;
+
+        if !token.is_cancelled() {
+            results.finish().await;
+        }
+
+        token.cancel();
+    }
+
+    /// Walk the filesystem and return all the matches at once
+    pub async fn collect(
+        self,
+        filesystem: Arc<Mutex<Box<dyn Filesystem + Send>>>,
+        token: CancellationToken,
+    ) -> Vec<SearchMatch> {
+        let mut results = ResultsSender {
+            sender: None,
+            state_id: 0,
+            search_id: String::new(),
+            batch: Vec::new(),
+            found: 0,
+            max_results: self.options.max_results,
+        };
+
+        self.walk(filesystem, &token, &mut results).await;
+
+        results.batch
+    }
+
+    async fn walk(
+        &self,
+        filesystem: Arc<Mutex<Box<dyn Filesystem + Send>>>,
+        token: &CancellationToken,
+        results: &mut ResultsSender,
+    ) {
+        let root = self.options.root.clone();
+        let mut rules = Vec::new();
+        let mut pending_dirs = vec![root.clone()];
+
+        // Output buffers are already in memory, so they are searched first
+        'buffers: for (source, lines) in &self.buffers {
+            let path = match source {
+                SearchSource::Terminal { terminal_shell_id } => terminal_shell_id,
+                SearchSource::OutputChannel { channel_id } => channel_id,
+                SearchSource::File => continue,
+            };
+            for found_match in self.find_matches(source, path, lines.iter().map(String::as_str)) {
+                if results.push(found_match).await {
+                    pending_dirs.clear();
+                    break 'buffers;
+                }
+            }
+        }
+
+        'walk: while let Some(dir) = pending_dirs.pop() {
+            if token.is_cancelled() {
+                break;
+            }
+
+            if self.options.respect_gitignore {
+                let gitignore = format!("{}/.gitignore", dir.trim_end_matches(['/', '\\']));
+                let gitignore = filesystem.lock().await.read_file_by_path(&gitignore).await;
+                if let Ok(gitignore) = gitignore {
+                    rules.extend(
+                        gitignore
+                            .content
+                            .lines()
+                            .filter_map(|line| IgnoreRule::parse(&dir, line)),
+                    );
+                }
+            }
+
+            let items = filesystem.lock().await.list_dir_by_path(&dir).await;
+            let items = if let Ok(items) = items {
+                items
+            } else {
+                continue;
+            };
+
+            for item in items {
+                if token.is_cancelled() {
+                    break 'walk;
+                }
+
+                let relative = relative_path(&root, &item.path);
+
+                if self.exclude.is_match(&relative) {
+                    continue;
+                }
+
+                if self.options.respect_gitignore
+                    && (item.name == ".git" || is_ignored(&rules, &item.path, !item.is_file))
+                {
+                    continue;
+                }
+
+                if !item.is_file {
+                    pending_dirs.push(item.path);
+                    continue;
+                }
+
+                if !self.options.include.is_empty() && !self.include.is_match(&relative) {
+                    continue;
+                }
+
+                let file = filesystem.lock().await.read_file_by_path(&item.path).await;
+                if let Ok(file) = file {
+                    for found_match in
+                        self.find_matches(&SearchSource::File, &item.path, file.content.lines())
+                    {
+                        if results.push(found_match).await {
+                            break 'walk;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::mpsc::channel;
+    use tokio::sync::Mutex;
+
+    use super::{is_ignored, CancellationToken, IgnoreRule, Search, SearchOptions, SearchSource};
+    use crate::filesystems::{Filesystem, LocalFilesystem};
+    use crate::messaging::{ClientMessages, ServerMessages};
+
+    #[test]
+    fn gitignore_rules() {
+        let rules = ["target/", "*.log", "!keep.log", "/build"]
+            .iter()
+            .filter_map(|line| IgnoreRule::parse("/project", line))
+            .collect::<Vec<IgnoreRule>>();
+
+        assert!(is_ignored(&rules, "/project/target", true));
+        assert!(!is_ignored(&rules, "/project/target", false));
+        assert!(is_ignored(&rules, "/project/src/debug.log", false));
+        assert!(!is_ignored(&rules, "/project/src/keep.log", false));
+        assert!(is_ignored(&rules, "/project/build", true));
+        assert!(!is_ignored(&rules, "/project/src/build", true));
+    }
+
+    #[test]
+    fn gitignore_rules_of_sibling_directories() {
+        let rules = ["target/", "*.log"]
+            .iter()
+            .filter_map(|line| IgnoreRule::parse("/project", line))
+            .collect::<Vec<IgnoreRule>>();
+
+        assert!(is_ignored(&rules, "/project/debug.log", false));
+        assert!(!is_ignored(&rules, "/project-old/debug.log", false));
+        assert!(!is_ignored(&rules, "/project-old/target", true));
+        assert!(!is_ignored(&rules, "/other/project/debug.log", false));
+    }
+
+    #[tokio::test]
+    async fn search_files() {
+        let fs: Box<dyn Filesystem + Send> = Box::new(LocalFilesystem::new());
+        let (sender, mut receiver) = channel(10);
+
+        let mut options = SearchOptions::new("./src");
+        options.include = vec!["*.rs".to_string()];
+        options.case_sensitive = true;
+
+        let query = format!("pub mod {};", "search");
+        let search = Search::new(&query, options).unwrap();
+        search
+            .run(
+                Arc::new(Mutex::new(fs)),
+                CancellationToken::new(),
+                sender,
+                0,
+                "search".to_string(),
+            )
+            .await;
+
+        let message = receiver.recv().await.unwrap();
+        if let ClientMessages::ServerMessage(ServerMessages::SearchResults {
+            matches, done, ..
+        }) = message
+        {
+            assert!(done);
+            assert_eq!(matches.len(), 1);
+            assert!(matches[0].path.ends_with("lib.rs"));
+        } else {
+            panic!("Unexpected message");
+        }
+    }
+
+    #[tokio::test]
+    async fn search_output_buffers() {
+        let fs: Box<dyn Filesystem + Send> = Box::new(LocalFilesystem::new());
+        let (sender, mut receiver) = channel(10);
+
+        let mut options = SearchOptions::new("./missing_folder");
+        options.include_terminals = true;
+
+        let terminal = SearchSource::Terminal {
+            terminal_shell_id: "shell".to_string(),
+        };
+        let channel_source = SearchSource::OutputChannel {
+            channel_id: "build".to_string(),
+        };
+
+        let search = Search::new("error", options).unwrap().with_buffers(vec![
+            (
+                terminal.clone(),
+                vec!["$ cargo build".to_string(), "error: oops".to_string()],
+            ),
+            (channel_source, vec!["error".to_string()]),
+        ]);
+        search
+            .run(
+                Arc::new(Mutex::new(fs)),
+                CancellationToken::new(),
+                sender,
+                0,
+                "search".to_string(),
+            )
+            .await;
+
+        let message = receiver.recv().await.unwrap();
+        if let ClientMessages::ServerMessage(ServerMessages::SearchResults { matches, .. }) =
+            message
+        {
+            // Output channels were not included
+            assert_eq!(matches.len(), 1);
+            assert_eq!(matches[0].source, terminal);
+            assert_eq!(matches[0].line, 1);
+        } else {
+            panic!("Unexpected message");
+        }
+    }
+}
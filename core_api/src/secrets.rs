@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Credentials kept in the server by ID, like the passwords of the FTP connections.
+///
+/// Unlike the State data, they are never sent to the clients, the extensions or the persistor,
+/// so they only last while the server is running
+#[derive(Clone, Default)]
+pub struct SecretsStore(Arc<RwLock<HashMap<String, String>>>);
+
+impl SecretsStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, secret_id: &str, value: String) {
+        self.0.write().unwrap().insert(secret_id.to_owned(), value);
+    }
+
+    pub fn get(&self, secret_id: &str) -> Option<String> {
+        self.0.read().unwrap().get(secret_id).cloned()
+    }
+
+    pub fn remove(&self, secret_id: &str) {
+        self.0.write().unwrap().remove(secret_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SecretsStore;
+
+    #[test]
+    fn shared_secrets() {
+        let secrets = SecretsStore::new();
+        let cloned = secrets.clone();
+
+        // Clones share the same secrets
+        secrets.set("ftp", "password".to_owned());
+        assert_eq!(cloned.get("ftp"), Some("password".to_owned()));
+
+        cloned.set("ftp", "new password".to_owned());
+        assert_eq!(secrets.get("ftp"), Some("new password".to_owned()));
+
+        secrets.remove("ftp");
+        assert_eq!(cloned.get("ftp"), None);
+    }
+}
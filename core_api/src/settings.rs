@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Settings errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum SettingsErrors {
+    SettingNotFound,
+    /// The value doesn't follow the schema of the setting
+    InvalidValue {
+        key: String,
+        reason: String,
+    },
+}
+
+/// Type of the value of a setting
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingType {
+    Boolean,
+    Number,
+    String,
+    Array,
+    Object,
+    Any,
+}
+
+impl SettingType {
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            Self::Boolean => value.is_boolean(),
+            Self::Number => value.is_number(),
+            Self::String => value.is_string(),
+            Self::Array => value.is_array(),
+            Self::Object => value.is_object(),
+            Self::Any => true,
+        }
+    }
+}
+
+/// Describes a setting contributed by an extension, e.g `git.autoFetch`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SettingSchema {
+    pub key: String,
+    pub description: String,
+    pub setting_type: SettingType,
+    /// Used when the user didn't set it
+    pub default: Value,
+    /// Only these values are allowed, if any
+    #[serde(default)]
+    pub allowed_values: Option<Vec<Value>>,
+}
+
+impl SettingSchema {
+    pub fn new(key: &str, setting_type: SettingType, default: Value) -> Self {
+        Self {
+            key: key.to_owned(),
+            description: String::new(),
+            setting_type,
+            default,
+            allowed_values: None,
+        }
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = description.to_owned();
+        self
+    }
+
+    pub fn with_allowed_values(mut self, allowed_values: Vec<Value>) -> Self {
+        self.allowed_values = Some(allowed_values);
+        self
+    }
+
+    pub fn validate(&self, value: &Value) -> Result<(), SettingsErrors> {
+        let invalid = |reason: String| SettingsErrors::InvalidValue {
+            key: self.key.clone(),
+            reason,
+        };
+
+        if !self.setting_type.matches(value) {
+            return Err(invalid(format!("expected a {:?}", self.setting_type)));
+        }
+
+        if let Some(allowed_values) = &self.allowed_values {
+            if !allowed_values.contains(value) {
+                return Err(invalid(format!("expected one of {:?}", allowed_values)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A key combination that runs a command
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Keybinding {
+    /// e.g `Ctrl+Shift+P`
+    pub key: String,
+    pub command: String,
+    /// Context where it's enabled, e.g `editorFocus`
+    #[serde(default)]
+    pub when: Option<String>,
+}
+
+/// Settings and keybindings of the user, shared by every workspace
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct UserSettings {
+    #[serde(default)]
+    pub values: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub keybindings: Vec<Keybinding>,
+}
+
+/// Schemas of the settings contributed by the extensions
+#[derive(Clone, Debug, Default)]
+pub struct SettingsSchemas {
+    /// Schemas by the setting key, with the extension contributing them
+    schemas: HashMap<String, (String, SettingSchema)>,
+}
+
+impl SettingsSchemas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, extension_id: &str, schemas: Vec<SettingSchema>) {
+        for schema in schemas {
+            self.schemas
+                .insert(schema.key.clone(), (extension_id.to_owned(), schema));
+        }
+    }
+
+    pub fn unregister(&mut self, extension_id: &str) {
+        self.schemas.retain(|_, (id, _)| id != extension_id);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&SettingSchema> {
+        self.schemas.get(key).map(|(_, schema)| schema)
+    }
+
+    /// ID of the extension contributing a setting
+    pub fn get_owner(&self, key: &str) -> Option<&str> {
+        self.schemas.get(key).map(|(owner, _)| owner.as_str())
+    }
+
+    pub fn get_all(&self) -> Vec<SettingSchema> {
+        self.schemas
+            .values()
+            .map(|(_, schema)| schema.clone())
+            .collect()
+    }
+
+    /// Settings without a schema are always valid, the extension contributing it might not be loaded yet
+    pub fn validate(&self, key: &str, value: &Value) -> Result<(), SettingsErrors> {
+        match self.get(key) {
+            Some(schema) => schema.validate(value),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Owns the user settings and keybindings
+#[derive(Clone, Debug, Default)]
+pub struct SettingsService {
+    settings: UserSettings,
+}
+
+impl SettingsService {
+    pub fn new(settings: UserSettings) -> Self {
+        Self { settings }
+    }
+
+    pub fn get_settings(&self) -> &UserSettings {
+        &self.settings
+    }
+
+    /// Value of a setting, or its default value if the user didn't set it
+    pub fn get(&self, key: &str, schemas: &SettingsSchemas) -> Option<Value> {
+        self.settings
+            .values
+            .get(key)
+            .cloned()
+            .or_else(|| schemas.get(key).map(|schema| schema.default.clone()))
+    }
+
+    /// Every setting with a value, including the defaults of the contributed schemas
+    pub fn get_values(&self, schemas: &SettingsSchemas) -> BTreeMap<String, Value> {
+        let mut values = schemas
+            .get_all()
+            .into_iter()
+            .map(|schema| (schema.key, schema.default))
+            .collect::<BTreeMap<String, Value>>();
+        values.extend(self.settings.values.clone());
+        values
+    }
+
+    /// Set several settings at once, nothing is changed if any of them is not valid.
+    /// Returns the keys that changed
+    pub fn set(
+        &mut self,
+        values: BTreeMap<String, Value>,
+        schemas: &SettingsSchemas,
+    ) -> Result<Vec<String>, SettingsErrors> {
+        for (key, value) in &values {
+            schemas.validate(key, value)?;
+        }
+
+        let mut changed = Vec::new();
+        for (key, value) in values {
+            if self.settings.values.get(&key) != Some(&value) {
+                self.settings.values.insert(key.clone(), value);
+                changed.push(key);
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Go back to the default value of a setting
+    pub fn reset(&mut self, key: &str) -> Result<(), SettingsErrors> {
+        self.settings
+            .values
+            .remove(key)
+            .map(|_| ())
+            .ok_or(SettingsErrors::SettingNotFound)
+    }
+
+    /// Replace the keybindings, returns false if they were the same
+    pub fn set_keybindings(&mut self, keybindings: Vec<Keybinding>) -> bool {
+        if self.settings.keybindings == keybindings {
+            false
+        } else {
+            self.settings.keybindings = keybindings;
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+
+    use super::{SettingSchema, SettingType, SettingsSchemas, SettingsService};
+
+    #[test]
+    fn validate_settings() {
+        let mut schemas = SettingsSchemas::new();
+        schemas.register(
+            "git",
+            vec![
+                SettingSchema::new("git.autoFetch", SettingType::Boolean, json!(false)),
+                SettingSchema::new("git.mode", SettingType::String, json!("cli"))
+                    .with_allowed_values(vec![json!("cli"), json!("libgit")]),
+            ],
+        );
+
+        let mut service = SettingsService::default();
+        assert_eq!(service.get("git.autoFetch", &schemas), Some(json!(false)));
+
+        let values = BTreeMap::from([
+            ("git.autoFetch".to_owned(), json!(true)),
+            ("editor.fontSize".to_owned(), json!(14)),
+        ]);
+        assert_eq!(service.set(values.clone(), &schemas).unwrap().len(), 2);
+        assert!(service.set(values, &schemas).unwrap().is_empty());
+
+        let values = BTreeMap::from([
+            ("git.autoFetch".to_owned(), json!(false)),
+            ("git.mode".to_owned(), json!("svn")),
+        ]);
+        assert!(service.set(values, &schemas).is_err());
+        assert_eq!(service.get("git.autoFetch", &schemas), Some(json!(true)));
+
+        // Without the schema anything goes
+        schemas.unregister("git");
+        let values = BTreeMap::from([("git.mode".to_owned(), json!(1))]);
+        assert!(service.set(values, &schemas).is_ok());
+
+        service.reset("git.mode").unwrap();
+        assert_eq!(service.get_values(&schemas).len(), 2);
+    }
+}
@@ -1,7 +1,13 @@
 use std::fs;
 use std::path::PathBuf;
 
-use crate::states::StateData;
+use tracing::warn;
+
+use crate::blobs::BlobStore;
+use crate::settings::UserSettings;
+use crate::states::{
+    DataEventLog, SnapshotsHistory, StateData, StoredDataEventLog, StoredSnapshotsHistory,
+};
 
 use super::Persistor;
 
@@ -10,11 +16,40 @@ use super::Persistor;
 pub struct FilePersistor {
     /// Where the state is persisted.
     path: PathBuf,
+    /// Where the data of the snapshots and checkpoints is kept, inline if none
+    blobs: Option<BlobStore>,
 }
 
 impl FilePersistor {
     pub fn new(path: PathBuf) -> Self {
-        Self { path }
+        Self { path, blobs: None }
+    }
+
+    /// Keep the data of the snapshots and checkpoints in a blob store,
+    /// e.g the one of [`crate::recovery::SessionRecovery`]
+    pub fn with_blob_store(mut self, blobs: BlobStore) -> Self {
+        self.blobs = Some(blobs);
+        self
+    }
+
+    /// Owner of the blobs referenced by this persistor, e.g `snapshots:/home/user/state.json`
+    fn get_blobs_owner(&self, kind: &str) -> String {
+        format!("{}:{}", kind, self.path.display())
+    }
+
+    /// The history is saved next to the state, e.g `state.history.json`
+    fn get_history_path(&self) -> PathBuf {
+        self.path.with_extension("history.json")
+    }
+
+    /// The changes log is saved next to the state, e.g `state.events.json`
+    fn get_event_log_path(&self) -> PathBuf {
+        self.path.with_extension("events.json")
+    }
+
+    /// The user settings are saved next to the state, e.g `state.settings.json`
+    fn get_settings_path(&self) -> PathBuf {
+        self.path.with_extension("settings.json")
     }
 }
 
@@ -28,4 +63,73 @@ impl Persistor for FilePersistor {
         let file_content = serde_json::to_string(&state).unwrap();
         fs::write(&self.path, file_content.as_bytes()).unwrap();
     }
+
+    fn load_history(&mut self) -> Option<SnapshotsHistory> {
+        let file_content = fs::read_to_string(self.get_history_path()).ok()?;
+
+        if let Some(blobs) = &self.blobs {
+            if let Ok(stored) = serde_json::from_str::<StoredSnapshotsHistory>(&file_content) {
+                return SnapshotsHistory::from_stored(stored, blobs);
+            }
+        }
+
+        // Histories saved without a blob store keep their data inline
+        serde_json::from_str(&file_content).ok()
+    }
+
+    fn save_history(&mut self, history: &SnapshotsHistory) {
+        let stored = self.blobs.as_ref().map(|blobs| {
+            history
+                .store(blobs, &self.get_blobs_owner("snapshots"))
+                .map(|stored| serde_json::to_string(&stored).unwrap())
+        });
+        let file_content = match stored {
+            Some(Ok(file_content)) => file_content,
+            Some(Err(err)) => {
+                warn!("Could not store the snapshots as blobs, error: {}", err);
+                serde_json::to_string(history).unwrap()
+            }
+            None => serde_json::to_string(history).unwrap(),
+        };
+        fs::write(self.get_history_path(), file_content.as_bytes()).unwrap();
+    }
+
+    fn load_event_log(&mut self) -> Option<DataEventLog> {
+        let file_content = fs::read_to_string(self.get_event_log_path()).ok()?;
+
+        if let Some(blobs) = &self.blobs {
+            if let Ok(stored) = serde_json::from_str::<StoredDataEventLog>(&file_content) {
+                return DataEventLog::from_stored(stored, blobs);
+            }
+        }
+
+        // Logs saved without a blob store keep their checkpoints inline
+        serde_json::from_str(&file_content).ok()
+    }
+
+    fn save_event_log(&mut self, log: &DataEventLog) {
+        let stored = self.blobs.as_ref().map(|blobs| {
+            log.store(blobs, &self.get_blobs_owner("checkpoints"))
+                .map(|stored| serde_json::to_string(&stored).unwrap())
+        });
+        let file_content = match stored {
+            Some(Ok(file_content)) => file_content,
+            Some(Err(err)) => {
+                warn!("Could not store the checkpoints as blobs, error: {}", err);
+                serde_json::to_string(log).unwrap()
+            }
+            None => serde_json::to_string(log).unwrap(),
+        };
+        fs::write(self.get_event_log_path(), file_content.as_bytes()).unwrap();
+    }
+
+    fn load_settings(&mut self) -> Option<UserSettings> {
+        let file_content = fs::read_to_string(self.get_settings_path()).ok()?;
+        serde_json::from_str(&file_content).ok()
+    }
+
+    fn save_settings(&mut self, settings: &UserSettings) {
+        let file_content = serde_json::to_string(settings).unwrap();
+        fs::write(self.get_settings_path(), file_content.as_bytes()).unwrap();
+    }
 }
@@ -1,4 +1,5 @@
-use crate::states::StateData;
+use crate::settings::UserSettings;
+use crate::states::{DataEventLog, SnapshotsHistory, StateData};
 
 use super::Persistor;
 
@@ -8,6 +9,12 @@ use super::Persistor;
 pub struct MemoryPersistor {
     /// Persisted data
     data: StateData,
+    /// Persisted snapshots
+    history: Option<SnapshotsHistory>,
+    /// Persisted changes log
+    event_log: Option<DataEventLog>,
+    /// Persisted user settings
+    settings: Option<UserSettings>,
 }
 
 impl MemoryPersistor {
@@ -23,4 +30,22 @@ impl Persistor for MemoryPersistor {
     fn save(&mut self, data: &StateData) {
         self.data = data.clone();
     }
+    fn load_history(&mut self) -> Option<SnapshotsHistory> {
+        self.history.clone()
+    }
+    fn save_history(&mut self, history: &SnapshotsHistory) {
+        self.history = Some(history.clone());
+    }
+    fn load_event_log(&mut self) -> Option<DataEventLog> {
+        self.event_log.clone()
+    }
+    fn save_event_log(&mut self, log: &DataEventLog) {
+        self.event_log = Some(log.clone());
+    }
+    fn load_settings(&mut self) -> Option<UserSettings> {
+        self.settings.clone()
+    }
+    fn save_settings(&mut self, settings: &UserSettings) {
+        self.settings = Some(settings.clone());
+    }
 }
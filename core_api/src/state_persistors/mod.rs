@@ -1,4 +1,5 @@
-use crate::states::StateData;
+use crate::settings::UserSettings;
+use crate::states::{DataEventLog, SnapshotsHistory, StateData};
 
 pub mod file;
 pub mod memory;
@@ -12,4 +13,28 @@ pub trait Persistor {
 
     /// Persist data
     fn save(&mut self, data: &StateData);
+
+    /// Retrieve the snapshots history, if any
+    fn load_history(&mut self) -> Option<SnapshotsHistory> {
+        None
+    }
+
+    /// Persist the snapshots history
+    fn save_history(&mut self, _history: &SnapshotsHistory) {}
+
+    /// Retrieve the log of changes made to the data, if any
+    fn load_event_log(&mut self) -> Option<DataEventLog> {
+        None
+    }
+
+    /// Persist the log of changes made to the data
+    fn save_event_log(&mut self, _log: &DataEventLog) {}
+
+    /// Retrieve the user settings and keybindings, if any
+    fn load_settings(&mut self) -> Option<UserSettings> {
+        None
+    }
+
+    /// Persist the user settings and keybindings
+    fn save_settings(&mut self, _settings: &UserSettings) {}
 }
@@ -0,0 +1,144 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::views::{TabData, TabPosition, ViewsData};
+use super::StateData;
+
+/// How many closed tabs are remembered
+pub const MAX_CLOSED_TABS: usize = 20;
+
+/// A tab that was closed, with where it was so it can be put back
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ClosedTab {
+    pub tab: TabData,
+    pub position: TabPosition,
+}
+
+impl StateData {
+    /// Remember the tabs of the previous views that are not open anymore, the last closed goes last.
+    /// Returns true if the closed tabs changed
+    pub(crate) fn record_closed_tabs(&mut self, previous_views: &[ViewsData]) -> bool {
+        let open_tabs = self
+            .views
+            .iter()
+            .flat_map(|view| view.get_tabs())
+            .map(|tab| tab.get_id().to_owned())
+            .collect::<HashSet<String>>();
+
+        let mut closed_tabs = Vec::new();
+        for (view_index, view) in previous_views.iter().enumerate() {
+            for (position, tab) in view.get_tab_positions() {
+                if !open_tabs.contains(tab.get_id()) {
+                    closed_tabs.push(ClosedTab {
+                        tab: tab.clone(),
+                        position: TabPosition {
+                            view: view_index,
+                            ..position
+                        },
+                    });
+                }
+            }
+        }
+
+        // Tabs opened again are not closed anymore
+        let previous_count = self.closed_tabs.len();
+        self.closed_tabs.retain(|closed_tab| {
+            let id = closed_tab.tab.get_id();
+            !open_tabs.contains(id) && !closed_tabs.iter().any(|tab| tab.tab.get_id() == id)
+        });
+        let changed = !closed_tabs.is_empty() || self.closed_tabs.len() != previous_count;
+
+        self.closed_tabs.extend(closed_tabs);
+        let overflow = self.closed_tabs.len().saturating_sub(MAX_CLOSED_TABS);
+        self.closed_tabs.drain(..overflow);
+
+        changed
+    }
+
+    /// Put a closed tab back where it was and select it, by default the last closed one
+    pub fn reopen_closed_tab(&mut self, index: Option<usize>) -> Option<TabData> {
+        let index = match index {
+            Some(index) => index,
+            None => self.closed_tabs.len().checked_sub(1)?,
+        };
+        if index >= self.closed_tabs.len() {
+            return None;
+        }
+
+        let closed_tab = self.closed_tabs.remove(index);
+        if self.views.is_empty() {
+            self.views.push(ViewsData::default());
+        }
+
+        let view = closed_tab.position.view.min(self.views.len() - 1);
+        self.views[view].insert_tab(
+            TabPosition {
+                selected: true,
+                ..closed_tab.position
+            },
+            closed_tab.tab.clone(),
+        );
+
+        Some(closed_tab.tab)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::states::views::{TabData, TabPosition, ViewsData};
+    use crate::states::{StateData, StateDataField};
+
+    fn tab(id: &str) -> TabData {
+        TabData::Basic {
+            title: id.to_owned(),
+            id: id.to_owned(),
+        }
+    }
+
+    #[test]
+    fn close_and_reopen_tabs() {
+        let mut data = StateData::default();
+        let mut views = ViewsData::default();
+        for (index, id) in ["a", "b", "c"].iter().enumerate() {
+            views.insert_tab(
+                TabPosition {
+                    index,
+                    ..TabPosition::default()
+                },
+                tab(id),
+            );
+        }
+        data.views = vec![views];
+        let base = data.clone();
+
+        // Close `b`
+        let mut update = base.clone();
+        update.views = vec![ViewsData::default()];
+        update.views[0].insert_tab(TabPosition::default(), tab("a"));
+        update.views[0].insert_tab(
+            TabPosition {
+                index: 1,
+                ..TabPosition::default()
+            },
+            tab("c"),
+        );
+        let merge = data.merge(update.into(), Some(&base));
+        assert!(merge.changed.contains(&StateDataField::ClosedTabs));
+        assert_eq!(data.closed_tabs.len(), 1);
+        assert_eq!(data.closed_tabs[0].position.index, 1);
+
+        // It goes back to where it was
+        let mut update = data.clone();
+        assert_eq!(update.reopen_closed_tab(None), Some(tab("b")));
+        assert!(update.reopen_closed_tab(None).is_none());
+        data.merge(update.into(), None);
+
+        let tabs = data.views[0]
+            .get_tabs()
+            .map(|tab| tab.get_id())
+            .collect::<Vec<&str>>();
+        assert_eq!(tabs, vec!["a", "b", "c"]);
+        assert!(data.closed_tabs.is_empty());
+    }
+}
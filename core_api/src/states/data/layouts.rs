@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+use super::views::ViewsData;
+use super::StateData;
+
+/// A named arrangement of the views, panels and tabs, e.g `debugging`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub name: String,
+    pub views: Vec<ViewsData>,
+    /// Unix timestamp (seconds)
+    pub saved_at: u64,
+}
+
+impl StateData {
+    /// Save the current views as a layout, replacing the one with the same name
+    pub fn save_layout(&mut self, name: &str, saved_at: u64) {
+        let layout = Layout {
+            name: name.to_owned(),
+            views: self.views.clone(),
+            saved_at,
+        };
+
+        match self.layouts.iter_mut().find(|layout| layout.name == name) {
+            Some(existing_layout) => *existing_layout = layout,
+            None => self.layouts.push(layout),
+        }
+    }
+
+    /// Replace the views with the ones of a layout, returns false if there is no such layout
+    pub fn apply_layout(&mut self, name: &str) -> bool {
+        if let Some(layout) = self.layouts.iter().find(|layout| layout.name == name) {
+            self.views = layout.views.clone();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns false if there is no such layout
+    pub fn remove_layout(&mut self, name: &str) -> bool {
+        let layouts_count = self.layouts.len();
+        self.layouts.retain(|layout| layout.name != name);
+        self.layouts.len() != layouts_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::states::views::ViewsData;
+    use crate::states::StateData;
+
+    #[test]
+    fn save_and_apply_layouts() {
+        let mut data = StateData::default();
+        data.save_layout("coding", 1);
+
+        data.views = vec![ViewsData::default(), ViewsData::default()];
+        data.save_layout("review", 2);
+        data.save_layout("review", 3);
+        assert_eq!(data.layouts.len(), 2);
+        assert_eq!(data.layouts[1].saved_at, 3);
+
+        assert!(data.apply_layout("coding"));
+        assert!(data.views.is_empty());
+        assert!(!data.apply_layout("debugging"));
+
+        assert!(data.remove_layout("coding"));
+        assert!(!data.remove_layout("coding"));
+    }
+}
@@ -2,9 +2,17 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-use self::{commands::CommandConfig, views::ViewsData};
+use self::{closed_tabs::ClosedTab, commands::CommandConfig, layouts::Layout, views::ViewsData};
+use crate::extensions::supervisor::PanicPolicy;
+use crate::filesystems::{EolPolicy, FtpSettings};
+use crate::http::HttpSettings;
+use crate::save_hooks::SaveHook;
+use crate::tasks::ScheduledTask;
+use crate::workspaces::WorkspaceConfig;
 
+pub mod closed_tabs;
 pub mod commands;
+pub mod layouts;
 pub mod views;
 
 /// The configuration of a State
@@ -12,18 +20,327 @@ pub mod views;
 pub struct StateData {
     /// Identification for the State
     pub id: u8,
+    /// Increased after every change, updates must say on which revision they were made
+    #[serde(default)]
+    pub revision: u64,
     /// Views, ViewPanels, and Tabs
     pub views: Vec<ViewsData>,
     /// Commands with their hotkeys
     pub commands: HashMap<String, CommandConfig>,
+    /// What to do when an extension panics, by extension ID
+    #[serde(default)]
+    pub panic_policies: HashMap<String, PanicPolicy>,
+    /// Proxy, certificates and offline mode
+    #[serde(default)]
+    pub http_settings: HttpSettings,
+    /// FTP servers mounted as filesystems
+    #[serde(default)]
+    pub ftp_connections: Vec<FtpSettings>,
+    /// How line endings are handled when saving files
+    #[serde(default)]
+    pub eol_policy: EolPolicy,
+    /// Tasks run periodically, with the result of their last run
+    #[serde(default)]
+    pub scheduled_tasks: Vec<ScheduledTask>,
+    /// Settings of every workspace, overridden by their own configuration
+    #[serde(default)]
+    pub workspace_settings: WorkspaceConfig,
+    /// Saved arrangements of the views, by name
+    #[serde(default)]
+    pub layouts: Vec<Layout>,
+    /// Hooks run in order before saving a document, by language
+    #[serde(default)]
+    pub save_hooks: HashMap<String, Vec<SaveHook>>,
+    /// Recently closed tabs, the last closed goes last
+    #[serde(default)]
+    pub closed_tabs: Vec<ClosedTab>,
 }
 
 impl Default for StateData {
     fn default() -> Self {
         Self {
             id: 1,
+            revision: 0,
             views: Vec::default(),
             commands: HashMap::default(),
+            panic_policies: HashMap::default(),
+            http_settings: HttpSettings::default(),
+            ftp_connections: Vec::default(),
+            eol_policy: EolPolicy::default(),
+            scheduled_tasks: Vec::default(),
+            workspace_settings: WorkspaceConfig::default(),
+            layouts: Vec::default(),
+            save_hooks: HashMap::default(),
+            closed_tabs: Vec::default(),
         }
     }
 }
+
+/// Fields of the StateData that are merged independently
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateDataField {
+    Views,
+    Commands,
+    PanicPolicies,
+    HttpSettings,
+    FtpConnections,
+    EolPolicy,
+    ScheduledTasks,
+    WorkspaceSettings,
+    Layouts,
+    SaveHooks,
+    ClosedTabs,
+}
+
+/// Result of merging an update into the StateData
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct StateDataMerge {
+    /// Revision after the merge
+    pub revision: u64,
+    /// Fields that were modified
+    pub changed: Vec<StateDataField>,
+    /// Fields modified by someone else since the update's revision, these were not applied
+    pub conflicts: Vec<StateDataField>,
+}
+
+/// Changes to the StateData, missing fields are left as they are
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateDataUpdate {
+    /// Revision the update was made on, without it conflicts are not checked
+    #[serde(default)]
+    pub revision: Option<u64>,
+    #[serde(default)]
+    pub views: Option<Vec<ViewsData>>,
+    #[serde(default)]
+    pub commands: Option<HashMap<String, CommandConfig>>,
+    #[serde(default)]
+    pub panic_policies: Option<HashMap<String, PanicPolicy>>,
+    #[serde(default)]
+    pub http_settings: Option<HttpSettings>,
+    #[serde(default)]
+    pub ftp_connections: Option<Vec<FtpSettings>>,
+    #[serde(default)]
+    pub eol_policy: Option<EolPolicy>,
+    #[serde(default)]
+    pub scheduled_tasks: Option<Vec<ScheduledTask>>,
+    #[serde(default)]
+    pub workspace_settings: Option<WorkspaceConfig>,
+    #[serde(default)]
+    pub layouts: Option<Vec<Layout>>,
+    #[serde(default)]
+    pub save_hooks: Option<HashMap<String, Vec<SaveHook>>>,
+    #[serde(default)]
+    pub closed_tabs: Option<Vec<ClosedTab>>,
+}
+
+impl From<StateData> for StateDataUpdate {
+    fn from(data: StateData) -> Self {
+        Self {
+            revision: Some(data.revision),
+            views: Some(data.views),
+            commands: Some(data.commands),
+            panic_policies: Some(data.panic_policies),
+            http_settings: Some(data.http_settings),
+            ftp_connections: Some(data.ftp_connections),
+            eol_policy: Some(data.eol_policy),
+            scheduled_tasks: Some(data.scheduled_tasks),
+            workspace_settings: Some(data.workspace_settings),
+            layouts: Some(data.layouts),
+            save_hooks: Some(data.save_hooks),
+            closed_tabs: Some(data.closed_tabs),
+        }
+    }
+}
+
+impl StateData {
+    /// Only the given fields, e.g to tell others what changed
+    pub fn get_delta(&self, fields: &[StateDataField]) -> StateDataUpdate {
+        let mut delta = StateDataUpdate {
+            revision: Some(self.revision),
+            ..StateDataUpdate::default()
+        };
+
+        for field in fields {
+            match field {
+                StateDataField::Views => delta.views = Some(self.views.clone()),
+                StateDataField::Commands => delta.commands = Some(self.commands.clone()),
+                StateDataField::PanicPolicies => {
+                    delta.panic_policies = Some(self.panic_policies.clone())
+                }
+                StateDataField::HttpSettings => {
+                    delta.http_settings = Some(self.http_settings.clone())
+                }
+                StateDataField::FtpConnections => {
+                    delta.ftp_connections = Some(self.ftp_connections.clone())
+                }
+                StateDataField::EolPolicy => delta.eol_policy = Some(self.eol_policy),
+                StateDataField::ScheduledTasks => {
+                    delta.scheduled_tasks = Some(self.scheduled_tasks.clone())
+                }
+                StateDataField::WorkspaceSettings => {
+                    delta.workspace_settings = Some(self.workspace_settings.clone())
+                }
+                StateDataField::Layouts => delta.layouts = Some(self.layouts.clone()),
+                StateDataField::SaveHooks => delta.save_hooks = Some(self.save_hooks.clone()),
+                StateDataField::ClosedTabs => delta.closed_tabs = Some(self.closed_tabs.clone()),
+            }
+        }
+
+        delta
+    }
+
+    /// Merge an update field by field
+    ///
+    /// # Arguments
+    ///
+    /// * `update`   - Changes, made on top of the revision `update.revision`
+    /// * `base`     - The data as it was on `update.revision`, if it's known
+    ///
+    pub fn merge(&mut self, update: StateDataUpdate, base: Option<&StateData>) -> StateDataMerge {
+        // Nothing happened since the update was made, or conflicts are not checked
+        let current = self.clone();
+        let base = match update.revision {
+            Some(revision) if revision != self.revision => base,
+            _ => Some(&current),
+        };
+
+        let mut merge = StateDataMerge::default();
+
+        macro_rules! merge_field {
+            ($field:ident, $name:expr) => {
+                if let Some(value) = update.$field {
+                    if value != self.$field {
+                        match base {
+                            // Only the update modified it
+                            Some(base) if base.$field == self.$field => {
+                                self.$field = value;
+                                merge.changed.push($name);
+                            }
+                            // Only someone else modified it
+                            Some(base) if base.$field == value => {}
+                            _ => merge.conflicts.push($name),
+                        }
+                    }
+                }
+            };
+        }
+
+        merge_field!(views, StateDataField::Views);
+        merge_field!(commands, StateDataField::Commands);
+        merge_field!(panic_policies, StateDataField::PanicPolicies);
+        merge_field!(http_settings, StateDataField::HttpSettings);
+        merge_field!(ftp_connections, StateDataField::FtpConnections);
+        merge_field!(eol_policy, StateDataField::EolPolicy);
+        merge_field!(scheduled_tasks, StateDataField::ScheduledTasks);
+        merge_field!(workspace_settings, StateDataField::WorkspaceSettings);
+        merge_field!(layouts, StateDataField::Layouts);
+        merge_field!(save_hooks, StateDataField::SaveHooks);
+        merge_field!(closed_tabs, StateDataField::ClosedTabs);
+
+        // Closing tabs is just updating the views, core keeps track of them
+        if merge.changed.contains(&StateDataField::Views)
+            && self.record_closed_tabs(&current.views)
+            && !merge.changed.contains(&StateDataField::ClosedTabs)
+        {
+            merge.changed.push(StateDataField::ClosedTabs);
+        }
+
+        if !merge.changed.is_empty() {
+            self.revision += 1;
+        }
+        merge.revision = self.revision;
+
+        merge
+    }
+
+    /// Check if an update modifies the commands run on the host, e.g formatters in the save hooks,
+    /// only the owner can make such changes
+    pub fn changes_host_commands(&self, update: &StateDataUpdate) -> bool {
+        let save_hooks = update
+            .save_hooks
+            .as_ref()
+            .is_some_and(|save_hooks| save_hooks != &self.save_hooks);
+        let scheduled_tasks = update
+            .scheduled_tasks
+            .as_ref()
+            .is_some_and(|scheduled_tasks| scheduled_tasks != &self.scheduled_tasks);
+
+        let workspace_tasks = update
+            .workspace_settings
+            .as_ref()
+            .is_some_and(|settings| settings.tasks != self.workspace_settings.tasks);
+
+        save_hooks || scheduled_tasks || workspace_tasks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StateData, StateDataField, StateDataUpdate};
+    use crate::extensions::supervisor::PanicPolicy;
+    use crate::filesystems::EolPolicy;
+    use crate::save_hooks::{SaveHook, SaveHookAction};
+
+    #[test]
+    fn merge_concurrent_updates() {
+        let mut data = StateData::default();
+        let base = data.clone();
+
+        // First client
+        let mut update = base.clone();
+        update.eol_policy = EolPolicy::ForceLf;
+        let merge = data.merge(update.into(), Some(&base));
+        assert_eq!(merge.changed, vec![StateDataField::EolPolicy]);
+        assert_eq!(merge.revision, 1);
+
+        // Second client, made on the same revision as the first one
+        let mut update = base.clone();
+        update
+            .panic_policies
+            .insert("sample".to_owned(), PanicPolicy::Disable);
+        update.eol_policy = EolPolicy::ForceCrlf;
+        let merge = data.merge(update.into(), Some(&base));
+        assert_eq!(merge.changed, vec![StateDataField::PanicPolicies]);
+        assert_eq!(merge.conflicts, vec![StateDataField::EolPolicy]);
+        assert_eq!(data.eol_policy, EolPolicy::ForceLf);
+        assert_eq!(data.revision, 2);
+
+        // Unknown base
+        let mut update = base;
+        update.eol_policy = EolPolicy::Preserve;
+        let merge = data.merge(update.into(), None);
+        assert!(merge.changed.is_empty());
+        assert_eq!(merge.conflicts.len(), 2);
+
+        // Without a revision only the given fields are applied
+        let update = StateDataUpdate {
+            eol_policy: Some(EolPolicy::Preserve),
+            ..StateDataUpdate::default()
+        };
+        let merge = data.merge(update, None);
+        assert_eq!(merge.changed, vec![StateDataField::EolPolicy]);
+        assert_eq!(data.panic_policies.len(), 1);
+    }
+
+    #[test]
+    fn detect_host_commands_changes() {
+        let data = StateData::default();
+
+        // Sending them back unmodified is fine
+        assert!(!data.changes_host_commands(&data.clone().into()));
+        assert!(!data.changes_host_commands(&StateDataUpdate::default()));
+
+        let mut update = data.clone();
+        update.save_hooks.insert(
+            "rust".to_owned(),
+            vec![SaveHook::new(
+                "format",
+                SaveHookAction::Format {
+                    command: "rustfmt".to_owned(),
+                    args: Vec::new(),
+                },
+            )],
+        );
+        assert!(data.changes_host_commands(&update.into()));
+    }
+}
@@ -1,6 +1,8 @@
+use std::path::Path;
+
 use serde::{Deserialize, Serialize};
 
-use crate::filesystems::FileFormat;
+use crate::filesystems::{remap_path, FileFormat, GravitonUri};
 
 /// Serialized Tab's data
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -18,12 +20,46 @@ pub enum TabData {
     Basic { title: String, id: String },
 }
 
+impl TabData {
+    pub fn get_id(&self) -> &str {
+        match self {
+            Self::TextEditor { id, .. } | Self::Basic { id, .. } => id,
+        }
+    }
+
+    /// The file opened in the tab, if any
+    pub fn get_uri(&self) -> Option<GravitonUri> {
+        match self {
+            Self::TextEditor {
+                path, filesystem, ..
+            } => Some(GravitonUri::new(filesystem, path)),
+            Self::Basic { .. } => None,
+        }
+    }
+}
+
+/// Where a tab is, or was, in the views
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TabPosition {
+    pub view: usize,
+    pub panel: usize,
+    /// Position inside the panel
+    pub index: usize,
+    pub selected: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
 pub struct ViewDataPanel {
     /// Focused tab in the specific View panel
     selected_tab_id: Option<String>,
     /// Data from all the tabs in the View panel
     tabs: Vec<TabData>,
+    /// Percentage of the View taken by the panel, shared equally if missing
+    #[serde(default)]
+    size: Option<u8>,
+    /// Hidden panels keep their tabs
+    #[serde(default)]
+    hidden: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
@@ -31,3 +67,76 @@ pub struct ViewsData {
     /// All the View panels in the View
     view_panels: Vec<ViewDataPanel>,
 }
+
+impl ViewsData {
+    /// Return the tabs of all the View panels
+    pub fn get_tabs(&self) -> impl Iterator<Item = &TabData> {
+        self.view_panels.iter().flat_map(|panel| panel.tabs.iter())
+    }
+
+    /// Return the tabs of all the View panels with their position, `view` is left as 0
+    pub fn get_tab_positions(&self) -> impl Iterator<Item = (TabPosition, &TabData)> {
+        self.view_panels
+            .iter()
+            .enumerate()
+            .flat_map(|(panel_index, panel)| {
+                panel.tabs.iter().enumerate().map(move |(index, tab)| {
+                    let position = TabPosition {
+                        view: 0,
+                        panel: panel_index,
+                        index,
+                        selected: panel.selected_tab_id.as_deref() == Some(tab.get_id()),
+                    };
+                    (position, tab)
+                })
+            })
+    }
+
+    /// Insert a tab in a View panel, the closest one if it doesn't exist anymore
+    pub fn insert_tab(&mut self, position: TabPosition, tab: TabData) {
+        if self.view_panels.is_empty() {
+            self.view_panels.push(ViewDataPanel::default());
+        }
+
+        let panel_index = position.panel.min(self.view_panels.len() - 1);
+        let panel = &mut self.view_panels[panel_index];
+        if position.selected {
+            panel.selected_tab_id = Some(tab.get_id().to_owned());
+        }
+        let index = position.index.min(panel.tabs.len());
+        panel.tabs.insert(index, tab);
+    }
+
+    /// Point the tabs of a renamed file or folder to the new paths, returns true if any changed
+    pub fn rename_paths(&mut self, filesystem_name: &str, from: &str, to: &str) -> bool {
+        let mut changed = false;
+
+        for tab in self
+            .view_panels
+            .iter_mut()
+            .flat_map(|panel| panel.tabs.iter_mut())
+        {
+            if let TabData::TextEditor {
+                path,
+                filesystem,
+                filename,
+                ..
+            } = tab
+            {
+                if filesystem != filesystem_name {
+                    continue;
+                }
+                if let Some(new_path) = remap_path(path, from, to) {
+                    *filename = Path::new(&new_path)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_else(|| filename.clone());
+                    *path = new_path;
+                    changed = true;
+                }
+            }
+        }
+
+        changed
+    }
+}
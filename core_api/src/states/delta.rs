@@ -0,0 +1,303 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::StateData;
+use crate::messaging::ServerMessages;
+
+/// How many deltas are sent before sending the whole StateData again
+pub const DEFAULT_SNAPSHOT_INTERVAL: u32 = 50;
+
+/// Deltas errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum DeltaErrors {
+    PathNotFound(String),
+    /// The patched value is not a valid StateData
+    BadData,
+}
+
+/// An operation of a JSON Patch (RFC 6902), paths are JSON Pointers
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+}
+
+fn escape_key(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn unescape_key(key: &str) -> String {
+    key.replace("~1", "/").replace("~0", "~")
+}
+
+fn diff_into(path: &str, old: &Value, new: &Value, operations: &mut Vec<PatchOperation>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let path = format!("{}/{}", path, escape_key(key));
+                match new.get(key) {
+                    Some(new_value) => diff_into(&path, old_value, new_value, operations),
+                    None => operations.push(PatchOperation::Remove { path }),
+                }
+            }
+            for (key, value) in new {
+                if !old.contains_key(key) {
+                    operations.push(PatchOperation::Add {
+                        path: format!("{}/{}", path, escape_key(key)),
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            let common = old.len().min(new.len());
+            for index in 0..common {
+                diff_into(
+                    &format!("{}/{}", path, index),
+                    &old[index],
+                    &new[index],
+                    operations,
+                );
+            }
+            for value in &new[common..] {
+                operations.push(PatchOperation::Add {
+                    path: format!("{}/-", path),
+                    value: value.clone(),
+                });
+            }
+            // From the end so the indexes are still valid
+            for index in (common..old.len()).rev() {
+                operations.push(PatchOperation::Remove {
+                    path: format!("{}/{}", path, index),
+                });
+            }
+        }
+        (old, new) if old != new => operations.push(PatchOperation::Replace {
+            path: path.to_owned(),
+            value: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Operations that turn `old` into `new`
+pub fn diff(old: &Value, new: &Value) -> Vec<PatchOperation> {
+    let mut operations = Vec::new();
+    diff_into("", old, new, &mut operations);
+    operations
+}
+
+/// Split a path into it's parent and it's last key
+fn split_path(path: &str) -> Result<(&str, String), DeltaErrors> {
+    path.rfind('/')
+        .map(|index| (&path[..index], unescape_key(&path[index + 1..])))
+        .ok_or_else(|| DeltaErrors::PathNotFound(path.to_owned()))
+}
+
+/// Apply the operations in order
+pub fn apply_patch(value: &mut Value, operations: &[PatchOperation]) -> Result<(), DeltaErrors> {
+    for operation in operations {
+        match operation {
+            PatchOperation::Replace { path, value: new } => {
+                *value
+                    .pointer_mut(path)
+                    .ok_or_else(|| DeltaErrors::PathNotFound(path.clone()))? = new.clone();
+            }
+            PatchOperation::Add { path, value: new } => {
+                let (parent, key) = split_path(path)?;
+                match value.pointer_mut(parent) {
+                    Some(Value::Object(map)) => {
+                        map.insert(key, new.clone());
+                    }
+                    Some(Value::Array(list)) => {
+                        let index = if key == "-" {
+                            list.len()
+                        } else {
+                            key.parse::<usize>()
+                                .ok()
+                                .filter(|index| *index <= list.len())
+                                .ok_or_else(|| DeltaErrors::PathNotFound(path.clone()))?
+                        };
+                        list.insert(index, new.clone());
+                    }
+                    _ => return Err(DeltaErrors::PathNotFound(path.clone())),
+                }
+            }
+            PatchOperation::Remove { path } => {
+                let (parent, key) = split_path(path)?;
+                let removed = match value.pointer_mut(parent) {
+                    Some(Value::Object(map)) => map.remove(&key).is_some(),
+                    Some(Value::Array(list)) => match key.parse::<usize>() {
+                        Ok(index) if index < list.len() => {
+                            list.remove(index);
+                            true
+                        }
+                        _ => false,
+                    },
+                    _ => false,
+                };
+                if !removed {
+                    return Err(DeltaErrors::PathNotFound(path.clone()));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Changes between two revisions of a StateData
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StateDelta {
+    /// Clients on another revision should get the whole StateData again
+    pub from_revision: u64,
+    pub to_revision: u64,
+    pub operations: Vec<PatchOperation>,
+}
+
+impl StateDelta {
+    /// Compute the delta, `None` if the data can't be serialized
+    pub fn new(old: &StateData, new: &StateData) -> Option<Self> {
+        let old_value = serde_json::to_value(old).ok()?;
+        let new_value = serde_json::to_value(new).ok()?;
+
+        Some(Self {
+            from_revision: old.revision,
+            to_revision: new.revision,
+            operations: diff(&old_value, &new_value),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Patch a copy of the data
+    pub fn apply(&self, data: &StateData) -> Result<StateData, DeltaErrors> {
+        let mut value = serde_json::to_value(data).map_err(|_| DeltaErrors::BadData)?;
+        apply_patch(&mut value, &self.operations)?;
+        serde_json::from_value(value).map_err(|_| DeltaErrors::BadData)
+    }
+}
+
+/// Decides what the clients are sent when the StateData changes, a delta
+/// from what they were sent last time or every once in a while the whole data
+#[derive(Debug, Clone)]
+pub struct DeltaSync {
+    last_sent: Option<StateData>,
+    deltas_sent: u32,
+    snapshot_interval: u32,
+}
+
+impl Default for DeltaSync {
+    fn default() -> Self {
+        Self::new(DEFAULT_SNAPSHOT_INTERVAL)
+    }
+}
+
+impl DeltaSync {
+    pub fn new(snapshot_interval: u32) -> Self {
+        Self {
+            last_sent: None,
+            deltas_sent: 0,
+            snapshot_interval: snapshot_interval.max(1),
+        }
+    }
+
+    /// Message with the whole data, it's used as the base of the next deltas
+    pub fn snapshot(&mut self, data: &StateData) -> ServerMessages {
+        self.last_sent = Some(data.clone());
+        self.deltas_sent = 0;
+        ServerMessages::StateUpdated {
+            state_data: Box::new(data.clone()),
+        }
+    }
+
+    /// Message for the current data, `None` if nothing changed since the last one
+    pub fn next(&mut self, data: &StateData) -> Option<ServerMessages> {
+        let last_sent = match &self.last_sent {
+            Some(last_sent) if self.deltas_sent < self.snapshot_interval => last_sent,
+            _ => return Some(self.snapshot(data)),
+        };
+
+        match StateDelta::new(last_sent, data) {
+            Some(delta) if delta.is_empty() => None,
+            Some(delta) => {
+                self.last_sent = Some(data.clone());
+                self.deltas_sent += 1;
+                Some(ServerMessages::StateDelta {
+                    state_id: data.id,
+                    delta,
+                })
+            }
+            None => Some(self.snapshot(data)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{apply_patch, diff, DeltaSync, StateDelta};
+    use crate::filesystems::EolPolicy;
+    use crate::messaging::ServerMessages;
+    use crate::states::StateData;
+
+    #[test]
+    fn diff_and_patch() {
+        let old = json!({ "a": [1, 2, 3], "b": { "c/d": "x" }, "e": true });
+        let new = json!({ "a": [1, 5], "b": { "c/d": "y", "f": null } });
+
+        let operations = diff(&old, &new);
+        assert_eq!(operations.len(), 5);
+
+        let mut patched = old.clone();
+        apply_patch(&mut patched, &operations).unwrap();
+        assert_eq!(patched, new);
+
+        let mut patched = json!([]);
+        apply_patch(&mut patched, &diff(&json!([]), &json!([1, 2]))).unwrap();
+        assert_eq!(patched, json!([1, 2]));
+    }
+
+    #[test]
+    fn periodic_snapshots() {
+        let mut sync = DeltaSync::new(2);
+        let mut data = StateData::default();
+
+        // There is nothing to diff with yet
+        assert!(matches!(
+            sync.next(&data),
+            Some(ServerMessages::StateUpdated { .. })
+        ));
+        assert!(sync.next(&data).is_none());
+
+        let old = data.clone();
+        data.revision += 1;
+        data.eol_policy = EolPolicy::ForceCrlf;
+        match sync.next(&data) {
+            Some(ServerMessages::StateDelta { delta, .. }) => {
+                assert_eq!(delta.operations.len(), 2);
+                assert_eq!(delta.apply(&old).unwrap(), data);
+            }
+            _ => panic!("Expected a delta"),
+        }
+
+        data.revision += 1;
+        assert!(matches!(
+            sync.next(&data),
+            Some(ServerMessages::StateDelta { .. })
+        ));
+
+        // The interval was reached
+        data.revision += 1;
+        assert!(matches!(
+            sync.next(&data),
+            Some(ServerMessages::StateUpdated { .. })
+        ));
+        assert!(StateDelta::new(&data, &data).unwrap().is_empty());
+    }
+}
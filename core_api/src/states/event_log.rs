@@ -0,0 +1,327 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use super::invitations::now_secs;
+use super::{StateData, StateDataField, StateDelta};
+use crate::blobs::{BlobId, BlobStore};
+
+/// How many events are kept by default
+pub const DEFAULT_EVENTS_LIMIT: usize = 500;
+
+/// How many events there are between two checkpoints
+pub const DEFAULT_CHECKPOINT_INTERVAL: usize = 25;
+
+/// What a change of the StateData was about
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind")]
+pub enum DataAction {
+    TabOpened {
+        tab_id: String,
+    },
+    TabClosed {
+        tab_id: String,
+    },
+    TabReopened {
+        tab_id: String,
+    },
+    /// A single field was modified, e.g the EOL policy or the HTTP settings
+    SettingChanged {
+        field: StateDataField,
+    },
+    LayoutSaved {
+        name: String,
+    },
+    LayoutApplied {
+        name: String,
+    },
+    LayoutRemoved {
+        name: String,
+    },
+    /// A scheduled task finished
+    TaskRunRecorded {
+        task_id: String,
+    },
+    /// A snapshot was restored, e.g by undoing
+    Restored {
+        label: String,
+    },
+    /// A group of commands was applied as a single change, e.g a refactor
+    Grouped {
+        label: String,
+        fields: Vec<StateDataField>,
+    },
+    /// Several fields were modified at once
+    Updated {
+        fields: Vec<StateDataField>,
+    },
+}
+
+fn get_tab_ids(data: &StateData) -> Vec<&str> {
+    data.views
+        .iter()
+        .flat_map(|view| view.get_tabs())
+        .map(|tab| tab.get_id())
+        .collect()
+}
+
+impl DataAction {
+    /// Guess what an update was about from the fields it modified
+    pub fn from_change(old: &StateData, new: &StateData, changed: &[StateDataField]) -> Self {
+        let only_tabs = changed
+            .iter()
+            .all(|field| matches!(field, StateDataField::Views | StateDataField::ClosedTabs));
+
+        if only_tabs && changed.contains(&StateDataField::Views) {
+            let old_tabs = get_tab_ids(old);
+            let new_tabs = get_tab_ids(new);
+            if let Some(tab_id) = new_tabs.iter().find(|id| !old_tabs.contains(id)) {
+                return Self::TabOpened {
+                    tab_id: tab_id.to_string(),
+                };
+            }
+            if let Some(tab_id) = old_tabs.iter().find(|id| !new_tabs.contains(id)) {
+                return Self::TabClosed {
+                    tab_id: tab_id.to_string(),
+                };
+            }
+        }
+
+        match changed {
+            [field] => Self::SettingChanged { field: *field },
+            fields => Self::Updated {
+                fields: fields.to_vec(),
+            },
+        }
+    }
+
+    /// Short description, e.g to label the undo history
+    pub fn get_label(&self) -> String {
+        match self {
+            Self::TabOpened { tab_id } => format!("Open tab {}", tab_id),
+            Self::TabClosed { tab_id } => format!("Close tab {}", tab_id),
+            Self::TabReopened { tab_id } => format!("Reopen tab {}", tab_id),
+            Self::SettingChanged { field } => format!("Change {:?}", field),
+            Self::LayoutSaved { name } => format!("Save layout {}", name),
+            Self::LayoutApplied { name } => format!("Apply layout {}", name),
+            Self::LayoutRemoved { name } => format!("Remove layout {}", name),
+            Self::TaskRunRecorded { task_id } => format!("Run task {}", task_id),
+            Self::Restored { label } => format!("Restore {}", label),
+            Self::Grouped { label, .. } => label.clone(),
+            Self::Updated { .. } => "update".to_owned(),
+        }
+    }
+}
+
+/// A change of the StateData
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DataEvent {
+    pub action: DataAction,
+    /// Unix timestamp (seconds) of when it happened
+    pub created_at: u64,
+    pub delta: StateDelta,
+}
+
+/// Every change made to the StateData, so it can be inspected or rebuilt as it was on any
+/// logged revision. The whole data is kept every once in a while as a checkpoint,
+/// so it's rebuilt by patching the closest checkpoint instead of replaying the whole log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DataEventLog {
+    events: Vec<DataEvent>,
+    /// The data before the first event, and after every `checkpoint_interval` events
+    checkpoints: Vec<StateData>,
+    limit: usize,
+    checkpoint_interval: usize,
+}
+
+/// A [`DataEventLog`] as it's persisted, see [`DataEventLog::store`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StoredDataEventLog {
+    events: Vec<DataEvent>,
+    checkpoints: Vec<BlobId>,
+    limit: usize,
+    checkpoint_interval: usize,
+}
+
+impl Default for DataEventLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENTS_LIMIT, DEFAULT_CHECKPOINT_INTERVAL)
+    }
+}
+
+impl DataEventLog {
+    pub fn new(limit: usize, checkpoint_interval: usize) -> Self {
+        let checkpoint_interval = checkpoint_interval.max(1);
+        Self {
+            events: Vec::new(),
+            checkpoints: Vec::new(),
+            limit: limit.max(checkpoint_interval),
+            checkpoint_interval,
+        }
+    }
+
+    /// Log the change from `old` to `new`, nothing is logged if they can't be serialized
+    pub fn record(&mut self, action: DataAction, old: &StateData, new: &StateData) {
+        let delta = match StateDelta::new(old, new) {
+            Some(delta) => delta,
+            None => return,
+        };
+
+        // The log starts wherever the data was when it was first changed
+        if self.checkpoints.is_empty() || self.get_latest_revision() != Some(old.revision) {
+            self.events.clear();
+            self.checkpoints = vec![old.clone()];
+        }
+
+        self.events.push(DataEvent {
+            action,
+            created_at: now_secs(),
+            delta,
+        });
+
+        if self.events.len().is_multiple_of(self.checkpoint_interval) {
+            self.checkpoints.push(new.clone());
+        }
+
+        // Drop the oldest checkpoint with the events made on top of it
+        if self.events.len() > self.limit {
+            self.checkpoints.remove(0);
+            self.events.drain(..self.checkpoint_interval);
+        }
+    }
+
+    /// Events made after a revision, from the oldest to the newest
+    pub fn get_events_since(&self, revision: u64) -> Vec<DataEvent> {
+        self.events
+            .iter()
+            .filter(|event| event.delta.from_revision >= revision)
+            .cloned()
+            .collect()
+    }
+
+    /// Revision of the data after the last event
+    pub fn get_latest_revision(&self) -> Option<u64> {
+        match self.events.last() {
+            Some(event) => Some(event.delta.to_revision),
+            None => self.checkpoints.last().map(|data| data.revision),
+        }
+    }
+
+    /// Rebuild the data as it was on a revision, none if it's not in the log
+    pub fn get_data_at(&self, revision: u64) -> Option<StateData> {
+        let checkpoint = self
+            .checkpoints
+            .iter()
+            .rev()
+            .find(|data| data.revision <= revision)?;
+
+        let mut data = checkpoint.clone();
+        for event in &self.events {
+            if data.revision == revision {
+                break;
+            }
+            if event.delta.from_revision == data.revision {
+                data = event.delta.apply(&data).ok()?;
+            }
+        }
+
+        if data.revision == revision {
+            Some(data)
+        } else {
+            None
+        }
+    }
+
+    /// Rebuild the data after the last event
+    pub fn get_latest_data(&self) -> Option<StateData> {
+        self.get_data_at(self.get_latest_revision()?)
+    }
+
+    pub fn get_checkpoints_count(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    /// Move the checkpoints to a blob store, so the ones matching a snapshot are only stored once.
+    /// The blobs previously referenced by `owner` are released
+    pub fn store(&self, blobs: &BlobStore, owner: &str) -> io::Result<StoredDataEventLog> {
+        blobs.release_owner(owner);
+        let checkpoints = self
+            .checkpoints
+            .iter()
+            .map(|data| blobs.put_json(owner, data))
+            .collect::<io::Result<Vec<BlobId>>>()?;
+        blobs.flush();
+
+        Ok(StoredDataEventLog {
+            events: self.events.clone(),
+            checkpoints,
+            limit: self.limit,
+            checkpoint_interval: self.checkpoint_interval,
+        })
+    }
+
+    /// Load a log saved with [`DataEventLog::store`], none if any of its checkpoints is missing
+    pub fn from_stored(stored: StoredDataEventLog, blobs: &BlobStore) -> Option<Self> {
+        let checkpoints = stored
+            .checkpoints
+            .iter()
+            .map(|id| blobs.get_json(id))
+            .collect::<Option<Vec<StateData>>>()?;
+
+        Some(Self {
+            events: stored.events,
+            checkpoints,
+            limit: stored.limit,
+            checkpoint_interval: stored.checkpoint_interval.max(1),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DataAction, DataEventLog};
+    use crate::filesystems::EolPolicy;
+    use crate::states::{StateData, StateDataField};
+
+    #[test]
+    fn rebuild_past_revisions() {
+        let mut log = DataEventLog::new(4, 2);
+        let mut data = StateData::default();
+
+        let policies = [
+            EolPolicy::ForceLf,
+            EolPolicy::ForceCrlf,
+            EolPolicy::Preserve,
+            EolPolicy::ForceLf,
+            EolPolicy::ForceCrlf,
+        ];
+        for eol_policy in policies {
+            let new = StateData {
+                revision: data.revision + 1,
+                eol_policy,
+                ..data.clone()
+            };
+            let action = DataAction::from_change(&data, &new, &[StateDataField::EolPolicy]);
+            assert_eq!(
+                action,
+                DataAction::SettingChanged {
+                    field: StateDataField::EolPolicy
+                }
+            );
+            log.record(action, &data, &new);
+            data = new;
+        }
+
+        assert_eq!(log.get_latest_revision(), Some(5));
+        assert_eq!(log.get_latest_data(), Some(data));
+        assert_eq!(
+            log.get_data_at(3).map(|data| data.eol_policy),
+            Some(EolPolicy::Preserve)
+        );
+
+        // The oldest events were dropped with their checkpoint
+        assert_eq!(log.get_data_at(1), None);
+        assert_eq!(log.get_events_since(0).len(), 3);
+        assert_eq!(log.get_checkpoints_count(), 2);
+    }
+}
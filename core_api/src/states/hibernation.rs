@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// What a hibernated State was running, so it can be started again once it's awakened
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Hibernation {
+    /// Watched repositories, by filesystem and path
+    pub repositories: Vec<(String, String)>,
+    /// Extensions reloaded when their files change
+    pub watched_extensions: Vec<String>,
+    /// Managed language servers, by configuration ID and root URI
+    pub language_servers: Vec<(String, String)>,
+    /// Workspaces whose configuration is reloaded when it changes, by filesystem and root
+    pub watched_workspaces: Vec<(String, String)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Hibernation;
+
+    #[test]
+    fn serialize_hibernation() {
+        let hibernation = Hibernation {
+            repositories: vec![("local".to_owned(), "/project".to_owned())],
+            watched_extensions: vec!["rust".to_owned()],
+            language_servers: vec![("rust-analyzer".to_owned(), "file:///project".to_owned())],
+            watched_workspaces: Vec::new(),
+        };
+
+        let serialized = serde_json::to_string(&hibernation).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Hibernation>(&serialized).unwrap(),
+            hibernation
+        );
+    }
+}
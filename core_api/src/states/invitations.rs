@@ -0,0 +1,88 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// What a guest is allowed to do in a State
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvitationAccess {
+    /// Only read and follow what the host does
+    Follow,
+    /// Also modify files and the State
+    Edit,
+}
+
+/// A time-limited token that grants a guest access to a State
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Invitation {
+    pub id: String,
+    pub token: String,
+    pub state_id: u8,
+    pub access: InvitationAccess,
+    /// Unix timestamp (seconds) from which the invitation is no longer valid
+    pub expires_at: u64,
+}
+
+/// Seconds since the Unix epoch
+pub(crate) fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+impl Invitation {
+    /// Create a new invitation valid for the given time
+    pub fn new(state_id: u8, access: InvitationAccess, duration: Duration) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            token: Uuid::new_v4().to_string(),
+            state_id,
+            access,
+            expires_at: now_secs() + duration.as_secs(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        now_secs() >= self.expires_at
+    }
+
+    /// Build the link a guest can open to join
+    ///
+    /// # Arguments
+    ///
+    /// * `host`   - Base URL of the host, e.g `http://192.168.1.40:8080`
+    ///
+    pub fn to_url(&self, host: &str) -> String {
+        format!(
+            "{}/?state_id={}&token={}",
+            host.trim_end_matches('/'),
+            self.state_id,
+            self.token
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Invitation, InvitationAccess};
+
+    #[test]
+    fn invitation_links() {
+        let invitation = Invitation::new(1, InvitationAccess::Follow, Duration::from_secs(60));
+
+        assert!(!invitation.is_expired());
+        assert_eq!(
+            invitation.to_url("http://localhost:8080/"),
+            format!(
+                "http://localhost:8080/?state_id=1&token={}",
+                invitation.token
+            )
+        );
+
+        let expired = Invitation::new(1, InvitationAccess::Edit, Duration::from_secs(0));
+        assert!(expired.is_expired());
+    }
+}
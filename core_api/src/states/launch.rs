@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::auth::AuthConfig;
+use crate::extensions::manager::ExtensionsManager;
+use crate::filesystems::{Filesystem, LocalFilesystem, MemoryFilesystem};
+use crate::language_servers::{LanguageServerConfig, LanguageServersPool};
+use crate::state_persistors::file::FilePersistor;
+use crate::state_persistors::memory::MemoryPersistor;
+use crate::state_persistors::Persistor;
+use crate::State;
+
+/// Prefix of the environment variables read on launch, e.g `GRAVITON_TOKENS`
+pub const LAUNCH_ENV_PREFIX: &str = "GRAVITON_";
+
+/// Launch configuration errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum LaunchErrors {
+    BadConfig(String),
+    UnknownPersistor(String),
+    UnknownFilesystem(String),
+}
+
+/// Where a launched State persists it's data
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LaunchPersistor {
+    /// Nothing is persisted
+    #[default]
+    Memory,
+    /// A JSON file, e.g the desktop's settings file
+    File { path: PathBuf },
+}
+
+impl LaunchPersistor {
+    /// Parse `memory` or `file:<path>`
+    fn parse(value: &str) -> Result<Self, LaunchErrors> {
+        match value.split_once(':') {
+            Some(("file", path)) if !path.is_empty() => Ok(Self::File {
+                path: PathBuf::from(path),
+            }),
+            None if value == "memory" => Ok(Self::Memory),
+            _ => Err(LaunchErrors::UnknownPersistor(value.to_owned())),
+        }
+    }
+
+    fn build(&self) -> Box<dyn Persistor + Send> {
+        match self {
+            Self::Memory => Box::new(MemoryPersistor::new()),
+            Self::File { path } => Box::new(FilePersistor::new(path.clone())),
+        }
+    }
+}
+
+/// A filesystem a launched State starts with, registered with the given name
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LaunchFilesystem {
+    Local { name: String },
+    Memory { name: String },
+}
+
+impl LaunchFilesystem {
+    /// Parse `<kind>` or `<name>=<kind>`, e.g `local` or `scratch=memory`
+    fn parse(value: &str) -> Result<Self, LaunchErrors> {
+        let (name, kind) = value.split_once('=').unwrap_or((value, value));
+        let name = name.to_owned();
+        match kind {
+            "local" => Ok(Self::Local { name }),
+            "memory" => Ok(Self::Memory { name }),
+            _ => Err(LaunchErrors::UnknownFilesystem(kind.to_owned())),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::Local { name } | Self::Memory { name } => name,
+        }
+    }
+
+    fn build(&self) -> Box<dyn Filesystem + Send> {
+        match self {
+            Self::Local { .. } => Box::new(LocalFilesystem::new()),
+            Self::Memory { .. } => Box::new(MemoryFilesystem::new()),
+        }
+    }
+}
+
+/// A managed language server launched before any document is opened, see [`LanguageServersPool`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LaunchLanguageServer {
+    pub config: LanguageServerConfig,
+    /// Workspace it's launched for
+    pub root_uri: String,
+}
+
+fn parse_list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+/// What a new State starts with, so embedders and the headless server can compose
+/// their states declaratively. It's read from, by priority:
+///
+/// 1. The command line, e.g `--tokens=abc --filesystems=local,scratch=memory`
+/// 2. The environment, e.g `GRAVITON_PERSISTOR=file:/home/user/state.json`
+/// 3. A TOML (or JSON) file passed with `--config=<path>` or `GRAVITON_CONFIG`
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct LaunchConfig {
+    pub state_id: u8,
+    pub persistor: LaunchPersistor,
+    /// Filesystems registered in the State, it keeps the default ones if there are none
+    pub filesystems: Option<Vec<LaunchFilesystem>>,
+    /// Tokens with full access to the State
+    pub tokens: Vec<String>,
+    /// IDs of the loaded extensions that are kept, all of them if there are none
+    pub extensions: Option<Vec<String>>,
+    /// Language servers warmed on launch, only read from the configuration file
+    pub language_servers: Vec<LaunchLanguageServer>,
+    /// How remote users log in, only read from the configuration file
+    pub auth: Option<AuthConfig>,
+}
+
+impl Default for LaunchConfig {
+    fn default() -> Self {
+        Self {
+            state_id: 1,
+            persistor: LaunchPersistor::default(),
+            filesystems: None,
+            tokens: Vec::new(),
+            extensions: None,
+            language_servers: Vec::new(),
+            auth: None,
+        }
+    }
+}
+
+impl LaunchConfig {
+    /// Parse a configuration file, TOML unless it's a JSON file
+    pub fn parse(path: &str, content: &str) -> Result<Self, LaunchErrors> {
+        if path.ends_with(".json") {
+            serde_json::from_str(content).map_err(|err| LaunchErrors::BadConfig(err.to_string()))
+        } else {
+            toml::from_str(content).map_err(|err| LaunchErrors::BadConfig(err.to_string()))
+        }
+    }
+
+    /// Read the configuration of the running process, see [`LaunchConfig::from_sources`]
+    pub fn from_environment() -> Result<Self, LaunchErrors> {
+        let args = std::env::args().skip(1).collect::<Vec<String>>();
+        let vars = std::env::vars().collect::<HashMap<String, String>>();
+        Self::from_sources(&args, &vars)
+    }
+
+    /// Merge the configuration file, the environment variables and the command line arguments
+    ///
+    /// # Arguments
+    ///
+    /// * `args`   - Command line arguments, the unknown ones are ignored
+    /// * `vars`   - Environment variables, only the ones prefixed with `GRAVITON_` are used
+    ///
+    pub fn from_sources(
+        args: &[String],
+        vars: &HashMap<String, String>,
+    ) -> Result<Self, LaunchErrors> {
+        // `--state-id=2` is the same as `GRAVITON_STATE_ID=2`
+        let args = args
+            .iter()
+            .filter_map(|arg| arg.strip_prefix("--")?.split_once('='))
+            .map(|(key, value)| (key.replace('-', "_").to_uppercase(), value.to_owned()))
+            .collect::<HashMap<String, String>>();
+        let get = |key: &str| {
+            args.get(key)
+                .or_else(|| vars.get(&format!("{}{}", LAUNCH_ENV_PREFIX, key)))
+        };
+
+        let mut config = match get("CONFIG") {
+            Some(path) => {
+                let content = std::fs::read_to_string(path)
+                    .map_err(|err| LaunchErrors::BadConfig(err.to_string()))?;
+                Self::parse(path, &content)?
+            }
+            None => Self::default(),
+        };
+
+        if let Some(state_id) = get("STATE_ID") {
+            config.state_id = state_id
+                .parse()
+                .map_err(|_| LaunchErrors::BadConfig(format!("Invalid state ID <{}>", state_id)))?;
+        }
+        if let Some(persistor) = get("PERSISTOR") {
+            config.persistor = LaunchPersistor::parse(persistor)?;
+        }
+        if let Some(filesystems) = get("FILESYSTEMS") {
+            config.filesystems = Some(
+                parse_list(filesystems)
+                    .map(LaunchFilesystem::parse)
+                    .collect::<Result<Vec<LaunchFilesystem>, LaunchErrors>>()?,
+            );
+        }
+        if let Some(tokens) = get("TOKENS") {
+            config.tokens = parse_list(tokens).map(str::to_owned).collect();
+        }
+        if let Some(extensions) = get("EXTENSIONS") {
+            config.extensions = Some(parse_list(extensions).map(str::to_owned).collect());
+        }
+
+        Ok(config)
+    }
+
+    /// Create the State described by the configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `extensions_manager`   - Extensions already loaded, the ones not listed are unloaded
+    ///
+    pub async fn build_state(&self, extensions_manager: ExtensionsManager) -> State {
+        let mut state = State::new(self.state_id, extensions_manager, self.persistor.build());
+
+        if let Some(filesystems) = &self.filesystems {
+            state.filesystems.clear();
+            for filesystem in filesystems {
+                state.register_filesystem(filesystem.get_name(), filesystem.build());
+            }
+        }
+
+        for token in &self.tokens {
+            if !state.tokens.contains(token) {
+                state.tokens.push(token.clone());
+            }
+        }
+
+        if !self.language_servers.is_empty() {
+            let pool = LanguageServersPool::new();
+            for server in &self.language_servers {
+                state.register_language_server_config(server.config.clone());
+                pool.warm(server.config.clone(), &server.root_uri);
+            }
+            state = state.with_language_servers_pool(pool);
+        }
+
+        if let Some(extensions) = &self.extensions {
+            let loaded = state.extensions_manager.get_manifest_ids();
+            for extension_id in extensions.iter().filter(|id| !loaded.contains(id)) {
+                warn!("Extension <{}> is not loaded", extension_id);
+            }
+            for extension_id in loaded.iter().filter(|id| !extensions.contains(id)) {
+                state.extensions_manager.unregister(extension_id).await;
+            }
+        }
+
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use super::{LaunchConfig, LaunchErrors, LaunchFilesystem, LaunchPersistor};
+    use crate::extensions::manager::ExtensionsManager;
+
+    #[tokio::test]
+    async fn compose_state_on_launch() {
+        let file = LaunchConfig::parse(
+            "launch.toml",
+            r#"
+            state_id = 3
+            tokens = ["from_file"]
+
+            [persistor]
+            kind = "file"
+            path = "/tmp/state.json"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.state_id, 3);
+        assert_eq!(
+            file.persistor,
+            LaunchPersistor::File {
+                path: PathBuf::from("/tmp/state.json")
+            }
+        );
+
+        // The command line wins over the environment
+        let args = vec![
+            "--headless".to_owned(),
+            "--tokens=abc, def".to_owned(),
+            "--filesystems=scratch=memory".to_owned(),
+        ];
+        let vars = HashMap::from([
+            ("GRAVITON_TOKENS".to_owned(), "ignored".to_owned()),
+            ("GRAVITON_STATE_ID".to_owned(), "2".to_owned()),
+            ("GRAVITON_EXTENSIONS".to_owned(), "".to_owned()),
+        ]);
+        let config = LaunchConfig::from_sources(&args, &vars).unwrap();
+        assert_eq!(config.state_id, 2);
+        assert_eq!(config.tokens, vec!["abc".to_owned(), "def".to_owned()]);
+        assert_eq!(
+            config.filesystems,
+            Some(vec![LaunchFilesystem::Memory {
+                name: "scratch".to_owned()
+            }])
+        );
+
+        let state = config.build_state(ExtensionsManager::default()).await;
+        assert_eq!(state.data.id, 2);
+        assert!(state.has_token("def"));
+        assert!(state.get_fs_by_name("scratch").is_some());
+        assert!(state.get_fs_by_name("local").is_none());
+
+        let wrong = HashMap::from([("GRAVITON_PERSISTOR".to_owned(), "sqlite".to_owned())]);
+        assert_eq!(
+            LaunchConfig::from_sources(&[], &wrong),
+            Err(LaunchErrors::UnknownPersistor("sqlite".to_owned()))
+        );
+    }
+}
@@ -1,7 +1,29 @@
 mod data;
+mod delta;
+mod event_log;
+mod hibernation;
+mod invitations;
+mod launch;
+mod resources;
+mod shutdown;
+mod snapshots;
 mod state;
 mod states_list;
+mod subscriptions;
+mod tokens;
+mod transactions;
 
 pub use data::*;
+pub use delta::*;
+pub use event_log::*;
+pub use hibernation::*;
+pub use invitations::*;
+pub use launch::*;
+pub use resources::*;
+pub use shutdown::*;
+pub use snapshots::*;
 pub use state::*;
 pub use states_list::*;
+pub use subscriptions::*;
+pub use tokens::*;
+pub use transactions::*;
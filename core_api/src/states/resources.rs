@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+/// Approximate bytes held in memory by the caches of a State
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct CachesMemory {
+    /// Content of the opened documents
+    pub documents: usize,
+    /// Unsaved content kept for the session recovery
+    pub drafts: usize,
+    /// Latest output of the terminals and output channels
+    pub output_buffers: usize,
+    /// Files and symbols of the watched workspaces
+    pub indexer: usize,
+    /// Previous versions of the data
+    pub snapshots: usize,
+}
+
+impl CachesMemory {
+    pub fn get_total(&self) -> usize {
+        self.documents + self.drafts + self.output_buffers + self.indexer + self.snapshots
+    }
+}
+
+/// What a State is holding, so the heaviest of many States can be found
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResourceReport {
+    pub state_id: u8,
+    pub hibernated: bool,
+    pub memory: CachesMemory,
+    /// Watched repositories, extensions and workspaces
+    pub watchers: usize,
+    /// Documents opened as text or with custom editors
+    pub open_documents: usize,
+    /// Terminal shells, running tasks and kernels
+    pub running_processes: usize,
+    /// Language servers of extensions and the ones managed by the Core
+    pub language_servers: usize,
+    /// Searches and file operations in progress
+    pub running_operations: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CachesMemory;
+
+    #[test]
+    fn caches_memory_total() {
+        assert_eq!(CachesMemory::default().get_total(), 0);
+
+        let memory = CachesMemory {
+            documents: 1024,
+            drafts: 512,
+            output_buffers: 256,
+            indexer: 128,
+            snapshots: 64,
+        };
+        assert_eq!(memory.get_total(), 1984);
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// A step of the shutdown, every subsystem is stopped before the ones it depends on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShutdownPhase {
+    /// Keep the unsaved documents as drafts
+    FlushDocuments,
+    /// Stop the language servers and the kernels
+    StopLanguageServers,
+    /// Kill the terminal shells and cancel the running tasks, searches and file operations
+    KillTerminals,
+    /// Save the States data with their persistors
+    PersistState,
+    /// Disconnect the clients
+    CloseTransports,
+}
+
+impl ShutdownPhase {
+    /// Every phase, in the order they are run
+    pub const ALL: [ShutdownPhase; 5] = [
+        ShutdownPhase::FlushDocuments,
+        ShutdownPhase::StopLanguageServers,
+        ShutdownPhase::KillTerminals,
+        ShutdownPhase::PersistState,
+        ShutdownPhase::CloseTransports,
+    ];
+}
+
+/// How long every phase of the shutdown can take before it's skipped
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownConfig {
+    pub default_timeout: Duration,
+    pub timeouts: HashMap<ShutdownPhase, Duration>,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            default_timeout: Duration::from_secs(5),
+            timeouts: HashMap::new(),
+        }
+    }
+}
+
+impl ShutdownConfig {
+    pub fn new(default_timeout: Duration) -> Self {
+        Self {
+            default_timeout,
+            timeouts: HashMap::new(),
+        }
+    }
+
+    /// Give a phase a different timeout, e.g more time to stop slow language servers
+    pub fn with_timeout(mut self, phase: ShutdownPhase, timeout: Duration) -> Self {
+        self.timeouts.insert(phase, timeout);
+        self
+    }
+
+    pub fn get_timeout(&self, phase: ShutdownPhase) -> Duration {
+        self.timeouts
+            .get(&phase)
+            .copied()
+            .unwrap_or(self.default_timeout)
+    }
+}
+
+/// How a phase of the shutdown went
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PhaseReport {
+    pub phase: ShutdownPhase,
+    pub elapsed_ms: u64,
+    /// States that didn't finish the phase in time
+    pub timed_out_states: Vec<u8>,
+}
+
+impl PhaseReport {
+    pub fn timed_out(&self) -> bool {
+        !self.timed_out_states.is_empty()
+    }
+}
+
+/// How the whole shutdown went, sent to the clients with `ServerMessages::ShutdownComplete`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct ShutdownReport {
+    pub phases: Vec<PhaseReport>,
+}
+
+impl ShutdownReport {
+    /// Check if every phase finished in time
+    pub fn is_clean(&self) -> bool {
+        !self.phases.iter().any(PhaseReport::timed_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{PhaseReport, ShutdownConfig, ShutdownPhase, ShutdownReport};
+
+    #[test]
+    fn phases_timeouts() {
+        let config = ShutdownConfig::new(Duration::from_secs(2))
+            .with_timeout(ShutdownPhase::StopLanguageServers, Duration::from_secs(10));
+
+        assert_eq!(
+            config.get_timeout(ShutdownPhase::StopLanguageServers),
+            Duration::from_secs(10)
+        );
+        for phase in ShutdownPhase::ALL {
+            if phase != ShutdownPhase::StopLanguageServers {
+                assert_eq!(config.get_timeout(phase), Duration::from_secs(2));
+            }
+        }
+    }
+
+    #[test]
+    fn clean_shutdowns() {
+        let mut report = ShutdownReport {
+            phases: ShutdownPhase::ALL
+                .iter()
+                .map(|phase| PhaseReport {
+                    phase: *phase,
+                    elapsed_ms: 10,
+                    timed_out_states: Vec::new(),
+                })
+                .collect(),
+        };
+        assert!(report.is_clean());
+
+        // A single State not finishing a phase in time is enough
+        report.phases[1].timed_out_states.push(3);
+        assert!(report.phases[1].timed_out());
+        assert!(!report.is_clean());
+    }
+}
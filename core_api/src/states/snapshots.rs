@@ -0,0 +1,240 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use super::invitations::now_secs;
+use super::StateData;
+use crate::blobs::{BlobId, BlobStore};
+
+/// How many snapshots are kept by default
+pub const DEFAULT_SNAPSHOTS_LIMIT: usize = 50;
+
+/// A copy of the StateData at some point
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StateSnapshot {
+    pub label: String,
+    /// Unix timestamp (seconds) of when it was taken
+    pub created_at: u64,
+    pub data: StateData,
+}
+
+impl StateSnapshot {
+    pub fn new(label: &str, data: StateData) -> Self {
+        Self {
+            label: label.to_owned(),
+            created_at: now_secs(),
+            data,
+        }
+    }
+}
+
+/// A snapshot as it's persisted, its data is kept in a blob store
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct StoredSnapshot {
+    label: String,
+    created_at: u64,
+    blob: BlobId,
+}
+
+/// A [`SnapshotsHistory`] as it's persisted, see [`SnapshotsHistory::store`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StoredSnapshotsHistory {
+    undo: Vec<StoredSnapshot>,
+    redo: Vec<StoredSnapshot>,
+    limit: usize,
+}
+
+fn store_snapshots(
+    snapshots: &[StateSnapshot],
+    blobs: &BlobStore,
+    owner: &str,
+) -> io::Result<Vec<StoredSnapshot>> {
+    snapshots
+        .iter()
+        .map(|snapshot| {
+            Ok(StoredSnapshot {
+                label: snapshot.label.clone(),
+                created_at: snapshot.created_at,
+                blob: blobs.put_json(owner, &snapshot.data)?,
+            })
+        })
+        .collect()
+}
+
+fn load_snapshots(snapshots: Vec<StoredSnapshot>, blobs: &BlobStore) -> Option<Vec<StateSnapshot>> {
+    snapshots
+        .into_iter()
+        .map(|snapshot| {
+            Some(StateSnapshot {
+                data: blobs.get_json(&snapshot.blob)?,
+                label: snapshot.label,
+                created_at: snapshot.created_at,
+            })
+        })
+        .collect()
+}
+
+/// Previous versions of the StateData that can be restored
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotsHistory {
+    undo: Vec<StateSnapshot>,
+    redo: Vec<StateSnapshot>,
+    limit: usize,
+}
+
+impl Default for SnapshotsHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_SNAPSHOTS_LIMIT)
+    }
+}
+
+impl SnapshotsHistory {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            limit: limit.max(1),
+        }
+    }
+
+    /// Save a snapshot, the oldest ones are dropped once the limit is reached
+    pub fn push(&mut self, snapshot: StateSnapshot) {
+        self.redo.clear();
+        self.undo.push(snapshot);
+        if self.undo.len() > self.limit {
+            let exceeding = self.undo.len() - self.limit;
+            self.undo.drain(..exceeding);
+        }
+    }
+
+    /// Go back to the last snapshot, `current` can be restored with [`SnapshotsHistory::redo`]
+    pub fn undo(&mut self, current: StateData) -> Option<StateData> {
+        let snapshot = self.undo.pop()?;
+        self.redo.push(StateSnapshot {
+            data: current,
+            ..snapshot.clone()
+        });
+        Some(snapshot.data)
+    }
+
+    /// Restore the last undone snapshot
+    pub fn redo(&mut self, current: StateData) -> Option<StateData> {
+        let snapshot = self.redo.pop()?;
+        self.undo.push(StateSnapshot {
+            data: current,
+            ..snapshot.clone()
+        });
+        Some(snapshot.data)
+    }
+
+    /// Approximate bytes held by the snapshots, measured as JSON
+    pub fn get_size(&self) -> usize {
+        self.undo
+            .iter()
+            .chain(&self.redo)
+            .filter_map(|snapshot| serde_json::to_vec(&snapshot.data).ok())
+            .map(|data| data.len())
+            .sum()
+    }
+
+    /// Snapshots that can be undone, from the oldest to the newest
+    pub fn get_snapshots(&self) -> &[StateSnapshot] {
+        &self.undo
+    }
+
+    /// Find the data as it was on a revision
+    pub fn find_revision(&self, revision: u64) -> Option<&StateData> {
+        self.undo
+            .iter()
+            .chain(self.redo.iter())
+            .map(|snapshot| &snapshot.data)
+            .find(|data| data.revision == revision)
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Move the data of the snapshots to a blob store, so the versions shared with other
+    /// snapshots or subsystems are only stored once. The blobs previously referenced by `owner` are released
+    pub fn store(&self, blobs: &BlobStore, owner: &str) -> io::Result<StoredSnapshotsHistory> {
+        blobs.release_owner(owner);
+        let stored = StoredSnapshotsHistory {
+            undo: store_snapshots(&self.undo, blobs, owner)?,
+            redo: store_snapshots(&self.redo, blobs, owner)?,
+            limit: self.limit,
+        };
+        blobs.flush();
+        Ok(stored)
+    }
+
+    /// Load a history saved with [`SnapshotsHistory::store`], none if any of its blobs is missing
+    pub fn from_stored(stored: StoredSnapshotsHistory, blobs: &BlobStore) -> Option<Self> {
+        Some(Self {
+            undo: load_snapshots(stored.undo, blobs)?,
+            redo: load_snapshots(stored.redo, blobs)?,
+            limit: stored.limit.max(1),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SnapshotsHistory, StateSnapshot};
+    use crate::blobs::BlobStore;
+    use crate::states::StateData;
+
+    fn data(id: u8) -> StateData {
+        StateData {
+            id,
+            ..StateData::default()
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_snapshots() {
+        let mut history = SnapshotsHistory::new(2);
+
+        history.push(StateSnapshot::new("first", data(1)));
+        history.push(StateSnapshot::new("second", data(2)));
+        history.push(StateSnapshot::new("third", data(3)));
+
+        // The first one was dropped
+        assert_eq!(history.get_snapshots().len(), 2);
+        assert_eq!(history.get_snapshots()[0].label, "second");
+
+        assert_eq!(history.undo(data(4)), Some(data(3)));
+        assert_eq!(history.undo(data(3)), Some(data(2)));
+        assert_eq!(history.undo(data(2)), None);
+
+        assert_eq!(history.redo(data(2)), Some(data(3)));
+        assert_eq!(history.redo(data(3)), Some(data(4)));
+        assert!(!history.can_redo());
+
+        // New snapshots discard what could be redone
+        history.undo(data(4));
+        history.push(StateSnapshot::new("fourth", data(5)));
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn store_snapshots_as_blobs() {
+        let blobs = BlobStore::new();
+        let mut history = SnapshotsHistory::new(5);
+        history.push(StateSnapshot::new("first", data(1)));
+        history.push(StateSnapshot::new("second", data(1)));
+        history.push(StateSnapshot::new("third", data(2)));
+        history.undo(data(3));
+
+        // The first two snapshots share their data
+        history.store(&blobs, "snapshots").unwrap();
+        assert_eq!(blobs.get_stats().blobs, 2);
+        assert_eq!(blobs.get_stats().references["snapshots"], 3);
+
+        // Storing it again replaces the previous references
+        let stored = history.store(&blobs, "snapshots").unwrap();
+        assert_eq!(blobs.get_stats().references["snapshots"], 3);
+
+        assert_eq!(SnapshotsHistory::from_stored(stored, &blobs), Some(history));
+    }
+}
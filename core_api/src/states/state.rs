@@ -1,19 +1,104 @@
+use crate::changes::{ChangeEntry, ChangesFeed, ChangesFilter};
+use crate::clipboard::{
+    extract_osc52, Clipboard, ClipboardEntry, ClipboardErrors, ClipboardOrigin, ClipboardSettings,
+    CLIPBOARD_MAX_SIZE_SETTING, CLIPBOARD_SYNC_SETTING,
+};
+use crate::decorations::{DecorationRegistry, FileDecoration};
+use crate::documents::{
+    Document, DocumentEdit, DocumentErrors, DocumentInfo, Documents, TextRange,
+};
+use crate::experiments::{
+    ExperimentFlag, ExperimentStatus, CORE_EXPERIMENTS_OWNER, EXPERIMENTS_SETTING_PREFIX,
+};
+use crate::explorer::{build_items, is_excluded, ExplorerListing};
 use crate::extensions::base::ExtensionInfo;
-use crate::extensions::manager::{ExtensionsManager, LoadedExtension};
-use crate::filesystems::{Filesystem, LocalFilesystem};
-use crate::language_servers::{LanguageServerBuilder, LanguageServerBuilderInfo};
+use crate::extensions::commands::CommandInfo;
+use crate::extensions::editors::{
+    CustomDocumentInfo, CustomEditor, CustomEditorAction, OpenedFile,
+};
+use crate::extensions::manager::ExtensionsManager;
+use crate::extensions::messages::ExtensionMessage;
+use crate::extensions::permissions::{ExtensionPermissions, Permission};
+use crate::extensions::supervisor::{
+    run_isolated, ExtensionHealth, ExtensionMetrics, ExtensionStatus, PanicPolicy,
+};
+use crate::filesystems::{
+    get_format_from_path, nest_items, remap_path, DirItemInfo, EolPolicy, FileChunk, FileEncoding,
+    FileFormat, FileInfo, Filesystem, FilesystemCapabilities, GravitonUri, LineEnding,
+    LocalFilesystem, MemoryFilesystem,
+};
+#[cfg(feature = "archives")]
+use crate::filesystems::{ArchiveFilesystem, ArchiveKind};
+#[cfg(feature = "ftp")]
+use crate::filesystems::{FtpFilesystem, FtpSettings};
+#[cfg(feature = "http")]
+use crate::http::HttpClient;
+use crate::http::HttpSettings;
+use crate::icons::{get_fallback_icon, FileIcon, IconTheme};
+use crate::indexer::{IndexedSymbol, Indexer, QuickOpenItem, QUICK_OPEN_LIMIT};
+#[cfg(feature = "kernels")]
+use crate::kernels::{get_runtime_dir, Kernel, KernelErrors, KernelSpec};
+#[cfg(feature = "installer")]
+use crate::language_servers::{get_installed, LanguageServerInstaller};
+use crate::language_servers::{
+    path_to_uri, LanguageServerBuilder, LanguageServerBuilderInfo, LanguageServerConfig,
+    LanguageServersManager, LanguageServersPool,
+};
+use crate::locale::ClientLocale;
+use crate::matcher::{FuzzyMatcher, MatchKind, Ranked, RecentItems, MATCHER_WEIGHTS_SETTING};
 use crate::messaging::{ClientMessages, ServerMessages};
+use crate::metrics::METRICS;
+use crate::modal_editing::{ModalEngine, ModalOutput};
+use crate::output_buffers::OutputBuffers;
+use crate::progress::{ProgressHandle, ProgressInfo, ProgressKind};
+use crate::recovery::{Draft, RecoveredSession, SessionRecovery};
+use crate::refactoring::RenamePreview;
+use crate::save_hooks::{
+    get_code_actions_edits, get_organize_imports_params, insert_final_newline, replace_content,
+    run_formatter, trim_trailing_whitespace, SaveHookAction, SaveHookErrors, SaveOptions,
+};
+use crate::search::{build_globset, CancellationToken, Search, SearchErrors, SearchOptions};
+use crate::secrets::SecretsStore;
+use crate::settings::{Keybinding, SettingSchema, SettingsService, UserSettings};
 pub use crate::state_persistors::memory::MemoryPersistor;
 use crate::state_persistors::Persistor;
+use crate::tasks::{
+    RegisteredTask, ScheduledTask, TaskDefinition, TaskErrors, TaskRun, TaskRunInfo, TaskRunner,
+};
+use crate::telemetry::{ExtensionHandler, FilesystemOperation, TelemetryConfig, TelemetryReport};
 use crate::terminal_shells::{TerminalShell, TerminalShellBuilder, TerminalShellBuilderInfo};
-use crate::{Errors, ExtensionErrors, LanguageServer, ManifestInfo};
-use std::collections::HashMap;
+use crate::tree_views::{TreeDataProvider, TreeItem, TreeViewInfo, TreeViewRegistry};
+use crate::validation::{Diagnostic, ValidationSchema};
+use crate::vcs::{GitCli, VcsErrors, VcsProvider};
+use crate::workspaces::{
+    find_config, get_workspace_id, get_workspace_task_id, Workspace, WorkspaceConfig,
+    WorkspaceErrors,
+};
+use crate::{
+    Errors, ExperimentsErrors, ExtensionErrors, FilesystemErrors, LanguageServer,
+    LanguageServerErrors, ManifestInfo, RefactorErrors,
+};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{broadcast, Mutex};
 use tracing::{info, warn};
+use uuid::Uuid;
 
-use super::StateData;
+use super::closed_tabs::ClosedTab;
+use super::views::TabData;
+use super::{
+    now_secs, CachesMemory, CommandGroup, CommandGroupInfo, DataAction, DataEvent, DataEventLog,
+    DeltaSync, Hibernation, Invitation, InvitationAccess, ResourceReport, ScopedToken,
+    ShutdownPhase, SnapshotsHistory, StateData, StateDataField, StateDataMerge, StateDataUpdate,
+    StateEvent, StateSnapshot, StateSubscriptions, TokenScope, TransactionErrors,
+    COMMAND_GROUP_TIMEOUT,
+};
+
+type FilesystemHandle = Arc<Mutex<Box<dyn Filesystem + Send>>>;
 
 /// A State (similar to a profile) holds persisted data (configuration)
 /// but also runtime data such as active Terminals or running Language Servers
@@ -34,6 +119,12 @@ pub struct State {
     /// Tokens allowed to use this State
     pub tokens: Vec<String>,
 
+    /// Invitations given to guests
+    pub invitations: HashMap<String, Invitation>,
+
+    /// Tokens with limited access, by ID
+    pub scoped_tokens: HashMap<String, ScopedToken>,
+
     // Registered Language Servers
     pub language_server_builders:
         HashMap<String, Arc<Mutex<Box<dyn LanguageServerBuilder + Send + Sync>>>>,
@@ -41,12 +132,125 @@ pub struct State {
     // Active Language Servers
     pub language_servers: HashMap<String, Arc<Mutex<Box<dyn LanguageServer + Send + Sync>>>>,
 
+    /// Language Servers launched and supervised by the Core
+    pub language_servers_manager: LanguageServersManager,
+
     // Registered shells
     pub terminal_shell_builders:
         HashMap<String, Arc<Mutex<Box<dyn TerminalShellBuilder + Send + Sync>>>>,
 
     // Active Shells
     pub terminal_shells: HashMap<String, Arc<Box<dyn TerminalShell + Send + Sync>>>,
+
+    /// Documents opened for incremental editing
+    pub documents: Documents,
+
+    /// Files opened with the custom editors of extensions, by filesystem and path
+    pub custom_documents: HashMap<(String, String), CustomDocumentInfo>,
+
+    /// Modal editing state of every view
+    pub modal_engines: HashMap<String, ModalEngine>,
+
+    /// Tree views contributed by extensions
+    pub tree_views: TreeViewRegistry,
+
+    /// What was last copied by the clients or remotely
+    clipboard: Clipboard,
+
+    /// Commands being applied as a single undoable change
+    command_group: Option<CommandGroup>,
+
+    /// File decorations contributed by extensions
+    pub decorations: DecorationRegistry,
+
+    /// Running searches, shared with them so they are removed once they finish
+    pub searches: Arc<std::sync::Mutex<HashMap<String, CancellationToken>>>,
+
+    /// Running file operations, like copying folders
+    pub file_operations: HashMap<String, CancellationToken>,
+
+    /// Latest output of the terminals and output channels
+    pub output_buffers: OutputBuffers,
+
+    /// Keeps the drafts and knows if the last session crashed
+    pub session_recovery: Option<Arc<SessionRecovery>>,
+
+    /// Unsaved content of files
+    pub drafts: Vec<Draft>,
+
+    /// Version control providers by the name of the filesystem they support
+    pub vcs_providers: HashMap<String, Arc<dyn VcsProvider + Send + Sync>>,
+
+    /// Repositories being watched for changes
+    pub repository_watchers: HashMap<String, CancellationToken>,
+
+    /// Filesystem extensions reloaded when their files change
+    pub extension_watchers: HashMap<String, CancellationToken>,
+
+    /// Opened folders with their configuration, by filesystem and root
+    workspaces: HashMap<String, Workspace>,
+
+    /// Workspaces whose configuration is reloaded when it changes
+    pub workspace_watchers: HashMap<String, CancellationToken>,
+
+    /// What was recovered from a crashed session, until the client is told about it
+    recovered_session: Option<RecoveredSession>,
+
+    /// What to start again once the State is awakened, if it's hibernated
+    hibernation: Option<Hibernation>,
+
+    /// Last time a client used the State
+    last_activity: Instant,
+
+    /// Previous versions of the data that can be restored
+    snapshots: SnapshotsHistory,
+
+    /// Changes made to the data, to rebuild it as it was on a past revision
+    event_log: DataEventLog,
+
+    /// What the clients were last sent of the data
+    delta_sync: DeltaSync,
+
+    /// Listeners of the changes made to the State
+    subscriptions: StateSubscriptions,
+
+    /// Settings and keybindings of the user
+    settings: SettingsService,
+
+    /// Commands invoked recently, they rank higher in the command palette
+    recent_commands: RecentItems,
+
+    /// Files opened recently, they rank higher in the quick-open
+    recent_files: RecentItems,
+
+    /// Files and symbols of the watched workspaces
+    indexer: Indexer,
+
+    /// Recent changes of the filesystems, saves and repositories
+    changes: ChangesFeed,
+
+    /// Icons shown in the directory listings
+    icon_theme: Option<IconTheme>,
+
+    /// Locale and formats declared by the clients, by their token
+    client_locales: HashMap<String, ClientLocale>,
+
+    /// Last time the scheduled tasks were checked, runs missed before are skipped
+    tasks_checked_at: u64,
+
+    /// Build, test... tasks registered by the workspace or the extensions
+    task_runner: TaskRunner,
+
+    /// Credentials that never leave the server, like the FTP passwords
+    pub secrets: SecretsStore,
+
+    /// Shared HTTP client, configured with the State's HTTP settings
+    #[cfg(feature = "http")]
+    pub http_client: HttpClient,
+
+    /// Running Jupyter kernels
+    #[cfg(feature = "kernels")]
+    pub kernels: HashMap<String, Kernel>,
 }
 
 impl fmt::Debug for State {
@@ -62,6 +266,7 @@ impl fmt::Debug for State {
 impl Default for State {
     /// The default constructor will include:
     /// - LocalFilesystem
+    /// - MemoryFilesystem, as `memory`
     ///
     /// But will not persist the state
     fn default() -> Self {
@@ -71,16 +276,65 @@ impl Default for State {
         let local_fs: Box<dyn Filesystem + Send> = Box::new(LocalFilesystem::new());
         filesystems.insert("local".to_string(), Arc::new(Mutex::new(local_fs)));
 
+        // Scratch files not saved anywhere yet
+        let memory_fs: Box<dyn Filesystem + Send> = Box::new(MemoryFilesystem::new());
+        filesystems.insert("memory".to_string(), Arc::new(Mutex::new(memory_fs)));
+
+        // Git support for the local filesystem
+        let mut vcs_providers: HashMap<String, Arc<dyn VcsProvider + Send + Sync>> = HashMap::new();
+        vcs_providers.insert("local".to_string(), Arc::new(GitCli::new()));
+
         Self {
             data: StateData::default(),
             filesystems,
             extensions_manager: ExtensionsManager::default(),
             tokens: Vec::new(),
+            invitations: HashMap::new(),
+            scoped_tokens: HashMap::new(),
             persistor: None,
             language_servers: HashMap::new(),
             language_server_builders: HashMap::new(),
+            language_servers_manager: LanguageServersManager::new(),
             terminal_shell_builders: HashMap::new(),
             terminal_shells: HashMap::new(),
+            documents: Documents::new(),
+            custom_documents: HashMap::new(),
+            modal_engines: HashMap::new(),
+            tree_views: TreeViewRegistry::new(),
+            clipboard: Clipboard::new(),
+            command_group: None,
+            decorations: DecorationRegistry::new(),
+            searches: Arc::default(),
+            file_operations: HashMap::new(),
+            output_buffers: OutputBuffers::new(),
+            session_recovery: None,
+            drafts: Vec::new(),
+            vcs_providers,
+            repository_watchers: HashMap::new(),
+            extension_watchers: HashMap::new(),
+            workspaces: HashMap::new(),
+            workspace_watchers: HashMap::new(),
+            recovered_session: None,
+            hibernation: None,
+            last_activity: Instant::now(),
+            snapshots: SnapshotsHistory::default(),
+            event_log: DataEventLog::default(),
+            delta_sync: DeltaSync::default(),
+            subscriptions: StateSubscriptions::default(),
+            settings: SettingsService::default(),
+            recent_commands: RecentItems::new(),
+            recent_files: RecentItems::new(),
+            indexer: Indexer::new(),
+            changes: ChangesFeed::new(),
+            icon_theme: None,
+            client_locales: HashMap::new(),
+            tasks_checked_at: now_secs(),
+            task_runner: TaskRunner::default(),
+            secrets: SecretsStore::new(),
+            #[cfg(feature = "http")]
+            http_client: HttpClient::default(),
+            #[cfg(feature = "kernels")]
+            kernels: HashMap::new(),
         }
     }
 }
@@ -98,321 +352,4229 @@ impl State {
         mut persistor: Box<dyn Persistor + Send>,
     ) -> Self {
         // Retrieve opened tabs from the persistor
-        let state = persistor.load();
+        let mut state = persistor.load();
+        let snapshots = persistor.load_history().unwrap_or_default();
+        let event_log = persistor.load_event_log().unwrap_or_default();
+
+        // The last changes were logged but the data wasn't saved, e.g the process was killed meanwhile
+        if event_log.get_latest_revision() > Some(state.revision) {
+            if let Some(latest) = event_log.get_latest_data() {
+                info!("Rebuilt the data of State by id <{}> from its log", id);
+                state = latest;
+            }
+        }
+        let settings = persistor.load_settings().unwrap_or_default();
+
+        #[cfg(feature = "http")]
+        let http_client = HttpClient::new(state.http_settings.clone()).unwrap_or_else(|err| {
+            warn!("Could not apply the HTTP settings, error: {:?}", err);
+            HttpClient::default()
+        });
 
-        State {
+        #[allow(unused_mut)]
+        let mut state = State {
             data: StateData { id, ..state },
             extensions_manager,
             persistor: Some(Arc::new(Mutex::new(persistor))),
+            snapshots,
+            event_log,
+            settings: SettingsService::new(settings),
+            #[cfg(feature = "http")]
+            http_client,
             ..Default::default()
-        }
-    }
+        };
 
-    /// Retrieve the specified filesystem by the given name
-    pub fn get_fs_by_name(
-        &self,
-        filesystem: &str,
-    ) -> Option<Arc<Mutex<Box<dyn Filesystem + Send>>>> {
-        return self.filesystems.get(filesystem).cloned();
-    }
+        // Data saved by older versions might still have the FTP passwords
+        state.store_ftp_passwords();
 
-    // Check if the state can be used with the specified token
-    pub fn has_token(&self, token: &str) -> bool {
-        self.tokens.contains(&token.to_owned())
+        #[cfg(feature = "ftp")]
+        state.mount_ftp_filesystems(&[]);
+
+        state
     }
 
-    /// Run all the extensions in the manager
-    pub async fn run_extensions(&self, state_handle: Arc<Mutex<State>>) {
-        for ext in &self.extensions_manager.extensions {
-            if let LoadedExtension::ExtensionInstance { plugin, .. } = ext {
-                let mut ext_plugin = plugin.lock().await;
-                ext_plugin.unload();
-                ext_plugin.init(state_handle.clone());
+    /// Share the secrets with other States, e.g so they all can connect to the same FTP servers
+    pub fn with_secrets(mut self, secrets: SecretsStore) -> Self {
+        for settings in &self.data.ftp_connections {
+            let secret_id = settings.password_secret.as_deref().unwrap_or_default();
+            if let Some(password) = self.secrets.get(secret_id) {
+                secrets.set(secret_id, password);
             }
         }
-    }
+        self.secrets = secrets;
 
-    /// Notify a specific extension about a perticular message
-    pub fn notify_extension(&self, extension_id: String, message: ClientMessages) {
-        for ext in &self.extensions_manager.extensions {
-            if let LoadedExtension::ExtensionInstance {
-                plugin, parent_id, ..
-            } = ext
-            {
-                if parent_id == &extension_id {
-                    let ext_plugin = plugin.clone();
-                    let message = message.clone();
-                    tokio::spawn(async move {
-                        let mut ext_plugin = ext_plugin.lock().await;
-                        ext_plugin.notify(message.clone());
-                    });
-                }
-            }
+        #[cfg(feature = "ftp")]
+        {
+            let connections = self.data.ftp_connections.clone();
+            self.mount_ftp_filesystems(&connections);
         }
+
+        self
     }
 
-    /// Notify all the extensions in a state about a message, asynchronously and independently
-    pub fn notify_extensions(&self, message: ClientMessages) {
-        for ext in &self.extensions_manager.extensions {
-            if let LoadedExtension::ExtensionInstance { plugin, .. } = ext {
-                let ext_plugin = plugin.clone();
-                let message = message.clone();
-                tokio::spawn(async move {
-                    let mut ext_plugin = ext_plugin.lock().await;
-                    ext_plugin.notify(message.clone());
-                });
-            }
+    /// Move the FTP passwords sent by the clients to the secrets, so the data never has them
+    fn store_ftp_passwords(&mut self) {
+        for settings in &mut self.data.ftp_connections {
+            settings.store_password(&self.secrets);
         }
     }
 
-    /// Try to retrieve info about a perticular loaded extension
-    pub fn get_ext_info_by_id(&self, ext_id: &str) -> Result<ManifestInfo, Errors> {
-        let extensions = &self.extensions_manager.extensions;
-        let result = extensions.iter().find_map(|extension| {
-            if let LoadedExtension::ManifestFile { manifest } = extension {
-                if manifest.info.extension.id == ext_id {
-                    Some(manifest.info.clone())
-                } else {
-                    None
-                }
-            } else if let LoadedExtension::ManifestBuiltin { info, .. } = extension {
-                if info.extension.id == ext_id {
-                    Some(info.clone())
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        });
+    /// Look for the language servers installed by the Core in the given directory, and install them there
+    pub fn with_language_servers_path(mut self, installs_path: PathBuf) -> Self {
+        self.language_servers_manager = self
+            .language_servers_manager
+            .with_installs_path(installs_path);
+        self
+    }
 
-        result.ok_or(Errors::Ext(ExtensionErrors::ExtensionNotFound))
+    /// Take the managed Language Servers from a pool launched when the app started, if they are in it
+    pub fn with_language_servers_pool(mut self, pool: LanguageServersPool) -> Self {
+        self.language_servers_manager = self.language_servers_manager.with_pool(pool);
+        self
     }
 
-    /// Try to retrieve info about a perticular loaded extension
-    pub fn get_ext_run_info_by_id(&self, ext_id: &str) -> Result<ExtensionInfo, Errors> {
-        let extensions = &self.extensions_manager.extensions;
-        let result = extensions.iter().find_map(|extension| {
-            if let LoadedExtension::ExtensionInstance { info, .. } = extension {
-                if info.id == ext_id {
-                    Some(info.clone())
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        });
+    /// Restore the drafts of the previous session, and if it crashed also prepare a recovery snapshot
+    pub fn with_session_recovery(mut self, session_recovery: Arc<SessionRecovery>) -> Self {
+        self.drafts = session_recovery.load_drafts();
 
-        result.ok_or(Errors::Ext(ExtensionErrors::ExtensionNotFound))
-    }
+        if session_recovery.was_unclean_shutdown() {
+            self.recovered_session = Some(RecoveredSession {
+                tabs: self
+                    .data
+                    .views
+                    .iter()
+                    .flat_map(|view| view.get_tabs().cloned())
+                    .collect(),
+                drafts: self.drafts.clone(),
+                data: session_recovery.load_state(self.data.id),
+            });
+        }
 
-    /// Return the list of loaded extensions
-    pub fn get_ext_list(&self) -> Vec<String> {
-        let extensions = &self.extensions_manager.extensions;
+        self.session_recovery = Some(session_recovery);
+        self
+    }
 
-        extensions
-            .iter()
-            .filter_map(|extension| {
-                if let LoadedExtension::ManifestBuiltin { info, .. } = extension {
-                    Some(info.extension.id.to_string())
-                } else if let LoadedExtension::ManifestFile { manifest } = extension {
-                    Some(manifest.info.extension.id.to_string())
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<String>>()
+    /// Return what was recovered from a crashed session, until it's restored or discarded
+    pub fn get_recovered_session(&self) -> Option<RecoveredSession> {
+        self.recovered_session.clone()
     }
 
-    /// Merge a new state data
-    pub async fn update(&mut self, new_data: StateData) {
-        let data_has_changed = new_data != self.data;
+    /// Keep the unsaved documents as drafts and the current data, so they survive a crash
+    pub fn autosave_recovery(&mut self) {
+        let session_recovery = match &self.session_recovery {
+            Some(session_recovery) => session_recovery.clone(),
+            None => return,
+        };
 
-        if let Some(persistor) = &self.persistor {
-            // Only save it if there has been any mutation in the state data
-            if data_has_changed {
-                persistor.lock().await.save(&new_data);
-                self.data = new_data;
-            } else {
-                info!(
-                    "Data from State by id <{}>, hasn't been modified",
-                    self.data.id
-                );
+        for info in self.documents.get_all() {
+            if !info.is_dirty {
+                continue;
+            }
+            if let Ok(content) = self.get_document_content(&info.filesystem, &info.path) {
+                self.drafts
+                    .retain(|d| d.filesystem != info.filesystem || d.path != info.path);
+                self.drafts.push(Draft {
+                    filesystem: info.filesystem,
+                    path: info.path,
+                    content,
+                });
             }
-        } else {
-            warn!(
-                "Persistor not found for State by id <{}>, could not save",
-                self.data.id
-            );
         }
+
+        session_recovery.save_drafts(&self.drafts);
+        session_recovery.save_state(&self.data);
     }
 
-    /// Return all the registered language server builders
-    pub async fn get_all_language_server_builders(&self) -> Vec<LanguageServerBuilderInfo> {
-        let mut list = vec![];
-        let language_server_builders = self.language_server_builders.values();
+    /// Apply the data and reopen the drafts of a crashed session, returns the restored documents
+    pub async fn restore_recovered_session(&mut self) -> Result<Vec<DocumentInfo>, Errors> {
+        let session = self
+            .recovered_session
+            .take()
+            .ok_or(Errors::RecoveredSessionNotFound)?;
 
-        for language_server_builder in language_server_builders {
-            list.push(language_server_builder.lock().await.get_info());
+        if let Some(data) = session.data {
+            // The recovered data wins over whatever was loaded since
+            let update = StateDataUpdate {
+                revision: None,
+                ..StateDataUpdate::from(data)
+            };
+            self.update(update).await;
         }
 
-        list
+        let mut restored = Vec::new();
+        for draft in session.drafts {
+            match self.restore_draft(&draft).await {
+                Ok(info) => restored.push(info),
+                Err(err) => warn!(
+                    "Could not restore the draft of <{}>, error: {:?}",
+                    draft.path, err
+                ),
+            }
+        }
+
+        if let Some(session_recovery) = &self.session_recovery {
+            session_recovery.discard_state(self.data.id);
+        }
+
+        Ok(restored)
     }
 
-    /// Return all the registered terminal shell builders
-    pub async fn get_terminal_shell_builders(&self) -> Vec<TerminalShellBuilderInfo> {
-        let mut list = vec![];
-        let shell_builders = self.terminal_shell_builders.values();
+    /// Open the document of a draft with the draft's content
+    async fn restore_draft(&mut self, draft: &Draft) -> Result<DocumentInfo, Errors> {
+        self.open_document(&draft.filesystem, &draft.path).await?;
 
-        for shell_builder in shell_builders {
-            list.push(shell_builder.lock().await.get_info());
+        let document = self
+            .documents
+            .get_mut(&draft.filesystem, &draft.path)
+            .ok_or(Errors::Document(DocumentErrors::DocumentNotFound))?;
+        document.set_content(&draft.content);
+        let info = document.get_info();
+
+        if let (Some(language), "local") = (&info.language, draft.filesystem.as_str()) {
+            let uri = path_to_uri(&draft.path);
+            self.language_servers_manager
+                .did_close_document(&uri, language)
+                .await;
+            self.language_servers_manager
+                .did_open_document(&uri, language, info.version, &draft.content)
+                .await;
         }
 
-        list
+        Ok(info)
     }
 
-    /// Create a new terminal shell from a builder ID
-    pub async fn create_terminal_shell(
-        &mut self,
-        terminal_shell_builder_id: String,
-        terminal_shell_id: String,
-    ) {
-        let shell_builder = self.terminal_shell_builders.get(&terminal_shell_builder_id);
+    /// Forget everything recovered from a crashed session, including the drafts
+    pub fn discard_recovered_session(&mut self) {
+        self.recovered_session = None;
+        self.drafts.clear();
+        self.persist_drafts();
 
-        if let Some(shell_builder) = shell_builder {
-            let shell_builder = shell_builder.lock().await;
-            let shell = shell_builder.build(&terminal_shell_id);
-            self.terminal_shells
-                .insert(terminal_shell_id.to_string(), Arc::new(shell));
-        } else {
-            warn!(
-                "Could not create a terminal shell, missing builder with id <{}>",
-                terminal_shell_builder_id
-            );
+        if let Some(session_recovery) = &self.session_recovery {
+            session_recovery.discard_state(self.data.id);
         }
     }
 
-    /// Write data to a terminal shell
-    pub async fn write_to_terminal_shell(&self, terminal_shell_id: String, data: String) {
-        let shell = self.terminal_shells.get(&terminal_shell_id);
-        if let Some(shell) = shell {
-            let shell = shell.clone();
-            shell.write(data).await;
-        } else {
-            warn!(
-                "Could not write to non-existent terminal shell, id <{}>",
-                terminal_shell_id
-            );
-        }
+    /// Keep the unsaved content of a file, replacing any previous draft of it
+    pub fn save_draft(&mut self, draft: Draft) {
+        self.drafts
+            .retain(|d| d.filesystem != draft.filesystem || d.path != draft.path);
+        self.drafts.push(draft);
+        self.persist_drafts();
     }
 
-    /// Terminate a terminal shell
-    pub async fn close_terminal_shell(&mut self, terminal_shell_id: String) {
-        self.terminal_shells.remove(&terminal_shell_id);
+    /// Rename a file or folder and update everything pointing to it,
+    /// this includes the open tabs, the drafts, the repository watchers and the language servers
+    pub async fn rename_path(
+        &mut self,
+        filesystem_name: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<(), Errors> {
+        self.ensure_writable(filesystem_name).await?;
+        let filesystem = self
+            .get_fs_by_name(filesystem_name)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+        filesystem.lock().await.rename_by_path(from, to).await?;
+
+        self.follow_renamed_path(filesystem_name, from, to).await;
+
+        Ok(())
     }
 
-    /// Resize a terminal shell
-    pub async fn resize_terminal_shell(&mut self, terminal_shell_id: String, cols: i32, rows: i32) {
-        let shell = self.terminal_shells.get(&terminal_shell_id).unwrap();
-        shell.resize(cols, rows).await;
+    /// Move a file or folder, even across devices, and update everything pointing to it like [`State::rename_path`]
+    pub async fn move_path(
+        &mut self,
+        filesystem_name: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<(), Errors> {
+        self.ensure_writable(filesystem_name).await?;
+        let filesystem = self
+            .get_fs_by_name(filesystem_name)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+        filesystem.lock().await.move_item(from, to).await?;
+
+        self.follow_renamed_path(filesystem_name, from, to).await;
+
+        Ok(())
     }
 
-    /// Create a Language Server instance from a Builder ID
-    pub async fn create_language_server(&mut self, language_server_builder_id: String) {
-        let language_server_builder = self
-            .language_server_builders
-            .get(&language_server_builder_id);
+    /// Update the tabs, drafts, documents, watchers and language servers after `from` became `to`
+    async fn follow_renamed_path(&mut self, filesystem_name: &str, from: &str, to: &str) {
+        // Open tabs
+        let mut data = self.data.clone();
+        let mut tabs_changed = false;
+        for view in &mut data.views {
+            tabs_changed |= view.rename_paths(filesystem_name, from, to);
+        }
+        if tabs_changed {
+            self.update(data).await;
+        }
 
-        if let Some(language_server_builder) = language_server_builder {
-            let language_server_builder = language_server_builder.lock().await;
-            let info = language_server_builder.get_info();
-            let language_server = language_server_builder.build();
-            self.language_servers
-                .insert(info.id, Arc::new(Mutex::new(language_server)));
-        } else {
-            warn!(
-                "Could not create a language server, missing builder with id <{}>",
-                language_server_builder_id
-            );
+        // Unsaved content
+        let mut drafts_changed = false;
+        for draft in &mut self.drafts {
+            if draft.filesystem == filesystem_name {
+                if let Some(new_path) = remap_path(&draft.path, from, to) {
+                    draft.path = new_path;
+                    drafts_changed = true;
+                }
+            }
+        }
+        if drafts_changed {
+            self.persist_drafts();
         }
-    }
 
-    /// Write to a Language Server instance
-    pub async fn write_to_language_server(&mut self, language_server_id: String, data: String) {
-        let language_server = self.language_servers.get(&language_server_id);
-        if let Some(language_server) = language_server {
-            let mut language_server = language_server.lock().await;
-            language_server.write(data).await;
-        } else {
-            warn!(
-                "Language Server by id <{}> is not running",
-                language_server_id
-            );
+        // Open documents
+        self.documents.rename_paths(filesystem_name, from, to);
+
+        // Repositories inside the renamed folder
+        let watcher_prefix = format!("{}:", filesystem_name);
+        let renamed_watchers = self
+            .repository_watchers
+            .keys()
+            .filter_map(|watcher_id| {
+                let path = watcher_id.strip_prefix(&watcher_prefix)?;
+                Some((path.to_owned(), remap_path(path, from, to)?))
+            })
+            .collect::<Vec<(String, String)>>();
+        for (old_path, new_path) in renamed_watchers {
+            self.unwatch_repository(filesystem_name, &old_path);
+            self.watch_repository(filesystem_name, &new_path).await.ok();
         }
-    }
 
-    /// Terminate to a Language Server instance
-    pub async fn unload_language_server(&mut self, language_server_builder_id: &str) {
-        self.language_server_builders
-            .remove(language_server_builder_id);
-        self.language_servers.remove(language_server_builder_id);
+        // Language servers only work with local files
+        if filesystem_name == "local" {
+            self.language_servers_manager
+                .did_rename_files(&[(path_to_uri(from), path_to_uri(to))])
+                .await;
+        }
+
+        self.broadcast_fs_change(StateEvent::PathRenamed {
+            filesystem: filesystem_name.to_owned(),
+            from: from.to_owned(),
+            to: to.to_owned(),
+        });
+
         self.extensions_manager
             .sender
-            .send(ClientMessages::ServerMessage(
-                ServerMessages::UnloadedLanguageServer {
-                    id: language_server_builder_id.to_owned(),
-                    state_id: self.data.id,
-                },
-            ))
+            .send(ClientMessages::ServerMessage(ServerMessages::PathRenamed {
+                state_id: self.data.id,
+                filesystem: filesystem_name.to_owned(),
+                from: from.to_owned(),
+                to: to.to_owned(),
+            }))
             .await
-            .unwrap();
+            .ok();
     }
-}
 
-#[cfg(test)]
-mod tests {
+    /// Move a file or folder to the trash of the platform
+    pub async fn move_to_trash(&mut self, filesystem_name: &str, path: &str) -> Result<(), Errors> {
+        let filesystem = self
+            .get_fs_by_name(filesystem_name)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+        let filesystem = filesystem.lock().await;
+        let capabilities = filesystem.capabilities();
+        if capabilities.read_only {
+            return Err(Errors::Fs(FilesystemErrors::ReadOnly));
+        }
+        // Deleting instead would lose the file for good, so the client must ask for it explicitly
+        if !capabilities.trash {
+            return Err(Errors::Fs(FilesystemErrors::FileNotSupported));
+        }
+        filesystem.move_to_trash(path).await?;
+        drop(filesystem);
 
-    use std::sync::Arc;
+        // Forget the drafts of the trashed file, or of the files inside the trashed folder
+        self.drafts.retain(|d| {
+            d.filesystem != filesystem_name || remap_path(&d.path, path, path).is_none()
+        });
+        self.persist_drafts();
 
-    use tokio::sync::Mutex;
+        self.broadcast_fs_change(StateEvent::PathDeleted {
+            filesystem: filesystem_name.to_owned(),
+            path: path.to_owned(),
+        });
 
-    use crate::extensions::base::{Extension, ExtensionInfo};
-    use crate::extensions::manager::ExtensionsManager;
-    use crate::messaging::ClientMessages;
-    use crate::states::MemoryPersistor;
+        Ok(())
+    }
 
-    use super::State;
+    /// Copy a folder in the background, the client is told about the progress and when it finishes.
+    /// Returns the ID of the operation, which can be used to cancel it
+    pub fn copy_folder(
+        &mut self,
+        filesystem_name: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<String, Errors> {
+        let filesystem = self
+            .get_fs_by_name(filesystem_name)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
 
-    fn get_sample_extension_info() -> ExtensionInfo {
-        ExtensionInfo {
-            id: "sample".to_string(),
-            name: "sample".to_string(),
-        }
+        // Forget finished operations
+        self.file_operations
+            .retain(|_, token| !token.is_cancelled());
+
+        let operation_id = Uuid::new_v4().to_string();
+        let token = CancellationToken::new();
+        self.file_operations
+            .insert(operation_id.clone(), token.clone());
+
+        let sender = self.extensions_manager.sender.clone();
+        let state_id = self.data.id;
+        let from = from.to_owned();
+        let to = to.to_owned();
+        let id = operation_id.clone();
+
+        tokio::spawn(async move {
+            let progress_sender = sender.clone();
+            let progress_id = id.clone();
+            // Progress is not important enough to wait for the channel
+            let progress = move |processed: usize, total: usize| {
+                progress_sender
+                    .try_send(ClientMessages::ServerMessage(
+                        ServerMessages::FileOperationProgress {
+                            state_id,
+                            operation_id: progress_id.clone(),
+                            processed,
+                            total,
+                        },
+                    ))
+                    .ok();
+            };
+
+            let result = {
+                let filesystem = filesystem.lock().await;
+                let copy = filesystem.copy_dir_recursive(&from, &to, Some(&progress), Some(&token));
+                copy.await
+            };
+
+            token.cancel();
+
+            sender
+                .send(ClientMessages::ServerMessage(
+                    ServerMessages::FileOperationFinished {
+                        state_id,
+                        operation_id: id,
+                        result,
+                    },
+                ))
+                .await
+                .ok();
+        });
+
+        Ok(operation_id)
     }
 
-    fn get_sample_extension() -> Box<dyn Extension + Send> {
-        struct SampleExtension;
+    /// Abort a running file operation
+    pub fn cancel_file_operation(&mut self, operation_id: &str) -> Result<(), Errors> {
+        let token = self
+            .file_operations
+            .remove(operation_id)
+            .ok_or(Errors::Fs(FilesystemErrors::OperationNotFound))?;
+        token.cancel();
+        Ok(())
+    }
 
-        impl Extension for SampleExtension {
-            fn get_info(&self) -> ExtensionInfo {
-                get_sample_extension_info()
-            }
+    /// Open a document so it can be edited incrementally, if it's already open it's reused
+    pub async fn open_document(
+        &mut self,
+        filesystem_name: &str,
+        path: &str,
+    ) -> Result<DocumentInfo, Errors> {
+        if let Some(document) = self.documents.get(filesystem_name, path) {
+            return Ok(document.get_info());
+        }
 
-            fn init(&mut self, _state: Arc<Mutex<State>>) {
-                todo!()
-            }
+        let file = self
+            .read_file_with_encoding(filesystem_name, path, None)
+            .await?;
+        let content = file.content;
 
-            fn unload(&mut self) {
-                todo!()
-            }
+        let document = Document::new(filesystem_name, path, &content).with_encoding(file.encoding);
+        let info = document.get_info();
+        self.documents.open(document);
+        self.recent_files.record(path, now_secs());
 
-            fn notify(&mut self, _message: ClientMessages) {
-                todo!()
-            }
+        // Language servers only work with local files
+        if let (Some(language), "local") = (&info.language, filesystem_name) {
+            self.language_servers_manager
+                .did_open_document(&path_to_uri(path), language, info.version, &content)
+                .await;
         }
 
-        Box::new(SampleExtension)
+        self.publish_diagnostics(filesystem_name, path).await;
+
+        Ok(info)
+    }
+
+    /// Apply edits to an open document, returns the new version
+    ///
+    /// # Arguments
+    ///
+    /// * `filesystem_name`   - Filesystem of the document
+    /// * `path`              - Path of the document
+    /// * `version`           - Version the edits were made on
+    /// * `edits`             - Edits to apply in order
+    ///
+    pub async fn edit_document(
+        &mut self,
+        filesystem_name: &str,
+        path: &str,
+        version: i32,
+        edits: Vec<DocumentEdit>,
+    ) -> Result<i32, Errors> {
+        let document = self
+            .documents
+            .get_mut(filesystem_name, path)
+            .ok_or(Errors::Document(DocumentErrors::DocumentNotFound))?;
+
+        let changes = document
+            .apply_edits(version, &edits)
+            .map_err(Errors::Document)?;
+        let version = document.get_version();
+        let language = document.get_language();
+
+        if let Some(group) = &mut self.command_group {
+            group.add_document(document.get_uri());
+        }
+
+        if let (Some(language), "local") = (&language, filesystem_name) {
+            self.language_servers_manager
+                .did_change_document(&path_to_uri(path), language, version, &changes)
+                .await;
+        }
+
+        self.subscriptions.broadcast(StateEvent::DocumentChanged {
+            filesystem: filesystem_name.to_owned(),
+            path: path.to_owned(),
+            version,
+            changes: changes.clone(),
+        });
+
+        self.notify_extensions(ClientMessages::DocumentChanged {
+            state_id: self.data.id,
+            filesystem: filesystem_name.to_owned(),
+            path: path.to_owned(),
+            version,
+            changes,
+        });
+
+        self.publish_diagnostics(filesystem_name, path).await;
+
+        Ok(version)
+    }
+
+    /// Validate an open document with the schema of it's file, if there is any, and send the diagnostics to the clients
+    async fn publish_diagnostics(&self, filesystem_name: &str, path: &str) {
+        let document = match self.documents.get(filesystem_name, path) {
+            Some(document) => document,
+            None => return,
+        };
+        let diagnostics = match self
+            .extensions_manager
+            .validation_schemas
+            .validate(path, &document.get_content())
+        {
+            Some(diagnostics) => diagnostics,
+            None => return,
+        };
+
+        self.extensions_manager
+            .sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::DocumentDiagnostics {
+                    state_id: self.data.id,
+                    filesystem: filesystem_name.to_owned(),
+                    path: path.to_owned(),
+                    version: document.get_version(),
+                    diagnostics,
+                },
+            ))
+            .await
+            .ok();
+    }
+
+    /// Apply edits to an open document identified by it's URI, e.g `graviton://local/main.rs`.
+    /// Only the edited ranges are sent to the language servers, extensions and subscribers
+    ///
+    /// # Arguments
+    ///
+    /// * `document`    - URI of the document, as in it's [`DocumentInfo`]
+    /// * `version`     - Version the edits were made on
+    /// * `edits`       - Edits to apply in order
+    ///
+    pub async fn apply_edits(
+        &mut self,
+        document: &GravitonUri,
+        version: i32,
+        edits: Vec<DocumentEdit>,
+    ) -> Result<i32, Errors> {
+        self.edit_document(
+            document.get_filesystem(),
+            document.get_path(),
+            version,
+            edits,
+        )
+        .await
+    }
+
+    /// Run the save hooks of the document's language in order, failing hooks are reported and skipped
+    async fn run_save_hooks(
+        &mut self,
+        filesystem_name: &str,
+        path: &str,
+        options: &SaveOptions,
+    ) -> Result<(), Errors> {
+        let language = self
+            .documents
+            .get(filesystem_name, path)
+            .ok_or(Errors::Document(DocumentErrors::DocumentNotFound))?
+            .get_language();
+        let hooks = language
+            .and_then(|language| self.data.save_hooks.get(&language).cloned())
+            .unwrap_or_default();
+
+        for hook in hooks.iter().filter(|hook| options.should_run(hook)) {
+            let edits = tokio::time::timeout(
+                hook.get_timeout(),
+                self.get_save_hook_edits(filesystem_name, path, &hook.action),
+            )
+            .await
+            .unwrap_or(Err(SaveHookErrors::TimedOut));
+
+            let result = match edits {
+                Ok(edits) if edits.is_empty() => Ok(()),
+                Ok(edits) => {
+                    let version = self
+                        .documents
+                        .get(filesystem_name, path)
+                        .map(|document| document.get_version())
+                        .unwrap_or_default();
+                    self.edit_document(filesystem_name, path, version, edits)
+                        .await
+                        .map(|_| ())
+                        .map_err(|_| SaveHookErrors::BadEdits)
+                }
+                Err(err) => Err(err),
+            };
+
+            if let Err(error) = result {
+                self.extensions_manager
+                    .sender
+                    .send(ClientMessages::ServerMessage(
+                        ServerMessages::SaveHookFailed {
+                            state_id: self.data.id,
+                            filesystem: filesystem_name.to_owned(),
+                            path: path.to_owned(),
+                            hook_id: hook.id.clone(),
+                            error,
+                        },
+                    ))
+                    .await
+                    .ok();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Edits a save hook wants to make, in characters and ready to be applied in order
+    async fn get_save_hook_edits(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+        action: &SaveHookAction,
+    ) -> Result<Vec<DocumentEdit>, SaveHookErrors> {
+        let document = match self.documents.get(filesystem_name, path) {
+            Some(document) => document,
+            None => return Ok(Vec::new()),
+        };
+        let content = document.get_content();
+
+        match action {
+            SaveHookAction::TrimTrailingWhitespace => Ok(trim_trailing_whitespace(&content)),
+            SaveHookAction::InsertFinalNewline => {
+                Ok(insert_final_newline(&content).into_iter().collect())
+            }
+            SaveHookAction::Format { command, args } => {
+                let formatted = run_formatter(command, args, &content).await?;
+                Ok(replace_content(&content, &formatted).into_iter().collect())
+            }
+            SaveHookAction::OrganizeImports => {
+                // Language servers only know about local documents
+                let language = match (document.get_language(), filesystem_name) {
+                    (Some(language), "local") => language,
+                    _ => {
+                        return Err(SaveHookErrors::LanguageServer(
+                            LanguageServerErrors::NotRunning,
+                        ))
+                    }
+                };
+
+                let uri = path_to_uri(path);
+                let actions = self
+                    .language_servers_manager
+                    .request(
+                        &language,
+                        &uri,
+                        "textDocument/codeAction",
+                        get_organize_imports_params(&uri, document.get_line_count()),
+                    )
+                    .await
+                    .map_err(SaveHookErrors::LanguageServer)?;
+
+                let mut edits = get_code_actions_edits(&actions, &uri)
+                    .into_iter()
+                    .map(|edit| {
+                        Ok(DocumentEdit {
+                            range: TextRange::new(
+                                document.get_char_position(edit.range.start)?,
+                                document.get_char_position(edit.range.end)?,
+                            ),
+                            text: edit.text,
+                        })
+                    })
+                    .collect::<Result<Vec<DocumentEdit>, DocumentErrors>>()
+                    .map_err(|_| SaveHookErrors::BadEdits)?;
+
+                // The edits are relative to the original content, applying them
+                // from the end keeps the positions of the previous ones valid
+                edits.reverse();
+                edits.sort_by_key(|edit| {
+                    Reverse((edit.range.start.line, edit.range.start.character))
+                });
+
+                Ok(edits)
+            }
+        }
+    }
+
+    /// Run the save hooks and write the content of an open document to it's filesystem
+    ///
+    /// # Arguments
+    ///
+    /// * `filesystem_name`   - Filesystem of the document
+    /// * `path`              - Path of the document
+    /// * `options`           - e.g hooks to skip in this save
+    ///
+    pub async fn save_document(
+        &mut self,
+        filesystem_name: &str,
+        path: &str,
+        options: &SaveOptions,
+    ) -> Result<DocumentInfo, Errors> {
+        // The save hooks would be run for nothing
+        self.ensure_writable(filesystem_name).await?;
+        self.run_save_hooks(filesystem_name, path, options).await?;
+
+        let document = self
+            .documents
+            .get(filesystem_name, path)
+            .ok_or(Errors::Document(DocumentErrors::DocumentNotFound))?;
+        let (content, encoding) = (document.get_content(), document.get_encoding());
+
+        let written = self
+            .write_file_with_encoding(filesystem_name, path, &content, Some(encoding))
+            .await?;
+        self.changes.record_save(filesystem_name, path);
+
+        let info = match self.documents.get_mut(filesystem_name, path) {
+            Some(document) => {
+                document.mark_saved();
+                document.set_line_ending(LineEnding::detect(&written));
+                document.get_info()
+            }
+            None => return Err(Errors::Document(DocumentErrors::DocumentNotFound)),
+        };
+
+        if self
+            .drafts
+            .iter()
+            .any(|d| d.filesystem == filesystem_name && d.path == path)
+        {
+            self.discard_draft(filesystem_name, path);
+        }
+
+        if let (Some(language), "local") = (&info.language, filesystem_name) {
+            self.language_servers_manager
+                .did_save_document(&path_to_uri(path), language)
+                .await;
+            self.index_document_symbols(path);
+        }
+
+        Ok(info)
+    }
+
+    /// Write a file following the line endings policy, keeping its encoding. Returns the written content
+    pub async fn write_file(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+        content: &str,
+    ) -> Result<String, Errors> {
+        self.write_file_with_encoding(filesystem_name, path, content, None)
+            .await
+    }
+
+    /// Write a file following the line endings policy, returns the written content
+    ///
+    /// # Arguments
+    ///
+    /// * `filesystem_name`   - Filesystem of the file
+    /// * `path`              - Path of the file
+    /// * `content`           - New content
+    /// * `encoding`          - Encoding to write it with, if none the file keeps the one it had (or UTF-8 for new files)
+    ///
+    pub async fn write_file_with_encoding(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+        content: &str,
+        encoding: Option<FileEncoding>,
+    ) -> Result<String, Errors> {
+        self.ensure_writable(filesystem_name).await?;
+        let filesystem = self
+            .get_fs_by_name(filesystem_name)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+        let filesystem = filesystem.lock().await;
+        let policy = self.get_eol_policy(filesystem_name, path);
+
+        // Only the beginning of the file is needed to know it's encoding and line endings
+        let start = if encoding.is_none() || policy == EolPolicy::Preserve {
+            filesystem.read_range(path, 0, 8192).await.ok()
+        } else {
+            None
+        };
+        let encoding = encoding
+            .or_else(|| start.as_deref().map(FileEncoding::detect))
+            .unwrap_or_default();
+        let current = match (&start, policy) {
+            (Some(start), EolPolicy::Preserve) => LineEnding::detect(&encoding.decode_lossy(start)),
+            _ => LineEnding::Unknown,
+        };
+
+        let content = policy.apply(content, current);
+        let start = Instant::now();
+        filesystem
+            .write_file_with_encoding(path, &content, encoding)
+            .await?;
+        self.extensions_manager
+            .telemetry
+            .record_filesystem(FilesystemOperation::Write, start.elapsed());
+
+        self.broadcast_fs_change(StateEvent::FileWritten {
+            filesystem: filesystem_name.to_owned(),
+            path: path.to_owned(),
+        });
+
+        if policy.should_warn(&content) {
+            self.extensions_manager
+                .sender
+                .send(ClientMessages::ServerMessage(
+                    ServerMessages::MixedLineEndings {
+                        state_id: self.data.id,
+                        filesystem: filesystem_name.to_owned(),
+                        path: path.to_owned(),
+                    },
+                ))
+                .await
+                .ok();
+        }
+
+        Ok(content)
+    }
+
+    /// Stop tracking a document, unsaved changes are lost
+    pub async fn close_document(
+        &mut self,
+        filesystem_name: &str,
+        path: &str,
+    ) -> Result<(), Errors> {
+        let document = self
+            .documents
+            .close(filesystem_name, path)
+            .ok_or(Errors::Document(DocumentErrors::DocumentNotFound))?;
+
+        if let (Some(language), "local") = (document.get_language(), filesystem_name) {
+            self.language_servers_manager
+                .did_close_document(&path_to_uri(path), &language)
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// Open a file with the custom editor claiming it, or as a text document if there is none
+    ///
+    /// # Arguments
+    ///
+    /// * `filesystem_name`   - Filesystem of the file
+    /// * `path`              - Path of the file
+    /// * `editor_id`         - Custom editor to use instead of the default one, e.g from "Open with..."
+    ///
+    pub async fn open_file(
+        &mut self,
+        filesystem_name: &str,
+        path: &str,
+        editor_id: Option<&str>,
+    ) -> Result<OpenedFile, Errors> {
+        let editors = &self.extensions_manager.custom_editors;
+        let editor = match editor_id {
+            Some(editor_id) => Some(
+                editors
+                    .get(editor_id)
+                    .map(|(_, editor)| editor)
+                    .filter(|editor| editor.matches(path))
+                    .ok_or(Errors::Ext(ExtensionErrors::CustomEditorNotFound))?,
+            ),
+            None => editors.get_default_editor(path),
+        };
+
+        let editor_id = match editor {
+            Some(editor) => editor.id.clone(),
+            None => {
+                return self
+                    .open_document(filesystem_name, path)
+                    .await
+                    .map(OpenedFile::Document)
+            }
+        };
+
+        let key = (filesystem_name.to_owned(), path.to_owned());
+        if let Some(info) = self.custom_documents.get(&key) {
+            if info.editor_id == editor_id {
+                return Ok(OpenedFile::Custom(info.clone()));
+            }
+        }
+
+        if self.get_fs_by_name(filesystem_name).is_none() {
+            return Err(Errors::Fs(FilesystemErrors::FilesystemNotFound));
+        }
+
+        let info = CustomDocumentInfo {
+            filesystem: filesystem_name.to_owned(),
+            path: path.to_owned(),
+            uri: GravitonUri::new(filesystem_name, path),
+            editor_id,
+        };
+        self.custom_documents.insert(key, info.clone());
+        self.recent_files.record(path, now_secs());
+        self.request_custom_editor(&info, CustomEditorAction::Open)?;
+
+        Ok(OpenedFile::Custom(info))
+    }
+
+    /// Ask the extension of the custom editor of a file to write it's changes
+    pub async fn save_custom_document(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+    ) -> Result<(), Errors> {
+        let info = self
+            .custom_documents
+            .get(&(filesystem_name.to_owned(), path.to_owned()))
+            .ok_or(Errors::Document(DocumentErrors::DocumentNotFound))?;
+
+        self.ensure_writable(filesystem_name).await?;
+
+        self.request_custom_editor(info, CustomEditorAction::Save)
+    }
+
+    /// Close a file opened with a custom editor, it's extension discards the unsaved changes
+    pub fn close_custom_document(
+        &mut self,
+        filesystem_name: &str,
+        path: &str,
+    ) -> Result<(), Errors> {
+        let info = self
+            .custom_documents
+            .remove(&(filesystem_name.to_owned(), path.to_owned()))
+            .ok_or(Errors::Document(DocumentErrors::DocumentNotFound))?;
+
+        self.request_custom_editor(&info, CustomEditorAction::Close)
+    }
+
+    /// Custom editors that can open a file, e.g for "Open with..."
+    pub fn get_custom_editors_for(&self, path: &str) -> Vec<CustomEditor> {
+        self.extensions_manager.custom_editors.get_editors_for(path)
+    }
+
+    fn request_custom_editor(
+        &self,
+        info: &CustomDocumentInfo,
+        action: CustomEditorAction,
+    ) -> Result<(), Errors> {
+        let extension_id = self
+            .extensions_manager
+            .custom_editors
+            .get(&info.editor_id)
+            .map(|(extension_id, _)| extension_id.to_owned())
+            .ok_or(Errors::Ext(ExtensionErrors::CustomEditorNotFound))?;
+
+        self.notify_extension(
+            extension_id,
+            ClientMessages::CustomEditorRequest {
+                state_id: self.data.id,
+                editor_id: info.editor_id.clone(),
+                filesystem: info.filesystem.clone(),
+                path: info.path.clone(),
+                action,
+            },
+        );
+        Ok(())
+    }
+
+    /// Read an open document again decoding it with the given encoding, unsaved changes are lost
+    pub async fn reopen_document_with_encoding(
+        &mut self,
+        filesystem_name: &str,
+        path: &str,
+        encoding: FileEncoding,
+    ) -> Result<DocumentInfo, Errors> {
+        let content = self
+            .read_file_with_encoding(filesystem_name, path, Some(encoding))
+            .await?
+            .content;
+
+        let document = self
+            .documents
+            .get_mut(filesystem_name, path)
+            .ok_or(Errors::Document(DocumentErrors::DocumentNotFound))?;
+        document.reload(&content, encoding);
+        let info = document.get_info();
+
+        // The language server must see the whole new content
+        if let (Some(language), "local") = (&info.language, filesystem_name) {
+            let uri = path_to_uri(path);
+            self.language_servers_manager
+                .did_close_document(&uri, language)
+                .await;
+            self.language_servers_manager
+                .did_open_document(&uri, language, info.version, &content)
+                .await;
+        }
+
+        Ok(info)
+    }
+
+    /// Change the encoding an open document is saved with, it's content is kept
+    pub fn set_document_encoding(
+        &mut self,
+        filesystem_name: &str,
+        path: &str,
+        encoding: FileEncoding,
+    ) -> Result<DocumentInfo, Errors> {
+        let document = self
+            .documents
+            .get_mut(filesystem_name, path)
+            .ok_or(Errors::Document(DocumentErrors::DocumentNotFound))?;
+        document.set_encoding(encoding);
+        Ok(document.get_info())
+    }
+
+    /// Return the current content of an open document
+    pub fn get_document_content(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+    ) -> Result<String, Errors> {
+        self.documents
+            .get(filesystem_name, path)
+            .map(|document| document.get_content())
+            .ok_or(Errors::Document(DocumentErrors::DocumentNotFound))
+    }
+
+    /// Find all the occurrences of a symbol in a project, for languages without a language server
+    pub async fn preview_rename(
+        &self,
+        filesystem_name: &str,
+        old_name: &str,
+        new_name: &str,
+        options: SearchOptions,
+    ) -> Result<RenamePreview, Errors> {
+        let filesystem = self
+            .get_fs_by_name(filesystem_name)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+
+        RenamePreview::new(filesystem, old_name, new_name, options).await
+    }
+
+    /// Apply a previewed rename to all the files at once, and to the open documents
+    pub async fn apply_rename(
+        &mut self,
+        filesystem_name: &str,
+        preview: RenamePreview,
+    ) -> Result<(), Errors> {
+        let filesystem = self
+            .get_fs_by_name(filesystem_name)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+
+        // Unsaved changes would be overwritten
+        for file in &preview.files {
+            if let Some(document) = self.documents.get(filesystem_name, &file.path) {
+                if document.is_dirty() {
+                    return Err(Errors::Refactor(RefactorErrors::UnsavedChanges));
+                }
+            }
+        }
+
+        preview.apply(filesystem).await?;
+
+        // Keep the open documents in sync with the files
+        for file in &preview.files {
+            let version = match self.documents.get(filesystem_name, &file.path) {
+                Some(document) => document.get_version(),
+                None => continue,
+            };
+            let edits = preview.get_document_edits(file);
+            self.edit_document(filesystem_name, &file.path, version, edits)
+                .await?;
+            if let Some(document) = self.documents.get_mut(filesystem_name, &file.path) {
+                document.mark_saved();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Forget the draft of a file, e.g. once it's saved
+    pub fn discard_draft(&mut self, filesystem: &str, path: &str) {
+        self.drafts
+            .retain(|d| d.filesystem != filesystem || d.path != path);
+        self.persist_drafts();
+    }
+
+    fn persist_drafts(&self) {
+        if let Some(session_recovery) = &self.session_recovery {
+            session_recovery.save_drafts(&self.drafts);
+        }
+    }
+
+    /// Register a filesystem, replacing any other with the same name
+    pub fn register_filesystem(&mut self, name: &str, filesystem: Box<dyn Filesystem + Send>) {
+        self.filesystems
+            .insert(name.to_owned(), Arc::new(Mutex::new(filesystem)));
+    }
+
+    /// Open an archive as a read-only filesystem, returns the name it's registered with,
+    /// e.g `zip:/home/user/code.zip`. The archive is read again if it was already open
+    ///
+    /// # Arguments
+    ///
+    /// * `filesystem_name`   - Filesystem where the archive is
+    /// * `path`              - Path of the archive
+    ///
+    #[cfg(feature = "archives")]
+    pub async fn open_archive(
+        &mut self,
+        filesystem_name: &str,
+        path: &str,
+    ) -> Result<String, Errors> {
+        let filesystem = self
+            .get_fs_by_name(filesystem_name)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+        let content = filesystem.lock().await.read_bytes_by_path(path).await?;
+
+        let archive = ArchiveFilesystem::from_bytes(content).await?;
+        let name = format!("{}:{}", archive.get_kind().get_prefix(), path);
+        self.register_filesystem(&name, Box::new(archive));
+
+        Ok(name)
+    }
+
+    /// Stop browsing an archive opened with [`State::open_archive`]
+    #[cfg(feature = "archives")]
+    pub fn close_archive(&mut self, name: &str) -> Result<(), Errors> {
+        let is_archive = [ArchiveKind::Zip, ArchiveKind::Tar]
+            .iter()
+            .any(|kind| name.starts_with(&format!("{}:", kind.get_prefix())));
+
+        if is_archive && self.filesystems.remove(name).is_some() {
+            Ok(())
+        } else {
+            Err(Errors::Fs(FilesystemErrors::FilesystemNotFound))
+        }
+    }
+
+    /// Mount the FTP servers configured in the State, unmounting the previous ones
+    #[cfg(feature = "ftp")]
+    fn mount_ftp_filesystems(&mut self, previous_connections: &[FtpSettings]) {
+        for settings in previous_connections {
+            self.filesystems.remove(&settings.name);
+        }
+
+        for settings in self.data.ftp_connections.clone() {
+            if settings.name == "local" {
+                warn!("FTP connections can't replace the local filesystem");
+                continue;
+            }
+            let name = settings.name.clone();
+            self.register_filesystem(
+                &name,
+                Box::new(FtpFilesystem::new(settings, self.secrets.clone())),
+            );
+        }
+    }
+
+    /// Retrieve the specified filesystem by the given name
+    pub fn get_fs_by_name(
+        &self,
+        filesystem: &str,
+    ) -> Option<Arc<Mutex<Box<dyn Filesystem + Send>>>> {
+        self.filesystems.get(filesystem).cloned()
+    }
+
+    /// What a filesystem supports
+    pub async fn get_fs_capabilities(
+        &self,
+        filesystem: &str,
+    ) -> Result<FilesystemCapabilities, Errors> {
+        let filesystem = self
+            .get_fs_by_name(filesystem)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+        let capabilities = filesystem.lock().await.capabilities();
+        Ok(capabilities)
+    }
+
+    /// Fail before doing any work if the filesystem can't be modified
+    async fn ensure_writable(&self, filesystem: &str) -> Result<(), Errors> {
+        if self.get_fs_capabilities(filesystem).await?.read_only {
+            Err(Errors::Fs(FilesystemErrors::ReadOnly))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Return the filesystem of an URI and the path in it
+    pub fn get_fs_by_uri<'a>(
+        &self,
+        uri: &'a GravitonUri,
+    ) -> Result<(FilesystemHandle, &'a str), Errors> {
+        self.get_fs_by_name(uri.get_filesystem())
+            .map(|filesystem| (filesystem, uri.get_path()))
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))
+    }
+
+    // Check if the state can be used with the specified token
+    pub fn has_token(&self, token: &str) -> bool {
+        self.has_scope(token, TokenScope::ReadOnly)
+    }
+
+    /// Check if the token grants the given scope, the owner has all of them
+    pub fn has_scope(&self, token: &str, scope: TokenScope) -> bool {
+        if self.is_owner_token(token) {
+            return true;
+        }
+
+        if let Some(invitation) = self.get_valid_invitation(token) {
+            let granted = match invitation.access {
+                InvitationAccess::Follow => TokenScope::ReadOnly,
+                InvitationAccess::Edit => TokenScope::Edit,
+            };
+            return granted.includes(scope);
+        }
+
+        self.scoped_tokens
+            .values()
+            .any(|scoped| scoped.token == token && !scoped.is_expired() && scoped.allows(scope))
+    }
+
+    /// Check if the token grants any of the given scopes
+    pub fn has_any_scope(&self, token: &str, scopes: &[TokenScope]) -> bool {
+        scopes.iter().any(|scope| self.has_scope(token, *scope))
+    }
+
+    /// Check if the token belongs to the owner of the State and not to a guest
+    pub fn is_owner_token(&self, token: &str) -> bool {
+        self.tokens.contains(&token.to_owned())
+    }
+
+    /// Check if the token is allowed to modify the State
+    pub fn has_edit_access(&self, token: &str) -> bool {
+        self.has_scope(token, TokenScope::Edit)
+    }
+
+    /// Remember the locale and formats declared by the client using a token
+    pub fn set_client_locale(&mut self, token: &str, locale: ClientLocale) {
+        self.client_locales.insert(token.to_owned(), locale);
+    }
+
+    /// Preferences of the client using a token, the neutral ones if it didn't declare any
+    pub fn get_client_locale(&self, token: &str) -> ClientLocale {
+        self.client_locales.get(token).cloned().unwrap_or_default()
+    }
+
+    /// Find a non-expired invitation by its token
+    fn get_valid_invitation(&self, token: &str) -> Option<&Invitation> {
+        self.invitations
+            .values()
+            .find(|invitation| invitation.token == token && !invitation.is_expired())
+    }
+
+    /// Invite a guest to this State
+    pub fn create_invitation(
+        &mut self,
+        access: InvitationAccess,
+        duration: Duration,
+    ) -> Invitation {
+        // Forget expired invitations
+        self.invitations
+            .retain(|_, invitation| !invitation.is_expired());
+
+        let invitation = Invitation::new(self.data.id, access, duration);
+        self.invitations
+            .insert(invitation.id.clone(), invitation.clone());
+
+        info!(
+            "Created invitation <{}> for State by id <{}>",
+            invitation.id, self.data.id
+        );
+
+        invitation
+    }
+
+    /// Revoke the access of a guest, even if it's in the middle of a session.
+    /// The transports close its connections once they are told
+    pub async fn revoke_invitation(&mut self, invitation_id: &str) -> Result<(), Errors> {
+        self.invitations
+            .remove(invitation_id)
+            .ok_or(Errors::InvitationNotFound)?;
+
+        self.extensions_manager
+            .sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::InvitationRevoked {
+                    state_id: self.data.id,
+                    invitation_id: invitation_id.to_owned(),
+                },
+            ))
+            .await
+            .ok();
+
+        Ok(())
+    }
+
+    /// Give a token with limited access to this State
+    ///
+    /// # Arguments
+    ///
+    /// * `label`      - Who or what the token is for
+    /// * `scopes`     - What the token allows
+    /// * `duration`   - For how long it's valid, forever if None
+    ///
+    pub fn issue_token(
+        &mut self,
+        label: &str,
+        scopes: Vec<TokenScope>,
+        duration: Option<Duration>,
+    ) -> ScopedToken {
+        // Forget expired tokens
+        self.scoped_tokens.retain(|_, scoped| !scoped.is_expired());
+
+        let scoped = ScopedToken::new(self.data.id, label, scopes, duration);
+        self.scoped_tokens.insert(scoped.id.clone(), scoped.clone());
+
+        info!(
+            "Issued token <{}> for State by id <{}>",
+            scoped.id, self.data.id
+        );
+
+        scoped
+    }
+
+    /// Revoke a scoped token, the transports close the connections using it once they are told
+    pub async fn revoke_token(&mut self, token_id: &str) -> Result<(), Errors> {
+        self.scoped_tokens
+            .remove(token_id)
+            .ok_or(Errors::TokenNotFound)?;
+
+        self.extensions_manager
+            .sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::TokenRevoked {
+                    state_id: self.data.id,
+                    token_id: token_id.to_owned(),
+                },
+            ))
+            .await
+            .ok();
+
+        Ok(())
+    }
+
+    /// Return all the scoped tokens that are still valid
+    pub fn get_scoped_tokens(&self) -> Vec<ScopedToken> {
+        self.scoped_tokens
+            .values()
+            .filter(|scoped| !scoped.is_expired())
+            .cloned()
+            .collect()
+    }
+
+    /// Return all the invitations that are still valid
+    pub fn get_invitations(&self) -> Vec<Invitation> {
+        self.invitations
+            .values()
+            .filter(|invitation| !invitation.is_expired())
+            .cloned()
+            .collect()
+    }
+
+    /// Run all the extensions in the manager
+    pub async fn run_extensions(&self, state_handle: Arc<Mutex<State>>) {
+        for instance in self.extensions_manager.get_all_instances() {
+            // Apply the configured panic policy
+            instance.health.lock().await.policy = self
+                .data
+                .panic_policies
+                .get(instance.parent_id)
+                .copied()
+                .unwrap_or_default();
+
+            let state_handle = state_handle.clone();
+            run_isolated(
+                instance.parent_id,
+                instance.plugin,
+                instance.health,
+                &self.extensions_manager.sender,
+                self.data.id,
+                self.extensions_manager.telemetry.timed(
+                    instance.parent_id,
+                    ExtensionHandler::Init,
+                    move |ext_plugin| {
+                        ext_plugin.unload();
+                        ext_plugin.init(state_handle);
+                    },
+                ),
+            )
+            .await
+            .ok();
+        }
+    }
+
+    /// Retrieve the permissions of an extension
+    pub fn get_extension_permissions(&self, ext_id: &str) -> Result<ExtensionPermissions, Errors> {
+        self.extensions_manager
+            .permissions
+            .get(ext_id)
+            .ok_or(Errors::Ext(ExtensionErrors::ExtensionNotFound))
+    }
+
+    /// Make sure an extension can do something, extensions without managed permissions have full access
+    fn check_extension_permission(
+        &self,
+        ext_id: &str,
+        permission: &Permission,
+    ) -> Result<(), Errors> {
+        match self.extensions_manager.permissions.get(ext_id) {
+            Some(permissions) if !permissions.allows(permission) => {
+                Err(Errors::Ext(ExtensionErrors::PermissionDenied))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Allow an extension to do something
+    pub fn grant_extension_permission(
+        &self,
+        ext_id: &str,
+        permission: Permission,
+    ) -> Result<(), Errors> {
+        self.extensions_manager
+            .permissions
+            .grant(ext_id, permission)
+            .map_err(Errors::Ext)
+    }
+
+    /// Disallow an extension to do something
+    pub fn revoke_extension_permission(
+        &self,
+        ext_id: &str,
+        permission: &Permission,
+    ) -> Result<(), Errors> {
+        self.extensions_manager
+            .permissions
+            .revoke(ext_id, permission)
+            .map_err(Errors::Ext)
+    }
+
+    /// Find the supervision data of an extension instance
+    fn get_extension_health(&self, ext_id: &str) -> Result<Arc<Mutex<ExtensionHealth>>, Errors> {
+        self.extensions_manager
+            .get_instances(ext_id)
+            .next()
+            .map(|instance| instance.health.clone())
+            .ok_or(Errors::Ext(ExtensionErrors::ExtensionNotFound))
+    }
+
+    /// Retrieve the panics and restarts counters of an extension
+    pub async fn get_extension_status(&self, ext_id: &str) -> Result<ExtensionStatus, Errors> {
+        let health = self.get_extension_health(ext_id)?;
+        let status = health.lock().await.status;
+        Ok(status)
+    }
+
+    pub async fn get_extension_metrics(&self, ext_id: &str) -> Result<ExtensionMetrics, Errors> {
+        let health = self.get_extension_health(ext_id)?;
+        let metrics = health.lock().await.metrics;
+        Ok(metrics)
+    }
+
+    /// Start timing the extensions, the filesystems and the messages of this State
+    pub fn enable_telemetry(&self, config: TelemetryConfig) {
+        self.extensions_manager.telemetry.enable(config);
+    }
+
+    /// Stop the telemetry and forget what was measured
+    pub fn disable_telemetry(&self) {
+        self.extensions_manager.telemetry.disable();
+    }
+
+    /// What the telemetry has measured, none if it's not enabled
+    pub fn get_metrics(&self) -> Option<TelemetryReport> {
+        self.extensions_manager.telemetry.get_report()
+    }
+
+    /// Summarize what the State is holding: caches, watchers, documents, processes and language servers
+    pub fn get_resource_report(&self) -> ResourceReport {
+        let memory = CachesMemory {
+            documents: self.documents.get_size(),
+            drafts: self.drafts.iter().map(|draft| draft.content.len()).sum(),
+            output_buffers: self.output_buffers.get_size(),
+            indexer: self.indexer.get_size(),
+            snapshots: self.snapshots.get_size(),
+        };
+
+        #[allow(unused_mut)]
+        let mut running_processes = self.terminal_shells.len() + self.task_runner.get_runs().len();
+        #[cfg(feature = "kernels")]
+        {
+            running_processes += self.kernels.len();
+        }
+
+        ResourceReport {
+            state_id: self.data.id,
+            hibernated: self.is_hibernated(),
+            memory,
+            watchers: self.repository_watchers.len()
+                + self.extension_watchers.len()
+                + self.workspace_watchers.len(),
+            open_documents: self.documents.len() + self.custom_documents.len(),
+            running_processes,
+            language_servers: self.language_servers.len()
+                + self.language_servers_manager.get_all_running().len(),
+            running_operations: self.searches.lock().unwrap().len() + self.file_operations.len(),
+        }
+    }
+
+    /// Configure what to do when an extension panics, it's persisted in the State data
+    pub async fn set_extension_panic_policy(
+        &mut self,
+        ext_id: &str,
+        policy: PanicPolicy,
+    ) -> Result<(), Errors> {
+        let health = self.get_extension_health(ext_id)?;
+        health.lock().await.policy = policy;
+
+        let mut data = self.data.clone();
+        data.panic_policies.insert(ext_id.to_owned(), policy);
+        self.update(data).await;
+
+        Ok(())
+    }
+
+    /// Configure the proxy, certificates and offline mode used for all the HTTP requests
+    pub async fn set_http_settings(&mut self, settings: HttpSettings) -> Result<(), Errors> {
+        #[cfg(feature = "http")]
+        {
+            self.http_client = HttpClient::new(settings.clone()).map_err(Errors::Http)?;
+        }
+
+        let mut data = self.data.clone();
+        data.http_settings = settings;
+        self.update(data).await;
+
+        Ok(())
+    }
+
+    /// Return the HTTP client extensions and services should use to make requests
+    #[cfg(feature = "http")]
+    pub fn get_http_client(&self) -> HttpClient {
+        self.http_client.clone()
+    }
+
+    /// Restart a extension, e.g after it crashed
+    pub async fn restart_extension(
+        &self,
+        ext_id: &str,
+        state_handle: Arc<Mutex<State>>,
+    ) -> Result<(), Errors> {
+        for instance in self.extensions_manager.get_instances(ext_id) {
+            let mut health = instance.health.lock().await;
+            health.status = ExtensionStatus::Running;
+            health.metrics.restarts += 1;
+        }
+
+        self.run_extension(ext_id, state_handle).await?;
+
+        info!("Restarted extension <{}>", ext_id);
+
+        self.subscriptions.broadcast(StateEvent::ExtensionReloaded {
+            extension_id: ext_id.to_owned(),
+        });
+
+        Ok(())
+    }
+
+    /// Run a specific extension, e.g after it was installed
+    pub async fn run_extension(
+        &self,
+        ext_id: &str,
+        state_handle: Arc<Mutex<State>>,
+    ) -> Result<(), Errors> {
+        let mut found = false;
+
+        for instance in self.extensions_manager.get_instances(ext_id) {
+            found = true;
+
+            let state_handle = state_handle.clone();
+            run_isolated(
+                instance.parent_id,
+                instance.plugin,
+                instance.health,
+                &self.extensions_manager.sender,
+                self.data.id,
+                self.extensions_manager.telemetry.timed(
+                    instance.parent_id,
+                    ExtensionHandler::Init,
+                    move |ext_plugin| {
+                        ext_plugin.unload();
+                        ext_plugin.init(state_handle);
+                    },
+                ),
+            )
+            .await
+            .map_err(Errors::Ext)?;
+        }
+
+        if found {
+            Ok(())
+        } else {
+            Err(Errors::Ext(ExtensionErrors::ExtensionNotFound))
+        }
+    }
+
+    /// Load again an extension from the filesystem and run it with the current State
+    pub async fn reload_extension(
+        &mut self,
+        ext_id: &str,
+        state_handle: Arc<Mutex<State>>,
+    ) -> Result<(), Errors> {
+        self.extensions_manager
+            .reload_extension(ext_id, self.data.id)
+            .await
+            .map_err(Errors::Ext)?;
+
+        // The new instance publishes its decorations and tasks again
+        self.clear_file_decorations(ext_id).await;
+        self.task_runner.unregister_extension(ext_id);
+
+        for instance in self.extensions_manager.get_instances(ext_id) {
+            instance.health.lock().await.policy = self
+                .data
+                .panic_policies
+                .get(ext_id)
+                .copied()
+                .unwrap_or_default();
+        }
+
+        self.run_extension(ext_id, state_handle).await?;
+
+        // The new instance didn't see any previous update
+        self.notify_extension(
+            ext_id.to_owned(),
+            ClientMessages::ServerMessage(ServerMessages::StateUpdated {
+                state_data: Box::new(self.data.clone()),
+            }),
+        );
+
+        info!("Reloaded extension <{}>", ext_id);
+
+        self.subscriptions.broadcast(StateEvent::ExtensionReloaded {
+            extension_id: ext_id.to_owned(),
+        });
+
+        Ok(())
+    }
+
+    /// Reload an extension whenever its manifest or its entry file change
+    pub async fn watch_extension(&mut self, ext_id: &str) -> Result<(), Errors> {
+        let source = self
+            .extensions_manager
+            .sources
+            .get(ext_id)
+            .ok_or(Errors::Ext(ExtensionErrors::NotReloadable))?;
+
+        if self.extension_watchers.contains_key(ext_id) {
+            return Ok(());
+        }
+
+        let mut paths = vec![source.manifest.location.clone()];
+        if let (Some(main), Some(folder)) = (
+            &source.manifest.info.extension.main,
+            source.manifest.location.parent(),
+        ) {
+            paths.push(folder.join(main));
+        }
+
+        let token = CancellationToken::new();
+        self.extension_watchers
+            .insert(ext_id.to_owned(), token.clone());
+
+        let sender = self.extensions_manager.sender.clone();
+        let state_id = self.data.id;
+        let extension_id = ext_id.to_owned();
+        let mut last_modified = get_modification_times(&paths).await;
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+
+                if token.is_cancelled() {
+                    break;
+                }
+
+                let modified = get_modification_times(&paths).await;
+                if modified != last_modified {
+                    last_modified = modified;
+                    sender
+                        .send(ClientMessages::ReloadExtension {
+                            state_id,
+                            extension_id: extension_id.clone(),
+                        })
+                        .await
+                        .ok();
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Stop reloading an extension when its files change
+    pub fn unwatch_extension(&mut self, ext_id: &str) {
+        if let Some(token) = self.extension_watchers.remove(ext_id) {
+            token.cancel();
+        }
+    }
+
+    /// Notify a specific extension about a perticular message
+    pub fn notify_extension(&self, extension_id: String, message: ClientMessages) {
+        let mut instances = self
+            .extensions_manager
+            .get_instances(&extension_id)
+            .peekable();
+        let mut message = Some(message);
+
+        while let Some(instance) = instances.next() {
+            let ext_plugin = instance.plugin.clone();
+            let health = instance.health.clone();
+            let sender = self.extensions_manager.sender.clone();
+            let state_id = self.data.id;
+            let extension_id = extension_id.clone();
+            // Extensions usually have a single instance, so the message is rarely cloned
+            let message = match instances.peek() {
+                Some(_) => message.clone(),
+                None => message.take(),
+            };
+            let message = match message {
+                Some(message) => message,
+                None => break,
+            };
+            let call = self.extensions_manager.telemetry.timed(
+                &extension_id,
+                ExtensionHandler::Notify,
+                move |ext_plugin| ext_plugin.notify(message),
+            );
+            tokio::spawn(async move {
+                run_isolated(&extension_id, &ext_plugin, &health, &sender, state_id, call)
+                    .await
+                    .ok();
+            });
+        }
+    }
+
+    /// Send a custom message to the extension handling its type, the answer is sent back as a `CustomResponse`
+    pub fn send_custom_request(
+        &mut self,
+        request_id: &str,
+        message_type: &str,
+        params: serde_json::Value,
+    ) -> Result<(), Errors> {
+        let extension_id = self
+            .extensions_manager
+            .message_handlers
+            .route(request_id, message_type, self.data.id)
+            .ok_or(Errors::Ext(ExtensionErrors::MessageHandlerNotFound))?;
+
+        self.notify_extension(
+            extension_id,
+            ClientMessages::CustomRequest {
+                state_id: self.data.id,
+                request_id: request_id.to_owned(),
+                message_type: message_type.to_owned(),
+                params,
+            },
+        );
+        Ok(())
+    }
+
+    /// Forget an answered custom message, returns false if the extension wasn't asked
+    pub fn complete_custom_request(&mut self, request_id: &str, extension_id: &str) -> bool {
+        self.extensions_manager
+            .message_handlers
+            .complete(request_id, extension_id)
+    }
+
+    /// Deliver a message between extensions, if it fails the sender is notified
+    pub fn send_extension_message(&self, message: ExtensionMessage) -> Result<(), Errors> {
+        let from = message.from.clone();
+        let target = message.target.clone();
+
+        let result = self
+            .extensions_manager
+            .send(message, self.data.id)
+            .map_err(Errors::Ext);
+
+        if let Err(err) = &result {
+            self.notify_extension(
+                from,
+                ClientMessages::ExtensionMessageFailed {
+                    state_id: self.data.id,
+                    target,
+                    error: err.clone(),
+                },
+            );
+        }
+
+        result
+    }
+
+    /// Add a command that can be invoked from the clients, e.g when initializing an extension
+    pub fn register_command(&mut self, command: CommandInfo) {
+        self.extensions_manager.commands.register(command);
+    }
+
+    pub fn get_commands(&self) -> Vec<CommandInfo> {
+        self.extensions_manager.commands.get_all()
+    }
+
+    /// If the clipboard is synced and how much, from the user settings
+    pub fn get_clipboard_settings(&self) -> ClipboardSettings {
+        let default = ClipboardSettings::default();
+        ClipboardSettings {
+            enabled: self
+                .get_setting(CLIPBOARD_SYNC_SETTING)
+                .and_then(|enabled| enabled.as_bool())
+                .unwrap_or(default.enabled),
+            max_size: self
+                .get_setting(CLIPBOARD_MAX_SIZE_SETTING)
+                .and_then(|max_size| max_size.as_u64())
+                .map(|max_size| max_size as usize)
+                .unwrap_or(default.max_size),
+        }
+    }
+
+    /// Copy a text to the clipboard of the State, the clients are told so they can read it and paste it locally
+    pub async fn copy_to_clipboard(
+        &mut self,
+        text: String,
+        origin: ClipboardOrigin,
+    ) -> Result<ClipboardEntry, Errors> {
+        let settings = self.get_clipboard_settings();
+        let entry = self
+            .clipboard
+            .copy(&settings, text, origin)
+            .map_err(Errors::Clipboard)?;
+
+        self.extensions_manager
+            .sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::ClipboardChanged {
+                    state_id: self.data.id,
+                    origin: entry.origin.clone(),
+                    copied_at: entry.copied_at,
+                },
+            ))
+            .await
+            .ok();
+
+        Ok(entry)
+    }
+
+    /// What was last copied, e.g to paste in a remote terminal what was copied locally
+    pub fn get_clipboard(&self) -> Result<Option<ClipboardEntry>, Errors> {
+        self.clipboard
+            .get(&self.get_clipboard_settings())
+            .map_err(Errors::Clipboard)
+    }
+
+    /// Sync what the programs running in a terminal copied
+    pub async fn copy_from_terminal(&mut self, terminal_shell_id: &str, output: &str) {
+        for text in extract_osc52(output) {
+            let origin = ClipboardOrigin::Terminal {
+                terminal_shell_id: terminal_shell_id.to_owned(),
+            };
+            match self.copy_to_clipboard(text, origin).await {
+                Ok(_) | Err(Errors::Clipboard(ClipboardErrors::SyncDisabled)) => {}
+                Err(err) => warn!(
+                    "Could not sync what terminal <{}> copied, error: {:?}",
+                    terminal_shell_id, err
+                ),
+            }
+        }
+    }
+
+    /// Fuzzy matcher configured with the user's weights
+    pub fn get_matcher(&self) -> FuzzyMatcher {
+        let weights = self
+            .get_setting(MATCHER_WEIGHTS_SETTING)
+            .and_then(|weights| serde_json::from_value(weights).ok())
+            .unwrap_or_default();
+        FuzzyMatcher::new(weights)
+    }
+
+    /// Commands matching a query, the recently invoked ones first when they match equally
+    pub fn search_commands(&self, query: &str) -> Vec<Ranked<CommandInfo>> {
+        self.get_matcher().rank(
+            query,
+            self.get_commands(),
+            Some(&self.recent_commands),
+            now_secs(),
+        )
+    }
+
+    /// Rank some candidates, e.g to filter the completions of a Language Server
+    pub fn fuzzy_match(
+        &self,
+        query: &str,
+        candidates: Vec<String>,
+        kind: MatchKind,
+    ) -> Vec<Ranked<String>> {
+        let matcher = self.get_matcher();
+        let mut ranked = candidates
+            .into_iter()
+            .filter_map(|candidate| {
+                let found = matcher.score(query, &candidate, kind)?;
+                Some(Ranked {
+                    item: candidate,
+                    score: found.score,
+                    positions: found.positions,
+                })
+            })
+            .collect::<Vec<_>>();
+        ranked.sort_by_key(|ranked| Reverse(ranked.score));
+        ranked
+    }
+
+    /// Ask an extension to run a command, the result is sent back as a `CommandFinished`.
+    /// Returns the ID of the invocation
+    pub fn invoke_command(
+        &mut self,
+        command_id: &str,
+        arguments: serde_json::Value,
+    ) -> Result<String, Errors> {
+        let invocation_id = Uuid::new_v4().to_string();
+        let command = self
+            .extensions_manager
+            .commands
+            .invoke(&invocation_id, command_id, &arguments, self.data.id)
+            .map_err(Errors::Command)?;
+
+        self.recent_commands.record(&command.id, now_secs());
+
+        self.notify_extension(
+            command.extension_id,
+            ClientMessages::InvokeCommand {
+                state_id: self.data.id,
+                invocation_id: invocation_id.clone(),
+                command_id: command.id,
+                arguments,
+            },
+        );
+        Ok(invocation_id)
+    }
+
+    /// Forget a finished invocation, returns false if the extension wasn't running it
+    pub fn finish_command(&mut self, invocation_id: &str, extension_id: &str) -> bool {
+        self.extensions_manager
+            .commands
+            .finish(invocation_id, extension_id)
+            .is_some()
+    }
+
+    /// Custom message types handled by every extension
+    pub fn get_message_handlers(&self) -> HashMap<String, String> {
+        self.extensions_manager.message_handlers.get_handlers()
+    }
+
+    /// Notify the extensions in a state interested in a message, asynchronously and independently
+    pub fn notify_extensions(&self, message: ClientMessages) {
+        let subscribers = self
+            .extensions_manager
+            .subscriptions
+            .get_subscribers(&message);
+
+        for instance in self.extensions_manager.get_all_instances() {
+            if !subscribers.contains(instance.parent_id) {
+                continue;
+            }
+
+            let ext_plugin = instance.plugin.clone();
+            let health = instance.health.clone();
+            let parent_id = instance.parent_id.to_owned();
+            let sender = self.extensions_manager.sender.clone();
+            let state_id = self.data.id;
+            let message = message.clone();
+            let call = self.extensions_manager.telemetry.timed(
+                &parent_id,
+                ExtensionHandler::Notify,
+                move |ext_plugin| ext_plugin.notify(message),
+            );
+            tokio::spawn(async move {
+                run_isolated(&parent_id, &ext_plugin, &health, &sender, state_id, call)
+                    .await
+                    .ok();
+            });
+        }
+    }
+
+    /// Try to retrieve info about a perticular loaded extension
+    pub fn get_ext_info_by_id(&self, ext_id: &str) -> Result<ManifestInfo, Errors> {
+        self.extensions_manager
+            .get_manifest_info(ext_id)
+            .cloned()
+            .ok_or(Errors::Ext(ExtensionErrors::ExtensionNotFound))
+    }
+
+    /// Try to retrieve info about a perticular loaded extension
+    pub fn get_ext_run_info_by_id(&self, ext_id: &str) -> Result<ExtensionInfo, Errors> {
+        self.extensions_manager
+            .get_instance_by_info_id(ext_id)
+            .map(|instance| instance.info.clone())
+            .ok_or(Errors::Ext(ExtensionErrors::ExtensionNotFound))
+    }
+
+    /// Return the list of loaded extensions
+    pub fn get_ext_list(&self) -> Vec<String> {
+        self.extensions_manager.get_manifest_ids()
+    }
+
+    /// Merge an update into the state data, the previous one is kept as a snapshot
+    ///
+    /// Fields modified by someone else since the revision the update was made on are not applied,
+    /// in that case the clients are sent the merged data
+    pub async fn update(&mut self, update: impl Into<StateDataUpdate>) -> StateDataMerge {
+        self.update_as(update.into(), None).await
+    }
+
+    /// Same as [`State::update`] but only the owner can change the host commands
+    pub async fn update_with_token(
+        &mut self,
+        update: StateDataUpdate,
+        token: &str,
+    ) -> Result<StateDataMerge, Errors> {
+        if self.data.changes_host_commands(&update) && !self.is_owner_token(token) {
+            Err(Errors::AccessDenied)
+        } else {
+            Ok(self.update(update).await)
+        }
+    }
+
+    /// Same as [`State::update`], the action is guessed from the modified fields if it's not given
+    async fn update_as(
+        &mut self,
+        update: StateDataUpdate,
+        action: Option<DataAction>,
+    ) -> StateDataMerge {
+        self.end_abandoned_group(COMMAND_GROUP_TIMEOUT).await;
+
+        let base = update.revision.and_then(|revision| {
+            self.snapshots
+                .find_revision(revision)
+                .cloned()
+                .or_else(|| self.event_log.get_data_at(revision))
+        });
+        let mut data = self.data.clone();
+        let merge = data.merge(update, base.as_ref());
+
+        if !merge.conflicts.is_empty() {
+            warn!(
+                "Conflicting update in State by id <{}>, fields {:?} were not applied",
+                self.data.id, merge.conflicts
+            );
+        }
+
+        // Only save it if there has been any mutation in the state data
+        if merge.changed.is_empty() {
+            info!(
+                "Data from State by id <{}>, hasn't been modified",
+                self.data.id
+            );
+        } else if let Some(group) = &mut self.command_group {
+            // It's applied as a whole once the group is ended
+            group.add_fields(&merge.changed);
+            self.replace_data(data);
+        } else {
+            let action = action
+                .unwrap_or_else(|| DataAction::from_change(&self.data, &data, &merge.changed));
+            self.snapshots
+                .push(StateSnapshot::new(&action.get_label(), self.data.clone()));
+            self.apply_data(data, action).await;
+            self.subscriptions.broadcast(StateEvent::DataChanged {
+                delta: Box::new(self.data.get_delta(&merge.changed)),
+            });
+        }
+
+        // The client that made the update is sent the merged data, even inside a group
+        if !merge.conflicts.is_empty() {
+            self.notify_data_updated().await;
+        }
+
+        merge
+    }
+
+    /// Replace the state data, log the change and persist it
+    async fn apply_data(&mut self, new_data: StateData, action: DataAction) {
+        let previous = self.replace_data(new_data);
+        self.event_log.record(action, &previous, &self.data);
+        self.persist_data().await;
+    }
+
+    /// Replace the state data and apply its settings, returns the previous data
+    fn replace_data(&mut self, mut new_data: StateData) -> StateData {
+        for settings in &mut new_data.ftp_connections {
+            settings.store_password(&self.secrets);
+        }
+
+        #[cfg(feature = "http")]
+        if &new_data.http_settings != self.http_client.get_settings() {
+            match HttpClient::new(new_data.http_settings.clone()) {
+                Ok(http_client) => self.http_client = http_client,
+                Err(err) => warn!("Could not apply the HTTP settings, error: {:?}", err),
+            }
+        }
+
+        // The ID never changes
+        let id = self.data.id;
+        let previous = std::mem::replace(&mut self.data, StateData { id, ..new_data });
+
+        #[cfg(feature = "ftp")]
+        if self.data.ftp_connections != previous.ftp_connections {
+            self.mount_ftp_filesystems(&previous.ftp_connections);
+        }
+
+        self.close_modal_engines_of_closed_tabs();
+
+        previous
+    }
+
+    /// Save the state data and the snapshots
+    async fn persist_data(&self) {
+        if let Some(persistor) = &self.persistor {
+            let mut persistor = persistor.lock().await;
+            let started = Instant::now();
+            persistor.save(&self.data);
+            persistor.save_history(&self.snapshots);
+            persistor.save_event_log(&self.event_log);
+            METRICS.record_persist(started.elapsed());
+        } else {
+            warn!(
+                "Persistor not found for State by id <{}>, could not save",
+                self.data.id
+            );
+        }
+    }
+
+    /// User settings, including the default values of the settings contributed by the extensions
+    pub fn get_settings(&self) -> UserSettings {
+        UserSettings {
+            values: self
+                .settings
+                .get_values(&self.extensions_manager.settings_schemas),
+            keybindings: self.settings.get_settings().keybindings.clone(),
+        }
+    }
+
+    /// Value of a setting, or its default value
+    pub fn get_setting(&self, key: &str) -> Option<serde_json::Value> {
+        self.settings
+            .get(key, &self.extensions_manager.settings_schemas)
+    }
+
+    pub fn get_settings_schemas(&self) -> Vec<SettingSchema> {
+        self.extensions_manager.settings_schemas.get_all()
+    }
+
+    /// Gate features of the Core behind flags, extensions contribute theirs when they are registered
+    pub fn register_core_experiments(&mut self, flags: Vec<ExperimentFlag>) {
+        self.extensions_manager
+            .experiments
+            .register(CORE_EXPERIMENTS_OWNER, flags);
+    }
+
+    /// What the user chose for a flag, values other than booleans are ignored
+    fn get_experiment_override(&self, flag_id: &str) -> Option<bool> {
+        self.settings
+            .get_settings()
+            .values
+            .get(&format!("{}{}", EXPERIMENTS_SETTING_PREFIX, flag_id))
+            .and_then(|value| value.as_bool())
+    }
+
+    /// Check if a feature is enabled in this install, unknown flags are always disabled
+    pub fn is_experiment_enabled(&self, flag_id: &str) -> bool {
+        self.extensions_manager
+            .experiments
+            .get_status(flag_id, self.get_experiment_override(flag_id))
+            .is_some_and(|status| status.enabled)
+    }
+
+    /// Every registered flag and whether it's enabled
+    pub fn get_experiments(&self) -> Vec<ExperimentStatus> {
+        let experiments = &self.extensions_manager.experiments;
+        let mut statuses = experiments
+            .get_all()
+            .iter()
+            .filter_map(|flag| {
+                experiments.get_status(&flag.id, self.get_experiment_override(&flag.id))
+            })
+            .collect::<Vec<ExperimentStatus>>();
+        statuses.sort_by(|a, b| a.flag.id.cmp(&b.flag.id));
+        statuses
+    }
+
+    /// Enable or disable a feature regardless of its rollout, or follow the rollout again with `None`.
+    /// The override is kept in the user settings
+    pub async fn set_experiment_override(
+        &mut self,
+        flag_id: &str,
+        enabled: Option<bool>,
+    ) -> Result<(), Errors> {
+        let key = self
+            .extensions_manager
+            .experiments
+            .get(flag_id)
+            .ok_or(Errors::Experiment(ExperimentsErrors::FlagNotFound))?
+            .get_setting_key();
+
+        match enabled {
+            Some(enabled) => {
+                let values = BTreeMap::from([(key, serde_json::Value::Bool(enabled))]);
+                self.set_settings(values).await
+            }
+            None if self.get_experiment_override(flag_id).is_some() => {
+                self.reset_setting(&key).await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Validate configuration files with a schema, e.g one of schemastore.org.
+    /// It replaces the schema registered by a client with the same name
+    pub fn register_validation_schema(&mut self, schema: ValidationSchema) {
+        self.extensions_manager
+            .validation_schemas
+            .register("client", vec![schema]);
+    }
+
+    pub fn get_validation_schemas(&self) -> Vec<ValidationSchema> {
+        self.extensions_manager.validation_schemas.get_all()
+    }
+
+    /// Validate a file with it's schema, the content of the open document is used if it's open.
+    /// There are no diagnostics if no schema applies to it
+    pub async fn validate_file(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+    ) -> Result<Vec<Diagnostic>, Errors> {
+        let schemas = &self.extensions_manager.validation_schemas;
+        if schemas.find(path).is_none() {
+            return Ok(Vec::new());
+        }
+
+        let content = match self.documents.get(filesystem_name, path) {
+            Some(document) => document.get_content(),
+            None => {
+                self.read_file_with_encoding(filesystem_name, path, None)
+                    .await?
+                    .content
+            }
+        };
+
+        Ok(schemas.validate(path, &content).unwrap_or_default())
+    }
+
+    /// Modify several settings, they are validated against the schemas contributed by the extensions
+    pub async fn set_settings(
+        &mut self,
+        values: BTreeMap<String, serde_json::Value>,
+    ) -> Result<(), Errors> {
+        let keys = self
+            .settings
+            .set(values, &self.extensions_manager.settings_schemas)
+            .map_err(Errors::Settings)?;
+        self.notify_settings_changed(keys).await;
+        Ok(())
+    }
+
+    /// Go back to the default value of a setting
+    pub async fn reset_setting(&mut self, key: &str) -> Result<(), Errors> {
+        self.settings.reset(key).map_err(Errors::Settings)?;
+        self.notify_settings_changed(vec![key.to_owned()]).await;
+        Ok(())
+    }
+
+    pub async fn set_keybindings(&mut self, keybindings: Vec<Keybinding>) {
+        if self.settings.set_keybindings(keybindings) {
+            self.notify_settings_changed(vec!["keybindings".to_owned()])
+                .await;
+        }
+    }
+
+    /// Persist the settings and let the clients and the extensions know they changed
+    async fn notify_settings_changed(&self, keys: Vec<String>) {
+        if keys.is_empty() {
+            return;
+        }
+
+        if let Some(persistor) = &self.persistor {
+            persistor
+                .lock()
+                .await
+                .save_settings(self.settings.get_settings());
+        }
+
+        self.extensions_manager
+            .sender
+            .send(ClientMessages::SettingsChanged {
+                state_id: self.data.id,
+                keys,
+                settings: self.get_settings(),
+            })
+            .await
+            .ok();
+    }
+
+    /// Save the current views as a named layout, replacing the one with the same name
+    pub async fn save_layout(&mut self, name: &str) {
+        let mut data = self.data.clone();
+        data.save_layout(name, now_secs());
+        self.update_as(
+            data.into(),
+            Some(DataAction::LayoutSaved {
+                name: name.to_owned(),
+            }),
+        )
+        .await;
+    }
+
+    /// Switch to a saved layout, this can be undone like any other update
+    pub async fn apply_layout(&mut self, name: &str) -> Result<(), Errors> {
+        let mut data = self.data.clone();
+        if !data.apply_layout(name) {
+            return Err(Errors::LayoutNotFound);
+        }
+        self.update_as(
+            data.into(),
+            Some(DataAction::LayoutApplied {
+                name: name.to_owned(),
+            }),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Recently closed tabs, the last closed goes last
+    pub fn get_closed_tabs(&self) -> Vec<ClosedTab> {
+        self.data.closed_tabs.clone()
+    }
+
+    /// Put the last closed tab back where it was
+    pub async fn reopen_last_closed_tab(&mut self) -> Result<TabData, Errors> {
+        self.reopen_closed_tab(None).await
+    }
+
+    /// Put a closed tab back where it was, by its position in the closed tabs or the last one
+    pub async fn reopen_closed_tab(&mut self, index: Option<usize>) -> Result<TabData, Errors> {
+        let mut data = self.data.clone();
+        let tab = data
+            .reopen_closed_tab(index)
+            .ok_or(Errors::ClosedTabNotFound)?;
+        self.update_as(
+            data.into(),
+            Some(DataAction::TabReopened {
+                tab_id: tab.get_id().to_owned(),
+            }),
+        )
+        .await;
+        Ok(tab)
+    }
+
+    pub async fn remove_layout(&mut self, name: &str) -> Result<(), Errors> {
+        let mut data = self.data.clone();
+        if !data.remove_layout(name) {
+            return Err(Errors::LayoutNotFound);
+        }
+        self.update_as(
+            data.into(),
+            Some(DataAction::LayoutRemoved {
+                name: name.to_owned(),
+            }),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Add a scheduled task, or replace the one with the same ID
+    pub async fn set_scheduled_task(&mut self, task: ScheduledTask) {
+        let mut data = self.data.clone();
+        match data.scheduled_tasks.iter_mut().find(|t| t.id == task.id) {
+            Some(existing_task) => *existing_task = task,
+            None => data.scheduled_tasks.push(task),
+        }
+        self.update(data).await;
+    }
+
+    pub async fn remove_scheduled_task(&mut self, task_id: &str) -> Result<(), Errors> {
+        let mut data = self.data.clone();
+        let tasks_count = data.scheduled_tasks.len();
+        data.scheduled_tasks.retain(|task| task.id != task_id);
+
+        if data.scheduled_tasks.len() == tasks_count {
+            return Err(Errors::Task(TaskErrors::TaskNotFound));
+        }
+
+        self.update(data).await;
+        Ok(())
+    }
+
+    /// Enable or disable a scheduled task without removing it
+    pub async fn enable_scheduled_task(
+        &mut self,
+        task_id: &str,
+        enabled: bool,
+    ) -> Result<(), Errors> {
+        let mut data = self.data.clone();
+        let task = data
+            .scheduled_tasks
+            .iter_mut()
+            .find(|task| task.id == task_id)
+            .ok_or(Errors::Task(TaskErrors::TaskNotFound))?;
+        task.enabled = enabled;
+
+        self.update(data).await;
+        Ok(())
+    }
+
+    /// Return the scheduled tasks that should have run since the last check.
+    /// Hibernated States don't run tasks, the runs missed meanwhile are skipped
+    pub fn take_due_tasks(&mut self, now: u64) -> Vec<ScheduledTask> {
+        let checked_at = std::mem::replace(&mut self.tasks_checked_at, now);
+
+        if self.is_hibernated() {
+            return Vec::new();
+        }
+
+        self.data
+            .scheduled_tasks
+            .iter()
+            .filter(|task| task.is_due(checked_at, now))
+            .cloned()
+            .collect()
+    }
+
+    /// Save the result of a scheduled task, this can't be undone
+    pub async fn record_task_run(&mut self, task_id: &str, run: TaskRun) -> Result<(), Errors> {
+        let mut data = self.data.clone();
+        let task = data
+            .scheduled_tasks
+            .iter_mut()
+            .find(|task| task.id == task_id)
+            .ok_or(Errors::Task(TaskErrors::TaskNotFound))?;
+        task.last_run = Some(run);
+
+        data.revision += 1;
+        self.apply_data(
+            data,
+            DataAction::TaskRunRecorded {
+                task_id: task_id.to_owned(),
+            },
+        )
+        .await;
+        self.subscriptions.broadcast(StateEvent::DataChanged {
+            delta: Box::new(self.data.get_delta(&[StateDataField::ScheduledTasks])),
+        });
+
+        Ok(())
+    }
+
+    /// Add a task that can be run, or replace the one with the same ID
+    pub fn register_task(
+        &mut self,
+        task_id: &str,
+        definition: TaskDefinition,
+        extension_id: Option<String>,
+    ) -> Result<(), Errors> {
+        self.task_runner
+            .register(RegisteredTask {
+                id: task_id.to_owned(),
+                definition,
+                extension_id,
+            })
+            .map_err(Errors::Task)
+    }
+
+    pub fn unregister_task(&mut self, task_id: &str) -> Result<(), Errors> {
+        self.task_runner.unregister(task_id).map_err(Errors::Task)
+    }
+
+    pub fn get_tasks(&self) -> Vec<RegisteredTask> {
+        self.task_runner.get_tasks()
+    }
+
+    /// Run a registered task, its output is streamed to the clients.
+    /// Returns the ID of the run
+    pub fn run_task(&mut self, task_id: &str) -> Result<String, Errors> {
+        // Tasks of extensions are processes spawned on their behalf
+        if let Some(ext_id) = self
+            .task_runner
+            .get_task(task_id)
+            .and_then(|task| task.extension_id.clone())
+        {
+            self.check_extension_permission(&ext_id, &Permission::Run)?;
+        }
+
+        self.task_runner
+            .run(
+                task_id,
+                self.extensions_manager.sender.clone(),
+                self.data.id,
+            )
+            .map_err(Errors::Task)
+    }
+
+    pub fn cancel_task(&mut self, run_id: &str) -> Result<(), Errors> {
+        self.task_runner.cancel(run_id).map_err(Errors::Task)
+    }
+
+    pub fn get_running_tasks(&self) -> Vec<TaskRunInfo> {
+        self.task_runner.get_runs()
+    }
+
+    /// Listen for the changes made to the State from now on, e.g to keep other clients in sync
+    pub fn subscribe(&self) -> broadcast::Receiver<StateEvent> {
+        self.subscriptions.subscribe()
+    }
+
+    /// Save the current state data so it can be restored later
+    pub async fn snapshot(&mut self, label: &str) {
+        self.snapshots
+            .push(StateSnapshot::new(label, self.data.clone()));
+        self.persist_data().await;
+    }
+
+    /// Apply the next commands as a single undoable change until the group is ended,
+    /// e.g the steps of a refactor. If a group is already open this one joins it, unless it was abandoned
+    /// or begun by another client. Returns the ID of the group to end
+    ///
+    /// # Arguments
+    ///
+    /// * `label`   - Label of the undo snapshot
+    /// * `owner`   - Token of the client, only it can join or end the group
+    ///
+    pub async fn begin_group(&mut self, label: &str, owner: &str) -> Result<String, Errors> {
+        self.end_abandoned_group(COMMAND_GROUP_TIMEOUT).await;
+
+        match &mut self.command_group {
+            Some(group) if !group.is_owned_by(owner) => {
+                return Err(Errors::Transaction(TransactionErrors::GroupInUse));
+            }
+            Some(group) => group.begin(),
+            None => self.command_group = Some(CommandGroup::new(label, owner, self.data.clone())),
+        }
+        Ok(self
+            .command_group
+            .as_ref()
+            .map(|group| group.get_id().to_owned())
+            .unwrap_or_default())
+    }
+
+    /// End a group, once the outermost one is ended its changes are saved as a single snapshot
+    /// and the clients are sent one update. Returns what the group changed if it was the outermost
+    pub async fn end_group(
+        &mut self,
+        group_id: &str,
+        owner: &str,
+    ) -> Result<Option<CommandGroupInfo>, Errors> {
+        let group = self
+            .command_group
+            .as_mut()
+            .ok_or(Errors::Transaction(TransactionErrors::NotGrouping))?;
+        if group.get_id() != group_id {
+            return Err(Errors::Transaction(TransactionErrors::WrongGroup));
+        }
+        if !group.is_owned_by(owner) {
+            return Err(Errors::Transaction(TransactionErrors::GroupInUse));
+        }
+        if !group.end() {
+            return Ok(None);
+        }
+
+        Ok(self.finish_group().await)
+    }
+
+    /// End the open group, even if it's nested, when no commands were added to it for longer than the timeout.
+    /// Returns what it changed if it was ended
+    pub async fn end_abandoned_group(&mut self, timeout: Duration) -> Option<CommandGroupInfo> {
+        let is_abandoned = self
+            .command_group
+            .as_ref()
+            .is_some_and(|group| group.is_abandoned(timeout));
+        if !is_abandoned {
+            return None;
+        }
+
+        warn!(
+            "Ending abandoned command group in State by id <{}>",
+            self.data.id
+        );
+        self.finish_group().await
+    }
+
+    /// Save the changes of the open group as a single snapshot and send them to the clients
+    async fn finish_group(&mut self) -> Option<CommandGroupInfo> {
+        let (base, info) = self.command_group.take()?.finish();
+
+        if !info.fields.is_empty() {
+            let action = DataAction::Grouped {
+                label: info.label.clone(),
+                fields: info.fields.clone(),
+            };
+            self.snapshots
+                .push(StateSnapshot::new(&action.get_label(), base.clone()));
+            self.event_log.record(action, &base, &self.data);
+            self.persist_data().await;
+            self.subscriptions.broadcast(StateEvent::DataChanged {
+                delta: Box::new(self.data.get_delta(&info.fields)),
+            });
+            self.notify_data_updated().await;
+        }
+
+        self.extensions_manager
+            .sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::CommandGroupEnded {
+                    state_id: self.data.id,
+                    group: info.clone(),
+                },
+            ))
+            .await
+            .ok();
+
+        Some(info)
+    }
+
+    /// Restore the last snapshot, returns false if there was nothing to undo
+    pub async fn undo(&mut self) -> bool {
+        match self.snapshots.undo(self.data.clone()) {
+            Some(data) => {
+                self.restore_data(data, "undo").await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Restore the last undone snapshot, returns false if there was nothing to redo
+    pub async fn redo(&mut self) -> bool {
+        match self.snapshots.redo(self.data.clone()) {
+            Some(data) => {
+                self.restore_data(data, "redo").await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Snapshots that can be undone, from the oldest to the newest
+    pub fn get_snapshots(&self) -> &[StateSnapshot] {
+        self.snapshots.get_snapshots()
+    }
+
+    /// Changes made to the data after a revision, from the oldest to the newest
+    pub fn get_data_events(&self, since_revision: u64) -> Vec<DataEvent> {
+        self.event_log.get_events_since(since_revision)
+    }
+
+    /// Rebuild the data as it was on a past revision, e.g to find when something went wrong
+    pub fn get_data_at(&self, revision: u64) -> Option<StateData> {
+        self.event_log.get_data_at(revision)
+    }
+
+    /// Apply a snapshot as a new revision
+    async fn restore_data(&mut self, data: StateData, label: &str) {
+        let revision = self.data.revision + 1;
+        self.apply_data(
+            StateData { revision, ..data },
+            DataAction::Restored {
+                label: label.to_owned(),
+            },
+        )
+        .await;
+        self.subscriptions.broadcast(StateEvent::DataChanged {
+            delta: Box::new(self.data.clone().into()),
+        });
+        self.notify_data_updated().await;
+    }
+
+    /// Let the clients and extensions know about the new data
+    async fn notify_data_updated(&mut self) {
+        if let Some(message) = self.get_data_message() {
+            self.extensions_manager
+                .sender
+                .send(ClientMessages::ServerMessage(message))
+                .await
+                .ok();
+        }
+    }
+
+    /// Changes in the data since the clients were last sent it, or all of it periodically.
+    /// `None` if nothing changed
+    pub fn get_data_message(&mut self) -> Option<ServerMessages> {
+        self.delta_sync.next(&self.data)
+    }
+
+    /// The whole data, e.g when the clients missed some changes
+    pub fn get_data_snapshot_message(&mut self) -> ServerMessages {
+        self.delta_sync.snapshot(&self.data)
+    }
+
+    /// Return all the registered language server builders
+    pub async fn get_all_language_server_builders(&self) -> Vec<LanguageServerBuilderInfo> {
+        let mut list = vec![];
+        let language_server_builders = self.language_server_builders.values();
+
+        for language_server_builder in language_server_builders {
+            list.push(language_server_builder.lock().await.get_info());
+        }
+
+        list
+    }
+
+    /// Return all the registered terminal shell builders
+    pub async fn get_terminal_shell_builders(&self) -> Vec<TerminalShellBuilderInfo> {
+        let mut list = vec![];
+        let shell_builders = self.terminal_shell_builders.values();
+
+        for shell_builder in shell_builders {
+            list.push(shell_builder.lock().await.get_info());
+        }
+
+        list
+    }
+
+    /// Create a new terminal shell from a builder ID
+    pub async fn create_terminal_shell(
+        &mut self,
+        terminal_shell_builder_id: String,
+        terminal_shell_id: String,
+    ) {
+        let shell_builder = self.terminal_shell_builders.get(&terminal_shell_builder_id);
+
+        if let Some(shell_builder) = shell_builder {
+            let shell_builder = shell_builder.lock().await;
+            match shell_builder.build(&terminal_shell_id) {
+                Ok(shell) => {
+                    self.terminal_shells
+                        .insert(terminal_shell_id.to_string(), Arc::new(shell));
+                }
+                Err(err) => {
+                    warn!(
+                        "Could not create the terminal shell <{}>, error: {}",
+                        terminal_shell_id, err
+                    );
+                }
+            }
+        } else {
+            warn!(
+                "Could not create a terminal shell, missing builder with id <{}>",
+                terminal_shell_builder_id
+            );
+        }
+    }
+
+    /// Write data to a terminal shell
+    pub async fn write_to_terminal_shell(&self, terminal_shell_id: String, data: String) {
+        let shell = self.terminal_shells.get(&terminal_shell_id);
+        if let Some(shell) = shell {
+            let shell = shell.clone();
+            shell.write(data).await;
+        } else {
+            warn!(
+                "Could not write to non-existent terminal shell, id <{}>",
+                terminal_shell_id
+            );
+        }
+    }
+
+    /// Terminate a terminal shell, its process is killed once it's dropped
+    pub async fn close_terminal_shell(&mut self, terminal_shell_id: String) {
+        self.terminal_shells.remove(&terminal_shell_id);
+    }
+
+    /// Resize a terminal shell
+    pub async fn resize_terminal_shell(&mut self, terminal_shell_id: String, cols: i32, rows: i32) {
+        let shell = self.terminal_shells.get(&terminal_shell_id);
+        if let Some(shell) = shell {
+            shell.resize(cols, rows).await;
+        } else {
+            warn!(
+                "Could not resize non-existent terminal shell, id <{}>",
+                terminal_shell_id
+            );
+        }
+    }
+
+    /// Handle a key pressed in a view with modal editing
+    pub fn handle_modal_key(
+        &mut self,
+        view_id: &str,
+        key: &str,
+        content: &str,
+        cursor: usize,
+    ) -> ModalOutput {
+        self.modal_engines
+            .entry(view_id.to_owned())
+            .or_default()
+            .handle_key(key, content, cursor)
+    }
+
+    /// Forget the modal editing state of a view
+    pub fn close_modal_engine(&mut self, view_id: &str) {
+        self.modal_engines.remove(view_id);
+    }
+
+    /// Forget the modal editing state of the views whose tab isn't open anymore,
+    /// e.g after the tab or its whole view was closed. Renamed tabs keep their ID, and so their state
+    fn close_modal_engines_of_closed_tabs(&mut self) {
+        let closed = self
+            .modal_engines
+            .keys()
+            .filter(|view_id| {
+                !self
+                    .data
+                    .views
+                    .iter()
+                    .flat_map(|view| view.get_tabs())
+                    .any(|tab| tab.get_id() == view_id.as_str())
+            })
+            .cloned()
+            .collect::<Vec<String>>();
+        for view_id in closed {
+            self.close_modal_engine(&view_id);
+        }
+    }
+
+    /// Launch a Jupyter kernel
+    #[cfg(feature = "kernels")]
+    pub async fn start_kernel(&mut self, kernel_id: &str, spec: KernelSpec) -> Result<(), Errors> {
+        let kernel = Kernel::start(
+            kernel_id,
+            spec,
+            &get_runtime_dir(),
+            self.data.id,
+            self.extensions_manager.sender.clone(),
+        )
+        .await
+        .map_err(Errors::Kernel)?;
+
+        if let Some(old_kernel) = self.kernels.insert(kernel_id.to_owned(), kernel) {
+            old_kernel.shutdown().await.ok();
+        }
+
+        info!("Started kernel <{}>", kernel_id);
+
+        Ok(())
+    }
+
+    /// Retrieve a running Jupyter kernel, the State doesn't need to be kept locked while using it
+    #[cfg(feature = "kernels")]
+    pub fn get_kernel(&self, kernel_id: &str) -> Result<Kernel, Errors> {
+        self.kernels
+            .get(kernel_id)
+            .cloned()
+            .ok_or(Errors::Kernel(KernelErrors::KernelNotFound))
+    }
+
+    /// Shutdown a Jupyter kernel
+    #[cfg(feature = "kernels")]
+    pub async fn shutdown_kernel(&mut self, kernel_id: &str) -> Result<(), Errors> {
+        let kernel = self
+            .kernels
+            .remove(kernel_id)
+            .ok_or(Errors::Kernel(KernelErrors::KernelNotFound))?;
+
+        kernel.shutdown().await.map_err(Errors::Kernel)
+    }
+
+    /// Restart a Jupyter kernel that was launched by the State
+    #[cfg(feature = "kernels")]
+    pub async fn restart_kernel(&mut self, kernel_id: &str) -> Result<(), Errors> {
+        let spec = self
+            .get_kernel(kernel_id)?
+            .spec
+            .ok_or(Errors::Kernel(KernelErrors::CouldNotStart))?;
+
+        self.shutdown_kernel(kernel_id).await?;
+        self.start_kernel(kernel_id, spec).await
+    }
+
+    /// Register a tree view with the provider of its items
+    pub async fn register_tree_view(
+        &mut self,
+        info: TreeViewInfo,
+        provider: Arc<dyn TreeDataProvider + Send + Sync>,
+    ) {
+        self.tree_views.register(info.clone(), provider);
+
+        self.extensions_manager
+            .sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::TreeViewRegistered {
+                    state_id: self.data.id,
+                    view: info,
+                },
+            ))
+            .await
+            .ok();
+    }
+
+    /// Return the children of an item of a tree view, or its root items
+    pub async fn get_tree_view_children(
+        &self,
+        view_id: &str,
+        parent_id: Option<String>,
+    ) -> Result<Vec<TreeItem>, Errors> {
+        let view = self
+            .tree_views
+            .get_view(view_id)
+            .ok_or(Errors::TreeViewNotFound)?;
+
+        Ok(view.get_children(parent_id).await)
+    }
+
+    /// Reload an item of a tree view (or the whole tree) and let the client know
+    pub async fn refresh_tree_view(
+        &self,
+        view_id: &str,
+        item_id: Option<String>,
+    ) -> Result<(), Errors> {
+        let view = self
+            .tree_views
+            .get_view(view_id)
+            .ok_or(Errors::TreeViewNotFound)?;
+
+        view.invalidate(item_id.clone()).await;
+
+        self.extensions_manager
+            .sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::TreeViewRefreshed {
+                    state_id: self.data.id,
+                    view_id: view_id.to_owned(),
+                    item_id,
+                },
+            ))
+            .await
+            .ok();
+
+        Ok(())
+    }
+
+    /// Set or remove (with `None`) the decorations of some paths on behalf of an extension,
+    /// the client receives the updates in batches
+    pub async fn set_file_decorations(
+        &self,
+        extension_id: &str,
+        filesystem: &str,
+        decorations: Vec<(String, Option<FileDecoration>)>,
+    ) {
+        if self
+            .decorations
+            .update(extension_id, filesystem, decorations)
+            .await
+        {
+            self.decorations
+                .schedule_flush(self.extensions_manager.sender.clone(), self.data.id);
+        }
+    }
+
+    /// Remove all the decorations of an extension
+    pub async fn clear_file_decorations(&self, extension_id: &str) {
+        if self.decorations.clear_extension(extension_id).await {
+            self.decorations
+                .schedule_flush(self.extensions_manager.sender.clone(), self.data.id);
+        }
+    }
+
+    /// Search across a filesystem, the matches are sent to the client in batches
+    ///
+    /// # Arguments
+    ///
+    /// * `filesystem`   - The name of the filesystem
+    /// * `query`        - The text or regular expression to look for
+    /// * `options`      - Where and how to search
+    ///
+    pub fn search(
+        &mut self,
+        filesystem: &str,
+        query: &str,
+        options: SearchOptions,
+    ) -> Result<String, Errors> {
+        let filesystem = self
+            .get_fs_by_name(filesystem)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+        let search = Search::new(query, options)?.with_buffers(self.output_buffers.snapshot());
+
+        let search_id = Uuid::new_v4().to_string();
+        let token = CancellationToken::new();
+        self.searches
+            .lock()
+            .unwrap()
+            .insert(search_id.clone(), token.clone());
+        let searches = self.searches.clone();
+
+        let progress = self.extensions_manager.progress.clone();
+        let sender = self.extensions_manager.sender.clone();
+        let state_id = self.data.id;
+
+        tokio::spawn({
+            let search_id = search_id.clone();
+            async move {
+                // Cancelling the progress cancels the search too
+                let progress = progress
+                    .start(
+                        "Searching",
+                        ProgressKind::Indeterminate,
+                        true,
+                        None,
+                        sender.clone(),
+                        state_id,
+                    )
+                    .await
+                    .with_cancellation(token.clone());
+
+                search
+                    .run(filesystem, token, sender, state_id, search_id.clone())
+                    .await;
+                searches.lock().unwrap().remove(&search_id);
+                progress.finish().await;
+            }
+        });
+
+        Ok(search_id)
+    }
+
+    /// Report a long-running operation of the Core to the clients, it's finished once the handle is dropped
+    pub async fn start_progress(
+        &self,
+        title: &str,
+        kind: ProgressKind,
+        cancellable: bool,
+    ) -> ProgressHandle {
+        self.extensions_manager
+            .progress
+            .start(
+                title,
+                kind,
+                cancellable,
+                None,
+                self.extensions_manager.sender.clone(),
+                self.data.id,
+            )
+            .await
+    }
+
+    /// Return the running long operations of the Core and the extensions
+    pub fn get_progress(&self) -> Vec<ProgressInfo> {
+        self.extensions_manager.progress.get_all()
+    }
+
+    /// Ask the owner of a long operation to stop it
+    pub fn cancel_progress(&self, progress_id: &str) -> Result<(), Errors> {
+        self.extensions_manager
+            .progress
+            .cancel(progress_id)
+            .map_err(Errors::Progress)
+    }
+
+    /// Abort a running search
+    pub fn cancel_search(&mut self, search_id: &str) -> Result<(), Errors> {
+        let token = self
+            .searches
+            .lock()
+            .unwrap()
+            .remove(search_id)
+            .ok_or(Errors::Search(SearchErrors::SearchNotFound))?;
+        token.cancel();
+        Ok(())
+    }
+
+    /// Retrieve the version control provider of a filesystem
+    pub fn get_vcs_provider(
+        &self,
+        filesystem: &str,
+    ) -> Result<Arc<dyn VcsProvider + Send + Sync>, Errors> {
+        self.vcs_providers
+            .get(filesystem)
+            .cloned()
+            .ok_or(Errors::Vcs(VcsErrors::ProviderNotFound))
+    }
+
+    /// Periodically check a repository and notify the client when its status changes
+    pub async fn watch_repository(&mut self, filesystem: &str, path: &str) -> Result<(), Errors> {
+        let provider = self.get_vcs_provider(filesystem)?;
+        let mut last_status = provider.get_status(path).await?;
+
+        let watcher_id = format!("{}:{}", filesystem, path);
+        if self.repository_watchers.contains_key(&watcher_id) {
+            return Ok(());
+        }
+
+        let token = CancellationToken::new();
+        self.repository_watchers.insert(watcher_id, token.clone());
+
+        let sender = self.extensions_manager.sender.clone();
+        let changes = self.changes.clone();
+        let state_id = self.data.id;
+        let filesystem = filesystem.to_owned();
+        let path = path.to_owned();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+
+                if token.is_cancelled() {
+                    break;
+                }
+
+                if let Ok(status) = provider.get_status(&path).await {
+                    if status != last_status {
+                        changes.record_repository(&filesystem, &path, &last_status, &status);
+                        last_status = status.clone();
+                        sender
+                            .send(ClientMessages::ServerMessage(
+                                ServerMessages::RepositoryChanged {
+                                    state_id,
+                                    filesystem: filesystem.clone(),
+                                    path: path.clone(),
+                                    status,
+                                },
+                            ))
+                            .await
+                            .ok();
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Recent changes across the filesystems, oldest first
+    pub fn get_changes(&self, filter: &ChangesFilter) -> Vec<ChangeEntry> {
+        self.changes.get(filter)
+    }
+
+    /// Let the subscribers and the changes feed know about a change in a filesystem
+    fn broadcast_fs_change(&self, event: StateEvent) {
+        self.changes.record_event(&event);
+        self.subscriptions.broadcast(event);
+    }
+
+    /// Stop watching a repository
+    pub fn unwatch_repository(&mut self, filesystem: &str, path: &str) {
+        let watcher_id = format!("{}:{}", filesystem, path);
+        if let Some(token) = self.repository_watchers.remove(&watcher_id) {
+            token.cancel();
+        }
+    }
+
+    /// Open a folder as a workspace, its configuration is loaded and reloaded when it changes.
+    /// Returns its configuration merged with the global settings
+    pub async fn open_workspace(
+        &mut self,
+        filesystem: &str,
+        root: &str,
+    ) -> Result<WorkspaceConfig, Errors> {
+        self.get_fs_by_name(filesystem)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+
+        let workspace = Workspace::new(filesystem, root);
+        let workspace_id = workspace.get_id();
+        self.workspaces.entry(workspace_id).or_insert(workspace);
+
+        let config = self.reload_workspace_config(filesystem, root).await;
+        self.watch_workspace(filesystem, root).await?;
+        config
+    }
+
+    /// Forget a workspace, its tasks are removed
+    pub fn close_workspace(&mut self, filesystem: &str, root: &str) -> Result<(), Errors> {
+        let config = self.get_workspace_config(filesystem, root)?;
+        for task_id in config.tasks.keys() {
+            let task_id = get_workspace_task_id(filesystem, root, task_id);
+            self.task_runner.unregister(&task_id).ok();
+        }
+
+        self.unwatch_workspace(filesystem, root);
+        self.workspaces.remove(&get_workspace_id(filesystem, root));
+        Ok(())
+    }
+
+    pub fn get_workspaces(&self) -> Vec<Workspace> {
+        self.workspaces.values().cloned().collect()
+    }
+
+    /// Trust a workspace so the tasks of its configuration are registered
+    pub async fn trust_workspace(
+        &mut self,
+        filesystem: &str,
+        root: &str,
+    ) -> Result<WorkspaceConfig, Errors> {
+        let workspace = self
+            .workspaces
+            .get_mut(&get_workspace_id(filesystem, root))
+            .ok_or(Errors::Workspace(WorkspaceErrors::WorkspaceNotFound))?;
+        workspace.trusted = true;
+
+        self.reload_workspace_config(filesystem, root).await
+    }
+
+    /// Configuration of a workspace merged with the global settings
+    pub fn get_workspace_config(
+        &self,
+        filesystem: &str,
+        root: &str,
+    ) -> Result<WorkspaceConfig, Errors> {
+        let workspace = self
+            .workspaces
+            .get(&get_workspace_id(filesystem, root))
+            .ok_or(Errors::Workspace(WorkspaceErrors::WorkspaceNotFound))?;
+        Ok(self.data.workspace_settings.merge(&workspace.config))
+    }
+
+    /// Deepest workspace containing the path
+    fn get_path_workspace(&self, filesystem_name: &str, path: &str) -> Option<&Workspace> {
+        self.workspaces
+            .values()
+            .filter(|workspace| workspace.filesystem == filesystem_name)
+            .filter(|workspace| {
+                let root = workspace.root.trim_end_matches(['/', '\\']);
+                path.strip_prefix(root)
+                    .map(|rest| rest.is_empty() || rest.starts_with(['/', '\\']))
+                    .unwrap_or(false)
+            })
+            .max_by_key(|workspace| workspace.root.len())
+    }
+
+    /// Nesting rules of the deepest workspace containing the path, or the global ones
+    pub fn get_file_nesting(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+    ) -> HashMap<String, Vec<String>> {
+        match self.get_path_workspace(filesystem_name, path) {
+            Some(workspace) => {
+                self.data
+                    .workspace_settings
+                    .merge(&workspace.config)
+                    .file_nesting
+            }
+            None => self.data.workspace_settings.file_nesting.clone(),
+        }
+    }
+
+    /// Line endings policy of the deepest workspace containing the path, or the State's one
+    pub fn get_eol_policy(&self, filesystem_name: &str, path: &str) -> EolPolicy {
+        self.get_path_workspace(filesystem_name, path)
+            .and_then(|workspace| workspace.config.eol_policy)
+            .or(self.data.workspace_settings.eol_policy)
+            .unwrap_or(self.data.eol_policy)
+    }
+
+    /// Read a file, its encoding is detected if not specified
+    pub async fn read_file_with_encoding(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+        encoding: Option<FileEncoding>,
+    ) -> Result<FileInfo, Errors> {
+        let filesystem = self
+            .get_fs_by_name(filesystem_name)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+
+        let start = Instant::now();
+        let file = filesystem
+            .lock()
+            .await
+            .read_file_with_encoding(path, encoding)
+            .await?;
+        self.extensions_manager
+            .telemetry
+            .record_filesystem(FilesystemOperation::Read, start.elapsed());
+
+        Ok(file)
+    }
+
+    /// Same as [`State::read_file_with_encoding`] but the extensions are notified of the result
+    pub async fn read_file_and_notify(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+        encoding: Option<FileEncoding>,
+    ) -> Result<FileInfo, Errors> {
+        if self.get_fs_by_name(filesystem_name).is_none() {
+            return Err(Errors::Fs(FilesystemErrors::FilesystemNotFound));
+        }
+
+        let result = self
+            .read_file_with_encoding(filesystem_name, path, encoding)
+            .await;
+
+        self.notify_extensions(ClientMessages::ReadFile(
+            self.data.id,
+            filesystem_name.to_owned(),
+            result.clone(),
+        ));
+
+        result
+    }
+
+    /// Same as [`State::write_file_with_encoding`] but the extensions are notified of the result
+    pub async fn write_file_and_notify(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+        content: String,
+        encoding: Option<FileEncoding>,
+    ) -> Result<(), Errors> {
+        let result = self
+            .write_file_with_encoding(filesystem_name, path, &content, encoding)
+            .await;
+        let (content, result) = match result {
+            Ok(written) => (written, Ok(())),
+            Err(err) => (content, Err(err)),
+        };
+
+        self.notify_extensions(ClientMessages::WriteFile(
+            self.data.id,
+            filesystem_name.to_owned(),
+            content,
+            result.clone(),
+        ));
+
+        result
+    }
+
+    /// Same as [`State::list_dir`] but the extensions are notified of the result
+    pub async fn list_dir_and_notify(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+    ) -> Result<Vec<DirItemInfo>, Errors> {
+        let result = self.list_dir(filesystem_name, path).await;
+
+        self.notify_extensions(ClientMessages::ListDir(
+            self.data.id,
+            filesystem_name.to_owned(),
+            path.to_owned(),
+            result.clone(),
+        ));
+
+        result
+    }
+
+    /// Read up to `length` bytes of a file starting at `offset`, so big files can be paged through
+    pub async fn read_file_range(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+        offset: u64,
+        length: usize,
+    ) -> Result<FileChunk, Errors> {
+        let filesystem = self
+            .get_fs_by_name(filesystem_name)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+        let filesystem = filesystem.lock().await;
+        let size = filesystem.get_file_size(path).await?;
+        let content = filesystem.read_range(path, offset, length).await?;
+
+        Ok(FileChunk {
+            is_last: offset + content.len() as u64 >= size,
+            path: path.to_owned(),
+            offset,
+            content,
+            size,
+        })
+    }
+
+    /// List a directory grouping its files following the nesting rules
+    pub async fn list_dir(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+    ) -> Result<Vec<DirItemInfo>, Errors> {
+        let filesystem = self
+            .get_fs_by_name(filesystem_name)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+        let start = Instant::now();
+        let mut items = filesystem.lock().await.list_dir_by_path(path).await?;
+        self.extensions_manager
+            .telemetry
+            .record_filesystem(FilesystemOperation::ListDir, start.elapsed());
+
+        for item in &mut items {
+            item.icon = self.get_file_icon(&item.name, item.is_file);
+        }
+
+        Ok(nest_items(
+            items,
+            &self.get_file_nesting(filesystem_name, path),
+        ))
+    }
+
+    /// List a directory as it's shown in the explorer, the excluded items are left out and the others
+    /// come with their icon, nested files, decorations and git status
+    pub async fn explorer_list(
+        &self,
+        filesystem_name: &str,
+        path: &str,
+    ) -> Result<ExplorerListing, Errors> {
+        let filesystem = self
+            .get_fs_by_name(filesystem_name)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+        let start = Instant::now();
+        let items = filesystem.lock().await.list_dir_by_path(path).await?;
+        self.extensions_manager
+            .telemetry
+            .record_filesystem(FilesystemOperation::ListDir, start.elapsed());
+
+        let workspace = self.get_path_workspace(filesystem_name, path);
+        let config = match workspace {
+            Some(workspace) => self.data.workspace_settings.merge(&workspace.config),
+            None => self.data.workspace_settings.clone(),
+        };
+        let excluded = build_globset(&config.excluded)?;
+        let root = workspace.map(|workspace| workspace.root.as_str());
+
+        let total = items.len();
+        let mut items = items
+            .into_iter()
+            .filter(|item| !is_excluded(&excluded, root, item))
+            .collect::<Vec<DirItemInfo>>();
+        let excluded = total - items.len();
+
+        for item in &mut items {
+            item.icon = self.get_file_icon(&item.name, item.is_file);
+        }
+        let items = nest_items(items, &config.file_nesting);
+
+        // Folders outside a repository are listed anyway
+        let status = match self.get_vcs_provider(filesystem_name) {
+            Ok(provider) => provider.get_status(path).await.ok(),
+            Err(_) => None,
+        };
+        let (branch, changes) = match status {
+            Some(status) => (status.branch, status.files),
+            None => (None, Vec::new()),
+        };
+
+        let mut paths = Vec::new();
+        let mut pending = items.iter().collect::<Vec<&DirItemInfo>>();
+        while let Some(item) = pending.pop() {
+            paths.push(item.path.clone());
+            pending.extend(&item.nested);
+        }
+        let mut decorations = self.decorations.get(filesystem_name, &paths).await;
+
+        Ok(ExplorerListing {
+            path: path.to_owned(),
+            branch,
+            items: build_items(items, &changes, &mut decorations),
+            excluded,
+        })
+    }
+
+    /// Use an icon theme in the directory listings, files it has no icon for get a generated one
+    pub fn set_icon_theme(&mut self, icon_theme: Option<IconTheme>) {
+        self.icon_theme = icon_theme;
+    }
+
+    pub fn get_icon_theme(&self) -> Option<&IconTheme> {
+        self.icon_theme.as_ref()
+    }
+
+    /// Icon of a file or folder, files always have one
+    pub fn get_file_icon(&self, name: &str, is_file: bool) -> Option<FileIcon> {
+        match &self.icon_theme {
+            Some(icon_theme) => icon_theme.get_icon(name, is_file),
+            None if is_file => Some(get_fallback_icon(name)),
+            None => None,
+        }
+    }
+
+    /// Read again the configuration of a workspace and register its tasks,
+    /// the previous configuration is kept if the new one is not valid
+    pub async fn reload_workspace_config(
+        &mut self,
+        filesystem: &str,
+        root: &str,
+    ) -> Result<WorkspaceConfig, Errors> {
+        let result = self.load_workspace_config(filesystem, root).await;
+
+        if let Err(err) = &result {
+            warn!(
+                "Could not load the configuration of the workspace <{}>: {:?}",
+                root, err
+            );
+        }
+
+        self.extensions_manager
+            .sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::WorkspaceConfigUpdated {
+                    state_id: self.data.id,
+                    filesystem: filesystem.to_owned(),
+                    root: root.to_owned(),
+                    config: result.clone(),
+                },
+            ))
+            .await
+            .ok();
+
+        result
+    }
+
+    async fn load_workspace_config(
+        &mut self,
+        filesystem: &str,
+        root: &str,
+    ) -> Result<WorkspaceConfig, Errors> {
+        let previous_config = self.get_workspace_config(filesystem, root)?;
+        let fs = self
+            .get_fs_by_name(filesystem)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+
+        let (config_path, config) = match find_config(&fs, root).await {
+            Some((path, content)) => {
+                let config = WorkspaceConfig::parse(&path, &content).map_err(Errors::Workspace)?;
+                (Some(path), config)
+            }
+            None => (None, WorkspaceConfig::default()),
+        };
+
+        let mut trusted = false;
+        if let Some(workspace) = self.workspaces.get_mut(&get_workspace_id(filesystem, root)) {
+            workspace.config_path = config_path;
+            workspace.config = config;
+            trusted = workspace.trusted;
+        }
+        let config = self.get_workspace_config(filesystem, root)?;
+
+        // Tasks removed from the configuration can't be run anymore
+        for task_id in previous_config.tasks.keys() {
+            let task_id = get_workspace_task_id(filesystem, root, task_id);
+            self.task_runner.unregister(&task_id).ok();
+        }
+
+        // Only the global settings are trusted until the owner trusts the workspace
+        let tasks = if trusted {
+            config.tasks.clone()
+        } else {
+            self.data.workspace_settings.tasks.clone()
+        };
+        for (task_id, definition) in tasks {
+            let task_id = get_workspace_task_id(filesystem, root, &task_id);
+            if let Err(err) = self.register_task(&task_id, definition, None) {
+                warn!("Could not register the task <{}>: {:?}", task_id, err);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Reload the configuration of a workspace when it's modified
+    async fn watch_workspace(&mut self, filesystem: &str, root: &str) -> Result<(), Errors> {
+        let watcher_id = get_workspace_id(filesystem, root);
+        if self.workspace_watchers.contains_key(&watcher_id) {
+            return Ok(());
+        }
+
+        let fs = self
+            .get_fs_by_name(filesystem)
+            .ok_or(Errors::Fs(FilesystemErrors::FilesystemNotFound))?;
+
+        let token = CancellationToken::new();
+        self.workspace_watchers.insert(watcher_id, token.clone());
+
+        self.index_workspace(filesystem, root).await;
+
+        // Nothing changes behind the editor's back, or checking would be too expensive (e.g over FTP)
+        if !fs.lock().await.capabilities().watch {
+            return Ok(());
+        }
+
+        // Only the local folders can be watched by the OS
+        let external_watcher = if filesystem == "local" {
+            let excluded = self
+                .get_workspace_config(filesystem, root)
+                .map(|config| config.excluded)
+                .unwrap_or_default();
+            self.changes
+                .watch_folder(filesystem, root, &excluded)
+                .map_err(|err| warn!("Could not watch the workspace <{}>: {:?}", root, err))
+                .ok()
+        } else {
+            None
+        };
+
+        let sender = self.extensions_manager.sender.clone();
+        let state_id = self.data.id;
+        let filesystem = filesystem.to_owned();
+        let root = root.to_owned();
+        let mut last_config = find_config(&fs, &root).await;
+
+        tokio::spawn(async move {
+            // The changes are recorded until the workspace is closed
+            let _external_watcher = external_watcher;
+
+            loop {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+
+                if token.is_cancelled() {
+                    break;
+                }
+
+                let config = find_config(&fs, &root).await;
+                if config != last_config {
+                    last_config = config;
+                    sender
+                        .send(ClientMessages::WorkspaceConfigChanged {
+                            state_id,
+                            filesystem: filesystem.clone(),
+                            root: root.clone(),
+                        })
+                        .await
+                        .ok();
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    fn unwatch_workspace(&mut self, filesystem: &str, root: &str) {
+        let watcher_id = get_workspace_id(filesystem, root);
+        if let Some(token) = self.workspace_watchers.remove(&watcher_id) {
+            token.cancel();
+        }
+        self.indexer.remove(&watcher_id);
+    }
+
+    /// Crawl a workspace and keep its files indexed for the quick-open
+    async fn index_workspace(&self, filesystem: &str, root: &str) {
+        let (fs, config) = match (
+            self.get_fs_by_name(filesystem),
+            self.get_workspace_config(filesystem, root),
+        ) {
+            (Some(fs), Ok(config)) => (fs, config),
+            _ => return,
+        };
+
+        let progress = self
+            .start_progress("Indexing", ProgressKind::Indeterminate, false)
+            .await;
+        let indexed = self.indexer.index(
+            filesystem,
+            fs,
+            root,
+            &config.excluded,
+            self.subscriptions.subscribe(),
+            progress,
+        );
+
+        if let Err(err) = indexed {
+            warn!("Could not index the workspace <{}>: {:?}", root, err);
+        }
+    }
+
+    /// Files of the workspaces matching a query, best first. Queries starting with `#` look for symbols instead
+    pub fn quick_open(&self, query: &str) -> Vec<Ranked<QuickOpenItem>> {
+        let (query, items) = match query.strip_prefix('#') {
+            Some(query) => (query, self.indexer.get_symbols()),
+            None => (query, self.indexer.get_files()),
+        };
+
+        let mut ranked =
+            self.get_matcher()
+                .rank(query, items, Some(&self.recent_files), now_secs());
+        ranked.truncate(QUICK_OPEN_LIMIT);
+        ranked
+    }
+
+    /// Ask the Language Server of a local file for its symbols, so they can be found with the quick-open.
+    /// The request is made in the background, so the State is not locked while the server replies
+    pub fn index_document_symbols(&self, path: &str) {
+        let language = match get_format_from_path(path) {
+            FileFormat::Text(language) => language.to_lowercase(),
+            _ => return,
+        };
+
+        let manager = self.language_servers_manager.clone();
+        let indexer = self.indexer.clone();
+        let path = path.to_owned();
+        tokio::spawn(async move {
+            let uri = path_to_uri(&path);
+            let reply = manager
+                .request(
+                    &language,
+                    &uri,
+                    "textDocument/documentSymbol",
+                    serde_json::json!({ "textDocument": { "uri": uri } }),
+                )
+                .await;
+
+            if let Ok(reply) = reply {
+                indexer.set_symbols("local", &path, IndexedSymbol::from_lsp(&reply));
+            }
+        });
+    }
+
+    /// Create a Language Server instance from a Builder ID
+    pub async fn create_language_server(&mut self, language_server_builder_id: String) {
+        let language_server_builder = self
+            .language_server_builders
+            .get(&language_server_builder_id);
+
+        if let Some(language_server_builder) = language_server_builder {
+            let language_server_builder = language_server_builder.lock().await;
+            let info = language_server_builder.get_info();
+            let language_server = language_server_builder.build();
+            self.language_servers
+                .insert(info.id, Arc::new(Mutex::new(language_server)));
+        } else {
+            warn!(
+                "Could not create a language server, missing builder with id <{}>",
+                language_server_builder_id
+            );
+        }
+    }
+
+    /// Register how to launch a Language Server managed by the Core
+    pub fn register_language_server_config(&mut self, config: LanguageServerConfig) {
+        self.language_servers_manager.register_config(config);
+    }
+
+    /// Launch a managed Language Server for a workspace, returns the ID of the running server.
+    /// The State is not locked while the server starts
+    pub async fn start_language_server(
+        state_handle: Arc<Mutex<State>>,
+        config_id: &str,
+        root_uri: &str,
+    ) -> Result<String, Errors> {
+        let (manager, state_id, sender) = {
+            let state = state_handle.lock().await;
+
+            // Servers that can be installed by the Core are installed the first time they are needed
+            #[cfg(feature = "installer")]
+            if let Some(installs_path) = state.language_servers_manager.get_installs_path() {
+                let installable = state
+                    .language_servers_manager
+                    .get_config(config_id)
+                    .map(|config| config.source.is_some())
+                    .unwrap_or(false);
+                if installable && get_installed(installs_path, config_id).await.is_none() {
+                    state.install_language_server(config_id).await?;
+                }
+            }
+
+            (
+                state.language_servers_manager.clone(),
+                state.data.id,
+                state.extensions_manager.sender.clone(),
+            )
+        };
+
+        manager
+            .start(config_id, root_uri, state_id, sender)
+            .await
+            .map_err(Errors::LanguageServer)
+    }
+
+    #[cfg(feature = "installer")]
+    fn get_language_server_installer(
+        &self,
+        config_id: &str,
+    ) -> Result<(LanguageServerInstaller, LanguageServerConfig), Errors> {
+        let installs_path = self
+            .language_servers_manager
+            .get_installs_path()
+            .ok_or(Errors::LanguageServer(LanguageServerErrors::NotInstallable))?;
+        let config = self
+            .language_servers_manager
+            .get_config(config_id)
+            .ok_or(Errors::LanguageServer(LanguageServerErrors::ConfigNotFound))?;
+
+        let installer = LanguageServerInstaller::new(installs_path.clone())
+            .with_http_client(self.get_http_client());
+        Ok((installer, config.clone()))
+    }
+
+    /// Download and install the version of a language server its configuration declares,
+    /// returns the installed version
+    #[cfg(feature = "installer")]
+    pub async fn install_language_server(&self, config_id: &str) -> Result<String, Errors> {
+        let (installer, config) = self.get_language_server_installer(config_id)?;
+        let source = config
+            .source
+            .ok_or(Errors::LanguageServer(LanguageServerErrors::NotInstallable))?;
+
+        let progress = self
+            .start_progress(
+                &format!("Installing {}", config.name),
+                ProgressKind::Indeterminate,
+                false,
+            )
+            .await;
+        let installed = installer.install(config_id, &source).await;
+        progress.finish().await;
+
+        let installed = installed.map_err(Errors::LanguageServer)?;
+        self.notify_language_server_installed(config_id, &installed.version)
+            .await;
+        Ok(installed.version)
+    }
+
+    /// Install the latest version of a language server, returns None if it was up to date.
+    /// The running servers keep using the previous version until they are restarted
+    #[cfg(feature = "installer")]
+    pub async fn update_language_server(&self, config_id: &str) -> Result<Option<String>, Errors> {
+        let (installer, config) = self.get_language_server_installer(config_id)?;
+        let installed = installer
+            .update(&config)
+            .await
+            .map_err(Errors::LanguageServer)?;
+
+        if let Some(installed) = &installed {
+            self.notify_language_server_installed(config_id, &installed.version)
+                .await;
+        }
+        Ok(installed.map(|installed| installed.version))
+    }
+
+    #[cfg(feature = "installer")]
+    async fn notify_language_server_installed(&self, config_id: &str, version: &str) {
+        self.extensions_manager
+            .sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::LanguageServerInstalled {
+                    state_id: self.data.id,
+                    config_id: config_id.to_owned(),
+                    version: version.to_owned(),
+                },
+            ))
+            .await
+            .ok();
+    }
+
+    /// Run a phase of the shutdown, see [`ShutdownPhase`] for the order they are run in
+    pub async fn run_shutdown_phase(&mut self, phase: ShutdownPhase) {
+        match phase {
+            ShutdownPhase::FlushDocuments => {
+                self.keep_dirty_documents();
+                self.persist_drafts();
+            }
+            ShutdownPhase::StopLanguageServers => {
+                self.language_servers_manager.stop_all().await;
+                // Warm servers no State took
+                if let Some(pool) = self.language_servers_manager.get_pool() {
+                    pool.stop_all().await;
+                }
+                #[cfg(feature = "kernels")]
+                {
+                    let kernels = self.kernels.keys().cloned().collect::<Vec<String>>();
+                    for kernel_id in kernels {
+                        self.shutdown_kernel(&kernel_id).await.ok();
+                    }
+                }
+            }
+            ShutdownPhase::KillTerminals => {
+                self.terminal_shells.clear();
+                self.task_runner.cancel_all();
+                for (_, token) in self.searches.lock().unwrap().drain() {
+                    token.cancel();
+                }
+                for (_, token) in self.file_operations.drain() {
+                    token.cancel();
+                }
+            }
+            ShutdownPhase::PersistState => {
+                if let Some(persistor) = &self.persistor {
+                    persistor.lock().await.save(&self.data);
+                }
+                info!("State by id <{}> is ready to shut down", self.data.id);
+            }
+            // The transports are shared by all the States
+            ShutdownPhase::CloseTransports => {}
+        }
+    }
+
+    /// Keep the unsaved documents as drafts
+    fn keep_dirty_documents(&mut self) {
+        for info in self.documents.get_all() {
+            if info.is_dirty {
+                if let Ok(content) = self.get_document_content(&info.filesystem, &info.path) {
+                    self.save_draft(Draft {
+                        filesystem: info.filesystem,
+                        path: info.path,
+                        content,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Remember a client just used the State
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// How long the State has not been used by any client
+    pub fn get_idle_time(&self) -> Duration {
+        self.last_activity.elapsed()
+    }
+
+    pub fn is_hibernated(&self) -> bool {
+        self.hibernation.is_some()
+    }
+
+    /// Free everything an idle State doesn't need: the watchers, language servers, terminals,
+    /// kernels and caches are stopped, and the data and unsaved documents are persisted.
+    /// It's awakened with [`State::awake`]
+    pub async fn hibernate(&mut self) {
+        if self.is_hibernated() {
+            return;
+        }
+
+        let mut hibernation = Hibernation::default();
+
+        // Watchers
+        let repositories = self
+            .repository_watchers
+            .keys()
+            .filter_map(|watcher_id| watcher_id.split_once(':'))
+            .map(|(filesystem, path)| (filesystem.to_owned(), path.to_owned()))
+            .collect::<Vec<(String, String)>>();
+        for (filesystem, path) in &repositories {
+            self.unwatch_repository(filesystem, path);
+        }
+        hibernation.repositories = repositories;
+
+        let watched_extensions = self
+            .extension_watchers
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>();
+        for ext_id in &watched_extensions {
+            self.unwatch_extension(ext_id);
+        }
+        hibernation.watched_extensions = watched_extensions;
+
+        let watched_workspaces = self
+            .workspace_watchers
+            .keys()
+            .filter_map(|watcher_id| self.workspaces.get(watcher_id))
+            .map(|workspace| (workspace.filesystem.clone(), workspace.root.clone()))
+            .collect::<Vec<(String, String)>>();
+        for (filesystem, root) in &watched_workspaces {
+            self.unwatch_workspace(filesystem, root);
+        }
+        hibernation.watched_workspaces = watched_workspaces;
+
+        // Tasks
+        self.task_runner.cancel_all();
+
+        // Language servers
+        hibernation.language_servers = self
+            .language_servers_manager
+            .get_all_running()
+            .into_iter()
+            .map(|server| (server.config.id, server.root_uri))
+            .collect();
+        self.language_servers_manager.stop_all().await;
+
+        // Processes
+        self.terminal_shells.clear();
+        #[cfg(feature = "kernels")]
+        {
+            let kernels = self.kernels.keys().cloned().collect::<Vec<String>>();
+            for kernel_id in kernels {
+                self.shutdown_kernel(&kernel_id).await.ok();
+            }
+        }
+
+        // Running operations
+        for (_, token) in self.searches.lock().unwrap().drain() {
+            token.cancel();
+        }
+        for (_, token) in self.file_operations.drain() {
+            token.cancel();
+        }
+
+        // Unsaved documents are kept as drafts
+        self.keep_dirty_documents();
+
+        if let Some(persistor) = &self.persistor {
+            persistor.lock().await.save(&self.data);
+        }
+        self.persist_drafts();
+
+        // Caches
+        self.output_buffers = OutputBuffers::new();
+        self.modal_engines.clear();
+        self.tree_views.invalidate_all().await;
+
+        self.hibernation = Some(hibernation);
+
+        self.extensions_manager
+            .sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::StateHibernated {
+                    state_id: self.data.id,
+                },
+            ))
+            .await
+            .ok();
+
+        info!("State by id <{}> is hibernating", self.data.id);
+    }
+
+    /// Start again what was running before the State hibernated, it's a no-op if it's not hibernated
+    pub async fn awake(&mut self) {
+        self.touch();
+
+        let hibernation = if let Some(hibernation) = self.hibernation.take() {
+            hibernation
+        } else {
+            return;
+        };
+
+        for (filesystem, path) in hibernation.repositories {
+            if let Err(err) = self.watch_repository(&filesystem, &path).await {
+                warn!("Could not watch again the repository <{}>: {:?}", path, err);
+            }
+        }
+
+        for ext_id in hibernation.watched_extensions {
+            self.watch_extension(&ext_id).await.ok();
+        }
+
+        // The configurations might have changed meanwhile
+        for (filesystem, root) in hibernation.watched_workspaces {
+            self.reload_workspace_config(&filesystem, &root).await.ok();
+            self.watch_workspace(&filesystem, &root).await.ok();
+        }
+
+        // They are started in the background, so the State isn't locked during their handshakes
+        for (config_id, root_uri) in hibernation.language_servers {
+            let manager = self.language_servers_manager.clone();
+            let state_id = self.data.id;
+            let sender = self.extensions_manager.sender.clone();
+            tokio::spawn(async move {
+                if let Err(err) = manager.start(&config_id, &root_uri, state_id, sender).await {
+                    warn!(
+                        "Could not start again the language server <{}>: {:?}",
+                        config_id, err
+                    );
+                }
+            });
+        }
+
+        self.extensions_manager
+            .sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::StateAwakened {
+                    state_id: self.data.id,
+                },
+            ))
+            .await
+            .ok();
+
+        info!("State by id <{}> is awake", self.data.id);
+    }
+
+    /// Stop a managed Language Server
+    pub async fn stop_language_server(&mut self, language_server_id: &str) -> Result<(), Errors> {
+        self.language_servers_manager
+            .stop(language_server_id)
+            .await
+            .map_err(Errors::LanguageServer)
+    }
+
+    /// Restart a managed Language Server, the State is not locked while it starts again
+    pub async fn restart_language_server(
+        state_handle: Arc<Mutex<State>>,
+        language_server_id: &str,
+    ) -> Result<String, Errors> {
+        let (manager, state_id, sender) = {
+            let state = state_handle.lock().await;
+            (
+                state.language_servers_manager.clone(),
+                state.data.id,
+                state.extensions_manager.sender.clone(),
+            )
+        };
+
+        manager
+            .restart(language_server_id, state_id, sender)
+            .await
+            .map_err(Errors::LanguageServer)
+    }
+
+    /// Write to a Language Server instance
+    pub async fn write_to_language_server(&mut self, language_server_id: String, data: String) {
+        if let Some(language_server) = self
+            .language_servers_manager
+            .get_running(&language_server_id)
+        {
+            if let Err(err) = language_server.write(&data).await {
+                warn!(
+                    "Could not write to Language Server by id <{}>, error: {:?}",
+                    language_server_id, err
+                );
+            }
+            return;
+        }
+
+        let language_server = self.language_servers.get(&language_server_id);
+        if let Some(language_server) = language_server {
+            let mut language_server = language_server.lock().await;
+            language_server.write(data).await;
+        } else {
+            warn!(
+                "Language Server by id <{}> is not running",
+                language_server_id
+            );
+        }
+    }
+
+    /// Terminate to a Language Server instance
+    pub async fn unload_language_server(&mut self, language_server_builder_id: &str) {
+        self.language_server_builders
+            .remove(language_server_builder_id);
+        self.language_servers.remove(language_server_builder_id);
+        self.extensions_manager
+            .sender
+            .send(ClientMessages::ServerMessage(
+                ServerMessages::UnloadedLanguageServer {
+                    id: language_server_builder_id.to_owned(),
+                    state_id: self.data.id,
+                },
+            ))
+            .await
+            .unwrap();
+    }
+}
+
+/// Last time each file was modified, None if it can't be read
+async fn get_modification_times(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    let mut times = Vec::new();
+    for path in paths {
+        let modified = tokio::fs::metadata(path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        times.push(modified);
+    }
+    times
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::sync::Mutex;
+
+    use crate::decorations::FileDecoration;
+    use crate::documents::{DocumentEdit, DocumentErrors, Position};
+    use crate::experiments::ExperimentFlag;
+    use crate::extensions::base::{Extension, ExtensionInfo};
+    use crate::extensions::editors::{CustomEditor, CustomEditorAction, OpenedFile};
+    use crate::extensions::manager::{ExtensionsManager, LoadedExtension};
+    use crate::extensions::supervisor::{run_isolated, ExtensionStatus, PanicPolicy};
+    use crate::filesystems::{
+        DirItemInfo, EolPolicy, FileInfo, Filesystem, FilesystemCapabilities, MemoryFilesystem,
+    };
+    use crate::indexer::QuickOpenItem;
+    use crate::matcher::{Matchable, Ranked};
+    use crate::messaging::ClientMessages;
+    use crate::recovery::{Draft, SessionRecovery};
+    use crate::save_hooks::{SaveHook, SaveHookAction, SaveOptions};
+    use crate::search::{SearchErrors, SearchOptions, SearchSource};
+    use crate::state_persistors::Persistor;
+    use crate::states::data::views::{TabData, TabPosition, ViewsData};
+    use crate::states::{
+        DataAction, MemoryPersistor, StateData, StateDataField, TokenScope, TransactionErrors,
+    };
+    use crate::{Errors, ExperimentsErrors, ExtensionErrors, FilesystemErrors, Manifest};
+
+    use super::State;
+
+    fn get_sample_extension_info() -> ExtensionInfo {
+        ExtensionInfo {
+            id: "sample".to_string(),
+            name: "sample".to_string(),
+        }
+    }
+
+    fn get_sample_extension() -> Box<dyn Extension + Send> {
+        struct SampleExtension;
+
+        impl Extension for SampleExtension {
+            fn get_info(&self) -> ExtensionInfo {
+                get_sample_extension_info()
+            }
+
+            fn init(&mut self, _state: Arc<Mutex<State>>) {
+                todo!()
+            }
+
+            fn unload(&mut self) {
+                todo!()
+            }
+
+            fn notify(&mut self, _message: ClientMessages) {
+                todo!()
+            }
+        }
+
+        Box::new(SampleExtension)
     }
 
     #[test]
@@ -421,10 +4583,969 @@ mod tests {
         manager.register("sample", get_sample_extension());
         let test_state = State::new(0, manager, Box::new(MemoryPersistor::new()));
 
-        let ext_info = test_state.get_ext_run_info_by_id("sample");
-        assert!(ext_info.is_ok());
+        let ext_info = test_state.get_ext_run_info_by_id("sample");
+        assert!(ext_info.is_ok());
+
+        let ext_info = ext_info.unwrap();
+        assert_eq!(get_sample_extension_info(), ext_info);
+    }
+
+    #[tokio::test]
+    async fn index_extensions_by_id() {
+        let mut manager = ExtensionsManager::default();
+        manager.register("first", get_sample_extension());
+        manager.register("second", get_sample_extension());
+        manager.register("first", get_sample_extension());
+
+        assert_eq!(manager.get_instances("first").count(), 2);
+        assert!(manager.get_instance_by_info_id("sample").is_some());
+
+        // Positions are still right after removing some
+        assert!(manager.unregister("first").await);
+        assert!(!manager.unregister("first").await);
+        assert_eq!(manager.get_instances("first").count(), 0);
+        let second = manager.get_instances("second").collect::<Vec<_>>();
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].parent_id, "second");
+    }
+
+    #[tokio::test]
+    async fn isolate_crashed_extensions() {
+        let mut manager = ExtensionsManager::default();
+        manager.register("sample", get_sample_extension());
+        let test_state = State::new(0, manager, Box::new(MemoryPersistor::new()));
+
+        let extensions = test_state.extensions_manager.get_all();
+        let LoadedExtension::ExtensionInstance { plugin, health, .. } = &extensions[0] else {
+            panic!("The sample extension is not running");
+        };
 
-        let ext_info = ext_info.unwrap();
-        assert_eq!(get_sample_extension_info(), ext_info);
+        let res = run_isolated(
+            "sample",
+            plugin,
+            health,
+            &test_state.extensions_manager.sender,
+            0,
+            |ext_plugin| ext_plugin.notify(ClientMessages::Unload(0)),
+        )
+        .await;
+
+        assert_eq!(res, Err(ExtensionErrors::ExtensionCrashed));
+        assert_eq!(health.lock().await.status, ExtensionStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn ignore_panics_by_policy() {
+        let mut manager = ExtensionsManager::default();
+        manager.register("sample", get_sample_extension());
+        let mut test_state = State::new(0, manager, Box::new(MemoryPersistor::new()));
+
+        test_state
+            .set_extension_panic_policy("sample", PanicPolicy::Ignore)
+            .await
+            .unwrap();
+
+        if let LoadedExtension::ExtensionInstance { plugin, health, .. } =
+            &test_state.extensions_manager.get_all()[0]
+        {
+            for _ in 0..2 {
+                run_isolated(
+                    "sample",
+                    plugin,
+                    health,
+                    &test_state.extensions_manager.sender,
+                    0,
+                    |ext_plugin| ext_plugin.notify(ClientMessages::Unload(0)),
+                )
+                .await
+                .ok();
+            }
+
+            assert_eq!(health.lock().await.status, ExtensionStatus::Running);
+        }
+
+        let metrics = test_state.get_extension_metrics("sample").await.unwrap();
+        assert_eq!(metrics.panics, 2);
+        assert_eq!(metrics.ignored_panics, 2);
+        assert_eq!(
+            test_state.data.panic_policies.get("sample"),
+            Some(&PanicPolicy::Ignore)
+        );
+    }
+
+    #[tokio::test]
+    async fn reload_filesystem_extensions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let location = dir.join("Graviton.toml");
+        tokio::fs::write(
+            &location,
+            "[extension]\nname = \"sample\"\nid = \"sample\"\nauthor = \"\"\nversion = \"0.1.0\"\nrepository = \"\"\n",
+        )
+        .await
+        .unwrap();
+        let manifest = Manifest::parse(&location).await.unwrap();
+
+        let mut manager = ExtensionsManager::default();
+        manager.register("sample", get_sample_extension());
+        manager.set_source(&manifest, |manager, _, _| {
+            manager.register("sample", get_sample_extension());
+        });
+
+        assert!(manager.reload_extension("sample", 0).await.is_ok());
+        assert_eq!(manager.get_all().len(), 1);
+        assert!(manager.sources.contains_key("sample"));
+
+        assert_eq!(
+            manager.reload_extension("other", 0).await,
+            Err(ExtensionErrors::NotReloadable)
+        );
+    }
+
+    #[tokio::test]
+    async fn rename_folders() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        tokio::fs::create_dir_all(dir.join("src")).await.unwrap();
+        tokio::fs::write(dir.join("src").join("main.rs"), "")
+            .await
+            .unwrap();
+
+        let from = dir.join("src").to_str().unwrap().to_string();
+        let to = dir.join("source").to_str().unwrap().to_string();
+        let file = dir
+            .join("src")
+            .join("main.rs")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let mut test_state = State::new(
+            0,
+            ExtensionsManager::default(),
+            Box::new(MemoryPersistor::new()),
+        );
+        test_state.save_draft(Draft {
+            filesystem: "local".to_string(),
+            path: file,
+            content: "fn main() {}".to_string(),
+        });
+
+        test_state.rename_path("local", &from, &to).await.unwrap();
+
+        let renamed_file = dir.join("source").join("main.rs");
+        assert!(renamed_file.exists());
+        assert_eq!(test_state.drafts[0].path, renamed_file.to_str().unwrap());
+    }
+
+    #[tokio::test]
+    async fn forget_finished_searches() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        tokio::fs::write(dir.join("main.rs"), "fn main() {}")
+            .await
+            .unwrap();
+
+        let mut test_state = State::default();
+        let search_id = test_state
+            .search("local", "main", SearchOptions::new(dir.to_str().unwrap()))
+            .unwrap();
+
+        for _ in 0..100 {
+            if test_state.searches.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        assert_eq!(
+            test_state.cancel_search(&search_id),
+            Err(Errors::Search(SearchErrors::SearchNotFound))
+        );
+    }
+
+    #[tokio::test]
+    async fn close_modal_engines() {
+        let mut test_state = State::default();
+        let mut views = ViewsData::default();
+        views.insert_tab(
+            TabPosition::default(),
+            TabData::Basic {
+                title: "Notes".to_string(),
+                id: "notes".to_string(),
+            },
+        );
+        let mut new_data = test_state.data.clone();
+        new_data.views = vec![views];
+        test_state.update(new_data).await;
+
+        test_state.handle_modal_key("notes", "i", "", 0);
+        test_state.handle_modal_key("closed", "i", "", 0);
+        test_state.save_layout("focus").await;
+        assert!(test_state.modal_engines.contains_key("notes"));
+        assert!(!test_state.modal_engines.contains_key("closed"));
+
+        let mut new_data = test_state.data.clone();
+        new_data.views.clear();
+        test_state.update(new_data).await;
+        assert!(test_state.modal_engines.is_empty());
+    }
+
+    #[tokio::test]
+    async fn edit_documents() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let file = dir.join("notes.md");
+        tokio::fs::write(&file, "Hello\n").await.unwrap();
+        let path = file.to_str().unwrap();
+
+        let mut test_state = State::default();
+
+        let info = test_state.open_document("local", path).await.unwrap();
+        assert_eq!(info.version, 1);
+
+        let version = test_state
+            .edit_document(
+                "local",
+                path,
+                1,
+                vec![DocumentEdit::insert(Position::new(0, 5), " World")],
+            )
+            .await
+            .unwrap();
+        assert_eq!(version, 2);
+        assert_eq!(
+            test_state.get_document_content("local", path).unwrap(),
+            "Hello World\n"
+        );
+
+        let info = test_state
+            .save_document("local", path, &SaveOptions::default())
+            .await
+            .unwrap();
+        assert!(!info.is_dirty);
+        assert_eq!(
+            tokio::fs::read_to_string(&file).await.unwrap(),
+            "Hello World\n"
+        );
+
+        test_state.close_document("local", path).await.unwrap();
+        assert!(test_state.get_document_content("local", path).is_err());
+    }
+
+    #[tokio::test]
+    async fn hibernate_and_awake() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let file = dir.join("notes.md");
+        tokio::fs::write(&file, "Hello").await.unwrap();
+        let path = file.to_str().unwrap();
+
+        let mut test_state = State::default();
+        test_state.open_document("local", path).await.unwrap();
+        test_state
+            .edit_document(
+                "local",
+                path,
+                1,
+                vec![DocumentEdit::insert(Position::new(0, 5), "!")],
+            )
+            .await
+            .unwrap();
+
+        test_state.hibernate().await;
+        assert!(test_state.is_hibernated());
+
+        // The unsaved document is persisted
+        assert_eq!(test_state.drafts[0].content, "Hello!");
+
+        test_state.awake().await;
+        assert!(!test_state.is_hibernated());
+        assert!(test_state.get_idle_time() < std::time::Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn report_resources() {
+        let manager = ExtensionsManager::default();
+        let mut test_state = State::new(1, manager, Box::new(MemoryPersistor::new()));
+
+        let memory = test_state.get_fs_by_name("memory").unwrap();
+        memory
+            .lock()
+            .await
+            .write_file_by_path("/notes.md", "Hello")
+            .await
+            .unwrap();
+        test_state
+            .open_document("memory", "/notes.md")
+            .await
+            .unwrap();
+        test_state.output_buffers.push(
+            SearchSource::Terminal {
+                terminal_shell_id: "bash".to_string(),
+            },
+            "ls\n",
+        );
+
+        let report = test_state.get_resource_report();
+        assert_eq!(report.state_id, 1);
+        assert_eq!(report.open_documents, 1);
+        assert_eq!(report.memory.documents, 5);
+        assert_eq!(report.memory.output_buffers, 2);
+        assert_eq!(report.watchers, 0);
+        assert_eq!(report.running_processes, 0);
+        assert!(report.memory.get_total() >= 7);
+    }
+
+    #[tokio::test]
+    async fn undo_and_redo_updates() {
+        let manager = ExtensionsManager::default();
+        let mut test_state = State::new(1, manager, Box::new(MemoryPersistor::new()));
+
+        let mut new_data = test_state.data.clone();
+        new_data.eol_policy = EolPolicy::ForceLf;
+        test_state.update(new_data).await;
+        test_state.snapshot("before crlf").await;
+        test_state.data.eol_policy = EolPolicy::ForceCrlf;
+
+        assert_eq!(test_state.get_snapshots().len(), 2);
+
+        assert!(test_state.undo().await);
+        assert_eq!(test_state.data.eol_policy, EolPolicy::ForceLf);
+        assert!(test_state.undo().await);
+        assert_eq!(test_state.data.eol_policy, EolPolicy::Preserve);
+        assert!(!test_state.undo().await);
+
+        assert!(test_state.redo().await);
+        assert!(test_state.redo().await);
+        assert_eq!(test_state.data.eol_policy, EolPolicy::ForceCrlf);
+
+        // Restoring a snapshot is also a new revision
+        assert_eq!(test_state.data.revision, 5);
+
+        // The history is persisted too
+        let history = test_state
+            .persistor
+            .as_ref()
+            .unwrap()
+            .lock()
+            .await
+            .load_history()
+            .unwrap();
+        assert_eq!(history.get_snapshots().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn group_commands() {
+        let manager = ExtensionsManager::default();
+        let mut test_state = State::new(1, manager, Box::new(MemoryPersistor::new()));
+        let original = test_state.data.eol_policy;
+
+        let group_id = test_state.begin_group("Scaffold", "owner").await.unwrap();
+        let mut new_data = test_state.data.clone();
+        new_data.eol_policy = EolPolicy::ForceLf;
+        test_state.update(new_data).await;
+
+        // Nested groups join the open one
+        assert_eq!(
+            test_state.begin_group("Layout", "owner").await,
+            Ok(group_id.clone())
+        );
+        test_state.save_layout("focus").await;
+        assert_eq!(test_state.end_group(&group_id, "owner").await, Ok(None));
+        assert!(test_state.get_snapshots().is_empty());
+
+        // Other clients can't join or end it
+        assert_eq!(
+            test_state.begin_group("Other", "other").await,
+            Err(Errors::Transaction(TransactionErrors::GroupInUse))
+        );
+        assert_eq!(
+            test_state.end_group(&group_id, "other").await,
+            Err(Errors::Transaction(TransactionErrors::GroupInUse))
+        );
+
+        let info = test_state
+            .end_group(&group_id, "owner")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            info.fields,
+            vec![StateDataField::EolPolicy, StateDataField::Layouts]
+        );
+        assert_eq!(test_state.get_snapshots().len(), 1);
+        assert_eq!(test_state.get_data_events(0).len(), 1);
+        assert_eq!(
+            test_state.end_group(&group_id, "owner").await,
+            Err(Errors::Transaction(TransactionErrors::NotGrouping))
+        );
+
+        // Undone at once
+        assert!(test_state.undo().await);
+        assert_eq!(test_state.data.eol_policy, original);
+        assert!(test_state.data.layouts.is_empty());
+
+        // Groups that are never ended don't hold back the changes
+        let group_id = test_state.begin_group("Macro", "owner").await.unwrap();
+        test_state.save_layout("focus").await;
+        assert_eq!(
+            test_state
+                .end_abandoned_group(Duration::from_secs(60))
+                .await,
+            None
+        );
+        let info = test_state
+            .end_abandoned_group(Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(info.id, group_id);
+        assert_eq!(info.fields, vec![StateDataField::Layouts]);
+        assert_eq!(test_state.get_snapshots().len(), 1);
+        assert_ne!(test_state.begin_group("Macro", "other").await, Ok(group_id));
+    }
+
+    #[tokio::test]
+    async fn log_data_changes() {
+        let manager = ExtensionsManager::default();
+        let mut test_state = State::new(1, manager, Box::new(MemoryPersistor::new()));
+
+        let mut new_data = test_state.data.clone();
+        new_data.eol_policy = EolPolicy::ForceLf;
+        test_state.update(new_data).await;
+        test_state.save_layout("focus").await;
+        assert!(test_state.undo().await);
+
+        let actions = test_state
+            .get_data_events(0)
+            .into_iter()
+            .map(|event| event.action)
+            .collect::<Vec<DataAction>>();
+        assert_eq!(
+            actions,
+            vec![
+                DataAction::SettingChanged {
+                    field: StateDataField::EolPolicy
+                },
+                DataAction::LayoutSaved {
+                    name: "focus".to_string()
+                },
+                DataAction::Restored {
+                    label: "undo".to_string()
+                }
+            ]
+        );
+        assert_eq!(test_state.get_snapshots()[0].label, "Change EolPolicy");
+
+        // Time travel
+        let saved = test_state.get_data_at(2).unwrap();
+        assert_eq!(saved.layouts.len(), 1);
+        assert_eq!(saved.eol_policy, EolPolicy::ForceLf);
+
+        // The data is rebuilt from the log if it wasn't saved
+        let log = test_state
+            .persistor
+            .as_ref()
+            .unwrap()
+            .lock()
+            .await
+            .load_event_log()
+            .unwrap();
+        let mut persistor = MemoryPersistor::new();
+        persistor.save(&StateData::default());
+        persistor.save_event_log(&log);
+        let rebuilt = State::new(1, ExtensionsManager::default(), Box::new(persistor));
+        assert_eq!(rebuilt.data.revision, 3);
+        assert_eq!(rebuilt.data.eol_policy, EolPolicy::ForceLf);
+        assert!(rebuilt.data.layouts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn restore_crashed_session() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let manager = ExtensionsManager::default();
+
+        {
+            let session_recovery = Arc::new(SessionRecovery::start(dir.to_path_buf()).unwrap());
+            let mut test_state = State::new(1, manager.clone(), Box::new(MemoryPersistor::new()))
+                .with_session_recovery(session_recovery);
+            test_state.save_draft(Draft {
+                filesystem: "memory".to_string(),
+                path: "/notes.md".to_string(),
+                content: "Unsaved".to_string(),
+            });
+            test_state.autosave_recovery();
+            // Crashed, the session is never finished
+        }
+
+        let session_recovery = Arc::new(SessionRecovery::start(dir.to_path_buf()).unwrap());
+        let mut test_state = State::new(1, manager, Box::new(MemoryPersistor::new()))
+            .with_session_recovery(session_recovery);
+        let filesystem = test_state.get_fs_by_name("memory").unwrap();
+        filesystem
+            .lock()
+            .await
+            .write_file_by_path("/notes.md", "Saved")
+            .await
+            .unwrap();
+
+        let session = test_state.get_recovered_session().unwrap();
+        assert!(session.data.is_some());
+
+        let restored = test_state.restore_recovered_session().await.unwrap();
+        assert_eq!(restored.len(), 1);
+        assert!(restored[0].is_dirty);
+        assert_eq!(
+            test_state
+                .get_document_content("memory", "/notes.md")
+                .unwrap(),
+            "Unsaved"
+        );
+        assert!(test_state.get_recovered_session().is_none());
+    }
+
+    #[tokio::test]
+    async fn load_workspace_config() {
+        let manager = ExtensionsManager::default();
+        let mut test_state = State::new(1, manager, Box::new(MemoryPersistor::new()));
+        let filesystem = test_state.get_fs_by_name("memory").unwrap();
+
+        filesystem
+            .lock()
+            .await
+            .write_file_by_path(
+                "/project/.graviton/config.toml",
+                "excluded = [\"target\"]\n[tasks.build]\ncommand = \"cargo\"",
+            )
+            .await
+            .unwrap();
+
+        let config = test_state
+            .open_workspace("memory", "/project")
+            .await
+            .unwrap();
+        assert_eq!(config.excluded, vec!["target".to_owned()]);
+        // Its tasks are not registered until it's trusted
+        assert!(test_state.get_tasks().is_empty());
+
+        // Tasks of others with the same ID are kept
+        test_state
+            .register_task("build", config.tasks["build"].clone(), None)
+            .unwrap();
+
+        test_state
+            .trust_workspace("memory", "/project")
+            .await
+            .unwrap();
+        assert_eq!(test_state.get_tasks().len(), 2);
+
+        // Invalid configurations are ignored
+        filesystem
+            .lock()
+            .await
+            .write_file_by_path("/project/.graviton/config.toml", "excluded = 1")
+            .await
+            .unwrap();
+        assert!(test_state
+            .reload_workspace_config("memory", "/project")
+            .await
+            .is_err());
+        assert_eq!(
+            test_state
+                .get_workspace_config("memory", "/project")
+                .unwrap()
+                .excluded,
+            vec!["target".to_owned()]
+        );
+
+        test_state.close_workspace("memory", "/project").unwrap();
+        assert_eq!(test_state.get_tasks()[0].id, "build");
+        assert!(test_state.get_workspaces().is_empty());
+    }
+
+    #[tokio::test]
+    async fn save_with_workspace_eol_policy() {
+        let manager = ExtensionsManager::default();
+        let mut test_state = State::new(1, manager, Box::new(MemoryPersistor::new()));
+        test_state.data.eol_policy = EolPolicy::ForceLf;
+        let filesystem = test_state.get_fs_by_name("memory").unwrap();
+
+        filesystem
+            .lock()
+            .await
+            .write_file_by_path(
+                "/project/.graviton/config.toml",
+                "eol_policy = \"ForceCrlf\"",
+            )
+            .await
+            .unwrap();
+        test_state
+            .open_workspace("memory", "/project")
+            .await
+            .unwrap();
+
+        // The workspace's policy is used inside of it, the State's one everywhere else
+        let saved = test_state
+            .write_file("memory", "/project/main.rs", "a\nb\r\n")
+            .await
+            .unwrap();
+        assert_eq!(saved, "a\r\nb\r\n");
+        let saved = test_state
+            .write_file("memory", "/projects/main.rs", "a\nb\r\n")
+            .await
+            .unwrap();
+        assert_eq!(saved, "a\nb\n");
+    }
+
+    #[tokio::test]
+    async fn override_experiments() {
+        let mut test_state = State::default();
+        test_state.register_core_experiments(vec![ExperimentFlag::new("collab", 0)]);
+        assert!(!test_state.is_experiment_enabled("collab"));
+
+        test_state
+            .set_experiment_override("collab", Some(true))
+            .await
+            .unwrap();
+        assert!(test_state.is_experiment_enabled("collab"));
+        assert_eq!(
+            test_state.get_setting("experiments.collab"),
+            Some(serde_json::Value::Bool(true))
+        );
+
+        test_state
+            .set_experiment_override("collab", None)
+            .await
+            .unwrap();
+        assert!(!test_state.get_experiments()[0].overridden);
+        assert_eq!(
+            test_state
+                .set_experiment_override("unknown", Some(true))
+                .await,
+            Err(Errors::Experiment(ExperimentsErrors::FlagNotFound))
+        );
+    }
+
+    #[tokio::test]
+    async fn explorer_list() {
+        let mut test_state = State::default();
+        let filesystem = test_state.get_fs_by_name("memory").unwrap();
+        for path in ["/project/a.js", "/project/a.js.map", "/project/debug.log"] {
+            filesystem
+                .lock()
+                .await
+                .write_file_by_path(path, "")
+                .await
+                .unwrap();
+        }
+        test_state.data.workspace_settings.excluded = vec!["*.log".to_owned()];
+        test_state
+            .data
+            .workspace_settings
+            .file_nesting
+            .insert("*.js".to_owned(), vec!["$(capture).js.map".to_owned()]);
+        test_state
+            .decorations
+            .update(
+                "linter",
+                "memory",
+                vec![(
+                    "/project/a.js.map".to_owned(),
+                    Some(FileDecoration {
+                        badge: Some("!".to_owned()),
+                        ..Default::default()
+                    }),
+                )],
+            )
+            .await;
+
+        let listing = test_state
+            .explorer_list("memory", "/project")
+            .await
+            .unwrap();
+        assert_eq!(listing.excluded, 1);
+        assert_eq!(listing.items.len(), 1);
+
+        let item = &listing.items[0];
+        assert_eq!(item.name, "a.js");
+        assert!(item.icon.is_some());
+        assert_eq!(item.nested[0].name, "a.js.map");
+        assert_eq!(
+            item.nested[0].decorations["linter"].badge,
+            Some("!".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn quick_open_workspace_files() {
+        let mut test_state = State::default();
+        let filesystem = test_state.get_fs_by_name("memory").unwrap();
+        for (path, content) in [
+            ("/project/.gitignore", "target/"),
+            ("/project/src/main.rs", ""),
+            ("/project/target/main.rs", ""),
+        ] {
+            filesystem
+                .lock()
+                .await
+                .write_file_by_path(path, content)
+                .await
+                .unwrap();
+        }
+
+        test_state
+            .open_workspace("memory", "/project")
+            .await
+            .unwrap();
+
+        // The workspace is indexed in the background
+        async fn wait_for(state: &State, query: &str, count: usize) -> Vec<Ranked<QuickOpenItem>> {
+            for _ in 0..100 {
+                let found = state.quick_open(query);
+                if found.len() == count {
+                    return found;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("Expected {} results for <{}>", count, query);
+        }
+
+        // Ignored files are left out
+        let found = wait_for(&test_state, "main", 1).await;
+        assert_eq!(found[0].item.get_key(), "/project/src/main.rs");
+
+        // New files are indexed without crawling again
+        test_state
+            .write_file("memory", "/project/src/mainframe.rs", "")
+            .await
+            .unwrap();
+        let found = wait_for(&test_state, "main", 2).await;
+        assert_eq!(found[0].item.get_key(), "/project/src/main.rs");
+
+        test_state.close_workspace("memory", "/project").unwrap();
+        assert!(test_state.quick_open("").is_empty());
+    }
+
+    /// A memory filesystem that can't be modified
+    struct ReadOnlyFilesystem(MemoryFilesystem);
+
+    #[async_trait::async_trait]
+    impl Filesystem for ReadOnlyFilesystem {
+        fn capabilities(&self) -> FilesystemCapabilities {
+            FilesystemCapabilities {
+                read_only: true,
+                ..FilesystemCapabilities::default()
+            }
+        }
+
+        async fn read_file_by_path(&self, path: &str) -> Result<FileInfo, Errors> {
+            self.0.read_file_by_path(path).await
+        }
+
+        async fn write_file_by_path(&self, _path: &str, _content: &str) -> Result<(), Errors> {
+            // Nothing should be written, the State must refuse it before
+            Err(Errors::Fs(FilesystemErrors::PermissionDenied))
+        }
+
+        async fn list_dir_by_path(&self, path: &str) -> Result<Vec<DirItemInfo>, Errors> {
+            self.0.list_dir_by_path(path).await
+        }
+    }
+
+    #[tokio::test]
+    async fn adapt_to_filesystem_capabilities() {
+        let manager = ExtensionsManager::default();
+        let mut test_state = State::new(1, manager, Box::new(MemoryPersistor::new()));
+
+        let memory = MemoryFilesystem::new();
+        memory
+            .write_file_by_path("/notes.md", "Hello")
+            .await
+            .unwrap();
+        test_state.register_filesystem("readonly", Box::new(ReadOnlyFilesystem(memory)));
+
+        let capabilities = test_state.get_fs_capabilities("readonly").await.unwrap();
+        assert!(capabilities.read_only);
+        assert!(
+            test_state
+                .get_fs_capabilities("memory")
+                .await
+                .unwrap()
+                .watch
+        );
+
+        // Files can still be opened, but not saved
+        test_state
+            .open_document("readonly", "/notes.md")
+            .await
+            .unwrap();
+        assert_eq!(
+            test_state
+                .save_document("readonly", "/notes.md", &SaveOptions::default())
+                .await,
+            Err(Errors::Fs(FilesystemErrors::ReadOnly))
+        );
+        assert_eq!(
+            test_state
+                .rename_path("readonly", "/notes.md", "/renamed.md")
+                .await,
+            Err(Errors::Fs(FilesystemErrors::ReadOnly))
+        );
+
+        // There is no trash in memory
+        assert_eq!(
+            test_state.move_to_trash("memory", "/notes.md").await,
+            Err(Errors::Fs(FilesystemErrors::FileNotSupported))
+        );
+    }
+
+    #[tokio::test]
+    async fn open_files_with_custom_editors() {
+        struct ImageEditor(Arc<std::sync::Mutex<Vec<CustomEditorAction>>>);
+
+        impl Extension for ImageEditor {
+            fn get_info(&self) -> ExtensionInfo {
+                ExtensionInfo {
+                    id: "images".to_string(),
+                    name: "Images".to_string(),
+                }
+            }
+
+            fn init(&mut self, _state: Arc<Mutex<State>>) {}
+
+            fn unload(&mut self) {}
+
+            fn notify(&mut self, message: ClientMessages) {
+                if let ClientMessages::CustomEditorRequest { action, .. } = message {
+                    self.0.lock().unwrap().push(action);
+                }
+            }
+
+            fn get_custom_editors(&self) -> Vec<CustomEditor> {
+                vec![CustomEditor::new(
+                    "images/viewer",
+                    "Image Viewer",
+                    &["*.png"],
+                )]
+            }
+        }
+
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut manager = ExtensionsManager::default();
+        manager.register("images", Box::new(ImageEditor(requests.clone())));
+        let mut test_state = State::new(1, manager, Box::new(MemoryPersistor::new()));
+
+        let memory = test_state.get_fs_by_name("memory").unwrap();
+        memory
+            .lock()
+            .await
+            .write_file_by_path("/notes.md", "Hello")
+            .await
+            .unwrap();
+
+        // Text files still go to the documents
+        let notes = test_state.open_file("memory", "/notes.md", None).await;
+        assert!(matches!(notes, Ok(OpenedFile::Document(_))));
+        assert_eq!(
+            test_state
+                .open_file("memory", "/notes.md", Some("images/viewer"))
+                .await,
+            Err(Errors::Ext(ExtensionErrors::CustomEditorNotFound))
+        );
+
+        let logo = test_state.open_file("memory", "/logo.png", None).await;
+        match logo {
+            Ok(OpenedFile::Custom(info)) => assert_eq!(info.editor_id, "images/viewer"),
+            other => panic!("Unexpected {:?}", other),
+        }
+        assert!(test_state.documents.get("memory", "/logo.png").is_none());
+
+        test_state
+            .save_custom_document("memory", "/logo.png")
+            .await
+            .unwrap();
+        test_state
+            .close_custom_document("memory", "/logo.png")
+            .unwrap();
+        assert_eq!(
+            test_state.save_custom_document("memory", "/logo.png").await,
+            Err(Errors::Document(DocumentErrors::DocumentNotFound))
+        );
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 3);
+        for action in [
+            CustomEditorAction::Open,
+            CustomEditorAction::Save,
+            CustomEditorAction::Close,
+        ] {
+            assert!(requests.contains(&action));
+        }
+    }
+
+    #[tokio::test]
+    async fn only_owner_changes_host_commands() {
+        let manager = ExtensionsManager::default();
+        let mut test_state = State::new(1, manager, Box::new(MemoryPersistor::new()));
+        test_state.tokens.push("owner".to_owned());
+        let guest = test_state.issue_token("guest", vec![TokenScope::Edit], None);
+
+        let mut data = test_state.data.clone();
+        data.save_hooks.insert(
+            "rust".to_owned(),
+            vec![SaveHook::new(
+                "format",
+                SaveHookAction::Format {
+                    command: "rustfmt".to_owned(),
+                    args: Vec::new(),
+                },
+            )],
+        );
+
+        assert!(matches!(
+            test_state
+                .update_with_token(data.clone().into(), &guest.token)
+                .await,
+            Err(Errors::AccessDenied)
+        ));
+        assert!(test_state.data.save_hooks.is_empty());
+
+        test_state
+            .update_with_token(data.into(), "owner")
+            .await
+            .unwrap();
+        assert_eq!(test_state.data.save_hooks.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn read_file_ranges() {
+        let manager = ExtensionsManager::default();
+        let test_state = State::new(1, manager, Box::new(MemoryPersistor::new()));
+        test_state
+            .write_file("memory", "/notes.txt", "0123456789")
+            .await
+            .unwrap();
+
+        let chunk = test_state
+            .read_file_range("memory", "/notes.txt", 2, 4)
+            .await
+            .unwrap();
+        assert_eq!(chunk.content, b"2345");
+        assert_eq!(chunk.size, 10);
+        assert!(!chunk.is_last);
+
+        let chunk = test_state
+            .read_file_range("memory", "/notes.txt", 8, 4)
+            .await
+            .unwrap();
+        assert_eq!(chunk.content, b"89");
+        assert!(chunk.is_last);
+
+        assert!(matches!(
+            test_state.read_file_range("ftp", "/notes.txt", 0, 4).await,
+            Err(Errors::Fs(FilesystemErrors::FilesystemNotFound))
+        ));
     }
 }
@@ -1,5 +1,5 @@
+use crate::auth::Authenticator;
 use crate::messaging::ClientMessages;
-pub use crate::state_persistors::memory::MemoryPersistor;
 use crate::State;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -15,6 +15,7 @@ pub enum TokenFlags {
 pub struct StatesList {
     states: HashMap<u8, Arc<Mutex<State>>>,
     provided_tokens: Vec<TokenFlags>,
+    authenticator: Option<Authenticator>,
 }
 
 impl StatesList {
@@ -23,6 +24,7 @@ impl StatesList {
         Self {
             states: HashMap::new(),
             provided_tokens: Vec::new(),
+            authenticator: None,
         }
     }
 
@@ -31,11 +33,26 @@ impl StatesList {
         self
     }
 
+    /// Let remote users log in with an authentication provider
+    pub fn with_authenticator(mut self, authenticator: Authenticator) -> Self {
+        self.authenticator = Some(authenticator);
+        self
+    }
+
+    pub fn get_authenticator(&self) -> Option<Authenticator> {
+        self.authenticator.clone()
+    }
+
     /// Return the state by the given ID if found
     pub fn get_state_by_id(&self, id: u8) -> Option<Arc<Mutex<State>>> {
         self.states.get(&id).cloned()
     }
 
+    /// Return all the states
+    pub fn get_states(&self) -> Vec<Arc<Mutex<State>>> {
+        self.states.values().cloned().collect()
+    }
+
     /// Return the state by the given ID if found
     pub fn with_state(mut self, state: State) -> Self {
         let mut state = state;
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use super::StateDataUpdate;
+use crate::documents::DocumentEdit;
+
+/// How many events a slow subscriber can fall behind before missing some
+const SUBSCRIPTIONS_CAPACITY: usize = 256;
+
+/// Something that changed in a State
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event_type")]
+pub enum StateEvent {
+    /// Only the modified fields are included
+    DataChanged {
+        delta: Box<StateDataUpdate>,
+    },
+    FileWritten {
+        filesystem: String,
+        path: String,
+    },
+    PathRenamed {
+        filesystem: String,
+        from: String,
+        to: String,
+    },
+    PathDeleted {
+        filesystem: String,
+        path: String,
+    },
+    DocumentChanged {
+        filesystem: String,
+        path: String,
+        version: i32,
+        changes: Vec<DocumentEdit>,
+    },
+    ExtensionReloaded {
+        extension_id: String,
+    },
+}
+
+/// Listeners of the changes in a State, e.g other clients or extensions.
+/// A subscriber that falls too far behind receives a `Lagged` error and should get the whole State again
+#[derive(Clone, Debug)]
+pub struct StateSubscriptions {
+    sender: broadcast::Sender<StateEvent>,
+}
+
+impl Default for StateSubscriptions {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(SUBSCRIPTIONS_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl StateSubscriptions {
+    /// Listen for the events sent from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<StateEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Send an event to all the subscribers
+    pub fn broadcast(&self, event: StateEvent) {
+        // Fails if there are no subscribers, which is fine
+        self.sender.send(event).ok();
+    }
+
+    pub fn get_subscribers_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StateEvent, StateSubscriptions};
+
+    #[tokio::test]
+    async fn broadcast_to_subscribers() {
+        let subscriptions = StateSubscriptions::default();
+
+        // Nobody is listening
+        subscriptions.broadcast(StateEvent::ExtensionReloaded {
+            extension_id: "git".to_owned(),
+        });
+
+        let mut first = subscriptions.subscribe();
+        let mut second = subscriptions.clone().subscribe();
+        assert_eq!(subscriptions.get_subscribers_count(), 2);
+
+        let event = StateEvent::PathDeleted {
+            filesystem: "local".to_owned(),
+            path: "/notes.md".to_owned(),
+        };
+        subscriptions.broadcast(event.clone());
+
+        assert_eq!(first.recv().await.unwrap(), event);
+        assert_eq!(second.recv().await.unwrap(), event);
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::invitations::now_secs;
+
+/// What a token is allowed to do in a State
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenScope {
+    /// Read the State, files and extensions but not modify anything
+    ReadOnly,
+    /// Only read and write files
+    Filesystem,
+    /// Manage the extensions, e.g restart them or change their permissions
+    ExtensionAdmin,
+    /// Modify files and the State
+    Edit,
+}
+
+impl TokenScope {
+    /// Check if having this scope also grants the other one
+    pub fn includes(&self, other: TokenScope) -> bool {
+        match self {
+            Self::ReadOnly => other == Self::ReadOnly,
+            Self::Filesystem => other == Self::Filesystem,
+            Self::ExtensionAdmin => matches!(other, Self::ExtensionAdmin | Self::ReadOnly),
+            Self::Edit => matches!(other, Self::Edit | Self::ReadOnly | Self::Filesystem),
+        }
+    }
+}
+
+/// A token with limited access to a State, given to remote clients or extensions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScopedToken {
+    pub id: String,
+    pub token: String,
+    pub state_id: u8,
+    /// Who or what the token was given to, e.g `CI runner`
+    pub label: String,
+    pub scopes: Vec<TokenScope>,
+    /// Unix timestamp (seconds) from which the token is no longer valid, it never expires if None
+    pub expires_at: Option<u64>,
+}
+
+impl ScopedToken {
+    /// Create a new token, optionally valid for the given time
+    pub fn new(
+        state_id: u8,
+        label: &str,
+        scopes: Vec<TokenScope>,
+        duration: Option<Duration>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            token: Uuid::new_v4().to_string(),
+            state_id,
+            label: label.to_owned(),
+            scopes,
+            expires_at: duration.map(|duration| now_secs() + duration.as_secs()),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .map(|expires_at| now_secs() >= expires_at)
+            .unwrap_or(false)
+    }
+
+    /// Check if any of the token's scopes grants the given one
+    pub fn allows(&self, scope: TokenScope) -> bool {
+        self.scopes.iter().any(|granted| granted.includes(scope))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ScopedToken, TokenScope};
+
+    #[test]
+    fn scoped_tokens() {
+        let token = ScopedToken::new(1, "ci", vec![TokenScope::Filesystem], None);
+
+        assert!(!token.is_expired());
+        assert!(token.allows(TokenScope::Filesystem));
+        assert!(!token.allows(TokenScope::ReadOnly));
+        assert!(!token.allows(TokenScope::Edit));
+
+        let token = ScopedToken::new(1, "guest", vec![TokenScope::Edit], Some(Duration::ZERO));
+
+        assert!(token.is_expired());
+        assert!(token.allows(TokenScope::ReadOnly));
+        assert!(!token.allows(TokenScope::ExtensionAdmin));
+    }
+}
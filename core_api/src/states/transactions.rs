@@ -0,0 +1,167 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{StateData, StateDataField};
+use crate::filesystems::GravitonUri;
+
+/// Command groups errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum TransactionErrors {
+    /// There is no open group
+    NotGrouping,
+    /// The group being ended is not the open one
+    WrongGroup,
+    /// The open group was begun by another client
+    GroupInUse,
+}
+
+/// Groups without new commands for this long are ended, e.g the client that began it disconnected,
+/// so they don't hold back the persistence and the updates of the clients forever
+pub const COMMAND_GROUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What a group of commands changed, sent to the clients once it's ended
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CommandGroupInfo {
+    pub id: String,
+    /// e.g `Rename symbol`, it's the label of its undo snapshot
+    pub label: String,
+    /// Fields of the StateData modified by its commands
+    pub fields: Vec<StateDataField>,
+    /// Documents edited by its commands, so the editors can undo their edits at once
+    pub documents: Vec<GravitonUri>,
+}
+
+/// Several commands applied as a single undoable change, e.g a refactor, a scaffold or a macro.
+///
+/// Groups begun while another one is open join it, so a macro running commands that group
+/// their own steps is still undone at once. Only the client that began the group can join or end it.
+///
+/// Only the StateData is restored when the group is undone, the documents it edited are
+/// listed in its [`CommandGroupInfo`] so the editors can undo their edits at once
+#[derive(Debug, Clone)]
+pub struct CommandGroup {
+    info: CommandGroupInfo,
+    /// Token of the client that began it
+    owner: String,
+    /// How many times it was begun and not ended yet
+    depth: usize,
+    /// The data before the first command
+    base: StateData,
+    last_activity: Instant,
+}
+
+impl CommandGroup {
+    pub fn new(label: &str, owner: &str, base: StateData) -> Self {
+        Self {
+            info: CommandGroupInfo {
+                id: Uuid::new_v4().to_string(),
+                label: label.to_owned(),
+                fields: Vec::new(),
+                documents: Vec::new(),
+            },
+            owner: owner.to_owned(),
+            depth: 1,
+            base,
+            last_activity: Instant::now(),
+        }
+    }
+
+    pub fn get_id(&self) -> &str {
+        &self.info.id
+    }
+
+    pub fn is_owned_by(&self, owner: &str) -> bool {
+        self.owner == owner
+    }
+
+    /// Join a nested group
+    pub fn begin(&mut self) {
+        self.depth += 1;
+        self.last_activity = Instant::now();
+    }
+
+    /// Leave a group, returns true once the outermost one is ended
+    pub fn end(&mut self) -> bool {
+        self.depth = self.depth.saturating_sub(1);
+        self.depth == 0
+    }
+
+    /// Check if no commands were added to the group for longer than the given timeout
+    pub fn is_abandoned(&self, timeout: Duration) -> bool {
+        self.last_activity.elapsed() >= timeout
+    }
+
+    pub fn add_fields(&mut self, fields: &[StateDataField]) {
+        self.last_activity = Instant::now();
+        for field in fields {
+            if !self.info.fields.contains(field) {
+                self.info.fields.push(*field);
+            }
+        }
+    }
+
+    pub fn add_document(&mut self, uri: GravitonUri) {
+        self.last_activity = Instant::now();
+        if !self.info.documents.contains(&uri) {
+            self.info.documents.push(uri);
+        }
+    }
+
+    /// The data before the group and what it changed
+    pub fn finish(self) -> (StateData, CommandGroupInfo) {
+        (self.base, self.info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::CommandGroup;
+    use crate::filesystems::GravitonUri;
+    use crate::states::{StateData, StateDataField};
+
+    #[test]
+    fn nested_groups() {
+        let mut group = CommandGroup::new("Rename symbol", "client", StateData::default());
+        assert!(group.is_owned_by("client"));
+        assert!(!group.is_owned_by("guest"));
+
+        // Only the outermost group ends it
+        group.begin();
+        assert!(!group.end());
+        assert!(group.end());
+    }
+
+    #[test]
+    fn group_changes() {
+        let mut group = CommandGroup::new("Scaffold", "client", StateData::default());
+        let uri = GravitonUri::parse("graviton://local/project/main.rs").unwrap();
+
+        group.add_fields(&[StateDataField::Views, StateDataField::EolPolicy]);
+        group.add_fields(&[StateDataField::Views]);
+        group.add_document(uri.clone());
+        group.add_document(uri.clone());
+
+        let id = group.get_id().to_owned();
+        let (base, info) = group.finish();
+        assert_eq!(base, StateData::default());
+        assert_eq!(info.id, id);
+        assert_eq!(info.label, "Scaffold");
+        assert_eq!(
+            info.fields,
+            vec![StateDataField::Views, StateDataField::EolPolicy]
+        );
+        assert_eq!(info.documents, vec![uri]);
+    }
+
+    #[test]
+    fn abandoned_groups() {
+        let group = CommandGroup::new("Macro", "client", StateData::default());
+
+        assert!(!group.is_abandoned(Duration::from_secs(60)));
+        assert!(group.is_abandoned(Duration::ZERO));
+    }
+}
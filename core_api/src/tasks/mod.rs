@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::locale::ClientLocale;
+use crate::states::now_secs;
+
+mod runner;
+mod schedule;
+pub use runner::*;
+pub use schedule::*;
+
+/// How much of the output of a task is kept
+const OUTPUT_LIMIT: usize = 4096;
+
+/// Tasks errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum TaskErrors {
+    TaskNotFound,
+    InvalidSchedule,
+    CouldNotStart,
+    Cancelled,
+    InvalidProblemMatcher,
+    RunNotFound,
+}
+
+/// A command to run, e.g `cargo build`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TaskDefinition {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Folder where it's run
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Used to find problems in the output
+    #[serde(default)]
+    pub problem_matchers: Vec<ProblemMatcher>,
+}
+
+/// Result of running a task
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TaskRun {
+    /// Unix timestamps (seconds)
+    pub started_at: u64,
+    pub finished_at: u64,
+    /// Missing if it couldn't be started or it was killed by a signal
+    pub exit_code: Option<i32>,
+    /// The last part of both the stdout and the stderr
+    pub output: String,
+}
+
+impl TaskRun {
+    pub fn is_success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// Text of the notification shown when it finishes, in the format of the client
+    pub fn get_summary(&self, task_id: &str, locale: &ClientLocale) -> String {
+        let outcome = match self.exit_code {
+            Some(0) => "succeeded".to_owned(),
+            Some(code) => format!("failed with exit code {}", code),
+            None => "was stopped".to_owned(),
+        };
+        let seconds = self.finished_at.saturating_sub(self.started_at) as f64;
+
+        format!(
+            "Task <{}> {} at {} after {}s",
+            task_id,
+            outcome,
+            locale.format_timestamp(self.finished_at),
+            locale.format_number(seconds, 0)
+        )
+    }
+}
+
+impl TaskDefinition {
+    fn get_command(&self) -> Command {
+        let mut command = Command::new(&self.command);
+        command.args(&self.args).envs(&self.env);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        command
+    }
+
+    /// Run the task until it finishes
+    pub async fn run(&self) -> TaskRun {
+        let started_at = now_secs();
+
+        let output = self
+            .get_command()
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await;
+
+        let (exit_code, output) = match output {
+            Ok(output) => {
+                let mut content = String::from_utf8_lossy(&output.stdout).to_string();
+                content.push_str(&String::from_utf8_lossy(&output.stderr));
+                (output.status.code(), content)
+            }
+            Err(err) => (None, err.to_string()),
+        };
+
+        TaskRun {
+            started_at,
+            finished_at: now_secs(),
+            exit_code,
+            output: get_tail(&output, OUTPUT_LIMIT).to_owned(),
+        }
+    }
+}
+
+/// The last `limit` bytes of a text, at most
+fn get_tail(text: &str, limit: usize) -> &str {
+    let mut start = text.len().saturating_sub(limit);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_tail, TaskDefinition, TaskRun};
+    use crate::locale::ClientLocale;
+
+    #[test]
+    fn output_tails() {
+        assert_eq!(get_tail("cargo build", 5), "build");
+        assert_eq!(get_tail("build", 10), "build");
+        // The tail never starts in the middle of a character
+        assert_eq!(get_tail("añb", 2), "b");
+    }
+
+    #[test]
+    fn runs_summaries() {
+        let locale = ClientLocale::default();
+        let mut run = TaskRun {
+            started_at: 1659322800,
+            finished_at: 1659322803,
+            exit_code: Some(0),
+            output: String::new(),
+        };
+        let finished_at = locale.format_timestamp(run.finished_at);
+
+        assert!(run.is_success());
+        assert_eq!(
+            run.get_summary("build", &locale),
+            format!("Task <build> succeeded at {} after 3s", finished_at)
+        );
+
+        run.exit_code = Some(101);
+        assert!(!run.is_success());
+        assert_eq!(
+            run.get_summary("build", &locale),
+            format!(
+                "Task <build> failed with exit code 101 at {} after 3s",
+                finished_at
+            )
+        );
+
+        run.exit_code = None;
+        assert!(!run.is_success());
+        assert_eq!(
+            run.get_summary("build", &locale),
+            format!("Task <build> was stopped at {} after 3s", finished_at)
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn run_tasks() {
+        let task = TaskDefinition {
+            command: "sh".to_owned(),
+            args: vec!["-c".to_owned(), "echo $GREETING; exit 3".to_owned()],
+            cwd: None,
+            env: [("GREETING".to_owned(), "hello".to_owned())].into(),
+            problem_matchers: Vec::new(),
+        };
+
+        let run = task.run().await;
+        assert_eq!(run.exit_code, Some(3));
+        assert_eq!(run.output, "hello\n");
+
+        // Commands that can't be started have no exit code
+        let task = TaskDefinition {
+            command: "graviton-missing-command".to_owned(),
+            ..task
+        };
+        let run = task.run().await;
+        assert_eq!(run.exit_code, None);
+        assert!(!run.output.is_empty());
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+use super::{TaskDefinition, TaskErrors};
+use crate::messaging::{ClientMessages, ServerMessages};
+use crate::search::CancellationToken;
+
+/// How many tasks can run at the same time by default, the rest wait
+pub const DEFAULT_CONCURRENT_TASKS: usize = 4;
+
+/// How often a running task checks if it was cancelled
+const CANCELLATION_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Where an output line was written to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl ProblemSeverity {
+    fn parse(severity: &str) -> Self {
+        match severity.to_lowercase().as_str() {
+            "error" => Self::Error,
+            "warning" | "warn" => Self::Warning,
+            _ => Self::Info,
+        }
+    }
+}
+
+/// Finds problems, like compiler errors, in the output lines of a task
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProblemMatcher {
+    /// Regex matched against every line
+    pub pattern: String,
+    /// Capture groups of the pattern
+    pub file: usize,
+    pub line: usize,
+    #[serde(default)]
+    pub column: Option<usize>,
+    pub message: usize,
+    /// Problems are errors if there is no severity group
+    #[serde(default)]
+    pub severity: Option<usize>,
+}
+
+/// A problem found in the output of a task
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub file: String,
+    pub line: u32,
+    pub column: Option<u32>,
+    pub message: String,
+    pub severity: ProblemSeverity,
+}
+
+impl ProblemMatcher {
+    fn compile(&self) -> Result<Regex, TaskErrors> {
+        Regex::new(&self.pattern).map_err(|_| TaskErrors::InvalidProblemMatcher)
+    }
+
+    /// Find a problem in an output line
+    fn find(&self, regex: &Regex, line: &str) -> Option<Problem> {
+        let captures = regex.captures(line)?;
+        let group = |index: usize| captures.get(index).map(|group| group.as_str());
+
+        Some(Problem {
+            file: group(self.file)?.to_owned(),
+            line: group(self.line)?.parse().ok()?,
+            column: self
+                .column
+                .and_then(group)
+                .and_then(|column| column.parse().ok()),
+            message: group(self.message)?.to_owned(),
+            severity: self
+                .severity
+                .and_then(group)
+                .map(ProblemSeverity::parse)
+                .unwrap_or(ProblemSeverity::Error),
+        })
+    }
+}
+
+/// A task that can be run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RegisteredTask {
+    pub id: String,
+    pub definition: TaskDefinition,
+    /// Extension that registered it, if any
+    pub extension_id: Option<String>,
+}
+
+/// A task being run, or waiting to be run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TaskRunInfo {
+    pub run_id: String,
+    pub task_id: String,
+}
+
+/// Runs the build, test... tasks of a State, streaming their output to the clients
+#[derive(Clone)]
+pub struct TaskRunner {
+    tasks: HashMap<String, RegisteredTask>,
+    /// Runs by ID, the token is cancelled once they finish
+    runs: HashMap<String, (String, CancellationToken)>,
+    permits: Arc<Semaphore>,
+}
+
+impl Default for TaskRunner {
+    fn default() -> Self {
+        Self::new(DEFAULT_CONCURRENT_TASKS)
+    }
+}
+
+impl TaskRunner {
+    pub fn new(concurrent_tasks: usize) -> Self {
+        Self {
+            tasks: HashMap::new(),
+            runs: HashMap::new(),
+            permits: Arc::new(Semaphore::new(concurrent_tasks.max(1))),
+        }
+    }
+
+    /// Add a task, or replace the one with the same ID
+    pub fn register(&mut self, task: RegisteredTask) -> Result<(), TaskErrors> {
+        for matcher in &task.definition.problem_matchers {
+            matcher.compile()?;
+        }
+        self.tasks.insert(task.id.clone(), task);
+        Ok(())
+    }
+
+    pub fn unregister(&mut self, task_id: &str) -> Result<(), TaskErrors> {
+        self.tasks
+            .remove(task_id)
+            .map(|_| ())
+            .ok_or(TaskErrors::TaskNotFound)
+    }
+
+    /// Remove the tasks registered by an extension, e.g when it's reloaded
+    pub fn unregister_extension(&mut self, extension_id: &str) {
+        self.tasks
+            .retain(|_, task| task.extension_id.as_deref() != Some(extension_id));
+    }
+
+    pub fn get_task(&self, task_id: &str) -> Option<&RegisteredTask> {
+        self.tasks.get(task_id)
+    }
+
+    pub fn get_tasks(&self) -> Vec<RegisteredTask> {
+        self.tasks.values().cloned().collect()
+    }
+
+    pub fn get_runs(&self) -> Vec<TaskRunInfo> {
+        self.runs
+            .iter()
+            .filter(|(_, (_, token))| !token.is_cancelled())
+            .map(|(run_id, (task_id, _))| TaskRunInfo {
+                run_id: run_id.clone(),
+                task_id: task_id.clone(),
+            })
+            .collect()
+    }
+
+    /// Run a task in the background, it waits if too many tasks are running.
+    /// Returns the ID of the run, which can be used to cancel it
+    pub fn run(
+        &mut self,
+        task_id: &str,
+        sender: Sender<ClientMessages>,
+        state_id: u8,
+    ) -> Result<String, TaskErrors> {
+        let task = self
+            .tasks
+            .get(task_id)
+            .ok_or(TaskErrors::TaskNotFound)?
+            .definition
+            .clone();
+
+        // Forget finished runs
+        self.runs.retain(|_, (_, token)| !token.is_cancelled());
+
+        let run_id = Uuid::new_v4().to_string();
+        let token = CancellationToken::new();
+        self.runs
+            .insert(run_id.clone(), (task_id.to_owned(), token.clone()));
+
+        let permits = self.permits.clone();
+        let task_id = task_id.to_owned();
+        let id = run_id.clone();
+
+        tokio::spawn(async move {
+            let mut problems = Vec::new();
+
+            let result = match wait_for_permit(&permits, &token).await {
+                Some(_permit) => {
+                    sender
+                        .send(ClientMessages::ServerMessage(ServerMessages::TaskStarted {
+                            state_id,
+                            run_id: id.clone(),
+                            task_id: task_id.clone(),
+                        }))
+                        .await
+                        .ok();
+
+                    let output = |stream: TaskStream, line: String| {
+                        let sender = sender.clone();
+                        let run_id = id.clone();
+                        async move {
+                            sender
+                                .send(ClientMessages::ServerMessage(ServerMessages::TaskOutput {
+                                    state_id,
+                                    run_id,
+                                    stream,
+                                    line,
+                                }))
+                                .await
+                                .ok();
+                        }
+                    };
+
+                    execute(&task, &token, &mut problems, output).await
+                }
+                None => Err(TaskErrors::Cancelled),
+            };
+
+            token.cancel();
+
+            sender
+                .send(ClientMessages::ServerMessage(
+                    ServerMessages::TaskFinished {
+                        state_id,
+                        run_id: id,
+                        task_id,
+                        result,
+                        problems,
+                    },
+                ))
+                .await
+                .ok();
+        });
+
+        Ok(run_id)
+    }
+
+    /// Stop a task, or don't run it if it's still waiting
+    pub fn cancel(&mut self, run_id: &str) -> Result<(), TaskErrors> {
+        let (_, token) = self.runs.remove(run_id).ok_or(TaskErrors::RunNotFound)?;
+        token.cancel();
+        Ok(())
+    }
+
+    /// Stop all the tasks
+    pub fn cancel_all(&mut self) {
+        for (_, (_, token)) in self.runs.drain() {
+            token.cancel();
+        }
+    }
+}
+
+/// Wait until another task can run, returns nothing if it's cancelled meanwhile
+async fn wait_for_permit(
+    permits: &Arc<Semaphore>,
+    token: &CancellationToken,
+) -> Option<tokio::sync::OwnedSemaphorePermit> {
+    let mut interval = tokio::time::interval(CANCELLATION_CHECK_INTERVAL);
+    let permit = permits.clone().acquire_owned();
+    tokio::pin!(permit);
+
+    loop {
+        tokio::select! {
+            permit = &mut permit => return permit.ok(),
+            _ = interval.tick() => {
+                if token.is_cancelled() {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Run a task until it exits or it's cancelled, returns the exit code
+async fn execute<F, Fut>(
+    task: &TaskDefinition,
+    token: &CancellationToken,
+    problems: &mut Vec<Problem>,
+    output: F,
+) -> Result<Option<i32>, TaskErrors>
+where
+    F: Fn(TaskStream, String) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    let matchers = task
+        .problem_matchers
+        .iter()
+        .map(|matcher| matcher.compile().map(|regex| (matcher, regex)))
+        .collect::<Result<Vec<(&ProblemMatcher, Regex)>, TaskErrors>>()?;
+
+    let mut child = task
+        .get_command()
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|_| TaskErrors::CouldNotStart)?;
+
+    let mut stdout = child.stdout.take().map(|out| BufReader::new(out).lines());
+    let mut stderr = child.stderr.take().map(|err| BufReader::new(err).lines());
+    let mut interval = tokio::time::interval(CANCELLATION_CHECK_INTERVAL);
+
+    while stdout.is_some() || stderr.is_some() {
+        let (stream, line) = tokio::select! {
+            line = async { stdout.as_mut().unwrap().next_line().await }, if stdout.is_some() => {
+                (TaskStream::Stdout, line)
+            }
+            line = async { stderr.as_mut().unwrap().next_line().await }, if stderr.is_some() => {
+                (TaskStream::Stderr, line)
+            }
+            _ = interval.tick() => {
+                if token.is_cancelled() {
+                    child.kill().await.ok();
+                    return Err(TaskErrors::Cancelled);
+                }
+                continue;
+            }
+        };
+
+        match line {
+            Ok(Some(line)) => {
+                problems.extend(
+                    matchers
+                        .iter()
+                        .filter_map(|(matcher, regex)| matcher.find(regex, &line)),
+                );
+                output(stream, line).await;
+            }
+            // The stream was closed
+            _ => match stream {
+                TaskStream::Stdout => stdout = None,
+                TaskStream::Stderr => stderr = None,
+            },
+        }
+    }
+
+    let status = child.wait().await.map_err(|_| TaskErrors::CouldNotStart)?;
+    Ok(status.code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProblemMatcher, ProblemSeverity};
+
+    #[test]
+    fn match_problems() {
+        let matcher = ProblemMatcher {
+            pattern: r"^(.+):(\d+):(\d+): (error|warning): (.+)$".to_owned(),
+            file: 1,
+            line: 2,
+            column: Some(3),
+            message: 5,
+            severity: Some(4),
+        };
+        let regex = matcher.compile().unwrap();
+
+        let problem = matcher
+            .find(&regex, "src/main.rs:10:5: warning: unused variable")
+            .unwrap();
+        assert_eq!(problem.file, "src/main.rs");
+        assert_eq!(problem.line, 10);
+        assert_eq!(problem.column, Some(5));
+        assert_eq!(problem.message, "unused variable");
+        assert_eq!(problem.severity, ProblemSeverity::Warning);
+
+        assert!(matcher.find(&regex, "Compiling graviton").is_none());
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{TaskDefinition, TaskErrors, TaskRun};
+
+/// Allowed values of each field of a cron expression
+const FIELDS_RANGES: [(u8, u8); 5] = [(0, 59), (0, 23), (1, 31), (1, 12), (0, 7)];
+
+/// Cron-like schedule, `minute hour day-of-month month day-of-week`, e.g `0 3 * * *` for every night at 3:00.
+/// Supports `*`, lists (`1,15`), ranges (`1-5`) and steps (`*/15`). Times are in UTC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    expression: String,
+    /// Allowed values of every field, as bits
+    fields: [u64; 5],
+    /// Day-of-month and day-of-week are combined with OR if both are restricted
+    days_restricted: (bool, bool),
+}
+
+fn parse_field(field: &str, (min, max): (u8, u8)) -> Result<u64, TaskErrors> {
+    let mut bits = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u8>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or(TaskErrors::InvalidSchedule)?,
+            ),
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            let start = start.parse().map_err(|_| TaskErrors::InvalidSchedule)?;
+            let end = end.parse().map_err(|_| TaskErrors::InvalidSchedule)?;
+            (start, end)
+        } else {
+            let value = range.parse().map_err(|_| TaskErrors::InvalidSchedule)?;
+            (value, value)
+        };
+
+        if start < min || end > max || start > end {
+            return Err(TaskErrors::InvalidSchedule);
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+/// Minute, hour, day of the month, month and day of the week (0 is Sunday) of a Unix timestamp
+fn get_date_fields(timestamp: u64) -> [u8; 5] {
+    let minutes = timestamp / 60;
+    let days = (minutes / (60 * 24)) as i64;
+
+    // Convert the days since the epoch into a civil date
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+
+    // 1970-01-01 was a Thursday
+    let weekday = (days + 4).rem_euclid(7);
+
+    [
+        (minutes % 60) as u8,
+        (minutes / 60 % 24) as u8,
+        day as u8,
+        month as u8,
+        weekday as u8,
+    ]
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, TaskErrors> {
+        let parts = expression.split_whitespace().collect::<Vec<&str>>();
+        if parts.len() != 5 {
+            return Err(TaskErrors::InvalidSchedule);
+        }
+
+        let mut fields = [0u64; 5];
+        for (index, part) in parts.iter().enumerate() {
+            fields[index] = parse_field(part, FIELDS_RANGES[index])?;
+        }
+
+        // 7 is also Sunday
+        if fields[4] & (1 << 7) != 0 {
+            fields[4] |= 1;
+        }
+
+        Ok(Self {
+            expression: parts.join(" "),
+            fields,
+            days_restricted: (parts[2] != "*", parts[4] != "*"),
+        })
+    }
+
+    /// Check if the schedule runs on the minute of a Unix timestamp
+    pub fn matches(&self, timestamp: u64) -> bool {
+        let [minute, hour, day, month, weekday] = get_date_fields(timestamp);
+        let has = |field: usize, value: u8| self.fields[field] & (1 << value) != 0;
+
+        let day_matches = match self.days_restricted {
+            (true, true) => has(2, day) || has(4, weekday),
+            _ => has(2, day) && has(4, weekday),
+        };
+
+        has(0, minute) && has(1, hour) && has(3, month) && day_matches
+    }
+
+    /// Check if the schedule runs on any minute after `from` and until `to`, both Unix timestamps.
+    /// At most the last day is checked
+    pub fn matches_between(&self, from: u64, to: u64) -> bool {
+        let from = (from / 60 + 1).max((to / 60).saturating_sub(60 * 24));
+        (from..=to / 60).any(|minute| self.matches(minute * 60))
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = TaskErrors;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        Self::parse(expression)
+    }
+}
+
+impl Serialize for CronSchedule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.expression)
+    }
+}
+
+impl<'de> Deserialize<'de> for CronSchedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let expression = String::deserialize(deserializer)?;
+        Self::parse(&expression).map_err(|_| serde::de::Error::custom("invalid cron schedule"))
+    }
+}
+
+/// A task run periodically while the State is active
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledTask {
+    pub id: String,
+    pub task: TaskDefinition,
+    pub schedule: CronSchedule,
+    pub enabled: bool,
+    /// Result of the last time it was run
+    #[serde(default)]
+    pub last_run: Option<TaskRun>,
+}
+
+impl ScheduledTask {
+    pub fn new(id: &str, task: TaskDefinition, schedule: CronSchedule) -> Self {
+        Self {
+            id: id.to_owned(),
+            task,
+            schedule,
+            enabled: true,
+            last_run: None,
+        }
+    }
+
+    /// Check if it should have run after `from` and until `to`, both Unix timestamps
+    pub fn is_due(&self, from: u64, to: u64) -> bool {
+        self.enabled && self.schedule.matches_between(from, to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CronSchedule;
+
+    #[test]
+    fn cron_schedules() {
+        // 2022-08-01 03:00:00 UTC, a Monday
+        let monday = 1659322800;
+
+        assert!(CronSchedule::parse("0 3 * * *").unwrap().matches(monday));
+        assert!(!CronSchedule::parse("0 4 * * *").unwrap().matches(monday));
+        assert!(CronSchedule::parse("*/15 1-5 1 8 1")
+            .unwrap()
+            .matches(monday));
+        assert!(CronSchedule::parse("0 3 * * 1-5").unwrap().matches(monday));
+        assert!(!CronSchedule::parse("0 3 * * 0,6").unwrap().matches(monday));
+
+        // Either the day of the month or the day of the week
+        assert!(CronSchedule::parse("0 3 15 * 1").unwrap().matches(monday));
+
+        let nightly = CronSchedule::parse("0 3 * * *").unwrap();
+        assert!(nightly.matches_between(monday - 120, monday + 30));
+        assert!(!nightly.matches_between(monday, monday + 120));
+
+        assert!(CronSchedule::parse("0 3 * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+    }
+}
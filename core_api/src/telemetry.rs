@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::extensions::base::Extension;
+use crate::metrics::METRICS;
+
+/// How the telemetry of a State behaves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TelemetryConfig {
+    /// Handlers of the extensions taking longer than this are slow
+    pub slow_handler_threshold: Duration,
+    /// How many slow calls in a row flag an extension as slow
+    pub slow_handler_limit: u32,
+    /// Send the metrics to the clients and extensions this often
+    pub broadcast_interval: Option<Duration>,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            slow_handler_threshold: Duration::from_millis(100),
+            slow_handler_limit: 3,
+            broadcast_interval: None,
+        }
+    }
+}
+
+/// Handlers of the extensions that are timed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtensionHandler {
+    Init,
+    Notify,
+}
+
+/// Filesystem operations that are timed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilesystemOperation {
+    Read,
+    Write,
+    ListDir,
+}
+
+/// Latencies of an operation, the average is `total_micros / calls`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Timings {
+    pub calls: u64,
+    pub total_micros: u64,
+    pub max_micros: u64,
+}
+
+impl Timings {
+    fn record(&mut self, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        self.calls += 1;
+        self.total_micros += micros;
+        self.max_micros = self.max_micros.max(micros);
+    }
+}
+
+/// Timings of the handlers of an extension
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExtensionTimings {
+    pub init: Timings,
+    pub notify: Timings,
+    /// Calls that took longer than the threshold
+    pub slow_calls: u64,
+    /// Slow calls since the last fast one
+    pub slow_streak: u32,
+    /// Exceeded the threshold too many times in a row, it stays flagged until the telemetry is enabled again
+    pub slow: bool,
+}
+
+/// Latencies of the filesystem operations
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FilesystemTimings {
+    pub read: Timings,
+    pub write: Timings,
+    pub list_dir: Timings,
+}
+
+/// What has been measured since the telemetry was enabled
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TelemetryReport {
+    pub extensions: HashMap<String, ExtensionTimings>,
+    pub filesystem: FilesystemTimings,
+    /// Messages received by the server, from [`METRICS`], the throughput is `messages / recording_secs`
+    pub messages: u64,
+    pub recording_secs: u64,
+    /// Extensions flagged as slow
+    pub slow_extensions: Vec<String>,
+}
+
+struct TelemetryData {
+    config: TelemetryConfig,
+    started: Instant,
+    extensions: HashMap<String, ExtensionTimings>,
+    filesystem: FilesystemTimings,
+    /// Messages the server had received when it was enabled
+    messages_before: u64,
+}
+
+/// Opt-in metrics of a State, shared between the State and its extensions manager.
+/// Nothing is recorded until it's enabled
+#[derive(Clone, Default)]
+pub struct Telemetry(Arc<RwLock<Option<TelemetryData>>>);
+
+impl Telemetry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start recording, this resets what was measured before
+    pub fn enable(&self, config: TelemetryConfig) {
+        *self.0.write().unwrap() = Some(TelemetryData {
+            config,
+            started: Instant::now(),
+            extensions: HashMap::new(),
+            filesystem: FilesystemTimings::default(),
+            messages_before: METRICS.get_messages(),
+        });
+    }
+
+    /// Stop recording and forget what was measured
+    pub fn disable(&self) {
+        *self.0.write().unwrap() = None;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.read().unwrap().is_some()
+    }
+
+    pub fn get_config(&self) -> Option<TelemetryConfig> {
+        self.0.read().unwrap().as_ref().map(|data| data.config)
+    }
+
+    /// Record how long a handler of an extension took, flagging it if it's repeatedly slow
+    pub fn record_handler(&self, extension_id: &str, handler: ExtensionHandler, elapsed: Duration) {
+        let mut data = self.0.write().unwrap();
+        let data = match data.as_mut() {
+            Some(data) => data,
+            None => return,
+        };
+        let config = data.config;
+        let timings = data.extensions.entry(extension_id.to_owned()).or_default();
+
+        match handler {
+            ExtensionHandler::Init => timings.init.record(elapsed),
+            ExtensionHandler::Notify => timings.notify.record(elapsed),
+        }
+
+        if elapsed < config.slow_handler_threshold {
+            timings.slow_streak = 0;
+            return;
+        }
+
+        timings.slow_calls += 1;
+        timings.slow_streak += 1;
+        if !timings.slow && timings.slow_streak >= config.slow_handler_limit {
+            timings.slow = true;
+            warn!(
+                "Extension <{}> exceeded {}ms {} times in a row",
+                extension_id,
+                config.slow_handler_threshold.as_millis(),
+                timings.slow_streak
+            );
+        }
+    }
+
+    /// Record how long a filesystem operation took
+    pub fn record_filesystem(&self, operation: FilesystemOperation, elapsed: Duration) {
+        if let Some(data) = self.0.write().unwrap().as_mut() {
+            let timings = &mut data.filesystem;
+            match operation {
+                FilesystemOperation::Read => timings.read.record(elapsed),
+                FilesystemOperation::Write => timings.write.record(elapsed),
+                FilesystemOperation::ListDir => timings.list_dir.record(elapsed),
+            }
+        }
+    }
+
+    /// Wrap a call to an extension so its duration is recorded
+    pub fn timed(
+        &self,
+        extension_id: &str,
+        handler: ExtensionHandler,
+        call: impl FnOnce(&mut Box<dyn Extension + Send>) + Send,
+    ) -> impl FnOnce(&mut Box<dyn Extension + Send>) + Send {
+        let telemetry = self.clone();
+        let extension_id = extension_id.to_owned();
+
+        move |extension| {
+            if !telemetry.is_enabled() {
+                call(extension);
+                return;
+            }
+
+            let start = Instant::now();
+            call(extension);
+            telemetry.record_handler(&extension_id, handler, start.elapsed());
+        }
+    }
+
+    /// What has been measured, none if it's not enabled
+    pub fn get_report(&self) -> Option<TelemetryReport> {
+        let data = self.0.read().unwrap();
+        let data = data.as_ref()?;
+
+        let mut slow_extensions = data
+            .extensions
+            .iter()
+            .filter(|(_, timings)| timings.slow)
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<String>>();
+        slow_extensions.sort();
+
+        Some(TelemetryReport {
+            extensions: data.extensions.clone(),
+            filesystem: data.filesystem,
+            messages: METRICS.get_messages().saturating_sub(data.messages_before),
+            recording_secs: data.started.elapsed().as_secs(),
+            slow_extensions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ExtensionHandler, FilesystemOperation, Telemetry, TelemetryConfig};
+    use crate::metrics::METRICS;
+
+    #[test]
+    fn flag_slow_extensions() {
+        let telemetry = Telemetry::new();
+        assert!(telemetry.get_report().is_none());
+
+        telemetry.enable(TelemetryConfig {
+            slow_handler_threshold: Duration::from_millis(50),
+            slow_handler_limit: 2,
+            broadcast_interval: None,
+        });
+
+        let slow = Duration::from_millis(80);
+        let fast = Duration::from_millis(1);
+        telemetry.record_handler("slowpoke", ExtensionHandler::Init, slow);
+        telemetry.record_handler("slowpoke", ExtensionHandler::Notify, fast);
+        telemetry.record_handler("slowpoke", ExtensionHandler::Notify, slow);
+        telemetry.record_handler("quick", ExtensionHandler::Notify, fast);
+
+        // Not in a row
+        let report = telemetry.get_report().unwrap();
+        assert!(report.slow_extensions.is_empty());
+        assert_eq!(report.extensions["slowpoke"].slow_calls, 2);
+        assert_eq!(report.extensions["slowpoke"].notify.calls, 2);
+        assert_eq!(report.extensions["slowpoke"].notify.max_micros, 80_000);
+
+        telemetry.record_handler("slowpoke", ExtensionHandler::Notify, slow);
+        telemetry.record_filesystem(FilesystemOperation::Read, fast);
+        METRICS.record_message();
+
+        let report = telemetry.get_report().unwrap();
+        assert_eq!(report.slow_extensions, vec!["slowpoke".to_owned()]);
+        assert_eq!(report.filesystem.read.calls, 1);
+        assert!(report.messages >= 1);
+
+        telemetry.disable();
+        assert!(!telemetry.is_enabled());
+    }
+}
@@ -22,6 +22,9 @@ pub trait TerminalShellBuilder {
     /// Retrieve Info about the shell
     fn get_info(&self) -> TerminalShellBuilderInfo;
 
-    /// Create an instance of the shell
-    fn build(&self, terminal_shell_id: &str) -> Box<dyn TerminalShell + Send + Sync>;
+    /// Create an instance of the shell, fails if the process could not be started
+    fn build(
+        &self,
+        terminal_shell_id: &str,
+    ) -> Result<Box<dyn TerminalShell + Send + Sync>, String>;
 }
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+/// An item of a tree view
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TreeItem {
+    pub id: String,
+    pub label: String,
+    pub description: Option<String>,
+    pub tooltip: Option<String>,
+    pub icon: Option<String>,
+    /// If it has children that can be loaded
+    pub collapsible: bool,
+    /// ID of the command to run when the item is clicked
+    pub command: Option<String>,
+}
+
+/// Information about a registered tree view
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TreeViewInfo {
+    pub id: String,
+    pub title: String,
+    pub extension_id: String,
+}
+
+/// Provides the items of a tree view, implemented by the extensions
+#[async_trait]
+pub trait TreeDataProvider {
+    /// Return the children of the given item, or the root items if there is no parent
+    async fn get_children(&self, parent_id: Option<String>) -> Vec<TreeItem>;
+}
+
+type TreeCache = HashMap<Option<String>, Vec<TreeItem>>;
+
+/// A tree view with its provider and the items loaded so far
+#[derive(Clone)]
+pub struct RegisteredTreeView {
+    pub info: TreeViewInfo,
+    provider: Arc<dyn TreeDataProvider + Send + Sync>,
+    cache: Arc<Mutex<TreeCache>>,
+}
+
+impl RegisteredTreeView {
+    /// Return the children of the given item, only asking the provider if they weren't loaded before
+    pub async fn get_children(&self, parent_id: Option<String>) -> Vec<TreeItem> {
+        if let Some(items) = self.cache.lock().await.get(&parent_id) {
+            return items.clone();
+        }
+
+        let items = self.provider.get_children(parent_id.clone()).await;
+        self.cache.lock().await.insert(parent_id, items.clone());
+        items
+    }
+
+    /// Forget the loaded children of the given item (and theirs), or everything if there is no item
+    pub async fn invalidate(&self, item_id: Option<String>) {
+        let mut cache = self.cache.lock().await;
+        if item_id.is_none() {
+            cache.clear();
+        } else {
+            invalidate_recursively(&mut cache, item_id);
+        }
+    }
+}
+
+fn invalidate_recursively(cache: &mut TreeCache, item_id: Option<String>) {
+    if let Some(items) = cache.remove(&item_id) {
+        for item in items {
+            invalidate_recursively(cache, Some(item.id));
+        }
+    }
+}
+
+/// Tree views contributed by extensions
+#[derive(Clone, Default)]
+pub struct TreeViewRegistry {
+    views: HashMap<String, RegisteredTreeView>,
+}
+
+impl TreeViewRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a tree view, replacing any other with the same ID
+    pub fn register(
+        &mut self,
+        info: TreeViewInfo,
+        provider: Arc<dyn TreeDataProvider + Send + Sync>,
+    ) {
+        self.views.insert(
+            info.id.clone(),
+            RegisteredTreeView {
+                info,
+                provider,
+                cache: Arc::new(Mutex::new(HashMap::new())),
+            },
+        );
+    }
+
+    pub fn unregister(&mut self, view_id: &str) -> Option<RegisteredTreeView> {
+        self.views.remove(view_id)
+    }
+
+    /// Retrieve a tree view, the State doesn't need to be kept locked while using it
+    pub fn get_view(&self, view_id: &str) -> Option<RegisteredTreeView> {
+        self.views.get(view_id).cloned()
+    }
+
+    /// Forget the loaded items of all the tree views
+    pub async fn invalidate_all(&self) {
+        for view in self.views.values() {
+            view.invalidate(None).await;
+        }
+    }
+
+    /// Return the info about all the tree views
+    pub fn get_views(&self) -> Vec<TreeViewInfo> {
+        self.views.values().map(|view| view.info.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+
+    use super::{TreeDataProvider, TreeItem, TreeViewInfo, TreeViewRegistry};
+
+    struct CountingProvider(AtomicUsize);
+
+    #[async_trait]
+    impl TreeDataProvider for CountingProvider {
+        async fn get_children(&self, parent_id: Option<String>) -> Vec<TreeItem> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            vec![TreeItem {
+                id: format!("{}/child", parent_id.unwrap_or_default()),
+                label: "child".to_string(),
+                description: None,
+                tooltip: None,
+                icon: None,
+                collapsible: true,
+                command: None,
+            }]
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_children() {
+        let provider = Arc::new(CountingProvider(AtomicUsize::new(0)));
+        let mut registry = TreeViewRegistry::new();
+        registry.register(
+            TreeViewInfo {
+                id: "tests".to_string(),
+                title: "Tests".to_string(),
+                extension_id: "sample".to_string(),
+            },
+            provider.clone(),
+        );
+
+        let view = registry.get_view("tests").unwrap();
+        let root = view.get_children(None).await;
+        view.get_children(None).await;
+        view.get_children(Some(root[0].id.clone())).await;
+
+        assert_eq!(provider.0.load(Ordering::SeqCst), 2);
+
+        // Invalidating the root also invalidates its children
+        view.invalidate(Some(root[0].id.clone())).await;
+        view.get_children(Some(root[0].id.clone())).await;
+
+        assert_eq!(provider.0.load(Ordering::SeqCst), 3);
+    }
+}
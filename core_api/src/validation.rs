@@ -0,0 +1,683 @@
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::documents::{Position, TextRange};
+
+/// How deep `$ref`s are followed, recursive schemas stop here
+const MAX_DEPTH: usize = 32;
+
+/// Formats of the configuration files that can be validated
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Guess the format by the extension of the file, e.g `Cargo.toml`
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = path.rsplit_once('.')?.1.to_lowercase();
+        match extension.as_str() {
+            "json" | "jsonc" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Parse the content, the error has the position where the parsing failed
+    fn parse(&self, content: &str) -> Result<Value, (Position, String)> {
+        match self {
+            Self::Json => serde_json::from_str(content).map_err(|err| {
+                let position =
+                    Position::new(err.line().saturating_sub(1), err.column().saturating_sub(1));
+                (position, err.to_string())
+            }),
+            Self::Toml => toml::from_str(content).map_err(|err| {
+                let position = err
+                    .line_col()
+                    .map(|(line, column)| Position::new(line, column))
+                    .unwrap_or_default();
+                (position, err.to_string())
+            }),
+            Self::Yaml => {
+                // An empty document is valid, but it's not a value
+                if content.trim().is_empty() {
+                    return Ok(Value::Null);
+                }
+                serde_yaml::from_str(content).map_err(|err| {
+                    let position = err
+                        .location()
+                        .map(|location| {
+                            Position::new(
+                                location.line().saturating_sub(1),
+                                location.column().saturating_sub(1),
+                            )
+                        })
+                        .unwrap_or_default();
+                    (position, err.to_string())
+                })
+            }
+        }
+    }
+
+    /// Pattern finding a key in the content, the key is in the `key` group
+    fn key_pattern(&self, key: &str) -> Regex {
+        let key = regex::escape(key);
+        let pattern = match self {
+            Self::Json => format!(r#""(?P<key>{})"\s*:"#, key),
+            // `[table]`, `[parent.table]`, `key = `, `parent.key = ` and `{ key = `
+            Self::Toml => format!(
+                r#"(?m)(?:^[ \t]*(?:\[\[?[ \t]*)?|[.{{,][ \t]*)"?(?P<key>{})"?[ \t]*[=\].]"#,
+                key
+            ),
+            // `key:`, `- key:` and `{ key: `
+            Self::Yaml => format!(
+                r#"(?m)(?:^[ \t]*(?:-[ \t]+)*|[{{,][ \t]*)["']?(?P<key>{})["']?[ \t]*:"#,
+                key
+            ),
+        };
+        Regex::new(&pattern).expect("The keys are escaped")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+}
+
+/// A problem found in a file, e.g a missing property
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub range: TextRange,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    /// What found it, e.g the name of the schema
+    pub source: String,
+}
+
+/// A JSON schema and the files it applies to, like the mappings of schemastore.org
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ValidationSchema {
+    /// e.g `Cargo manifest`
+    pub name: String,
+    /// Globs of the files, the ones without a slash match the file name, e.g `Cargo.toml`
+    /// or `.github/workflows/*.yml`
+    pub file_match: Vec<String>,
+    pub schema: Value,
+}
+
+impl ValidationSchema {
+    pub fn new(name: &str, file_match: &[&str], schema: Value) -> Self {
+        Self {
+            name: name.to_owned(),
+            file_match: file_match.iter().map(|glob| glob.to_string()).collect(),
+            schema,
+        }
+    }
+
+    /// Check the content of a file, a file that can't be parsed has a single diagnostic
+    pub fn validate(&self, format: ConfigFormat, content: &str) -> Vec<Diagnostic> {
+        let value = match format.parse(content) {
+            Ok(value) => value,
+            Err((position, message)) => {
+                return vec![Diagnostic {
+                    range: TextRange::new(position, position),
+                    severity: DiagnosticSeverity::Error,
+                    message,
+                    source: self.name.clone(),
+                }]
+            }
+        };
+
+        let mut validator = Validator {
+            root: &self.schema,
+            errors: Vec::new(),
+        };
+        validator.validate(&value, &self.schema, &mut Vec::new(), 0);
+
+        let mut diagnostics = validator
+            .errors
+            .into_iter()
+            .map(|error| Diagnostic {
+                range: locate(format, content, &error.path),
+                severity: error.severity,
+                message: error.message,
+                source: self.name.clone(),
+            })
+            .collect::<Vec<Diagnostic>>();
+        diagnostics.sort_by_key(|diagnostic| {
+            (
+                diagnostic.range.start.line,
+                diagnostic.range.start.character,
+            )
+        });
+
+        diagnostics
+    }
+}
+
+/// Build the matcher of a glob of a [`ValidationSchema`]
+fn get_matcher(glob: &str) -> Option<GlobMatcher> {
+    let glob = match glob.trim_start_matches("./") {
+        glob if !glob.contains('/') || glob.starts_with("**/") || glob.starts_with('/') => {
+            glob.to_owned()
+        }
+        glob => format!("**/{}", glob),
+    };
+    GlobBuilder::new(&glob)
+        .literal_separator(true)
+        .build()
+        .ok()
+        .map(|glob| glob.compile_matcher())
+}
+
+/// Schemas contributed by the extensions and the clients
+#[derive(Clone, Debug, Default)]
+pub struct ValidationSchemas {
+    /// Schemas with the extension (or client) contributing them
+    schemas: Vec<(String, ValidationSchema)>,
+}
+
+impl ValidationSchemas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the schemas of an extension, the ones with the same name are replaced
+    pub fn register(&mut self, extension_id: &str, schemas: Vec<ValidationSchema>) {
+        for schema in schemas {
+            self.schemas
+                .retain(|(id, s)| id != extension_id || s.name != schema.name);
+            self.schemas.push((extension_id.to_owned(), schema));
+        }
+    }
+
+    pub fn unregister(&mut self, extension_id: &str) {
+        self.schemas.retain(|(id, _)| id != extension_id);
+    }
+
+    pub fn get_all(&self) -> Vec<ValidationSchema> {
+        self.schemas
+            .iter()
+            .map(|(_, schema)| schema.clone())
+            .collect()
+    }
+
+    /// The schema of a file, the last registered wins
+    pub fn find(&self, path: &str) -> Option<&ValidationSchema> {
+        let path = path.replace('\\', "/");
+        let file_name = path.rsplit('/').next().unwrap_or_default();
+
+        self.schemas
+            .iter()
+            .rev()
+            .map(|(_, schema)| schema)
+            .find(|schema| {
+                schema.file_match.iter().any(|glob| {
+                    get_matcher(glob).is_some_and(|matcher| {
+                        if glob.contains('/') {
+                            matcher.is_match(&path)
+                        } else {
+                            matcher.is_match(file_name)
+                        }
+                    })
+                })
+            })
+    }
+
+    /// Validate a file with it's schema, none if there isn't any or the format is not supported
+    pub fn validate(&self, path: &str, content: &str) -> Option<Vec<Diagnostic>> {
+        let format = ConfigFormat::from_path(path)?;
+        let schema = self.find(path)?;
+        Some(schema.validate(format, content))
+    }
+}
+
+struct ValidationError {
+    /// Keys (and indexes) of the invalid value
+    path: Vec<String>,
+    severity: DiagnosticSeverity,
+    message: String,
+}
+
+/// Validates values with a subset of JSON Schema, enough for most of the configuration files
+struct Validator<'a> {
+    root: &'a Value,
+    errors: Vec<ValidationError>,
+}
+
+fn type_matches(value: &Value, expected: &str) -> bool {
+    match expected {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+impl Validator<'_> {
+    fn error(&mut self, path: &[String], message: String) {
+        self.errors.push(ValidationError {
+            path: path.to_vec(),
+            severity: DiagnosticSeverity::Error,
+            message,
+        });
+    }
+
+    /// Check if a value follows a schema without reporting anything
+    fn matches(&self, value: &Value, schema: &Value, depth: usize) -> bool {
+        let mut validator = Validator {
+            root: self.root,
+            errors: Vec::new(),
+        };
+        validator.validate(value, schema, &mut Vec::new(), depth);
+        validator.errors.is_empty()
+    }
+
+    fn validate(&mut self, value: &Value, schema: &Value, path: &mut Vec<String>, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => {
+                self.error(path, "Not allowed".to_owned());
+                return;
+            }
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+
+        // Only local references are followed, e.g `#/definitions/dependency`
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            if let Some(referenced) = reference
+                .strip_prefix('#')
+                .and_then(|pointer| self.root.pointer(pointer))
+            {
+                self.validate(value, referenced, path, depth + 1);
+            }
+        }
+
+        if let Some(expected) = schema.get("type") {
+            let types = match expected {
+                Value::String(expected) => vec![expected.as_str()],
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !types.is_empty() && !types.iter().any(|t| type_matches(value, t)) {
+                self.error(
+                    path,
+                    format!("Incorrect type, expected {}", types.join(" or ")),
+                );
+                return;
+            }
+        }
+
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+            if !allowed.contains(value) {
+                let allowed = allowed
+                    .iter()
+                    .map(Value::to_string)
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                self.error(
+                    path,
+                    format!("Value is not accepted, valid values: {}", allowed),
+                );
+            }
+        }
+
+        if let Some(constant) = schema.get("const") {
+            if constant != value {
+                self.error(path, format!("Value must be {}", constant));
+            }
+        }
+
+        for sub_schema in schema
+            .get("allOf")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            self.validate(value, sub_schema, path, depth + 1);
+        }
+
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(sub_schemas) = schema.get(keyword).and_then(Value::as_array) {
+                if !sub_schemas
+                    .iter()
+                    .any(|sub_schema| self.matches(value, sub_schema, depth + 1))
+                {
+                    self.error(
+                        path,
+                        "Value doesn't match any of the allowed schemas".to_owned(),
+                    );
+                }
+            }
+        }
+
+        match value {
+            Value::String(text) => self.validate_string(text, schema, path),
+            Value::Number(number) => {
+                if let Some(number) = number.as_f64() {
+                    self.validate_number(number, schema, path)
+                }
+            }
+            Value::Array(items) => self.validate_array(items, schema, path, depth),
+            Value::Object(properties) => self.validate_object(properties, schema, path, depth),
+            _ => {}
+        }
+    }
+
+    fn validate_string(
+        &mut self,
+        text: &str,
+        schema: &serde_json::Map<String, Value>,
+        path: &[String],
+    ) {
+        let len = text.chars().count() as u64;
+        if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+            if len < min {
+                self.error(path, format!("String is shorter than {} characters", min));
+            }
+        }
+        if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+            if len > max {
+                self.error(path, format!("String is longer than {} characters", max));
+            }
+        }
+        if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+            // Patterns this engine doesn't support are ignored
+            if let Ok(regex) = Regex::new(pattern) {
+                if !regex.is_match(text) {
+                    self.error(
+                        path,
+                        format!("String doesn't match the pattern \"{}\"", pattern),
+                    );
+                }
+            }
+        }
+    }
+
+    fn validate_number(
+        &mut self,
+        number: f64,
+        schema: &serde_json::Map<String, Value>,
+        path: &[String],
+    ) {
+        let limit = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+
+        if let Some(minimum) = limit("minimum") {
+            if number < minimum {
+                self.error(path, format!("Value is below the minimum of {}", minimum));
+            }
+        }
+        if let Some(maximum) = limit("maximum") {
+            if number > maximum {
+                self.error(path, format!("Value is above the maximum of {}", maximum));
+            }
+        }
+        if let Some(minimum) = limit("exclusiveMinimum") {
+            if number <= minimum {
+                self.error(path, format!("Value must be greater than {}", minimum));
+            }
+        }
+        if let Some(maximum) = limit("exclusiveMaximum") {
+            if number >= maximum {
+                self.error(path, format!("Value must be less than {}", maximum));
+            }
+        }
+    }
+
+    fn validate_array(
+        &mut self,
+        items: &[Value],
+        schema: &serde_json::Map<String, Value>,
+        path: &mut Vec<String>,
+        depth: usize,
+    ) {
+        let len = items.len() as u64;
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if len < min {
+                self.error(path, format!("Array has fewer than {} items", min));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if len > max {
+                self.error(path, format!("Array has more than {} items", max));
+            }
+        }
+
+        let items_schema = match schema.get("items") {
+            Some(items_schema) => items_schema,
+            None => return,
+        };
+        for (index, item) in items.iter().enumerate() {
+            // A list of schemas validates the items by their position
+            let item_schema = match items_schema {
+                Value::Array(schemas) => match schemas.get(index) {
+                    Some(item_schema) => item_schema,
+                    None => continue,
+                },
+                item_schema => item_schema,
+            };
+            path.push(index.to_string());
+            self.validate(item, item_schema, path, depth + 1);
+            path.pop();
+        }
+    }
+
+    fn validate_object(
+        &mut self,
+        properties: &serde_json::Map<String, Value>,
+        schema: &serde_json::Map<String, Value>,
+        path: &mut Vec<String>,
+        depth: usize,
+    ) {
+        for required in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !properties.contains_key(required) {
+                self.error(path, format!("Missing property \"{}\"", required));
+            }
+        }
+
+        let known = schema.get("properties").and_then(Value::as_object);
+        let patterns = schema
+            .get("patternProperties")
+            .and_then(Value::as_object)
+            .map(|patterns| {
+                patterns
+                    .iter()
+                    .filter_map(|(pattern, schema)| Some((Regex::new(pattern).ok()?, schema)))
+                    .collect::<Vec<(Regex, &Value)>>()
+            })
+            .unwrap_or_default();
+        let additional = schema.get("additionalProperties");
+
+        for (key, property) in properties {
+            path.push(key.clone());
+
+            let mut described = false;
+            if let Some(property_schema) = known.and_then(|known| known.get(key)) {
+                described = true;
+                self.validate(property, property_schema, path, depth + 1);
+            }
+            for (pattern, property_schema) in &patterns {
+                if pattern.is_match(key) {
+                    described = true;
+                    self.validate(property, property_schema, path, depth + 1);
+                }
+            }
+
+            if !described {
+                match additional {
+                    // Unknown properties are usually typos, or options of newer versions
+                    Some(Value::Bool(false)) => self.errors.push(ValidationError {
+                        path: path.clone(),
+                        severity: DiagnosticSeverity::Warning,
+                        message: format!("Property \"{}\" is not allowed", key),
+                    }),
+                    Some(additional) => self.validate(property, additional, path, depth + 1),
+                    None => {}
+                }
+            }
+
+            path.pop();
+        }
+    }
+}
+
+/// Convert a byte offset of the content into a position
+fn get_position(content: &str, offset: usize) -> Position {
+    let before = &content[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map(|index| index + 1).unwrap_or(0);
+    Position::new(line, before[line_start..].chars().count())
+}
+
+/// Find the key of the deepest value of the path that is in the content,
+/// the start of the content if none is found. Indexes of arrays are skipped
+fn locate(format: ConfigFormat, content: &str, path: &[String]) -> TextRange {
+    let mut offset = 0;
+    let mut range = TextRange::default();
+
+    for key in path {
+        if key.parse::<usize>().is_ok() {
+            continue;
+        }
+        let found = format
+            .key_pattern(key)
+            .captures(&content[offset..])
+            .and_then(|captures| captures.name("key"));
+        match found {
+            Some(found) => {
+                let (start, end) = (offset + found.start(), offset + found.end());
+                range = TextRange::new(get_position(content, start), get_position(content, end));
+                offset = end;
+            }
+            None => break,
+        }
+    }
+
+    range
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{ConfigFormat, DiagnosticSeverity, ValidationSchema, ValidationSchemas};
+    use crate::documents::{Position, TextRange};
+
+    fn cargo_schema() -> ValidationSchema {
+        ValidationSchema::new(
+            "Cargo manifest",
+            &["Cargo.toml"],
+            json!({
+                "type": "object",
+                "properties": {
+                    "package": {
+                        "type": "object",
+                        "required": ["name"],
+                        "properties": {
+                            "name": { "type": "string" },
+                            "edition": { "enum": ["2015", "2018", "2021"] }
+                        },
+                        "additionalProperties": false
+                    },
+                    "dependencies": {
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/definitions/dependency" }
+                    }
+                },
+                "definitions": {
+                    "dependency": {
+                        "anyOf": [
+                            { "type": "string" },
+                            { "type": "object", "properties": { "version": { "type": "string" } } }
+                        ]
+                    }
+                }
+            }),
+        )
+    }
+
+    #[test]
+    fn validate_config_files() {
+        let mut schemas = ValidationSchemas::new();
+        schemas.register("rust", vec![cargo_schema()]);
+        schemas.register(
+            "github",
+            vec![ValidationSchema::new(
+                "GitHub workflow",
+                &[".github/workflows/*.yml"],
+                json!({ "type": "object", "required": ["on", "jobs"] }),
+            )],
+        );
+
+        assert!(schemas.find("/home/user/app/Cargo.toml").is_some());
+        assert!(schemas.find("/home/user/app/Cargo.lock").is_none());
+        assert!(schemas.find("/app/.github/workflows/ci.yml").is_some());
+        assert!(schemas.find("/app/workflows/ci.yml").is_none());
+
+        let manifest = "[package]\nedition = \"2020\"\nlicence = \"MIT\"\n\n[dependencies]\nserde = { version = 1 }\n";
+        let diagnostics = schemas.validate("/app/Cargo.toml", manifest).unwrap();
+        let messages = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            messages,
+            vec![
+                "Missing property \"name\"",
+                "Value is not accepted, valid values: \"2015\", \"2018\", \"2021\"",
+                "Property \"licence\" is not allowed",
+                "Value doesn't match any of the allowed schemas",
+            ]
+        );
+
+        // The ranges point to the keys
+        assert_eq!(
+            diagnostics[0].range,
+            TextRange::new(Position::new(0, 1), Position::new(0, 8))
+        );
+        assert_eq!(
+            diagnostics[1].range,
+            TextRange::new(Position::new(1, 0), Position::new(1, 7))
+        );
+        assert_eq!(diagnostics[2].severity, DiagnosticSeverity::Warning);
+        assert_eq!(diagnostics[3].range.start, Position::new(5, 0));
+
+        // YAML and syntax errors
+        let workflow = "name: CI\njobs:\n  build:\n    runs-on: ubuntu-latest\n";
+        let diagnostics = schemas
+            .validate("/app/.github/workflows/ci.yml", workflow)
+            .unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Missing property \"on\"");
+
+        let broken = cargo_schema().validate(ConfigFormat::Toml, "[package\nname = 1");
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].range.start.line, 0);
+
+        schemas.unregister("rust");
+        assert!(schemas.validate("/app/Cargo.toml", manifest).is_none());
+    }
+}
@@ -0,0 +1,150 @@
+use async_trait::async_trait;
+use tokio::process::Command;
+
+use super::{FileChange, FileStatus, RepositoryStatus, VcsErrors, VcsProvider};
+use crate::Errors;
+
+/// Git support for local folders, it uses the installed `git` executable
+#[derive(Default)]
+pub struct GitCli;
+
+impl GitCli {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn run(&self, path: &str, args: &[&str]) -> Result<String, Errors> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(path)
+            .output()
+            .await
+            .map_err(|_| Errors::Vcs(VcsErrors::CommandFailed))?;
+
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        } else if String::from_utf8_lossy(&output.stderr).contains("not a git repository") {
+            Err(Errors::Vcs(VcsErrors::RepositoryNotFound))
+        } else {
+            Err(Errors::Vcs(VcsErrors::CommandFailed))
+        }
+    }
+}
+
+/// Parse the branch header of `git status --porcelain --branch`
+fn parse_branch(header: &str) -> Option<String> {
+    let branch = header.strip_prefix("## ")?;
+
+    if branch.starts_with("HEAD (no branch)") {
+        return None;
+    }
+
+    let branch = branch
+        .strip_prefix("No commits yet on ")
+        .or_else(|| branch.strip_prefix("Initial commit on "))
+        .unwrap_or(branch);
+
+    let branch = branch.split("...").next()?;
+    let branch = branch.split(' ').next()?;
+
+    Some(branch.to_string())
+}
+
+fn parse_file_status(code: &str) -> FileStatus {
+    let mut chars = code.chars();
+    let index = chars.next().unwrap_or(' ');
+    let worktree = chars.next().unwrap_or(' ');
+
+    match (index, worktree) {
+        ('?', '?') => FileStatus::Untracked,
+        ('U', _) | (_, 'U') | ('A', 'A') | ('D', 'D') => FileStatus::Conflicted,
+        ('R', _) => FileStatus::Renamed,
+        ('A', _) => FileStatus::Added,
+        ('D', _) | (_, 'D') => FileStatus::Deleted,
+        _ => FileStatus::Modified,
+    }
+}
+
+/// Parse the output of `git status --porcelain --branch`
+fn parse_status(output: &str) -> RepositoryStatus {
+    let mut branch = None;
+    let mut files = Vec::new();
+
+    for line in output.lines() {
+        if line.starts_with("## ") {
+            branch = parse_branch(line);
+        } else if line.len() > 3 {
+            let (code, path) = line.split_at(3);
+
+            // Renamed files show both paths
+            let path = path.rsplit(" -> ").next().unwrap_or(path);
+
+            files.push(FileChange {
+                path: path.trim_matches('"').to_string(),
+                status: parse_file_status(code),
+            });
+        }
+    }
+
+    RepositoryStatus { branch, files }
+}
+
+#[async_trait]
+impl VcsProvider for GitCli {
+    async fn get_status(&self, path: &str) -> Result<RepositoryStatus, Errors> {
+        let output = self
+            .run(path, &["status", "--porcelain=v1", "--branch"])
+            .await?;
+        Ok(parse_status(&output))
+    }
+
+    async fn get_diff(&self, path: &str, file_path: &str) -> Result<String, Errors> {
+        self.run(path, &["diff", "HEAD", "--", file_path]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_status;
+    use crate::vcs::{FileChange, FileStatus};
+
+    #[test]
+    fn parse_porcelain_status() {
+        let status = parse_status(
+            "## main...origin/main [ahead 1]\n M src/lib.rs\nA  src/new.rs\nR  old.rs -> renamed.rs\n?? notes.txt\nUU conflict.rs\n",
+        );
+
+        assert_eq!(status.branch, Some("main".to_string()));
+        assert_eq!(
+            status.files,
+            vec![
+                FileChange {
+                    path: "src/lib.rs".to_string(),
+                    status: FileStatus::Modified
+                },
+                FileChange {
+                    path: "src/new.rs".to_string(),
+                    status: FileStatus::Added
+                },
+                FileChange {
+                    path: "renamed.rs".to_string(),
+                    status: FileStatus::Renamed
+                },
+                FileChange {
+                    path: "notes.txt".to_string(),
+                    status: FileStatus::Untracked
+                },
+                FileChange {
+                    path: "conflict.rs".to_string(),
+                    status: FileStatus::Conflicted
+                },
+            ]
+        );
+
+        let status = parse_status("## No commits yet on develop\n");
+        assert_eq!(status.branch, Some("develop".to_string()));
+
+        let status = parse_status("## HEAD (no branch)\n");
+        assert_eq!(status.branch, None);
+    }
+}
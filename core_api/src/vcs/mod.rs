@@ -0,0 +1,124 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::Errors;
+
+mod git;
+pub use git::GitCli;
+
+/// Version control errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum VcsErrors {
+    RepositoryNotFound,
+    ProviderNotFound,
+    CommandFailed,
+}
+
+/// State of a file in the repository
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum FileStatus {
+    Modified,
+    Added,
+    Deleted,
+    Renamed,
+    Untracked,
+    Conflicted,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// Path relative to the root of the repository
+    pub path: String,
+    pub status: FileStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RepositoryStatus {
+    /// Current branch, none if the HEAD is detached
+    pub branch: Option<String>,
+    pub files: Vec<FileChange>,
+}
+
+/// Version control support for the folders of a filesystem
+#[async_trait]
+pub trait VcsProvider {
+    /// Return the branch and the changed files of the repository containing the folder
+    async fn get_status(&self, path: &str) -> Result<RepositoryStatus, Errors>;
+
+    /// Return the unified diff of a file against the last commit
+    async fn get_diff(&self, path: &str, file_path: &str) -> Result<String, Errors>;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+
+    use super::{FileChange, FileStatus, RepositoryStatus, VcsErrors, VcsProvider};
+    use crate::Errors;
+
+    /// Provider of a single repository with a modified file
+    struct TestProvider;
+
+    #[async_trait]
+    impl VcsProvider for TestProvider {
+        async fn get_status(&self, path: &str) -> Result<RepositoryStatus, Errors> {
+            if path.starts_with("/project") {
+                Ok(RepositoryStatus {
+                    branch: Some("main".to_owned()),
+                    files: vec![FileChange {
+                        path: "src/main.rs".to_owned(),
+                        status: FileStatus::Modified,
+                    }],
+                })
+            } else {
+                Err(Errors::Vcs(VcsErrors::RepositoryNotFound))
+            }
+        }
+
+        async fn get_diff(&self, path: &str, file_path: &str) -> Result<String, Errors> {
+            self.get_status(path).await?;
+            Ok(format!("--- a/{0}\n+++ b/{0}\n", file_path))
+        }
+    }
+
+    #[tokio::test]
+    async fn dynamic_providers() {
+        let provider: Arc<dyn VcsProvider + Send + Sync> = Arc::new(TestProvider);
+
+        let status = provider.get_status("/project/src").await.unwrap();
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert_eq!(status.files[0].status, FileStatus::Modified);
+        assert!(provider
+            .get_diff("/project", "src/main.rs")
+            .await
+            .unwrap()
+            .starts_with("--- a/src/main.rs"));
+
+        assert!(matches!(
+            provider.get_diff("/other", "main.rs").await,
+            Err(Errors::Vcs(VcsErrors::RepositoryNotFound))
+        ));
+    }
+
+    #[test]
+    fn serialize_repository_status() {
+        // Detached HEADs have no branch
+        let status = RepositoryStatus {
+            branch: None,
+            files: vec![FileChange {
+                path: "notes.md".to_owned(),
+                status: FileStatus::Untracked,
+            }],
+        };
+
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            serde_json::json!({
+                "branch": null,
+                "files": [{ "path": "notes.md", "status": "Untracked" }]
+            })
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::filesystems::{EolPolicy, Filesystem};
+use crate::tasks::TaskDefinition;
+
+/// Folder inside a workspace where its configuration is kept
+pub const CONFIG_FOLDER: &str = ".graviton";
+
+/// Configuration files looked for, in order
+const CONFIG_FILES: [&str; 2] = ["config.toml", "config.json"];
+
+/// Workspaces errors
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum WorkspaceErrors {
+    WorkspaceNotFound,
+    BadConfig(String),
+}
+
+/// Settings of a project, read from `.graviton/config.toml` (or `.json`) in its root folder
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct WorkspaceConfig {
+    /// Globs of the paths hidden from the explorer and the searches
+    pub excluded: Vec<String>,
+    /// Language server configuration used for each language, by language ID
+    pub language_servers: HashMap<String, String>,
+    /// Tasks that can be run, by ID
+    pub tasks: HashMap<String, TaskDefinition>,
+    /// How line endings are handled when saving files
+    pub eol_policy: Option<EolPolicy>,
+    /// Files nested under others in the explorer, child patterns by parent pattern,
+    /// e.g `"*.js" = ["$(capture).js.map"]`
+    pub file_nesting: HashMap<String, Vec<String>>,
+}
+
+impl WorkspaceConfig {
+    /// Parse a configuration file, TOML unless it's a JSON file
+    pub fn parse(path: &str, content: &str) -> Result<Self, WorkspaceErrors> {
+        if path.ends_with(".json") {
+            serde_json::from_str(content).map_err(|err| WorkspaceErrors::BadConfig(err.to_string()))
+        } else {
+            toml::from_str(content).map_err(|err| WorkspaceErrors::BadConfig(err.to_string()))
+        }
+    }
+
+    /// Apply a workspace's configuration on top of this one, the workspace wins
+    pub fn merge(&self, workspace: &WorkspaceConfig) -> WorkspaceConfig {
+        let mut config = self.clone();
+
+        for glob in &workspace.excluded {
+            if !config.excluded.contains(glob) {
+                config.excluded.push(glob.clone());
+            }
+        }
+        config.language_servers.extend(
+            workspace
+                .language_servers
+                .iter()
+                .map(|(language, server)| (language.clone(), server.clone())),
+        );
+        config.tasks.extend(
+            workspace
+                .tasks
+                .iter()
+                .map(|(id, task)| (id.clone(), task.clone())),
+        );
+        config.eol_policy = workspace.eol_policy.or(self.eol_policy);
+        config.file_nesting.extend(
+            workspace
+                .file_nesting
+                .iter()
+                .map(|(parent, children)| (parent.clone(), children.clone())),
+        );
+
+        config
+    }
+}
+
+/// A folder opened in a State
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Workspace {
+    pub filesystem: String,
+    pub root: String,
+    /// Path of the configuration file, if there is one
+    pub config_path: Option<String>,
+    /// Configuration of the workspace alone, without the global settings
+    pub config: WorkspaceConfig,
+    /// Anyone able to write the workspace's files can change its configuration,
+    /// so its tasks are only registered once the owner trusts it
+    #[serde(default)]
+    pub trusted: bool,
+}
+
+impl Workspace {
+    pub fn new(filesystem: &str, root: &str) -> Self {
+        Self {
+            filesystem: filesystem.to_owned(),
+            root: root.to_owned(),
+            config_path: None,
+            config: WorkspaceConfig::default(),
+            trusted: false,
+        }
+    }
+
+    /// Identifies the workspace inside a State
+    pub fn get_id(&self) -> String {
+        get_workspace_id(&self.filesystem, &self.root)
+    }
+}
+
+pub fn get_workspace_id(filesystem: &str, root: &str) -> String {
+    format!("{}:{}", filesystem, root)
+}
+
+/// ID of a workspace task in the task runner, so it can't replace the tasks of others
+pub fn get_workspace_task_id(filesystem: &str, root: &str, task_id: &str) -> String {
+    format!("{}#{}", get_workspace_id(filesystem, root), task_id)
+}
+
+/// Look for the configuration file of a workspace, returns its path and content
+pub async fn find_config(
+    filesystem: &Arc<Mutex<Box<dyn Filesystem + Send>>>,
+    root: &str,
+) -> Option<(String, String)> {
+    let filesystem = filesystem.lock().await;
+    let root = root.trim_end_matches(['/', '\\']);
+
+    for file in CONFIG_FILES {
+        let path = format!("{}/{}/{}", root, CONFIG_FOLDER, file);
+        if let Ok(file) = filesystem.read_file_by_path(&path).await {
+            return Some((path, file.content));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WorkspaceConfig;
+    use crate::filesystems::EolPolicy;
+
+    #[test]
+    fn parse_and_merge_configs() {
+        let global = WorkspaceConfig {
+            excluded: vec!["**/node_modules".to_owned()],
+            eol_policy: Some(EolPolicy::ForceLf),
+            ..WorkspaceConfig::default()
+        };
+
+        let workspace = WorkspaceConfig::parse(
+            ".graviton/config.toml",
+            r#"
+            excluded = ["target"]
+
+            [language_servers]
+            rust = "rust-analyzer"
+
+            [tasks.build]
+            command = "cargo"
+            args = ["build"]
+
+            [file_nesting]
+            "Cargo.toml" = ["Cargo.lock"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(workspace.tasks["build"].args, vec!["build".to_owned()]);
+
+        let config = global.merge(&workspace);
+        assert_eq!(config.excluded, vec!["**/node_modules", "target"]);
+        assert_eq!(config.language_servers["rust"], "rust-analyzer");
+        assert_eq!(config.eol_policy, Some(EolPolicy::ForceLf));
+        assert_eq!(config.file_nesting["Cargo.toml"], vec!["Cargo.lock"]);
+
+        let workspace =
+            WorkspaceConfig::parse(".graviton/config.json", r#"{ "eol_policy": "Preserve" }"#)
+                .unwrap();
+        assert_eq!(
+            global.merge(&workspace).eol_policy,
+            Some(EolPolicy::Preserve)
+        );
+
+        assert!(WorkspaceConfig::parse(".graviton/config.toml", "excluded = 1").is_err());
+    }
+}
@@ -4,6 +4,7 @@ use events_manager::EventsManager;
 use gveditor_core_api::extensions::base::{Extension, ExtensionInfo};
 use gveditor_core_api::extensions::client::ExtensionClient;
 use gveditor_core_api::extensions::manager::{ExtensionsManager, LoadedExtension};
+use gveditor_core_api::extensions::subscriptions::MessageFilter;
 use gveditor_core_api::messaging::ClientMessages;
 use gveditor_core_api::{Manifest, ManifestInfo, Mutex, Sender, State};
 use std::collections::HashMap;
@@ -18,8 +19,12 @@ use uuid::Uuid;
 mod events_manager;
 mod exts;
 mod main_worker;
+mod registry;
 
-use main_worker::create_main_worker;
+use main_worker::{create_main_worker, follow_permissions};
+pub use registry::{
+    install_extension, install_extension_bundle, uninstall_extension, update_extension,
+};
 
 pub type EventListeners = Arc<Mutex<HashMap<String, HashMap<Uuid, Sender<ClientMessages>>>>>;
 pub type WorkerHandle = Arc<Mutex<Option<IsolateHandle>>>;
@@ -64,13 +69,22 @@ impl Extension for DenoExtension {
         std::thread::spawn(move || {
             let rt = Runtime::new().unwrap();
             rt.block_on(async move {
-                let res =
-                    create_main_worker(&main_path, client, events_manager.clone(), state_id).await;
+                let res = create_main_worker(
+                    &main_path,
+                    client.clone(),
+                    events_manager.clone(),
+                    state_id,
+                )
+                .await;
 
                 if let Err(err) = res {
                     error!("Could not load Deno extension <{}> , error: {}", name, err);
                 } else if let Ok(mut worker) = res {
-                    let res = worker.run_event_loop(false).await;
+                    let op_state = worker.js_runtime.op_state();
+                    let res = tokio::select! {
+                        res = worker.run_event_loop(false) => res,
+                        _ = follow_permissions(op_state, client) => Ok(()),
+                    };
                     if let Err(err) = res {
                         let msg = err.to_string();
                         if msg != "Uncaught Error: execution terminated" {
@@ -101,6 +115,10 @@ impl Extension for DenoExtension {
             id: self.info.extension.id.clone(),
         }
     }
+
+    fn get_message_filter(&self) -> MessageFilter {
+        self.info.subscriptions.clone()
+    }
 }
 
 /// Add support for a special method that allows core invokers to execute Deno extensions
@@ -112,6 +130,11 @@ pub trait DenoExtensionSupport {
         info: ManifestInfo,
         state_id: u8,
     ) -> &mut ExtensionsManager;
+    fn load_extension_from_manifest(
+        &mut self,
+        manifest: &Manifest,
+        state_id: u8,
+    ) -> &mut ExtensionsManager;
     async fn load_extensions_with_deno_in_directory(
         &mut self,
         path: &str,
@@ -127,12 +150,16 @@ impl DenoExtensionSupport for ExtensionsManager {
         info: ManifestInfo,
         state_id: u8,
     ) -> &mut ExtensionsManager {
+        self.permissions
+            .set(&info.extension.id, info.permissions.clone());
         let client = ExtensionClient::new(
             &info.extension.id.clone(),
             &info.extension.name.clone(),
             self.sender.clone(),
             self.settings_path.clone(),
-        );
+        )
+        .with_permissions(self.permissions.clone())
+        .with_progress(self.progress.clone());
         let events_manager = EventsManager::new();
         let deno_extension = Box::new(DenoExtension::new(
             path,
@@ -142,8 +169,35 @@ impl DenoExtensionSupport for ExtensionsManager {
             events_manager,
         ));
         self.register(&info.extension.id, deno_extension);
-        self.extensions
-            .push(LoadedExtension::ManifestBuiltin { info });
+        self.push(LoadedExtension::ManifestBuiltin { info });
+        self
+    }
+
+    fn load_extension_from_manifest(
+        &mut self,
+        manifest: &Manifest,
+        state_id: u8,
+    ) -> &mut ExtensionsManager {
+        // Load it's entry file if specified
+        if let Some(main) = &manifest.info.extension.main {
+            let main_path = manifest.location.parent().unwrap().join(main);
+
+            self.load_extension_with_deno(
+                main_path.to_str().unwrap(),
+                manifest.info.clone(),
+                state_id,
+            );
+            self.set_source(manifest, |manager, manifest, state_id| {
+                manager.load_extension_from_manifest(manifest, state_id);
+            });
+        } else {
+            tracing::error!(
+                "Could not register Deno Extension <{}> from {}",
+                manifest.info.extension.name,
+                manifest.location.to_str().unwrap()
+            );
+            // Doesn't have an entry file
+        }
         self
     }
 
@@ -166,23 +220,7 @@ impl DenoExtensionSupport for ExtensionsManager {
                 let manifest = Manifest::parse(&manifest_path).await;
 
                 if let Ok(manifest) = manifest {
-                    // Load it's entry file if specified
-                    if let Some(main) = &manifest.info.extension.main {
-                        let main_path = item_path.join(main);
-
-                        self.load_extension_with_deno(
-                            main_path.to_str().unwrap(),
-                            manifest.info.clone(),
-                            state_id,
-                        );
-                    } else {
-                        tracing::error!(
-                            "Could not register Deno Extension <{}> from {}",
-                            manifest.info.extension.name,
-                            manifest.location.to_str().unwrap()
-                        );
-                        // Doesn't have an entry file
-                    }
+                    self.load_extension_from_manifest(&manifest, state_id);
                 }
             }
         }
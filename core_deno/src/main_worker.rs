@@ -1,16 +1,19 @@
 use deno_core::error::AnyError;
-use deno_core::FsModuleLoader;
+use deno_core::{FsModuleLoader, OpState};
 use deno_runtime::deno_broadcast_channel::InMemoryBroadcastChannel;
 use deno_runtime::deno_web::BlobStore;
 use deno_runtime::ops::io::Stdio;
-use deno_runtime::permissions::Permissions;
+use deno_runtime::permissions::{Permissions, PermissionsOptions};
 use deno_runtime::worker::{MainWorker, WorkerOptions};
 use deno_runtime::BootstrapOptions;
 use gveditor_core_api::Mutex;
+use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::Arc;
 
 use gveditor_core_api::extensions::client::ExtensionClient;
+use gveditor_core_api::extensions::permissions::ExtensionPermissions;
+use std::path::PathBuf;
 
 use crate::events_manager::EventsManager;
 use crate::exts::{events, statusbar_items};
@@ -38,6 +41,9 @@ pub async fn create_main_worker(
 
     let main_module = deno_core::resolve_path(main_path).unwrap();
 
+    // Only allow what the extension was granted
+    let permissions_options = get_permissions_options(&client.get_permissions());
+
     let options = WorkerOptions {
         bootstrap: BootstrapOptions {
             args: vec![],
@@ -75,8 +81,7 @@ pub async fn create_main_worker(
         stdio: Stdio::default(),
     };
 
-    // TODO(marc2332) Add ability to specify what permissions the extension should run with
-    let permissions = Permissions::allow_all();
+    let permissions = Permissions::from_options(&permissions_options);
 
     let mut worker = MainWorker::bootstrap_from_options(main_module.clone(), permissions, options);
 
@@ -93,6 +98,48 @@ pub async fn create_main_worker(
     Ok(worker)
 }
 
+/// Apply the permissions granted or revoked while the worker is running, it never returns
+pub async fn follow_permissions(op_state: Rc<RefCell<OpState>>, client: ExtensionClient) {
+    if let Some(mut changes) = client.subscribe_permissions() {
+        while changes.changed().await.is_ok() {
+            let permissions_options = get_permissions_options(&client.get_permissions());
+            op_state
+                .borrow_mut()
+                .put(Permissions::from_options(&permissions_options));
+        }
+    }
+    std::future::pending::<()>().await;
+}
+
+/// Translate the extension permissions into Deno permissions, an empty list means no access
+fn get_permissions_options(permissions: &ExtensionPermissions) -> PermissionsOptions {
+    let to_paths = |paths: &Vec<String>| {
+        if paths.is_empty() {
+            None
+        } else if paths.iter().any(|path| path == "*") {
+            Some(vec![])
+        } else {
+            Some(paths.iter().map(PathBuf::from).collect())
+        }
+    };
+
+    let net = if permissions.net.is_empty() {
+        None
+    } else if permissions.net.iter().any(|host| host == "*") {
+        Some(vec![])
+    } else {
+        Some(permissions.net.clone())
+    };
+
+    PermissionsOptions {
+        allow_read: to_paths(&permissions.read),
+        allow_write: to_paths(&permissions.write),
+        allow_run: if permissions.run { Some(vec![]) } else { None },
+        allow_net: net,
+        ..Default::default()
+    }
+}
+
 fn get_error_class_name(e: &AnyError) -> &'static str {
     deno_runtime::errors::get_error_class_name(e).unwrap_or("Error")
 }
@@ -0,0 +1,97 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use gveditor_core_api::extensions::bundles::{install_bundle, BundleOptions};
+use gveditor_core_api::extensions::registry::RegistryClient;
+use gveditor_core_api::{Errors, ExtensionErrors, Manifest, ManifestInfo, Mutex, State};
+
+use crate::DenoExtensionSupport;
+
+/// Replace any loaded version of the extension with the given one and run it
+async fn load_and_run(
+    state: Arc<Mutex<State>>,
+    manifest: Manifest,
+) -> Result<ManifestInfo, Errors> {
+    if manifest.info.extension.main.is_none() {
+        return Err(Errors::Ext(ExtensionErrors::BadPackage));
+    }
+
+    let state_handle = state.clone();
+    let mut state = state.lock().await;
+    let state_id = state.data.id;
+    let extension_id = manifest.info.extension.id.clone();
+
+    state.extensions_manager.unregister(&extension_id).await;
+    state
+        .extensions_manager
+        .load_extension_from_manifest(&manifest, state_id);
+    state.run_extension(&extension_id, state_handle).await?;
+
+    Ok(manifest.info)
+}
+
+/// Make the registry requests with the State's networking settings
+async fn use_state_http_client(
+    state: &Arc<Mutex<State>>,
+    registry: &RegistryClient,
+) -> RegistryClient {
+    let http_client = state.lock().await.get_http_client();
+    registry.clone().with_http_client(http_client)
+}
+
+/// Install an extension from a registry and run it in the State without restarting
+pub async fn install_extension(
+    state: Arc<Mutex<State>>,
+    registry: &RegistryClient,
+    extension_id: &str,
+) -> Result<ManifestInfo, Errors> {
+    let registry = use_state_http_client(&state, registry).await;
+    let manifest = registry.install(extension_id).await.map_err(Errors::Ext)?;
+    load_and_run(state, manifest).await
+}
+
+/// Update an extension if there is a new version, returns None if it was already updated
+pub async fn update_extension(
+    state: Arc<Mutex<State>>,
+    registry: &RegistryClient,
+    extension_id: &str,
+) -> Result<Option<ManifestInfo>, Errors> {
+    let registry = use_state_http_client(&state, registry).await;
+    let manifest = registry.update(extension_id).await.map_err(Errors::Ext)?;
+
+    if let Some(manifest) = manifest {
+        load_and_run(state, manifest).await.map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Install an extension from a local `.gvext` bundle and run it in the State without restarting,
+/// for machines that can't reach a registry
+pub async fn install_extension_bundle(
+    state: Arc<Mutex<State>>,
+    bundle_path: &Path,
+    extensions_path: &Path,
+    options: &BundleOptions,
+) -> Result<ManifestInfo, Errors> {
+    let manifest = install_bundle(bundle_path, extensions_path, options)
+        .await
+        .map_err(Errors::Ext)?;
+    load_and_run(state, manifest).await
+}
+
+/// Unload an extension from the State and remove its files
+pub async fn uninstall_extension(
+    state: Arc<Mutex<State>>,
+    registry: &RegistryClient,
+    extension_id: &str,
+) -> Result<(), Errors> {
+    state
+        .lock()
+        .await
+        .extensions_manager
+        .unregister(extension_id)
+        .await;
+
+    registry.uninstall(extension_id).await.map_err(Errors::Ext)
+}
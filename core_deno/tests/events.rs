@@ -16,7 +16,7 @@ async fn send_receive_events() {
     manager.load_extension_with_deno(location.to_str().unwrap(), ManifestInfo::default(), 0);
 
     // Load
-    if let LoadedExtension::ExtensionInstance { plugin, .. } = &manager.extensions[0] {
+    if let LoadedExtension::ExtensionInstance { plugin, .. } = &manager.get_all()[0] {
         let mut ext_plugin = plugin.lock().await;
         ext_plugin.init(Arc::new(Mutex::new(State::default())));
     }
@@ -25,7 +25,7 @@ async fn send_receive_events() {
     rv.recv().await;
 
     // Send some dumy event
-    if let LoadedExtension::ExtensionInstance { plugin, .. } = &manager.extensions[0] {
+    if let LoadedExtension::ExtensionInstance { plugin, .. } = &manager.get_all()[0] {
         let mut ext_plugin = plugin.lock().await;
         ext_plugin.notify(ClientMessages::ListDir(
             0,
@@ -39,7 +39,7 @@ async fn send_receive_events() {
     rv.recv().await;
 
     // Unload
-    if let LoadedExtension::ExtensionInstance { plugin, .. } = &manager.extensions[0] {
+    if let LoadedExtension::ExtensionInstance { plugin, .. } = &manager.get_all()[0] {
         let mut ext_plugin = plugin.lock().await;
         ext_plugin.unload();
     }
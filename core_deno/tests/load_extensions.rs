@@ -15,7 +15,7 @@ async fn load_sample_extension() {
 
     manager.load_extension_with_deno(location.to_str().unwrap(), ManifestInfo::default(), 0);
 
-    assert_eq!(manager.extensions.len(), 2);
+    assert_eq!(manager.get_all().len(), 2);
 }
 
 /// Loads the extension located under tests/extensions, executes it, and waits for a message sent from it
@@ -31,5 +31,5 @@ async fn load_extensions_from_folder() {
         .load_extensions_with_deno_in_directory(location, 0)
         .await;
 
-    assert_eq!(manager.extensions.len(), 2);
+    assert_eq!(manager.get_all().len(), 2);
 }
@@ -20,7 +20,7 @@ async fn show_hide_statusbar_item() {
     manager.load_extension_with_deno(location.to_str().unwrap(), ManifestInfo::default(), 0);
 
     // Load
-    if let LoadedExtension::ExtensionInstance { plugin, .. } = &manager.extensions[0] {
+    if let LoadedExtension::ExtensionInstance { plugin, .. } = &manager.get_all()[0] {
         let mut ext_plugin = plugin.lock().await;
         ext_plugin.init(Arc::new(Mutex::new(State::default())));
     }
@@ -43,7 +43,7 @@ async fn show_hide_statusbar_item() {
 
     // Simulate an onClick
     tokio::spawn(async move {
-        if let LoadedExtension::ExtensionInstance { plugin, .. } = &manager.extensions[0] {
+        if let LoadedExtension::ExtensionInstance { plugin, .. } = &manager.get_all()[0] {
             let mut ext_plugin = plugin.lock().await;
             ext_plugin.notify(ClientMessages::UIEvent(UIEvent::StatusBarItemClicked {
                 state_id: 0,
@@ -2,7 +2,7 @@ use tokio::sync::mpsc::Sender;
 
 use crate::Pty;
 
-#[cfg(any(target_os = "windows"))]
+#[cfg(target_os = "windows")]
 pub mod win;
 
 #[cfg(not(windows))]
@@ -14,10 +14,10 @@ pub fn new_pty(
     command: &str,
     args: Vec<&str>,
     sender: Sender<Vec<u8>>,
-) -> Box<dyn Pty + Send + Sync> {
-    #[cfg(any(target_os = "windows"))]
-    return Box::new(win::PtyWin::new(command, args, sender));
+) -> Result<Box<dyn Pty + Send + Sync>, String> {
+    #[cfg(target_os = "windows")]
+    return Ok(Box::new(win::PtyWin::new(command, args, sender)?));
 
     #[cfg(not(windows))]
-    return Box::new(unix::PtyUnix::new());
+    return Ok(Box::new(unix::PtyUnix::new(command, args, sender)?));
 }
@@ -1,22 +1,156 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
 use async_trait::async_trait;
+use tokio::sync::mpsc::Sender;
 
 use crate::Pty;
 
-pub struct PtyUnix {}
+pub struct PtyUnix {
+    master: Arc<Mutex<File>>,
+    child: Mutex<Child>,
+}
+
+fn map_io_error(err: std::io::Error) -> String {
+    err.to_string()
+}
+
+fn window_size(cols: i32, rows: i32) -> libc::winsize {
+    libc::winsize {
+        ws_row: rows as u16,
+        ws_col: cols as u16,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
+
+/// Don't let other processes spawned by the Core inherit the file descriptor
+fn set_cloexec(fd: RawFd) -> Result<(), String> {
+    let res = unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    if res == -1 {
+        Err(std::io::Error::last_os_error().to_string())
+    } else {
+        Ok(())
+    }
+}
 
 impl PtyUnix {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(command: &str, args: Vec<&str>, sender: Sender<Vec<u8>>) -> Result<Self, String> {
+        let mut master: RawFd = 0;
+        let mut slave: RawFd = 0;
+        let size = window_size(80, 25);
+
+        let res = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &size,
+            )
+        };
+
+        if res != 0 {
+            return Err(format!(
+                "Could not open a pty, error: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        let master = unsafe { File::from_raw_fd(master) };
+        let slave = unsafe { File::from_raw_fd(slave) };
+        set_cloexec(master.as_raw_fd())?;
+        set_cloexec(slave.as_raw_fd())?;
+
+        let clone_slave = || slave.try_clone().map(Stdio::from).map_err(map_io_error);
+
+        let mut cmd = Command::new(command);
+        cmd.args(args)
+            .env("TERM", "xterm-256color")
+            .stdin(clone_slave()?)
+            .stdout(clone_slave()?)
+            .stderr(clone_slave()?);
+
+        unsafe {
+            cmd.pre_exec(|| {
+                // Run in a new session with the pty as controlling terminal
+                if libc::setsid() == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                if libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        let child = cmd.spawn().map_err(map_io_error)?;
+
+        // Only the child process should keep the slave side open
+        drop(slave);
+
+        let mut reader = master.try_clone().map_err(map_io_error)?;
+        tokio::task::spawn_blocking(move || {
+            let mut buf = [0; 4096];
+            loop {
+                match reader.read(&mut buf) {
+                    // The process exited
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if sender.blocking_send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            master: Arc::new(Mutex::new(master)),
+            child: Mutex::new(child),
+        })
     }
 }
 
 #[async_trait]
 impl Pty for PtyUnix {
-    async fn write(&self, _data: &str) -> Result<(), String> {
-        Ok(())
+    async fn write(&self, data: &str) -> Result<(), String> {
+        let master = self.master.clone();
+        let data = data.to_owned();
+        tokio::task::spawn_blocking(move || {
+            let mut master = master.lock().unwrap();
+            master.write_all(data.as_bytes())
+        })
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| err.to_string())
     }
 
-    async fn resize(&self, (_cols, _rows): (i32, i32)) -> Result<(), String> {
-        Ok(())
+    async fn resize(&self, (cols, rows): (i32, i32)) -> Result<(), String> {
+        let master = self.master.lock().unwrap();
+        let size = window_size(cols, rows);
+        let res =
+            unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ as _, &size as *const _) };
+
+        if res == -1 {
+            Err(std::io::Error::last_os_error().to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for PtyUnix {
+    fn drop(&mut self) {
+        // Don't leave the process running once the pty is gone
+        if let Ok(mut child) = self.child.lock() {
+            child.kill().ok();
+            child.wait().ok();
+        }
     }
 }
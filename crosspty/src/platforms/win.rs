@@ -7,13 +7,17 @@ use winptyrs::{AgentConfig, MouseMode, PTYArgs, PTYBackend, PTY};
 
 use crate::Pty;
 
+fn map_pty_error(err: OsString) -> String {
+    err.to_string_lossy().to_string()
+}
+
 #[derive(Clone)]
 pub struct PtyWin {
     pty: Arc<PTY>,
 }
 
 impl PtyWin {
-    pub fn new(command: &str, _args: Vec<&str>, sender: Sender<Vec<u8>>) -> Self {
+    pub fn new(command: &str, _args: Vec<&str>, sender: Sender<Vec<u8>>) -> Result<Self, String> {
         let command = command.to_owned();
 
         let cmd = OsString::from(command);
@@ -26,9 +30,10 @@ impl PtyWin {
             agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES,
         };
 
-        let mut pty = PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).unwrap();
+        let mut pty =
+            PTY::new_with_backend(&pty_args, PTYBackend::ConPTY).map_err(map_pty_error)?;
 
-        pty.spawn(cmd, None, None, None).unwrap();
+        pty.spawn(cmd, None, None, None).map_err(map_pty_error)?;
 
         let pty = Arc::new(pty);
         {
@@ -54,7 +59,7 @@ impl PtyWin {
             });
         }
 
-        Self { pty }
+        Ok(Self { pty })
     }
 }
 
@@ -1,13 +1,32 @@
 use crosspty::platforms::new_pty;
 use tokio::sync::mpsc::channel;
 
-#[cfg(any(target_os = "windows"))]
+#[cfg(target_os = "windows")]
 #[tokio::test]
 async fn boots_up() {
     let (tx, mut rx) = channel::<Vec<u8>>(1);
-    let _pty = new_pty("powershell", vec![], tx);
+    let _pty = new_pty("powershell", vec![], tx).unwrap();
     let res = rx.recv().await.unwrap();
     let res = String::from_utf8_lossy(&res);
     assert!(res.contains("Windows PowerShell"));
     assert!(res.contains("https://aka.ms/PSWindows"));
 }
+
+#[cfg(unix)]
+#[tokio::test]
+async fn runs_commands() {
+    let (tx, mut rx) = channel::<Vec<u8>>(1);
+    let _pty = new_pty("sh", vec!["-c", "echo hello world"], tx).unwrap();
+    let mut output = String::new();
+    while let Some(res) = rx.recv().await {
+        output.push_str(&String::from_utf8_lossy(&res));
+    }
+    assert!(output.contains("hello world"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn fails_to_spawn_missing_commands() {
+    let (tx, _rx) = channel::<Vec<u8>>(1);
+    assert!(new_pty("graviton-missing-command", vec![], tx).is_err());
+}
@@ -7,9 +7,11 @@ mod methods;
 use gveditor_core::gen_client::Client;
 use gveditor_core::handlers::{LocalHandler, TransportHandler};
 use gveditor_core::tokio::sync::mpsc::{channel, Receiver, Sender};
-use gveditor_core::{tokio, Configuration, Server};
+use gveditor_core::{tokio, Configuration, Server, ShutdownCoordinator};
 use gveditor_core_api::extensions::manager::ExtensionsManager;
+use gveditor_core_api::logging::{LogLevel, Logger};
 use gveditor_core_api::messaging::{ClientMessages, ServerMessages};
+use gveditor_core_api::recovery::SessionRecovery;
 use gveditor_core_api::state_persistors::file::FilePersistor;
 use gveditor_core_api::states::{StatesList, TokenFlags};
 use gveditor_core_api::{Mutex, State};
@@ -18,12 +20,11 @@ use std::fs;
 use std::fs::File;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tauri::api::path::{resolve_path, BaseDirectory};
 use tauri::utils::assets::EmbeddedAssets;
-use tauri::{Context, Env, Manager};
+use tauri::{Context, Env, Manager, RunEvent};
 use tracing::{error, info, warn};
-use tracing_subscriber::prelude::__tracing_subscriber_SubscriberExt;
-use tracing_subscriber::{fmt, EnvFilter, Registry};
 
 #[cfg(any(target_os = "windows"))]
 use window_shadows::set_shadow;
@@ -38,6 +39,8 @@ fn open_window(
     client: Client,
     sender_to_handler: Sender<ClientMessages>,
     mut receiver_from_handler: Receiver<ServerMessages>,
+    session_recovery: Arc<SessionRecovery>,
+    shutdown_coordinator: ShutdownCoordinator,
 ) -> tauri::Result<()> {
     tauri::Builder::default()
         .setup(move |app| {
@@ -98,7 +101,18 @@ fn open_window(
             methods::create_language_server,
             methods::write_to_language_server
         ])
-        .run(context)
+        .build(context)?
+        .run(move |_, event| {
+            // Closing the app normally is a clean exit
+            if let RunEvent::Exit = event {
+                tokio::task::block_in_place(|| {
+                    tokio::runtime::Handle::current().block_on(shutdown_coordinator.run())
+                });
+                session_recovery.finish();
+            }
+        });
+
+    Ok(())
 }
 
 /// Returns the location in which where to save the settings and state
@@ -145,17 +159,37 @@ fn get_extensions_installation_path(context: &Context<EmbeddedAssets>) -> anyhow
     Ok(extensions_installation_path)
 }
 
-/// Setup the logger
-fn setup_logger() {
-    let filter = EnvFilter::default()
-        .add_directive("graviton=info".parse().unwrap())
-        .add_directive("gveditor_core_api=info".parse().unwrap())
-        .add_directive("gveditor_core=info".parse().unwrap())
-        .add_directive("typescript_lsp_graviton=info".parse().unwrap());
+/// Returns the path where the language servers downloaded by the Core are installed
+///
+/// # Arguments
+///
+/// * `context` - The Tauri Context
+///
+fn get_language_servers_installation_path(
+    context: &Context<EmbeddedAssets>,
+) -> anyhow::Result<PathBuf> {
+    let language_servers_installation_path = resolve_path(
+        context.config(),
+        context.package_info(),
+        &Env::default(),
+        ".graviton/language_servers",
+        Some(BaseDirectory::Home),
+    )?;
 
-    let subscriber = Registry::default().with(filter).with(fmt::Layer::default());
+    fs::create_dir_all(&language_servers_installation_path)?;
 
-    tracing::subscriber::set_global_default(subscriber).expect("Unable to set global subscriber");
+    Ok(language_servers_installation_path)
+}
+
+/// Setup the logger
+fn setup_logger() {
+    Logger::builder()
+        .level("graviton", LogLevel::Info)
+        .level("gveditor_core_api", LogLevel::Info)
+        .level("gveditor_core", LogLevel::Info)
+        .level("typescript_lsp_graviton", LogLevel::Info)
+        .init()
+        .expect("Unable to set global subscriber");
 }
 
 // Graviton Desktop is fully local therefore doesn't need authentication which the Core
@@ -220,13 +254,29 @@ async fn main() -> anyhow::Result<()> {
         )
         .await;
 
+    let language_servers_path = get_language_servers_installation_path(&context);
+
+    if let Err(err) = &language_servers_path {
+        error!("Could not get the language servers path, error: {err}");
+    }
+
+    let language_servers_path = language_servers_path?;
+
+    // Detect if the last session crashed
+    let session_recovery = Arc::new(SessionRecovery::start(settings_path.join("recovery"))?);
+
     // Create the StatesList
     let states = {
         let default_state = State::new(
             STATE_ID,
             extensions_manager,
-            Box::new(FilePersistor::new(settings_file_path)),
-        );
+            Box::new(
+                FilePersistor::new(settings_file_path)
+                    .with_blob_store(session_recovery.get_blob_store()),
+            ),
+        )
+        .with_language_servers_path(language_servers_path)
+        .with_session_recovery(session_recovery.clone());
         let states = StatesList::new()
             .with_tokens(&[TokenFlags::All(TOKEN.to_string())])
             .with_state(default_state);
@@ -241,14 +291,24 @@ async fn main() -> anyhow::Result<()> {
     let (local_handler, client, to_local) = LocalHandler::new(states.clone(), to_webview);
     let local_handler: Box<dyn TransportHandler + Send + Sync> = Box::new(local_handler);
 
-    let config = Configuration::new(local_handler, core_tx, core_rx);
+    let config = Configuration::new(local_handler, core_tx, core_rx)
+        .with_recovery_autosave(Duration::from_secs(30));
 
     let mut server = Server::new(config, states);
 
     server.run().await;
 
+    let shutdown_coordinator = server.get_shutdown_coordinator();
+
     // Open the window
-    let res = open_window(context, client, to_local, from_handler);
+    let res = open_window(
+        context,
+        client,
+        to_local,
+        from_handler,
+        session_recovery,
+        shutdown_coordinator,
+    );
 
     if let Err(err) = res {
         error!("Graviton crashed, error: {err}");
@@ -1,7 +1,7 @@
 use gveditor_core::RPCResult;
-use gveditor_core_api::filesystems::{DirItemInfo, FileInfo};
+use gveditor_core_api::filesystems::{DirItemInfo, FileEncoding, FileInfo};
 use gveditor_core_api::language_servers::LanguageServerBuilderInfo;
-use gveditor_core_api::states::StateData;
+use gveditor_core_api::states::{StateData, StateDataMerge, StateDataUpdate};
 use gveditor_core_api::terminal_shells::TerminalShellBuilderInfo;
 use gveditor_core_api::{Errors, ManifestInfo};
 
@@ -37,11 +37,13 @@ pub async fn read_file_by_path(
     filesystem_name: String,
     state_id: u8,
     token: String,
+    encoding: Option<FileEncoding>,
     tauri_state: tauri::State<'_, TauriState>,
 ) -> RPCResult<Result<FileInfo, Errors>> {
-    let res = tauri_state
-        .client
-        .read_file_by_path(path, filesystem_name, state_id, token);
+    let res =
+        tauri_state
+            .client
+            .read_file_by_path(path, filesystem_name, state_id, token, encoding);
     Ok(res.await.unwrap())
 }
 
@@ -52,22 +54,27 @@ pub async fn write_file_by_path(
     filesystem_name: String,
     state_id: u8,
     token: String,
+    encoding: Option<FileEncoding>,
     tauri_state: tauri::State<'_, TauriState>,
 ) -> RPCResult<Result<(), Errors>> {
-    let res =
-        tauri_state
-            .client
-            .write_file_by_path(path, content, filesystem_name, state_id, token);
+    let res = tauri_state.client.write_file_by_path(
+        path,
+        content,
+        filesystem_name,
+        state_id,
+        token,
+        encoding,
+    );
     Ok(res.await.unwrap())
 }
 
 #[tauri::command(async)]
 pub async fn set_state_by_id(
     state_id: u8,
-    state_data: StateData,
+    state_data: StateDataUpdate,
     token: String,
     tauri_state: tauri::State<'_, TauriState>,
-) -> RPCResult<Result<(), Errors>> {
+) -> RPCResult<Result<StateDataMerge, Errors>> {
     let res = tauri_state
         .client
         .set_state_by_id(state_id, state_data, token);
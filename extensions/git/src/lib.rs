@@ -5,6 +5,8 @@ use gveditor_core_api::extensions::base::{Extension, ExtensionInfo};
 use gveditor_core_api::extensions::client::ExtensionClient;
 use gveditor_core_api::extensions::manager::ExtensionsManager;
 use gveditor_core_api::extensions::modules::statusbar_item::StatusBarItem;
+use gveditor_core_api::extensions::permissions::ExtensionPermissions;
+use gveditor_core_api::extensions::subscriptions::MessageFilter;
 use gveditor_core_api::messaging::{ClientMessages, NotifyExtension, ServerMessages};
 use gveditor_core_api::tokio::sync::mpsc::{channel, Receiver, Sender};
 use gveditor_core_api::{tokio, ManifestExtension, ManifestInfo, Mutex, Serialize, State};
@@ -132,13 +134,11 @@ impl Extension for GitExtension {
                 loop {
                     if let Some(message) = receiver.recv().await {
                         match message {
-                            ClientMessages::ListDir(_, fs_name, path, _) => {
-                                // Only react when using the local file system
-                                if fs_name == "local" {
-                                    let branch = Self::get_repo_branch(path);
-                                    if let Ok(Some(branch)) = branch {
-                                        status_bar_item.set_label(&branch).await;
-                                    }
+                            // Only react when using the local file system
+                            ClientMessages::ListDir(_, fs_name, path, _) if fs_name == "local" => {
+                                let branch = Self::get_repo_branch(path);
+                                if let Ok(Some(branch)) = branch {
+                                    status_bar_item.set_label(&branch).await;
                                 }
                             }
                             ClientMessages::NotifyExtension(
@@ -170,6 +170,10 @@ impl Extension for GitExtension {
 
     fn unload(&mut self) {}
 
+    fn get_message_filter(&self) -> MessageFilter {
+        MessageFilter::messages(&["listDir", "notifyExtension"]).with_filesystems(&["local"])
+    }
+
     fn notify(&mut self, message: ClientMessages) {
         let tx = self.tx.clone();
         tokio::spawn(async move {
@@ -202,5 +206,7 @@ pub fn get_info() -> ManifestInfo {
             repository: "https://github.com/Graviton-Code-Editor/Graviton-App".to_string(),
             main: None,
         },
+        permissions: ExtensionPermissions::all(),
+        subscriptions: MessageFilter::all(),
     }
 }
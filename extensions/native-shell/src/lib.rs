@@ -3,6 +3,8 @@ use std::sync::Arc;
 use gveditor_core_api::extensions::base::{Extension, ExtensionInfo};
 use gveditor_core_api::extensions::client::ExtensionClient;
 use gveditor_core_api::extensions::manager::ExtensionsManager;
+use gveditor_core_api::extensions::permissions::ExtensionPermissions;
+use gveditor_core_api::extensions::subscriptions::MessageFilter;
 use gveditor_core_api::messaging::ClientMessages;
 use gveditor_core_api::terminal_shells::TerminalShellBuilderInfo;
 use gveditor_core_api::{tokio, ManifestExtension, ManifestInfo, Mutex, State};
@@ -29,7 +31,7 @@ impl Extension for NativeShellExtension {
         let state_id = self.state_id;
         let client = self.client.clone();
         tokio::spawn(async move {
-            #[cfg(any(target_os = "windows"))]
+            #[cfg(target_os = "windows")]
             state.lock().await.terminal_shell_builders.insert(
                 "Powershell".to_string(),
                 Arc::new(Mutex::new(Box::new(NativeShellBuilder {
@@ -43,7 +45,7 @@ impl Extension for NativeShellExtension {
                 }))),
             );
 
-            #[cfg(any(target_os = "windows"))]
+            #[cfg(target_os = "windows")]
             state.lock().await.terminal_shell_builders.insert(
                 "cmd".to_string(),
                 Arc::new(Mutex::new(Box::new(NativeShellBuilder {
@@ -57,7 +59,22 @@ impl Extension for NativeShellExtension {
                 }))),
             );
 
-            // TODO(marc2332): Add bash shell for Linux and MacOS
+            #[cfg(not(windows))]
+            {
+                let command = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+                state.lock().await.terminal_shell_builders.insert(
+                    "shell".to_string(),
+                    Arc::new(Mutex::new(Box::new(NativeShellBuilder {
+                        client: client.clone(),
+                        state_id,
+                        command,
+                        info: TerminalShellBuilderInfo {
+                            name: "Shell".to_string(),
+                            id: "shell".to_string(),
+                        },
+                    }))),
+                );
+            }
         });
     }
 
@@ -83,5 +100,7 @@ pub fn get_info() -> ManifestInfo {
             repository: "https://github.com/Graviton-Code-Editor/Graviton-App".to_string(),
             main: None,
         },
+        permissions: ExtensionPermissions::all(),
+        subscriptions: MessageFilter::all(),
     }
 }
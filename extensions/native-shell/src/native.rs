@@ -8,6 +8,7 @@ use gveditor_core_api::terminal_shells::{
 };
 use gveditor_core_api::tokio;
 use gveditor_core_api::tokio::sync::mpsc::channel;
+use tracing::error;
 
 pub struct NativeShellBuilder {
     pub state_id: u8,
@@ -21,20 +22,22 @@ impl TerminalShellBuilder for NativeShellBuilder {
         self.info.clone()
     }
 
-    fn build(&self, terminal_shell_id: &str) -> Box<dyn TerminalShell + Send + Sync> {
+    fn build(
+        &self,
+        terminal_shell_id: &str,
+    ) -> Result<Box<dyn TerminalShell + Send + Sync>, String> {
         let client = self.client.clone();
         let terminal_shell_id = terminal_shell_id.to_owned();
         let state_id = self.state_id;
 
         let (tx, mut rx) = channel::<Vec<u8>>(1);
-        let pty = new_pty(&self.command, vec![], tx);
+        let pty = new_pty(&self.command, vec![], tx)?;
 
         let shell = Box::new(NativeShell { pty });
 
         tokio::spawn(async move {
-            loop {
-                let data = rx.recv().await.unwrap();
-                client
+            while let Some(data) = rx.recv().await {
+                let sent = client
                     .send(ClientMessages::ServerMessage(
                         ServerMessages::TerminalShellUpdated {
                             data,
@@ -42,12 +45,27 @@ impl TerminalShellBuilder for NativeShellBuilder {
                             terminal_shell_id: terminal_shell_id.clone(),
                         },
                     ))
-                    .await
-                    .unwrap();
+                    .await;
+
+                // Nobody is listening anymore
+                if sent.is_err() {
+                    return;
+                }
             }
+
+            // The shell process exited
+            client
+                .send(ClientMessages::ServerMessage(
+                    ServerMessages::TerminalShellClosed {
+                        state_id,
+                        terminal_shell_id,
+                    },
+                ))
+                .await
+                .ok();
         });
 
-        shell
+        Ok(shell)
     }
 }
 
@@ -58,10 +76,14 @@ pub struct NativeShell {
 #[async_trait]
 impl TerminalShell for NativeShell {
     async fn write(&self, data: String) {
-        self.pty.write(&data).await.unwrap();
+        if let Err(err) = self.pty.write(&data).await {
+            error!("Could not write to the native shell, error: {}", err);
+        }
     }
 
     async fn resize(&self, cols: i32, rows: i32) {
-        self.pty.resize((cols, rows)).await.unwrap();
+        if let Err(err) = self.pty.resize((cols, rows)).await {
+            error!("Could not resize the native shell, error: {}", err);
+        }
     }
 }
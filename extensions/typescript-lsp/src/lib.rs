@@ -3,6 +3,8 @@ use gveditor_core_api::extensions::client::ExtensionClient;
 use gveditor_core_api::extensions::manager::ExtensionsManager;
 use gveditor_core_api::extensions::modules::command::Command;
 use gveditor_core_api::extensions::modules::statusbar_item::StatusBarItem;
+use gveditor_core_api::extensions::permissions::ExtensionPermissions;
+use gveditor_core_api::extensions::subscriptions::MessageFilter;
 use gveditor_core_api::messaging::ClientMessages;
 use gveditor_core_api::{tokio, ManifestExtension, ManifestInfo, Mutex, State};
 use lsp::JSTSLanguageServerBuilder;
@@ -128,5 +130,7 @@ pub fn get_info() -> ManifestInfo {
             repository: "https://github.com/Graviton-Code-Editor/Graviton-App".to_string(),
             main: None,
         },
+        permissions: ExtensionPermissions::all(),
+        subscriptions: MessageFilter::all(),
     }
 }
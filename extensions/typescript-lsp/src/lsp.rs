@@ -47,13 +47,15 @@ impl LanguageServerBuilder for JSTSLanguageServerBuilder {
         let state_id = self.state_id;
         let mut status_bar_item = self.status_bar_item.clone();
 
-        let mut proc = Command::new(NPX_BINARY)
-            .arg("typescript-language-server")
-            .arg("--stdio")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
+        let mut proc = ls_client
+            .spawn_process(
+                Command::new(NPX_BINARY)
+                    .arg("typescript-language-server")
+                    .arg("--stdio")
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped()),
+            )
             .unwrap();
 
         let stdin = proc.stdin.take().unwrap();
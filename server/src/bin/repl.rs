@@ -0,0 +1,29 @@
+use gveditor_core::repl::Repl;
+use gveditor_core_api::tokio;
+
+fn get_arg(name: &str) -> Option<String> {
+    let prefix = format!("--{}=", name);
+    std::env::args().find_map(|arg| arg.strip_prefix(&prefix).map(str::to_owned))
+}
+
+/// e.g `graviton-repl --address=localhost:50020 --state=1 --token=test`
+#[tokio::main]
+async fn main() {
+    let address = get_arg("address").unwrap_or_else(|| "localhost:50020".to_owned());
+    let state_id = get_arg("state")
+        .and_then(|state_id| state_id.parse().ok())
+        .unwrap_or(1);
+    let token = get_arg("token").unwrap_or_else(|| "test".to_owned());
+
+    let mut repl = match Repl::connect(&address, state_id, &token).await {
+        Ok(repl) => repl,
+        Err(err) => {
+            eprintln!("Could not connect to ws://{}, error: {}", address, err);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(err) = repl.run().await {
+        eprintln!("Connection lost, error: {}", err);
+    }
+}
@@ -1,28 +1,35 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
-use gveditor_core::handlers::HTTPHandler;
+use gveditor_core::handlers::{HTTPHandler, WebSocketHandler};
 use gveditor_core::{Configuration, Server};
 use gveditor_core_api::extensions::manager::ExtensionsManager;
+use gveditor_core_api::logging::{LogLevel, Logger};
 use gveditor_core_api::messaging::ClientMessages;
-use gveditor_core_api::states::{MemoryPersistor, StatesList, TokenFlags};
+use gveditor_core_api::states::{LaunchConfig, StatesList};
 use gveditor_core_api::tokio;
 use gveditor_core_api::tokio::sync::mpsc::channel;
-use gveditor_core_api::{Mutex, State};
-use tracing_subscriber::prelude::__tracing_subscriber_SubscriberExt;
-use tracing_subscriber::{fmt, EnvFilter, Registry};
+use gveditor_core_api::Mutex;
+use uuid::Uuid;
 
 fn setup_logger() {
-    let filter = EnvFilter::default()
-        .add_directive("server=info".parse().unwrap())
-        .add_directive("graviton=info".parse().unwrap())
-        .add_directive("gveditor_core_api=info".parse().unwrap())
-        .add_directive("gveditor_core=info".parse().unwrap())
-        .add_directive("typescript_lsp_graviton=info".parse().unwrap());
-
-    let subscriber = Registry::default().with(filter).with(fmt::Layer::default());
-
-    tracing::subscriber::set_global_default(subscriber).expect("Unable to set global subscriber");
+    let mut logger = Logger::builder()
+        .level("server", LogLevel::Info)
+        .level("graviton", LogLevel::Info)
+        .level("gveditor_core_api", LogLevel::Info)
+        .level("gveditor_core", LogLevel::Info)
+        .level("typescript_lsp_graviton", LogLevel::Info);
+
+    // Keep up to 5 files of 10MB, e.g `--log-file=/var/log/graviton.log`
+    let log_file =
+        std::env::args().find_map(|arg| arg.strip_prefix("--log-file=").map(PathBuf::from));
+    if let Some(log_file) = log_file {
+        logger = logger.file(log_file, 10 * 1024 * 1024, 5);
+    }
+
+    logger.init().expect("Unable to set global subscriber");
 }
 
 #[tokio::main]
@@ -36,25 +43,76 @@ async fn main() {
         .await
         .to_owned();
 
-    let states = {
-        let sample_state = State::new(1, extensions_manager, Box::new(MemoryPersistor::new()));
+    // e.g `--tokens=secret --persistor=file:/var/lib/graviton/state.json`
+    let mut launch_config = LaunchConfig::from_environment().unwrap_or_else(|err| {
+        eprintln!("Invalid launch configuration: {:?}", err);
+        std::process::exit(1);
+    });
+
+    // In headless mode remote clients talk JSON RPC through a single WebSocket
+    let headless = std::env::args().any(|arg| arg == "--headless");
+
+    // Remote clients get full access with the token, so never fall back to a well-known one
+    if launch_config.tokens.is_empty() {
+        let token = if headless {
+            Uuid::new_v4().to_string()
+        } else {
+            "test".to_string()
+        };
+        launch_config.tokens.push(token);
+    }
 
-        let states = StatesList::new()
-            .with_tokens(&[TokenFlags::All("test".to_string())])
-            .with_state(sample_state);
+    let states = {
+        let state = launch_config.build_state(extensions_manager).await;
+        let mut states = StatesList::new().with_state(state);
+
+        // e.g `provider = "github"` under `[auth]` in the configuration file
+        if let Some(auth) = &launch_config.auth {
+            let authenticator = auth.build().unwrap_or_else(|err| {
+                eprintln!("Invalid authentication configuration: {:?}", err);
+                std::process::exit(1);
+            });
+            states = states.with_authenticator(authenticator);
+        }
 
         Arc::new(Mutex::new(states))
     };
 
-    let http_handler = HTTPHandler::builder().build().wrap();
+    // Operators can scrape the metrics on `/metrics` of the HTTP server
+    let metrics = std::env::args().any(|arg| arg == "--metrics");
+
+    let handler = if headless {
+        // Only reachable from this machine unless a host is given, e.g `--host=0.0.0.0`
+        let host = std::env::args()
+            .find_map(|arg| arg.strip_prefix("--host=").map(str::to_owned))
+            .unwrap_or_else(|| "127.0.0.1".to_string());
+        WebSocketHandler::builder().host(&host).build().wrap()
+    } else {
+        HTTPHandler::builder().metrics(metrics).build().wrap()
+    };
+
+    let mut config = Configuration::new(handler, core_tx, core_rx).with_autosave_on_signals(true);
 
-    let config = Configuration::new(http_handler, core_tx, core_rx);
+    // Remote instances might host workspaces nobody is using
+    if headless {
+        config = config.with_hibernate_after(Duration::from_secs(30 * 60));
+    }
 
     let mut server = Server::new(config, states);
 
     server.run().await;
 
-    println!("Open http://localhost:8080/?state=0&token=test");
+    if headless {
+        println!(
+            "Connect to ws://<host>:50020/?state_id={}&token={}",
+            launch_config.state_id, launch_config.tokens[0]
+        );
+    } else {
+        println!(
+            "Open http://localhost:8080/?state={}&token={}",
+            launch_config.state_id, launch_config.tokens[0]
+        );
+    }
 
     thread::park();
 }